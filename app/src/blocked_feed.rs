@@ -0,0 +1,113 @@
+//! Live feed of blocked DNS queries, coalesced per site
+//!
+//! Tails the daemon's query log for blocked queries and keeps a sliding
+//! one-minute window of attempts per site, so the UI can show e.g.
+//! "instagram.com - 14 attempts in the last minute".
+
+use crate::ipc_client::IpcClient;
+use blockandfocus_shared::{site_key, Capability, QueryAction, QueryLogFilter};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex;
+
+/// Length of the sliding window attempts are counted over, in seconds
+const WINDOW_SECONDS: i64 = 60;
+
+/// How often the feed is re-emitted so old attempts age out in the UI
+const REFRESH_SECONDS: u64 = 5;
+
+/// Seconds to wait before reconnecting to the daemon
+const RECONNECT_SECONDS: u64 = 5;
+
+/// Seconds to wait before checking again whether a daemon without query log
+/// tailing has been upgraded
+const UNSUPPORTED_RETRY_SECONDS: u64 = 300;
+
+/// Blocked attempts for one site within the window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedAttempts {
+    pub domain: String,
+    pub attempts: usize,
+    pub last_attempt: i64,
+}
+
+/// Recent blocked attempts per site
+#[derive(Default)]
+pub struct BlockedFeed {
+    attempts: HashMap<String, VecDeque<i64>>,
+}
+
+impl BlockedFeed {
+    /// Record a blocked query
+    pub fn record(&mut self, domain: &str, timestamp: i64) {
+        self.attempts
+            .entry(site_key(domain))
+            .or_default()
+            .push_back(timestamp);
+    }
+
+    /// Attempts within the window ending at `now`, most attempts first
+    pub fn snapshot(&mut self, now: i64) -> Vec<BlockedAttempts> {
+        let cutoff = now - WINDOW_SECONDS;
+        self.attempts.retain(|_, times| {
+            while times.front().is_some_and(|t| *t <= cutoff) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let mut feed: Vec<BlockedAttempts> = self
+            .attempts
+            .iter()
+            .map(|(domain, times)| BlockedAttempts {
+                domain: domain.clone(),
+                attempts: times.len(),
+                last_attempt: times.back().copied().unwrap_or(now),
+            })
+            .collect();
+        feed.sort_by(|a, b| b.attempts.cmp(&a.attempts).then_with(|| a.domain.cmp(&b.domain)));
+        feed
+    }
+}
+
+/// Tail blocked queries and emit the coalesced feed as `blocked-attempts`
+pub async fn run(app: AppHandle, feed: Arc<Mutex<BlockedFeed>>) {
+    let client = IpcClient::new();
+    let filter = QueryLogFilter {
+        action: Some(QueryAction::Blocked),
+        ..QueryLogFilter::default()
+    };
+
+    loop {
+        if let Ok(false) = client.supports(Capability::QueryLogTail).await {
+            tokio::time::sleep(Duration::from_secs(UNSUPPORTED_RETRY_SECONDS)).await;
+            continue;
+        }
+
+        if let Ok(mut stream) = client.tail_query_log(filter.clone(), 0).await {
+            let mut refresh = tokio::time::interval(Duration::from_secs(REFRESH_SECONDS));
+
+            loop {
+                tokio::select! {
+                    entry = stream.next_query_log_entry() => {
+                        let Ok(entry) = entry else {
+                            break;
+                        };
+                        feed.lock().await.record(&entry.domain, entry.timestamp);
+                    }
+                    _ = refresh.tick() => {}
+                }
+
+                let snapshot = feed.lock().await.snapshot(chrono::Utc::now().timestamp());
+                if let Err(e) = app.emit("blocked-attempts", &snapshot) {
+                    tracing::warn!("Failed to emit blocked attempts: {}", e);
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(RECONNECT_SECONDS)).await;
+    }
+}
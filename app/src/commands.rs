@@ -1,12 +1,21 @@
 //! Tauri commands for UI-daemon communication
 
-use blockandfocus_shared::{Response, Schedule};
-use crate::{AppState, StatusInfo, QuizInfo, QuizResult};
-use tauri::State;
+use blockandfocus_shared::{
+    ActivityHeatmap, BlockingWindow, BlocklistDiff, CacheStats, ClientProfile, DomainExplanation,
+    DomainName, Duration, Error, ImportPreview, ImportSource, Locale, PendingScheduleChange,
+    PurgeScope, QueryLogEntry, QueryLogFilter, QuizAnswer, Response, Schedule, ScheduleTemplate,
+    SelfCheckReport, SubscriptionStatus, Vacation, UpdateStatus, UsageStats, WeeklyBypassSummary,
+    PROTOCOL_VERSION,
+};
+use crate::blocked_feed::BlockedAttempts;
+use crate::hotkey::{self, FocusHotkey};
+use crate::installer;
+use crate::{AppState, StatusInfo, QuizInfo, QuizResult, VersionDetails};
+use tauri::{AppHandle, State};
 
 /// Get the current daemon status
 #[tauri::command]
-pub async fn get_status(state: State<'_, AppState>) -> Result<StatusInfo, String> {
+pub async fn get_status(state: State<'_, AppState>) -> Result<StatusInfo, Error> {
     let client = state.client.lock().await;
 
     if !client.is_daemon_running() {
@@ -18,6 +27,19 @@ pub async fn get_status(state: State<'_, AppState>) -> Result<StatusInfo, String
             bypass_remaining_seconds: None,
             blocked_count: 0,
             daemon_connected: false,
+            bypass_budget_remaining_minutes: None,
+            bypass_countdown_seconds: None,
+            allowances: Vec::new(),
+            dry_run: false,
+            would_block_count: 0,
+            encrypted_dns_count: 0,
+            last_encrypted_dns: None,
+            update_available: None,
+            strictness: None,
+            settings_lock_remaining_minutes: None,
+            vacation_until: None,
+            wind_down_groups: Vec::new(),
+            locale: Locale::default(),
         });
     }
 
@@ -25,6 +47,10 @@ pub async fn get_status(state: State<'_, AppState>) -> Result<StatusInfo, String
         Ok(Response::Status(status)) => {
             let now = chrono::Utc::now().timestamp();
             let bypass_remaining = status.bypass_until.map(|until| (until - now).max(0));
+            let bypass_countdown = status
+                .bypass_starts_at
+                .map(|starts_at| starts_at - now)
+                .filter(|seconds| *seconds > 0);
 
             Ok(StatusInfo {
                 blocking_active: status.blocking_active,
@@ -34,80 +60,157 @@ pub async fn get_status(state: State<'_, AppState>) -> Result<StatusInfo, String
                 bypass_remaining_seconds: bypass_remaining,
                 blocked_count: status.queries_blocked,
                 daemon_connected: true,
+                bypass_budget_remaining_minutes: status.bypass_budget_remaining_minutes,
+                bypass_countdown_seconds: bypass_countdown,
+                allowances: status.allowances,
+                dry_run: status.dry_run,
+                would_block_count: status.queries_would_block,
+                encrypted_dns_count: status.encrypted_dns_connections,
+                last_encrypted_dns: status.last_encrypted_dns,
+                update_available: status.update_available,
+                strictness: status.active_strictness,
+                settings_lock_remaining_minutes: status.settings_lock_remaining_minutes,
+                vacation_until: status.vacation_until,
+                wind_down_groups: status.wind_down_groups,
+                locale: status.locale,
             })
         }
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to get status: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get status: {}", e))),
     }
 }
 
 /// Get the current blocklist
 #[tauri::command]
-pub async fn get_blocklist(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+pub async fn get_blocklist(state: State<'_, AppState>) -> Result<Vec<DomainName>, Error> {
     let client = state.client.lock().await;
 
     match client.get_blocklist().await {
-        Ok(Response::Blocklist { domains }) => Ok(domains),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to get blocklist: {}", e)),
+        Ok(Response::Blocklist { domains, .. }) => Ok(domains),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get blocklist: {}", e))),
     }
 }
 
 /// Add a domain to the blocklist
 #[tauri::command]
-pub async fn add_domain(state: State<'_, AppState>, domain: String) -> Result<bool, String> {
+pub async fn add_domain(state: State<'_, AppState>, domain: String) -> Result<bool, Error> {
+    let domain = DomainName::parse(&domain)?;
     let client = state.client.lock().await;
 
     match client.add_domain(domain).await {
         Ok(Response::Success) => Ok(true),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to add domain: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to add domain: {}", e))),
     }
 }
 
 /// Remove a domain from the blocklist
 #[tauri::command]
-pub async fn remove_domain(state: State<'_, AppState>, domain: String) -> Result<bool, String> {
+pub async fn remove_domain(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<Option<QuizInfo>, Error> {
+    let domain = DomainName::parse(&domain)?;
     let client = state.client.lock().await;
 
     match client.remove_domain(domain).await {
+        Ok(Response::Success) => Ok(None),
+        // Blocking is active and removals are quiz-gated
+        Ok(Response::QuizChallenge(quiz)) => Ok(Some(quiz.into())),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to remove domain: {}", e))),
+    }
+}
+
+/// Get the domains that are never blocked
+#[tauri::command]
+pub async fn get_essential_domains(
+    state: State<'_, AppState>,
+) -> Result<Vec<DomainName>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_essential_domains().await {
+        Ok(Response::EssentialDomains { domains }) => Ok(domains),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get essential domains: {}", e))),
+    }
+}
+
+/// Never block a domain (refused during blocking hours)
+#[tauri::command]
+pub async fn add_essential_domain(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<bool, Error> {
+    let domain = DomainName::parse(&domain)?;
+    let client = state.client.lock().await;
+
+    match client.add_essential_domain(domain).await {
         Ok(Response::Success) => Ok(true),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to remove domain: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to add essential domain: {}", e))),
+    }
+}
+
+/// Remove a domain from the essential-services list
+#[tauri::command]
+pub async fn remove_essential_domain(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<bool, Error> {
+    let domain = DomainName::parse(&domain)?;
+    let client = state.client.lock().await;
+
+    match client.remove_essential_domain(domain).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to remove essential domain: {}", e))),
+    }
+}
+
+/// Enable or disable a blocklist group, returning a quiz if disabling needs one
+#[tauri::command]
+pub async fn set_group_enabled(
+    state: State<'_, AppState>,
+    name: String,
+    enabled: bool,
+) -> Result<Option<QuizInfo>, Error> {
+    let client = state.client.lock().await;
+
+    match client.set_group_enabled(name, enabled).await {
+        Ok(Response::Success) => Ok(None),
+        Ok(Response::QuizChallenge(quiz)) => Ok(Some(quiz.into())),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to update group: {}", e))),
     }
 }
 
 /// Get the current schedule
 #[tauri::command]
-pub async fn get_schedule(state: State<'_, AppState>) -> Result<Schedule, String> {
+pub async fn get_schedule(state: State<'_, AppState>) -> Result<Schedule, Error> {
     let client = state.client.lock().await;
 
     match client.get_schedule().await {
         Ok(Response::Schedule(schedule)) => Ok(schedule),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to get schedule: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get schedule: {}", e))),
     }
 }
 
-/// Set schedule enabled status
+/// Set schedule enabled status; false if the change waits for midnight
 #[tauri::command]
 pub async fn set_schedule_enabled(
     state: State<'_, AppState>,
     enabled: bool,
-) -> Result<bool, String> {
+) -> Result<bool, Error> {
     let client = state.client.lock().await;
 
     // First get the current schedule
     let schedule = match client.get_schedule().await {
         Ok(Response::Schedule(s)) => s,
-        Ok(Response::Error { message, .. }) => return Err(message),
-        Ok(_) => return Err("Unexpected response from daemon".to_string()),
-        Err(e) => return Err(format!("Failed to get schedule: {}", e)),
+        Ok(response) => return Err(response.into()),
+        Err(e) => return Err(Error::unavailable(format!("Failed to get schedule: {}", e))),
     };
 
     // Update the enabled flag
@@ -118,9 +221,173 @@ pub async fn set_schedule_enabled(
 
     match client.update_schedule(updated_schedule).await {
         Ok(Response::Success) => Ok(true),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to update schedule: {}", e)),
+        // Reduces blocking: takes effect at midnight
+        Ok(Response::ScheduleChangePending { .. }) => Ok(false),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to update schedule: {}", e))),
+    }
+}
+
+/// Get schedule edits waiting to take effect
+#[tauri::command]
+pub async fn get_pending_changes(
+    state: State<'_, AppState>,
+) -> Result<Option<PendingScheduleChange>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_pending_changes().await {
+        Ok(Response::PendingChanges { schedule }) => Ok(schedule),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get pending changes: {}", e))),
+    }
+}
+
+/// Suspend the schedule from `start` to `end` (inclusive)
+#[tauri::command]
+pub async fn schedule_vacation(
+    state: State<'_, AppState>,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.schedule_vacation(start, end).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to schedule vacation: {}", e))),
+    }
+}
+
+/// Cancel a vacation that has not started yet
+#[tauri::command]
+pub async fn cancel_vacation(state: State<'_, AppState>, id: String) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.cancel_vacation(id).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to cancel vacation: {}", e))),
+    }
+}
+
+/// Get upcoming and current vacations
+#[tauri::command]
+pub async fn get_vacations(state: State<'_, AppState>) -> Result<Vec<Vacation>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_vacations().await {
+        Ok(Response::Vacations { vacations }) => Ok(vacations),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get vacations: {}", e))),
+    }
+}
+
+/// Get the periods the schedule will block in over the next 7 days
+#[tauri::command]
+pub async fn get_schedule_timeline(
+    state: State<'_, AppState>,
+) -> Result<Vec<BlockingWindow>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_schedule_timeline().await {
+        Ok(Response::ScheduleTimeline { windows }) => Ok(windows),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get schedule timeline: {}", e))),
+    }
+}
+
+/// Generate a schedule from a template for the user to edit before saving it
+#[tauri::command]
+pub async fn generate_schedule(
+    state: State<'_, AppState>,
+    template: ScheduleTemplate,
+) -> Result<Schedule, Error> {
+    let client = state.client.lock().await;
+
+    match client.generate_schedule(template).await {
+        Ok(Response::Schedule(schedule)) => Ok(schedule),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to generate schedule: {}", e))),
+    }
+}
+
+/// Ask to delete personal data, returning the confirmation to send back
+#[tauri::command]
+pub async fn request_purge(state: State<'_, AppState>, scope: PurgeScope) -> Result<String, Error> {
+    let client = state.client.lock().await;
+
+    match client.purge_data(scope, None).await {
+        Ok(Response::PurgeConfirmationRequired { confirmation, .. }) => Ok(confirmation),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to request purge: {}", e))),
+    }
+}
+
+/// Delete personal data with a confirmation from request_purge
+#[tauri::command]
+pub async fn confirm_purge(
+    state: State<'_, AppState>,
+    scope: PurgeScope,
+    confirmation: String,
+) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.purge_data(scope, Some(confirmation)).await {
+        Ok(Response::DataPurged { .. }) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to purge data: {}", e))),
+    }
+}
+
+/// Start blocking right now for the given number of minutes
+#[tauri::command]
+pub async fn focus_now(state: State<'_, AppState>, minutes: u32) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.focus_now(minutes).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to start focus: {}", e))),
+    }
+}
+
+/// Get the global shortcut that starts a focus session
+#[tauri::command]
+pub async fn get_focus_hotkey(state: State<'_, AppState>) -> Result<FocusHotkey, Error> {
+    Ok(state.focus_hotkey.lock().await.clone())
+}
+
+/// Change the focus session shortcut, registering it right away
+#[tauri::command]
+pub async fn set_focus_hotkey(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    hotkey: FocusHotkey,
+) -> Result<bool, Error> {
+    if hotkey.minutes == 0 {
+        return Err(Error::invalid("A focus session lasts at least one minute"));
+    }
+
+    let mut current = state.focus_hotkey.lock().await;
+    if let Err(e) = hotkey::register(&app, &hotkey) {
+        // Keep the previous shortcut working
+        let _ = hotkey::register(&app, &current);
+        return Err(e);
+    }
+    hotkey::save(&app, &hotkey)?;
+    *current = hotkey;
+    Ok(true)
+}
+
+/// Request a quiz to snooze the next schedule rule
+#[tauri::command]
+pub async fn snooze_schedule(state: State<'_, AppState>, minutes: u32) -> Result<QuizInfo, Error> {
+    let client = state.client.lock().await;
+
+    match client.snooze_schedule(minutes).await {
+        Ok(Response::QuizChallenge(quiz)) => Ok(quiz.into()),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to snooze schedule: {}", e))),
     }
 }
 
@@ -129,18 +396,32 @@ pub async fn set_schedule_enabled(
 pub async fn request_bypass(
     state: State<'_, AppState>,
     duration_minutes: u32,
-) -> Result<QuizInfo, String> {
+    reason: Option<String>,
+) -> Result<Option<QuizInfo>, Error> {
     let client = state.client.lock().await;
 
-    match client.request_bypass(duration_minutes).await {
-        Ok(Response::QuizChallenge(quiz)) => Ok(QuizInfo {
-            challenge_id: quiz.challenge_id,
-            questions: quiz.questions,
-            expires_at: quiz.expires_at,
-        }),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to request bypass: {}", e)),
+    let duration = Duration::from_mins(duration_minutes.into());
+    match client.request_bypass(duration, reason).await {
+        Ok(Response::QuizChallenge(quiz)) => Ok(Some(quiz.into())),
+        // Granted without a quiz under a relaxed schedule rule
+        Ok(Response::Success) => Ok(None),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to request bypass: {}", e))),
+    }
+}
+
+/// Finish the reveal phase of a memory quiz and start answering
+#[tauri::command]
+pub async fn begin_quiz_answer(
+    state: State<'_, AppState>,
+    challenge_id: String,
+) -> Result<QuizInfo, Error> {
+    let client = state.client.lock().await;
+
+    match client.begin_quiz_answer(challenge_id).await {
+        Ok(Response::QuizChallenge(quiz)) => Ok(quiz.into()),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to start answering quiz: {}", e))),
     }
 }
 
@@ -149,33 +430,358 @@ pub async fn request_bypass(
 pub async fn submit_quiz_answers(
     state: State<'_, AppState>,
     challenge_id: String,
-    answers: Vec<i32>,
-) -> Result<QuizResult, String> {
+    answers: Vec<QuizAnswer>,
+) -> Result<QuizResult, Error> {
     let client = state.client.lock().await;
 
     match client.submit_quiz_answers(challenge_id, answers).await {
         Ok(Response::Success) => Ok(QuizResult {
             success: true,
-            message: "Bypass granted!".to_string(),
+            message: "Quiz passed!".to_string(),
+            next_stage: None,
+        }),
+        Ok(Response::QuizChallenge(next)) => Ok(QuizResult {
+            success: true,
+            message: format!("Stage {} of {}", next.stage, next.total_stages),
+            next_stage: Some(next.into()),
         }),
         Ok(Response::Error { message, .. }) => Ok(QuizResult {
             success: false,
             message,
+            next_stage: None,
         }),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to submit answers: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to submit answers: {}", e))),
     }
 }
 
 /// Cancel an active bypass
 #[tauri::command]
-pub async fn cancel_bypass(state: State<'_, AppState>) -> Result<bool, String> {
+pub async fn cancel_bypass(state: State<'_, AppState>) -> Result<bool, Error> {
     let client = state.client.lock().await;
 
     match client.cancel_bypass().await {
         Ok(Response::Success) => Ok(true),
-        Ok(Response::Error { message, .. }) => Err(message),
-        Ok(_) => Err("Unexpected response from daemon".to_string()),
-        Err(e) => Err(format!("Failed to cancel bypass: {}", e)),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to cancel bypass: {}", e))),
+    }
+}
+
+/// Stop the daemon, returning a quiz if it insists on one first
+#[tauri::command]
+pub async fn stop_daemon(state: State<'_, AppState>) -> Result<Option<QuizInfo>, Error> {
+    let client = state.client.lock().await;
+
+    match client.shutdown().await {
+        Ok(Response::QuizChallenge(quiz)) => Ok(Some(quiz.into())),
+        Ok(Response::Success) => Ok(None),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to stop daemon: {}", e))),
+    }
+}
+
+/// Lock settings against weakening for a number of hours
+#[tauri::command]
+pub async fn lock_settings(state: State<'_, AppState>, hours: u32) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.lock_settings(hours).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to lock settings: {}", e))),
+    }
+}
+
+/// Explain whether a domain is blocked right now, and why
+#[tauri::command]
+pub async fn explain_domain(
+    state: State<'_, AppState>,
+    domain: String,
+) -> Result<DomainExplanation, Error> {
+    let client = state.client.lock().await;
+
+    match client.explain_domain(domain).await {
+        Ok(Response::DomainExplanation(explanation)) => Ok(explanation),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to explain domain: {}", e))),
+    }
+}
+
+/// Check that the system routes DNS through the daemon
+#[tauri::command]
+pub async fn run_self_check(state: State<'_, AppState>) -> Result<SelfCheckReport, Error> {
+    let client = state.client.lock().await;
+
+    match client.run_self_check().await {
+        Ok(Response::SelfCheck(report)) => Ok(report),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to run self-check: {}", e))),
+    }
+}
+
+/// Send this week's report now, to check the delivery settings
+#[tauri::command]
+pub async fn send_weekly_report(state: State<'_, AppState>) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.send_weekly_report().await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to send weekly report: {}", e))),
+    }
+}
+
+/// Export the configuration and persisted state as a backup archive
+#[tauri::command]
+pub async fn create_backup(state: State<'_, AppState>) -> Result<String, Error> {
+    let client = state.client.lock().await;
+
+    match client.create_backup().await {
+        Ok(Response::Backup { archive }) => Ok(archive),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to create backup: {}", e))),
+    }
+}
+
+/// Replace the configuration and persisted state with a backup archive
+#[tauri::command]
+pub async fn restore_backup(state: State<'_, AppState>, archive: String) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.restore_backup(archive).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to restore backup: {}", e))),
+    }
+}
+
+/// Import another blocker's export, or only preview it with `dry_run`
+#[tauri::command]
+pub async fn import_external(
+    state: State<'_, AppState>,
+    source: ImportSource,
+    data: String,
+    dry_run: bool,
+) -> Result<ImportPreview, Error> {
+    let client = state.client.lock().await;
+
+    match client.import_external(source, data, dry_run).await {
+        Ok(Response::ImportPreview(preview)) => Ok(preview),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to import: {}", e))),
+    }
+}
+
+/// Get daemon and app versions, flagging an incompatible daemon
+#[tauri::command]
+pub async fn get_version(state: State<'_, AppState>) -> Result<VersionDetails, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_version().await {
+        Ok(Response::Version(daemon)) => Ok(VersionDetails {
+            protocol_mismatch: daemon.protocol_version != PROTOCOL_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            daemon,
+        }),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get version: {}", e))),
+    }
+}
+
+/// Check whether a newer daemon is available
+#[tauri::command]
+pub async fn check_for_update(state: State<'_, AppState>) -> Result<UpdateStatus, Error> {
+    let client = state.client.lock().await;
+
+    match client.check_for_update().await {
+        Ok(Response::UpdateStatus(status)) => Ok(status),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to check for update: {}", e))),
+    }
+}
+
+/// Install the latest daemon; returns the installed version
+#[tauri::command]
+pub async fn apply_update(state: State<'_, AppState>) -> Result<String, Error> {
+    let client = state.client.lock().await;
+
+    match client.apply_update().await {
+        Ok(Response::UpdateApplied { version }) => Ok(version),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to apply update: {}", e))),
+    }
+}
+
+/// Get query counters and today's estimated usage per site
+#[tauri::command]
+pub async fn get_stats(state: State<'_, AppState>) -> Result<UsageStats, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_stats().await {
+        Ok(Response::Stats(stats)) => Ok(stats),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get stats: {}", e))),
+    }
+}
+
+/// Get the most recent stored queries, oldest first
+#[tauri::command]
+pub async fn get_query_log(
+    state: State<'_, AppState>,
+    limit: usize,
+    filter: Option<QueryLogFilter>,
+) -> Result<Vec<QueryLogEntry>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_query_log(limit, filter.unwrap_or_default()).await {
+        Ok(Response::QueryLog { entries }) => Ok(entries),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get query log: {}", e))),
+    }
+}
+
+/// Get blocked attempts per site over the last minute
+///
+/// Updates are also pushed to the webview as `blocked-attempts` events.
+#[tauri::command]
+pub async fn get_blocked_attempts(
+    state: State<'_, AppState>,
+) -> Result<Vec<BlockedAttempts>, Error> {
+    let mut feed = state.blocked_feed.lock().await;
+    Ok(feed.snapshot(chrono::Utc::now().timestamp()))
+}
+
+/// Get DNS cache statistics
+#[tauri::command]
+pub async fn get_cache_stats(state: State<'_, AppState>) -> Result<CacheStats, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_cache_stats().await {
+        Ok(Response::CacheStats(stats)) => Ok(stats),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get cache stats: {}", e))),
+    }
+}
+
+/// Get subscribed blocklists with their last refresh result
+#[tauri::command]
+pub async fn get_subscriptions(
+    state: State<'_, AppState>,
+) -> Result<Vec<SubscriptionStatus>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_subscriptions().await {
+        Ok(Response::Subscriptions { lists }) => Ok(lists),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get subscriptions: {}", e))),
+    }
+}
+
+/// Get the policy profiles for other devices using the daemon
+#[tauri::command]
+pub async fn get_client_profiles(state: State<'_, AppState>) -> Result<Vec<ClientProfile>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_client_profiles().await {
+        Ok(Response::ClientProfiles { profiles }) => Ok(profiles),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get client profiles: {}", e))),
+    }
+}
+
+/// Add a client profile, or replace the one with the same name
+#[tauri::command]
+pub async fn set_client_profile(
+    state: State<'_, AppState>,
+    profile: ClientProfile,
+) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.set_client_profile(profile).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to save client profile: {}", e))),
     }
 }
+
+/// Remove a client profile
+#[tauri::command]
+pub async fn remove_client_profile(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<bool, Error> {
+    let client = state.client.lock().await;
+
+    match client.remove_client_profile(name).await {
+        Ok(Response::Success) => Ok(true),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to remove client profile: {}", e))),
+    }
+}
+
+/// Get recent subscription changes, optionally only those that added a domain
+#[tauri::command]
+pub async fn get_blocklist_diffs(
+    state: State<'_, AppState>,
+    domain: Option<String>,
+) -> Result<Vec<BlocklistDiff>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_blocklist_diffs(domain).await {
+        Ok(Response::BlocklistDiffs { diffs }) => Ok(diffs),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get blocklist changes: {}", e))),
+    }
+}
+
+/// Flush the DNS cache, optionally for a single domain
+#[tauri::command]
+pub async fn flush_cache(
+    state: State<'_, AppState>,
+    domain: Option<String>,
+) -> Result<usize, Error> {
+    let client = state.client.lock().await;
+
+    match client.flush_cache(domain).await {
+        Ok(Response::CacheFlushed { entries_removed }) => Ok(entries_removed),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to flush cache: {}", e))),
+    }
+}
+
+/// Get the weekly bypass breakdown (reasons, durations, overruns)
+#[tauri::command]
+pub async fn get_bypass_analytics(
+    state: State<'_, AppState>,
+    weeks: u32,
+) -> Result<Vec<WeeklyBypassSummary>, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_bypass_analytics(weeks).await {
+        Ok(Response::BypassAnalytics { weeks }) => Ok(weeks),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get bypass analytics: {}", e))),
+    }
+}
+
+/// Get blocked queries per day and hour for the dashboard heatmap
+#[tauri::command]
+pub async fn get_activity_heatmap(
+    state: State<'_, AppState>,
+    weeks: u32,
+) -> Result<ActivityHeatmap, Error> {
+    let client = state.client.lock().await;
+
+    match client.get_activity_heatmap(weeks).await {
+        Ok(Response::ActivityHeatmap(heatmap)) => Ok(heatmap),
+        Ok(response) => Err(response.into()),
+        Err(e) => Err(Error::unavailable(format!("Failed to get activity heatmap: {}", e))),
+    }
+}
+
+/// Install or upgrade the bundled daemon after an administrator prompt, reporting
+/// each step as a `daemon-install-progress` event; returns the installed version
+#[tauri::command]
+pub async fn install_daemon(app: AppHandle) -> Result<String, Error> {
+    installer::install(&app).await
+}
@@ -0,0 +1,122 @@
+//! Global hotkey to start a focus session
+//!
+//! A system-wide shortcut starts blocking for a preset number of minutes
+//! without opening the window, and a notification confirms it. The shortcut
+//! and duration are saved in the app's config directory.
+
+use crate::AppState;
+use blockandfocus_shared::{Capability, Error, Response};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
+
+/// File the hotkey settings are saved in
+const SETTINGS_FILE: &str = "focus-hotkey.json";
+
+/// Focus hotkey settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusHotkey {
+    /// Whether the shortcut is registered
+    pub enabled: bool,
+    /// Shortcut in accelerator form, e.g. "CmdOrCtrl+Shift+F"
+    pub shortcut: String,
+    /// Length of the focus session it starts, in minutes
+    pub minutes: u32,
+}
+
+impl Default for FocusHotkey {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shortcut: "CmdOrCtrl+Shift+F".to_string(),
+            minutes: 25,
+        }
+    }
+}
+
+/// Plugin starting a focus session whenever the registered shortcut is pressed
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, _shortcut, event| {
+            if event.state() == ShortcutState::Pressed {
+                tauri::async_runtime::spawn(start_focus(app.clone()));
+            }
+        })
+        .build()
+}
+
+/// Load the saved settings and register the shortcut
+pub async fn init(app: AppHandle) {
+    let hotkey = load(&app);
+    if let Err(e) = register(&app, &hotkey) {
+        tracing::warn!("Focus hotkey not registered: {}", e);
+    }
+    *app.state::<AppState>().focus_hotkey.lock().await = hotkey;
+}
+
+/// Register `hotkey` in place of the current shortcut
+pub fn register(app: &AppHandle, hotkey: &FocusHotkey) -> Result<(), Error> {
+    let shortcuts = app.global_shortcut();
+    shortcuts
+        .unregister_all()
+        .map_err(|e| Error::app(format!("Failed to unregister the shortcut: {}", e)))?;
+    if !hotkey.enabled {
+        return Ok(());
+    }
+    shortcuts
+        .register(hotkey.shortcut.as_str())
+        .map_err(|e| Error::invalid(format!("Failed to register {}: {}", hotkey.shortcut, e)))
+}
+
+/// Saved settings, or the defaults
+fn load(app: &AppHandle) -> FocusHotkey {
+    settings_path(app)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Save settings for the next launch
+pub fn save(app: &AppHandle, hotkey: &FocusHotkey) -> Result<(), Error> {
+    let path = settings_path(app).ok_or_else(|| Error::app("No config directory for the app"))?;
+    let failed = |e: std::io::Error| Error::app(format!("Failed to save settings: {}", e));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(failed)?;
+    }
+    let contents = serde_json::to_string_pretty(hotkey).map_err(|e| Error::app(e.to_string()))?;
+    std::fs::write(path, contents).map_err(failed)
+}
+
+fn settings_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_config_dir().ok().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+/// Start a focus session and confirm it with a notification
+async fn start_focus(app: AppHandle) {
+    let state = app.state::<AppState>();
+    let minutes = state.focus_hotkey.lock().await.minutes;
+    let client = state.client.lock().await;
+
+    let failed = "Couldn't start a focus session";
+    let (title, body) = match client.supports(Capability::FocusSessions).await {
+        Ok(false) => (failed, "Update the daemon to use focus sessions".to_string()),
+        _ => match client.focus_now(minutes).await {
+            Ok(Response::Success) => (
+                "Focus session started",
+                format!("Blocking for the next {} minutes", minutes),
+            ),
+            Ok(Response::Error { message, .. }) => (failed, message),
+            Ok(_) => (failed, "Unexpected response from daemon".to_string()),
+            Err(e) => (failed, format!("Failed to reach the daemon: {}", e)),
+        },
+    };
+    drop(client);
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
@@ -0,0 +1,217 @@
+//! Daemon installation from the app
+//!
+//! Installs or upgrades the daemon bundled with the app. The user authorizes it
+//! through the standard macOS administrator prompt, a root script copies the
+//! binary and launchd plist into place and bootstraps the service, and each
+//! step is reported to the webview as a `daemon-install-progress` event until
+//! the new daemon answers.
+
+use crate::ipc_client::IpcClient;
+use blockandfocus_shared::{Error, Response};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Event carrying install progress to the webview
+const PROGRESS_EVENT: &str = "daemon-install-progress";
+
+/// Script run as root to do the install
+const INSTALL_SCRIPT: &str = include_str!("../../installer/scripts/install-daemon.sh");
+
+/// Bundled resources installed by the script
+const DAEMON_RESOURCE: &str = "blockandfocus-daemon";
+const PLIST_RESOURCE: &str = "com.blockandfocus.daemon.plist";
+
+/// How often the script's progress is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long the new daemon gets to start answering
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// osascript error number for a cancelled administrator prompt
+const USER_CANCELED: &str = "(-128)";
+
+/// Step of a daemon install
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStep {
+    /// Finding the daemon in the app bundle
+    Locating,
+    /// Waiting for the user to authorize the install
+    Authorizing,
+    /// Stopping the daemon already installed
+    Stopping,
+    /// Copying the daemon binary
+    CopyingDaemon,
+    /// Writing the launchd plist
+    WritingPlist,
+    /// Starting the service with launchd
+    Bootstrapping,
+    /// Waiting for the new daemon to answer
+    Verifying,
+    /// Installed and running
+    Done,
+}
+
+/// Install progress sent to the webview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallProgress {
+    pub step: InstallStep,
+    /// Why the step failed, if it did
+    pub error: Option<String>,
+}
+
+/// Install or upgrade the bundled daemon, returning its version
+pub async fn install(app: &AppHandle) -> Result<String, Error> {
+    match run(app).await {
+        Ok(version) => {
+            report(app, InstallStep::Done, None);
+            Ok(version)
+        }
+        Err((step, message)) => {
+            report(app, step, Some(message.clone()));
+            Err(Error::app(message))
+        }
+    }
+}
+
+async fn run(app: &AppHandle) -> Result<String, (InstallStep, String)> {
+    report(app, InstallStep::Locating, None);
+    if !cfg!(target_os = "macos") {
+        return Err((
+            InstallStep::Locating,
+            "Installing the daemon is only supported on macOS".to_string(),
+        ));
+    }
+    let daemon = resource(app, DAEMON_RESOURCE)?;
+    let plist = resource(app, PLIST_RESOURCE)?;
+
+    let workdir =
+        std::env::temp_dir().join(format!("blockandfocus-install-{}", std::process::id()));
+    let result = authorize_and_run(app, &workdir, &daemon, &plist).await;
+    let _ = std::fs::remove_dir_all(&workdir);
+    result?;
+
+    report(app, InstallStep::Verifying, None);
+    verify().await.map_err(|message| (InstallStep::Verifying, message))
+}
+
+/// Path of a bundled resource
+fn resource(app: &AppHandle, name: &str) -> Result<PathBuf, (InstallStep, String)> {
+    app.path()
+        .resolve(name, BaseDirectory::Resource)
+        .ok()
+        .filter(|path| path.exists())
+        .ok_or_else(|| {
+            (InstallStep::Locating, format!("{} is missing from the app bundle", name))
+        })
+}
+
+/// Run the install script as root, reporting its steps as they start
+async fn authorize_and_run(
+    app: &AppHandle,
+    workdir: &Path,
+    daemon: &Path,
+    plist: &Path,
+) -> Result<(), (InstallStep, String)> {
+    let prepare = |e: std::io::Error| {
+        (InstallStep::Locating, format!("Failed to prepare the install: {}", e))
+    };
+    let script = workdir.join("install-daemon.sh");
+    let progress = workdir.join("progress");
+    create_private_dir(workdir).map_err(prepare)?;
+    std::fs::write(&script, INSTALL_SCRIPT).map_err(prepare)?;
+    std::fs::write(&progress, "").map_err(prepare)?;
+
+    report(app, InstallStep::Authorizing, None);
+    let child = tokio::process::Command::new("/usr/bin/osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "do shell script \"/bin/bash \" & quoted form of item 1 of argv & \" \" \
+             & quoted form of item 2 of argv & \" \" & quoted form of item 3 of argv \
+             & \" \" & quoted form of item 4 of argv \
+             with prompt \"BlockAndFocus wants to install its blocking service.\" \
+             with administrator privileges",
+            "-e",
+            "end run",
+        ])
+        .arg(&script)
+        .arg(daemon)
+        .arg(plist)
+        .arg(&progress)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| (InstallStep::Authorizing, format!("Failed to run osascript: {}", e)))?;
+
+    let mut step = InstallStep::Authorizing;
+    let mut output = Box::pin(child.wait_with_output());
+    let output = loop {
+        tokio::select! {
+            output = &mut output => break output,
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+        step = report_new_steps(app, &progress, step);
+    };
+    step = report_new_steps(app, &progress, step);
+
+    let output = output.map_err(|e| (step, format!("Failed to run the install: {}", e)))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains(USER_CANCELED) {
+        return Err((InstallStep::Authorizing, "Authorization was cancelled".to_string()));
+    }
+    Err((step, stderr.trim().to_string()))
+}
+
+/// Report steps the script started since `current`, returning the latest one
+fn report_new_steps(app: &AppHandle, progress: &Path, current: InstallStep) -> InstallStep {
+    let steps: Vec<InstallStep> = std::fs::read_to_string(progress)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_value(line.trim().into()).ok())
+        .collect();
+    let new = steps.iter().position(|&step| step == current).map_or(0, |i| i + 1);
+    for &step in &steps[new..] {
+        report(app, step, None);
+    }
+    steps.last().copied().unwrap_or(current)
+}
+
+/// Wait for the new daemon to answer, returning its version
+async fn verify() -> Result<String, String> {
+    let deadline = Instant::now() + VERIFY_TIMEOUT;
+    loop {
+        let client = IpcClient::new();
+        if let Ok(Response::Version(info)) = client.get_version().await {
+            return Ok(info.version);
+        }
+        if Instant::now() >= deadline {
+            return Err("The daemon was installed but isn't answering".to_string());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// Create a directory only the current user can read
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    let _ = std::fs::remove_dir_all(path);
+    builder.create(path)
+}
+
+fn report(app: &AppHandle, step: InstallStep, error: Option<String>) {
+    let _ = app.emit(PROGRESS_EVENT, InstallProgress { step, error });
+}
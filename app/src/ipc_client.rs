@@ -1,29 +1,58 @@
 //! IPC Client for communicating with the BlockAndFocus daemon
 //!
-//! Uses Unix domain sockets to send commands and receive responses.
+//! Uses Unix domain sockets to send commands and receive responses. When the
+//! daemon's IPC key is readable, commands are signed for an authenticated session.
 
 use anyhow::{Context, Result};
-use blockandfocus_shared::{Command, Response, Schedule, IPC_SOCKET_PATH, IPC_SOCKET_PATH_DEV};
+use blockandfocus_shared::{
+    command_mac, Capability, ClientProfile, Command, DomainName, Duration, ErrorCode, Event,
+    ImportSource, PurgeScope, QueryLogEntry, QueryLogFilter, QuizAnswer, Response, Schedule,
+    ScheduleTemplate, IPC_KEY_PATH, IPC_KEY_PATH_DEV, IPC_SOCKET_PATH, IPC_SOCKET_PATH_DEV,
+};
+use chrono::NaiveDate;
 use std::path::Path;
+use std::sync::Mutex;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedReadHalf;
 use tokio::net::UnixStream;
 
 /// Client for communicating with the daemon over IPC
 pub struct IpcClient {
     socket_path: String,
+    /// Key for signing commands, if readable
+    key: Option<Vec<u8>>,
+    /// Current authenticated session
+    session: Mutex<Option<ClientSession>>,
+}
+
+/// An authenticated session started with the daemon
+struct ClientSession {
+    session_id: String,
+    nonce: String,
+    /// Last counter used
+    counter: u64,
 }
 
 impl IpcClient {
     /// Create a new IPC client
     pub fn new() -> Self {
         // Use development socket path if running in dev mode
-        let socket_path = if std::env::var("BLOCKANDFOCUS_DEV").is_ok() {
+        let is_dev = std::env::var("BLOCKANDFOCUS_DEV").is_ok();
+        let socket_path = if is_dev {
             IPC_SOCKET_PATH_DEV.to_string()
         } else {
             IPC_SOCKET_PATH.to_string()
         };
+        let key_path = if is_dev { IPC_KEY_PATH_DEV } else { IPC_KEY_PATH };
+        let key = std::fs::read_to_string(key_path)
+            .ok()
+            .and_then(|hex| decode_hex(hex.trim()));
 
-        Self { socket_path }
+        Self {
+            socket_path,
+            key,
+            session: Mutex::new(None),
+        }
     }
 
     /// Check if the daemon is running (socket exists)
@@ -31,8 +60,52 @@ impl IpcClient {
         Path::new(&self.socket_path).exists()
     }
 
-    /// Send a command to the daemon and receive a response
+    /// Send a command to the daemon and receive a response, signed if possible
     pub async fn send_command(&self, command: Command) -> Result<Response> {
+        let Some(key) = &self.key else {
+            return self.send_raw(&command).await;
+        };
+
+        let response = self.send_raw(&self.sign(key, &command).await?).await?;
+        match response {
+            // The daemon restarted or the session expired: start over once
+            Response::Error { code: ErrorCode::Unauthenticated, .. } => {
+                *self.session.lock().unwrap() = None;
+                self.send_raw(&self.sign(key, &command).await?).await
+            }
+            response => Ok(response),
+        }
+    }
+
+    /// Wrap a command with its HMAC for the current session, starting one if needed
+    async fn sign(&self, key: &[u8], command: &Command) -> Result<Command> {
+        if self.session.lock().unwrap().is_none() {
+            match self.send_raw(&Command::StartSession).await? {
+                Response::SessionStarted { session_id, nonce, .. } => {
+                    *self.session.lock().unwrap() = Some(ClientSession {
+                        session_id,
+                        nonce,
+                        counter: 0,
+                    });
+                }
+                other => anyhow::bail!("Unexpected session response: {:?}", other),
+            }
+        }
+
+        let mut session = self.session.lock().unwrap();
+        let session = session.as_mut().context("No session")?;
+        session.counter += 1;
+
+        Ok(Command::Authenticated {
+            session_id: session.session_id.clone(),
+            counter: session.counter,
+            mac: command_mac(key, &session.session_id, &session.nonce, session.counter, command),
+            command: Box::new(command.clone()),
+        })
+    }
+
+    /// Send a command as-is and receive a response
+    async fn send_raw(&self, command: &Command) -> Result<Response> {
         // Connect to the daemon
         let stream = UnixStream::connect(&self.socket_path)
             .await
@@ -41,7 +114,7 @@ impl IpcClient {
         let (reader, mut writer) = stream.into_split();
 
         // Serialize and send the command
-        let mut json = serde_json::to_string(&command)?;
+        let mut json = serde_json::to_string(command)?;
         json.push('\n');
         writer.write_all(json.as_bytes()).await?;
 
@@ -57,6 +130,45 @@ impl IpcClient {
         Ok(response)
     }
 
+    /// Open a connection that streams daemon events
+    pub async fn subscribe(&self) -> Result<ResponseStream> {
+        self.open_stream(Command::Subscribe).await
+    }
+
+    /// Open a connection that streams DNS queries matching the filter
+    pub async fn tail_query_log(&self, filter: QueryLogFilter, recent: usize) -> Result<ResponseStream> {
+        self.open_stream(Command::TailQueryLog { filter, recent }).await
+    }
+
+    /// Send a streaming command and wait for the daemon to confirm the subscription
+    async fn open_stream(&self, command: Command) -> Result<ResponseStream> {
+        let stream = UnixStream::connect(&self.socket_path)
+            .await
+            .context("Failed to connect to daemon. Is it running?")?;
+
+        let (reader, mut writer) = stream.into_split();
+
+        // Reading the query log needs a signed command when the daemon asks for it
+        let command = match &self.key {
+            Some(key) => self.sign(key, &command).await?,
+            None => command,
+        };
+        let mut json = serde_json::to_string(&command)?;
+        json.push('\n');
+        writer.write_all(json.as_bytes()).await?;
+
+        let mut responses = ResponseStream {
+            reader: BufReader::new(reader),
+            _writer: writer,
+        };
+
+        match responses.read_response().await? {
+            Response::Subscribed => Ok(responses),
+            Response::Error { message, .. } => anyhow::bail!(message),
+            other => anyhow::bail!("Unexpected subscription response: {:?}", other),
+        }
+    }
+
     /// Get the current daemon status
     pub async fn get_status(&self) -> Result<Response> {
         self.send_command(Command::GetStatus).await
@@ -68,15 +180,35 @@ impl IpcClient {
     }
 
     /// Add a domain to the blocklist
-    pub async fn add_domain(&self, domain: String) -> Result<Response> {
+    pub async fn add_domain(&self, domain: DomainName) -> Result<Response> {
         self.send_command(Command::AddDomain { domain }).await
     }
 
     /// Remove a domain from the blocklist
-    pub async fn remove_domain(&self, domain: String) -> Result<Response> {
+    pub async fn remove_domain(&self, domain: DomainName) -> Result<Response> {
         self.send_command(Command::RemoveDomain { domain }).await
     }
 
+    /// Get the domains that are never blocked
+    pub async fn get_essential_domains(&self) -> Result<Response> {
+        self.send_command(Command::GetEssentialDomains).await
+    }
+
+    /// Never block a domain
+    pub async fn add_essential_domain(&self, domain: DomainName) -> Result<Response> {
+        self.send_command(Command::AddEssentialDomain { domain }).await
+    }
+
+    /// Remove a domain from the essential-services list
+    pub async fn remove_essential_domain(&self, domain: DomainName) -> Result<Response> {
+        self.send_command(Command::RemoveEssentialDomain { domain }).await
+    }
+
+    /// Enable or disable a blocklist group
+    pub async fn set_group_enabled(&self, name: String, enabled: bool) -> Result<Response> {
+        self.send_command(Command::SetGroupEnabled { name, enabled }).await
+    }
+
     /// Get the current schedule
     pub async fn get_schedule(&self) -> Result<Response> {
         self.send_command(Command::GetSchedule).await
@@ -87,13 +219,63 @@ impl IpcClient {
         self.send_command(Command::UpdateSchedule { schedule }).await
     }
 
+    /// Get schedule edits waiting to take effect
+    pub async fn get_pending_changes(&self) -> Result<Response> {
+        self.send_command(Command::GetPendingChanges).await
+    }
+
+    /// Suspend the schedule for a date range
+    pub async fn schedule_vacation(&self, start: NaiveDate, end: NaiveDate) -> Result<Response> {
+        self.send_command(Command::ScheduleVacation { start, end }).await
+    }
+
+    /// Cancel a vacation that has not started yet
+    pub async fn cancel_vacation(&self, id: String) -> Result<Response> {
+        self.send_command(Command::CancelVacation { id }).await
+    }
+
+    /// Get upcoming and current vacations
+    pub async fn get_vacations(&self) -> Result<Response> {
+        self.send_command(Command::GetVacations).await
+    }
+
+    /// Get the periods the schedule will block in over the next 7 days
+    pub async fn get_schedule_timeline(&self) -> Result<Response> {
+        self.send_command(Command::GetScheduleTimeline).await
+    }
+
+    /// Generate a schedule from a template, without applying it
+    pub async fn generate_schedule(&self, template: ScheduleTemplate) -> Result<Response> {
+        self.send_command(Command::GenerateSchedule { template }).await
+    }
+
+    /// Delete personal data (without a confirmation, only get one)
+    pub async fn purge_data(&self, scope: PurgeScope, confirmation: Option<String>) -> Result<Response> {
+        self.send_command(Command::PurgeData { scope, confirmation }).await
+    }
+
+    /// Block for the next N minutes with a temporary schedule rule
+    pub async fn focus_now(&self, minutes: u32) -> Result<Response> {
+        self.send_command(Command::FocusNow { minutes }).await
+    }
+
+    /// Request a quiz to snooze the next schedule rule
+    pub async fn snooze_schedule(&self, minutes: u32) -> Result<Response> {
+        self.send_command(Command::SnoozeSchedule { minutes }).await
+    }
+
     /// Request a bypass quiz
-    pub async fn request_bypass(&self, duration_minutes: u32) -> Result<Response> {
-        self.send_command(Command::RequestBypass { duration_minutes }).await
+    pub async fn request_bypass(&self, duration: Duration, reason: Option<String>) -> Result<Response> {
+        self.send_command(Command::RequestBypass { duration, reason }).await
+    }
+
+    /// Move a memory quiz from the reveal phase to the answer phase
+    pub async fn begin_quiz_answer(&self, challenge_id: String) -> Result<Response> {
+        self.send_command(Command::BeginQuizAnswer { challenge_id }).await
     }
 
     /// Submit quiz answers
-    pub async fn submit_quiz_answers(&self, challenge_id: String, answers: Vec<i32>) -> Result<Response> {
+    pub async fn submit_quiz_answers(&self, challenge_id: String, answers: Vec<QuizAnswer>) -> Result<Response> {
         self.send_command(Command::SubmitQuizAnswers { challenge_id, answers }).await
     }
 
@@ -101,6 +283,130 @@ impl IpcClient {
     pub async fn cancel_bypass(&self) -> Result<Response> {
         self.send_command(Command::CancelBypass).await
     }
+
+    /// Stop the daemon
+    pub async fn shutdown(&self) -> Result<Response> {
+        self.send_command(Command::Shutdown).await
+    }
+
+    /// Lock settings against weakening for a number of hours
+    pub async fn lock_settings(&self, hours: u32) -> Result<Response> {
+        self.send_command(Command::LockSettings { hours }).await
+    }
+
+    /// Explain whether a domain is blocked right now, and why
+    pub async fn explain_domain(&self, domain: String) -> Result<Response> {
+        self.send_command(Command::ExplainDomain { domain }).await
+    }
+
+    /// Check that the system routes DNS through the daemon
+    pub async fn run_self_check(&self) -> Result<Response> {
+        self.send_command(Command::RunSelfCheck).await
+    }
+
+    /// Send this week's report now, to check the delivery settings
+    pub async fn send_weekly_report(&self) -> Result<Response> {
+        self.send_command(Command::SendWeeklyReport).await
+    }
+
+    /// Export the configuration and persisted state as a backup archive
+    pub async fn create_backup(&self) -> Result<Response> {
+        self.send_command(Command::CreateBackup).await
+    }
+
+    /// Replace the configuration and persisted state with a backup archive
+    pub async fn restore_backup(&self, archive: String) -> Result<Response> {
+        self.send_command(Command::RestoreBackup { archive }).await
+    }
+
+    /// Import another blocker's export (the file's bytes, base64-encoded)
+    pub async fn import_external(
+        &self,
+        source: ImportSource,
+        data: String,
+        dry_run: bool,
+    ) -> Result<Response> {
+        self.send_command(Command::ImportExternal { source, data, dry_run }).await
+    }
+
+    /// Get the daemon version and build information
+    pub async fn get_version(&self) -> Result<Response> {
+        self.send_command(Command::GetVersion).await
+    }
+
+    /// Whether the running daemon supports an optional feature
+    pub async fn supports(&self, capability: Capability) -> Result<bool> {
+        match self.get_version().await? {
+            Response::Version(info) => Ok(info.supports(capability)),
+            // Daemons predating GetVersion support none of the optional features
+            _ => Ok(false),
+        }
+    }
+
+    /// Check the release manifest for a newer daemon
+    pub async fn check_for_update(&self) -> Result<Response> {
+        self.send_command(Command::CheckForUpdate).await
+    }
+
+    /// Download and install the latest daemon (the daemon restarts afterwards)
+    pub async fn apply_update(&self) -> Result<Response> {
+        self.send_command(Command::ApplyUpdate).await
+    }
+
+    /// Get query counters and today's usage estimates
+    pub async fn get_stats(&self) -> Result<Response> {
+        self.send_command(Command::GetStats).await
+    }
+
+    /// Get up to `limit` of the most recent stored queries matching `filter`
+    pub async fn get_query_log(&self, limit: usize, filter: QueryLogFilter) -> Result<Response> {
+        self.send_command(Command::GetQueryLog { limit, filter }).await
+    }
+
+    /// Get DNS cache statistics
+    pub async fn get_cache_stats(&self) -> Result<Response> {
+        self.send_command(Command::GetCacheStats).await
+    }
+
+    /// Flush the DNS cache (a single domain, or everything)
+    pub async fn flush_cache(&self, domain: Option<String>) -> Result<Response> {
+        self.send_command(Command::FlushCache { domain }).await
+    }
+
+    /// Get the per-client policy profiles
+    pub async fn get_client_profiles(&self) -> Result<Response> {
+        self.send_command(Command::GetClientProfiles).await
+    }
+
+    /// Add or replace a client profile
+    pub async fn set_client_profile(&self, profile: ClientProfile) -> Result<Response> {
+        self.send_command(Command::SetClientProfile { profile }).await
+    }
+
+    /// Remove a client profile
+    pub async fn remove_client_profile(&self, name: String) -> Result<Response> {
+        self.send_command(Command::RemoveClientProfile { name }).await
+    }
+
+    /// Get subscribed blocklists and their refresh status
+    pub async fn get_subscriptions(&self) -> Result<Response> {
+        self.send_command(Command::GetSubscriptions).await
+    }
+
+    /// Get recent subscription changes, optionally only those that added a domain
+    pub async fn get_blocklist_diffs(&self, domain: Option<String>) -> Result<Response> {
+        self.send_command(Command::GetBlocklistDiffs { domain }).await
+    }
+
+    /// Get the weekly bypass breakdown
+    pub async fn get_bypass_analytics(&self, weeks: u32) -> Result<Response> {
+        self.send_command(Command::GetBypassAnalytics { weeks }).await
+    }
+
+    /// Get blocked queries per day and hour
+    pub async fn get_activity_heatmap(&self, weeks: u32) -> Result<Response> {
+        self.send_command(Command::GetActivityHeatmap { weeks }).await
+    }
 }
 
 impl Default for IpcClient {
@@ -108,3 +414,50 @@ impl Default for IpcClient {
         Self::new()
     }
 }
+
+/// Decode a hex string (None if malformed)
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Responses streamed from the daemon after a streaming command
+pub struct ResponseStream {
+    reader: BufReader<OwnedReadHalf>,
+    // Kept so the daemon does not see the connection as closed
+    _writer: tokio::net::unix::OwnedWriteHalf,
+}
+
+impl ResponseStream {
+    /// Wait for the next event (errors once the daemon closes the connection)
+    pub async fn next_event(&mut self) -> Result<Event> {
+        loop {
+            if let Response::Event(event) = self.read_response().await? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Wait for the next query log entry (errors once the daemon closes the connection)
+    pub async fn next_query_log_entry(&mut self) -> Result<QueryLogEntry> {
+        loop {
+            if let Response::QueryLogEntry(entry) = self.read_response().await? {
+                return Ok(entry);
+            }
+        }
+    }
+
+    async fn read_response(&mut self) -> Result<Response> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("Daemon closed the event stream");
+        }
+
+        serde_json::from_str(&line).context("Failed to parse daemon response")
+    }
+}
@@ -2,22 +2,35 @@
 //!
 //! This module contains the Tauri commands and state management for the menu bar app.
 
+mod blocked_feed;
 mod commands;
+mod hotkey;
+mod installer;
 mod ipc_client;
 
+use blockandfocus_shared::{
+    AllowanceStatus, AnswerKind, Capability, EncryptedDnsDetection, Event, Locale, QuizChallenge,
+    QuizKind, QuizPhase, QuizPurpose, Strictness, VersionInfo,
+};
+use blocked_feed::BlockedFeed;
+use hotkey::FocusHotkey;
 use ipc_client::IpcClient;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::{
     menu::{Menu, MenuItem},
     tray::TrayIconBuilder,
-    AppHandle, Manager,
+    AppHandle, Emitter, Manager,
 };
 use tokio::sync::Mutex;
 
 /// Application state shared across Tauri commands
 pub struct AppState {
     pub client: Arc<Mutex<IpcClient>>,
+    /// Recent blocked attempts per site, kept up to date in the background
+    pub blocked_feed: Arc<Mutex<BlockedFeed>>,
+    /// Global shortcut that starts a focus session
+    pub focus_hotkey: Arc<Mutex<FocusHotkey>>,
 }
 
 /// Status information returned to the frontend
@@ -30,6 +43,38 @@ pub struct StatusInfo {
     pub bypass_remaining_seconds: Option<i64>,
     pub blocked_count: u64,
     pub daemon_connected: bool,
+    pub bypass_budget_remaining_minutes: Option<u32>,
+    /// Seconds until a granted soft-start bypass takes effect
+    pub bypass_countdown_seconds: Option<i64>,
+    /// Daily allowances and today's usage
+    pub allowances: Vec<AllowanceStatus>,
+    /// Observe-only mode: nothing is actually blocked
+    pub dry_run: bool,
+    pub would_block_count: u64,
+    /// Connections to encrypted DNS resolvers seen while blocking
+    pub encrypted_dns_count: u64,
+    pub last_encrypted_dns: Option<EncryptedDnsDetection>,
+    /// Version of a newer daemon release, if one was found
+    pub update_available: Option<String>,
+    /// Strictness of the active schedule rules
+    pub strictness: Option<Strictness>,
+    /// Minutes until locked settings can be weakened again
+    pub settings_lock_remaining_minutes: Option<u32>,
+    /// Last day of the vacation in progress
+    pub vacation_until: Option<chrono::NaiveDate>,
+    /// Groups blocked early by wind-down rules
+    pub wind_down_groups: Vec<String>,
+    /// Language of daemon-produced text
+    pub locale: Locale,
+}
+
+/// Daemon and app versions for the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionDetails {
+    pub daemon: VersionInfo,
+    pub app_version: String,
+    /// The daemon speaks a different IPC protocol version than the app
+    pub protocol_mismatch: bool,
 }
 
 /// Quiz information for the frontend
@@ -38,6 +83,37 @@ pub struct QuizInfo {
     pub challenge_id: String,
     pub questions: Vec<String>,
     pub expires_at: i64,
+    pub kind: QuizKind,
+    pub phase: QuizPhase,
+    pub reveal: Vec<String>,
+    pub reveal_seconds: u32,
+    pub answer_kinds: Vec<AnswerKind>,
+    pub stage: u32,
+    pub total_stages: u32,
+    pub wait_until: Option<i64>,
+    pub last_reason: Option<String>,
+    /// What passing the quiz unlocks
+    pub purpose: QuizPurpose,
+}
+
+impl From<QuizChallenge> for QuizInfo {
+    fn from(quiz: QuizChallenge) -> Self {
+        Self {
+            challenge_id: quiz.challenge_id,
+            questions: quiz.questions,
+            expires_at: quiz.expires_at,
+            kind: quiz.kind,
+            phase: quiz.phase,
+            reveal: quiz.reveal,
+            reveal_seconds: quiz.reveal_seconds,
+            answer_kinds: quiz.answer_kinds,
+            stage: quiz.stage,
+            total_stages: quiz.total_stages,
+            wait_until: quiz.wait_until,
+            last_reason: quiz.last_reason,
+            purpose: quiz.purpose,
+        }
+    }
 }
 
 /// Result of quiz submission
@@ -45,6 +121,8 @@ pub struct QuizInfo {
 pub struct QuizResult {
     pub success: bool,
     pub message: String,
+    /// Next stage to complete when the challenge has multiple stages
+    pub next_stage: Option<QuizInfo>,
 }
 
 // ============================================================================
@@ -81,6 +159,47 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// ============================================================================
+// Daemon Events
+// ============================================================================
+
+/// Seconds to wait before reconnecting to the daemon's event stream
+const EVENT_RECONNECT_SECONDS: u64 = 5;
+
+/// Seconds to wait before checking again whether a daemon without event
+/// streaming has been upgraded (the UI keeps polling status meanwhile)
+const EVENT_UNSUPPORTED_RETRY_SECONDS: u64 = 300;
+
+/// Forward daemon events to the frontend as `daemon-event`, reconnecting as needed
+async fn forward_daemon_events(app: AppHandle) {
+    let client = IpcClient::new();
+
+    loop {
+        if let Ok(false) = client.supports(Capability::EventStreaming).await {
+            tokio::time::sleep(std::time::Duration::from_secs(EVENT_UNSUPPORTED_RETRY_SECONDS))
+                .await;
+            continue;
+        }
+
+        if let Ok(mut events) = client.subscribe().await {
+            while let Ok(event) = events.next_event().await {
+                // A bypass asked for from the block page is requested here
+                if let Event::BypassRequested(_) = &event {
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.show();
+                        let _ = window.set_focus();
+                    }
+                }
+                if let Err(e) = app.emit("daemon-event", &event) {
+                    tracing::warn!("Failed to emit daemon event: {}", e);
+                }
+            }
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(EVENT_RECONNECT_SECONDS)).await;
+    }
+}
+
 // ============================================================================
 // App Runner
 // ============================================================================
@@ -88,13 +207,22 @@ pub fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
 /// Run the Tauri application
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let blocked = Arc::new(Mutex::new(BlockedFeed::default()));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(hotkey::plugin())
         .manage(AppState {
             client: Arc::new(Mutex::new(IpcClient::new())),
+            blocked_feed: blocked.clone(),
+            focus_hotkey: Arc::new(Mutex::new(FocusHotkey::default())),
         })
-        .setup(|app| {
+        .setup(move |app| {
             setup_tray(app.handle())?;
+            tauri::async_runtime::spawn(forward_daemon_events(app.handle().clone()));
+            tauri::async_runtime::spawn(blocked_feed::run(app.handle().clone(), blocked));
+            tauri::async_runtime::spawn(hotkey::init(app.handle().clone()));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -102,11 +230,52 @@ pub fn run() {
             commands::get_blocklist,
             commands::add_domain,
             commands::remove_domain,
+            commands::set_group_enabled,
+            commands::get_essential_domains,
+            commands::add_essential_domain,
+            commands::remove_essential_domain,
             commands::get_schedule,
             commands::set_schedule_enabled,
+            commands::get_pending_changes,
+            commands::schedule_vacation,
+            commands::cancel_vacation,
+            commands::get_vacations,
+            commands::get_schedule_timeline,
+            commands::generate_schedule,
+            commands::request_purge,
+            commands::confirm_purge,
+            commands::focus_now,
+            commands::get_focus_hotkey,
+            commands::set_focus_hotkey,
+            commands::snooze_schedule,
             commands::request_bypass,
+            commands::begin_quiz_answer,
             commands::submit_quiz_answers,
             commands::cancel_bypass,
+            commands::stop_daemon,
+            commands::lock_settings,
+            commands::get_stats,
+            commands::get_query_log,
+            commands::explain_domain,
+            commands::run_self_check,
+            commands::send_weekly_report,
+            commands::create_backup,
+            commands::restore_backup,
+            commands::import_external,
+            commands::get_version,
+            commands::check_for_update,
+            commands::apply_update,
+            commands::get_blocked_attempts,
+            commands::get_cache_stats,
+            commands::get_subscriptions,
+            commands::get_client_profiles,
+            commands::set_client_profile,
+            commands::remove_client_profile,
+            commands::get_blocklist_diffs,
+            commands::flush_cache,
+            commands::get_bypass_analytics,
+            commands::get_activity_heatmap,
+            commands::install_daemon,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
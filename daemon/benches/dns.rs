@@ -0,0 +1,107 @@
+//! Benchmarks for the DNS hot path: blocklist matching and query handling.
+//!
+//! The daemon has no library target, so the modules under test are included
+//! from source. Run with `just bench`.
+
+use blockandfocus_shared::DomainName;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use hickory_proto::op::{Message, MessageType, Query};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::serialize::binary::BinEncodable;
+use std::hint::black_box;
+
+// Their unit tests are compiled in too, but not run
+#[allow(dead_code, unused_imports)]
+#[path = "../src/dns"]
+mod dns {
+    pub mod blocker;
+    mod bloom;
+    pub mod parse;
+    mod trie;
+}
+
+use dns::blocker::DomainBlocker;
+use dns::parse::parse_query;
+
+/// Blocklist sizes, from a hand-written list to a large subscribed one.
+const LIST_SIZES: [usize; 4] = [100, 10_000, 100_000, 1_000_000];
+
+/// A blocklist of `size` distinct domains spread over a few TLDs.
+fn blocklist(size: usize) -> Vec<DomainName> {
+    const TLDS: [&str; 5] = ["com", "net", "org", "io", "co.uk"];
+    (0..size)
+        .map(|i| format!("site{}.example{}.{}", i, i % 97, TLDS[i % 5]))
+        .map(|domain| DomainName::parse(&domain).unwrap())
+        .collect()
+}
+
+/// Queries checked against every list: listed, under a listed entry, and not listed.
+fn queries(size: usize) -> [(&'static str, String); 3] {
+    let last = size - 1;
+    [
+        ("exact", format!("site{}.example{}.com.", last / 5 * 5, last / 5 * 5 % 97)),
+        ("subdomain", "cdn.static.site0.example0.com.".to_string()),
+        ("miss", "www.wikipedia.org.".to_string()),
+    ]
+}
+
+fn encoded_query(domain: &str) -> Vec<u8> {
+    let mut message = Message::new();
+    message.set_id(0x1234);
+    message.set_message_type(MessageType::Query);
+    message.set_recursion_desired(true);
+    message.add_query(Query::query(Name::from_ascii(domain).unwrap(), RecordType::A));
+    message.to_bytes().unwrap()
+}
+
+fn should_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("should_block");
+    for size in LIST_SIZES {
+        let blocker = DomainBlocker::new(&blocklist(size));
+        for (kind, domain) in queries(size) {
+            assert_eq!(blocker.should_block(&domain), kind != "miss", "{}", domain);
+            group.bench_with_input(BenchmarkId::new(kind, size), &domain, |b, domain| {
+                b.iter(|| blocker.should_block(black_box(domain)))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn build_blocker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_blocker");
+    group.sample_size(10);
+    for size in LIST_SIZES {
+        let domains = blocklist(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &domains, |b, domains| {
+            b.iter(|| DomainBlocker::new(black_box(domains)))
+        });
+    }
+    group.finish();
+}
+
+/// Parsing a packet and deciding whether to block it, as the server does
+/// before answering or forwarding.
+fn parse_and_decide(c: &mut Criterion) {
+    let size = 100_000;
+    let blocker = DomainBlocker::new(&blocklist(size));
+    let mut group = c.benchmark_group("parse_and_decide");
+    for (kind, domain) in queries(size) {
+        let packet = encoded_query(&domain);
+        group.bench_with_input(BenchmarkId::new(kind, size), &packet, |b, packet| {
+            b.iter_batched(
+                || packet.clone(),
+                |packet| {
+                    let query = parse_query(&packet).unwrap();
+                    let domain = query.queries()[0].name().to_string();
+                    blocker.matching_entry(&domain).is_some()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, should_block, build_blocker, parse_and_decide);
+criterion_main!(benches);
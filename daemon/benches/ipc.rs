@@ -0,0 +1,93 @@
+//! Benchmarks for IPC round trips, framed the way the app sends them: one
+//! connection per command, a JSON line each way.
+//!
+//! The in-process server measures the protocol's own cost (connecting,
+//! serializing and parsing). If a daemon is listening on the development
+//! socket (`just daemon-dev`), or on `BLOCKANDFOCUS_BENCH_SOCKET`, round trips
+//! to it are measured too.
+
+use blockandfocus_shared::{Command, DomainName, Response, Status, IPC_SOCKET_PATH_DEV};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::runtime::Runtime;
+
+/// Blocklist entries in the in-process server's `GetBlocklist` answer.
+const BLOCKLIST_SIZE: usize = 1_000;
+
+async fn round_trip(socket: &Path, command: &Command) -> Response {
+    let stream = UnixStream::connect(socket).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(command).unwrap();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await.unwrap();
+    serde_json::from_str(&response).unwrap()
+}
+
+/// Serve canned responses on a socket in a temporary directory.
+fn start_server(rt: &Runtime) -> PathBuf {
+    let name = format!("blockandfocus-bench-{}.sock", std::process::id());
+    let socket = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_file(&socket);
+    let listener = rt.block_on(async { UnixListener::bind(&socket) }).unwrap();
+
+    let domains: Vec<DomainName> = (0..BLOCKLIST_SIZE)
+        .map(|i| DomainName::parse(&format!("site{}.example.com", i)).unwrap())
+        .collect();
+    rt.spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let domains = domains.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut line = String::new();
+                BufReader::new(reader).read_line(&mut line).await.ok()?;
+                let response = match serde_json::from_str(&line).ok()? {
+                    Command::GetStatus => Response::Status(Status::default()),
+                    Command::GetBlocklist => Response::Blocklist {
+                        domains,
+                        allowances: Vec::new(),
+                    },
+                    _ => Response::Pong,
+                };
+                let mut line = serde_json::to_string(&response).ok()?;
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await.ok()
+            });
+        }
+    });
+    socket
+}
+
+fn ipc_round_trip(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut targets = vec![("in_process", start_server(&rt))];
+    let daemon = std::env::var_os("BLOCKANDFOCUS_BENCH_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(IPC_SOCKET_PATH_DEV));
+    if std::os::unix::net::UnixStream::connect(&daemon).is_ok() {
+        targets.push(("daemon", daemon));
+    } else {
+        eprintln!("No daemon on {}; measuring the in-process server only", daemon.display());
+    }
+
+    let mut group = c.benchmark_group("ipc_round_trip");
+    for (target, socket) in &targets {
+        for (name, command) in [
+            ("ping", Command::Ping),
+            ("status", Command::GetStatus),
+            ("blocklist", Command::GetBlocklist),
+        ] {
+            group.bench_with_input(BenchmarkId::new(name, target), &command, |b, command| {
+                b.to_async(&rt).iter(|| round_trip(socket, command))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ipc_round_trip);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+fn main() {
+    // Git commit the daemon was built from
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if let Some(hash) = git_hash {
+        println!("cargo:rustc-env=BLOCKANDFOCUS_GIT_HASH={}", hash);
+    }
+
+    // Build date (UTC)
+    let build_date = Command::new("date")
+        .args(["-u", "+%Y-%m-%dT%H:%M:%SZ"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if let Some(date) = build_date {
+        println!("cargo:rustc-env=BLOCKANDFOCUS_BUILD_DATE={}", date);
+    }
+
+    // Enabled cargo features, comma separated
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=BLOCKANDFOCUS_FEATURES={}", features.join(","));
+
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs");
+}
@@ -0,0 +1,187 @@
+//! Weekly bypass breakdowns.
+
+use crate::store::BypassRecord;
+use blockandfocus_shared::{ReasonCount, WeeklyBypassSummary};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+use std::collections::HashMap;
+
+/// A follow-up bypass starting within this many seconds of the previous one
+/// ending counts as extending it (an overrun) rather than a new bypass.
+const EXTENSION_GAP_SECONDS: i64 = 5 * 60;
+
+/// Number of reasons reported per week.
+const TOP_REASONS: usize = 5;
+
+/// Build a per-week breakdown of bypasses for the last `weeks` weeks
+/// (current week included), most recent week first.
+pub fn weekly_bypass_analytics(
+    history: &[BypassRecord],
+    weeks: u32,
+    now: DateTime<Local>,
+) -> Vec<WeeklyBypassSummary> {
+    let current_week = week_start(now.date_naive());
+
+    (0..weeks.max(1))
+        .map(|i| {
+            let start = current_week - Duration::weeks(i as i64);
+            let end = start + Duration::weeks(1);
+            let records: Vec<&BypassRecord> = history
+                .iter()
+                .filter(|r| {
+                    let day = local_date(r.started_at);
+                    day >= start && day < end
+                })
+                .collect();
+            summarize_week(start, &records)
+        })
+        .collect()
+}
+
+/// Summarize the bypasses of a single week.
+fn summarize_week(week_start: NaiveDate, records: &[&BypassRecord]) -> WeeklyBypassSummary {
+    let total_minutes_requested = records.iter().map(|r| r.duration_minutes as u64).sum();
+    let total_minutes_used = records.iter().map(|r| used_seconds(r) as u64).sum::<u64>() / 60;
+
+    // Group chained bypasses into sessions and compare against the first request
+    let mut overruns = Vec::new();
+    let mut iter = records.iter().peekable();
+    while let Some(first) = iter.next() {
+        let mut session_end = first.started_at + used_seconds(first);
+        while let Some(next) = iter.peek() {
+            if next.started_at - session_end > EXTENSION_GAP_SECONDS {
+                break;
+            }
+            session_end = session_end.max(next.started_at + used_seconds(next));
+            iter.next();
+        }
+        let requested = first.duration_minutes as i64 * 60;
+        let overrun = (session_end - first.started_at - requested).max(0);
+        overruns.push(overrun as f64 / 60.0);
+    }
+    let average_overrun_minutes = if overruns.is_empty() {
+        0.0
+    } else {
+        overruns.iter().sum::<f64>() / overruns.len() as f64
+    };
+
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for reason in records.iter().filter_map(|r| r.reason.as_deref()) {
+        *counts.entry(normalize_reason(reason)).or_default() += 1;
+    }
+    let mut top_reasons: Vec<ReasonCount> = counts
+        .into_iter()
+        .map(|(reason, count)| ReasonCount { reason, count })
+        .collect();
+    top_reasons.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.reason.cmp(&b.reason)));
+    top_reasons.truncate(TOP_REASONS);
+
+    WeeklyBypassSummary {
+        week_start: week_start.format("%Y-%m-%d").to_string(),
+        bypass_count: records.len() as u32,
+        total_minutes_requested,
+        total_minutes_used,
+        average_overrun_minutes,
+        top_reasons,
+    }
+}
+
+/// Seconds a bypass was actually active.
+fn used_seconds(record: &BypassRecord) -> i64 {
+    let requested = record.duration_minutes as i64 * 60;
+    match record.cancelled_at {
+        Some(cancelled) => (cancelled - record.started_at).clamp(0, requested),
+        None => requested,
+    }
+}
+
+/// Monday of the week containing the given date.
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Local calendar date of a Unix timestamp.
+fn local_date(timestamp: i64) -> NaiveDate {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.date_naive())
+        .unwrap_or_default()
+}
+
+/// Normalize a reason for grouping (lowercase, collapsed whitespace).
+fn normalize_reason(reason: &str) -> String {
+    reason
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: i64, minutes: u32, reason: Option<&str>) -> BypassRecord {
+        BypassRecord {
+            started_at,
+            duration_minutes: minutes,
+            reason: reason.map(|r| r.to_string()),
+            cancelled_at: None,
+        }
+    }
+
+    #[test]
+    fn test_weekly_breakdown() {
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap(); // Wednesday
+        let monday = Local.with_ymd_and_hms(2025, 6, 9, 10, 0, 0).unwrap().timestamp();
+        let last_week = Local.with_ymd_and_hms(2025, 6, 3, 10, 0, 0).unwrap().timestamp();
+
+        let history = vec![
+            record(last_week, 10, Some("Flights")),
+            record(monday, 15, Some("check  flights")),
+            record(monday + 3600, 15, Some("Check flights")),
+            record(monday + 7200, 5, Some("messages")),
+        ];
+
+        let weeks = weekly_bypass_analytics(&history, 2, now);
+        assert_eq!(weeks.len(), 2);
+
+        assert_eq!(weeks[0].week_start, "2025-06-09");
+        assert_eq!(weeks[0].bypass_count, 3);
+        assert_eq!(weeks[0].total_minutes_requested, 35);
+        assert_eq!(weeks[0].top_reasons[0].reason, "check flights");
+        assert_eq!(weeks[0].top_reasons[0].count, 2);
+
+        assert_eq!(weeks[1].week_start, "2025-06-02");
+        assert_eq!(weeks[1].bypass_count, 1);
+    }
+
+    #[test]
+    fn test_overrun_from_chained_bypasses() {
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        let start = Local.with_ymd_and_hms(2025, 6, 10, 10, 0, 0).unwrap().timestamp();
+
+        // 15 minutes requested, then extended by another 10 right after it ended
+        let history = vec![
+            record(start, 15, None),
+            record(start + 15 * 60 + 60, 10, None),
+        ];
+
+        let weeks = weekly_bypass_analytics(&history, 1, now);
+        assert!((weeks[0].average_overrun_minutes - 11.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_cancelled_bypass_counts_used_time() {
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap();
+        let start = Local.with_ymd_and_hms(2025, 6, 10, 10, 0, 0).unwrap().timestamp();
+
+        let mut cancelled = record(start, 30, None);
+        cancelled.cancelled_at = Some(start + 10 * 60);
+
+        let weeks = weekly_bypass_analytics(&[cancelled], 1, now);
+        assert_eq!(weeks[0].total_minutes_requested, 30);
+        assert_eq!(weeks[0].total_minutes_used, 10);
+        assert_eq!(weeks[0].average_overrun_minutes, 0.0);
+    }
+}
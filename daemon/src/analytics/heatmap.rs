@@ -0,0 +1,77 @@
+//! Day-by-hour blocking activity.
+
+use crate::store::DailyBlocks;
+use blockandfocus_shared::{ActivityHeatmap, HeatmapDay};
+use chrono::{DateTime, Datelike, Duration, Local};
+
+/// Build a heatmap of blocked queries for the last `weeks` weeks (current week
+/// included). Days run from a Monday to today, so they fill whole columns.
+pub fn activity_heatmap(
+    activity: &[DailyBlocks],
+    weeks: u32,
+    now: DateTime<Local>,
+) -> ActivityHeatmap {
+    let today = now.date_naive();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let first = monday - Duration::weeks(weeks.max(1) as i64 - 1);
+
+    let days: Vec<HeatmapDay> = first
+        .iter_days()
+        .take_while(|date| *date <= today)
+        .map(|date| {
+            let date = date.format("%Y-%m-%d").to_string();
+            let mut hours = vec![0; 24];
+            if let Some(day) = activity.iter().find(|day| day.date == date) {
+                for (total, blocked) in hours.iter_mut().zip(&day.hours) {
+                    *total = *blocked;
+                }
+            }
+            HeatmapDay {
+                date,
+                total: hours.iter().sum(),
+                hours,
+            }
+        })
+        .collect();
+
+    let max_hourly = days
+        .iter()
+        .flat_map(|day| day.hours.iter().copied())
+        .max()
+        .unwrap_or(0);
+    ActivityHeatmap { days, max_hourly }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_heatmap_covers_whole_weeks_to_today() {
+        let now = Local.with_ymd_and_hms(2025, 6, 11, 12, 0, 0).unwrap(); // Wednesday
+        let mut busy = vec![0; 24];
+        busy[9] = 7;
+        busy[21] = 2;
+        let activity = vec![
+            DailyBlocks {
+                date: "2025-05-01".to_string(),
+                hours: vec![1; 24],
+            },
+            DailyBlocks {
+                date: "2025-06-03".to_string(),
+                hours: busy,
+            },
+        ];
+
+        let heatmap = activity_heatmap(&activity, 2, now);
+        assert_eq!(heatmap.days.len(), 7 + 3);
+        assert_eq!(heatmap.days[0].date, "2025-06-02");
+        assert_eq!(heatmap.days.last().unwrap().date, "2025-06-11");
+
+        let tuesday = &heatmap.days[1];
+        assert_eq!((tuesday.hours[9], tuesday.total), (7, 9));
+        assert_eq!(heatmap.days[0].total, 0);
+        assert_eq!(heatmap.max_hourly, 7);
+    }
+}
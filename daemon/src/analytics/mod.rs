@@ -0,0 +1,7 @@
+//! Aggregations over persisted history for reporting.
+
+mod bypass;
+mod heatmap;
+
+pub use bypass::weekly_bypass_analytics;
+pub use heatmap::activity_heatmap;
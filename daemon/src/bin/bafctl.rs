@@ -0,0 +1,759 @@
+//! Command-line client for the BlockAndFocus daemon.
+//!
+//! `bafctl <command>` sends a one-shot text command (see `bafctl help`) and
+//! prints the daemon's one-line reply. `bafctl statusbar` prints the current
+//! status in the format SwiftBar and xbar read from plugin scripts, with menu
+//! items that call back into `bafctl`. `bafctl backup [FILE]` and
+//! `bafctl restore FILE` save and restore the configuration and state.
+//! `bafctl import SOURCE FILE [--apply]` previews or imports another
+//! blocker's export. `bafctl template NAME [key=value...]` prints a schedule
+//! generated from a template, as TOML to edit and paste into the config.
+//! `bafctl status --watch` and `bafctl stats --watch` keep printing as things
+//! change, and `--json-lines` prints each update as one JSON object per line
+//! for other tools to read. `bafctl config validate|edit|set` checks the
+//! config file, edits it with `$EDITOR` or sets one dotted key, and only
+//! saves a config that passes the same checks the daemon runs.
+
+use base64::Engine;
+use blockandfocus_shared::{
+    check_config, parse_config, Command, ImportPreview, ImportSource, Response, Schedule,
+    Status, UsageStats, CONFIG_PATH, CONFIG_PATH_DEV, IPC_SOCKET_PATH, IPC_SOCKET_PATH_DEV,
+};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for the daemon before giving up.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often `--watch` asks the daemon again without an event.
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sites listed in the one-line stats summary.
+const STATS_TOP_SITES: usize = 3;
+
+/// Focus session lengths offered in the status bar menu, in minutes.
+const FOCUS_MINUTES: [u32; 3] = [25, 50, 90];
+
+fn socket_path() -> &'static str {
+    if std::env::var("BLOCKANDFOCUS_DEV").is_ok() {
+        IPC_SOCKET_PATH_DEV
+    } else {
+        IPC_SOCKET_PATH
+    }
+}
+
+fn connect() -> std::io::Result<UnixStream> {
+    let stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Send a text command and return the daemon's reply.
+fn quick(line: &str) -> std::io::Result<String> {
+    let mut stream = connect()?;
+    stream.write_all(format!("{}\n", line).as_bytes())?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// Send a JSON command and return the response, or the error to show.
+fn send(command: &Command) -> Result<Response, String> {
+    let exchange = || -> std::io::Result<String> {
+        let mut stream = connect()?;
+        let mut line = serde_json::to_string(command)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response)?;
+        Ok(response)
+    };
+    let response = exchange().map_err(|e| format!("can't reach the daemon: {}", e))?;
+
+    match serde_json::from_str(&response) {
+        Ok(Response::Error { message, .. }) => Err(message),
+        Ok(response) => Ok(response),
+        Err(e) => Err(format!("unreadable response: {}", e)),
+    }
+}
+
+fn get_status() -> Result<Status, String> {
+    match send(&Command::GetStatus)? {
+        Response::Status(status) => Ok(status),
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+fn get_stats() -> Result<UsageStats, String> {
+    match send(&Command::GetStats)? {
+        Response::Stats(stats) => Ok(stats),
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+/// How `status` and `stats` print.
+#[derive(Debug, PartialEq)]
+struct WatchOptions {
+    /// Keep printing as things change
+    watch: bool,
+    /// How often to ask again when no event arrives
+    interval: Duration,
+    /// One JSON object per line instead of text
+    json_lines: bool,
+}
+
+fn parse_watch_options(args: &[String]) -> Result<WatchOptions, String> {
+    let mut options = WatchOptions {
+        watch: false,
+        interval: DEFAULT_WATCH_INTERVAL,
+        json_lines: false,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => options.watch = true,
+            "--json-lines" => options.json_lines = true,
+            "--interval" => {
+                let value = args.next().ok_or("--interval needs a duration, e.g. 5s")?;
+                let interval: blockandfocus_shared::Duration =
+                    value.parse().map_err(|e| format!("--interval: {}", e))?;
+                if interval.as_secs() == 0 {
+                    return Err("--interval must be at least 1s".to_string());
+                }
+                options.interval = Duration::from_secs(interval.as_secs());
+            }
+            other => return Err(format!("unknown option {}", other)),
+        }
+    }
+    Ok(options)
+}
+
+/// Print the status or stats once, or with `--watch` again whenever the
+/// daemon reports an event or `--interval` passes and the output changed.
+fn watch<T: Serialize>(
+    options: &WatchOptions,
+    fetch: impl Fn() -> Result<T, String>,
+    render: impl Fn(&T) -> String,
+) -> Result<(), String> {
+    if !options.watch {
+        let value = fetch()?;
+        println!("{}", render_update(&value, &render, options.json_lines));
+        return Ok(());
+    }
+
+    let events = subscribe_events(options.interval);
+    let mut last = None;
+    loop {
+        let line = match fetch() {
+            Ok(value) => render_update(&value, &render, options.json_lines),
+            Err(e) => {
+                eprintln!("{}", e);
+                String::new()
+            }
+        };
+        if !line.is_empty() && last.as_ref() != Some(&line) {
+            if options.json_lines {
+                println!("{}", line);
+            } else {
+                println!("{} {}", chrono::Local::now().format("%H:%M:%S"), line);
+            }
+            // Readers on the other end of a pipe see each update right away
+            std::io::stdout().flush().map_err(|e| e.to_string())?;
+            last = Some(line);
+        }
+
+        match events.recv_timeout(options.interval) {
+            Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err("event subscription ended".to_string());
+            }
+        }
+    }
+}
+
+fn render_update<T: Serialize>(value: &T, render: impl Fn(&T) -> String, json: bool) -> String {
+    if json {
+        serde_json::to_string(value).unwrap_or_default()
+    } else {
+        render(value)
+    }
+}
+
+/// Subscribe to daemon events on a background thread, sending a message for
+/// each one. The subscription is made again after `retry` if it drops, e.g.
+/// while the daemon restarts.
+fn subscribe_events(retry: Duration) -> mpsc::Receiver<()> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let subscription = || -> std::io::Result<UnixStream> {
+            let mut stream = connect()?;
+            let mut line = serde_json::to_string(&Command::Subscribe)?;
+            line.push('\n');
+            stream.write_all(line.as_bytes())?;
+            // Events may be hours apart
+            stream.set_read_timeout(None)?;
+            Ok(stream)
+        };
+        if let Ok(stream) = subscription() {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                let event = matches!(serde_json::from_str(&line), Ok(Response::Event(_)));
+                if event && sender.send(()).is_err() {
+                    return;
+                }
+            }
+        }
+        std::thread::sleep(retry);
+    });
+    receiver
+}
+
+/// The status in one line, as the `status` quick command prints it.
+fn render_status(status: &Status) -> String {
+    let mut parts = vec![if status.blocking_active {
+        match &status.active_schedule_rule {
+            Some(rule) => format!("blocking ({})", rule),
+            None => "blocking".to_string(),
+        }
+    } else {
+        "not blocking".to_string()
+    }];
+    if let Some(until) = status.bypass_until {
+        parts.push(format!("bypass until {}", local_time(until)));
+    }
+    if let Some(until) = status.schedule_snoozed_until {
+        parts.push(format!("snoozed until {}", local_time(until)));
+    }
+    parts.push(format!("{} domains", status.blocked_domains_count));
+    parts.push(format!("{} queries blocked", status.queries_blocked));
+    parts.join(", ")
+}
+
+/// Query counters and the most used sites in one line.
+fn render_stats(stats: &UsageStats) -> String {
+    let mut line = format!(
+        "{} queries blocked, {} forwarded",
+        stats.queries_blocked, stats.queries_forwarded
+    );
+    let top: Vec<String> = stats
+        .domains
+        .iter()
+        .take(STATS_TOP_SITES)
+        .map(|site| format!("{} {}m", site.domain, site.minutes))
+        .collect();
+    if !top.is_empty() {
+        line.push_str(&format!("; today: {}", top.join(", ")));
+    }
+    line
+}
+
+/// Write a backup to `path`, or to stdout without one.
+fn backup(path: Option<&str>) -> Result<(), String> {
+    let archive = match send(&Command::CreateBackup)? {
+        Response::Backup { archive } => archive,
+        other => return Err(format!("unexpected response: {:?}", other)),
+    };
+    match path {
+        Some(path) => std::fs::write(path, archive).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            println!("{}", archive);
+            Ok(())
+        }
+    }
+}
+
+/// Restore the backup in `path`.
+fn restore(path: &str) -> Result<(), String> {
+    let archive = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    match send(&Command::RestoreBackup { archive })? {
+        Response::Success => Ok(()),
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+/// Preview the import of the export in `path`, or import it with `apply`.
+fn import(source: &str, path: &str, apply: bool) -> Result<(), String> {
+    let source = match source {
+        "selfcontrol" => ImportSource::SelfControl,
+        "cold-turkey" => ImportSource::ColdTurkey,
+        "pihole" => ImportSource::PiHole,
+        other => return Err(format!("unknown source {} (selfcontrol, cold-turkey, pihole)", other)),
+    };
+    let export = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let data = base64::engine::general_purpose::STANDARD.encode(export);
+
+    match send(&Command::ImportExternal { source, data, dry_run: !apply })? {
+        Response::ImportPreview(preview) => {
+            print!("{}", render_import(&preview));
+            Ok(())
+        }
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+fn render_import(preview: &ImportPreview) -> String {
+    let mut lines = Vec::new();
+    for group in &preview.groups {
+        lines.push(format!("group {}: {} sites", group.name, group.domains.len()));
+    }
+    for list in &preview.subscriptions {
+        lines.push(format!("subscription {}: {}", list.name, list.url));
+    }
+    for entry in &preview.skipped {
+        lines.push(format!("skipped {}", entry));
+    }
+    if preview.groups.is_empty() && preview.subscriptions.is_empty() {
+        lines.push("Nothing to import".to_string());
+    } else if preview.applied {
+        lines.push("Imported".to_string());
+    } else {
+        lines.push("Dry run; add --apply to import".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Print the schedule generated from template `name` with `params`
+/// (`start=08:30`, `days=mon,wed`...) as a config section.
+fn template(name: &str, params: &[String]) -> Result<(), String> {
+    let mut template = serde_json::Map::new();
+    template.insert("template".to_string(), name.replace('-', "_").into());
+    for param in params {
+        let (key, value) = param
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {}", param))?;
+        let value = match key {
+            "days" => value.split(',').map(|day| day.trim().to_lowercase()).collect(),
+            _ => serde_json::Value::from(value),
+        };
+        template.insert(key.replace('-', "_"), value);
+    }
+    let template = serde_json::from_value(template.into()).map_err(|e| e.to_string())?;
+
+    match send(&Command::GenerateSchedule { template })? {
+        Response::Schedule(schedule) => {
+            #[derive(Serialize)]
+            struct Section {
+                schedule: Schedule,
+            }
+            let section = toml::to_string(&Section { schedule }).map_err(|e| e.to_string())?;
+            print!("{}", section);
+            Ok(())
+        }
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+/// Run `bafctl config validate|edit|set`, on the daemon's config file unless
+/// a path is given.
+fn config(args: &[String]) -> Result<(), String> {
+    let daemon_config = if std::env::var("BLOCKANDFOCUS_DEV").is_ok() {
+        CONFIG_PATH_DEV
+    } else {
+        CONFIG_PATH
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["validate"] => validate_config(daemon_config),
+        ["validate", path] => validate_config(path),
+        ["edit"] => edit_config(daemon_config),
+        ["edit", path] => edit_config(path),
+        ["set", key, value] => set_config(daemon_config, key, value),
+        ["set", key, value, path] => set_config(path, key, value),
+        _ => Err("usage: bafctl config validate [PATH] | edit [PATH] | set KEY VALUE [PATH]"
+            .to_string()),
+    }
+}
+
+/// Parse config file contents the way the daemon does and check them,
+/// returning the problems found (a parse error is one problem).
+fn check_config_text(content: &str) -> Vec<String> {
+    match parse_config(content) {
+        Ok(config) => check_config(&config),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+fn report_problems(path: &str, problems: &[String]) {
+    for problem in problems {
+        eprintln!("{}: {}", path, problem.trim_end());
+    }
+}
+
+/// Check the config file at `path` without loading it into the daemon.
+fn validate_config(path: &str) -> Result<(), String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let problems = check_config_text(&content);
+    if problems.is_empty() {
+        println!("{}: OK", path);
+        Ok(())
+    } else {
+        report_problems(path, &problems);
+        Err(format!("{}: {} problems", path, problems.len()))
+    }
+}
+
+/// Replace the config file at `path`, keeping its permissions.
+fn save_config(path: &str, content: &str) -> Result<(), String> {
+    let fail = |e: std::io::Error| format!("{}: {}", path, e);
+    let permissions = std::fs::metadata(path).map_err(fail)?.permissions();
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, content).map_err(fail)?;
+    std::fs::set_permissions(&temp, permissions).map_err(fail)?;
+    std::fs::rename(&temp, path).map_err(fail)
+}
+
+/// Edit the config file at `path` with `$EDITOR`, only saving it once it
+/// passes the checks.
+fn edit_config(path: &str) -> Result<(), String> {
+    let original = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let draft = std::env::temp_dir()
+        .join(format!("blockandfocus-config-{}.toml", std::process::id()));
+    std::fs::write(&draft, &original).map_err(|e| format!("{}: {}", draft.display(), e))?;
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+
+    let edited = loop {
+        // Through the shell, so that EDITOR can carry arguments ("code --wait")
+        let edit = std::process::Command::new("sh")
+            .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+            .arg(&draft)
+            .status();
+        let edited = match edit {
+            Ok(status) if status.success() => {
+                std::fs::read_to_string(&draft).map_err(|e| e.to_string())
+            }
+            Ok(status) => Err(format!("{} exited with {}", editor, status)),
+            Err(e) => Err(format!("can't run {}: {}", editor, e)),
+        };
+        let edited = match edited {
+            Ok(edited) => edited,
+            Err(e) => {
+                let _ = std::fs::remove_file(&draft);
+                return Err(e);
+            }
+        };
+
+        let problems = check_config_text(&edited);
+        if problems.is_empty() {
+            break edited;
+        }
+        report_problems(path, &problems);
+        eprint!("Edit again? [Y/n] ");
+        let mut answer = String::new();
+        let _ = std::io::stdin().read_line(&mut answer);
+        if answer.trim().to_lowercase().starts_with('n') {
+            let _ = std::fs::remove_file(&draft);
+            return Err(format!("{} not saved", path));
+        }
+    };
+    let _ = std::fs::remove_file(&draft);
+
+    if edited == original {
+        println!("No changes");
+        return Ok(());
+    }
+    save_config(path, &edited)?;
+    println!("Saved {}; restart the daemon for it to take effect.", path);
+    Ok(())
+}
+
+/// Set the value at the dotted `key` (`dns.upstream`, `schedule.rules.0.name`)
+/// in config file contents, keeping the rest of the file and its comments.
+/// `value` is read as TOML (`true`, `5`, `["a", "b"]`), or else as a string.
+fn set_config_value(content: &str, key: &str, value: &str) -> Result<String, String> {
+    let mut document: toml_edit::Document = content.parse().map_err(|e| format!("{}", e))?;
+    let parts: Vec<&str> = key.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("invalid key {}", key));
+    }
+
+    let mut item = document.as_item_mut();
+    for (i, part) in parts.iter().enumerate() {
+        // Missing sections become tables rather than inline tables
+        if item.is_none() {
+            *item = toml_edit::table();
+        }
+        let next = match part.parse::<usize>() {
+            Ok(index) => item.get_mut(index),
+            Err(_) => item.get_mut(*part),
+        };
+        item = next.ok_or_else(|| format!("{} is not a table or list", parts[..i].join(".")))?;
+    }
+
+    let mut value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| toml_edit::Value::from(value));
+    if let Some(old) = item.as_value() {
+        *value.decor_mut() = old.decor().clone();
+    }
+    *item = toml_edit::Item::Value(value);
+    Ok(document.to_string())
+}
+
+/// Set `key` to `value` in the config file at `path`, if the result passes
+/// the checks.
+fn set_config(path: &str, key: &str, value: &str) -> Result<(), String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let updated = set_config_value(&content, key, value)?;
+    let problems = check_config_text(&updated);
+    if !problems.is_empty() {
+        report_problems(path, &problems);
+        return Err(format!("{} not saved", path));
+    }
+    save_config(path, &updated)?;
+    println!("Set {}; restart the daemon for it to take effect.", key);
+    Ok(())
+}
+
+/// Render the SwiftBar/xbar plugin output: the menu bar line, then the menu.
+fn render_statusbar(status: Result<&Status, &str>, bafctl: &str, now: i64) -> String {
+    let status = match status {
+        Ok(status) => status,
+        Err(e) => {
+            return format!("⚠️\n---\nBlockAndFocus daemon isn't running\n{}\n", e);
+        }
+    };
+
+    let mut lines = Vec::new();
+    let bypassing = status.bypass_until.is_some_and(|until| until > now);
+    lines.push(if bypassing {
+        "⏸️".to_string()
+    } else if status.blocking_active {
+        match &status.active_schedule_rule {
+            Some(rule) => format!("🛡️ {}", rule),
+            None => "🛡️".to_string(),
+        }
+    } else {
+        "🟢".to_string()
+    });
+
+    lines.push("---".to_string());
+    lines.push(if status.blocking_active { "Blocking" } else { "Not blocking" }.to_string());
+    if let Some(until) = status.bypass_until.filter(|_| bypassing) {
+        lines.push(format!("Bypass until {}", local_time(until)));
+    }
+    if let Some(until) = status.schedule_snoozed_until.filter(|&until| until > now) {
+        lines.push(format!("Schedule snoozed until {}", local_time(until)));
+    }
+    lines.push(format!(
+        "{} domains, {} queries blocked",
+        status.blocked_domains_count, status.queries_blocked
+    ));
+
+    lines.push("---".to_string());
+    for minutes in FOCUS_MINUTES {
+        lines.push(action(
+            &format!("Focus for {} minutes", minutes),
+            bafctl,
+            &["focus", &format!("{}m", minutes)],
+        ));
+    }
+    if status.schedule_enabled {
+        lines.push(action("Snooze schedule for 15 minutes", bafctl, &["snooze", "15m"]));
+    }
+    lines.push("---".to_string());
+    lines.push("Refresh | refresh=true".to_string());
+
+    lines.join("\n") + "\n"
+}
+
+/// A menu item that runs `bafctl` with `args` in the background, then refreshes.
+fn action(title: &str, bafctl: &str, args: &[&str]) -> String {
+    let mut line = format!("{} | bash=\"{}\"", title, bafctl);
+    for (i, arg) in args.iter().enumerate() {
+        line.push_str(&format!(" param{}={}", i + 1, arg));
+    }
+    line + " terminal=false refresh=true"
+}
+
+fn local_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.with_timezone(&chrono::Local).format("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let done = match args.first().map(String::as_str) {
+        Some("backup") => Some(backup(args.get(1).map(String::as_str))),
+        Some("restore") => Some(match args.get(1) {
+            Some(path) => restore(path).map(|()| println!("Restored {}", path)),
+            None => Err("usage: bafctl restore FILE".to_string()),
+        }),
+        Some("import") => Some(match (args.get(1), args.get(2)) {
+            (Some(source), Some(path)) => {
+                import(source, path, args[3..].iter().any(|arg| arg == "--apply"))
+            }
+            _ => Err("usage: bafctl import SOURCE FILE [--apply]".to_string()),
+        }),
+        // Plain `status` is the quick command; options need the JSON protocol
+        Some("status") if args.len() > 1 => Some(
+            parse_watch_options(&args[1..])
+                .and_then(|options| watch(&options, get_status, render_status)),
+        ),
+        Some("stats") => Some(
+            parse_watch_options(&args[1..])
+                .and_then(|options| watch(&options, get_stats, render_stats)),
+        ),
+        Some("config") => Some(config(&args[1..])),
+        Some("template") => Some(match args.get(1) {
+            Some(name) => template(name, &args[2..]),
+            None => Err("usage: bafctl template workday|student-evenings|night-owl \
+                         [key=value...]"
+                .to_string()),
+        }),
+        _ => None,
+    };
+    if let Some(result) = done {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("statusbar") {
+        let status = get_status();
+        let bafctl = std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "bafctl".to_string());
+        let now = chrono::Utc::now().timestamp();
+        print!("{}", render_statusbar(status.as_ref().map_err(String::as_str), &bafctl, now));
+        return;
+    }
+
+    let line = if args.is_empty() { "help".to_string() } else { args.join(" ") };
+    match quick(&line) {
+        Ok(reply) => {
+            print!("{}", reply);
+            if reply.starts_with("error: ") {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("Can't reach the daemon at {}: {}", socket_path(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_statusbar() {
+        let mut status = Status {
+            blocking_active: true,
+            blocked_domains_count: 12,
+            queries_blocked: 3,
+            active_schedule_rule: Some("Work".to_string()),
+            schedule_enabled: true,
+            ..Default::default()
+        };
+        let output = render_statusbar(Ok(&status), "/usr/local/bin/bafctl", 1000);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "🛡️ Work");
+        assert_eq!(lines[1], "---");
+        assert!(lines.contains(&"12 domains, 3 queries blocked"));
+        assert!(lines.contains(
+            &"Focus for 25 minutes | bash=\"/usr/local/bin/bafctl\" param1=focus param2=25m \
+              terminal=false refresh=true"
+        ));
+        assert!(output.contains("param1=snooze"));
+
+        status.bypass_until = Some(2000);
+        status.schedule_enabled = false;
+        let output = render_statusbar(Ok(&status), "bafctl", 1000);
+        assert!(output.starts_with("⏸️\n"));
+        assert!(output.contains("Bypass until "));
+        assert!(!output.contains("snooze"));
+
+        status.blocking_active = false;
+        status.bypass_until = None;
+        assert!(render_statusbar(Ok(&status), "bafctl", 1000).starts_with("🟢\n"));
+
+        let output = render_statusbar(Err("connection refused"), "bafctl", 1000);
+        assert!(output.starts_with("⚠️\n---\n"));
+    }
+
+    #[test]
+    fn test_watch_options_and_stats_line() {
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            parse_watch_options(&args("--watch --interval 2m --json-lines")),
+            Ok(WatchOptions {
+                watch: true,
+                interval: Duration::from_secs(120),
+                json_lines: true,
+            })
+        );
+        assert_eq!(
+            parse_watch_options(&[]).map(|options| (options.watch, options.interval)),
+            Ok((false, DEFAULT_WATCH_INTERVAL))
+        );
+        assert!(parse_watch_options(&args("--interval 0")).is_err());
+        assert!(parse_watch_options(&args("--interval")).is_err());
+        assert!(parse_watch_options(&args("--follow")).is_err());
+
+        let mut stats = UsageStats {
+            date: "2026-10-15".to_string(),
+            queries_blocked: 4,
+            queries_forwarded: 120,
+            domains: Vec::new(),
+            blocklist_entries: 0,
+            blocker_memory_bytes: 0,
+        };
+        assert_eq!(render_stats(&stats), "4 queries blocked, 120 forwarded");
+        stats.domains = ["youtube.com", "github.com", "news.test", "extra.test"]
+            .iter()
+            .map(|domain| blockandfocus_shared::DomainUsage {
+                domain: domain.to_string(),
+                minutes: 10,
+                sessions: 1,
+                queries: 5,
+            })
+            .collect();
+        assert_eq!(
+            render_stats(&stats),
+            "4 queries blocked, 120 forwarded; today: youtube.com 10m, github.com 10m, news.test 10m"
+        );
+        let json = render_update(&stats, render_stats, true);
+        assert!(json.starts_with('{') && !json.contains('\n'));
+    }
+
+    #[test]
+    fn test_set_config_value() {
+        let content = "# DNS settings\n[dns]\nlisten_port = 53 # standard port\n\n\
+                       [[schedule.rules]]\nname = \"Work\"\n";
+
+        let updated = set_config_value(content, "dns.listen_port", "5353").unwrap();
+        assert!(updated.starts_with("# DNS settings\n[dns]\nlisten_port = 5353 # standard port\n"));
+
+        // Strings need no quotes, lists index by position, missing tables are created
+        let updated = set_config_value(&updated, "schedule.rules.0.name", "Deep work").unwrap();
+        assert!(updated.contains("name = \"Deep work\""));
+        let updated = set_config_value(&updated, "query_log.enabled", "true").unwrap();
+        assert!(updated.contains("[query_log]\nenabled = true\n"));
+
+        assert!(set_config_value(content, "dns.listen_port.port", "1").is_err());
+        assert!(set_config_value(content, "schedule.rules.3.name", "x").is_err());
+        assert!(set_config_value(content, "dns..port", "1").is_err());
+
+        // Checked like the daemon: parse errors and problems both count
+        let config = toml::to_string(&blockandfocus_shared::Config::default()).unwrap();
+        assert!(check_config_text(&config).is_empty());
+        assert_eq!(check_config_text("[dns]\nlisten_port = 53\n").len(), 1);
+        let updated = set_config_value(&config, "dns.upstream", "[]").unwrap();
+        assert_eq!(
+            check_config_text(&updated),
+            vec!["dns.upstream lists no servers, so nothing can be resolved"]
+        );
+    }
+}
@@ -0,0 +1,10 @@
+//! Local page shown by browsers for blocked sites.
+//!
+//! With the `redirect` block mode pointing at the page's address, a blocked
+//! site opens this page instead of failing to load. It names the active rule
+//! and the blocklist entry, and links to requesting a bypass in the app.
+
+mod page;
+mod server;
+
+pub use server::BlockPage;
@@ -0,0 +1,159 @@
+//! Parsing requests and rendering the block page.
+
+use crate::i18n::{tr, Msg};
+use blockandfocus_shared::Locale;
+
+/// Path the page's bypass form posts to.
+pub const BYPASS_PATH: &str = "/bypass";
+
+/// The parts of an HTTP request the page needs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Host header without the port, lowercased
+    pub host: Option<String>,
+}
+
+/// Parse an HTTP request head (up to and including the blank line).
+pub fn parse_request(head: &str) -> Option<Request> {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    if !request_line.next()?.starts_with("HTTP/") {
+        return None;
+    }
+
+    let host = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+        .map(|(_, value)| strip_port(value.trim()).to_ascii_lowercase())
+        .filter(|host| !host.is_empty());
+
+    Some(Request { method, path, host })
+}
+
+/// Host without a trailing `:port` (IPv6 literals keep their brackets).
+fn strip_port(host: &str) -> &str {
+    if let Some(end) = host.find(']') {
+        return &host[..=end];
+    }
+    match host.split_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// What the page knows about why a site is blocked.
+pub struct Blocked<'a> {
+    /// Site the browser asked for
+    pub domain: &'a str,
+    /// Blocklist entry blocking it, None if it isn't blocked anymore
+    pub entry: Option<&'a str>,
+    /// Schedule rule in effect, None outside rules (always-on blocking)
+    pub rule: Option<&'a str>,
+}
+
+/// Render the page explaining the block, with a form to request a bypass.
+pub fn render_blocked(locale: Locale, blocked: &Blocked) -> String {
+    let domain = escape(blocked.domain);
+    let mut body = match blocked.entry {
+        Some(entry) => {
+            let entry = escape(entry);
+            paragraph(&tr(locale, Msg::BlockPageEntry, &[&domain, &entry]))
+        }
+        None => paragraph(&tr(locale, Msg::BlockPageUnblocked, &[&domain])),
+    };
+    body.push_str(&paragraph(&match blocked.rule {
+        Some(rule) => tr(locale, Msg::BlockPageRule, &[&escape(rule)]),
+        None => tr(locale, Msg::BlockPageAlwaysOn, &[]),
+    }));
+    if blocked.entry.is_some() {
+        body.push_str(&format!(
+            "<form method=\"post\" action=\"{}\"><button type=\"submit\">{}</button></form>\n",
+            BYPASS_PATH,
+            escape(&tr(locale, Msg::BlockPageRequestBypass, &[])),
+        ));
+    }
+
+    document(locale, &body)
+}
+
+/// Render the page shown once the app was asked to come to the front.
+pub fn render_bypass_requested(locale: Locale) -> String {
+    document(locale, &paragraph(&tr(locale, Msg::BlockPageBypassRequested, &[])))
+}
+
+fn paragraph(text: &str) -> String {
+    format!("<p>{}</p>\n", text)
+}
+
+fn document(locale: Locale, body: &str) -> String {
+    let title = escape(&tr(locale, Msg::BlockPageTitle, &[]));
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n\
+         <style>body {{ font-family: system-ui, sans-serif; max-width: 36em; \
+         margin: 4em auto; padding: 0 1em; line-height: 1.5; }}</style>\n\
+         </head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n"
+    )
+}
+
+/// Escape text for HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = parse_request("GET /watch?v=1 HTTP/1.1\r\nHOST: YouTube.com:8080\r\n\r\n");
+        assert_eq!(
+            request,
+            Some(Request {
+                method: "GET".into(),
+                path: "/watch?v=1".into(),
+                host: Some("youtube.com".into()),
+            })
+        );
+
+        let request = parse_request("POST /bypass HTTP/1.0\r\nAccept: */*\r\n\r\n").unwrap();
+        assert_eq!(request.host, None);
+        assert_eq!(parse_request("GET /\r\n\r\n"), None);
+        assert_eq!(strip_port("[::1]:80"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+    }
+
+    #[test]
+    fn test_render_escapes() {
+        let blocked = Blocked {
+            domain: "<script>.example",
+            entry: Some("example"),
+            rule: Some("Deep \"work\""),
+        };
+        let page = render_blocked(Locale::En, &blocked);
+        assert!(page.contains("&lt;script&gt;.example"));
+        assert!(page.contains("Deep &quot;work&quot;"));
+        assert!(!page.contains("<script>"));
+        assert!(page.contains(BYPASS_PATH));
+
+        let unblocked = Blocked { domain: "example.com", entry: None, rule: None };
+        assert!(!render_blocked(Locale::En, &unblocked).contains(BYPASS_PATH));
+    }
+}
@@ -0,0 +1,225 @@
+//! HTTP listener serving the block page.
+
+use super::page::{parse_request, render_blocked, render_bypass_requested, Blocked, BYPASS_PATH};
+use crate::AppState;
+use anyhow::{Context, Result};
+use blockandfocus_shared::{BypassPrompt, Event};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{debug, info, warn};
+
+/// Largest request head read before giving up on a connection.
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+/// How long a browser may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections served at once.
+const MAX_CONNECTIONS: usize = 32;
+
+/// Shortest time between bringing the app to the front, so a page
+/// submitted over and over doesn't keep stealing focus.
+const PROMPT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Serves the block page.
+pub struct BlockPage;
+
+impl BlockPage {
+    /// Serve the page if enabled. Changes to `[block_page]` take effect when
+    /// the daemon restarts.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let config = state.read().await.config.get().block_page.clone();
+        if !config.enabled {
+            return;
+        }
+
+        let addr = SocketAddr::new(config.address, config.http_port);
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Failed to bind block page on {}: {}", addr, e);
+                return;
+            }
+        };
+        let https = match config.https_port {
+            Some(port) => {
+                let addr = SocketAddr::new(config.address, port);
+                match TcpListener::bind(addr).await {
+                    Ok(listener) => Some(listener),
+                    Err(e) => {
+                        warn!("Failed to bind block page HTTPS port on {}: {}", addr, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        info!("Block page listening on {}", addr);
+        Self::serve(listener, https, state).await;
+    }
+
+    /// Serve the page on `listener`, closing connections to `https` at once.
+    pub async fn serve(
+        listener: TcpListener,
+        https: Option<TcpListener>,
+        state: Arc<RwLock<AppState>>,
+    ) {
+        if let Some(https) = https {
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = https.accept().await {
+                    drop(stream);
+                }
+            });
+        }
+
+        let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+        let last_prompt = Arc::new(Mutex::new(None));
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Block page accept failed: {}", e);
+                    continue;
+                }
+            };
+            let Ok(permit) = connections.clone().try_acquire_owned() else {
+                continue;
+            };
+
+            let state = state.clone();
+            let last_prompt = last_prompt.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle(stream, peer.ip(), &state, &last_prompt).await {
+                    debug!("Block page connection from {} failed: {:#}", peer, e);
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Answer one request and close the connection.
+async fn handle(
+    mut stream: TcpStream,
+    peer: IpAddr,
+    state: &RwLock<AppState>,
+    last_prompt: &Mutex<Option<Instant>>,
+) -> Result<()> {
+    let head = tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream))
+        .await
+        .context("Timed out reading request")??;
+    let Some(request) = parse_request(&head) else {
+        return respond(&mut stream, "400 Bad Request", "").await;
+    };
+
+    let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", BYPASS_PATH) => {
+            let state_guard = state.read().await;
+            let now = Instant::now();
+            let mut last = last_prompt.lock().unwrap();
+            if last.is_none_or(|at| now.duration_since(at) >= PROMPT_INTERVAL) {
+                *last = Some(now);
+                let domain = request.host.unwrap_or_default();
+                state_guard.emit(Event::BypassRequested(BypassPrompt { domain }));
+            }
+            ("200 OK", render_bypass_requested(state_guard.locale()))
+        }
+        ("GET" | "HEAD", _) => {
+            let state_guard = state.read().await;
+            let domain = request.host.unwrap_or_default();
+            let profile = state_guard.clients.profile_for(peer);
+            let entry = state_guard.blocked_entry_for(&domain, profile);
+            let rule = state_guard.schedule.active_rule_name();
+            let blocked =
+                Blocked { domain: &domain, entry: entry.as_deref(), rule: rule.as_deref() };
+            ("403 Forbidden", render_blocked(state_guard.locale(), &blocked))
+        }
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+
+    let body = if request.method == "HEAD" { "" } else { body.as_str() };
+    respond(&mut stream, status, body).await
+}
+
+/// Read up to the blank line ending the request head.
+async fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD_BYTES {
+            anyhow::bail!("Request head too large");
+        }
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            anyhow::bail!("Connection closed before the request head ended");
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDaemon;
+
+    async fn fetch(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_block_page() {
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+        })
+        .await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let https = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let https_addr = https.local_addr().unwrap();
+        let mut events = daemon.state.read().await.events.subscribe();
+        tokio::spawn(BlockPage::serve(listener, Some(https), daemon.state.clone()));
+
+        let page = fetch(addr, "GET / HTTP/1.1\r\nHost: www.distraction.test\r\n\r\n").await;
+        assert!(page.starts_with("HTTP/1.1 403"), "{}", page);
+        assert!(page.contains("www.distraction.test"));
+        assert!(page.contains(BYPASS_PATH));
+
+        let page = fetch(addr, "GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").await;
+        assert!(!page.contains(BYPASS_PATH));
+
+        // Asking twice in a row brings the app up once
+        let request = "POST /bypass HTTP/1.1\r\nHost: distraction.test\r\nContent-Length: 0\r\n\r\n";
+        assert!(fetch(addr, request).await.starts_with("HTTP/1.1 200"));
+        assert!(fetch(addr, request).await.starts_with("HTTP/1.1 200"));
+        let event = events.recv().await.unwrap();
+        assert!(matches!(event, Event::BypassRequested(p) if p.domain == "distraction.test"));
+        assert!(events.try_recv().is_err());
+
+        assert!(fetch(addr, "PUT / HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 405"));
+        assert!(fetch(https_addr, "").await.is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+//! Daily per-domain allowances.
+
+use crate::dns::DomainBlocker;
+use blockandfocus_shared::DomainAllowance;
+use std::collections::HashMap;
+
+/// Domains that resolve normally until their daily allowance is used up.
+pub struct DomainAllowances {
+    blocker: DomainBlocker,
+    /// Normalized domain -> allowed minutes per day
+    minutes: HashMap<String, u32>,
+}
+
+impl DomainAllowances {
+    /// Create matchers for the configured allowances.
+    pub fn new(allowances: &[DomainAllowance]) -> Self {
+        let minutes = allowances
+            .iter()
+            .map(|a| (a.domain.to_string(), a.daily_minutes))
+            .collect();
+        let domains: Vec<_> = allowances.iter().map(|a| a.domain.clone()).collect();
+        let blocker = DomainBlocker::new(&domains);
+
+        Self { blocker, minutes }
+    }
+
+    /// Find the allowance covering a domain: the matching entry and its daily minutes.
+    pub fn matching(&self, domain: &str) -> Option<(&str, u32)> {
+        let entry = self.blocker.matching_entry(domain)?;
+        let minutes = *self.minutes.get(entry)?;
+        Some((entry, minutes))
+    }
+
+    /// All allowances as (domain, daily minutes), sorted by domain.
+    pub fn entries(&self) -> Vec<(&str, u32)> {
+        let mut entries: Vec<(&str, u32)> =
+            self.minutes.iter().map(|(d, m)| (d.as_str(), *m)).collect();
+        entries.sort();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching_allowance() {
+        let allowances = DomainAllowances::new(&[
+            DomainAllowance {
+                domain: "YouTube.com".parse().unwrap(),
+                daily_minutes: 30,
+            },
+            DomainAllowance {
+                domain: "news.ycombinator.com".parse().unwrap(),
+                daily_minutes: 10,
+            },
+        ]);
+
+        assert_eq!(allowances.matching("m.youtube.com."), Some(("youtube.com", 30)));
+        assert_eq!(allowances.matching("ycombinator.com"), None);
+        assert_eq!(
+            allowances.entries(),
+            vec![("news.ycombinator.com", 10), ("youtube.com", 30)]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Blocklist groups.
+
+use crate::dns::DomainBlocker;
+use blockandfocus_shared::BlocklistGroup;
+
+/// A configured group with its domain matcher.
+pub struct Group {
+    pub name: String,
+    pub weekly_budget_minutes: Option<u32>,
+    enabled: bool,
+    blocker: DomainBlocker,
+}
+
+/// Blocklist groups with their domain matchers.
+pub struct GroupBudgets {
+    groups: Vec<Group>,
+}
+
+impl GroupBudgets {
+    /// Create matchers for the groups; disabled ones are only matched by name.
+    pub fn new(groups: &[BlocklistGroup]) -> Self {
+        let groups = groups
+            .iter()
+            .map(|g| Group {
+                name: g.name.clone(),
+                weekly_budget_minutes: g.weekly_budget_minutes,
+                enabled: g.enabled,
+                blocker: DomainBlocker::new(&g.domains),
+            })
+            .collect();
+
+        Self { groups }
+    }
+
+    /// Find the first enabled group containing a domain, with the matching entry.
+    pub fn matching_group(&self, domain: &str) -> Option<(&Group, &str)> {
+        self.find(domain, |g| g.enabled)
+    }
+
+    /// Find the first of the groups named in `names`, enabled or not,
+    /// containing a domain, with the matching entry.
+    pub fn matching_named(&self, domain: &str, names: &[String]) -> Option<(&Group, &str)> {
+        self.find(domain, |g| names.contains(&g.name))
+    }
+
+    fn find(&self, domain: &str, include: impl Fn(&Group) -> bool) -> Option<(&Group, &str)> {
+        self.groups
+            .iter()
+            .filter(|g| include(g))
+            .find_map(|g| g.blocker.matching_entry(domain).map(|entry| (g, entry)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> GroupBudgets {
+        GroupBudgets::new(&[
+            BlocklistGroup {
+                name: "Video".to_string(),
+                domains: vec!["youtube.com".parse().unwrap(), "netflix.com".parse().unwrap()],
+                weekly_budget_minutes: Some(180),
+                enabled: true,
+            },
+            BlocklistGroup {
+                name: "News".to_string(),
+                domains: vec!["news.ycombinator.com".parse().unwrap()],
+                weekly_budget_minutes: None,
+                enabled: true,
+            },
+            BlocklistGroup {
+                name: "Games".to_string(),
+                domains: vec!["twitch.tv".parse().unwrap()],
+                weekly_budget_minutes: None,
+                enabled: false,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_matching_group() {
+        let groups = groups();
+
+        let (group, entry) = groups.matching_group("www.youtube.com.").unwrap();
+        assert_eq!(group.name, "Video");
+        assert_eq!(group.weekly_budget_minutes, Some(180));
+        assert_eq!(entry, "youtube.com");
+
+        assert_eq!(groups.matching_group("news.ycombinator.com").unwrap().0.name, "News");
+        assert!(groups.matching_group("example.com").is_none());
+        assert!(groups.matching_group("twitch.tv").is_none());
+    }
+
+    #[test]
+    fn test_matching_named() {
+        let groups = groups();
+        let names = ["Games".to_string(), "News".to_string()];
+
+        assert_eq!(groups.matching_named("twitch.tv", &names).unwrap().1, "twitch.tv");
+        assert!(groups.matching_named("news.ycombinator.com", &names).is_some());
+        assert!(groups.matching_named("youtube.com", &names).is_none());
+    }
+}
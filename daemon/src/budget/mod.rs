@@ -0,0 +1,45 @@
+//! Usage budgets for blocklist groups and individual domains.
+
+mod allowance;
+mod groups;
+
+pub use allowance::DomainAllowances;
+pub use groups::GroupBudgets;
+
+/// Budget usage percentages that trigger a warning event.
+const WARNING_THRESHOLDS: [u8; 3] = [75, 90, 100];
+
+/// The highest warning threshold crossed when usage grows from `before` to `after` minutes.
+pub fn crossed_threshold(before: u32, after: u32, budget: u32) -> Option<u8> {
+    if budget == 0 {
+        return None;
+    }
+
+    let reached = |used: u32, percent: u8| used as u64 * 100 >= budget as u64 * percent as u64;
+
+    WARNING_THRESHOLDS
+        .iter()
+        .rev()
+        .find(|&&percent| reached(after, percent) && !reached(before, percent))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_threshold() {
+        // 30 minute allowance: 75% = 22.5, 90% = 27, 100% = 30
+        assert_eq!(crossed_threshold(20, 21, 30), None);
+        assert_eq!(crossed_threshold(22, 23, 30), Some(75));
+        assert_eq!(crossed_threshold(23, 24, 30), None);
+        assert_eq!(crossed_threshold(26, 27, 30), Some(90));
+        assert_eq!(crossed_threshold(29, 30, 30), Some(100));
+
+        // A jump over several thresholds reports the highest one
+        assert_eq!(crossed_threshold(10, 40, 30), Some(100));
+        assert_eq!(crossed_threshold(30, 31, 30), None);
+        assert_eq!(crossed_threshold(0, 5, 0), None);
+    }
+}
@@ -0,0 +1,7 @@
+//! Per-client policies for devices using the daemon as their DNS server.
+
+mod neighbors;
+mod policies;
+
+pub use neighbors::NeighborMonitor;
+pub use policies::ClientPolicies;
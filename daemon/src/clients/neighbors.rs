@@ -0,0 +1,83 @@
+//! Learning the MAC addresses of devices on the network from the ARP table,
+//! so profiles can name devices whose IP address changes.
+
+use crate::AppState;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use blockandfocus_shared::normalize_mac;
+
+/// How often the ARP table is read.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keeps the client policies' view of the ARP table current.
+pub struct NeighborMonitor;
+
+impl NeighborMonitor {
+    /// Run the monitor loop. The ARP table is only read while a profile
+    /// names a device by MAC address.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            if !state.read().await.clients.uses_mac() {
+                continue;
+            }
+
+            let Some(neighbors) = read_arp_table().await else { continue };
+            debug!(neighbors = neighbors.len(), "Read ARP table");
+            let mut state_guard = state.write().await;
+            state_guard.clients.set_neighbors(neighbors);
+            state_guard.publish_policy();
+        }
+    }
+}
+
+async fn read_arp_table() -> Option<HashMap<IpAddr, String>> {
+    let output = tokio::process::Command::new("/usr/sbin/arp")
+        .arg("-an")
+        .output()
+        .await
+        .ok()?;
+
+    Some(parse_arp_table(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `arp -an` lines like
+/// `? (192.168.1.23) at a4:83:e7:2:3:4f on en0 ifscope [ethernet]`.
+fn parse_arp_table(output: &str) -> HashMap<IpAddr, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once('(')?;
+            let (ip, rest) = rest.split_once(") at ")?;
+            let mac = normalize_mac(rest.split_whitespace().next()?)?;
+            Some((ip.parse().ok()?, mac))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arp_table() {
+        let output = "\
+? (192.168.1.1) at 0:11:22:33:44:55 on en0 ifscope [ethernet]
+? (192.168.1.23) at a4:83:e7:2:3:4f on en0 ifscope [ethernet]
+? (192.168.1.99) at (incomplete) on en0 ifscope [ethernet]
+? (224.0.0.251) at 1:0:5e:0:0:fb on en0 ifscope permanent [ethernet]
+";
+        let neighbors = parse_arp_table(output);
+        assert_eq!(neighbors.len(), 3);
+        let ip: IpAddr = "192.168.1.23".parse().unwrap();
+        assert_eq!(neighbors[&ip], "a4:83:e7:02:03:4f");
+        assert!(!neighbors.contains_key(&"192.168.1.99".parse::<IpAddr>().unwrap()));
+    }
+}
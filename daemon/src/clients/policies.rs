@@ -0,0 +1,107 @@
+//! Matching query sources to client profiles.
+
+use blockandfocus_shared::{parse_client, ClientMatch, ClientProfile};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// A profile with its parsed `clients`.
+#[derive(Clone)]
+struct Policy {
+    profile: ClientProfile,
+    matches: Vec<ClientMatch>,
+}
+
+/// The configured client profiles, matched against query sources.
+#[derive(Default, Clone)]
+pub struct ClientPolicies {
+    policies: Vec<Policy>,
+    /// MAC address of each neighbour, from the ARP table
+    neighbors: HashMap<IpAddr, String>,
+}
+
+impl ClientPolicies {
+    /// Parse the profiles' `clients`; invalid entries are left out (and
+    /// reported by `--check-config`).
+    pub fn new(profiles: &[ClientProfile]) -> Self {
+        let policies = profiles
+            .iter()
+            .map(|profile| Policy {
+                profile: profile.clone(),
+                matches: profile.clients.iter().filter_map(|c| parse_client(c).ok()).collect(),
+            })
+            .collect();
+
+        Self { policies, neighbors: HashMap::new() }
+    }
+
+    /// Keep the neighbours learned so far when the profiles are replaced.
+    pub fn replace(&mut self, profiles: &[ClientProfile]) {
+        let neighbors = std::mem::take(&mut self.neighbors);
+        *self = Self::new(profiles);
+        self.neighbors = neighbors;
+    }
+
+    /// The profile of the first policy matching `ip`, if any.
+    pub fn profile_for(&self, ip: IpAddr) -> Option<&ClientProfile> {
+        let ip = ip.to_canonical();
+        let mac = self.neighbors.get(&ip).map(String::as_str);
+        self.policies
+            .iter()
+            .find(|policy| policy.matches.iter().any(|m| m.matches(ip, mac)))
+            .map(|policy| &policy.profile)
+    }
+
+    /// Whether any profile names a device by MAC address.
+    pub fn uses_mac(&self) -> bool {
+        let is_mac = |m: &ClientMatch| matches!(m, ClientMatch::Mac(_));
+        self.policies.iter().any(|policy| policy.matches.iter().any(is_mac))
+    }
+
+    pub fn set_neighbors(&mut self, neighbors: HashMap<IpAddr, String>) {
+        self.neighbors = neighbors;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::ClientBlocking;
+
+    fn profile(name: &str, clients: &[&str]) -> ClientProfile {
+        ClientProfile {
+            name: name.to_string(),
+            clients: clients.iter().map(|c| c.to_string()).collect(),
+            blocking: ClientBlocking::Always,
+            allow_bypass: false,
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_profile_for() {
+        let mut policies = ClientPolicies::new(&[
+            profile("Tablet", &["a4:83:e7:02:03:4f"]),
+            profile("Kids", &["192.168.1.16/28", "fd00::20"]),
+            profile("Laptop", &["192.168.1.30", "not a client"]),
+        ]);
+        let name = |policies: &ClientPolicies, ip: &str| {
+            policies.profile_for(ip.parse().unwrap()).map(|p| p.name.clone())
+        };
+
+        assert_eq!(name(&policies, "192.168.1.17").as_deref(), Some("Kids"));
+        assert_eq!(name(&policies, "::ffff:192.168.1.31").as_deref(), Some("Kids"));
+        assert_eq!(name(&policies, "fd00::20").as_deref(), Some("Kids"));
+        assert_eq!(name(&policies, "192.168.1.32"), None);
+        assert_eq!(name(&policies, "127.0.0.1"), None);
+
+        assert!(policies.uses_mac());
+        let ip: IpAddr = "192.168.1.40".parse().unwrap();
+        policies.set_neighbors(HashMap::from([(ip, "a4:83:e7:02:03:4f".to_string())]));
+        assert_eq!(name(&policies, "192.168.1.40").as_deref(), Some("Tablet"));
+
+        // Neighbours outlive a profile edit
+        policies.replace(&[profile("Tablet", &["a4:83:e7:02:03:4f"])]);
+        assert_eq!(name(&policies, "192.168.1.40").as_deref(), Some("Tablet"));
+        assert_eq!(name(&policies, "192.168.1.17"), None);
+    }
+}
@@ -0,0 +1,23 @@
+//! A monotonic clock that keeps counting while the machine sleeps.
+//!
+//! `std::time::Instant` stops during sleep on macOS and Linux, and the wall
+//! clock can be changed by the user; comparing the two tells sleep and clock
+//! changes apart only with a clock that is neither.
+
+#[cfg(target_os = "macos")]
+const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+
+#[cfg(not(target_os = "macos"))]
+const CLOCK: libc::clockid_t = libc::CLOCK_BOOTTIME;
+
+/// Seconds since an arbitrary point, including time spent asleep.
+pub fn boot_seconds() -> i64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid timespec for clock_gettime to write to
+    let result = unsafe { libc::clock_gettime(CLOCK, &mut ts) };
+    debug_assert_eq!(result, 0, "clock_gettime failed");
+    ts.tv_sec
+}
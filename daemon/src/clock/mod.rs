@@ -0,0 +1,7 @@
+//! System clock and time zone change detection.
+
+mod boottime;
+mod monitor;
+
+pub use boottime::boot_seconds;
+pub use monitor::ClockMonitor;
@@ -0,0 +1,137 @@
+//! Detects the system clock being set and the time zone changing.
+//!
+//! The wall clock is sampled alongside a monotonic clock that keeps counting
+//! during sleep. When the two drift apart, the wall clock was set, and timers
+//! measured against it are shifted so the change neither extends nor ends them.
+//! A change of the local UTC offset (time zone or daylight saving time) moves
+//! schedule rules instead. Either way subscribers get a `ClockChanged` event
+//! with the re-evaluated blocking state.
+
+use crate::AppState;
+use blockandfocus_shared::{ClockChange, Event};
+use chrono::Offset;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use super::boot_seconds;
+
+/// How often the clocks are compared.
+const CHECK_INTERVAL_SECONDS: u64 = 10;
+
+/// Drift between the wall clock and the monotonic clock that counts as a change.
+const JUMP_THRESHOLD_SECONDS: i64 = 60;
+
+/// Keeps bypasses, focus sessions and locks on real elapsed time.
+pub struct ClockMonitor;
+
+impl ClockMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECONDS));
+        let mut last_sample = Sample::now();
+
+        loop {
+            interval.tick().await;
+
+            let sample = Sample::now();
+            let jump_seconds = clock_jump(&last_sample, &sample);
+            let previous_utc_offset_seconds = last_sample.utc_offset;
+            last_sample = sample;
+
+            let zone_changed = sample.utc_offset != previous_utc_offset_seconds;
+            if jump_seconds == 0 && !zone_changed {
+                continue;
+            }
+
+            let mut state_guard = state.write().await;
+            if jump_seconds != 0 {
+                warn!(jump_seconds, "System clock changed");
+                state_guard.on_clock_jump(jump_seconds).await;
+            }
+            if zone_changed {
+                info!(
+                    previous_utc_offset_seconds,
+                    utc_offset_seconds = sample.utc_offset,
+                    "Time zone changed"
+                );
+            }
+
+            let blocking_active = state_guard.is_blocking_active();
+            info!(blocking_active, "Re-evaluated blocking after clock change");
+            state_guard.emit(Event::ClockChanged(ClockChange {
+                jump_seconds,
+                previous_utc_offset_seconds,
+                utc_offset_seconds: sample.utc_offset,
+                blocking_active,
+            }));
+        }
+    }
+}
+
+/// Clock readings taken together.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Wall clock, Unix seconds
+    wall: i64,
+    /// Monotonic clock including sleep, seconds
+    mono: i64,
+    /// Offset of local time from UTC, seconds
+    utc_offset: i32,
+}
+
+impl Sample {
+    fn now() -> Self {
+        let local = chrono::Local::now();
+        Self {
+            wall: local.timestamp(),
+            mono: boot_seconds(),
+            utc_offset: local.offset().fix().local_minus_utc(),
+        }
+    }
+}
+
+/// Seconds the wall clock moved beyond real elapsed time between two samples.
+///
+/// Returns 0 for drift within the threshold, such as NTP corrections.
+fn clock_jump(last: &Sample, now: &Sample) -> i64 {
+    let jump = (now.wall - last.wall) - (now.mono - last.mono);
+    if jump.abs() > JUMP_THRESHOLD_SECONDS {
+        jump
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(wall: i64, mono: i64) -> Sample {
+        Sample {
+            wall,
+            mono,
+            utc_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_clock_jump() {
+        let last = sample(1000, 50);
+
+        // Sleep advances both clocks alike
+        assert_eq!(clock_jump(&last, &sample(1000 + 3600, 50 + 3600)), 0);
+        assert_eq!(clock_jump(&last, &sample(1012, 60)), 0);
+
+        // Setting the clock back an hour or forward a day
+        assert_eq!(clock_jump(&last, &sample(1010 - 3600, 60)), -3600);
+        assert_eq!(clock_jump(&last, &sample(1010 + 86400, 60)), 86400);
+    }
+
+    #[test]
+    fn test_boot_seconds_is_monotonic() {
+        let first = boot_seconds();
+        assert!(boot_seconds() >= first);
+    }
+}
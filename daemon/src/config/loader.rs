@@ -1,33 +1,41 @@
 //! Configuration loading and management.
 
 use anyhow::{Context, Result};
-use blockandfocus_shared::{Config, CONFIG_PATH, CONFIG_PATH_DEV};
+use arc_swap::ArcSwap;
+use blockandfocus_shared::{parse_config, Config, DomainName, CONFIG_PATH, CONFIG_PATH_DEV};
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::Mutex;
 use tracing::{info, warn};
 
+use super::check_config;
+
 /// Configuration manager with hot-reload support.
+///
+/// The current configuration is published as an immutable snapshot, so
+/// readers (including the DNS path) never wait for a writer.
 pub struct ConfigManager {
-    config: Arc<RwLock<Config>>,
+    config: ArcSwap<Config>,
+    /// Serializes updates so concurrent edits are not lost
+    write_lock: Mutex<()>,
     path: String,
 }
 
 impl ConfigManager {
     /// Load configuration from file, or create default if not exists.
     pub fn load(is_dev: bool) -> Result<Self> {
-        let path = if is_dev {
-            CONFIG_PATH_DEV.to_string()
-        } else {
-            CONFIG_PATH.to_string()
-        };
+        let path = if is_dev { CONFIG_PATH_DEV } else { CONFIG_PATH };
+        Self::load_from(path.to_string())
+    }
 
+    /// Load configuration from `path`, creating it with defaults if it doesn't exist.
+    pub fn load_from(path: String) -> Result<Self> {
         let config = if Path::new(&path).exists() {
             info!("Loading config from {}", path);
             let content = fs::read_to_string(&path)
                 .with_context(|| format!("Failed to read config file: {}", path))?;
-            toml::from_str(&content)
+            parse_config(&content)
                 .with_context(|| format!("Failed to parse config file: {}", path))?
         } else {
             warn!("Config file not found at {}, using defaults", path);
@@ -43,34 +51,43 @@ impl ConfigManager {
             config
         };
 
+        for problem in check_config(&config) {
+            warn!("Config: {}", problem);
+        }
+
         Ok(Self {
-            config: Arc::new(RwLock::new(config)),
+            config: ArcSwap::from_pointee(config),
+            write_lock: Mutex::new(()),
             path,
         })
     }
 
-    /// Get the current configuration (read-only).
-    pub fn get(&self) -> Config {
-        // Use try_read to avoid blocking; fall back to default on contention
-        self.config
-            .try_read()
-            .map(|guard| guard.clone())
-            .unwrap_or_default()
+    /// Parse the config file at `path` and check it, without loading it.
+    pub fn check_file(path: &str) -> Result<Vec<String>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path))?;
+        let config = parse_config(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path))?;
+        Ok(check_config(&config))
     }
 
-    /// Get a reference to the config Arc for async access.
-    pub fn config_arc(&self) -> Arc<RwLock<Config>> {
-        self.config.clone()
+    /// Get a snapshot of the current configuration.
+    pub fn get(&self) -> Arc<Config> {
+        self.config.load_full()
     }
 
     /// Update and persist configuration.
+    ///
+    /// The new snapshot is only published once it has been saved.
     pub async fn update<F>(&self, updater: F) -> Result<()>
     where
         F: FnOnce(&mut Config),
     {
-        let mut config = self.config.write().await;
+        let _guard = self.write_lock.lock().await;
+        let mut config = Config::clone(&self.config.load());
         updater(&mut config);
         Self::save_config(&self.path, &config)?;
+        self.config.store(Arc::new(config));
         info!("Configuration updated and saved");
         Ok(())
     }
@@ -86,35 +103,42 @@ impl ConfigManager {
         let content = toml::to_string_pretty(config)
             .context("Failed to serialize config")?;
 
-        fs::write(path, content)
-            .with_context(|| format!("Failed to write config file: {}", path))?;
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, content)
+            .with_context(|| format!("Failed to write config file: {}", tmp))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace config file: {}", path))?;
 
         Ok(())
     }
 
     /// Get blocked domains list.
-    pub fn blocked_domains(&self) -> Vec<String> {
+    pub fn blocked_domains(&self) -> Vec<DomainName> {
         self.get().blocking.domains.clone()
     }
 
+    /// Whether a domain is on the blocklist.
+    pub fn contains_domain(&self, domain: &DomainName) -> bool {
+        self.get().blocking.domains.contains(domain)
+    }
+
     /// Add a domain to the blocklist.
-    pub async fn add_domain(&self, domain: String) -> Result<()> {
+    pub async fn add_domain(&self, domain: DomainName) -> Result<()> {
         self.update(|config| {
-            let normalized = normalize_domain(&domain);
-            if !config.blocking.domains.contains(&normalized) {
-                config.blocking.domains.push(normalized);
+            if !config.blocking.domains.contains(&domain) {
+                config.blocking.domains.push(domain.clone());
             }
         })
         .await
     }
 
     /// Remove a domain from the blocklist.
-    pub async fn remove_domain(&self, domain: &str) -> Result<bool> {
-        let normalized = normalize_domain(domain);
+    pub async fn remove_domain(&self, domain: &DomainName) -> Result<bool> {
         let mut removed = false;
 
         self.update(|config| {
-            if let Some(pos) = config.blocking.domains.iter().position(|d| d == &normalized) {
+            if let Some(pos) = config.blocking.domains.iter().position(|d| d == domain) {
                 config.blocking.domains.remove(pos);
                 removed = true;
             }
@@ -123,15 +147,32 @@ impl ConfigManager {
 
         Ok(removed)
     }
-}
 
-/// Normalize a domain name (lowercase, remove trailing dot).
-fn normalize_domain(domain: &str) -> String {
-    domain
-        .to_lowercase()
-        .trim()
-        .trim_end_matches('.')
-        .to_string()
+    /// Add a domain to the essential-services list.
+    pub async fn add_essential_domain(&self, domain: &DomainName) -> Result<()> {
+        self.update(|config| {
+            if !config.blocking.essential_domains.contains(domain) {
+                config.blocking.essential_domains.push(domain.clone());
+            }
+        })
+        .await
+    }
+
+    /// Remove a domain from the essential-services list.
+    pub async fn remove_essential_domain(&self, domain: &DomainName) -> Result<bool> {
+        let mut removed = false;
+
+        self.update(|config| {
+            let essential = &mut config.blocking.essential_domains;
+            if let Some(pos) = essential.iter().position(|d| d == domain) {
+                essential.remove(pos);
+                removed = true;
+            }
+        })
+        .await?;
+
+        Ok(removed)
+    }
 }
 
 #[cfg(test)]
@@ -139,9 +180,21 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_normalize_domain() {
-        assert_eq!(normalize_domain("Facebook.COM"), "facebook.com");
-        assert_eq!(normalize_domain("twitter.com."), "twitter.com");
-        assert_eq!(normalize_domain("  Reddit.com  "), "reddit.com");
+    fn test_upstream_entries_survive_saving() {
+        let mut config = Config::default();
+        config.dns.upstream = toml::from_str::<blockandfocus_shared::DnsConfig>(
+            r#"
+            upstream = ["1.1.1.1", { address = "dns.quad9.net", protocol = "tls", bootstrap = "9.9.9.9" }]
+            listen_address = "127.0.0.1"
+            listen_port = 53
+            "#,
+        )
+        .unwrap()
+        .upstream;
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.dns.upstream, config.dns.upstream);
+        assert_eq!(reloaded.dns.upstream[1].port(), 853);
     }
 }
@@ -2,4 +2,5 @@
 
 mod loader;
 
+pub use blockandfocus_shared::{check_client_profile, check_config};
 pub use loader::ConfigManager;
@@ -0,0 +1,195 @@
+//! Detection of apps bypassing the DNS filter with encrypted DNS.
+//!
+//! Browsers and some apps can resolve names over DNS-over-HTTPS (443) or
+//! DNS-over-TLS (853) directly, which never reaches the daemon. While
+//! blocking is active, established TCP connections are sampled and any to a
+//! well-known public resolver on those ports are reported.
+
+use crate::AppState;
+use blockandfocus_shared::{EncryptedDnsDetection, Event};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// How often connections are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Ports used by DNS-over-HTTPS and DNS-over-TLS.
+const ENCRYPTED_DNS_PORTS: [u16; 2] = [443, 853];
+
+/// Public resolvers offering DoH/DoT.
+const KNOWN_RESOLVERS: &[&str] = &[
+    // Cloudflare
+    "1.1.1.1",
+    "1.0.0.1",
+    "2606:4700:4700::1111",
+    "2606:4700:4700::1001",
+    // Google
+    "8.8.8.8",
+    "8.8.4.4",
+    "2001:4860:4860::8888",
+    "2001:4860:4860::8844",
+    // Quad9
+    "9.9.9.9",
+    "149.112.112.112",
+    "2620:fe::fe",
+    // OpenDNS
+    "208.67.222.222",
+    "208.67.220.220",
+    // AdGuard
+    "94.140.14.14",
+    "94.140.15.15",
+    // NextDNS
+    "45.90.28.0",
+    "45.90.30.0",
+];
+
+/// An established connection to a known resolver.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ResolverConnection {
+    local: String,
+    resolver: IpAddr,
+    port: u16,
+}
+
+/// Samples outbound connections for encrypted DNS use.
+pub struct EncryptedDnsMonitor;
+
+impl EncryptedDnsMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let resolvers: HashSet<IpAddr> = KNOWN_RESOLVERS.iter().filter_map(|ip| ip.parse().ok()).collect();
+        let mut seen: HashSet<ResolverConnection> = HashSet::new();
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let (enabled, blocking_active, upstream) = {
+                let state_guard = state.read().await;
+                let config = state_guard.config.get();
+                (
+                    config.dns.detect_encrypted_dns,
+                    state_guard.is_blocking_active(),
+                    config.dns.upstream.clone(),
+                )
+            };
+            if !enabled || !blocking_active {
+                seen.clear();
+                continue;
+            }
+
+            let Some(output) = sample_connections().await else {
+                continue;
+            };
+
+            // Our own upstreams may legitimately use encrypted transports
+            let own: HashSet<IpAddr> = upstream.iter().filter_map(|u| u.ip()).collect();
+
+            let current: HashSet<ResolverConnection> = parse_connections(&output)
+                .into_iter()
+                .filter(|c| resolvers.contains(&c.resolver) && !own.contains(&c.resolver))
+                .collect();
+
+            let new: Vec<&ResolverConnection> = current.difference(&seen).collect();
+            if !new.is_empty() {
+                let mut state_guard = state.write().await;
+                for connection in &new {
+                    warn!(
+                        resolver = %connection.resolver,
+                        port = connection.port,
+                        "Connection to an encrypted DNS resolver while blocking is active"
+                    );
+                    let detection = EncryptedDnsDetection {
+                        resolver: connection.resolver.to_string(),
+                        port: connection.port,
+                        detected_at: chrono::Utc::now().timestamp(),
+                    };
+                    state_guard.stats.encrypted_dns_connections += 1;
+                    state_guard.stats.last_encrypted_dns = Some(detection.clone());
+                    state_guard.emit(Event::EncryptedDnsDetected(detection));
+                }
+            }
+
+            seen = current;
+        }
+    }
+}
+
+/// Established TCP connections as printed by `netstat`.
+#[cfg(target_os = "macos")]
+async fn sample_connections() -> Option<String> {
+    let output = tokio::process::Command::new("/usr/sbin/netstat")
+        .args(["-an", "-p", "tcp"])
+        .output()
+        .await
+        .ok()?;
+
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Established TCP connections as printed by `netstat`.
+#[cfg(not(target_os = "macos"))]
+async fn sample_connections() -> Option<String> {
+    None
+}
+
+/// Parse established connections on encrypted DNS ports from macOS `netstat -an` output.
+///
+/// Addresses are printed as `address.port`, e.g. `1.1.1.1.443`.
+fn parse_connections(output: &str) -> Vec<ResolverConnection> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("tcp") && line.contains("ESTABLISHED"))
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let local = columns.get(3)?;
+            let (ip, port) = columns.get(4)?.rsplit_once('.')?;
+            let port: u16 = port.parse().ok()?;
+            if !ENCRYPTED_DNS_PORTS.contains(&port) {
+                return None;
+            }
+
+            Some(ResolverConnection {
+                local: local.to_string(),
+                resolver: ip.parse().ok()?,
+                port,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_connections() {
+        let output = "\
+Active Internet connections (including servers)
+Proto Recv-Q Send-Q  Local Address          Foreign Address        (state)
+tcp4       0      0  192.168.1.5.51234      1.1.1.1.443            ESTABLISHED
+tcp4       0      0  192.168.1.5.51235      8.8.8.8.853            ESTABLISHED
+tcp4       0      0  192.168.1.5.51236      140.82.112.3.443       ESTABLISHED
+tcp4       0      0  192.168.1.5.51237      9.9.9.9.443            TIME_WAIT
+tcp4       0      0  192.168.1.5.51238      1.1.1.1.80             ESTABLISHED
+tcp6       0      0  2001:db8::5.51239      2606:4700:4700::1111.443 ESTABLISHED
+";
+
+        let connections = parse_connections(output);
+        let resolvers: Vec<String> = connections.iter().map(|c| format!("{}:{}", c.resolver, c.port)).collect();
+        assert_eq!(
+            resolvers,
+            vec![
+                "1.1.1.1:443",
+                "8.8.8.8:853",
+                "140.82.112.3:443",
+                "2606:4700:4700::1111:443"
+            ]
+        );
+        assert_eq!(connections[0].local, "192.168.1.5.51234");
+    }
+}
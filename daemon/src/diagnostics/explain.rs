@@ -0,0 +1,97 @@
+//! "Why was this blocked?" explanations.
+
+use crate::{today, week_start, AppState};
+use blockandfocus_shared::{DomainExplanation, MatchSource};
+
+/// Explain whether a domain would be blocked right now, and why.
+///
+/// Mirrors the decision made by `AppState::blocked_entry` step by step.
+pub fn explain_domain(state: &AppState, domain: &str) -> DomainExplanation {
+    let now = chrono::Utc::now().timestamp();
+    let config = state.config.get();
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+
+    let mut explanation = DomainExplanation {
+        domain: domain.clone(),
+        blocked: state.blocked_entry(&domain).is_some() && !config.blocking.dry_run,
+        reason: String::new(),
+        matched_entry: None,
+        match_source: None,
+        exact_match: false,
+        group: None,
+        budget_minutes: None,
+        budget_used_minutes: None,
+        blocking_enabled: config.blocking.enabled,
+        schedule_enabled: config.schedule.enabled,
+        active_schedule_rule: state.schedule.active_rule_name(),
+        schedule_snoozed_until: state.schedule.snoozed_until(now),
+        bypass_active: state.is_bypass_active(now),
+        bypass_until: state.bypass.until,
+    };
+
+    let mut budget_left = false;
+    if let Some(entry) = state.essential.load().matching_entry(&domain) {
+        explanation.matched_entry = Some(entry.to_string());
+        explanation.match_source = Some(MatchSource::Essential);
+    } else if let Some(entry) = state.blocker.load().matching_entry(&domain) {
+        explanation.matched_entry = Some(entry.to_string());
+        explanation.match_source = Some(MatchSource::Blocklist);
+    } else if let Some((group, entry)) = state.groups.matching_group(&domain) {
+        explanation.matched_entry = Some(entry.to_string());
+        explanation.match_source = Some(MatchSource::Group);
+        explanation.group = Some(group.name.clone());
+        if let Some(budget) = group.weekly_budget_minutes {
+            let used = state.store.group_minutes_used(&group.name, &week_start());
+            explanation.budget_minutes = Some(budget);
+            explanation.budget_used_minutes = Some(used);
+            budget_left = used < budget;
+        }
+    } else if let Some((entry, daily_minutes)) = state.allowances.matching(&domain) {
+        let used = state.store.domain_minutes_used(entry, &today());
+        explanation.matched_entry = Some(entry.to_string());
+        explanation.match_source = Some(MatchSource::Allowance);
+        explanation.budget_minutes = Some(daily_minutes);
+        explanation.budget_used_minutes = Some(used);
+        budget_left = used < daily_minutes;
+    }
+    explanation.exact_match = explanation.matched_entry.as_deref() == Some(domain.as_str());
+
+    explanation.reason = match &explanation.matched_entry {
+        None => "Not on the blocklist, in a group or covered by an allowance".to_string(),
+        Some(entry) if explanation.match_source == Some(MatchSource::Essential) => {
+            format!("Never blocked: {} is an essential service", entry)
+        }
+        Some(entry) if explanation.blocked && !state.is_blocking_scheduled() => format!(
+            "Blocked by {} while a wind-down rule blocks group \"{}\"",
+            entry,
+            explanation.group.as_deref().unwrap_or_default()
+        ),
+        Some(_) if budget_left => format!(
+            "Allowed until its budget is used ({} of {} minutes)",
+            explanation.budget_used_minutes.unwrap_or(0),
+            explanation.budget_minutes.unwrap_or(0)
+        ),
+        Some(_) if !config.blocking.enabled => "Blocking is disabled".to_string(),
+        Some(_) if !state.is_blocking_scheduled() => {
+            if explanation.schedule_snoozed_until.is_some() {
+                "Matched, but the schedule is snoozed".to_string()
+            } else {
+                "Matched, but no schedule rule is active right now".to_string()
+            }
+        }
+        Some(entry) if explanation.blocked && explanation.bypass_active => {
+            format!("Blocked by {} (kept blocked during the start of a soft-start bypass)", entry)
+        }
+        Some(_) if explanation.bypass_active => "Matched, but a bypass is active".to_string(),
+        Some(entry) => match &explanation.active_schedule_rule {
+            Some(rule) => format!("Blocked by {} during schedule rule \"{}\"", entry, rule),
+            None => format!("Blocked by {}", entry),
+        },
+    };
+
+    if config.blocking.dry_run && state.blocked_entry(&domain).is_some() {
+        explanation.reason = format!("{} (dry-run mode: forwarded anyway)", explanation.reason);
+    }
+
+    explanation
+}
@@ -0,0 +1,372 @@
+//! Synthetic query load for validating a deployment.
+//!
+//! `blockandfocus-daemon --selftest-load` sends queries to a running daemon at
+//! a fixed rate and reports throughput, latency percentiles and unanswered
+//! queries. By default it asks for self-check probe names, which the daemon
+//! answers itself, so the load never reaches the upstream resolvers.
+
+use super::selfcheck::PROBE_SUFFIX;
+use anyhow::{anyhow, bail, Context, Result};
+use blockandfocus_shared::DNS_PORT_FILE_DEV;
+use hickory_proto::op::{Message, MessageType, Query};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::serialize::binary::BinEncodable;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::time::MissedTickBehavior;
+
+/// Queries per second sent from one socket, so a query ID isn't reused while
+/// an earlier query with it may still be answered.
+const RATE_PER_SOCKET: u32 = 20_000;
+
+/// How often the sender catches up with the target rate.
+const TICK: Duration = Duration::from_millis(1);
+
+/// How long answers are waited for after the last query.
+const GRACE: Duration = Duration::from_secs(2);
+
+/// How often a receiver checks whether it is done while no answers arrive.
+const POLL: Duration = Duration::from_millis(20);
+
+/// Distinct probe names queried by default.
+const DEFAULT_NAMES: usize = 64;
+
+/// What load to generate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadOptions {
+    pub target: SocketAddr,
+    /// Queries per second
+    pub rate: u32,
+    pub duration: Duration,
+    /// Names queried in turn
+    pub names: Vec<String>,
+}
+
+impl LoadOptions {
+    /// Parse `--target ADDR`, `--rate QPS`, `--duration DURATION` and
+    /// (repeatable) `--name NAME`.
+    pub fn from_args(args: &[String], is_dev: bool) -> Result<Self> {
+        let mut options = Self {
+            target: default_target(is_dev),
+            rate: 1000,
+            duration: Duration::from_secs(10),
+            names: Vec::new(),
+        };
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("{} needs a value", flag))?;
+            match flag.as_str() {
+                "--target" => options.target = parse_target(value)?,
+                "--rate" => {
+                    options.rate = value
+                        .parse()
+                        .ok()
+                        .filter(|rate| *rate > 0)
+                        .ok_or_else(|| anyhow!("Invalid rate: {}", value))?;
+                }
+                "--duration" => {
+                    let duration: blockandfocus_shared::Duration = value.parse()?;
+                    if duration.as_secs() == 0 {
+                        bail!("The duration must be at least one second");
+                    }
+                    options.duration = duration.into();
+                }
+                "--name" => options.names.push(value.clone()),
+                _ => bail!("Unknown option: {}", flag),
+            }
+        }
+
+        if options.names.is_empty() {
+            options.names = (0..DEFAULT_NAMES)
+                .map(|i| format!("load-{}.{}", i, PROBE_SUFFIX))
+                .collect();
+        }
+        Ok(options)
+    }
+}
+
+/// Where a daemon started on this machine listens.
+fn default_target(is_dev: bool) -> SocketAddr {
+    let port = if is_dev {
+        std::fs::read_to_string(DNS_PORT_FILE_DEV)
+            .ok()
+            .and_then(|port| port.trim().parse().ok())
+            .unwrap_or(5353)
+    } else {
+        53
+    };
+    (Ipv4Addr::LOCALHOST, port).into()
+}
+
+/// An address with a port, or an IP address on port 53.
+fn parse_target(value: &str) -> Result<SocketAddr> {
+    value
+        .parse()
+        .or_else(|_| IpAddr::from_str(value).map(|ip| SocketAddr::new(ip, 53)))
+        .with_context(|| format!("Invalid target: {}", value))
+}
+
+/// Outcome of a load run.
+#[derive(Debug)]
+pub struct LoadReport {
+    pub target: SocketAddr,
+    pub sent: u64,
+    pub answered: u64,
+    /// Answers with an error code (e.g. SERVFAIL)
+    pub failed: u64,
+    /// Time spent sending
+    pub elapsed: Duration,
+    /// Latency of each answered query in microseconds, sorted
+    latencies_us: Vec<u32>,
+}
+
+impl LoadReport {
+    pub fn dropped(&self) -> u64 {
+        self.sent - self.answered
+    }
+
+    /// Answered queries per second.
+    pub fn throughput(&self) -> f64 {
+        self.answered as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Latency below which `percent` of answers came.
+    pub fn latency(&self, percent: f64) -> Duration {
+        if self.latencies_us.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (self.latencies_us.len() as f64 * percent / 100.0).ceil() as usize;
+        let index = rank.clamp(1, self.latencies_us.len()) - 1;
+        Duration::from_micros(self.latencies_us[index].into())
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(
+            f,
+            "Sent {} queries to {} in {:.1}s ({:.0}/s)",
+            self.sent,
+            self.target,
+            self.elapsed.as_secs_f64(),
+            self.sent as f64 / self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "Answered: {} ({} with an error, {:.0}/s), dropped: {} ({:.2}%)",
+            self.answered,
+            self.failed,
+            self.throughput(),
+            self.dropped(),
+            self.dropped() as f64 * 100.0 / self.sent.max(1) as f64
+        )?;
+        write!(
+            f,
+            "Latency: p50 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            ms(self.latency(50.0)),
+            ms(self.latency(99.0)),
+            ms(self.latency(100.0))
+        )
+    }
+}
+
+/// Queries in flight on one socket.
+struct Outstanding {
+    /// Send time of the unanswered query with each ID
+    sent_at: Vec<Option<Instant>>,
+    unanswered: usize,
+    /// When the last query was sent, once sending is over
+    finished: Option<Instant>,
+}
+
+/// Answers received on one socket.
+#[derive(Default)]
+struct Received {
+    latencies_us: Vec<u32>,
+    failed: u64,
+}
+
+/// Send queries at the configured rate and wait for the answers.
+pub async fn run_load_test(options: &LoadOptions) -> Result<LoadReport> {
+    let templates = options
+        .names
+        .iter()
+        .map(|name| encode_query(name))
+        .collect::<Result<Vec<_>>>()?;
+
+    let socket_count = options.rate.div_ceil(RATE_PER_SOCKET) as usize;
+    let bind: SocketAddr = match options.target {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let mut sockets = Vec::new();
+    let mut receivers = Vec::new();
+    for _ in 0..socket_count {
+        let socket = Arc::new(UdpSocket::bind(bind).await?);
+        socket
+            .connect(options.target)
+            .await
+            .with_context(|| format!("Cannot reach {}", options.target))?;
+        let outstanding = Arc::new(Mutex::new(Outstanding {
+            sent_at: vec![None; 1 << 16],
+            unanswered: 0,
+            finished: None,
+        }));
+        receivers.push(tokio::spawn(receive(socket.clone(), outstanding.clone())));
+        sockets.push((socket, outstanding));
+    }
+
+    let total = options.rate as u64 * options.duration.as_secs();
+    let start = Instant::now();
+    let mut interval = tokio::time::interval(TICK);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut sent: u64 = 0;
+    while sent < total {
+        interval.tick().await;
+        let due = (start.elapsed().as_secs_f64() * options.rate as f64) as u64;
+        while sent < due.min(total) {
+            let (socket, outstanding) = &sockets[(sent % socket_count as u64) as usize];
+            let id = (sent / socket_count as u64) as u16;
+            let mut packet = templates[(sent % templates.len() as u64) as usize].clone();
+            packet[..2].copy_from_slice(&id.to_be_bytes());
+            {
+                let mut outstanding = outstanding.lock().unwrap();
+                let slot = &mut outstanding.sent_at[id as usize];
+                if slot.replace(Instant::now()).is_none() {
+                    outstanding.unanswered += 1;
+                }
+            }
+            // A query that can't be sent counts as dropped
+            let _ = socket.send(&packet).await;
+            sent += 1;
+        }
+    }
+    let elapsed = start.elapsed();
+    for (_, outstanding) in &sockets {
+        outstanding.lock().unwrap().finished = Some(Instant::now());
+    }
+
+    let mut latencies_us = Vec::new();
+    let mut failed = 0;
+    for receiver in receivers {
+        let received = receiver.await?;
+        latencies_us.extend(received.latencies_us);
+        failed += received.failed;
+    }
+    latencies_us.sort_unstable();
+
+    Ok(LoadReport {
+        target: options.target,
+        sent,
+        answered: latencies_us.len() as u64,
+        failed,
+        elapsed,
+        latencies_us,
+    })
+}
+
+/// Match answers to the queries sent on `socket`, until every query has
+/// been answered or `GRACE` has passed since the last one was sent.
+async fn receive(socket: Arc<UdpSocket>, outstanding: Arc<Mutex<Outstanding>>) -> Received {
+    let mut received = Received::default();
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let done = {
+            let outstanding = outstanding.lock().unwrap();
+            outstanding
+                .finished
+                .is_some_and(|at| outstanding.unanswered == 0 || at.elapsed() >= GRACE)
+        };
+        if done {
+            return received;
+        }
+
+        // Errors (e.g. nothing listening) just mean no answer
+        let Ok(Ok(len)) = tokio::time::timeout(POLL, socket.recv(&mut buf)).await else {
+            continue;
+        };
+        if len < 4 {
+            continue;
+        }
+        let id = u16::from_be_bytes([buf[0], buf[1]]);
+        let at = {
+            let mut outstanding = outstanding.lock().unwrap();
+            let at = outstanding.sent_at[id as usize].take();
+            if at.is_some() {
+                outstanding.unanswered -= 1;
+            }
+            at
+        };
+        let Some(at) = at else {
+            continue;
+        };
+        let micros = at.elapsed().as_micros().min(u32::MAX.into()) as u32;
+        received.latencies_us.push(micros);
+        // Response code: the low four bits of the fourth header byte
+        if buf[3] & 0x0f != 0 {
+            received.failed += 1;
+        }
+    }
+}
+
+fn encode_query(name: &str) -> Result<Vec<u8>> {
+    let name = Name::from_str(name).with_context(|| format!("Invalid name: {}", name))?;
+    let mut query = Message::new();
+    query.set_message_type(MessageType::Query);
+    query.set_recursion_desired(true);
+    query.add_query(Query::query(name, RecordType::A));
+    Ok(query.to_bytes()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDaemon;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_options() {
+        let options = LoadOptions::from_args(&[], false).unwrap();
+        assert_eq!(options.target, "127.0.0.1:53".parse().unwrap());
+        assert_eq!(options.names.len(), DEFAULT_NAMES);
+
+        let options = LoadOptions::from_args(
+            &args(&["--target", "192.168.1.2", "--rate", "50", "--duration", "1m"]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(options.target, "192.168.1.2:53".parse().unwrap());
+        assert_eq!(options.rate, 50);
+        assert_eq!(options.duration, Duration::from_secs(60));
+
+        for bad in [&["--rate", "0"][..], &["--rate"], &["--duration", "0s"], &["--verbose", "1"]] {
+            assert!(LoadOptions::from_args(&args(bad), false).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_against_daemon() {
+        let daemon = TestDaemon::start(|_| {}).await;
+        let target = daemon.dns_addr.to_string();
+        let options = args(&["--target", &target, "--rate", "500", "--duration", "1s"]);
+        let options = LoadOptions::from_args(&options, false).unwrap();
+
+        let report = run_load_test(&options).await.unwrap();
+        assert_eq!(report.sent, 500);
+        assert_eq!(report.dropped(), 0);
+        assert_eq!(report.failed, 0);
+        assert!(report.latency(50.0) <= report.latency(99.0));
+    }
+}
@@ -0,0 +1,13 @@
+//! Troubleshooting helpers.
+
+mod encrypted_dns;
+mod explain;
+mod load;
+mod selfcheck;
+mod watchdog;
+
+pub use encrypted_dns::EncryptedDnsMonitor;
+pub use explain::explain_domain;
+pub use load::{run_load_test, LoadOptions};
+pub use selfcheck::{run_self_check, ProbeTracker};
+pub use watchdog::DnsWatchdog;
@@ -0,0 +1,186 @@
+//! End-to-end check that the system actually routes DNS through the daemon.
+//!
+//! A daemon that runs and answers on its listener is not enough: macOS may
+//! still be using another resolver. The self-check resolves a unique probe
+//! name through the system resolver and checks whether the query arrived at
+//! the DNS server.
+
+use crate::i18n::{tr, Msg};
+use crate::AppState;
+use blockandfocus_shared::SelfCheckReport;
+use hickory_proto::op::{Message, MessageType, Query};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+/// Names under this suffix are answered locally and recorded as probes.
+pub const PROBE_SUFFIX: &str = "selfcheck.blockandfocus.test";
+
+/// A name that should resolve normally through the daemon.
+const ALLOWED_NAME: &str = "example.com";
+
+/// How long to wait for each resolution.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Probe names that reached the DNS server.
+#[derive(Default)]
+pub struct ProbeTracker {
+    seen: Mutex<HashSet<String>>,
+}
+
+impl ProbeTracker {
+    /// Check if a query name is a self-check probe.
+    pub fn is_probe(name: &str) -> bool {
+        name.trim_end_matches('.')
+            .to_lowercase()
+            .ends_with(&format!(".{}", PROBE_SUFFIX))
+    }
+
+    /// Record a probe query.
+    pub fn record(&self, name: &str) {
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.insert(name.trim_end_matches('.').to_lowercase());
+        }
+    }
+
+    /// Check and forget whether a probe was seen.
+    pub(super) fn take(&self, name: &str) -> bool {
+        self.seen.lock().is_ok_and(|mut seen| seen.remove(name))
+    }
+}
+
+/// Run the self-check.
+///
+/// Must be called without holding the state lock: the DNS server needs it to
+/// answer the probe queries.
+pub async fn run_self_check(state: &Arc<RwLock<AppState>>) -> SelfCheckReport {
+    let (listen_addr, blocked_name, blocking_active, probes, locale) = {
+        let state_guard = state.read().await;
+        let config = state_guard.config.get();
+        (
+            state_guard
+                .dns_listen_addr
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|| format!("{}:{}", config.dns.listen_address, config.dns.listen_port)),
+            config.blocking.domains.first().map(|domain| domain.to_string()),
+            state_guard.is_blocking_active(),
+            state_guard.probes.clone(),
+            config.locale,
+        )
+    };
+
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let listener_probe = format!("listener-{}.{}", id, PROBE_SUFFIX);
+    let system_probe = format!("system-{}.{}", id, PROBE_SUFFIX);
+
+    let listener_reachable = query_listener(&listen_addr, &listener_probe).await
+        && probes.take(&listener_probe);
+
+    // The result does not matter, only whether the query reached the daemon
+    let _ = system_lookup(&system_probe).await;
+    let system_routed = probes.take(&system_probe);
+
+    let blocked_name_blocked = match &blocked_name {
+        Some(name) if blocking_active => Some(
+            system_lookup(name)
+                .await
+                .is_some_and(|ips| !ips.is_empty() && ips.iter().all(|ip| ip.is_unspecified())),
+        ),
+        _ => None,
+    };
+
+    let allowed_name_resolved = system_lookup(ALLOWED_NAME)
+        .await
+        .is_some_and(|ips| ips.iter().any(|ip| !ip.is_unspecified()));
+
+    let summary = if !listener_reachable {
+        tr(locale, Msg::SelfCheckListenerDown, &[&listen_addr])
+    } else if !system_routed {
+        tr(locale, Msg::SelfCheckNotRouted, &[])
+    } else if blocked_name_blocked == Some(false) {
+        tr(locale, Msg::SelfCheckBlockedResolves, &[])
+    } else if !allowed_name_resolved {
+        tr(locale, Msg::SelfCheckAllowedFails, &[])
+    } else {
+        tr(locale, Msg::SelfCheckOk, &[])
+    };
+
+    info!(listener_reachable, system_routed, "Self-check finished: {}", summary);
+
+    SelfCheckReport {
+        listener_reachable,
+        system_routed,
+        blocked_name,
+        blocked_name_blocked,
+        allowed_name: ALLOWED_NAME.to_string(),
+        allowed_name_resolved,
+        summary,
+    }
+}
+
+/// Send a single A query directly to the daemon's listener.
+pub(super) async fn query_listener(listen_addr: &str, name: &str) -> bool {
+    let Ok(name) = Name::from_str(name) else {
+        return false;
+    };
+
+    let mut query = Message::new();
+    query.set_id(rand::random());
+    query.set_message_type(MessageType::Query);
+    query.set_recursion_desired(true);
+    query.add_query(Query::query(name, RecordType::A));
+
+    let Ok(bytes) = query.to_bytes() else {
+        return false;
+    };
+
+    let exchange = async {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.ok()?;
+        socket.send_to(&bytes, listen_addr).await.ok()?;
+        let mut buf = vec![0u8; 512];
+        let len = socket.recv(&mut buf).await.ok()?;
+        Message::from_bytes(&buf[..len]).ok()
+    };
+
+    match tokio::time::timeout(PROBE_TIMEOUT, exchange).await {
+        Ok(Some(response)) => response.id() == query.id(),
+        _ => {
+            debug!(listen_addr, "Listener probe got no answer");
+            false
+        }
+    }
+}
+
+/// Resolve a name through the system resolver.
+async fn system_lookup(name: &str) -> Option<Vec<IpAddr>> {
+    let lookup = tokio::net::lookup_host((name, 0));
+    match tokio::time::timeout(PROBE_TIMEOUT, lookup).await {
+        Ok(Ok(addrs)) => Some(addrs.map(|a| a.ip()).collect()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_tracking() {
+        let probe = format!("system-abc.{}.", PROBE_SUFFIX);
+        assert!(ProbeTracker::is_probe(&probe));
+        assert!(!ProbeTracker::is_probe("example.com."));
+        assert!(!ProbeTracker::is_probe(PROBE_SUFFIX));
+
+        let tracker = ProbeTracker::default();
+        tracker.record(&probe.to_uppercase());
+        assert!(tracker.take(&format!("system-abc.{}", PROBE_SUFFIX)));
+        assert!(!tracker.take(&format!("system-abc.{}", PROBE_SUFFIX)));
+    }
+}
@@ -0,0 +1,75 @@
+//! Watchdog that keeps checking the DNS listener still answers.
+//!
+//! A wedged socket looks like "the internet is down" to the user while the
+//! daemon appears healthy. The watchdog periodically sends a probe query to
+//! the daemon's own listener and, after repeated failures, asks the DNS
+//! server to rebind its socket.
+
+use super::selfcheck::{query_listener, PROBE_SUFFIX};
+use crate::AppState;
+use blockandfocus_shared::{DnsWatchdogAlert, Event};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, error};
+
+/// Periodically probes the DNS listener.
+pub struct DnsWatchdog;
+
+impl DnsWatchdog {
+    /// Run the watchdog loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut failures = 0;
+
+        loop {
+            let (interval, threshold, listen_addr, probes, rebind) = {
+                let state_guard = state.read().await;
+                let config = state_guard.config.get();
+                (
+                    config.dns.watchdog_interval_seconds,
+                    config.dns.watchdog_failure_threshold.max(1),
+                    state_guard.dns_listen_addr,
+                    state_guard.probes.clone(),
+                    state_guard.dns_rebind.clone(),
+                )
+            };
+
+            if interval == 0 {
+                // Disabled; check again later in case the config changes
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(u64::from(interval))).await;
+
+            // The DNS server has not bound its socket yet
+            let Some(listen_addr) = listen_addr else {
+                continue;
+            };
+
+            let probe = format!("watchdog-{}.{}", uuid::Uuid::new_v4().simple(), PROBE_SUFFIX);
+            let answered = query_listener(&listen_addr.to_string(), &probe).await && probes.take(&probe);
+            if answered {
+                debug!("DNS watchdog probe answered");
+                failures = 0;
+                continue;
+            }
+
+            failures += 1;
+            error!(
+                "DNS listener on {} did not answer the watchdog probe ({}/{})",
+                listen_addr, failures, threshold
+            );
+            if failures < threshold {
+                continue;
+            }
+
+            error!("DNS listener on {} is unresponsive, rebinding", listen_addr);
+            state.read().await.emit(Event::DnsWatchdogAlert(DnsWatchdogAlert {
+                listen_addr: listen_addr.to_string(),
+                consecutive_failures: failures,
+            }));
+            rebind.notify_one();
+            failures = 0;
+        }
+    }
+}
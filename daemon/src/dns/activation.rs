@@ -0,0 +1,132 @@
+//! Receiving an already-bound DNS socket from the service manager.
+//!
+//! With socket activation launchd (or systemd) binds port 53 and hands the
+//! socket to the daemon, so the daemon process itself does not need root to
+//! listen on a privileged port.
+
+use anyhow::Result;
+use std::net::UdpSocket;
+use std::os::fd::{FromRawFd, RawFd};
+use tracing::info;
+
+/// Name of the socket entry in the launchd plist (and `FileDescriptorName` for systemd).
+pub const SOCKET_NAME: &str = "DNS";
+
+/// First file descriptor passed by systemd.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Take the DNS socket passed by the service manager, if any.
+pub fn activated_socket() -> Result<Option<UdpSocket>> {
+    let fd = match launchd_socket()? {
+        Some(fd) => {
+            info!("Using DNS socket passed by launchd");
+            Some(fd)
+        }
+        None => systemd_socket().inspect(|_| info!("Using DNS socket passed by systemd")),
+    };
+
+    // SAFETY: the descriptor was handed to this process by the service manager
+    // and is not owned by anything else.
+    Ok(fd.map(|fd| unsafe { UdpSocket::from_raw_fd(fd) }))
+}
+
+/// Check in with launchd for the socket named [`SOCKET_NAME`].
+#[cfg(target_os = "macos")]
+fn launchd_socket() -> Result<Option<RawFd>> {
+    use std::ffi::{c_char, c_int, c_void, CString};
+
+    extern "C" {
+        fn launch_activate_socket(name: *const c_char, fds: *mut *mut c_int, cnt: *mut usize) -> c_int;
+        fn free(ptr: *mut c_void);
+    }
+
+    const ENOENT: c_int = 2;
+    const ESRCH: c_int = 3;
+
+    let name = CString::new(SOCKET_NAME)?;
+    let mut fds: *mut c_int = std::ptr::null_mut();
+    let mut count: usize = 0;
+
+    // SAFETY: `name` is a valid C string and `fds`/`count` are valid out-pointers.
+    let result = unsafe { launch_activate_socket(name.as_ptr(), &mut fds, &mut count) };
+    match result {
+        0 => {}
+        // Not started by launchd, or the plist declares no such socket
+        ESRCH | ENOENT => return Ok(None),
+        errno => anyhow::bail!(
+            "launch_activate_socket failed: {}",
+            std::io::Error::from_raw_os_error(errno)
+        ),
+    }
+
+    // SAFETY: on success launchd returns `count` descriptors in a malloc'd array.
+    let descriptors = unsafe { std::slice::from_raw_parts(fds, count).to_vec() };
+    unsafe { free(fds.cast()) };
+
+    Ok(descriptors.first().copied())
+}
+
+/// Check in with launchd for the socket named [`SOCKET_NAME`].
+#[cfg(not(target_os = "macos"))]
+fn launchd_socket() -> Result<Option<RawFd>> {
+    Ok(None)
+}
+
+/// Pick up a socket passed with the systemd `LISTEN_FDS` protocol.
+fn systemd_socket() -> Option<RawFd> {
+    let fd = listen_fd(
+        std::process::id(),
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_FDNAMES").ok().as_deref(),
+    );
+
+    // Child processes must not try to take the same descriptors
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+
+    fd
+}
+
+/// Work out which passed descriptor is the DNS socket.
+///
+/// The descriptors belong to this process only if `LISTEN_PID` matches. When
+/// names are given the one called [`SOCKET_NAME`] is used, otherwise the first.
+fn listen_fd(pid: u32, listen_pid: Option<&str>, listen_fds: Option<&str>, names: Option<&str>) -> Option<RawFd> {
+    if listen_pid?.trim().parse::<u32>().ok()? != pid {
+        return None;
+    }
+
+    let count: RawFd = listen_fds?.trim().parse().ok()?;
+    if count < 1 {
+        return None;
+    }
+
+    let index = match names {
+        Some(names) => names.split(':').position(|name| name == SOCKET_NAME)? as RawFd,
+        None => 0,
+    };
+
+    (index < count).then_some(SD_LISTEN_FDS_START + index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fd() {
+        assert_eq!(listen_fd(42, Some("42"), Some("1"), None), Some(3));
+        assert_eq!(listen_fd(42, Some("42"), Some("2"), Some("IPC:DNS")), Some(4));
+
+        // Meant for another process
+        assert_eq!(listen_fd(42, Some("7"), Some("1"), None), None);
+        // Nothing passed, or no DNS socket among the names
+        assert_eq!(listen_fd(42, None, None, None), None);
+        assert_eq!(listen_fd(42, Some("42"), Some("0"), None), None);
+        assert_eq!(listen_fd(42, Some("42"), Some("1"), Some("IPC")), None);
+        // Names list longer than the descriptor count
+        assert_eq!(listen_fd(42, Some("42"), Some("1"), Some("IPC:DNS")), None);
+    }
+}
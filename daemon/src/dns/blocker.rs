@@ -1,29 +1,28 @@
 //! Domain blocking logic.
 
+use blockandfocus_shared::DomainName;
 use tracing::debug;
 
+use super::bloom::SuffixFilter;
+use super::trie::DomainTrie;
+
 /// Domain blocker with exact and subdomain matching.
 pub struct DomainBlocker {
-    blocked_domains: Vec<String>,
+    blocked_domains: DomainTrie,
+    /// Rejects most non-blocked domains before the trie is walked
+    filter: SuffixFilter,
 }
 
 impl DomainBlocker {
     /// Create a new blocker with the given domain list.
-    pub fn new(domains: Vec<String>) -> Self {
-        let blocked_domains: Vec<String> = domains
-            .into_iter()
-            .map(|d| normalize_domain(&d))
-            .collect();
-
-        Self { blocked_domains }
-    }
+    pub fn new(domains: &[DomainName]) -> Self {
+        let blocked_domains = DomainTrie::new(domains.iter().map(DomainName::as_str));
+        let filter = SuffixFilter::new(domains.iter().map(DomainName::as_str));
 
-    /// Update the blocked domains list.
-    pub fn update_domains(&mut self, domains: Vec<String>) {
-        self.blocked_domains = domains
-            .into_iter()
-            .map(|d| normalize_domain(&d))
-            .collect();
+        Self {
+            blocked_domains,
+            filter,
+        }
     }
 
     /// Check if a domain should be blocked.
@@ -31,50 +30,52 @@ impl DomainBlocker {
     /// Matches exact domain and all subdomains.
     /// E.g., blocking "facebook.com" also blocks "www.facebook.com" and "m.facebook.com".
     pub fn should_block(&self, query_domain: &str) -> bool {
-        let normalized = normalize_domain(query_domain);
-
-        for blocked in &self.blocked_domains {
-            // Exact match
-            if normalized == *blocked {
-                debug!(domain = %normalized, "Blocked (exact match)");
-                return true;
-            }
-
-            // Subdomain match: query ends with ".blocked_domain"
-            if normalized.ends_with(&format!(".{}", blocked)) {
-                debug!(domain = %normalized, blocked = %blocked, "Blocked (subdomain match)");
-                return true;
-            }
+        self.matching_entry(query_domain).is_some()
+    }
+
+    /// Get the blocklist entry matching a domain, if any.
+    ///
+    /// When both a domain and its parent are listed, the parent is returned.
+    pub fn matching_entry(&self, query_domain: &str) -> Option<&str> {
+        let query = query_domain.trim().trim_end_matches('.');
+        if !self.filter.may_contain_suffix(query) {
+            return None;
         }
 
-        false
+        let entry = self.blocked_domains.find_suffix(query)?;
+
+        if entry.len() == query.len() {
+            debug!(domain = %query_domain, "Blocked (exact match)");
+        } else {
+            debug!(domain = %query_domain, blocked = %entry, "Blocked (subdomain match)");
+        }
+        Some(entry)
     }
 
     /// Get the number of blocked domains.
     pub fn blocked_count(&self) -> usize {
         self.blocked_domains.len()
     }
-}
 
-/// Normalize a domain name for comparison.
-fn normalize_domain(domain: &str) -> String {
-    domain
-        .to_lowercase()
-        .trim()
-        .trim_end_matches('.')
-        .to_string()
+    /// Approximate memory used by the blocklist, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        self.blocked_domains.memory_bytes() + self.filter.memory_bytes()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn blocker(domains: &[&str]) -> DomainBlocker {
+        let domains: Vec<DomainName> =
+            domains.iter().map(|d| DomainName::parse(d).unwrap()).collect();
+        DomainBlocker::new(&domains)
+    }
+
     #[test]
     fn test_exact_match() {
-        let blocker = DomainBlocker::new(vec![
-            "facebook.com".to_string(),
-            "twitter.com".to_string(),
-        ]);
+        let blocker = blocker(&["facebook.com", "twitter.com"]);
 
         assert!(blocker.should_block("facebook.com"));
         assert!(blocker.should_block("FACEBOOK.COM"));
@@ -85,7 +86,7 @@ mod tests {
 
     #[test]
     fn test_subdomain_match() {
-        let blocker = DomainBlocker::new(vec!["facebook.com".to_string()]);
+        let blocker = blocker(&["facebook.com"]);
 
         assert!(blocker.should_block("www.facebook.com"));
         assert!(blocker.should_block("m.facebook.com"));
@@ -98,26 +99,25 @@ mod tests {
     }
 
     #[test]
-    fn test_update_domains() {
-        let mut blocker = DomainBlocker::new(vec!["facebook.com".to_string()]);
-
-        assert!(blocker.should_block("facebook.com"));
-        assert!(!blocker.should_block("twitter.com"));
-
-        blocker.update_domains(vec!["twitter.com".to_string()]);
+    fn test_matching_entry() {
+        let blocker = blocker(&["Facebook.com"]);
 
-        assert!(!blocker.should_block("facebook.com"));
-        assert!(blocker.should_block("twitter.com"));
+        assert_eq!(blocker.matching_entry("m.facebook.com."), Some("facebook.com"));
+        assert_eq!(blocker.matching_entry("google.com"), None);
     }
 
     #[test]
     fn test_blocked_count() {
-        let blocker = DomainBlocker::new(vec![
-            "facebook.com".to_string(),
-            "twitter.com".to_string(),
-            "instagram.com".to_string(),
-        ]);
+        let blocker = blocker(&["facebook.com", "twitter.com", "instagram.com"]);
 
         assert_eq!(blocker.blocked_count(), 3);
     }
+
+    #[test]
+    fn test_empty_blocklist() {
+        let blocker = blocker(&[]);
+
+        assert_eq!(blocker.blocked_count(), 0);
+        assert!(!blocker.should_block("example.com"));
+    }
 }
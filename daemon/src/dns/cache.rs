@@ -0,0 +1,590 @@
+//! Bounded DNS response cache with LRU eviction.
+//!
+//! Besides answers, "no such name" (NXDOMAIN) and "no such record" (NODATA)
+//! responses are cached as RFC 2308 describes: for the TTL their SOA record
+//! gives, capped by `max_negative_ttl`, and not at all without an SOA.
+//!
+//! Expired entries are kept for `max_stale` longer, so that when every
+//! upstream fails the last known answer can be served instead of SERVFAIL
+//! (RFC 8767).
+
+use blockandfocus_shared::{CacheConfig, CacheStats};
+use hickory_proto::op::{Message, ResponseCode};
+use hickory_proto::rr::{RData, RecordType};
+use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use super::normalize_domain;
+
+/// TTL of stale answers, so clients ask again soon (RFC 8767 suggests 30s).
+const STALE_TTL: u32 = 30;
+
+/// Header identifying a saved cache (and its format version).
+const SAVED_MAGIC: &[u8] = b"BAFDNSC1";
+
+/// Cache key: normalized query name and record type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    name: String,
+    record_type: RecordType,
+}
+
+impl CacheKey {
+    fn new(name: &str, record_type: RecordType) -> Self {
+        Self {
+            name: normalize_domain(name),
+            record_type,
+        }
+    }
+}
+
+/// A cached response with its bookkeeping data.
+#[derive(Debug)]
+struct CacheEntry {
+    response: Message,
+    inserted_at: Instant,
+    expires_at: Instant,
+    size: usize,
+    last_used: u64,
+}
+
+/// TTL-respecting DNS response cache bounded by entry count and total size.
+///
+/// When either limit is exceeded the least recently used entries are evicted.
+pub struct DnsCache {
+    config: CacheConfig,
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Access order: last-used tick -> key (oldest first)
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl DnsCache {
+    /// Create a new cache with the given limits.
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Whether caching is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled && self.config.max_entries > 0
+    }
+
+    /// Look up a cached response.
+    ///
+    /// Returned answers have their TTLs reduced by the time spent in the cache.
+    pub fn get(&mut self, name: &str, record_type: RecordType) -> Option<Message> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let key = CacheKey::new(name, record_type);
+        let now = Instant::now();
+
+        let expired = match self.entries.get(&key) {
+            Some(entry) => entry.expires_at <= now,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+
+        if expired {
+            if !self.keeps_stale(&key, now) {
+                self.remove_key(&key);
+            }
+            self.misses += 1;
+            return None;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(&key)?;
+        self.lru.remove(&entry.last_used);
+        self.lru.insert(tick, key);
+        entry.last_used = tick;
+        self.hits += 1;
+
+        let elapsed = now.duration_since(entry.inserted_at).as_secs() as u32;
+        let mut response = entry.response.clone();
+        age(&mut response, elapsed);
+
+        Some(response)
+    }
+
+    /// An expired answer still within `max_stale`, for when every upstream
+    /// fails. All its TTLs are set to `STALE_TTL`.
+    pub fn get_stale(&mut self, name: &str, record_type: RecordType) -> Option<Message> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let key = CacheKey::new(name, record_type);
+        if !self.keeps_stale(&key, Instant::now()) {
+            self.remove_key(&key);
+            return None;
+        }
+
+        let mut response = self.entries.get(&key)?.response.clone();
+        for record in response.answers_mut() {
+            record.set_ttl(STALE_TTL);
+        }
+        for record in response.name_servers_mut() {
+            record.set_ttl(STALE_TTL);
+        }
+        Some(response)
+    }
+
+    /// Whether the entry for `key` may still be served stale at `now`.
+    fn keeps_stale(&self, key: &CacheKey, now: Instant) -> bool {
+        let max_stale = Duration::from_secs(self.config.max_stale.as_secs());
+        self.entries
+            .get(key)
+            .is_some_and(|entry| entry.expires_at + max_stale > now)
+    }
+
+    /// Insert a forwarded response.
+    ///
+    /// Answers are cached for the smallest answer TTL, negative responses for
+    /// their SOA's negative TTL up to `max_negative_ttl`. Other errors and
+    /// responses with a zero TTL are not cached.
+    pub fn insert(&mut self, name: &str, record_type: RecordType, response: &Message) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let ttl = if is_negative(response) {
+            let max = self.config.max_negative_ttl.as_secs_u32();
+            negative_ttl(response).map(|ttl| ttl.min(max))
+        } else if response.response_code() == ResponseCode::NoError {
+            response.answers().iter().map(|r| r.ttl()).min()
+        } else {
+            None
+        };
+        let ttl = match ttl {
+            Some(ttl) if ttl > 0 => ttl,
+            _ => return,
+        };
+
+        let size = response.to_bytes().map(|b| b.len()).unwrap_or(0);
+        if size == 0 || size > self.config.max_bytes {
+            return;
+        }
+
+        self.add(CacheKey::new(name, record_type), response.clone(), u64::from(ttl), size);
+        self.enforce_limits();
+    }
+
+    /// Encode the unexpired entries, least recently used first, so `import`
+    /// can restore them after a restart. `now` is the current Unix time.
+    pub fn export(&self, now: i64) -> Vec<u8> {
+        let instant = Instant::now();
+        let mut out = SAVED_MAGIC.to_vec();
+        out.extend_from_slice(&now.to_be_bytes());
+
+        for key in self.lru.values() {
+            let Some(entry) = self.entries.get(key) else {
+                continue;
+            };
+            let remaining = entry.expires_at.saturating_duration_since(instant).as_secs();
+            if remaining == 0 {
+                continue;
+            }
+            let elapsed = instant.duration_since(entry.inserted_at).as_secs() as u32;
+            let mut response = entry.response.clone();
+            age(&mut response, elapsed);
+            let Ok(message) = response.to_bytes() else {
+                continue;
+            };
+
+            out.extend_from_slice(&(key.name.len() as u16).to_be_bytes());
+            out.extend_from_slice(key.name.as_bytes());
+            out.extend_from_slice(&u16::from(key.record_type).to_be_bytes());
+            out.extend_from_slice(&(remaining.min(u64::from(u32::MAX)) as u32).to_be_bytes());
+            out.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            out.extend_from_slice(&message);
+        }
+
+        out
+    }
+
+    /// Add entries encoded by `export`, skipping those that have expired since.
+    /// Stops at the first damaged entry. Returns the number of entries added.
+    pub fn import(&mut self, data: &[u8], now: i64) -> usize {
+        if !self.is_enabled() {
+            return 0;
+        }
+        let Some(mut rest) = data.strip_prefix(SAVED_MAGIC) else {
+            return 0;
+        };
+        let Some(saved_at) = take(&mut rest, 8).map(|b| i64::from_be_bytes(b.try_into().unwrap()))
+        else {
+            return 0;
+        };
+        // A clock set back since the save counts as no time passing
+        let elapsed = u64::try_from(now.saturating_sub(saved_at)).unwrap_or(0);
+
+        let mut added = 0;
+        while let Some((name, record_type, remaining, message)) = read_saved_entry(&mut rest) {
+            let Some(ttl) = u64::from(remaining).checked_sub(elapsed).filter(|ttl| *ttl > 0) else {
+                continue;
+            };
+            let Ok(mut response) = Message::from_bytes(message) else {
+                break;
+            };
+            age(&mut response, elapsed as u32);
+            let key = CacheKey {
+                name,
+                record_type,
+            };
+            self.add(key, response, ttl, message.len());
+            added += 1;
+        }
+
+        self.enforce_limits();
+        added
+    }
+
+    /// Get current cache counters.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    /// Remove all cached record types for a single name.
+    ///
+    /// Returns the number of entries removed.
+    pub fn flush_domain(&mut self, name: &str) -> usize {
+        let name = normalize_domain(name);
+        let keys: Vec<CacheKey> = self
+            .entries
+            .keys()
+            .filter(|k| k.name == name)
+            .cloned()
+            .collect();
+
+        keys.iter().filter(|k| self.remove_key(k)).count()
+    }
+
+    /// Remove all cached entries.
+    ///
+    /// Returns the number of entries removed.
+    pub fn flush(&mut self) -> usize {
+        let removed = self.entries.len();
+        self.entries.clear();
+        self.lru.clear();
+        self.bytes = 0;
+        removed
+    }
+
+    /// Add an entry as the most recently used, replacing any with the same key.
+    fn add(&mut self, key: CacheKey, response: Message, ttl: u64, size: usize) {
+        self.remove_key(&key);
+
+        let now = Instant::now();
+        self.tick += 1;
+        self.lru.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: now,
+                expires_at: now + Duration::from_secs(ttl),
+                size,
+                last_used: self.tick,
+            },
+        );
+        self.bytes += size;
+    }
+
+    /// Evict least recently used entries until both limits are respected.
+    fn enforce_limits(&mut self) {
+        while self.entries.len() > self.config.max_entries || self.bytes > self.config.max_bytes {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.size;
+                self.evictions += 1;
+                debug!(name = %key.name, record_type = ?key.record_type, "Evicted cache entry");
+            }
+        }
+    }
+
+    /// Remove a single entry, keeping the size and LRU bookkeeping consistent.
+    fn remove_key(&mut self, key: &CacheKey) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.lru.remove(&entry.last_used);
+                self.bytes -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Split `len` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let (head, tail) = data.split_at_checked(len)?;
+    *data = tail;
+    Some(head)
+}
+
+/// Read one entry written by `DnsCache::export`: name, record type, seconds
+/// left and the encoded response.
+fn read_saved_entry<'a>(data: &mut &'a [u8]) -> Option<(String, RecordType, u32, &'a [u8])> {
+    let name_len = u16::from_be_bytes(take(data, 2)?.try_into().ok()?);
+    let name = std::str::from_utf8(take(data, name_len as usize)?).ok()?.to_string();
+    let record_type = RecordType::from(u16::from_be_bytes(take(data, 2)?.try_into().ok()?));
+    let remaining = u32::from_be_bytes(take(data, 4)?.try_into().ok()?);
+    let message_len = u32::from_be_bytes(take(data, 4)?.try_into().ok()?);
+    let message = take(data, message_len as usize)?;
+    Some((name, record_type, remaining, message))
+}
+
+/// Whether `response` says the name, or the record type for it, doesn't exist.
+fn is_negative(response: &Message) -> bool {
+    match response.response_code() {
+        ResponseCode::NXDomain => true,
+        ResponseCode::NoError => response.answers().is_empty(),
+        _ => false,
+    }
+}
+
+/// How long a negative response may be cached: the lower of the SOA
+/// record's TTL and its MINIMUM field (RFC 2308 section 5).
+fn negative_ttl(response: &Message) -> Option<u32> {
+    response
+        .name_servers()
+        .iter()
+        .find_map(|record| match record.data() {
+            RData::SOA(soa) => Some(record.ttl().min(soa.minimum())),
+            _ => None,
+        })
+}
+
+/// Reduce the TTLs of a cached response by the seconds it spent in the cache.
+fn age(response: &mut Message, elapsed: u32) {
+    for record in response.answers_mut() {
+        record.set_ttl(record.ttl().saturating_sub(elapsed));
+    }
+    for record in response.name_servers_mut() {
+        record.set_ttl(record.ttl().saturating_sub(elapsed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::rr::{Name, RData, Record};
+    use std::str::FromStr;
+
+    fn response(name: &str, ttl: u32) -> Message {
+        let mut message = Message::new();
+        let rdata = RData::A("93.184.216.34".parse().unwrap());
+        message.add_answer(Record::from_rdata(Name::from_str(name).unwrap(), ttl, rdata));
+        message
+    }
+
+    fn config(max_entries: usize, max_bytes: usize) -> CacheConfig {
+        CacheConfig {
+            enabled: true,
+            max_entries,
+            max_bytes,
+            ..CacheConfig::default()
+        }
+    }
+
+    fn negative(name: &str, code: ResponseCode, soa_ttl: Option<u32>) -> Message {
+        use hickory_proto::rr::rdata::SOA;
+
+        let mut message = Message::new();
+        message.set_response_code(code);
+        if let Some(ttl) = soa_ttl {
+            let root = Name::root();
+            let soa = SOA::new(root.clone(), root.clone(), 1, 7200, 3600, 86400, 600);
+            let name = Name::from_str(name).unwrap();
+            message.add_name_server(Record::from_rdata(name, ttl, RData::SOA(soa)));
+        }
+        message
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let mut cache = DnsCache::new(config(10, 1024 * 1024));
+
+        assert!(cache.get("example.com.", RecordType::A).is_none());
+        cache.insert("example.com.", RecordType::A, &response("example.com.", 300));
+
+        assert!(cache.get("EXAMPLE.com", RecordType::A).is_some());
+        assert!(cache.get("example.com.", RecordType::AAAA).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+    }
+
+    #[test]
+    fn test_zero_ttl_not_cached() {
+        let mut cache = DnsCache::new(config(10, 1024 * 1024));
+        cache.insert("example.com.", RecordType::A, &response("example.com.", 0));
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_lru_eviction_by_entries() {
+        let mut cache = DnsCache::new(config(2, 1024 * 1024));
+        cache.insert("a.com.", RecordType::A, &response("a.com.", 300));
+        cache.insert("b.com.", RecordType::A, &response("b.com.", 300));
+
+        // Touch a.com so b.com becomes the least recently used
+        assert!(cache.get("a.com.", RecordType::A).is_some());
+        cache.insert("c.com.", RecordType::A, &response("c.com.", 300));
+
+        assert!(cache.get("a.com.", RecordType::A).is_some());
+        assert!(cache.get("b.com.", RecordType::A).is_none());
+        assert!(cache.get("c.com.", RecordType::A).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn test_eviction_by_bytes() {
+        let size = response("a.com.", 300).to_bytes().unwrap().len();
+        let mut cache = DnsCache::new(config(100, size * 2));
+
+        cache.insert("a.com.", RecordType::A, &response("a.com.", 300));
+        cache.insert("b.com.", RecordType::A, &response("b.com.", 300));
+        cache.insert("c.com.", RecordType::A, &response("c.com.", 300));
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert!(stats.bytes <= size * 2);
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut cache = DnsCache::new(config(10, 1024 * 1024));
+        cache.insert("a.com.", RecordType::A, &response("a.com.", 300));
+        cache.insert("a.com.", RecordType::AAAA, &response("a.com.", 300));
+        cache.insert("b.com.", RecordType::A, &response("b.com.", 300));
+
+        assert_eq!(cache.flush_domain("A.com"), 2);
+        assert_eq!(cache.stats().entries, 1);
+        assert!(cache.get("b.com.", RecordType::A).is_some());
+
+        assert_eq!(cache.flush(), 1);
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 0);
+        assert_eq!(stats.bytes, 0);
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let mut cache = DnsCache::new(config(10, 1024 * 1024));
+        cache.insert("a.com.", RecordType::A, &response("a.com.", 300));
+        cache.insert("b.com.", RecordType::A, &response("b.com.", 60));
+        let saved = cache.export(1_000);
+
+        // A minute and a half later b.com has expired, a.com has 210s left
+        let mut restored = DnsCache::new(config(10, 1024 * 1024));
+        assert_eq!(restored.import(&saved, 1_090), 1);
+        assert!(restored.get("b.com.", RecordType::A).is_none());
+        let answer = restored.get("a.com", RecordType::A).unwrap();
+        assert_eq!(answer.answers()[0].ttl(), 210);
+
+        // Damaged data adds what it can and never panics
+        for len in 0..saved.len() {
+            let mut restored = DnsCache::new(config(10, 1024 * 1024));
+            assert!(restored.import(&saved[..len], 1_000) <= 2);
+        }
+        assert_eq!(restored.import(b"not a cache", 1_000), 0);
+    }
+
+    #[test]
+    fn test_negative_answers() {
+        let mut cache = DnsCache::new(config(10, 1 << 20));
+        let nxdomain = negative("missing.com.", ResponseCode::NXDomain, Some(3600));
+        cache.insert("missing.com.", RecordType::A, &nxdomain);
+        let cached = cache.get("missing.com.", RecordType::A).unwrap();
+        assert_eq!(cached.response_code(), ResponseCode::NXDomain);
+        // The SOA's MINIMUM (600) is lower than its TTL
+        assert_eq!(cached.name_servers()[0].ttl(), 3600);
+        assert_eq!(negative_ttl(&cached), Some(600));
+
+        // NODATA is cached for its own type only
+        let nodata = negative("v4only.com.", ResponseCode::NoError, Some(60));
+        cache.insert("v4only.com.", RecordType::AAAA, &nodata);
+        assert!(cache.get("v4only.com.", RecordType::AAAA).is_some());
+        assert!(cache.get("v4only.com.", RecordType::A).is_none());
+
+        // Without an SOA there is no negative TTL, and failures aren't answers
+        let no_soa = negative("nosoa.com.", ResponseCode::NXDomain, None);
+        cache.insert("nosoa.com.", RecordType::A, &no_soa);
+        assert!(cache.get("nosoa.com.", RecordType::A).is_none());
+        let servfail = negative("broken.com.", ResponseCode::ServFail, Some(60));
+        cache.insert("broken.com.", RecordType::A, &servfail);
+        assert!(cache.get("broken.com.", RecordType::A).is_none());
+
+        let mut capped = DnsCache::new(CacheConfig {
+            max_negative_ttl: blockandfocus_shared::Duration::from_secs(0),
+            ..CacheConfig::default()
+        });
+        capped.insert("missing.com.", RecordType::A, &nxdomain);
+        assert!(capped.get("missing.com.", RecordType::A).is_none());
+    }
+
+    #[test]
+    fn test_stale_answers() {
+        let mut cache = DnsCache::new(config(10, 1 << 20));
+        let key = CacheKey::new("a.com.", RecordType::A);
+        let answer = response("a.com.", 300);
+        cache.add(key.clone(), answer.clone(), 0, 100);
+
+        // Expired, so only served when asked for a stale answer
+        assert!(cache.get("a.com.", RecordType::A).is_none());
+        let stale = cache.get_stale("a.com.", RecordType::A).unwrap();
+        assert_eq!(stale.answers()[0].ttl(), STALE_TTL);
+        assert!(cache.get_stale("b.com.", RecordType::A).is_none());
+
+        let mut no_stale = DnsCache::new(CacheConfig {
+            max_stale: blockandfocus_shared::Duration::from_secs(0),
+            ..config(10, 1 << 20)
+        });
+        no_stale.add(key, answer, 0, 100);
+        assert!(no_stale.get_stale("a.com.", RecordType::A).is_none());
+        assert_eq!(no_stale.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut cache = DnsCache::new(CacheConfig {
+            enabled: false,
+            ..CacheConfig::default()
+        });
+        cache.insert("a.com.", RecordType::A, &response("a.com.", 300));
+        assert!(cache.get("a.com.", RecordType::A).is_none());
+    }
+}
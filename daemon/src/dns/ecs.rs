@@ -0,0 +1,70 @@
+//! EDNS Client Subnet (RFC 7871) removal.
+//!
+//! A client subnet option tells resolvers which network a query came from.
+//! Upstream queries are built from the question alone, so client options are
+//! never sent on; the option is still removed from every incoming query so no
+//! code path handling the query can pass it along.
+
+use hickory_proto::op::Message;
+use hickory_proto::rr::rdata::opt::EdnsCode;
+
+/// Remove any client subnet option from `query`, returning whether it had one.
+pub fn strip_client_subnet(query: &mut Message) -> bool {
+    let Some(edns) = query.extensions_mut() else {
+        return false;
+    };
+    if edns.option(EdnsCode::Subnet).is_none() {
+        return false;
+    }
+    edns.options_mut().remove(EdnsCode::Subnet);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::{Edns, Query};
+    use hickory_proto::rr::rdata::opt::{ClientSubnet, EdnsOption};
+    use hickory_proto::rr::{Name, RecordType};
+    use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+
+    fn query_with(options: Vec<EdnsOption>) -> Message {
+        let mut query = Message::new();
+        query.add_query(Query::query(
+            Name::from_ascii("example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let mut edns = Edns::new();
+        for option in options {
+            edns.options_mut().insert(option);
+        }
+        query.set_edns(edns);
+        query
+    }
+
+    #[test]
+    fn test_strips_subnet_and_keeps_other_options() {
+        let subnet = ClientSubnet::new([192, 168, 1, 0].into(), 24, 0);
+        let cookie = EdnsOption::Unknown(10, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        let query = query_with(vec![EdnsOption::Subnet(subnet), cookie.clone()]);
+
+        // Through the wire format, as queries arrive
+        let mut query = Message::from_bytes(&query.to_bytes().unwrap()).unwrap();
+        assert!(strip_client_subnet(&mut query));
+
+        let query = Message::from_bytes(&query.to_bytes().unwrap()).unwrap();
+        let edns = query.extensions().as_ref().unwrap();
+        assert!(edns.option(EdnsCode::Subnet).is_none());
+        assert_eq!(edns.option(EdnsCode::Cookie), Some(&cookie));
+    }
+
+    #[test]
+    fn test_queries_without_subnet_are_untouched() {
+        let mut query = query_with(Vec::new());
+        assert!(!strip_client_subnet(&mut query));
+
+        let mut query = Message::new();
+        assert!(!strip_client_subnet(&mut query));
+        assert!(query.extensions().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Split-horizon forwarding of internal zones.
+//!
+//! Names in a forwarded zone (e.g. `*.internal.company.com`) go to the zone's
+//! own servers, typically a VPN's resolver. While those servers are
+//! unreachable — the VPN is down — the zone falls back to the regular
+//! upstreams, and the servers are retried every `RECHECK_INTERVAL`.
+
+use anyhow::Result;
+use blockandfocus_shared::ForwardZone;
+use hickory_proto::op::Message;
+use hickory_proto::rr::{Name, RecordType};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::{Resolve, UpstreamResolver};
+
+/// How long a zone's servers get to answer before they count as down.
+const ZONE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a zone whose servers are down waits before trying them again.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of forwarding a query to a zone's servers.
+pub enum ZoneAnswer {
+    /// The zone's servers answered (or reported an error for the name)
+    Answered(Result<Message>),
+    /// The zone's servers are down and the regular upstreams should be used
+    Fallback,
+    /// The zone's servers are down and the query should fail
+    Unavailable,
+}
+
+/// Liveness of a zone's servers.
+struct Health {
+    up: bool,
+    checked_at: Option<Instant>,
+}
+
+/// Forwarder for one zone.
+struct ZoneForwarder {
+    zone: String,
+    fallback: bool,
+    servers: Vec<SocketAddr>,
+    resolver: UpstreamResolver,
+    health: Mutex<Health>,
+}
+
+/// Forwarders for all configured zones.
+#[derive(Default)]
+pub struct ZoneForwarders {
+    zones: Vec<ZoneForwarder>,
+}
+
+impl ZoneForwarders {
+    /// Create forwarders for the configured zones, skipping invalid ones.
+    pub fn new(zones: &[ForwardZone], idle_timeout: Duration) -> Self {
+        let zones = zones
+            .iter()
+            .filter_map(|zone| {
+                let name = zone.zone.trim().trim_end_matches('.').to_lowercase();
+                match UpstreamResolver::for_servers(&zone.servers, ZONE_TIMEOUT, idle_timeout) {
+                    Ok(resolver) => Some(ZoneForwarder {
+                        zone: name,
+                        fallback: zone.fallback,
+                        servers: zone.servers.iter().filter_map(|s| s.socket_addr()).collect(),
+                        resolver,
+                        health: Mutex::new(Health {
+                            up: true,
+                            checked_at: None,
+                        }),
+                    }),
+                    Err(e) => {
+                        warn!(zone = %name, "Ignoring forwarded zone: {:#}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self { zones }
+    }
+
+    /// Whether `domain` belongs to a forwarded zone.
+    pub fn covers(&self, domain: &str) -> bool {
+        self.matching(domain).is_some()
+    }
+
+    /// Forward a query to its zone's servers, or None if no zone covers it.
+    pub async fn resolve(
+        &self,
+        domain: &str,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Option<ZoneAnswer> {
+        let zone = self.matching(domain)?;
+        Some(zone.resolve(name, record_type).await)
+    }
+
+    /// The most specific zone covering `domain`.
+    fn matching(&self, domain: &str) -> Option<&ZoneForwarder> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        self.zones
+            .iter()
+            .filter(|z| domain == z.zone || domain.ends_with(&format!(".{}", z.zone)))
+            .max_by_key(|z| z.zone.len())
+    }
+}
+
+impl ZoneForwarder {
+    async fn resolve(&self, name: &Name, record_type: RecordType) -> ZoneAnswer {
+        let recheck = {
+            let health = self.health.lock().unwrap();
+            health.up || health.checked_at.is_none_or(|at| at.elapsed() >= RECHECK_INTERVAL)
+        };
+        if !recheck || !self.has_route() {
+            self.set_up(false);
+            return self.unavailable();
+        }
+
+        let result = self.resolver.resolve(name, record_type).await;
+        // An answer about the name (even "no such name") means the servers are up
+        let reachable = match &result {
+            Ok(_) => true,
+            Err(e) => e.is_no_records_found(),
+        };
+        self.set_up(reachable);
+
+        if reachable {
+            ZoneAnswer::Answered(result.map_err(Into::into))
+        } else {
+            self.unavailable()
+        }
+    }
+
+    /// Whether any of the zone's servers can be routed to. A VPN's resolver has
+    /// no route while the VPN is down, which is found without sending anything.
+    fn has_route(&self) -> bool {
+        self.servers.iter().any(|addr| {
+            let bind: SocketAddr = if addr.is_ipv4() {
+                ([0, 0, 0, 0], 0).into()
+            } else {
+                ([0u16; 8], 0).into()
+            };
+            UdpSocket::bind(bind).and_then(|socket| socket.connect(addr)).is_ok()
+        })
+    }
+
+    fn set_up(&self, up: bool) {
+        let mut health = self.health.lock().unwrap();
+        if health.up != up {
+            if up {
+                info!(zone = %self.zone, "Forwarded zone servers are reachable again");
+            } else {
+                warn!(
+                    zone = %self.zone,
+                    fallback = self.fallback,
+                    "Forwarded zone servers are unreachable"
+                );
+            }
+        }
+        health.up = up;
+        health.checked_at = Some(Instant::now());
+    }
+
+    fn unavailable(&self) -> ZoneAnswer {
+        if self.fallback {
+            ZoneAnswer::Fallback
+        } else {
+            ZoneAnswer::Unavailable
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::UpstreamServer;
+
+    fn zone(name: &str, server: &str, fallback: bool) -> ForwardZone {
+        ForwardZone {
+            zone: name.to_string(),
+            servers: vec![UpstreamServer::parse(server).unwrap()],
+            fallback,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_matching_zone() {
+        let forwarders = ZoneForwarders::new(
+            &[
+                zone("company.com", "10.0.0.53", true),
+                zone("internal.company.com", "10.1.0.53", true),
+            ],
+            Duration::from_secs(60),
+        );
+
+        assert!(forwarders.matching("company.com").is_some());
+        assert!(forwarders.matching("notcompany.com").is_none());
+        let zone = forwarders.matching("wiki.internal.company.com.").unwrap();
+        assert_eq!(zone.zone, "internal.company.com");
+    }
+
+    #[tokio::test]
+    async fn test_down_zone_falls_back_until_recheck() {
+        let forwarders = ZoneForwarders::new(
+            &[
+                zone("corp.test", "127.0.0.1:9", true),
+                zone("strict.test", "127.0.0.1:9", false),
+            ],
+            Duration::from_secs(60),
+        );
+        for zone in &forwarders.zones {
+            zone.set_up(false);
+        }
+
+        // Down and recently checked, so no query is sent
+        let name = Name::from_ascii("wiki.corp.test.").unwrap();
+        let answer = forwarders.resolve("wiki.corp.test.", &name, RecordType::A).await;
+        assert!(matches!(answer, Some(ZoneAnswer::Fallback)));
+
+        let name = Name::from_ascii("wiki.strict.test.").unwrap();
+        let answer = forwarders.resolve("wiki.strict.test.", &name, RecordType::A).await;
+        assert!(matches!(answer, Some(ZoneAnswer::Unavailable)));
+
+        let name = Name::from_ascii("example.com.").unwrap();
+        assert!(forwarders.resolve("example.com.", &name, RecordType::A).await.is_none());
+    }
+}
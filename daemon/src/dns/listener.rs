@@ -0,0 +1,116 @@
+//! Binding the DNS listener socket.
+
+use anyhow::{Context, Result};
+use blockandfocus_shared::{DnsConfig, DNS_PORT_FILE_DEV};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, UdpSocket};
+use tracing::{info, warn};
+
+use super::activation::activated_socket;
+
+/// Environment variable overriding the listen port in development mode.
+const PORT_ENV: &str = "BLOCKANDFOCUS_PORT";
+
+/// Bind the UDP socket the DNS server listens on.
+///
+/// A socket passed by launchd or systemd (socket activation) is used as-is.
+/// In development mode the port can be overridden with `BLOCKANDFOCUS_PORT`.
+/// If the configured port cannot be bound (port 53 needs root), a free high
+/// port is picked instead and written to [`DNS_PORT_FILE_DEV`] so tooling can
+/// find it.
+pub async fn bind_udp(config: &DnsConfig, is_dev: bool) -> Result<UdpSocket> {
+    if let Some(socket) = activated_socket()? {
+        socket.set_nonblocking(true)?;
+        return UdpSocket::from_std(socket).context("Failed to use activated DNS socket");
+    }
+
+    let port = if is_dev {
+        port_override(std::env::var(PORT_ENV).ok().as_deref()).unwrap_or(config.listen_port)
+    } else {
+        config.listen_port
+    };
+
+    let listen_addr = format!("{}:{}", config.listen_address, port);
+    let socket = match UdpSocket::bind(&listen_addr).await {
+        Ok(socket) => socket,
+        Err(e) if is_dev && is_bind_refused(e.kind()) => {
+            warn!("Cannot bind {} ({}), picking a free port", listen_addr, e);
+            UdpSocket::bind(format!("{}:0", config.listen_address))
+                .await
+                .context("Failed to bind DNS socket on a free port")?
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to bind DNS socket on {}", listen_addr))
+        }
+    };
+
+    if is_dev {
+        let port = socket.local_addr()?.port();
+        announce_dev_port(port);
+    }
+
+    Ok(socket)
+}
+
+/// Bind the TCP listener next to the UDP socket bound at `udp_addr`.
+///
+/// TCP is bound by the daemon itself even when the UDP socket came from the
+/// service manager, so it lands on whatever address and port UDP ended up on.
+/// Failing to bind it is not fatal: DNS still works over UDP, only answers
+/// too large for a datagram can't be fetched.
+pub async fn bind_tcp(udp_addr: SocketAddr) -> Option<TcpListener> {
+    match TcpListener::bind(udp_addr).await {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            warn!("Cannot bind DNS over TCP on {} ({}), serving UDP only", udp_addr, e);
+            None
+        }
+    }
+}
+
+/// Parse a port override, ignoring empty or invalid values.
+fn port_override(value: Option<&str>) -> Option<u16> {
+    value?.trim().parse().ok().filter(|port| *port != 0)
+}
+
+/// Whether a bind error means another port should be tried.
+fn is_bind_refused(kind: ErrorKind) -> bool {
+    matches!(kind, ErrorKind::PermissionDenied | ErrorKind::AddrInUse)
+}
+
+/// Print and record the development DNS port.
+fn announce_dev_port(port: u16) {
+    if let Err(e) = std::fs::write(DNS_PORT_FILE_DEV, format!("{}\n", port)) {
+        warn!("Could not write {}: {}", DNS_PORT_FILE_DEV, e);
+    }
+
+    info!(
+        "Development DNS port is {} (test with: dig @127.0.0.1 -p {} example.com)",
+        port, port
+    );
+    // Printed on stdout so it can be picked up with `eval`
+    println!("export BLOCKANDFOCUS_DNS_PORT={}", port);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_override() {
+        assert_eq!(port_override(Some("5353")), Some(5353));
+        assert_eq!(port_override(Some(" 15353\n")), Some(15353));
+        assert_eq!(port_override(Some("0")), None);
+        assert_eq!(port_override(Some("dns")), None);
+        assert_eq!(port_override(Some("70000")), None);
+        assert_eq!(port_override(None), None);
+    }
+
+    #[test]
+    fn test_is_bind_refused() {
+        assert!(is_bind_refused(ErrorKind::PermissionDenied));
+        assert!(is_bind_refused(ErrorKind::AddrInUse));
+        assert!(!is_bind_refused(ErrorKind::AddrNotAvailable));
+    }
+}
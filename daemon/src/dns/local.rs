@@ -0,0 +1,41 @@
+//! Names that only have meaning on the local link.
+//!
+//! `.local` names are resolved by multicast DNS (Bonjour) and link-local
+//! addresses have no public reverse entries, so forwarding these queries to a
+//! public resolver only leaks local device names and slows discovery down.
+
+/// Suffixes of link-local names and reverse zones (169.254.0.0/16, fe80::/10).
+const LOCAL_SUFFIXES: &[&str] = &[
+    "local",
+    "254.169.in-addr.arpa",
+    "8.e.f.ip6.arpa",
+    "9.e.f.ip6.arpa",
+    "a.e.f.ip6.arpa",
+    "b.e.f.ip6.arpa",
+];
+
+/// Whether a name belongs to the local link and should not go to public upstreams.
+pub fn is_link_local_name(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    LOCAL_SUFFIXES
+        .iter()
+        .any(|suffix| domain == *suffix || domain.ends_with(&format!(".{}", suffix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_local_names() {
+        assert!(is_link_local_name("printer.local."));
+        assert!(is_link_local_name("Office-Printer.LOCAL"));
+        assert!(is_link_local_name("12.1.254.169.in-addr.arpa."));
+        assert!(is_link_local_name(
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.e.f.ip6.arpa."
+        ));
+        assert!(!is_link_local_name("localhost"));
+        assert!(!is_link_local_name("notlocal"));
+        assert!(!is_link_local_name("1.1.1.1.in-addr.arpa."));
+    }
+}
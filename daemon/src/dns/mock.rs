@@ -0,0 +1,154 @@
+//! Deterministic in-memory upstream for tests.
+//!
+//! `MockUpstream` answers from a fixed table instead of the network, and can
+//! be told to fail or to answer slowly, so forwarding, caching, failover and
+//! SERVFAIL paths can be tested repeatably.
+
+use hickory_proto::op::{Message, Query, ResponseCode};
+use hickory_proto::rr::rdata::{CNAME, SOA};
+use hickory_proto::rr::{Name, RData, Record, RecordType};
+use hickory_proto::ProtoError;
+use hickory_resolver::ResolveError;
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::upstream::{empty_response, Resolution};
+use super::Resolve;
+
+/// TTL of the mock's answers.
+pub const MOCK_TTL: u32 = 300;
+
+/// How a mock upstream fails every query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockFailure {
+    /// No answer within the timeout
+    Timeout,
+    /// The connection broke
+    ConnectionLost,
+}
+
+/// An upstream answering from a fixed table.
+///
+/// Names without an entry are answered with NXDOMAIN, unless a default
+/// answer is set. Every query is counted, failed ones included.
+#[derive(Default)]
+pub struct MockUpstream {
+    answers: HashMap<String, Vec<IpAddr>>,
+    aliases: HashMap<String, Name>,
+    default_answer: Option<IpAddr>,
+    latency: Duration,
+    failure: Mutex<Option<MockFailure>>,
+    queries: AtomicUsize,
+}
+
+impl MockUpstream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `name` with `addr` (as A or AAAA, by address family).
+    pub fn answer(mut self, name: &str, addr: IpAddr) -> Self {
+        self.answers.entry(normalize(name)).or_default().push(addr);
+        self
+    }
+
+    /// Answer `name` with a CNAME to `target`, followed by the target's answer.
+    pub fn alias(mut self, name: &str, target: &str) -> Self {
+        let target = Name::from_ascii(target).expect("valid alias target");
+        self.aliases.insert(normalize(name), target);
+        self
+    }
+
+    /// Answer names without an entry with `addr` instead of NXDOMAIN.
+    pub fn default_answer(mut self, addr: IpAddr) -> Self {
+        self.default_answer = Some(addr);
+        self
+    }
+
+    /// Wait `latency` before answering or failing.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Fail every query from now on, or answer again with `None`.
+    pub fn set_failure(&self, failure: Option<MockFailure>) {
+        *self.failure.lock().unwrap() = failure;
+    }
+
+    /// Queries received so far.
+    pub fn queries(&self) -> usize {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    async fn lookup(&self, name: &Name, record_type: RecordType) -> Result<Message, ResolveError> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let failure = *self.failure.lock().unwrap();
+        let kind = match failure {
+            Some(MockFailure::Timeout) => Some(io::ErrorKind::TimedOut),
+            Some(MockFailure::ConnectionLost) => Some(io::ErrorKind::ConnectionReset),
+            None => None,
+        };
+        if let Some(kind) = kind {
+            return Err(ProtoError::from(io::Error::from(kind)).into());
+        }
+
+        let alias = self.aliases.get(&normalize(&name.to_string()));
+        let target = alias.unwrap_or(name);
+        let addrs = match self.answers.get(&normalize(&target.to_string())) {
+            Some(addrs) => addrs.as_slice(),
+            None => match &self.default_answer {
+                Some(addr) => std::slice::from_ref(addr),
+                None => return Err(no_records(name, record_type, ResponseCode::NXDomain)),
+            },
+        };
+
+        let mut response = empty_response(name, record_type);
+        if let Some(alias) = alias {
+            let cname = RData::CNAME(CNAME(alias.clone()));
+            response.add_answer(Record::from_rdata(name.clone(), MOCK_TTL, cname));
+        }
+        for addr in addrs {
+            let rdata = match (addr, record_type) {
+                (IpAddr::V4(v4), RecordType::A) => RData::A((*v4).into()),
+                (IpAddr::V6(v6), RecordType::AAAA) => RData::AAAA((*v6).into()),
+                _ => continue,
+            };
+            response.add_answer(Record::from_rdata(target.clone(), MOCK_TTL, rdata));
+        }
+        if response.answers().iter().all(|record| record.record_type() == RecordType::CNAME) {
+            // The name exists, just not with this type
+            return Err(no_records(name, record_type, ResponseCode::NoError));
+        }
+        Ok(response)
+    }
+}
+
+impl Resolve for MockUpstream {
+    fn resolve<'a>(&'a self, name: &'a Name, record_type: RecordType) -> Resolution<'a> {
+        Box::pin(self.lookup(name, record_type))
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+/// The error hickory reports when a server answers without records, with
+/// an SOA allowing the answer to be cached for `MOCK_TTL`.
+fn no_records(name: &Name, record_type: RecordType, code: ResponseCode) -> ResolveError {
+    let query = Box::new(Query::query(name.clone(), record_type));
+    let root = Name::root();
+    let soa = SOA::new(root.clone(), root, 1, 7200, 3600, 86400, MOCK_TTL);
+    let soa = Box::new(Record::from_rdata(name.clone(), MOCK_TTL, soa));
+    let negative_ttl = Some(MOCK_TTL);
+    ProtoError::nx_error(query, Some(soa), None, negative_ttl, code, true, None).into()
+}
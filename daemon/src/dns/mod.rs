@@ -1,9 +1,36 @@
 //! DNS server implementation.
 
+mod activation;
 mod blocker;
+mod bloom;
+mod cache;
+mod ecs;
+mod forward;
+mod listener;
+mod local;
+#[cfg(test)]
+pub mod mock;
+mod parse;
+mod policy;
+mod pool;
+mod rebinding;
+mod scoring;
 mod server;
+mod trie;
+mod ttl;
 mod upstream;
 
 pub use blocker::DomainBlocker;
+pub use cache::DnsCache;
+pub use policy::{BlockingPolicy, BypassWindow};
 pub use server::DnsServer;
-pub use upstream::UpstreamResolver;
+pub use upstream::{Resolve, UpstreamResolver};
+
+/// Normalize a domain name for comparison.
+fn normalize_domain(domain: &str) -> String {
+    domain
+        .to_lowercase()
+        .trim()
+        .trim_end_matches('.')
+        .to_string()
+}
@@ -0,0 +1,194 @@
+//! Validation of incoming DNS queries.
+//!
+//! Anything can arrive on the DNS port. A packet is handled only if it is a
+//! standard query with exactly one question, within the size and name limits.
+//! Anything else that has a query header is answered with FORMERR (or NOTIMP
+//! for other opcodes). Packets too short for a header, and responses, are
+//! dropped: answering them could feed a loop between two servers.
+//!
+//! This file only depends on `hickory_proto`, so the fuzz target in `fuzz/`
+//! can include it as it is.
+
+use hickory_proto::op::{Header, Message, MessageType, OpCode, ResponseCode};
+use hickory_proto::serialize::binary::{BinDecodable, BinDecoder};
+
+/// Largest query accepted over UDP (the limit without EDNS).
+pub const MAX_QUERY_LEN: usize = 512;
+
+/// Size of the fixed DNS header.
+const HEADER_LEN: usize = 12;
+
+/// Longest label and name, per RFC 1035.
+const MAX_LABEL_LEN: usize = 63;
+const MAX_NAME_LEN: usize = 255;
+
+/// Why a packet was not handled as a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// Too short for a DNS header
+    Truncated,
+    /// A response rather than a query
+    NotAQuery,
+    /// An opcode other than QUERY
+    Unsupported,
+    /// Over `MAX_QUERY_LEN`
+    TooLarge,
+    /// Not exactly one question
+    QuestionCount(u16),
+    /// The message doesn't decode
+    Unparseable,
+    /// A label or name over the RFC 1035 limits
+    NameTooLong,
+}
+
+/// A packet that was not handled as a query.
+#[derive(Debug)]
+pub struct Rejected {
+    pub reason: Malformed,
+    /// Error response to send back, if the packet deserves one
+    pub response: Option<Box<Message>>,
+}
+
+/// Parse a packet into a query with exactly one question.
+pub fn parse_query(packet: &[u8]) -> Result<Message, Rejected> {
+    if packet.len() < HEADER_LEN {
+        return Err(Rejected {
+            reason: Malformed::Truncated,
+            response: None,
+        });
+    }
+    let header = Header::read(&mut BinDecoder::new(&packet[..HEADER_LEN])).map_err(|_| {
+        Rejected {
+            reason: Malformed::Truncated,
+            response: None,
+        }
+    })?;
+    if header.message_type() != MessageType::Query {
+        return Err(Rejected {
+            reason: Malformed::NotAQuery,
+            response: None,
+        });
+    }
+
+    let reject = |reason, code| Rejected {
+        reason,
+        response: Some(Box::new(error_response(&header, code))),
+    };
+    if header.op_code() != OpCode::Query {
+        return Err(reject(Malformed::Unsupported, ResponseCode::NotImp));
+    }
+    if packet.len() > MAX_QUERY_LEN {
+        return Err(reject(Malformed::TooLarge, ResponseCode::FormErr));
+    }
+    if header.query_count() != 1 {
+        let count = header.query_count();
+        return Err(reject(Malformed::QuestionCount(count), ResponseCode::FormErr));
+    }
+
+    let message = Message::from_bytes(packet)
+        .map_err(|_| reject(Malformed::Unparseable, ResponseCode::FormErr))?;
+    let name = message.queries()[0].name();
+    let labels_ok = name.iter().all(|label| label.len() <= MAX_LABEL_LEN);
+    if !labels_ok || name.len() > MAX_NAME_LEN {
+        return Err(reject(Malformed::NameTooLong, ResponseCode::FormErr));
+    }
+
+    Ok(message)
+}
+
+/// A response to `header` carrying only an error code; the question isn't
+/// echoed, since it couldn't be trusted.
+fn error_response(header: &Header, code: ResponseCode) -> Message {
+    let mut response = Message::new();
+    response.set_id(header.id());
+    response.set_message_type(MessageType::Response);
+    response.set_op_code(header.op_code());
+    response.set_recursion_desired(header.recursion_desired());
+    response.set_recursion_available(true);
+    response.set_response_code(code);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::Query;
+    use hickory_proto::rr::{Name, RecordType};
+    use hickory_proto::serialize::binary::BinEncodable;
+
+    fn query(name: &str) -> Vec<u8> {
+        let mut message = Message::new();
+        message.set_id(0x1234);
+        message.set_message_type(MessageType::Query);
+        message.set_recursion_desired(true);
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        message.to_bytes().unwrap()
+    }
+
+    fn rejected(packet: &[u8]) -> (Malformed, Option<ResponseCode>) {
+        let rejected = parse_query(packet).unwrap_err();
+        let code = rejected.response.map(|response| {
+            assert_eq!(response.id(), 0x1234);
+            response.response_code()
+        });
+        (rejected.reason, code)
+    }
+
+    #[test]
+    fn test_rejects_malformed_queries() {
+        let valid = query("example.com.");
+        assert_eq!(parse_query(&valid).unwrap().queries().len(), 1);
+
+        assert_eq!(rejected(&valid[..5]), (Malformed::Truncated, None));
+
+        let mut response = valid.clone();
+        response[2] |= 0x80;
+        assert_eq!(rejected(&response), (Malformed::NotAQuery, None));
+
+        let mut notify = valid.clone();
+        notify[2] |= 4 << 3;
+        assert_eq!(rejected(&notify), (Malformed::Unsupported, Some(ResponseCode::NotImp)));
+
+        let mut two_questions = valid.clone();
+        two_questions[5] = 2;
+        let formerr = Some(ResponseCode::FormErr);
+        assert_eq!(rejected(&two_questions), (Malformed::QuestionCount(2), formerr));
+
+        // The question's name runs past the end of the packet
+        let cut = &valid[..valid.len() - 6];
+        assert_eq!(rejected(cut), (Malformed::Unparseable, formerr));
+
+        let mut padded = valid.clone();
+        padded.resize(MAX_QUERY_LEN + 1, 0);
+        assert_eq!(rejected(&padded), (Malformed::TooLarge, formerr));
+    }
+
+    #[test]
+    fn test_garbage_never_panics() {
+        // Every truncation and single-byte corruption of a valid query
+        let valid = query("www.example.com.");
+        for len in 0..valid.len() {
+            let _ = parse_query(&valid[..len]);
+        }
+        for i in 0..valid.len() {
+            for byte in [0x00, 0x3f, 0x40, 0xc0, 0xff] {
+                let mut packet = valid.clone();
+                packet[i] = byte;
+                let _ = parse_query(&packet);
+            }
+        }
+
+        // Pseudo-random packets from a fixed seed
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        for _ in 0..2000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let len = (seed % 80) as usize;
+            let packet: Vec<u8> = (0..len).map(|i| (seed >> (i % 8 * 8)) as u8).collect();
+            if let Ok(message) = parse_query(&packet) {
+                assert_eq!(message.queries().len(), 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! The blocking decision, made from a published snapshot of its inputs.
+//!
+//! The DNS server answers every query from the latest `BlockingPolicy`
+//! without taking the state lock; `AppState::publish_policy` replaces it
+//! whenever something the decision reads changes. Anything depending on the
+//! time (schedule rules, bypass windows, budget weeks) is evaluated per query.
+
+use crate::budget::{DomainAllowances, GroupBudgets};
+use crate::clients::ClientPolicies;
+use crate::schedule::ScheduleEngine;
+use crate::{today, week_start};
+use blockandfocus_shared::{ClientBlocking, ClientProfile, Config, Schedule};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use super::DomainBlocker;
+
+/// Everything deciding whether a query is blocked, as of its publication.
+pub struct BlockingPolicy {
+    pub blocker: Arc<DomainBlocker>,
+    pub essential: Arc<DomainBlocker>,
+    pub groups: Arc<GroupBudgets>,
+    pub allowances: Arc<DomainAllowances>,
+    pub schedule: ScheduleEngine,
+    pub clients: ClientPolicies,
+    pub bypass: BypassWindow,
+    /// Local week (its Monday, YYYY-MM-DD) `group_minutes` counts
+    pub week: String,
+    /// Minutes used this week by each group with a budget
+    pub group_minutes: HashMap<String, u32>,
+    /// Local date `domain_minutes` counts
+    pub date: String,
+    /// Minutes used today of each allowance
+    pub domain_minutes: HashMap<String, u32>,
+}
+
+/// The running bypass, if any.
+#[derive(Default, Clone)]
+pub struct BypassWindow {
+    /// When the bypass takes effect (after a soft-start countdown)
+    pub starts_at: Option<i64>,
+    pub until: Option<i64>,
+    /// Blocklist entries that stay blocked during the first minutes
+    pub excluded: Vec<String>,
+    pub excluded_until: i64,
+}
+
+impl BypassWindow {
+    /// Whether the bypass is in effect (past its countdown and not expired).
+    pub fn is_active(&self, now: i64) -> bool {
+        let started = self.starts_at.is_none_or(|starts_at| now >= starts_at);
+        started && self.until.is_some_and(|until| now < until)
+    }
+}
+
+/// Blocks nothing, until the first policy is published.
+impl Default for BlockingPolicy {
+    fn default() -> Self {
+        Self {
+            blocker: Arc::new(DomainBlocker::new(&[])),
+            essential: Arc::new(DomainBlocker::new(&[])),
+            groups: Arc::new(GroupBudgets::new(&[])),
+            allowances: Arc::new(DomainAllowances::new(&[])),
+            schedule: ScheduleEngine::new(Schedule::default()),
+            clients: ClientPolicies::default(),
+            bypass: BypassWindow::default(),
+            week: String::new(),
+            group_minutes: HashMap::new(),
+            date: String::new(),
+            domain_minutes: HashMap::new(),
+        }
+    }
+}
+
+impl BlockingPolicy {
+    /// The profile of the device at `ip`, if one matches.
+    pub fn profile_for(&self, ip: IpAddr) -> Option<&ClientProfile> {
+        self.clients.profile_for(ip)
+    }
+
+    /// Get the blocklist entry that blocks this domain right now for a
+    /// device of `profile` (or this Mac, without one), if any.
+    ///
+    /// During the first minutes of a soft-start bypass the most-blocked
+    /// entries remain blocked. Outside blocking hours, wind-down rules may
+    /// already block some groups. Essential services are never blocked. A
+    /// profile decides whether the schedule applies and whether bypasses do,
+    /// and may block groups of its own.
+    pub fn blocked_entry_for(
+        &self,
+        config: &Config,
+        domain: &str,
+        profile: Option<&ClientProfile>,
+    ) -> Option<String> {
+        if self.essential.matching_entry(domain).is_some() {
+            return None;
+        }
+
+        let scheduled = match profile.map(|p| p.blocking) {
+            Some(ClientBlocking::Never) => return None,
+            Some(ClientBlocking::Always) => config.blocking.enabled,
+            Some(ClientBlocking::Schedule) | None => {
+                config.blocking.enabled && self.schedule.is_blocking_time()
+            }
+        };
+        let entry = if scheduled {
+            match self.blocker.matching_entry(domain) {
+                Some(entry) => entry.to_string(),
+                None => self
+                    .exhausted_group_entry(domain)
+                    .or_else(|| self.exhausted_allowance_entry(domain))
+                    .or_else(|| Some(self.groups.matching_named(domain, &profile?.groups)?.1))?
+                    .to_string(),
+            }
+        } else {
+            self.wind_down_entry(config, domain)?.to_string()
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        if !self.bypass.is_active(now) || profile.is_some_and(|p| !p.allow_bypass) {
+            return Some(entry);
+        }
+
+        if now < self.bypass.excluded_until && self.bypass.excluded.contains(&entry) {
+            return Some(entry);
+        }
+
+        None
+    }
+
+    /// Get the group entry matching a domain if its group is blocked.
+    ///
+    /// Groups without a budget are always blocked; budgeted groups only once
+    /// this week's estimated usage reaches the budget.
+    fn exhausted_group_entry(&self, domain: &str) -> Option<&str> {
+        let (group, entry) = self.groups.matching_group(domain)?;
+        match group.weekly_budget_minutes {
+            None => Some(entry),
+            Some(budget) => {
+                let used = match self.week == week_start() {
+                    true => self.group_minutes.get(&group.name).copied().unwrap_or(0),
+                    false => 0,
+                };
+                (used >= budget).then_some(entry)
+            }
+        }
+    }
+
+    /// Get the group entry matching a domain if a wind-down rule blocks its group.
+    fn wind_down_entry(&self, config: &Config, domain: &str) -> Option<&str> {
+        if !config.blocking.enabled {
+            return None;
+        }
+        let (group, entry) = self.groups.matching_group(domain)?;
+        self.schedule
+            .wind_down_groups()
+            .contains(&group.name)
+            .then_some(entry)
+    }
+
+    /// Get the allowance entry matching a domain once today's allowance is used up.
+    fn exhausted_allowance_entry(&self, domain: &str) -> Option<&str> {
+        let (entry, daily_minutes) = self.allowances.matching(domain)?;
+        let used = match self.date == today() {
+            true => self.domain_minutes.get(entry).copied().unwrap_or(0),
+            false => 0,
+        };
+        (used >= daily_minutes).then_some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::{BlocklistGroup, DomainAllowance};
+
+    #[test]
+    fn test_budgets_count_for_their_week_and_day() {
+        let config = Config::default();
+        let mut policy = BlockingPolicy {
+            blocker: Arc::new(DomainBlocker::new(&["games.test".parse().unwrap()])),
+            groups: Arc::new(GroupBudgets::new(&[BlocklistGroup {
+                name: "Video".to_string(),
+                domains: vec!["video.test".parse().unwrap()],
+                weekly_budget_minutes: Some(60),
+                enabled: true,
+            }])),
+            allowances: Arc::new(DomainAllowances::new(&[DomainAllowance {
+                domain: "news.test".parse().unwrap(),
+                daily_minutes: 20,
+            }])),
+            week: week_start(),
+            group_minutes: HashMap::from([("Video".to_string(), 60)]),
+            date: today(),
+            domain_minutes: HashMap::from([("news.test".to_string(), 20)]),
+            ..BlockingPolicy::default()
+        };
+        let blocked =
+            |policy: &BlockingPolicy, domain| policy.blocked_entry_for(&config, domain, None);
+
+        // A disabled schedule blocks all the time
+        assert_eq!(blocked(&policy, "www.video.test").as_deref(), Some("video.test"));
+        assert_eq!(blocked(&policy, "news.test").as_deref(), Some("news.test"));
+        assert_eq!(blocked(&policy, "games.test").as_deref(), Some("games.test"));
+
+        // Usage from another week or day no longer counts
+        policy.week = "2001-01-01".to_string();
+        policy.date = "2001-01-01".to_string();
+        assert_eq!(blocked(&policy, "www.video.test"), None);
+        assert_eq!(blocked(&policy, "news.test"), None);
+
+        // A running bypass lets everything through
+        policy.bypass.until = Some(chrono::Utc::now().timestamp() + 60);
+        assert_eq!(blocked(&policy, "games.test"), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Reusable packet buffers for the DNS path.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// Pool of byte buffers, so queries and responses do not allocate per packet.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    capacity: usize,
+    max_pooled: usize,
+}
+
+impl BufferPool {
+    /// Create a pool of buffers with the given capacity, keeping at most `max_pooled` idle.
+    pub fn new(capacity: usize, max_pooled: usize) -> Arc<Self> {
+        Arc::new(Self {
+            buffers: Mutex::new(Vec::new()),
+            capacity,
+            max_pooled,
+        })
+    }
+
+    /// Take an empty buffer from the pool; it is returned when dropped.
+    pub fn get(self: &Arc<Self>) -> PooledBuffer {
+        let buffer = self
+            .buffers
+            .lock()
+            .ok()
+            .and_then(|mut buffers| buffers.pop())
+            .unwrap_or_else(|| Vec::with_capacity(self.capacity));
+
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    /// Number of idle buffers.
+    #[cfg(test)]
+    fn idle(&self) -> usize {
+        self.buffers.lock().map(|buffers| buffers.len()).unwrap_or(0)
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`].
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        // Buffers that grew far beyond the usual size are not kept
+        if buffer.capacity() > self.pool.capacity * 4 {
+            return;
+        }
+
+        buffer.clear();
+        if let Ok(mut buffers) = self.pool.buffers.lock() {
+            if buffers.len() < self.pool.max_pooled {
+                buffers.push(buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let pool = BufferPool::new(512, 2);
+
+        let mut first = pool.get();
+        first.extend_from_slice(b"query");
+        let ptr = first.as_ptr();
+        drop(first);
+        assert_eq!(pool.idle(), 1);
+
+        let second = pool.get();
+        assert!(second.is_empty());
+        assert_eq!(second.as_ptr(), ptr);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[test]
+    fn test_pool_size_is_bounded() {
+        let pool = BufferPool::new(512, 2);
+
+        let buffers: Vec<PooledBuffer> = (0..5).map(|_| pool.get()).collect();
+        drop(buffers);
+        assert_eq!(pool.idle(), 2);
+
+        let mut large = pool.get();
+        large.reserve(512 * 8);
+        drop(large);
+        assert_eq!(pool.idle(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+//! DNS rebinding protection.
+//!
+//! Strips answers pointing at private, loopback or link-local addresses from
+//! upstream responses for public domains, so a hostile site cannot use DNS to
+//! reach devices on the local network.
+
+use hickory_proto::op::Message;
+use hickory_proto::rr::RData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::normalize_domain;
+
+/// Suffixes that are never public and may legitimately resolve to private addresses.
+const LOCAL_SUFFIXES: &[&str] = &["localhost", "local", "lan", "home.arpa", "internal"];
+
+/// Filter that removes private-range answers from forwarded responses.
+pub struct RebindingFilter {
+    allowlist: Vec<String>,
+}
+
+impl RebindingFilter {
+    /// Create a new filter with the given allowlist of split-horizon names.
+    pub fn new(allowlist: &[String]) -> Self {
+        Self {
+            allowlist: allowlist.iter().map(|d| normalize_domain(d)).collect(),
+        }
+    }
+
+    /// Remove private-range A/AAAA answers from the response.
+    ///
+    /// Returns the number of records removed; the caller logs it, since only it
+    /// knows the log privacy level. Names on the allowlist (and their
+    /// subdomains) as well as non-public suffixes like `.local` are left untouched.
+    pub fn filter(&self, query_domain: &str, response: &mut Message) -> usize {
+        let normalized = normalize_domain(query_domain);
+        if self.is_exempt(&normalized) {
+            return 0;
+        }
+
+        let answers = response.answers_mut();
+        let before = answers.len();
+        answers.retain(|record| match record.data() {
+            RData::A(a) => !is_private_ip(IpAddr::V4(a.0)),
+            RData::AAAA(aaaa) => !is_private_ip(IpAddr::V6(aaaa.0)),
+            _ => true,
+        });
+        before - answers.len()
+    }
+
+    /// Check whether a domain is exempt from rebinding protection.
+    fn is_exempt(&self, domain: &str) -> bool {
+        let matches = |suffix: &str| domain == suffix || domain.ends_with(&format!(".{}", suffix));
+
+        LOCAL_SUFFIXES.iter().any(|s| matches(s)) || self.allowlist.iter().any(|s| matches(s))
+    }
+}
+
+/// Check whether an address belongs to a private, loopback or link-local range.
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_private_ipv4(v4),
+        IpAddr::V6(v6) => {
+            if let Some(mapped) = v6.to_ipv4_mapped() {
+                return is_private_ipv4(mapped);
+            }
+            is_private_ipv6(v6)
+        }
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        // Carrier-grade NAT (100.64.0.0/10)
+        || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+        // "This network" (0.0.0.0/8)
+        || octets[0] == 0
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local (fc00::/7)
+        || (first & 0xfe00) == 0xfc00
+        // Link-local (fe80::/10)
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::rr::{Name, Record};
+    use std::str::FromStr;
+
+    fn response_with(name: &str, ips: &[&str]) -> Message {
+        let name = Name::from_str(name).unwrap();
+        let mut message = Message::new();
+        for ip in ips {
+            let rdata = match ip.parse::<IpAddr>().unwrap() {
+                IpAddr::V4(v4) => RData::A(v4.into()),
+                IpAddr::V6(v6) => RData::AAAA(v6.into()),
+            };
+            message.add_answer(Record::from_rdata(name.clone(), 300, rdata));
+        }
+        message
+    }
+
+    #[test]
+    fn test_private_ranges() {
+        assert!(is_private_ip("10.0.0.1".parse().unwrap()));
+        assert!(is_private_ip("172.16.5.4".parse().unwrap()));
+        assert!(is_private_ip("192.168.1.1".parse().unwrap()));
+        assert!(is_private_ip("127.0.0.1".parse().unwrap()));
+        assert!(is_private_ip("169.254.0.1".parse().unwrap()));
+        assert!(is_private_ip("100.64.0.1".parse().unwrap()));
+        assert!(is_private_ip("fd00::1".parse().unwrap()));
+        assert!(is_private_ip("fe80::1".parse().unwrap()));
+        assert!(is_private_ip("::1".parse().unwrap()));
+        assert!(is_private_ip("::ffff:192.168.0.1".parse().unwrap()));
+
+        assert!(!is_private_ip("1.1.1.1".parse().unwrap()));
+        assert!(!is_private_ip("172.32.0.1".parse().unwrap()));
+        assert!(!is_private_ip("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_strips_private_answers() {
+        let filter = RebindingFilter::new(&[]);
+        let mut response = response_with("evil.com.", &["192.168.1.1", "93.184.216.34"]);
+
+        assert_eq!(filter.filter("evil.com.", &mut response), 1);
+        assert_eq!(response.answers().len(), 1);
+    }
+
+    #[test]
+    fn test_allowlist_and_local_names_exempt() {
+        let filter = RebindingFilter::new(&["corp.example.com".to_string()]);
+
+        let mut response = response_with("git.corp.example.com.", &["10.1.2.3"]);
+        assert_eq!(filter.filter("git.corp.example.com.", &mut response), 0);
+        assert_eq!(response.answers().len(), 1);
+
+        let mut response = response_with("printer.local.", &["192.168.1.20"]);
+        assert_eq!(filter.filter("printer.local.", &mut response), 0);
+        assert_eq!(response.answers().len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! Adaptive ordering of upstream servers.
+//!
+//! Each upstream is scored on its recent latency and failure rate, and queries
+//! go to the best-scoring one first. A demoted upstream is re-measured every
+//! `REPROBE_INTERVAL` so it can win its place back once it recovers.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Weight of the newest measurement in the moving averages.
+const SMOOTHING: f64 = 0.2;
+
+/// Latency (ms) a failure rate of 100% is worth when comparing upstreams.
+const FAILURE_PENALTY_MS: f64 = 1000.0;
+
+/// How often an upstream that isn't preferred is measured again.
+const REPROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Recent performance of one upstream.
+struct Score {
+    /// Smoothed response time in milliseconds (None until first measured)
+    latency_ms: Option<f64>,
+    /// Smoothed share of queries that failed (0.0 to 1.0)
+    failure_rate: f64,
+    last_tried: Option<Instant>,
+}
+
+impl Score {
+    /// Lower is better. Unmeasured upstreams come first so each gets tried.
+    fn value(&self) -> f64 {
+        self.latency_ms.unwrap_or(0.0) + self.failure_rate * FAILURE_PENALTY_MS
+    }
+}
+
+/// Scores of the configured upstreams, by index.
+pub struct UpstreamScores {
+    names: Vec<String>,
+    scores: Mutex<Vec<Score>>,
+    preferred: Mutex<Option<usize>>,
+}
+
+impl UpstreamScores {
+    /// Create unmeasured scores for the named upstreams.
+    pub fn new(names: Vec<String>) -> Self {
+        let scores = (0..names.len())
+            .map(|_| Score {
+                latency_ms: None,
+                failure_rate: 0.0,
+                last_tried: None,
+            })
+            .collect();
+
+        Self {
+            names,
+            scores: Mutex::new(scores),
+            preferred: Mutex::new(None),
+        }
+    }
+
+    /// Upstream indexes, best first (ties keep the configured order).
+    pub fn order(&self) -> Vec<usize> {
+        let scores = self.scores.lock().unwrap();
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| scores[a].value().total_cmp(&scores[b].value()));
+        order
+    }
+
+    /// A demoted upstream due to be measured again, if any. It counts as
+    /// tried, so only one query probes it per interval.
+    pub fn due_for_probe(&self) -> Option<usize> {
+        let best = *self.order().first()?;
+        let mut scores = self.scores.lock().unwrap();
+        let (index, score) = scores.iter_mut().enumerate().find(|(index, score)| {
+            *index != best && score.last_tried.is_none_or(|at| at.elapsed() >= REPROBE_INTERVAL)
+        })?;
+        score.last_tried = Some(Instant::now());
+        Some(index)
+    }
+
+    /// Record how a query to upstream `index` went: its response time, or
+    /// None if it failed.
+    pub fn record(&self, index: usize, latency: Option<Duration>) {
+        {
+            let mut scores = self.scores.lock().unwrap();
+            let Some(score) = scores.get_mut(index) else {
+                return;
+            };
+            score.last_tried = Some(Instant::now());
+            match latency {
+                Some(latency) => {
+                    let ms = latency.as_secs_f64() * 1000.0;
+                    score.latency_ms = Some(match score.latency_ms {
+                        Some(avg) => avg + SMOOTHING * (ms - avg),
+                        None => ms,
+                    });
+                    score.failure_rate *= 1.0 - SMOOTHING;
+                }
+                None => score.failure_rate += SMOOTHING * (1.0 - score.failure_rate),
+            }
+        }
+
+        let best = self.order().first().copied();
+        let mut preferred = self.preferred.lock().unwrap();
+        if *preferred != best {
+            if let (Some(_), Some(best)) = (*preferred, best) {
+                info!(upstream = %self.names[best], "Preferred upstream changed");
+            }
+            *preferred = best;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(count: usize) -> UpstreamScores {
+        UpstreamScores::new((0..count).map(|i| format!("upstream {}", i)).collect())
+    }
+
+    fn ms(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn test_prefers_fastest_and_demotes_failing() {
+        let scores = named(3);
+        assert_eq!(scores.order(), vec![0, 1, 2]);
+
+        scores.record(0, ms(80));
+        scores.record(1, ms(20));
+        scores.record(2, ms(40));
+        assert_eq!(scores.order(), vec![1, 2, 0]);
+        assert_eq!(*scores.preferred.lock().unwrap(), Some(1));
+
+        // A few failures outweigh a latency advantage
+        for _ in 0..3 {
+            scores.record(1, None);
+        }
+        assert_eq!(scores.order(), vec![2, 0, 1]);
+        assert_eq!(*scores.preferred.lock().unwrap(), Some(2));
+
+        // Recovers as it answers again
+        for _ in 0..20 {
+            scores.record(1, ms(20));
+        }
+        assert_eq!(scores.order()[0], 1);
+    }
+
+    #[test]
+    fn test_reprobes_demoted_upstreams() {
+        let scores = named(2);
+        scores.record(0, ms(10));
+        scores.record(1, None);
+
+        // Just tried, so not due yet
+        assert_eq!(scores.due_for_probe(), None);
+
+        scores.scores.lock().unwrap()[1].last_tried =
+            Instant::now().checked_sub(REPROBE_INTERVAL);
+        assert_eq!(scores.due_for_probe(), Some(1));
+        assert_eq!(scores.due_for_probe(), None);
+    }
+}
@@ -1,72 +1,209 @@
-//! DNS server implementation using UDP sockets directly.
+//! DNS server implementation using UDP and TCP sockets directly.
+//!
+//! Both transports share one answering path. Over UDP an answer larger than
+//! the client accepts is sent with only the header and question and the TC
+//! flag set, and the client repeats the query over TCP, where messages carry
+//! a two-byte length prefix (RFC 1035 section 4.2.2).
 
-use crate::AppState;
+use crate::config::ConfigManager;
+use crate::diagnostics::ProbeTracker;
+use crate::querylog::QueryLog;
+use crate::{AppState, QueryCounters};
+use arc_swap::ArcSwap;
 use anyhow::{Context, Result};
+use blockandfocus_shared::{
+    BlockMode, BlockingConfig, Config, LinkLocalPolicy, QueryAction, QueryLogEntry,
+};
 use hickory_proto::op::{Message, MessageType, OpCode, ResponseCode};
 use hickory_proto::rr::{Name, RData, Record, RecordType};
-use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::UdpSocket;
-use tokio::sync::RwLock;
+use hickory_proto::serialize::binary::{BinEncodable, BinEncoder};
+use hickory_resolver::ResolveError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::{mpsc, RwLock, Semaphore};
 use tracing::{debug, error, info, warn};
 
-use super::upstream::UpstreamResolver;
+use super::cache::DnsCache;
+use super::ecs::strip_client_subnet;
+use super::forward::{ZoneAnswer, ZoneForwarders};
+use super::listener::{bind_tcp, bind_udp};
+use super::local::is_link_local_name;
+use super::parse::{parse_query, MAX_QUERY_LEN};
+use super::policy::BlockingPolicy;
+use super::pool::{BufferPool, PooledBuffer};
+use super::rebinding::RebindingFilter;
+use super::ttl::TtlPolicy;
+use super::upstream::no_records_response;
+use super::{Resolve, UpstreamResolver};
+
+/// Receive buffer size: one byte over the largest query accepted, so an
+/// oversized query is noticed rather than silently cut short.
+const RECEIVE_BUFFER: usize = MAX_QUERY_LEN + 1;
+
+/// Blocking settings answering probes, whatever blocked queries get.
+static NULL_IP: LazyLock<BlockingConfig> = LazyLock::new(BlockingConfig::default);
+
+/// Idle packet buffers kept for reuse.
+const POOLED_BUFFERS: usize = 256;
+
+/// TCP connections served at once; further ones are closed right away.
+const MAX_TCP_CONNECTIONS: usize = 64;
+
+/// How long a TCP connection may sit idle, or take to send a query or
+/// receive an answer, before it is closed.
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Query activity waiting to be recorded; more is dropped rather than
+/// slowing answers down.
+const ACTIVITY_CAPACITY: usize = 4096;
+
+/// Query activity recorded per state lock.
+const ACTIVITY_BATCH: usize = 256;
 
 /// DNS server that handles blocking and forwarding.
 pub struct DnsServer;
 
+/// Shared resources used by every query handler task.
+///
+/// Nothing here is behind the state lock: queries are answered from the
+/// published config and blocking policy, counted in atomic counters, and
+/// their activity is recorded in the state by a separate task.
+struct ServerContext {
+    config: Arc<ConfigManager>,
+    policy: Arc<ArcSwap<BlockingPolicy>>,
+    queries: Arc<QueryCounters>,
+    probes: Arc<ProbeTracker>,
+    activity: mpsc::Sender<QueryActivity>,
+    upstream: Arc<dyn Resolve>,
+    zones: ZoneForwarders,
+    link_local: LinkLocalPolicy,
+    force_ipv4: bool,
+    rebinding: Option<RebindingFilter>,
+    ttl: TtlPolicy,
+    cache: Arc<Mutex<DnsCache>>,
+    query_log: Arc<QueryLog>,
+    buffers: Arc<BufferPool>,
+    tcp_connections: Arc<Semaphore>,
+}
+
+/// What answering a query did to usage and the activity history.
+enum QueryActivity {
+    /// A query resolved for the domain, counting towards usage and budgets
+    Resolved(String),
+    /// A query blocked by the blocklist entry
+    Blocked(String),
+}
+
+/// A response to send, with the largest UDP payload the client accepts.
+struct Reply {
+    message: Message,
+    max_payload: u16,
+}
+
 impl DnsServer {
     /// Run the DNS server.
-    pub async fn run(state: Arc<RwLock<AppState>>) -> Result<()> {
+    pub async fn run(state: Arc<RwLock<AppState>>, is_dev: bool) -> Result<()> {
+        let config = state.read().await.config.get();
+        let idle_timeout = config.dns.upstream_idle_timeout.into();
+        let upstream = UpstreamResolver::new(&config.dns.upstream, idle_timeout)
+            .context("Failed to create upstream resolver")?;
+
+        Self::run_with(state, is_dev, Arc::new(upstream)).await
+    }
+
+    /// Run the DNS server, forwarding to `upstream` instead of the configured upstreams.
+    pub async fn run_with(
+        state: Arc<RwLock<AppState>>,
+        is_dev: bool,
+        upstream: Arc<dyn Resolve>,
+    ) -> Result<()> {
         let config = {
             let state_guard = state.read().await;
             state_guard.config.get()
         };
 
-        let listen_addr = format!(
-            "{}:{}",
+        info!(
+            "Starting DNS server on {}:{}",
             config.dns.listen_address, config.dns.listen_port
         );
 
-        info!("Starting DNS server on {}", listen_addr);
-
-        // Bind UDP socket
-        let socket = Arc::new(
-            UdpSocket::bind(&listen_addr)
-                .await
-                .with_context(|| format!("Failed to bind DNS socket on {}", listen_addr))?,
-        );
+        // Bind UDP socket, and TCP on the same address and port
+        let socket = Arc::new(bind_udp(&config.dns, is_dev).await?);
+        let listen_addr = socket.local_addr()?;
+        let tcp = bind_tcp(listen_addr).await;
+        state.write().await.dns_listen_addr = Some(listen_addr);
 
         info!("DNS server listening on {}", listen_addr);
 
-        // Initialize upstream resolver
-        let upstream = Arc::new(
-            UpstreamResolver::new(&config.dns.upstream)
-                .context("Failed to create upstream resolver")?,
-        );
+        let idle_timeout = config.dns.upstream_idle_timeout.into();
+        let zones = ZoneForwarders::new(&config.dns.forward_zones, idle_timeout);
 
-        // Main receive loop
-        let mut buf = vec![0u8; 512];
+        let rebinding = if config.dns.rebinding_protection {
+            info!("DNS rebinding protection enabled");
+            Some(RebindingFilter::new(&config.dns.rebinding_allowlist))
+        } else {
+            None
+        };
+
+        let (activity, recorded) = mpsc::channel(ACTIVITY_CAPACITY);
+        let state_guard = state.read().await;
+        let ctx = Arc::new(ServerContext {
+            config: state_guard.config.clone(),
+            policy: state_guard.policy.clone(),
+            queries: state_guard.stats.queries.clone(),
+            probes: state_guard.probes.clone(),
+            activity,
+            upstream,
+            zones,
+            link_local: config.dns.link_local_names,
+            force_ipv4: config.dns.force_ipv4,
+            rebinding,
+            ttl: TtlPolicy::from_config(&config.dns),
+            cache: state_guard.cache.clone(),
+            query_log: state_guard.query_log.clone(),
+            buffers: BufferPool::new(RECEIVE_BUFFER, POOLED_BUFFERS),
+            tcp_connections: Arc::new(Semaphore::new(MAX_TCP_CONNECTIONS)),
+        });
+        let rebind = state_guard.dns_rebind.clone();
+        drop(state_guard);
 
+        // Ends once the server stops and `ctx` with the sender is dropped
+        tokio::spawn(Self::record_activity(state, recorded));
+
+        // Main receive loop
         loop {
-            match socket.recv_from(&mut buf).await {
+            // Receive straight into a pooled buffer that is handed to the query task
+            let mut buf = ctx.buffers.get();
+            buf.resize(RECEIVE_BUFFER, 0);
+
+            let received = tokio::select! {
+                received = socket.recv_from(&mut buf) => received,
+                accepted = Self::accept(tcp.as_ref()) => {
+                    match accepted {
+                        Ok((stream, src)) => Self::spawn_connection(stream, src, &ctx),
+                        Err(e) => error!("Error accepting DNS connection: {}", e),
+                    }
+                    continue;
+                }
+                // Returning lets the supervisor bind fresh sockets
+                _ = rebind.notified() => anyhow::bail!("Listener rebind requested by the watchdog"),
+            };
+
+            match received {
                 Ok((len, src)) => {
-                    let query_data = buf[..len].to_vec();
+                    buf.truncate(len);
+                    let query_data = buf;
                     let socket_clone = socket.clone();
-                    let state_clone = state.clone();
-                    let upstream_clone = upstream.clone();
+                    let ctx_clone = ctx.clone();
 
                     // Handle query in a separate task
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_query(
-                            query_data,
-                            src,
-                            socket_clone,
-                            state_clone,
-                            upstream_clone,
-                        )
-                        .await
+                        if let Err(e) =
+                            Self::handle_datagram(query_data, src, socket_clone, ctx_clone).await
                         {
                             warn!("Error handling DNS query from {}: {}", src, e);
                         }
@@ -79,132 +216,430 @@ impl DnsServer {
         }
     }
 
-    /// Handle a single DNS query.
-    async fn handle_query(
-        query_data: Vec<u8>,
+    /// Wait for the next TCP connection, or forever without a TCP listener.
+    async fn accept(listener: Option<&TcpListener>) -> std::io::Result<(TcpStream, SocketAddr)> {
+        match listener {
+            Some(listener) => listener.accept().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Serve a TCP connection in its own task, unless too many are open.
+    fn spawn_connection(stream: TcpStream, src: SocketAddr, ctx: &Arc<ServerContext>) {
+        let Ok(permit) = ctx.tcp_connections.clone().try_acquire_owned() else {
+            debug!("Too many DNS connections, closing the one from {}", src);
+            return;
+        };
+
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = Self::handle_connection(stream, src, ctx).await {
+                debug!("DNS connection from {} ended: {:#}", src, e);
+            }
+            drop(permit);
+        });
+    }
+
+    /// Handle a UDP query, truncating answers too large for the client.
+    async fn handle_datagram(
+        query_data: PooledBuffer,
         src: SocketAddr,
         socket: Arc<UdpSocket>,
-        state: Arc<RwLock<AppState>>,
-        upstream: Arc<UpstreamResolver>,
+        ctx: Arc<ServerContext>,
     ) -> Result<()> {
-        // Parse the DNS query
-        let query = Message::from_bytes(&query_data)
-            .context("Failed to parse DNS query")?;
+        let Some(reply) = Self::answer(&ctx, &query_data, src).await else {
+            return Ok(());
+        };
+
+        let mut response_bytes = Self::encode(&ctx, &reply.message)?;
+        if response_bytes.len() > usize::from(reply.max_payload) {
+            debug!(id = reply.message.id(), "Answer too large for UDP, sending it truncated");
+            response_bytes = Self::encode(&ctx, &reply.message.truncate())?;
+        }
+
+        socket
+            .send_to(&response_bytes, src)
+            .await
+            .context("Failed to send DNS response")?;
+
+        Ok(())
+    }
+
+    /// Handle length-prefixed queries on a TCP connection until the client
+    /// closes it or stays idle too long.
+    async fn handle_connection(
+        mut stream: TcpStream,
+        src: SocketAddr,
+        ctx: Arc<ServerContext>,
+    ) -> Result<()> {
+        loop {
+            let len = match tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_u16()).await {
+                Ok(Ok(len)) => len,
+                // Closed by the client between queries
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => return Ok(()),
+            };
+
+            let mut query_data = ctx.buffers.get();
+            query_data.resize(len.into(), 0);
+            tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut query_data))
+                .await
+                .context("Timed out reading DNS query")?
+                .context("Failed to read DNS query")?;
+
+            let Some(reply) = Self::answer(&ctx, &query_data, src).await else {
+                continue;
+            };
+            let response_bytes = Self::encode(&ctx, &reply.message)?;
+            let len = u16::try_from(response_bytes.len()).context("DNS response too large")?;
+            let write = async {
+                stream.write_all(&len.to_be_bytes()).await?;
+                stream.write_all(&response_bytes).await
+            };
+            tokio::time::timeout(TCP_IDLE_TIMEOUT, write)
+                .await
+                .context("Timed out sending DNS response")?
+                .context("Failed to send DNS response")?;
+        }
+    }
+
+    /// Answer a single DNS query, whichever transport it came over.
+    async fn answer(ctx: &ServerContext, query_data: &[u8], src: SocketAddr) -> Option<Reply> {
+        // Parse the DNS query, answering malformed ones with an error where possible
+        let mut query = match parse_query(query_data) {
+            Ok(query) => query,
+            Err(rejected) => {
+                ctx.queries.malformed.fetch_add(1, Ordering::Relaxed);
+                debug!(reason = ?rejected.reason, "Malformed DNS packet from {}", src);
+                return rejected.response.map(|response| Reply {
+                    message: *response,
+                    max_payload: MAX_QUERY_LEN as u16,
+                });
+            }
+        };
+        let max_payload = query.max_payload();
 
         let query_id = query.id();
 
+        // The client's network is nobody else's business
+        if strip_client_subnet(&mut query) {
+            debug!(id = query_id, "Removed EDNS Client Subnet option from query");
+        }
+
         // Get the first question (most DNS queries have exactly one)
         let question = match query.queries().first() {
             Some(q) => q,
             None => {
                 warn!("DNS query with no questions from {}", src);
-                return Ok(());
+                return None;
             }
         };
 
         let name = question.name();
         let record_type = question.query_type();
 
+        // Formatted once and reused for matching, stats and logging
+        let domain = name.to_string();
+
         debug!(
             id = query_id,
-            name = %name,
+            name = %ctx.query_log.display_domain(&domain),
             record_type = ?record_type,
             "Received DNS query"
         );
 
-        // Check if blocking is active and if domain should be blocked
-        let should_block = {
-            let state_guard = state.read().await;
-            if state_guard.is_blocking_active() {
-                state_guard.blocker.should_block(&name.to_string())
-            } else {
-                false
-            }
+        // Self-check probes are answered locally and never forwarded
+        if ProbeTracker::is_probe(&domain) {
+            ctx.probes.record(&domain);
+            let message = Self::create_blocked_response(&query, name, record_type, 0, &NULL_IP);
+            return Some(Reply { message, max_payload });
+        }
+
+        // Check if blocking is active and if domain should be blocked, with
+        // the policy of the device asking
+        let config = ctx.config.get();
+        let blocked_entry = {
+            let policy = ctx.policy.load();
+            let profile = policy.profile_for(src.ip());
+            policy.blocked_entry_for(&config, &domain, profile)
         };
+        let blocking = &config.blocking;
+        let dry_run = blocking.dry_run;
 
-        let response = if should_block {
-            // Update stats
-            {
-                let mut state_guard = state.write().await;
-                state_guard.stats.queries_blocked += 1;
-            }
+        // In dry-run mode blocked queries are only counted and logged
+        let mut would_block = dry_run && blocked_entry.is_some();
+        if would_block {
+            ctx.queries.would_block.fetch_add(1, Ordering::Relaxed);
+            let name = ctx.query_log.display_domain(&domain);
+            info!(name = %name, "Would block DNS query (dry run)");
+        }
+        let blocked_entry = blocked_entry.filter(|_| !dry_run);
+
+        let (response, action) = if let Some(entry) = blocked_entry {
+            ctx.queries.blocked.fetch_add(1, Ordering::Relaxed);
+            Self::report(ctx, QueryActivity::Blocked(entry));
 
-            info!(name = %name, "Blocking DNS query");
-            Self::create_blocked_response(&query, name, record_type)
+            info!(name = %ctx.query_log.display_domain(&domain), "Blocking DNS query");
+            let ttl = ctx.ttl.blocked_ttl;
+            let response = Self::create_blocked_response(&query, name, record_type, ttl, blocking);
+            (response, QueryAction::Blocked)
         } else {
-            // Update stats
-            {
-                let mut state_guard = state.write().await;
-                state_guard.stats.queries_forwarded += 1;
-            }
+            ctx.queries.forwarded.fetch_add(1, Ordering::Relaxed);
+            Self::report(ctx, QueryActivity::Resolved(domain.clone()));
+
+            let (response, action) = Self::forward(ctx, &query, name, &domain, record_type).await;
 
-            // Forward to upstream
-            match upstream.resolve(name, record_type).await {
-                Ok(mut response) => {
-                    response.set_id(query_id);
-                    response
+            // Checked on every answer rather than before caching, so the
+            // cache holds the real answer whatever is blocked later
+            let cloaked = match blocking.block_cname_cloaking {
+                true => Self::cloaked_entry(ctx, &config, &domain, &response, src),
+                false => None,
+            };
+            match cloaked {
+                Some(entry) if !dry_run => {
+                    ctx.queries.blocked.fetch_add(1, Ordering::Relaxed);
+                    Self::report(ctx, QueryActivity::Blocked(entry.clone()));
+
+                    let shown = ctx.query_log.display_domain(&domain);
+                    info!(name = %shown, entry = %entry, "Blocking DNS query (CNAME cloaking)");
+                    let ttl = ctx.ttl.blocked_ttl;
+                    let response =
+                        Self::create_blocked_response(&query, name, record_type, ttl, blocking);
+                    (response, QueryAction::Blocked)
                 }
-                Err(e) => {
-                    warn!(name = %name, error = %e, "Upstream resolution failed");
-                    Self::create_servfail_response(&query)
+                Some(_) => {
+                    ctx.queries.would_block.fetch_add(1, Ordering::Relaxed);
+                    would_block = true;
+                    (response, action)
                 }
+                None => (response, action),
             }
         };
 
-        // Send response
-        let response_bytes = response.to_bytes()
-            .context("Failed to serialize DNS response")?;
+        ctx.query_log.record(QueryLogEntry {
+            timestamp: chrono::Utc::now().timestamp(),
+            domain: domain.trim_end_matches('.').to_string(),
+            record_type: record_type.to_string(),
+            action,
+            client: src.ip().to_string(),
+            would_block,
+        });
 
-        socket
-            .send_to(&response_bytes, src)
-            .await
-            .context("Failed to send DNS response")?;
+        Some(Reply {
+            message: response,
+            max_payload,
+        })
+    }
 
-        Ok(())
+    /// Queue query activity to be recorded in the state.
+    fn report(ctx: &ServerContext, activity: QueryActivity) {
+        if ctx.activity.try_send(activity).is_err() {
+            debug!("Query activity backlog full, not recording a query");
+        }
     }
 
-    /// Create a blocked response (NXDOMAIN or 0.0.0.0).
-    fn create_blocked_response(query: &Message, name: &Name, record_type: RecordType) -> Message {
-        let mut response = Message::new();
-        response.set_id(query.id());
-        response.set_message_type(MessageType::Response);
-        response.set_op_code(OpCode::Query);
-        response.set_authoritative(false);
-        response.set_recursion_desired(query.recursion_desired());
-        response.set_recursion_available(true);
+    /// Record queued query activity towards usage stats, budgets and the
+    /// activity history, taking the state lock once per batch.
+    async fn record_activity(
+        state: Arc<RwLock<AppState>>,
+        mut recorded: mpsc::Receiver<QueryActivity>,
+    ) {
+        let mut batch = Vec::with_capacity(ACTIVITY_BATCH);
+        while recorded.recv_many(&mut batch, ACTIVITY_BATCH).await > 0 {
+            let mut state_guard = state.write().await;
+            for activity in batch.drain(..) {
+                match activity {
+                    QueryActivity::Resolved(domain) => state_guard.record_activity(&domain),
+                    QueryActivity::Blocked(entry) => {
+                        *state_guard.stats.blocked_by_domain.entry(entry).or_default() += 1;
+                        state_guard.record_block();
+                    }
+                }
+            }
+        }
+    }
 
-        // Copy the query
-        for q in query.queries() {
-            response.add_query(q.clone());
+    /// Serialize a response into a pooled buffer.
+    fn encode(ctx: &Arc<ServerContext>, response: &Message) -> Result<PooledBuffer> {
+        let mut response_bytes = ctx.buffers.get();
+        {
+            let mut encoder = BinEncoder::new(&mut response_bytes);
+            response
+                .emit(&mut encoder)
+                .context("Failed to serialize DNS response")?;
         }
 
-        // Return 0.0.0.0 for A records (makes the block more obvious)
-        if record_type == RecordType::A {
-            response.set_response_code(ResponseCode::NoError);
-            let rdata = RData::A("0.0.0.0".parse().unwrap());
-            let record = Record::from_rdata(name.clone(), 60, rdata);
-            response.add_answer(record);
-        } else if record_type == RecordType::AAAA {
-            // Return :: for AAAA records
-            response.set_response_code(ResponseCode::NoError);
-            let rdata = RData::AAAA("::".parse().unwrap());
-            let record = Record::from_rdata(name.clone(), 60, rdata);
-            response.add_answer(record);
-        } else {
-            // NXDOMAIN for other record types
-            response.set_response_code(ResponseCode::NXDomain);
+        Ok(response_bytes)
+    }
+
+    /// Answer a non-blocked query from the cache or by forwarding it upstream.
+    async fn forward(
+        ctx: &ServerContext,
+        query: &Message,
+        name: &Name,
+        domain: &str,
+        record_type: RecordType,
+    ) -> (Message, QueryAction) {
+        // Link-local names mean nothing to public resolvers and would leak device names
+        let link_local = ctx.link_local == LinkLocalPolicy::Nxdomain
+            && is_link_local_name(domain)
+            && !ctx.zones.covers(domain);
+        if link_local {
+            return (Self::create_empty_response(query, ResponseCode::NXDomain), QueryAction::Local);
+        }
+
+        // Without IPv6 addresses, clients on broken IPv6 networks don't stall
+        // on connections that never succeed before falling back to IPv4
+        if ctx.force_ipv4 && record_type == RecordType::AAAA {
+            return (Self::create_empty_response(query, ResponseCode::NoError), QueryAction::Local);
+        }
+
+        let cached = ctx
+            .cache
+            .lock()
+            .ok()
+            .and_then(|mut cache| cache.get(domain, record_type));
+        if let Some(mut response) = cached {
+            debug!(name = %ctx.query_log.display_domain(domain), "Answered from cache");
+            response.set_id(query.id());
+            return (response, QueryAction::Cached);
+        }
+
+        // Internal zones go to their own servers while those are reachable
+        let (result, internal) = match ctx.zones.resolve(domain, name, record_type).await {
+            Some(ZoneAnswer::Answered(result)) => (result, true),
+            Some(ZoneAnswer::Unavailable) => {
+                let response = Self::create_empty_response(query, ResponseCode::ServFail);
+                return (response, QueryAction::Failed);
+            }
+            Some(ZoneAnswer::Fallback) | None => {
+                let result = ctx.upstream.resolve(name, record_type).await;
+                (result.map_err(Into::into), false)
+            }
+        };
+
+        // "No such name" is an answer to pass on (and cache), not a failure
+        let result = result.or_else(|e| {
+            let answer = e.downcast_ref::<ResolveError>().and_then(no_records_response);
+            answer.ok_or(e)
+        });
+
+        match result {
+            Ok(mut response) => {
+                response.set_id(query.id());
+                // Internal names legitimately resolve to private addresses
+                if let Some(rebinding) = ctx.rebinding.as_ref().filter(|_| !internal) {
+                    let removed = rebinding.filter(domain, &mut response);
+                    if removed > 0 {
+                        warn!(
+                            domain = %ctx.query_log.display_domain(domain),
+                            removed,
+                            "Stripped private-range answers (DNS rebinding protection)"
+                        );
+                    }
+                }
+                ctx.ttl.apply(&mut response);
+                if let Ok(mut cache) = ctx.cache.lock() {
+                    cache.insert(domain, record_type, &response);
+                }
+                (response, QueryAction::Forwarded)
+            }
+            Err(e) => {
+                let name = ctx.query_log.display_domain(domain);
+                warn!(name = %name, error = %e, "Upstream resolution failed");
+
+                // An outdated answer keeps things working through an outage
+                let stale = ctx
+                    .cache
+                    .lock()
+                    .ok()
+                    .and_then(|mut cache| cache.get_stale(domain, record_type));
+                if let Some(mut response) = stale {
+                    info!(name = %name, "Serving a stale answer from the cache");
+                    response.set_id(query.id());
+                    return (response, QueryAction::Stale);
+                }
+
+                (Self::create_empty_response(query, ResponseCode::ServFail), QueryAction::Failed)
+            }
+        }
+    }
+
+    /// The blocklist entry matching a name the answer's CNAME chain passes
+    /// through, if any, for the device asking. Essential domains are answered
+    /// whatever CDN they point to.
+    fn cloaked_entry(
+        ctx: &ServerContext,
+        config: &Config,
+        domain: &str,
+        response: &Message,
+        src: SocketAddr,
+    ) -> Option<String> {
+        let mut targets = response.answers().iter().filter_map(|record| match record.data() {
+            RData::CNAME(target) => Some(target.0.to_string()),
+            _ => None,
+        });
+        let first = targets.next()?;
+
+        let policy = ctx.policy.load();
+        if policy.essential.matching_entry(domain).is_some() {
+            return None;
+        }
+        let profile = policy.profile_for(src.ip());
+        std::iter::once(first)
+            .chain(targets)
+            .find_map(|target| policy.blocked_entry_for(config, &target, profile))
+    }
+
+    /// Create a blocked response in the configured `block_mode`.
+    fn create_blocked_response(
+        query: &Message,
+        name: &Name,
+        record_type: RecordType,
+        ttl: u32,
+        blocking: &BlockingConfig,
+    ) -> Message {
+        let null_ip = [Ipv4Addr::UNSPECIFIED.into(), Ipv6Addr::UNSPECIFIED.into()];
+        let addrs: &[IpAddr] = match blocking.block_mode {
+            BlockMode::Nxdomain => {
+                return Self::create_empty_response(query, ResponseCode::NXDomain);
+            }
+            BlockMode::Refused => return Self::create_empty_response(query, ResponseCode::Refused),
+            BlockMode::NullIp => &null_ip,
+            BlockMode::Redirect => &blocking.redirect_to,
+        };
+
+        let mut response = Self::create_empty_response(query, ResponseCode::NoError);
+        match record_type {
+            // An address family without an address is answered with no records
+            RecordType::A | RecordType::AAAA => {
+                for addr in addrs {
+                    let rdata = match (addr, record_type) {
+                        (IpAddr::V4(v4), RecordType::A) => RData::A((*v4).into()),
+                        (IpAddr::V6(v6), RecordType::AAAA) => RData::AAAA((*v6).into()),
+                        _ => continue,
+                    };
+                    response.add_answer(Record::from_rdata(name.clone(), ttl, rdata));
+                }
+            }
+            // No service binding, so clients fall back to the A/AAAA answers
+            RecordType::HTTPS | RecordType::SVCB => {}
+            _ => {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
         }
 
         response
     }
 
-    /// Create a SERVFAIL response for upstream failures.
-    fn create_servfail_response(query: &Message) -> Message {
+    /// Create an answer-less response with the given code (e.g. SERVFAIL for upstream failures).
+    fn create_empty_response(query: &Message, code: ResponseCode) -> Message {
         let mut response = Message::new();
         response.set_id(query.id());
         response.set_message_type(MessageType::Response);
         response.set_op_code(OpCode::Query);
-        response.set_response_code(ResponseCode::ServFail);
+        response.set_response_code(code);
         response.set_recursion_desired(query.recursion_desired());
         response.set_recursion_available(true);
 
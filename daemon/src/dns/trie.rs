@@ -0,0 +1,255 @@
+//! Compact suffix trie for blocklist domains.
+//!
+//! Domains are stored by reversed labels ("com" → "facebook" → "www"), so
+//! entries sharing a parent domain share its nodes. Label text lives in a
+//! single arena of newline-terminated domains: a node points at its label
+//! inside the first domain that created it, and because every node's path is
+//! a domain suffix, the text from that label to the end of the line is the
+//! node's full domain. Longer domains are inserted first, so a listed parent
+//! of a listed subdomain ("facebook.com" and "www.facebook.com") takes no
+//! arena space at all.
+//!
+//! Nodes are laid out breadth-first with each node's children stored
+//! contiguously and sorted by label, so a node is 12 bytes and lookups are a
+//! binary search per label.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// Index of the root node, which stands for the empty domain.
+const ROOT: usize = 0;
+
+/// Longest domain name DNS can carry; longer entries can never match.
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// Longest label DNS can carry.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Separates domains in the arena.
+const TERMINATOR: char = '\n';
+
+struct Node {
+    /// Offset of this node's label in the arena
+    start: u32,
+    /// Index of the first child; children are contiguous
+    first_child: u32,
+    /// Number of children, up to 2^24
+    child_count: [u8; 3],
+    /// Label length, with the top bit set if the domain is an entry
+    label: u8,
+}
+
+/// Node while the trie is built, before the breadth-first layout.
+struct BuildNode {
+    start: u32,
+    label_len: u8,
+    terminal: bool,
+    children: Vec<u32>,
+}
+
+/// Set of domains matched by suffix.
+pub struct DomainTrie {
+    arena: String,
+    nodes: Vec<Node>,
+    len: usize,
+}
+
+impl DomainTrie {
+    /// Build a trie from normalized (lowercase, no trailing dot) domains.
+    ///
+    /// Empty domains are ignored, since they would match everything.
+    pub fn new<'a>(domains: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut domains: Vec<&str> = domains
+            .into_iter()
+            .filter(|d| !d.is_empty() && d.len() <= MAX_DOMAIN_LEN)
+            .filter(|d| d.split('.').all(|label| label.len() <= MAX_LABEL_LEN))
+            .collect();
+        domains.sort_unstable_by_key(|d| std::cmp::Reverse(d.len()));
+
+        let mut arena = String::new();
+        let mut tree = vec![BuildNode {
+            start: 0,
+            label_len: 0,
+            terminal: false,
+            children: Vec::new(),
+        }];
+        let mut lookup: HashMap<(usize, &str), usize> = HashMap::new();
+        let mut len = 0;
+
+        for domain in domains {
+            // Offset of this domain in the arena, once it has been copied there
+            let mut offset: Option<usize> = None;
+            let mut node = ROOT;
+            let mut end = domain.len();
+
+            loop {
+                let start = domain[..end].rfind('.').map_or(0, |dot| dot + 1);
+                let label = &domain[start..end];
+
+                node = match lookup.get(&(node, label)) {
+                    Some(&child) => child,
+                    None => {
+                        let base = *offset.get_or_insert_with(|| {
+                            let base = arena.len();
+                            arena.push_str(domain);
+                            arena.push(TERMINATOR);
+                            base
+                        });
+                        let child = tree.len();
+                        tree.push(BuildNode {
+                            start: (base + start) as u32,
+                            label_len: label.len() as u8,
+                            terminal: false,
+                            children: Vec::new(),
+                        });
+                        tree[node].children.push(child as u32);
+                        lookup.insert((node, label), child);
+                        child
+                    }
+                };
+
+                if start == 0 {
+                    break;
+                }
+                end = start - 1;
+            }
+
+            if !tree[node].terminal {
+                tree[node].terminal = true;
+                len += 1;
+            }
+        }
+        drop(lookup);
+
+        // Breadth-first layout: `order[i]` is the build node stored at index i
+        let mut order = vec![ROOT as u32];
+        let mut nodes = Vec::with_capacity(tree.len());
+        let mut i = 0;
+        while i < order.len() {
+            let built = &mut tree[order[i] as usize];
+            let mut children = std::mem::take(&mut built.children);
+            children.sort_unstable_by(|&a, &b| {
+                build_label(&arena, &tree[a as usize]).cmp(build_label(&arena, &tree[b as usize]))
+            });
+
+            let built = &tree[order[i] as usize];
+            let count = (children.len() as u32).to_le_bytes();
+            nodes.push(Node {
+                start: built.start,
+                first_child: order.len() as u32,
+                child_count: [count[0], count[1], count[2]],
+                label: built.label_len | if built.terminal { 0x80 } else { 0 },
+            });
+            order.extend(children);
+            i += 1;
+        }
+        arena.shrink_to_fit();
+
+        Self { arena, nodes, len }
+    }
+
+    /// Find the shortest entry that is `domain` or one of its parents.
+    ///
+    /// `domain` may be in any case but must not have a trailing dot.
+    pub fn find_suffix(&self, domain: &str) -> Option<&str> {
+        let mut node = &self.nodes[ROOT];
+
+        for label in domain.rsplit('.') {
+            let first = node.first_child as usize;
+            let children = &self.nodes[first..first + node.child_count()];
+            let child = children
+                .binary_search_by(|child| cmp_ignore_ascii_case(self.label(child), label))
+                .ok()?;
+            node = &children[child];
+
+            if node.is_terminal() {
+                let rest = &self.arena[node.start as usize..];
+                let end = rest.find(TERMINATOR).unwrap_or(rest.len());
+                return Some(&rest[..end]);
+            }
+        }
+
+        None
+    }
+
+    /// Number of distinct entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Approximate heap memory used, in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        self.arena.capacity() + self.nodes.capacity() * size_of::<Node>()
+    }
+
+    /// A node's label text.
+    fn label(&self, node: &Node) -> &str {
+        let start = node.start as usize;
+        &self.arena[start..start + (node.label & 0x7f) as usize]
+    }
+}
+
+impl Node {
+    fn child_count(&self) -> usize {
+        let [a, b, c] = self.child_count;
+        u32::from_le_bytes([a, b, c, 0]) as usize
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.label & 0x80 != 0
+    }
+}
+
+/// A build node's label text.
+fn build_label<'a>(arena: &'a str, node: &BuildNode) -> &'a str {
+    let start = node.start as usize;
+    &arena[start..start + node.label_len as usize]
+}
+
+/// Compare a lowercase stored label with a label in any case.
+fn cmp_ignore_ascii_case(stored: &str, label: &str) -> Ordering {
+    stored
+        .bytes()
+        .cmp(label.bytes().map(|b| b.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_suffix() {
+        let trie = DomainTrie::new(["www.facebook.com", "facebook.com", "t.co", "co.uk"]);
+
+        assert_eq!(trie.len(), 4);
+        assert_eq!(trie.find_suffix("facebook.com"), Some("facebook.com"));
+        assert_eq!(trie.find_suffix("M.FaceBook.com"), Some("facebook.com"));
+        assert_eq!(trie.find_suffix("www.facebook.com"), Some("facebook.com"));
+        assert_eq!(trie.find_suffix("t.co"), Some("t.co"));
+        assert_eq!(trie.find_suffix("example.co.uk"), Some("co.uk"));
+        assert_eq!(trie.find_suffix("com"), None);
+        assert_eq!(trie.find_suffix("notfacebook.com"), None);
+        assert_eq!(trie.find_suffix(""), None);
+    }
+
+    #[test]
+    fn test_shared_suffixes_use_no_extra_arena() {
+        let trie = DomainTrie::new(["example.com", "com", "", "www.example.com", "example.com"]);
+
+        assert_eq!(trie.len(), 3);
+        assert_eq!(trie.arena, "www.example.com\n");
+        assert_eq!(trie.find_suffix("other.com"), Some("com"));
+    }
+
+    #[test]
+    fn test_skips_overlong_domains() {
+        let long = format!("{}.com", "a.".repeat(MAX_DOMAIN_LEN / 2));
+        let long_label = format!("{}.com", "a".repeat(MAX_LABEL_LEN + 1));
+        let trie = DomainTrie::new([long.as_str(), long_label.as_str()]);
+
+        assert_eq!(trie.len(), 0);
+        assert_eq!(trie.find_suffix(&long), None);
+        assert_eq!(trie.find_suffix(&long_label), None);
+    }
+}
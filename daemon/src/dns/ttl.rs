@@ -0,0 +1,76 @@
+//! TTL policy for blocked and forwarded responses.
+
+use blockandfocus_shared::DnsConfig;
+use hickory_proto::op::Message;
+
+/// TTL settings applied to every response the server sends.
+#[derive(Debug, Clone, Copy)]
+pub struct TtlPolicy {
+    /// TTL for synthesized blocked answers
+    pub blocked_ttl: u32,
+    min_ttl: Option<u32>,
+    max_ttl: Option<u32>,
+}
+
+impl TtlPolicy {
+    /// Build the policy from DNS configuration.
+    pub fn from_config(config: &DnsConfig) -> Self {
+        Self {
+            blocked_ttl: config.blocked_ttl.as_secs_u32(),
+            min_ttl: config.min_ttl.map(|ttl| ttl.as_secs_u32()),
+            max_ttl: config.max_ttl.map(|ttl| ttl.as_secs_u32()),
+        }
+    }
+
+    /// Clamp a single TTL value to the configured bounds.
+    pub fn clamp(&self, ttl: u32) -> u32 {
+        let ttl = self.min_ttl.map_or(ttl, |min| ttl.max(min));
+        self.max_ttl.map_or(ttl, |max| ttl.min(max))
+    }
+
+    /// Clamp the TTLs of all answer records in a forwarded response.
+    pub fn apply(&self, response: &mut Message) {
+        if self.min_ttl.is_none() && self.max_ttl.is_none() {
+            return;
+        }
+
+        for record in response.answers_mut() {
+            let ttl = self.clamp(record.ttl());
+            record.set_ttl(ttl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min: Option<u32>, max: Option<u32>) -> TtlPolicy {
+        TtlPolicy {
+            blocked_ttl: 5,
+            min_ttl: min,
+            max_ttl: max,
+        }
+    }
+
+    #[test]
+    fn test_no_bounds() {
+        let policy = policy(None, None);
+        assert_eq!(policy.clamp(0), 0);
+        assert_eq!(policy.clamp(86400), 86400);
+    }
+
+    #[test]
+    fn test_clamping() {
+        let policy = policy(Some(30), Some(3600));
+        assert_eq!(policy.clamp(5), 30);
+        assert_eq!(policy.clamp(300), 300);
+        assert_eq!(policy.clamp(86400), 3600);
+    }
+
+    #[test]
+    fn test_default_blocked_ttl() {
+        let policy = TtlPolicy::from_config(&DnsConfig::default());
+        assert_eq!(policy.blocked_ttl, 5);
+    }
+}
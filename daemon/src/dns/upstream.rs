@@ -1,52 +1,281 @@
 //! Upstream DNS resolver.
+//!
+//! Anything that answers queries implements `Resolve`: one upstream server,
+//! the failover set of them in `UpstreamResolver`, or the in-memory
+//! `MockUpstream` tests use instead of the network.
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
+use blockandfocus_shared::{UpstreamProtocol, UpstreamServer};
 use hickory_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
-use hickory_proto::rr::{Name, RData, Record, RecordType};
-use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_proto::rr::{Name, RData, RecordType};
+use hickory_proto::xfer::Protocol;
+use hickory_proto::ProtoErrorKind;
+use hickory_resolver::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+use hickory_resolver::lookup::Lookup;
 use hickory_resolver::name_server::TokioConnectionProvider;
-use hickory_resolver::Resolver;
-use tracing::debug;
+use hickory_resolver::{ResolveError, Resolver};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use super::scoring::UpstreamScores;
 
 /// Type alias for the async resolver
 type TokioResolver = Resolver<TokioConnectionProvider>;
 
+/// The pending answer to one query.
+pub type Resolution<'a> = Pin<Box<dyn Future<Output = Result<Message, ResolveError>> + Send + 'a>>;
+
+/// Something that answers DNS queries.
+///
+/// A "no records" error is an answer (the name or type doesn't exist); any
+/// other error means the query went unanswered.
+pub trait Resolve: Send + Sync {
+    fn resolve<'a>(&'a self, name: &'a Name, record_type: RecordType) -> Resolution<'a>;
+}
+
 /// Upstream DNS resolver with failover support.
-pub struct UpstreamResolver {
+///
+/// Queries go to the upstream with the best recent latency and failure rate
+/// (see `UpstreamScores`), failing over to the others in score order.
+pub struct UpstreamResolver<U = Upstream> {
+    upstreams: Vec<Arc<U>>,
+    scores: Arc<UpstreamScores>,
+}
+
+/// One upstream server, possibly reached over several transports.
+///
+/// It keeps its TCP connection open and multiplexes concurrent queries over
+/// it. A connection left unused for the idle timeout is replaced before the
+/// next query (the server has likely closed it), and a query that fails on a
+/// dropped connection is retried once on a fresh one.
+pub struct Upstream {
+    config: ResolverConfig,
+    opts: ResolverOpts,
+    /// Whether the server is reached over a connection worth keeping
+    persistent: bool,
+    idle_timeout: Duration,
+    connections: Mutex<Connections>,
+}
+
+/// The resolver holding the open connections.
+struct Connections {
     resolver: TokioResolver,
+    last_used: Instant,
 }
 
-impl UpstreamResolver {
-    /// Create a new upstream resolver with explicit upstream servers.
-    /// IMPORTANT: We cannot use system DNS config because we ARE the system DNS!
-    /// We use Cloudflare (1.1.1.1) as the upstream DNS.
-    pub fn new(_upstream_servers: &[String]) -> Result<Self> {
-        // Use Cloudflare DNS (1.1.1.1) - we CANNOT use system config since WE are the system DNS!
-        let config = ResolverConfig::cloudflare();
+impl UpstreamResolver<Upstream> {
+    /// Create the resolver for the configured `dns.upstream` servers.
+    ///
+    /// The system DNS config can't be used, since the daemon is the system
+    /// DNS. Entries that can't be used are skipped with a warning, and if
+    /// none is left Cloudflare (1.1.1.1) is asked instead.
+    pub fn new(upstream_servers: &[UpstreamServer], idle_timeout: Duration) -> Result<Self> {
+        let mut opts = ResolverOpts::default();
+        opts.attempts = 1;
+
+        let name_servers: Vec<_> = upstream_servers
+            .iter()
+            .filter_map(|server| {
+                name_server(server)
+                    .inspect_err(|e| warn!("Skipping upstream server: {:#}", e))
+                    .ok()
+            })
+            .collect();
+        if name_servers.is_empty() {
+            warn!("No usable upstream servers configured, using Cloudflare");
+            return Ok(Self::build(ResolverConfig::cloudflare().name_servers(), opts, idle_timeout));
+        }
+
+        info!(
+            "Forwarding to {}",
+            upstream_servers.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        );
+        Ok(Self::build(&name_servers, opts, idle_timeout))
+    }
 
-        let resolver = TokioResolver::builder_with_config(config, TokioConnectionProvider::default())
-            .with_options(ResolverOpts::default())
-            .build();
+    /// Create a resolver that only asks the given servers, over plain DNS or
+    /// DNS over TLS, giving up on each after `timeout`.
+    pub fn for_servers(
+        servers: &[UpstreamServer],
+        timeout: Duration,
+        idle_timeout: Duration,
+    ) -> Result<Self> {
+        let name_servers = servers.iter().map(name_server).collect::<Result<Vec<_>>>()?;
 
-        Ok(Self { resolver })
+        let mut opts = ResolverOpts::default();
+        opts.timeout = timeout;
+        opts.attempts = 1;
+
+        Ok(Self::build(&name_servers, opts, idle_timeout))
     }
 
-    /// Resolve a DNS query using upstream servers.
-    pub async fn resolve(&self, name: &Name, record_type: RecordType) -> Result<Message> {
+    /// Group the name servers into upstreams by address, so the transports of
+    /// one server are scored together.
+    fn build(
+        name_servers: &[NameServerConfig],
+        opts: ResolverOpts,
+        idle_timeout: Duration,
+    ) -> Self {
+        let mut grouped: Vec<(SocketAddr, ResolverConfig)> = Vec::new();
+        for server in name_servers {
+            let addr = server.socket_addr;
+            match grouped.iter_mut().find(|(existing, _)| existing.ip() == addr.ip()) {
+                Some((_, config)) => config.add_name_server(server.clone()),
+                None => {
+                    let mut config = ResolverConfig::new();
+                    config.add_name_server(server.clone());
+                    grouped.push((addr, config));
+                }
+            }
+        }
+
+        Self::with_upstreams(grouped.into_iter().map(|(addr, config)| {
+            (addr.to_string(), Upstream::new(config, opts.clone(), idle_timeout))
+        }))
+    }
+}
+
+impl<U: Resolve + 'static> UpstreamResolver<U> {
+    /// Fail over between the given upstreams, named for scoring and logs.
+    pub fn with_upstreams(upstreams: impl IntoIterator<Item = (String, U)>) -> Self {
+        let (names, upstreams): (Vec<_>, Vec<_>) = upstreams
+            .into_iter()
+            .map(|(name, upstream)| (name, Arc::new(upstream)))
+            .unzip();
+
+        Self {
+            upstreams,
+            scores: Arc::new(UpstreamScores::new(names)),
+        }
+    }
+
+    /// Resolve a DNS query using the first upstream to answer.
+    async fn resolve_any(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Result<Message, ResolveError> {
         debug!(?name, ?record_type, "Forwarding query to upstream");
 
-        let response = match record_type {
-            RecordType::A => {
-                let lookup = self.resolver.lookup_ip(name.to_string()).await?;
-                self.build_response(name, record_type, lookup)
+        if let Some(index) = self.scores.due_for_probe() {
+            // Measure a demoted upstream alongside, without waiting for it
+            let upstream = self.upstreams[index].clone();
+            let scores = self.scores.clone();
+            let name = name.clone();
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let result = upstream.resolve(&name, record_type).await;
+                scores.record(index, answered(&result).then(|| started.elapsed()));
+            });
+        }
+
+        let mut last_error = None;
+        for index in self.scores.order() {
+            let started = Instant::now();
+            let result = self.upstreams[index].resolve(name, record_type).await;
+            let ok = answered(&result);
+            self.scores.record(index, ok.then(|| started.elapsed()));
+            match result {
+                Ok(message) => return Ok(message),
+                // The upstream answered that there is nothing to return
+                Err(e) if ok => return Err(e),
+                Err(e) => {
+                    debug!(error = %e, "Upstream failed, trying the next one");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "No upstream servers configured".into()))
+    }
+}
+
+impl<U: Resolve + 'static> Resolve for UpstreamResolver<U> {
+    fn resolve<'a>(&'a self, name: &'a Name, record_type: RecordType) -> Resolution<'a> {
+        Box::pin(self.resolve_any(name, record_type))
+    }
+}
+
+impl Resolve for Upstream {
+    fn resolve<'a>(&'a self, name: &'a Name, record_type: RecordType) -> Resolution<'a> {
+        Box::pin(self.resolve_reconnecting(name, record_type))
+    }
+}
+
+impl Upstream {
+    fn new(config: ResolverConfig, opts: ResolverOpts, idle_timeout: Duration) -> Self {
+        let persistent = config
+            .name_servers()
+            .iter()
+            .any(|server| server.protocol != Protocol::Udp);
+        let connections = Connections {
+            resolver: Self::connect(&config, &opts),
+            last_used: Instant::now(),
+        };
+
+        Self {
+            config,
+            opts,
+            persistent,
+            idle_timeout,
+            connections: Mutex::new(connections),
+        }
+    }
+
+    /// A resolver with no open connections; they are opened on first use.
+    fn connect(config: &ResolverConfig, opts: &ResolverOpts) -> TokioResolver {
+        TokioResolver::builder_with_config(config.clone(), TokioConnectionProvider::default())
+            .with_options(opts.clone())
+            .build()
+    }
+
+    async fn resolve_reconnecting(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Result<Message, ResolveError> {
+        let resolver = self.resolver(false);
+        match self.lookup(&resolver, name, record_type).await {
+            Err(e) if self.persistent && is_connection_error(&e) => {
+                debug!(error = %e, "Upstream connection lost, reconnecting");
+                let resolver = self.resolver(true);
+                self.lookup(&resolver, name, record_type).await
             }
-            RecordType::AAAA => {
-                let lookup = self.resolver.lookup_ip(name.to_string()).await?;
-                self.build_response(name, record_type, lookup)
+            result => result,
+        }
+    }
+
+    /// The resolver to send a query with, on fresh connections if `reconnect`
+    /// or the current ones have been idle too long. Queries still running on
+    /// replaced connections finish before those are closed.
+    fn resolver(&self, reconnect: bool) -> TokioResolver {
+        let mut connections = self.connections.lock().unwrap();
+        let idle = connections.last_used.elapsed() >= self.idle_timeout;
+        if self.persistent && (reconnect || idle) {
+            connections.resolver = Self::connect(&self.config, &self.opts);
+        }
+        connections.last_used = Instant::now();
+        connections.resolver.clone()
+    }
+
+    async fn lookup(
+        &self,
+        resolver: &TokioResolver,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Result<Message, ResolveError> {
+        let response = match record_type {
+            RecordType::A | RecordType::AAAA => {
+                let lookup = resolver.lookup_ip(name.to_string()).await?;
+                Self::build_response(name, record_type, lookup.as_lookup())
             }
             _ => {
                 // For other record types, use generic lookup
-                let lookup = self.resolver.lookup(name.clone(), record_type).await?;
+                let lookup = resolver.lookup(name.clone(), record_type).await?;
                 self.build_generic_response(name, record_type, lookup)
             }
         };
@@ -54,39 +283,21 @@ impl UpstreamResolver {
         Ok(response)
     }
 
-    /// Build a DNS response message from a lookup result.
-    fn build_response(
-        &self,
-        name: &Name,
-        record_type: RecordType,
-        lookup: hickory_resolver::lookup_ip::LookupIp,
-    ) -> Message {
-        let mut message = Message::new();
-        message.set_id(0); // Will be set by caller
-        message.set_message_type(MessageType::Response);
-        message.set_op_code(OpCode::Query);
-        message.set_response_code(ResponseCode::NoError);
-        message.set_recursion_desired(true);
-        message.set_recursion_available(true);
-
-        // Add query section
-        let query = Query::query(name.clone(), record_type);
-        message.add_query(query);
-
-        // Add answers
-        for ip in lookup.iter() {
-            let rdata = match ip {
-                std::net::IpAddr::V4(v4) if record_type == RecordType::A => RData::A(v4.into()),
-                std::net::IpAddr::V6(v6) if record_type == RecordType::AAAA => {
-                    RData::AAAA(v6.into())
-                }
-                std::net::IpAddr::V4(_) if record_type == RecordType::AAAA => continue,
-                std::net::IpAddr::V6(_) if record_type == RecordType::A => continue,
-                _ => continue,
+    /// Build a DNS response message from an address lookup: the CNAME chain
+    /// and the addresses at its end, each with its own owner name and the
+    /// TTL upstream gave it.
+    fn build_response(name: &Name, record_type: RecordType, lookup: &Lookup) -> Message {
+        let mut message = empty_response(name, record_type);
+        for record in lookup.record_iter() {
+            let keep = match record.data() {
+                RData::CNAME(_) => true,
+                RData::A(_) => record_type == RecordType::A,
+                RData::AAAA(_) => record_type == RecordType::AAAA,
+                _ => false,
             };
-
-            let record = Record::from_rdata(name.clone(), 300, rdata);
-            message.add_answer(record);
+            if keep {
+                message.add_answer(record.clone());
+            }
         }
 
         message
@@ -97,19 +308,9 @@ impl UpstreamResolver {
         &self,
         name: &Name,
         record_type: RecordType,
-        lookup: hickory_resolver::lookup::Lookup,
+        lookup: Lookup,
     ) -> Message {
-        let mut message = Message::new();
-        message.set_id(0);
-        message.set_message_type(MessageType::Response);
-        message.set_op_code(OpCode::Query);
-        message.set_response_code(ResponseCode::NoError);
-        message.set_recursion_desired(true);
-        message.set_recursion_available(true);
-
-        let query = Query::query(name.clone(), record_type);
-        message.add_query(query);
-
+        let mut message = empty_response(name, record_type);
         for record in lookup.record_iter() {
             message.add_answer(record.clone());
         }
@@ -118,13 +319,330 @@ impl UpstreamResolver {
     }
 }
 
+/// How to reach `server`. TLS servers are verified against the Mozilla root
+/// store, for the server's `tls_name` (sent as SNI).
+fn name_server(server: &UpstreamServer) -> Result<NameServerConfig> {
+    let protocol = match server.protocol {
+        UpstreamProtocol::Udp => Protocol::Udp,
+        UpstreamProtocol::Tcp => Protocol::Tcp,
+        UpstreamProtocol::Tls => Protocol::Tls,
+        UpstreamProtocol::Https => {
+            return Err(anyhow!("DNS over HTTPS is not supported yet for {}", server));
+        }
+    };
+    let addr = server
+        .socket_addr()
+        .ok_or_else(|| anyhow!("No address to connect to for {}", server))?;
+
+    let mut name_server = NameServerConfig::new(addr, protocol);
+    name_server.tls_dns_name = server.tls_name().map(str::to_string);
+    Ok(name_server)
+}
+
+/// A NOERROR response to `name` without answers, for the caller to set the ID of.
+pub(super) fn empty_response(name: &Name, record_type: RecordType) -> Message {
+    let mut message = Message::new();
+    message.set_id(0);
+    message.set_message_type(MessageType::Response);
+    message.set_op_code(OpCode::Query);
+    message.set_response_code(ResponseCode::NoError);
+    message.set_recursion_desired(true);
+    message.set_recursion_available(true);
+    message.add_query(Query::query(name.clone(), record_type));
+    message
+}
+
+/// The response an upstream gave when it had no records for a query
+/// (NXDOMAIN, or NOERROR without answers), with its SOA record so the answer
+/// can be cached; None for any other error.
+pub fn no_records_response(error: &ResolveError) -> Option<Message> {
+    let ProtoErrorKind::NoRecordsFound {
+        query,
+        soa,
+        negative_ttl,
+        response_code,
+        ..
+    } = error.proto()?.kind()
+    else {
+        return None;
+    };
+
+    let mut response = empty_response(query.name(), query.query_type());
+    response.set_response_code(*response_code);
+    if let Some(soa) = soa {
+        let mut record = soa.as_ref().clone().into_record_of_rdata();
+        if let Some(ttl) = negative_ttl {
+            record.set_ttl(*ttl);
+        }
+        response.add_name_server(record);
+    }
+    Some(response)
+}
+
+/// Whether the upstream answered, possibly that the name has no records.
+fn answered(result: &Result<Message, ResolveError>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(e) => e.is_no_records_found(),
+    }
+}
+
+/// Whether a query failed because its connection broke rather than because
+/// of the name or a slow server.
+fn is_connection_error(error: &ResolveError) -> bool {
+    error
+        .proto()
+        .is_some_and(|e| e.is_io() || e.is_no_connections())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hickory_proto::rr::rdata::CNAME;
+    use hickory_proto::rr::Record;
+
+    impl Upstream {
+        fn addr(&self) -> SocketAddr {
+            self.config.name_servers()[0].socket_addr
+        }
+    }
+
+    /// A lookup of `www.example.com` through a CNAME chain, as the resolver
+    /// returns it.
+    fn aliased_lookup(record_type: RecordType) -> (Name, Lookup) {
+        let name = Name::from_ascii("www.example.com.").unwrap();
+        let edge = Name::from_ascii("www.example.com.cdn.test.").unwrap();
+        let target = Name::from_ascii("edge.cdn.test.").unwrap();
+        let cname = |from: &Name, to: &Name, ttl| {
+            Record::from_rdata(from.clone(), ttl, RData::CNAME(CNAME(to.clone())))
+        };
+        let records = [
+            cname(&name, &edge, 3600),
+            cname(&edge, &target, 600),
+            Record::from_rdata(target.clone(), 60, RData::A("192.0.2.1".parse().unwrap())),
+            Record::from_rdata(target.clone(), 60, RData::AAAA("2001:db8::1".parse().unwrap())),
+        ];
+        let query = Query::query(name.clone(), record_type);
+        let lookup = Lookup::new_with_max_ttl(query, records.into());
+        (name, lookup)
+    }
+
+    #[test]
+    fn test_build_response_keeps_owners() {
+        let (name, lookup) = aliased_lookup(RecordType::A);
+        let response = Upstream::build_response(&name, RecordType::A, &lookup);
+
+        let answers: Vec<(String, RecordType)> = response
+            .answers()
+            .iter()
+            .map(|record| (record.name().to_string(), record.record_type()))
+            .collect();
+        assert_eq!(
+            answers,
+            [
+                ("www.example.com.".to_string(), RecordType::CNAME),
+                ("www.example.com.cdn.test.".to_string(), RecordType::CNAME),
+                ("edge.cdn.test.".to_string(), RecordType::A),
+            ]
+        );
+        assert_eq!(response.queries()[0].name(), &name);
+    }
+
+    #[test]
+    fn test_build_response_keeps_ttls() {
+        let (name, lookup) = aliased_lookup(RecordType::AAAA);
+        let response = Upstream::build_response(&name, RecordType::AAAA, &lookup);
+
+        let ttls: Vec<u32> = response.answers().iter().map(Record::ttl).collect();
+        assert_eq!(ttls, [3600, 600, 60]);
+        assert_eq!(response.answers()[2].record_type(), RecordType::AAAA);
+    }
 
     #[tokio::test]
     async fn test_upstream_resolver_creation() {
-        let resolver = UpstreamResolver::new(&["1.1.1.1".to_string(), "8.8.8.8".to_string()]);
-        assert!(resolver.is_ok());
+        let idle = Duration::from_secs(60);
+        let servers = ["9.9.9.9", "149.112.112.112:5353", "[2620:fe::fe]:53"]
+            .map(|entry| UpstreamServer::parse(entry).unwrap());
+        let resolver = UpstreamResolver::new(&servers, idle).unwrap();
+        let addrs: Vec<_> = resolver.upstreams.iter().map(|u| u.addr().to_string()).collect();
+        assert_eq!(addrs, ["9.9.9.9:53", "149.112.112.112:5353", "[2620:fe::fe]:53"]);
+
+        // Nothing usable is left, so Cloudflare answers
+        let mut https = UpstreamServer::parse("9.9.9.9").unwrap();
+        https.protocol = UpstreamProtocol::Https;
+        let resolver = UpstreamResolver::new(&[https], idle).unwrap();
+        assert_eq!(resolver.upstreams[0].addr().to_string(), "1.1.1.1:53");
+    }
+
+    /// `dns.upstream` entries as written in the config.
+    fn upstream_entries(entries: &str) -> Result<Vec<UpstreamServer>, toml::de::Error> {
+        #[derive(serde::Deserialize)]
+        struct Dns {
+            upstream: Vec<UpstreamServer>,
+        }
+        toml::from_str::<Dns>(&format!("upstream = [\n{}\n]", entries)).map(|dns| dns.upstream)
+    }
+
+    #[test]
+    fn test_name_servers() {
+        let servers = upstream_entries(
+            r#""9.9.9.9", " 149.112.112.112:5353 ", "[2620:fe::fe]", "[2620:fe::9]:5353",
+            { address = "1.1.1.1", protocol = "tcp" },
+            { address = "1.1.1.1", protocol = "tls", tls_name = "cloudflare-dns.com" },
+            { address = "dns.quad9.net", protocol = "tls", bootstrap = "9.9.9.9", port = 8853 },
+            { address = "[2606:4700::1111]", protocol = "tls" },"#,
+        )
+        .unwrap();
+        let configs: Vec<_> = servers
+            .iter()
+            .map(|server| {
+                let config = name_server(server).unwrap();
+                (config.protocol, config.socket_addr.to_string(), config.tls_dns_name)
+            })
+            .collect();
+        let tls_name = |name: &str| Some(name.to_string());
+        assert_eq!(
+            configs,
+            [
+                (Protocol::Udp, "9.9.9.9:53".to_string(), None),
+                (Protocol::Udp, "149.112.112.112:5353".to_string(), None),
+                (Protocol::Udp, "[2620:fe::fe]:53".to_string(), None),
+                (Protocol::Udp, "[2620:fe::9]:5353".to_string(), None),
+                (Protocol::Tcp, "1.1.1.1:53".to_string(), None),
+                (Protocol::Tls, "1.1.1.1:853".to_string(), tls_name("cloudflare-dns.com")),
+                (Protocol::Tls, "9.9.9.9:8853".to_string(), tls_name("dns.quad9.net")),
+                (Protocol::Tls, "[2606:4700::1111]:853".to_string(), tls_name("2606:4700::1111")),
+            ]
+        );
+
+        let https = upstream_entries(r#"{ address = "1.1.1.1", protocol = "https" }"#).unwrap();
+        let error = name_server(&https[0]).unwrap_err().to_string();
+        assert!(error.contains("not supported yet"), "{}", error);
+
+        for invalid in [
+            r#""1.1.1.1:99999""#,
+            r#""dns.google""#,
+            r#""[2620:fe::fe""#,
+            r#""2620:fe::fe:53:x""#,
+            r#"{ address = "dns.google", protocol = "tls" }"#,
+            r#"{ address = "1.1.1.1", tls_name = "one.one.one.one" }"#,
+            r#"{ address = "1.1.1.1", protocol = "quic" }"#,
+        ] {
+            assert!(upstream_entries(invalid).is_err(), "{} should be rejected", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upstreams_grouped_by_ip() {
+        let servers = upstream_entries(
+            r#""9.9.9.9", { address = "1.1.1.1", protocol = "tcp" }, "[2620:fe::fe]",
+            { address = "1.1.1.1", protocol = "tls", tls_name = "cloudflare-dns.com" },
+            { address = "dns.quad9.net", protocol = "tls", bootstrap = "9.9.9.9" },
+            "[2620:fe::fe]:5353", "1.0.0.1","#,
+        )
+        .unwrap();
+        let resolver = UpstreamResolver::new(&servers, Duration::from_secs(60)).unwrap();
+
+        // Each upstream is named after the first entry for its IP
+        let addrs: Vec<_> = resolver.upstreams.iter().map(|u| u.addr().to_string()).collect();
+        assert_eq!(addrs, ["9.9.9.9:53", "1.1.1.1:53", "[2620:fe::fe]:53", "1.0.0.1:53"]);
+        let transports: Vec<_> =
+            resolver.upstreams.iter().map(|u| u.config.name_servers().len()).collect();
+        assert_eq!(transports, [2, 2, 2, 1]);
+
+        // Unusable entries are skipped, the others still grouped
+        let mut servers = servers;
+        servers[1].protocol = UpstreamProtocol::Https;
+        let resolver = UpstreamResolver::new(&servers, Duration::from_secs(60)).unwrap();
+        let addrs: Vec<_> = resolver.upstreams.iter().map(|u| u.addr().to_string()).collect();
+        assert_eq!(addrs, ["9.9.9.9:53", "[2620:fe::fe]:53", "1.1.1.1:853", "1.0.0.1:53"]);
+        let transports: Vec<_> =
+            resolver.upstreams.iter().map(|u| u.config.name_servers().len()).collect();
+        assert_eq!(transports, [2, 2, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_connections() {
+        let idle = Duration::from_secs(60);
+        let udp = UpstreamServer::parse("127.0.0.1:5353").unwrap();
+        let tcp = UpstreamServer {
+            protocol: UpstreamProtocol::Tcp,
+            ..udp.clone()
+        };
+        let timeout = Duration::from_secs(1);
+        let resolver = UpstreamResolver::for_servers(&[udp, tcp], timeout, idle).unwrap();
+        assert_eq!(resolver.upstreams.len(), 1);
+        assert!(resolver.upstreams[0].persistent);
+
+        let resolver = UpstreamResolver::new(&[], idle).unwrap();
+        assert!(resolver.upstreams.len() > 1);
+        let udp = UpstreamServer::parse("127.0.0.1:5353").unwrap();
+        let resolver = UpstreamResolver::for_servers(&[udp], timeout, idle).unwrap();
+        assert!(!resolver.upstreams[0].persistent);
+    }
+
+    #[tokio::test]
+    async fn test_tls_servers() {
+        let idle = Duration::from_secs(60);
+        let timeout = Duration::from_secs(1);
+        let mut tls = UpstreamServer::parse("1.1.1.1").unwrap();
+        tls.protocol = UpstreamProtocol::Tls;
+        tls.tls_name = Some("cloudflare-dns.com".to_string());
+
+        let config = name_server(&tls).unwrap();
+        assert_eq!(config.protocol, Protocol::Tls);
+        assert_eq!(config.socket_addr, "1.1.1.1:853".parse().unwrap());
+        assert_eq!(config.tls_dns_name.as_deref(), Some("cloudflare-dns.com"));
+
+        let resolver = UpstreamResolver::for_servers(&[tls.clone()], timeout, idle).unwrap();
+        assert!(resolver.upstreams[0].persistent);
+
+        tls.protocol = UpstreamProtocol::Https;
+        assert!(UpstreamResolver::for_servers(&[tls], timeout, idle).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        use crate::dns::mock::{MockFailure, MockUpstream};
+
+        let addr = "192.0.2.7".parse().unwrap();
+        let resolver = UpstreamResolver::with_upstreams([
+            (
+                "down".to_string(),
+                MockUpstream::new().answer("example.com", addr).latency(Duration::from_millis(20)),
+            ),
+            ("up".to_string(), MockUpstream::new().answer("example.com", addr)),
+        ]);
+        resolver.upstreams[0].set_failure(Some(MockFailure::Timeout));
+
+        let name = Name::from_ascii("example.com.").unwrap();
+        let response = resolver.resolve(&name, RecordType::A).await.unwrap();
+        assert_eq!(response.answers().len(), 1);
+        // The failed upstream is demoted and not asked again
+        assert_eq!(resolver.scores.order(), vec![1, 0]);
+        resolver.resolve(&name, RecordType::A).await.unwrap();
+        assert_eq!(resolver.upstreams[0].queries(), 1);
+
+        // "No such name" is an answer, not a reason to try the next upstream
+        let missing = Name::from_ascii("missing.com.").unwrap();
+        let error = resolver.resolve(&missing, RecordType::A).await.unwrap_err();
+        assert!(error.is_nx_domain());
+        assert_eq!(resolver.upstreams[0].queries(), 1);
+
+        resolver.upstreams[1].set_failure(Some(MockFailure::ConnectionLost));
+        let error = resolver.resolve(&name, RecordType::A).await.unwrap_err();
+        assert!(!error.is_no_records_found());
+    }
+
+    #[test]
+    fn test_connection_errors() {
+        use hickory_proto::ProtoError;
+        use std::io;
+
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(is_connection_error(&ProtoError::from(reset).into()));
+        let timeout = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(!is_connection_error(&ProtoError::from(timeout).into()));
+        assert!(!is_connection_error(&ResolveError::from("no records")));
     }
 }
@@ -0,0 +1,556 @@
+//! Message catalog.
+//!
+//! Every message has one template per locale, in the order of
+//! [`Locale::ALL`]. Templates use `{}` placeholders filled in order.
+
+use blockandfocus_shared::Locale;
+use std::fmt::Display;
+
+/// User-facing messages produced by the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    /// Memory quiz reveal phase; takes the item count
+    QuizMemorize,
+    /// Memory quiz answer phase; takes the item count
+    QuizRecall,
+    /// Wait stage; takes the number of seconds
+    QuizWait,
+    /// Confirmation stage; takes the sentence
+    QuizConfirm,
+    QuizNotFound,
+    QuizExpired,
+    QuizTooFast,
+    QuizWrongAnswerCount,
+    QuizWrongAnswer,
+    QuizWrongPhase,
+    QuizStillWaiting,
+    /// Takes the minimum reason length
+    ReasonRequired,
+    /// Takes the remaining bypass minutes
+    BudgetExceeded,
+    /// Takes the maximum snooze length
+    SnoozeRange,
+    SnoozedToday,
+    NothingToSnooze,
+    FocusTooShort,
+    DomainNotInBlocklist,
+    DomainNotEssential,
+    EssentialDuringBlocking,
+    RestoreDuringBlocking,
+    /// Takes the group name
+    UnknownGroup,
+    /// Takes the profile name
+    UnknownClientProfile,
+    StrictNoBypass,
+    StrictNoWeakening,
+    StrictNoShutdown,
+    /// Takes the remaining minutes
+    SettingsLocked,
+    StateUnreadable,
+    /// Takes the maximum number of hours
+    SettingsLockRange,
+    /// Takes the minimum notice in hours
+    VacationNotice,
+    VacationInvalid,
+    VacationOverlap,
+    VacationStarted,
+    VacationNotFound,
+    UntrustedClient,
+    PurgeConfirmationInvalid,
+    /// Takes the listen address
+    SelfCheckListenerDown,
+    SelfCheckNotRouted,
+    SelfCheckBlockedResolves,
+    SelfCheckAllowedFails,
+    SelfCheckOk,
+    BlockPageTitle,
+    /// Takes the site and the blocklist entry blocking it
+    BlockPageEntry,
+    /// Takes the site
+    BlockPageUnblocked,
+    /// Takes the schedule rule name
+    BlockPageRule,
+    BlockPageAlwaysOn,
+    BlockPageRequestBypass,
+    BlockPageBypassRequested,
+    /// Weekly report subject; uses the report's named placeholders
+    WeeklyReportSubject,
+    /// Weekly report text; uses the report's named placeholders
+    WeeklyReportText,
+}
+
+/// Templates for a message: English, Italian, Spanish, German, French.
+fn templates(msg: Msg) -> [&'static str; 5] {
+    match msg {
+        Msg::QuizMemorize => [
+            "Memorize these {} items in order",
+            "Memorizza questi {} elementi nell'ordine",
+            "Memoriza estos {} elementos en orden",
+            "Merke dir diese {} Elemente in der richtigen Reihenfolge",
+            "Mémorisez ces {} éléments dans l'ordre",
+        ],
+        Msg::QuizRecall => [
+            "Type the {} items you were shown, in order",
+            "Scrivi i {} elementi che ti sono stati mostrati, nell'ordine",
+            "Escribe los {} elementos que se te mostraron, en orden",
+            "Gib die {} angezeigten Elemente in der richtigen Reihenfolge ein",
+            "Saisissez les {} éléments affichés, dans l'ordre",
+        ],
+        Msg::QuizWait => [
+            "Wait {} seconds to continue",
+            "Attendi {} secondi per continuare",
+            "Espera {} segundos para continuar",
+            "Warte {} Sekunden, um fortzufahren",
+            "Attendez {} secondes pour continuer",
+        ],
+        Msg::QuizConfirm => [
+            "Type exactly: \"{}\"",
+            "Scrivi esattamente: \"{}\"",
+            "Escribe exactamente: \"{}\"",
+            "Gib genau ein: \"{}\"",
+            "Saisissez exactement : « {} »",
+        ],
+        Msg::QuizNotFound => [
+            "Quiz challenge not found",
+            "Quiz non trovato",
+            "No se encontró el cuestionario",
+            "Quiz nicht gefunden",
+            "Quiz introuvable",
+        ],
+        Msg::QuizExpired => [
+            "Quiz challenge has expired",
+            "Il quiz è scaduto",
+            "El cuestionario ha caducado",
+            "Das Quiz ist abgelaufen",
+            "Le quiz a expiré",
+        ],
+        Msg::QuizTooFast => [
+            "Quiz was solved too quickly",
+            "Il quiz è stato risolto troppo in fretta",
+            "El cuestionario se resolvió demasiado rápido",
+            "Das Quiz wurde zu schnell gelöst",
+            "Le quiz a été résolu trop rapidement",
+        ],
+        Msg::QuizWrongAnswerCount => [
+            "Wrong number of answers",
+            "Numero di risposte errato",
+            "Número de respuestas incorrecto",
+            "Falsche Anzahl an Antworten",
+            "Nombre de réponses incorrect",
+        ],
+        Msg::QuizWrongAnswer => [
+            "One or more answers are incorrect",
+            "Una o più risposte sono sbagliate",
+            "Una o más respuestas son incorrectas",
+            "Eine oder mehrere Antworten sind falsch",
+            "Une ou plusieurs réponses sont incorrectes",
+        ],
+        Msg::QuizWrongPhase => [
+            "Quiz challenge is not in the right phase",
+            "Il quiz non è nella fase corretta",
+            "El cuestionario no está en la fase correcta",
+            "Das Quiz ist nicht in der richtigen Phase",
+            "Le quiz n'est pas dans la bonne phase",
+        ],
+        Msg::QuizStillWaiting => [
+            "The waiting period has not elapsed yet",
+            "Il periodo di attesa non è ancora trascorso",
+            "El período de espera aún no ha terminado",
+            "Die Wartezeit ist noch nicht abgelaufen",
+            "Le délai d'attente n'est pas encore écoulé",
+        ],
+        Msg::ReasonRequired => [
+            "Please give a reason of at least {} characters",
+            "Indica un motivo di almeno {} caratteri",
+            "Indica un motivo de al menos {} caracteres",
+            "Bitte gib einen Grund mit mindestens {} Zeichen an",
+            "Indiquez une raison d'au moins {} caractères",
+        ],
+        Msg::BudgetExceeded => [
+            "Only {} bypass minutes left today",
+            "Oggi restano solo {} minuti di pausa",
+            "Hoy solo quedan {} minutos de pausa",
+            "Heute sind nur noch {} Pausenminuten übrig",
+            "Il ne reste que {} minutes de pause aujourd'hui",
+        ],
+        Msg::SnoozeRange => [
+            "Snooze must be between 1 and {} minutes",
+            "Il rinvio deve durare tra 1 e {} minuti",
+            "El aplazamiento debe durar entre 1 y {} minutos",
+            "Das Aufschieben muss zwischen 1 und {} Minuten dauern",
+            "Le report doit durer entre 1 et {} minutes",
+        ],
+        Msg::SnoozedToday => [
+            "The schedule was already snoozed today",
+            "La pianificazione è già stata rinviata oggi",
+            "La programación ya se aplazó hoy",
+            "Der Zeitplan wurde heute bereits aufgeschoben",
+            "Le planning a déjà été reporté aujourd'hui",
+        ],
+        Msg::NothingToSnooze => [
+            "No upcoming schedule rule to snooze",
+            "Nessuna regola in arrivo da rinviare",
+            "No hay ninguna regla próxima que aplazar",
+            "Keine anstehende Zeitplanregel zum Aufschieben",
+            "Aucune règle à venir à reporter",
+        ],
+        Msg::FocusTooShort => [
+            "Focus duration must be at least one minute",
+            "La durata della concentrazione deve essere di almeno un minuto",
+            "La sesión de concentración debe durar al menos un minuto",
+            "Die Fokusdauer muss mindestens eine Minute betragen",
+            "La durée de concentration doit être d'au moins une minute",
+        ],
+        Msg::DomainNotInBlocklist => [
+            "Domain not found in blocklist",
+            "Dominio non presente nella lista di blocco",
+            "El dominio no está en la lista de bloqueo",
+            "Domain nicht in der Sperrliste gefunden",
+            "Domaine absent de la liste de blocage",
+        ],
+        Msg::DomainNotEssential => [
+            "Domain not found in the essential-services list",
+            "Dominio non presente nell'elenco dei servizi essenziali",
+            "El dominio no está en la lista de servicios esenciales",
+            "Domain nicht in der Liste wichtiger Dienste gefunden",
+            "Domaine absent de la liste des services essentiels",
+        ],
+        Msg::EssentialDuringBlocking => [
+            "Essential services can only be added outside blocking hours",
+            "I servizi essenziali si possono aggiungere solo fuori dagli orari di blocco",
+            "Los servicios esenciales solo se pueden añadir fuera del horario de bloqueo",
+            "Wichtige Dienste können nur außerhalb der Sperrzeiten hinzugefügt werden",
+            "Les services essentiels ne peuvent être ajoutés qu'en dehors des heures de blocage",
+        ],
+        Msg::RestoreDuringBlocking => [
+            "A backup can only be restored outside blocking hours",
+            "Un backup si può ripristinare solo fuori dagli orari di blocco",
+            "Una copia de seguridad solo se puede restaurar fuera del horario de bloqueo",
+            "Eine Sicherung kann nur außerhalb der Sperrzeiten wiederhergestellt werden",
+            "Une sauvegarde ne peut être restaurée qu'en dehors des heures de blocage",
+        ],
+        Msg::UnknownGroup => [
+            "No blocklist group named {}",
+            "Nessun gruppo di blocco chiamato {}",
+            "No hay ningún grupo de bloqueo llamado {}",
+            "Keine Sperrgruppe namens {}",
+            "Aucun groupe de blocage nommé {}",
+        ],
+        Msg::UnknownClientProfile => [
+            "No client profile named {}",
+            "Nessun profilo dispositivo chiamato {}",
+            "No hay ningún perfil de dispositivo llamado {}",
+            "Kein Geräteprofil namens {}",
+            "Aucun profil d'appareil nommé {}",
+        ],
+        Msg::StrictNoBypass => [
+            "Bypasses are not allowed while a strict schedule rule is active",
+            "Non è possibile sospendere il blocco mentre è attiva una regola rigorosa",
+            "No se permiten excepciones mientras haya una regla estricta activa",
+            "Während eine strenge Zeitplanregel aktiv ist, sind keine Ausnahmen erlaubt",
+            "Aucune exception n'est permise tant qu'une règle stricte est active",
+        ],
+        Msg::StrictNoWeakening => [
+            "The blocklist and schedule cannot be weakened while a strict schedule rule is active",
+            "La lista di blocco e la pianificazione non possono essere allentate mentre è attiva una regola rigorosa",
+            "La lista de bloqueo y el horario no se pueden relajar mientras haya una regla estricta activa",
+            "Sperrliste und Zeitplan können nicht gelockert werden, solange eine strenge Regel aktiv ist",
+            "La liste de blocage et le planning ne peuvent pas être assouplis tant qu'une règle stricte est active",
+        ],
+        Msg::StrictNoShutdown => [
+            "The daemon cannot be stopped while a strict schedule rule is active",
+            "Il servizio non può essere arrestato mentre è attiva una regola rigorosa",
+            "El servicio no se puede detener mientras haya una regla estricta activa",
+            "Der Dienst kann nicht beendet werden, solange eine strenge Regel aktiv ist",
+            "Le service ne peut pas être arrêté tant qu'une règle stricte est active",
+        ],
+        Msg::SettingsLocked => [
+            "Settings are locked for another {} minutes",
+            "Le impostazioni sono bloccate per altri {} minuti",
+            "La configuración está bloqueada durante {} minutos más",
+            "Die Einstellungen sind noch {} Minuten gesperrt",
+            "Les réglages sont verrouillés pendant encore {} minutes",
+        ],
+        Msg::StateUnreadable => [
+            "Settings are locked: the state file, which may hold a settings lock, could not be read",
+            "Le impostazioni sono bloccate: il file di stato, che può contenere un blocco delle impostazioni, non è leggibile",
+            "La configuración está bloqueada: no se pudo leer el archivo de estado, que puede contener un bloqueo de la configuración",
+            "Die Einstellungen sind gesperrt: Die Zustandsdatei, die eine Einstellungssperre enthalten kann, ist nicht lesbar",
+            "Les réglages sont verrouillés : le fichier d'état, qui peut contenir un verrouillage des réglages, est illisible",
+        ],
+        Msg::SettingsLockRange => [
+            "Settings can be locked for 1 to {} hours",
+            "Le impostazioni possono essere bloccate da 1 a {} ore",
+            "La configuración se puede bloquear de 1 a {} horas",
+            "Die Einstellungen können für 1 bis {} Stunden gesperrt werden",
+            "Les réglages peuvent être verrouillés de 1 à {} heures",
+        ],
+        Msg::VacationNotice => [
+            "Vacations must be scheduled at least {} hours before they start",
+            "Le vacanze vanno programmate almeno {} ore prima dell'inizio",
+            "Las vacaciones deben programarse al menos {} horas antes de empezar",
+            "Urlaube müssen mindestens {} Stunden vor Beginn geplant werden",
+            "Les vacances doivent être programmées au moins {} heures avant leur début",
+        ],
+        Msg::VacationInvalid => [
+            "A vacation cannot end before it starts",
+            "Una vacanza non può finire prima di iniziare",
+            "Unas vacaciones no pueden terminar antes de empezar",
+            "Ein Urlaub kann nicht vor seinem Beginn enden",
+            "Des vacances ne peuvent pas se terminer avant de commencer",
+        ],
+        Msg::VacationOverlap => [
+            "This vacation overlaps one that is already scheduled",
+            "Questa vacanza si sovrappone a una già programmata",
+            "Estas vacaciones coinciden con otras ya programadas",
+            "Dieser Urlaub überschneidet sich mit einem bereits geplanten",
+            "Ces vacances chevauchent des vacances déjà programmées",
+        ],
+        Msg::VacationStarted => [
+            "A vacation cannot be changed once it has started",
+            "Una vacanza non può essere modificata dopo l'inizio",
+            "Unas vacaciones no se pueden modificar una vez empezadas",
+            "Ein Urlaub kann nach Beginn nicht mehr geändert werden",
+            "Des vacances ne peuvent plus être modifiées une fois commencées",
+        ],
+        Msg::VacationNotFound => [
+            "Vacation not found",
+            "Vacanza non trovata",
+            "Vacaciones no encontradas",
+            "Urlaub nicht gefunden",
+            "Vacances introuvables",
+        ],
+        Msg::PurgeConfirmationInvalid => [
+            "Confirmation is wrong or expired; request a new one",
+            "Conferma errata o scaduta; richiedine una nuova",
+            "La confirmación es incorrecta o ha caducado; solicita una nueva",
+            "Bestätigung falsch oder abgelaufen; fordere eine neue an",
+            "Confirmation incorrecte ou expirée ; demandez-en une nouvelle",
+        ],
+        Msg::UntrustedClient => [
+            "Only the BlockAndFocus app can change settings",
+            "Solo l'app BlockAndFocus può modificare le impostazioni",
+            "Solo la app BlockAndFocus puede cambiar la configuración",
+            "Nur die BlockAndFocus-App kann Einstellungen ändern",
+            "Seule l'application BlockAndFocus peut modifier les réglages",
+        ],
+        Msg::SelfCheckListenerDown => [
+            "The DNS server does not answer on {}",
+            "Il server DNS non risponde su {}",
+            "El servidor DNS no responde en {}",
+            "Der DNS-Server antwortet nicht auf {}",
+            "Le serveur DNS ne répond pas sur {}",
+        ],
+        Msg::SelfCheckNotRouted => [
+            "The daemon is running, but the system is not sending DNS queries to it",
+            "Il daemon è attivo, ma il sistema non gli invia le query DNS",
+            "El daemon está en ejecución, pero el sistema no le envía las consultas DNS",
+            "Der Daemon läuft, aber das System sendet keine DNS-Anfragen an ihn",
+            "Le démon fonctionne, mais le système ne lui envoie pas les requêtes DNS",
+        ],
+        Msg::SelfCheckBlockedResolves => [
+            "DNS is routed through the daemon, but a blocked name still resolves (check for cached or encrypted DNS)",
+            "Il DNS passa dal daemon, ma un nome bloccato viene ancora risolto (controlla cache o DNS cifrato)",
+            "El DNS pasa por el daemon, pero un nombre bloqueado aún se resuelve (revisa la caché o el DNS cifrado)",
+            "DNS läuft über den Daemon, aber ein gesperrter Name wird noch aufgelöst (Cache oder verschlüsseltes DNS prüfen)",
+            "Le DNS passe par le démon, mais un nom bloqué est encore résolu (vérifiez le cache ou le DNS chiffré)",
+        ],
+        Msg::SelfCheckAllowedFails => [
+            "DNS is routed through the daemon, but allowed names do not resolve (check upstream connectivity)",
+            "Il DNS passa dal daemon, ma i nomi consentiti non vengono risolti (controlla la connessione ai server upstream)",
+            "El DNS pasa por el daemon, pero los nombres permitidos no se resuelven (revisa la conexión con los servidores upstream)",
+            "DNS läuft über den Daemon, aber erlaubte Namen werden nicht aufgelöst (Verbindung zu den Upstream-Servern prüfen)",
+            "Le DNS passe par le démon, mais les noms autorisés ne sont pas résolus (vérifiez la connexion aux serveurs upstream)",
+        ],
+        Msg::SelfCheckOk => [
+            "DNS is routed through the daemon and blocking works",
+            "Il DNS passa dal daemon e il blocco funziona",
+            "El DNS pasa por el daemon y el bloqueo funciona",
+            "DNS läuft über den Daemon und die Sperre funktioniert",
+            "Le DNS passe par le démon et le blocage fonctionne",
+        ],
+        Msg::BlockPageTitle => [
+            "Blocked by BlockAndFocus",
+            "Bloccato da BlockAndFocus",
+            "Bloqueado por BlockAndFocus",
+            "Gesperrt von BlockAndFocus",
+            "Bloqué par BlockAndFocus",
+        ],
+        Msg::BlockPageEntry => [
+            "{} is blocked by the blocklist entry {}",
+            "{} è bloccato dalla voce {} della blocklist",
+            "{} está bloqueado por la entrada {} de la lista de bloqueo",
+            "{} ist durch den Sperrlisten-Eintrag {} gesperrt",
+            "{} est bloqué par l'entrée {} de la liste de blocage",
+        ],
+        Msg::BlockPageUnblocked => [
+            "{} isn't blocked anymore; reload the page to open it",
+            "{} non è più bloccato; ricarica la pagina per aprirlo",
+            "{} ya no está bloqueado; recarga la página para abrirlo",
+            "{} ist nicht mehr gesperrt; lade die Seite neu, um sie zu öffnen",
+            "{} n'est plus bloqué ; rechargez la page pour l'ouvrir",
+        ],
+        Msg::BlockPageRule => [
+            "Active rule: {}",
+            "Regola attiva: {}",
+            "Regla activa: {}",
+            "Aktive Regel: {}",
+            "Règle active : {}",
+        ],
+        Msg::BlockPageAlwaysOn => [
+            "Blocking is on outside of schedule rules",
+            "Il blocco è attivo al di fuori delle regole di pianificazione",
+            "El bloqueo está activo fuera de las reglas del horario",
+            "Die Sperre ist außerhalb der Zeitplanregeln aktiv",
+            "Le blocage est actif en dehors des règles du planning",
+        ],
+        Msg::BlockPageRequestBypass => [
+            "Request a bypass",
+            "Richiedi una pausa",
+            "Solicitar una pausa",
+            "Pause anfordern",
+            "Demander une pause",
+        ],
+        Msg::BlockPageBypassRequested => [
+            "BlockAndFocus is open on your screen; request the bypass there",
+            "BlockAndFocus è aperto sullo schermo; richiedi la pausa da lì",
+            "BlockAndFocus está abierto en la pantalla; solicita la pausa allí",
+            "BlockAndFocus ist auf dem Bildschirm geöffnet; fordere die Pause dort an",
+            "BlockAndFocus est ouvert à l'écran ; demandez la pause depuis l'application",
+        ],
+        Msg::WeeklyReportSubject => [
+            "BlockAndFocus weekly report, {week_start} to {week_end}",
+            "Resoconto settimanale di BlockAndFocus, dal {week_start} al {week_end}",
+            "Informe semanal de BlockAndFocus, del {week_start} al {week_end}",
+            "BlockAndFocus-Wochenbericht, {week_start} bis {week_end}",
+            "Rapport hebdomadaire BlockAndFocus, du {week_start} au {week_end}",
+        ],
+        Msg::WeeklyReportText => [
+            "Week of {week_start} to {week_end}\n\n\
+             Blocked queries: {blocked} (busiest day: {busiest_day})\n\
+             Bypasses: {bypasses}, {bypass_minutes} minutes in total, \
+             running {overrun_minutes} minutes over on average\n\
+             Most common reasons: {top_reasons}\n\
+             Time on limited groups: {groups}\n",
+            "Settimana dal {week_start} al {week_end}\n\n\
+             Richieste bloccate: {blocked} (giorno più intenso: {busiest_day})\n\
+             Pause: {bypasses}, {bypass_minutes} minuti in totale, \
+             sforando in media di {overrun_minutes} minuti\n\
+             Motivi più frequenti: {top_reasons}\n\
+             Tempo sui gruppi limitati: {groups}\n",
+            "Semana del {week_start} al {week_end}\n\n\
+             Consultas bloqueadas: {blocked} (día con más bloqueos: {busiest_day})\n\
+             Pausas: {bypasses}, {bypass_minutes} minutos en total, \
+             excediéndose {overrun_minutes} minutos de media\n\
+             Motivos más frecuentes: {top_reasons}\n\
+             Tiempo en grupos limitados: {groups}\n",
+            "Woche vom {week_start} bis {week_end}\n\n\
+             Gesperrte Anfragen: {blocked} (aktivster Tag: {busiest_day})\n\
+             Pausen: {bypasses}, insgesamt {bypass_minutes} Minuten, \
+             im Schnitt {overrun_minutes} Minuten überzogen\n\
+             Häufigste Gründe: {top_reasons}\n\
+             Zeit in begrenzten Gruppen: {groups}\n",
+            "Semaine du {week_start} au {week_end}\n\n\
+             Requêtes bloquées : {blocked} (jour le plus chargé : {busiest_day})\n\
+             Pauses : {bypasses}, {bypass_minutes} minutes au total, \
+             avec un dépassement moyen de {overrun_minutes} minutes\n\
+             Raisons les plus fréquentes : {top_reasons}\n\
+             Temps sur les groupes limités : {groups}\n",
+        ],
+    }
+}
+
+/// Translate a message, filling its `{}` placeholders with `args` in order.
+pub fn tr(locale: Locale, msg: Msg, args: &[&dyn Display]) -> String {
+    let index = Locale::ALL.iter().position(|l| *l == locale).unwrap_or(0);
+    let template = templates(msg)[index];
+
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    let mut text = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            text.push_str(&arg.to_string());
+        }
+        text.push_str(part);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tr() {
+        assert_eq!(tr(Locale::En, Msg::QuizWait, &[&30]), "Wait 30 seconds to continue");
+        assert_eq!(tr(Locale::It, Msg::QuizWait, &[&30]), "Attendi 30 secondi per continuare");
+        assert_eq!(tr(Locale::Fr, Msg::QuizConfirm, &[&"je veux"]), "Saisissez exactement : « je veux »");
+        assert_eq!(tr(Locale::De, Msg::QuizExpired, &[]), "Das Quiz ist abgelaufen");
+    }
+
+    #[test]
+    fn test_placeholders_match_across_locales() {
+        let all = [
+            Msg::QuizMemorize,
+            Msg::QuizRecall,
+            Msg::QuizWait,
+            Msg::QuizConfirm,
+            Msg::QuizNotFound,
+            Msg::QuizExpired,
+            Msg::QuizTooFast,
+            Msg::QuizWrongAnswerCount,
+            Msg::QuizWrongAnswer,
+            Msg::QuizWrongPhase,
+            Msg::QuizStillWaiting,
+            Msg::ReasonRequired,
+            Msg::BudgetExceeded,
+            Msg::SnoozeRange,
+            Msg::SnoozedToday,
+            Msg::NothingToSnooze,
+            Msg::FocusTooShort,
+            Msg::DomainNotInBlocklist,
+            Msg::DomainNotEssential,
+            Msg::EssentialDuringBlocking,
+            Msg::RestoreDuringBlocking,
+            Msg::UnknownGroup,
+            Msg::UnknownClientProfile,
+            Msg::StrictNoBypass,
+            Msg::StrictNoWeakening,
+            Msg::StrictNoShutdown,
+            Msg::SettingsLocked,
+            Msg::StateUnreadable,
+            Msg::SettingsLockRange,
+            Msg::VacationNotice,
+            Msg::VacationInvalid,
+            Msg::VacationOverlap,
+            Msg::VacationStarted,
+            Msg::VacationNotFound,
+            Msg::UntrustedClient,
+            Msg::PurgeConfirmationInvalid,
+            Msg::SelfCheckListenerDown,
+            Msg::SelfCheckNotRouted,
+            Msg::SelfCheckBlockedResolves,
+            Msg::SelfCheckAllowedFails,
+            Msg::SelfCheckOk,
+            Msg::WeeklyReportSubject,
+            Msg::WeeklyReportText,
+            Msg::BlockPageTitle,
+            Msg::BlockPageEntry,
+            Msg::BlockPageUnblocked,
+            Msg::BlockPageRule,
+            Msg::BlockPageAlwaysOn,
+            Msg::BlockPageRequestBypass,
+            Msg::BlockPageBypassRequested,
+        ];
+
+        for msg in all {
+            let templates = templates(msg);
+            let expected = templates[0].matches("{}").count();
+            for template in templates {
+                assert_eq!(template.matches("{}").count(), expected, "{:?}: {}", msg, template);
+            }
+        }
+    }
+}
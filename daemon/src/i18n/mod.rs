@@ -0,0 +1,5 @@
+//! Translations of user-facing text produced by the daemon.
+
+mod catalog;
+
+pub use catalog::{tr, Msg};
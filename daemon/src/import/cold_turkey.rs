@@ -0,0 +1,87 @@
+//! Cold Turkey Blocker exports: its settings JSON, with each block's sites
+//! under `blocks.<name>.web`, or a plain list of sites, one per line.
+//! Schedules and exceptions aren't converted; they are listed as skipped.
+
+use super::group_of;
+use anyhow::{Context, Result};
+use blockandfocus_shared::ImportPreview;
+use serde_json::Value;
+
+/// Name of the group a plain list of sites is imported into.
+const LIST_GROUP_NAME: &str = "Cold Turkey";
+
+pub fn read(data: &[u8]) -> Result<ImportPreview> {
+    let text = std::str::from_utf8(data).context("Not a text file")?;
+    let mut preview = ImportPreview::default();
+
+    if !text.trim_start().starts_with('{') {
+        let sites = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+        preview.groups.extend(group_of(LIST_GROUP_NAME, sites, &mut preview.skipped));
+        return Ok(preview);
+    }
+
+    let settings: Value = serde_json::from_str(text).context("Settings are not valid JSON")?;
+    let blocks = settings
+        .get("blocks")
+        .and_then(Value::as_object)
+        .context("No blocks in the settings")?;
+    for (name, block) in blocks {
+        let sites = block.get("web").and_then(Value::as_array).into_iter().flatten();
+        let sites = sites.filter_map(Value::as_str);
+        preview.groups.extend(group_of(name, sites, &mut preview.skipped));
+
+        for key in ["schedule", "exceptions"] {
+            if block.get(key).is_some_and(is_set) {
+                let what = format!("{} of block \"{}\"", key, name);
+                preview.skipped.push(format!("{}: not converted, set it up by hand", what));
+            }
+        }
+    }
+
+    Ok(preview)
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_settings() {
+        let settings = serde_json::json!({
+            "blocks": {
+                "Social": {
+                    "web": ["facebook.com", "https://twitter.com/", "youtube.com/shorts"],
+                    "schedule": [{"day": "mon", "from": "09:00", "to": "17:00"}],
+                    "exceptions": []
+                },
+                "Nothing": { "web": [] }
+            }
+        });
+        let preview = read(settings.to_string().as_bytes()).unwrap();
+        assert_eq!(preview.groups.len(), 1);
+        assert_eq!(preview.groups[0].name, "Social");
+        assert_eq!(preview.groups[0].domains, vec!["facebook.com", "twitter.com"]);
+        assert_eq!(preview.skipped.len(), 2);
+        assert!(preview.skipped[1].starts_with("schedule of block \"Social\""));
+
+        assert!(read(b"{\"version\": 4}").is_err());
+    }
+
+    #[test]
+    fn test_read_list() {
+        let preview = read(b"# Exported\nnews.ycombinator.com\n\nlobste.rs\n").unwrap();
+        assert_eq!(preview.groups[0].name, "Cold Turkey");
+        assert_eq!(preview.groups[0].domains, vec!["news.ycombinator.com", "lobste.rs"]);
+        assert!(preview.skipped.is_empty());
+    }
+}
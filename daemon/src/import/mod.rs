@@ -0,0 +1,138 @@
+//! Importing other blockers' exports as blocklist groups and subscriptions.
+
+mod cold_turkey;
+mod pihole;
+mod selfcontrol;
+
+use anyhow::Result;
+use blockandfocus_shared::{BlocklistGroup, Config, DomainName, ImportPreview, ImportSource};
+use std::net::IpAddr;
+
+/// Read an export from `source` into what importing it would create.
+pub async fn read_export(source: ImportSource, data: &[u8]) -> Result<ImportPreview> {
+    match source {
+        ImportSource::SelfControl => selfcontrol::read(data),
+        ImportSource::ColdTurkey => cold_turkey::read(data),
+        ImportSource::PiHole => pihole::read(data).await,
+    }
+}
+
+/// Add an import's groups and subscriptions to `config`. A group whose name
+/// already exists gets the new sites added to it.
+pub fn merge_import(config: &mut Config, import: &ImportPreview) {
+    for group in &import.groups {
+        match config.blocking.groups.iter_mut().find(|g| g.name == group.name) {
+            Some(existing) => {
+                for domain in &group.domains {
+                    if !existing.domains.contains(domain) {
+                        existing.domains.push(domain.clone());
+                    }
+                }
+            }
+            None => config.blocking.groups.push(group.clone()),
+        }
+    }
+
+    for list in &import.subscriptions {
+        if !config.subscriptions.lists.iter().any(|l| l.url == list.url) {
+            config.subscriptions.lists.push(list.clone());
+        }
+    }
+}
+
+/// A group of the sites among `entries`, or None if there are none. Entries
+/// that aren't whole sites are added to `skipped`.
+fn group_of<'a>(
+    name: &str,
+    entries: impl IntoIterator<Item = &'a str>,
+    skipped: &mut Vec<String>,
+) -> Option<BlocklistGroup> {
+    let mut domains: Vec<DomainName> = Vec::new();
+    for entry in entries {
+        match entry_domain(entry) {
+            Ok(domain) if domains.contains(&domain) => {}
+            Ok(domain) => domains.push(domain),
+            Err(reason) => skipped.push(format!("{}: {}", entry.trim(), reason)),
+        }
+    }
+
+    (!domains.is_empty()).then(|| BlocklistGroup {
+        name: name.to_string(),
+        domains,
+        weekly_budget_minutes: None,
+        enabled: true,
+    })
+}
+
+/// The site a blocker entry covers (subdomains included, as in every
+/// blocklist here), or why it has no equivalent.
+fn entry_domain(entry: &str) -> Result<DomainName, &'static str> {
+    let entry = entry.trim();
+    let entry = entry.split_once("://").map_or(entry, |(_, rest)| rest);
+    let (host, path) = entry.split_once('/').unwrap_or((entry, ""));
+    let host = host
+        .rsplit_once(':')
+        .filter(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        .map_or(host, |(host, _)| host);
+    let host = host.strip_prefix("*.").unwrap_or(host);
+
+    if host.parse::<IpAddr>().is_ok() {
+        return Err("IP addresses can't be blocked through DNS");
+    }
+    if !path.is_empty() {
+        return Err("blocks a single page, not a whole site");
+    }
+    if host.contains('*') {
+        return Err("wildcards are only supported at the start");
+    }
+    DomainName::parse(host).map_err(|_| "not a domain name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::BlocklistSubscription;
+
+    #[test]
+    fn test_entry_domain() {
+        let ok = |entry| entry_domain(entry).unwrap().to_string();
+        assert_eq!(ok("reddit.com"), "reddit.com");
+        assert_eq!(ok(" *.Reddit.com "), "reddit.com");
+        assert_eq!(ok("https://news.ycombinator.com/"), "news.ycombinator.com");
+        assert_eq!(ok("example.org:443"), "example.org");
+
+        assert!(entry_domain("reddit.com/r/all").is_err());
+        assert!(entry_domain("10.0.0.0/8").unwrap_err().contains("IP"));
+        assert!(entry_domain("1.2.3.4").is_err());
+        assert!(entry_domain("face*.com").is_err());
+        assert!(entry_domain("not a domain").is_err());
+    }
+
+    #[test]
+    fn test_merge_import() {
+        let mut config = Config::default();
+        let mut skipped = Vec::new();
+        config.blocking.groups = vec![group_of("Social", ["a.com"], &mut skipped).unwrap()];
+        let import = ImportPreview {
+            groups: vec![
+                group_of("Social", ["a.com", "b.com"], &mut skipped).unwrap(),
+                group_of("News", ["c.com", "c.com", "x/y"], &mut skipped).unwrap(),
+            ],
+            subscriptions: vec![BlocklistSubscription {
+                name: "Ads".to_string(),
+                url: "https://lists.example/ads.txt".to_string(),
+                enabled: true,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(skipped, vec!["x/y: blocks a single page, not a whole site"]);
+
+        merge_import(&mut config, &import);
+        merge_import(&mut config, &import);
+        let groups = &config.blocking.groups;
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].domains.len(), 2);
+        assert_eq!(groups[1].domains.len(), 1);
+        assert_eq!(config.subscriptions.lists.len(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+//! Pi-hole Teleporter archives. Pi-hole 5 exports a `.tar.gz` with its list
+//! tables as JSON (plain list files in older versions); Pi-hole 6 exports a
+//! `.zip` with a copy of gravity.db, which is read with sqlite3. Denied
+//! domains become a "Pi-hole" group and adlists become subscriptions; regex
+//! and allowlist entries have no equivalent and are listed as skipped.
+
+use super::group_of;
+use anyhow::{Context, Result};
+use blockandfocus_shared::{BlocklistSubscription, ImportPreview};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Name of the group the denied domains are imported into.
+const GROUP_NAME: &str = "Pi-hole";
+
+/// Pi-hole's domainlist types.
+const EXACT_DENY: u64 = 1;
+const REGEX_DENY: u64 = 3;
+
+/// The files of a Pi-hole 5 archive that lists are read from.
+const LIST_FILES: [&str; 10] = [
+    "blacklist.exact.json",
+    "blacklist.regex.json",
+    "whitelist.exact.json",
+    "whitelist.regex.json",
+    "adlist.json",
+    "blacklist.txt",
+    "regex.list",
+    "whitelist.txt",
+    "adlists.list",
+    "gravity.db",
+];
+
+/// What the archive's lists hold.
+#[derive(Debug, Default)]
+struct Lists {
+    denied: Vec<String>,
+    regex: Vec<String>,
+    allowed: Vec<String>,
+    /// Adlist URLs with their comment
+    adlists: Vec<(String, String)>,
+}
+
+pub async fn read(data: &[u8]) -> Result<ImportPreview> {
+    let dir = std::env::temp_dir().join(format!("blockandfocus-import-{}", Uuid::new_v4()));
+    fs::create_dir_all(&dir).context("Failed to create a directory to extract into")?;
+    let result = extract_and_read(data, &dir).await;
+    let _ = fs::remove_dir_all(&dir);
+    Ok(result?.into_preview())
+}
+
+async fn extract_and_read(data: &[u8], dir: &Path) -> Result<Lists> {
+    let archive = dir.join("teleporter");
+    fs::write(&archive, data).context("Failed to write the archive")?;
+    let target = dir.join("files");
+    fs::create_dir(&target)?;
+
+    // bsdtar reads both the .tar.gz and the .zip flavour, and by default
+    // refuses entries that would land outside the target directory
+    let output = Command::new("/usr/bin/tar")
+        .arg("-xf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&target)
+        .output()
+        .await
+        .context("Failed to run tar")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Not a Teleporter archive: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let files = regular_files(&target);
+    match files.get("gravity.db") {
+        Some(db) => read_gravity(db).await,
+        None => read_exported(&files),
+    }
+}
+
+/// The regular files under `dir` by name. Symlinks are left out, so an
+/// archive can't point the import at files elsewhere.
+fn regular_files(dir: &Path) -> HashMap<String, PathBuf> {
+    let mut files = HashMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.insert(entry.file_name().to_string_lossy().into_owned(), entry.path());
+            }
+        }
+    }
+    files
+}
+
+/// Read the lists of a Pi-hole 5 archive: JSON tables, or list files.
+fn read_exported(files: &HashMap<String, PathBuf>) -> Result<Lists> {
+    if !LIST_FILES.iter().any(|name| files.contains_key(*name)) {
+        anyhow::bail!("No Pi-hole lists in the archive");
+    }
+
+    let read = |name: &str| files.get(name).and_then(|path| fs::read_to_string(path).ok());
+    let column = |name: &str, key: &str| -> Vec<String> {
+        match read(name).and_then(|text| serde_json::from_str::<Vec<Value>>(&text).ok()) {
+            Some(rows) => rows
+                .iter()
+                .filter(|row| is_enabled(row))
+                .filter_map(|row| row.get(key)?.as_str().map(str::to_string))
+                .collect(),
+            None => Vec::new(),
+        }
+    };
+    let lines = |name: &str| -> Vec<String> {
+        let text = read(name).unwrap_or_default();
+        let lines = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+        lines.map(str::to_string).collect()
+    };
+
+    let mut lists = Lists {
+        denied: column("blacklist.exact.json", "domain"),
+        regex: column("blacklist.regex.json", "domain"),
+        allowed: column("whitelist.exact.json", "domain"),
+        ..Default::default()
+    };
+    lists.denied.extend(lines("blacklist.txt"));
+    lists.regex.extend(lines("regex.list"));
+    lists.allowed.extend(lines("whitelist.txt"));
+    lists.allowed.extend(column("whitelist.regex.json", "domain"));
+
+    match read("adlist.json").and_then(|text| serde_json::from_str::<Vec<Value>>(&text).ok()) {
+        Some(rows) => {
+            for row in rows.iter().filter(|row| is_enabled(row)) {
+                let Some(address) = row.get("address").and_then(Value::as_str) else { continue };
+                let comment = row.get("comment").and_then(Value::as_str).unwrap_or_default();
+                lists.adlists.push((address.to_string(), comment.to_string()));
+            }
+        }
+        None => lists.adlists.extend(lines("adlists.list").into_iter().map(|a| (a, String::new()))),
+    }
+
+    Ok(lists)
+}
+
+/// Rows without `enabled` count as enabled; Pi-hole writes it as 0/1.
+fn is_enabled(row: &Value) -> bool {
+    match row.get("enabled") {
+        Some(Value::Bool(enabled)) => *enabled,
+        Some(Value::Number(n)) => n.as_u64() != Some(0),
+        _ => true,
+    }
+}
+
+/// Read the lists of a Pi-hole 6 archive from its gravity.db.
+async fn read_gravity(db: &Path) -> Result<Lists> {
+    let mut lists = Lists::default();
+    for row in sqlite(db, "SELECT type, domain FROM domainlist WHERE enabled = 1").await? {
+        let Some(domain) = row.get("domain").and_then(Value::as_str) else { continue };
+        match row.get("type").and_then(Value::as_u64) {
+            Some(EXACT_DENY) => lists.denied.push(domain.to_string()),
+            Some(REGEX_DENY) => lists.regex.push(domain.to_string()),
+            _ => lists.allowed.push(domain.to_string()),
+        }
+    }
+    // Pi-hole 6 adlists can also be allowlists (type 1)
+    let query = "SELECT address, comment FROM adlist WHERE enabled = 1 AND type = 0";
+    for row in sqlite(db, query).await? {
+        let Some(address) = row.get("address").and_then(Value::as_str) else { continue };
+        let comment = row.get("comment").and_then(Value::as_str).unwrap_or_default();
+        lists.adlists.push((address.to_string(), comment.to_string()));
+    }
+
+    Ok(lists)
+}
+
+/// Run a query with sqlite3 and return the rows as JSON objects.
+async fn sqlite(db: &Path, query: &str) -> Result<Vec<Value>> {
+    let output = Command::new("/usr/bin/sqlite3")
+        .args(["-readonly", "-batch", "-json"])
+        .arg(db)
+        .arg(query)
+        .output()
+        .await
+        .context("Failed to run sqlite3")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to read gravity.db: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // No rows prints nothing rather than []
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&text).context("Unreadable sqlite3 output")
+}
+
+impl Lists {
+    fn into_preview(self) -> ImportPreview {
+        let mut preview = ImportPreview::default();
+        let denied = self.denied.iter().map(String::as_str);
+        preview.groups.extend(group_of(GROUP_NAME, denied, &mut preview.skipped));
+
+        for (address, comment) in self.adlists {
+            if !address.starts_with("https://") && !address.starts_with("http://") {
+                let skipped = format!("{}: only http(s) adlists can be subscribed", address);
+                preview.skipped.push(skipped);
+                continue;
+            }
+            let name = match comment.trim() {
+                "" => list_name(&address),
+                comment => comment.to_string(),
+            };
+            preview.subscriptions.push(BlocklistSubscription { name, url: address, enabled: true });
+        }
+
+        for regex in self.regex {
+            preview.skipped.push(format!("{}: regex entries aren't supported", regex));
+        }
+        for domain in self.allowed {
+            preview.skipped.push(format!("{}: allowlist entries aren't imported", domain));
+        }
+        preview
+    }
+}
+
+/// A subscription name for an adlist without a comment: its host.
+fn list_name(address: &str) -> String {
+    let rest = address.split_once("://").map_or(address, |(_, rest)| rest);
+    rest.split('/').next().unwrap_or(rest).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files_in(dir: &Path, files: &[(&str, &str)]) -> HashMap<String, PathBuf> {
+        fs::create_dir_all(dir.join("etc/pihole")).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join("etc/pihole").join(name), contents).unwrap();
+        }
+        regular_files(dir)
+    }
+
+    #[test]
+    fn test_read_v5_tables() {
+        let dir = std::env::temp_dir().join(format!("baf-pihole-test-{}", Uuid::new_v4()));
+        let exact = r#"[{"domain":"tiktok.com","enabled":1},{"domain":"off.com","enabled":0}]"#;
+        let regex = r#"[{"domain":"(\\.|^)facebook\\.com$","enabled":1}]"#;
+        let adlists = r#"[
+            {"address":"https://example.org/hosts","enabled":1,"comment":"Ads"},
+            {"address":"https://example.net/list.txt","enabled":1,"comment":""},
+            {"address":"file:///etc/local.list","enabled":1}
+        ]"#;
+        let files = files_in(
+            &dir,
+            &[
+                ("blacklist.exact.json", exact),
+                ("blacklist.regex.json", regex),
+                ("whitelist.exact.json", r#"[{"domain":"s.youtube.com","enabled":1}]"#),
+                ("adlist.json", adlists),
+            ],
+        );
+        let preview = read_exported(&files).unwrap().into_preview();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(preview.groups.len(), 1);
+        assert_eq!(preview.groups[0].name, "Pi-hole");
+        assert_eq!(preview.groups[0].domains, vec!["tiktok.com"]);
+        let names: Vec<&str> = preview.subscriptions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Ads", "example.net"]);
+        assert_eq!(preview.skipped.len(), 3);
+        assert!(preview.skipped[0].starts_with("file:///etc/local.list: "));
+        assert!(preview.skipped[2].starts_with("s.youtube.com: allowlist"));
+    }
+
+    #[test]
+    fn test_read_list_files() {
+        let dir = std::env::temp_dir().join(format!("baf-pihole-test-{}", Uuid::new_v4()));
+        let files = files_in(
+            &dir,
+            &[
+                ("blacklist.txt", "reddit.com\n\nnews.ycombinator.com\n"),
+                ("adlists.list", "https://example.org/hosts\n"),
+            ],
+        );
+        let preview = read_exported(&files).unwrap().into_preview();
+        assert_eq!(preview.groups[0].domains, vec!["reddit.com", "news.ycombinator.com"]);
+        assert_eq!(preview.subscriptions[0].url, "https://example.org/hosts");
+
+        let empty = files_in(&dir.join("other"), &[("setupVars.conf", "")]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(read_exported(&empty).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! SelfControl blocklists: a saved `.selfcontrol` file or SelfControl's
+//! preferences, both property lists with the sites under `Blocklist`
+//! (`HostBlacklist` in older versions).
+
+use super::group_of;
+use anyhow::{Context, Result};
+use blockandfocus_shared::ImportPreview;
+use plist::Value;
+use std::io::Cursor;
+
+/// Name of the group the sites are imported into.
+const GROUP_NAME: &str = "SelfControl";
+
+pub fn read(data: &[u8]) -> Result<ImportPreview> {
+    let value = Value::from_reader(Cursor::new(data)).context("Not a property list")?;
+    let dict = value.as_dictionary().context("Not a SelfControl blocklist")?;
+    if dict.get("BlockAsWhitelist").and_then(Value::as_boolean) == Some(true) {
+        anyhow::bail!("Allowlists (blocking everything but the listed sites) can't be imported");
+    }
+    let entries = ["Blocklist", "HostBlacklist"]
+        .iter()
+        .find_map(|key| dict.get(key)?.as_array())
+        .context("No blocklist in the file")?;
+
+    let mut preview = ImportPreview::default();
+    let sites = entries.iter().filter_map(Value::as_string);
+    preview.groups.extend(group_of(GROUP_NAME, sites, &mut preview.skipped));
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCKLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>HostBlacklist</key>
+    <array>
+        <string>facebook.com</string>
+        <string>*.reddit.com</string>
+        <string>10.0.0.1</string>
+    </array>
+    <key>BlockAsWhitelist</key>
+    <false/>
+</dict>
+</plist>"#;
+
+    #[test]
+    fn test_read() {
+        let preview = read(BLOCKLIST.as_bytes()).unwrap();
+        assert_eq!(preview.groups.len(), 1);
+        assert_eq!(preview.groups[0].name, "SelfControl");
+        assert_eq!(preview.groups[0].domains, vec!["facebook.com", "reddit.com"]);
+        assert_eq!(preview.skipped.len(), 1);
+        assert!(preview.skipped[0].starts_with("10.0.0.1: "));
+
+        let allowlist = BLOCKLIST.replace("<false/>", "<true/>");
+        assert!(read(allowlist.as_bytes()).unwrap_err().to_string().contains("Allowlists"));
+        assert!(read(b"facebook.com").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Limits protecting the daemon from misbehaving IPC clients.
+
+use blockandfocus_shared::{Command, Error, ErrorCode, ErrorContext};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::peer::PeerIdentity;
+
+/// Maximum number of IPC connections handled at once.
+pub const MAX_CONNECTIONS: usize = 32;
+
+/// Maximum length of a single command line, in bytes (room for a RestoreBackup archive).
+pub const MAX_LINE_BYTES: usize = 8 * 1024 * 1024;
+
+/// How many times a command may be sent within a time window.
+struct Limit {
+    /// Commands sharing a name share a budget
+    name: &'static str,
+    max: usize,
+    window: Duration,
+}
+
+/// Limit for expensive or sensitive commands (None if unlimited).
+fn limit_for(command: &Command) -> Option<Limit> {
+    let minute = Duration::from_secs(60);
+    let (name, max) = match command {
+        Command::RequestBypass { .. } | Command::SnoozeSchedule { .. } => ("bypass request", 5),
+        // Guessing quiz answers must not be cheap
+        Command::SubmitQuizAnswers { .. } => ("quiz submission", 10),
+        Command::RunSelfCheck => ("self-check", 3),
+        Command::SendWeeklyReport => ("report", 3),
+        Command::CreateBackup => ("backup", 3),
+        Command::RestoreBackup { .. } => ("restore", 3),
+        // Pi-hole archives are extracted with tar
+        Command::ImportExternal { .. } => ("import", 5),
+        Command::CheckForUpdate | Command::ApplyUpdate => ("update", 3),
+        Command::AddDomain { .. }
+        | Command::RemoveDomain { .. }
+        | Command::UpdateSchedule { .. }
+        | Command::SetClientProfile { .. }
+        | Command::RemoveClientProfile { .. } => ("config change", 60),
+        _ => return None,
+    };
+
+    Some(Limit { name, max, window: minute })
+}
+
+/// Sliding-window rate limiter for a single peer.
+#[derive(Default)]
+pub struct CommandLimiter {
+    recent: HashMap<&'static str, VecDeque<Instant>>,
+}
+
+impl CommandLimiter {
+    /// Record a command, or return a rate-limit error if it exceeds its limit.
+    pub fn check(&mut self, command: &Command, now: Instant) -> Result<(), Error> {
+        let Some(limit) = limit_for(command) else {
+            return Ok(());
+        };
+
+        let sent = self.recent.entry(limit.name).or_default();
+        while sent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= limit.window)
+        {
+            sent.pop_front();
+        }
+
+        if sent.len() >= limit.max {
+            let retry_after = sent
+                .front()
+                .map(|at| limit.window.saturating_sub(now.duration_since(*at)))
+                .unwrap_or_default();
+            let seconds = retry_after.as_secs().max(1);
+            return Err(Error::Daemon {
+                code: ErrorCode::RateLimited,
+                message: format!(
+                    "Too many {} commands, try again in {} seconds",
+                    limit.name, seconds
+                ),
+                context: Some(ErrorContext::RetryAfter { seconds }),
+            });
+        }
+
+        sent.push_back(now);
+        Ok(())
+    }
+}
+
+/// Rate limiters shared by every connection, one per peer user, so that
+/// reconnecting or sending one-shot quick commands doesn't start a fresh
+/// budget. Peers whose user can't be determined share one budget.
+#[derive(Default)]
+pub struct PeerLimiters {
+    limiters: Mutex<HashMap<Option<u32>, CommandLimiter>>,
+}
+
+impl PeerLimiters {
+    /// Record a command from `peer`, or return a rate-limit error if it
+    /// exceeds its limit.
+    pub fn check(&self, peer: &PeerIdentity, command: &Command, now: Instant) -> Result<(), Error> {
+        let mut limiters = self.limiters.lock().unwrap();
+        limiters.entry(peer.uid).or_default().check(command, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_limiter() {
+        let mut limiter = CommandLimiter::default();
+        let start = Instant::now();
+        let bypass = Command::RequestBypass {
+            duration: blockandfocus_shared::Duration::from_mins(15),
+            reason: None,
+        };
+
+        for _ in 0..5 {
+            assert!(limiter.check(&bypass, start).is_ok());
+        }
+        let err = limiter.check(&bypass, start + Duration::from_secs(20)).unwrap_err();
+        assert!(err.to_string().contains("40 seconds"));
+        assert_eq!(err.context(), Some(&ErrorContext::RetryAfter { seconds: 40 }));
+
+        // Snoozing shares the bypass budget; other commands are unlimited
+        assert!(limiter.check(&Command::SnoozeSchedule { minutes: 10 }, start).is_err());
+        for _ in 0..100 {
+            assert!(limiter.check(&Command::GetStatus, start).is_ok());
+        }
+
+        // The window slides
+        let later = start + Duration::from_secs(60);
+        for _ in 0..5 {
+            assert!(limiter.check(&bypass, later).is_ok());
+        }
+        assert!(limiter.check(&bypass, later).is_err());
+    }
+
+    #[test]
+    fn test_limits_are_per_peer_user() {
+        let limiters = PeerLimiters::default();
+        let now = Instant::now();
+        let user = |uid, pid| PeerIdentity { uid: Some(uid), pid: Some(pid), executable: None };
+        let snooze = Command::SnoozeSchedule { minutes: 10 };
+
+        // A new process (or connection) of the same user shares the budget
+        for pid in 0..5 {
+            assert!(limiters.check(&user(501, pid), &snooze, now).is_ok());
+        }
+        assert!(limiters.check(&user(501, 99), &snooze, now).is_err());
+        assert!(limiters.check(&user(502, 99), &snooze, now).is_ok());
+    }
+}
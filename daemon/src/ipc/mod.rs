@@ -1,5 +1,9 @@
 //! IPC server for UI communication.
 
+mod limits;
+mod peer;
+mod quick;
 mod server;
+mod session;
 
 pub use server::IpcServer;
@@ -0,0 +1,272 @@
+//! Identification of the program on the other end of an IPC connection.
+//!
+//! The socket is writable by the owner's group, so any script running as the
+//! user could otherwise cancel bypass friction or stop the daemon. Commands
+//! that change state can be restricted to trusted executables, identified by
+//! the peer's process (via its audit token on macOS) rather than anything the
+//! client claims about itself.
+
+use blockandfocus_shared::{Command, ProtectionConfig};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::net::UnixStream;
+use tracing::{debug, warn};
+
+/// The process behind an IPC connection.
+#[derive(Debug, Clone, Default)]
+pub struct PeerIdentity {
+    /// User ID of the peer, if it could be determined
+    pub uid: Option<u32>,
+
+    /// Process ID of the peer
+    pub pid: Option<i32>,
+
+    /// Executable the peer process is running
+    pub executable: Option<PathBuf>,
+}
+
+impl PeerIdentity {
+    /// Identify the peer of a connected stream.
+    pub fn of(stream: &UnixStream) -> Self {
+        let uid = stream.peer_cred().ok().map(|cred| cred.uid());
+        let (pid, executable) = match peer_process(stream) {
+            Some((pid, executable)) => (Some(pid), Some(executable)),
+            None => (None, None),
+        };
+
+        Self {
+            uid,
+            pid,
+            executable,
+        }
+    }
+}
+
+/// Whether a command changes state or reveals browsing history, and so
+/// requires a trusted client.
+pub fn is_privileged(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::AddDomain { .. }
+            | Command::RemoveDomain { .. }
+            | Command::AddEssentialDomain { .. }
+            | Command::RemoveEssentialDomain { .. }
+            | Command::SetGroupEnabled { .. }
+            | Command::UpdateSchedule { .. }
+            | Command::ScheduleVacation { .. }
+            | Command::CancelVacation { .. }
+            | Command::FocusNow { .. }
+            | Command::SnoozeSchedule { .. }
+            | Command::RequestBypass { .. }
+            | Command::BeginQuizAnswer { .. }
+            | Command::SubmitQuizAnswers { .. }
+            | Command::CancelBypass
+            | Command::Shutdown
+            | Command::LockSettings { .. }
+            | Command::FlushCache { .. }
+            | Command::ApplyUpdate
+            | Command::PurgeData { .. }
+            | Command::SendWeeklyReport
+            | Command::CreateBackup
+            | Command::SetClientProfile { .. }
+            | Command::RemoveClientProfile { .. }
+            | Command::ImportExternal { .. }
+            | Command::RestoreBackup { .. }
+            | Command::GetQueryLog { .. }
+            | Command::TailQueryLog { .. }
+    )
+}
+
+/// Decides which peers are trusted, remembering code signature checks.
+#[derive(Default)]
+pub struct ClientVerifier {
+    /// Signature check results by executable, with its modification time
+    checked: Mutex<HashMap<PathBuf, (SystemTime, bool)>>,
+}
+
+impl ClientVerifier {
+    /// Whether a peer may send privileged commands under `config`.
+    pub async fn is_trusted(&self, peer: &PeerIdentity, config: &ProtectionConfig) -> bool {
+        if !config.trusted_clients_only || peer.uid == Some(0) {
+            return true;
+        }
+
+        let Some(executable) = &peer.executable else {
+            warn!(pid = ?peer.pid, "Could not determine the IPC client's executable");
+            return false;
+        };
+        if !is_listed(executable, &config.trusted_clients) {
+            warn!(executable = %executable.display(), "IPC client is not a trusted program");
+            return false;
+        }
+
+        match &config.client_code_requirement {
+            Some(requirement) => self.satisfies(executable, requirement).await,
+            None => true,
+        }
+    }
+
+    /// Check a code signing requirement, reusing the result until the file changes.
+    async fn satisfies(&self, executable: &Path, requirement: &str) -> bool {
+        let modified = std::fs::metadata(executable).and_then(|m| m.modified()).ok();
+        if let Some(modified) = modified {
+            let checked = self.checked.lock().unwrap();
+            if let Some((at, valid)) = checked.get(executable) {
+                if *at == modified {
+                    return *valid;
+                }
+            }
+        }
+
+        let valid = verify_signature(executable, requirement).await;
+        if !valid {
+            warn!(
+                executable = %executable.display(),
+                "IPC client fails the code signing requirement"
+            );
+        }
+        if let Some(modified) = modified {
+            self.checked
+                .lock()
+                .unwrap()
+                .insert(executable.to_path_buf(), (modified, valid));
+        }
+        valid
+    }
+}
+
+/// Whether an executable is one of `trusted`, or inside one of them (an app bundle).
+fn is_listed(executable: &Path, trusted: &[String]) -> bool {
+    trusted
+        .iter()
+        .any(|entry| executable.starts_with(Path::new(entry)))
+}
+
+/// Check an executable's code signature against a requirement with `codesign`.
+#[cfg(target_os = "macos")]
+async fn verify_signature(executable: &Path, requirement: &str) -> bool {
+    let status = tokio::process::Command::new("/usr/bin/codesign")
+        .arg("--verify")
+        .arg(format!("-R={}", requirement))
+        .arg(executable)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await;
+
+    match status {
+        Ok(status) => status.success(),
+        Err(e) => {
+            warn!("Failed to run codesign: {}", e);
+            false
+        }
+    }
+}
+
+/// Code signatures can only be checked on macOS; fail closed elsewhere.
+#[cfg(not(target_os = "macos"))]
+async fn verify_signature(executable: &Path, _requirement: &str) -> bool {
+    debug!(
+        executable = %executable.display(),
+        "Code signatures cannot be verified on this platform"
+    );
+    false
+}
+
+/// PID and executable of the peer, from its audit token.
+///
+/// The audit token is captured when the connection is made, so unlike a PID
+/// looked up later it cannot refer to a different process.
+#[cfg(target_os = "macos")]
+fn peer_process(stream: &UnixStream) -> Option<(i32, PathBuf)> {
+    use std::ffi::OsString;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::ffi::OsStringExt;
+
+    // From <sys/un.h>
+    const SOL_LOCAL: libc::c_int = 0;
+    const LOCAL_PEERTOKEN: libc::c_int = 0x006;
+
+    // audit_token_t is eight 32-bit words; the PID is the sixth
+    let mut token = [0u32; 8];
+    let mut len = std::mem::size_of_val(&token) as libc::socklen_t;
+    // SAFETY: `token` is a writable buffer of `len` bytes
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            SOL_LOCAL,
+            LOCAL_PEERTOKEN,
+            token.as_mut_ptr().cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let pid = token[5] as i32;
+
+    let mut path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // SAFETY: `path` is a writable buffer of the given size
+    let len = unsafe { libc::proc_pidpath(pid, path.as_mut_ptr().cast(), path.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    path.truncate(len as usize);
+
+    debug!(pid, "Identified IPC client from its audit token");
+    Some((pid, PathBuf::from(OsString::from_vec(path))))
+}
+
+/// PID and executable of the peer, from its credentials and `/proc`.
+#[cfg(not(target_os = "macos"))]
+fn peer_process(stream: &UnixStream) -> Option<(i32, PathBuf)> {
+    let pid = stream.peer_cred().ok()?.pid()?;
+    let executable = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    Some((pid, executable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_listed() {
+        let trusted = vec!["/Applications/BlockAndFocus.app".to_string()];
+        assert!(is_listed(
+            Path::new("/Applications/BlockAndFocus.app/Contents/MacOS/BlockAndFocus"),
+            &trusted
+        ));
+        assert!(!is_listed(Path::new("/Applications/BlockAndFocus.app.evil/x"), &trusted));
+        assert!(!is_listed(Path::new("/usr/bin/nc"), &trusted));
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_unless_enabled_or_root() {
+        let verifier = ClientVerifier::default();
+        let script = PeerIdentity {
+            uid: Some(501),
+            pid: Some(1234),
+            executable: Some(PathBuf::from("/usr/bin/nc")),
+        };
+        let mut config = ProtectionConfig::default();
+        assert!(verifier.is_trusted(&script, &config).await);
+
+        config.trusted_clients_only = true;
+        config.trusted_clients = vec!["/Applications/BlockAndFocus.app".to_string()];
+        assert!(!verifier.is_trusted(&script, &config).await);
+        assert!(!is_privileged(&Command::GetStatus));
+        assert!(is_privileged(&Command::CancelBypass));
+        assert!(is_privileged(&Command::TailQueryLog {
+            filter: Default::default(),
+            recent: 0,
+        }));
+
+        let root = PeerIdentity {
+            uid: Some(0),
+            ..script
+        };
+        assert!(verifier.is_trusted(&root, &config).await);
+    }
+}
@@ -0,0 +1,140 @@
+//! One-shot text commands for launchers and scripts.
+//!
+//! A line that isn't JSON is read as `<verb> [argument]`, answered with one
+//! line of text, and the connection is closed, so a Raycast or Alfred script
+//! is just `echo "block reddit.com" | nc -U <socket>`. Quick commands go
+//! through the same trust checks and rate limits as JSON ones; commands that
+//! need a signed session can't be sent this way.
+
+use blockandfocus_shared::{Command, DomainName, Duration, Response, Status};
+use chrono::{Local, TimeZone};
+
+/// Verbs and what they do, as listed by `help`.
+pub const QUICK_HELP: &str = "status | block <domain> | allow <domain> | focus <25m> \
+     | snooze <15m> | explain <domain> | flush [domain] | ping";
+
+/// Parse a quick command into the command it stands for.
+pub fn parse_quick(line: &str) -> Result<Command, String> {
+    let (verb, argument) = match line.trim().split_once(char::is_whitespace) {
+        Some((verb, argument)) => (verb, Some(argument.trim())),
+        None => (line.trim(), None),
+    };
+    let domain = || -> Result<DomainName, String> {
+        let argument = argument.ok_or_else(|| format!("usage: {} <domain>", verb))?;
+        DomainName::parse(argument).map_err(|e| e.to_string())
+    };
+    let minutes = || -> Result<u32, String> {
+        let argument = argument.ok_or_else(|| format!("usage: {} <minutes, or e.g. 1h30m>", verb))?;
+        // A bare number means minutes here, unlike in the config
+        match argument.parse::<u32>() {
+            Ok(minutes) => Ok(minutes),
+            Err(_) => argument
+                .parse::<Duration>()
+                .map(|duration| duration.as_mins())
+                .map_err(|e| e.to_string()),
+        }
+    };
+
+    match verb.to_lowercase().as_str() {
+        "status" => Ok(Command::GetStatus),
+        "ping" => Ok(Command::Ping),
+        "block" => Ok(Command::AddDomain { domain: domain()? }),
+        "allow" | "unblock" => Ok(Command::RemoveDomain { domain: domain()? }),
+        "focus" => Ok(Command::FocusNow { minutes: minutes()? }),
+        "snooze" => Ok(Command::SnoozeSchedule { minutes: minutes()? }),
+        "explain" => Ok(Command::ExplainDomain {
+            domain: domain()?.to_string(),
+        }),
+        "flush" => Ok(Command::FlushCache {
+            domain: argument.map(str::to_string),
+        }),
+        _ => Err(format!("unknown command {:?}; try: {}", verb, QUICK_HELP)),
+    }
+}
+
+/// Describe a response in one line.
+pub fn format_quick(response: &Response) -> String {
+    match response {
+        Response::Success => "ok".to_string(),
+        Response::Pong => "pong".to_string(),
+        Response::Status(status) => format_status(status),
+        Response::DomainExplanation(explanation) => format!(
+            "{}: {} ({})",
+            explanation.domain,
+            if explanation.blocked { "blocked" } else { "allowed" },
+            explanation.reason
+        ),
+        Response::CacheFlushed { entries_removed } => {
+            format!("flushed {} cache entries", entries_removed)
+        }
+        Response::QuizChallenge(_) => "a quiz is required; answer it in the app".to_string(),
+        Response::ScheduleChangePending { applies_at } => {
+            format!("takes effect at {}", local_time(*applies_at))
+        }
+        Response::Error { message, .. } => format!("error: {}", message),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+fn format_status(status: &Status) -> String {
+    let mut parts = vec![if status.blocking_active {
+        match &status.active_schedule_rule {
+            Some(rule) => format!("blocking ({})", rule),
+            None => "blocking".to_string(),
+        }
+    } else {
+        "not blocking".to_string()
+    }];
+    if let Some(until) = status.bypass_until {
+        parts.push(format!("bypass until {}", local_time(until)));
+    }
+    if let Some(until) = status.schedule_snoozed_until {
+        parts.push(format!("snoozed until {}", local_time(until)));
+    }
+    parts.push(format!("{} domains", status.blocked_domains_count));
+    parts.push(format!("{} queries blocked", status.queries_blocked));
+    parts.join(", ")
+}
+
+fn local_time(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%H:%M").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quick() {
+        assert!(matches!(parse_quick("status"), Ok(Command::GetStatus)));
+        let Ok(Command::AddDomain { domain }) = parse_quick("  Block   reddit.com ") else {
+            panic!("expected AddDomain");
+        };
+        assert_eq!(domain.as_str(), "reddit.com");
+        assert!(matches!(parse_quick("focus 25"), Ok(Command::FocusNow { minutes: 25 })));
+        assert!(matches!(parse_quick("focus 1h30m"), Ok(Command::FocusNow { minutes: 90 })));
+        assert!(matches!(parse_quick("flush"), Ok(Command::FlushCache { domain: None })));
+        assert!(parse_quick("block").unwrap_err().contains("usage"));
+        assert!(parse_quick("focus soon").is_err());
+        assert!(parse_quick("reboot").unwrap_err().contains("unknown command"));
+    }
+
+    #[test]
+    fn test_format_quick() {
+        let status = Status {
+            blocking_active: true,
+            active_schedule_rule: Some("Work".to_string()),
+            blocked_domains_count: 12,
+            queries_blocked: 3,
+            ..Status::default()
+        };
+        assert_eq!(
+            format_quick(&Response::Status(status)),
+            "blocking (Work), 12 domains, 3 queries blocked"
+        );
+        assert_eq!(format_quick(&Response::Success), "ok");
+    }
+}
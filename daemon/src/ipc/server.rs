@@ -1,15 +1,45 @@
 //! Unix domain socket IPC server.
 
-use crate::AppState;
+use crate::analytics::{activity_heatmap, weekly_bypass_analytics};
+use crate::budget::GroupBudgets;
+use crate::config::check_client_profile;
+use crate::diagnostics::{explain_domain, run_self_check};
+use crate::i18n::{tr, Msg};
+use crate::import::{merge_import, read_export};
+use crate::quiz::{BypassRequest, GatedAction, QuizError, QuizOutcome};
+use crate::reports::send_weekly_report;
+use crate::schedule::{focus_rule, merge_temporary_rules, next_midnight, weakens_schedule};
+use crate::subscriptions::{diff_adds, refresh_subscriptions};
+use crate::update::{apply_update, check_for_update};
+use crate::store::{BackupArchive, SettingsLock};
+use crate::{AppState, PendingPurge};
 use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use blockandfocus_shared::{
-    Command, ErrorCode, Response, Status, IPC_SOCKET_PATH, IPC_SOCKET_PATH_DEV,
+    Command, Config, DomainName, ErrorCode, ErrorContext, PendingScheduleChange, QueryLogFilter,
+    Response, Schedule, Status, Strictness, SubscriptionStatus, Vacation, VersionInfo, CAPABILITIES,
+    IPC_KEY_PATH, IPC_KEY_PATH_DEV, IPC_SOCKET_PATH, IPC_SOCKET_PATH_DEV, PROTOCOL_VERSION,
 };
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock, Semaphore};
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::limits::{PeerLimiters, MAX_CONNECTIONS, MAX_LINE_BYTES};
+use super::peer::{is_privileged, ClientVerifier, PeerIdentity};
+use super::quick::{format_quick, parse_quick, QUICK_HELP};
+use super::session::{load_or_create_key, Sessions};
+
+/// Longest settings lock that can be taken at once, in hours.
+const MAX_SETTINGS_LOCK_HOURS: u32 = 7 * 24;
+
+/// How long a PurgeData confirmation token is accepted, in seconds.
+const PURGE_CONFIRMATION_SECONDS: u32 = 60;
 
 /// IPC server for handling UI commands.
 pub struct IpcServer;
@@ -23,7 +53,16 @@ impl IpcServer {
         } else {
             IPC_SOCKET_PATH
         };
+        let key_path = if is_dev { IPC_KEY_PATH_DEV } else { IPC_KEY_PATH };
+        Self::serve(state, socket_path, key_path).await
+    }
 
+    /// Serve IPC on `socket_path`, signing sessions with the key at `key_path`.
+    pub async fn serve(
+        state: Arc<RwLock<AppState>>,
+        socket_path: &str,
+        key_path: &str,
+    ) -> Result<()> {
         // Remove existing socket file if present
         let _ = std::fs::remove_file(socket_path);
 
@@ -47,14 +86,38 @@ impl IpcServer {
 
         info!("IPC server listening on {}", socket_path);
 
+        let sessions = Arc::new(Sessions::new(load_or_create_key(key_path)?));
+
+        let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+        let verifier = Arc::new(ClientVerifier::default());
+        let limiters = Arc::new(PeerLimiters::default());
+
         loop {
             match listener.accept().await {
                 Ok((stream, _addr)) => {
+                    let Ok(permit) = connections.clone().try_acquire_owned() else {
+                        warn!("Too many IPC connections, rejecting");
+                        tokio::spawn(Self::reject_connection(stream));
+                        continue;
+                    };
+
                     let state_clone = state.clone();
+                    let verifier = verifier.clone();
+                    let sessions = sessions.clone();
+                    let limiters = limiters.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, state_clone).await {
+                        let result = Self::handle_connection(
+                            stream,
+                            state_clone,
+                            &verifier,
+                            &sessions,
+                            &limiters,
+                        )
+                        .await;
+                        if let Err(e) = result {
                             warn!("IPC connection error: {}", e);
                         }
+                        drop(permit);
                     });
                 }
                 Err(e) => {
@@ -68,20 +131,40 @@ impl IpcServer {
     async fn handle_connection(
         stream: UnixStream,
         state: Arc<RwLock<AppState>>,
+        verifier: &ClientVerifier,
+        sessions: &Sessions,
+        limiters: &PeerLimiters,
     ) -> Result<()> {
+        let peer = PeerIdentity::of(&stream);
+        // Whether the peer may send privileged commands, checked on first use
+        let mut trusted = None;
         let (reader, mut writer) = stream.into_split();
         let mut reader = BufReader::new(reader);
         let mut line = String::new();
 
         loop {
             line.clear();
-            let bytes_read = reader.read_line(&mut line).await?;
+            let bytes_read = (&mut reader)
+                .take(MAX_LINE_BYTES as u64 + 1)
+                .read_line(&mut line)
+                .await?;
 
             if bytes_read == 0 {
                 // Connection closed
                 break;
             }
 
+            if bytes_read > MAX_LINE_BYTES {
+                warn!("IPC command exceeds {} bytes, closing connection", MAX_LINE_BYTES);
+                let response = Response::Error {
+                    code: ErrorCode::InvalidCommand,
+                    message: format!("Command too long (limit is {} bytes)", MAX_LINE_BYTES),
+                    context: None,
+                };
+                Self::write_response(&mut writer, &response).await?;
+                break;
+            }
+
             let trimmed = line.trim();
             if trimmed.is_empty() {
                 continue;
@@ -89,90 +172,432 @@ impl IpcServer {
 
             debug!(command = %trimmed, "Received IPC command");
 
-            let response = match serde_json::from_str::<Command>(trimmed) {
-                Ok(cmd) => Self::handle_command(cmd, &state).await,
-                Err(e) => {
-                    warn!("Invalid IPC command: {}", e);
+            // A line that isn't JSON is a one-shot quick command
+            let quick = !trimmed.starts_with('{');
+            if quick && trimmed.eq_ignore_ascii_case("help") {
+                writer.write_all(format!("{}\n", QUICK_HELP).as_bytes()).await?;
+                break;
+            }
+
+            let parsed = if quick {
+                parse_quick(trimmed)
+                    .map(|cmd| (cmd, false))
+                    .map_err(|message| (ErrorCode::InvalidCommand, message))
+            } else {
+                match serde_json::from_str::<Command>(trimmed) {
+                    Ok(cmd) => Self::open_signed(cmd, sessions),
+                    Err(e) => {
+                        warn!("Invalid IPC command: {}", e);
+                        Err((ErrorCode::InvalidCommand, format!("Invalid command: {}", e)))
+                    }
+                }
+            };
+
+            let response = match parsed {
+                Ok((Command::Subscribe, _)) => {
+                    return Self::stream_events(writer, &state).await;
+                }
+                Ok((Command::StartSession, _)) => {
+                    let (session_id, nonce) = sessions.start();
+                    debug!(%session_id, "IPC session started");
+                    Response::SessionStarted {
+                        session_id,
+                        nonce,
+                        capabilities: CAPABILITIES.to_vec(),
+                    }
+                }
+                Ok((cmd, false))
+                    if is_privileged(&cmd)
+                        && state.read().await.config.get().protection.require_authenticated_commands =>
+                {
                     Response::Error {
-                        code: ErrorCode::InvalidCommand,
-                        message: format!("Invalid command: {}", e),
+                        code: ErrorCode::Unauthenticated,
+                        message: "This command must be signed for an authenticated session"
+                            .to_string(),
+                        context: None,
                     }
                 }
+                Ok((cmd, _))
+                    if is_privileged(&cmd)
+                        && !Self::check_trusted(&mut trusted, &peer, verifier, &state).await =>
+                {
+                    Response::Error {
+                        code: ErrorCode::UntrustedClient,
+                        message: tr(state.read().await.locale(), Msg::UntrustedClient, &[]),
+                        context: None,
+                    }
+                }
+                // Only after the trust checks above: the query log is browsing history
+                Ok((Command::TailQueryLog { filter, recent }, _)) => {
+                    return Self::stream_query_log(writer, &state, filter, recent).await;
+                }
+                Ok((cmd, _)) => match limiters.check(&peer, &cmd, Instant::now()) {
+                    Ok(()) => {
+                        let response = Self::handle_command(cmd, &state).await;
+                        // Commands may change anything the DNS server blocks by
+                        state.read().await.publish_policy();
+                        response
+                    }
+                    Err(error) => {
+                        warn!("IPC command rate limited: {}", error);
+                        error.into()
+                    }
+                },
+                Err((code, message)) => Response::Error { code, message, context: None },
             };
 
-            let response_json = serde_json::to_string(&response)?;
-            writer.write_all(response_json.as_bytes()).await?;
-            writer.write_all(b"\n").await?;
-            writer.flush().await?;
+            if quick {
+                writer.write_all(format!("{}\n", format_quick(&response)).as_bytes()).await?;
+                break;
+            }
+            Self::write_response(&mut writer, &response).await?;
         }
 
         Ok(())
     }
 
+    /// Unwrap a signed command after checking its signature.
+    ///
+    /// Returns the command and whether it was signed, or the error to send back.
+    fn open_signed(
+        cmd: Command,
+        sessions: &Sessions,
+    ) -> Result<(Command, bool), (ErrorCode, String)> {
+        let Command::Authenticated {
+            session_id,
+            counter,
+            mac,
+            command,
+        } = cmd
+        else {
+            return Ok((cmd, false));
+        };
+
+        if matches!(*command, Command::Authenticated { .. } | Command::StartSession) {
+            return Err((
+                ErrorCode::InvalidCommand,
+                "Session commands cannot be signed".to_string(),
+            ));
+        }
+
+        match sessions.verify(&session_id, counter, &mac, &command) {
+            Ok(()) => Ok((*command, true)),
+            Err(message) => {
+                warn!(%session_id, "Rejected signed IPC command: {}", message);
+                Err((ErrorCode::Unauthenticated, message.to_string()))
+            }
+        }
+    }
+
+    /// Whether the peer is trusted, verifying it the first time this is asked.
+    async fn check_trusted(
+        trusted: &mut Option<bool>,
+        peer: &PeerIdentity,
+        verifier: &ClientVerifier,
+        state: &Arc<RwLock<AppState>>,
+    ) -> bool {
+        if let Some(trusted) = *trusted {
+            return trusted;
+        }
+
+        let config = state.read().await.config.get().protection.clone();
+        let result = verifier.is_trusted(peer, &config).await;
+        if !result {
+            warn!(
+                pid = ?peer.pid,
+                uid = ?peer.uid,
+                "Refusing privileged IPC commands from untrusted client"
+            );
+        }
+        *trusted = Some(result);
+        result
+    }
+
+    /// Tell a client over the connection limit to retry later.
+    async fn reject_connection(stream: UnixStream) {
+        let (_reader, mut writer) = stream.into_split();
+        let response = Response::Error {
+            code: ErrorCode::RateLimited,
+            message: "Too many connections to the daemon".to_string(),
+            context: None,
+        };
+        let _ = Self::write_response(&mut writer, &response).await;
+    }
+
+    /// Stream daemon events to a subscribed connection until it closes.
+    async fn stream_events(
+        mut writer: tokio::net::unix::OwnedWriteHalf,
+        state: &Arc<RwLock<AppState>>,
+    ) -> Result<()> {
+        let mut events = state.read().await.events.subscribe();
+        Self::write_response(&mut writer, &Response::Subscribed).await?;
+        debug!("IPC client subscribed to events");
+
+        loop {
+            match events.recv().await {
+                Ok(event) => Self::write_response(&mut writer, &Response::Event(event)).await?,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(missed, "Event subscriber lagging, events dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    /// Stream matching DNS queries to a connection until it closes.
+    async fn stream_query_log(
+        mut writer: tokio::net::unix::OwnedWriteHalf,
+        state: &Arc<RwLock<AppState>>,
+        filter: QueryLogFilter,
+        recent: usize,
+    ) -> Result<()> {
+        let query_log = state.read().await.query_log.clone();
+        let mut entries = query_log.subscribe();
+        Self::write_response(&mut writer, &Response::Subscribed).await?;
+        debug!(?filter, "IPC client tailing query log");
+
+        for entry in query_log.recent(recent, &filter) {
+            Self::write_response(&mut writer, &Response::QueryLogEntry(entry)).await?;
+        }
+
+        loop {
+            match entries.recv().await {
+                Ok(entry) if filter.matches(&entry) => {
+                    Self::write_response(&mut writer, &Response::QueryLogEntry(entry)).await?
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(missed, "Query log tail lagging, entries dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    /// Write a single newline-terminated response.
+    async fn write_response(
+        writer: &mut tokio::net::unix::OwnedWriteHalf,
+        response: &Response,
+    ) -> Result<()> {
+        let response_json = serde_json::to_string(response)?;
+        writer.write_all(response_json.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
     /// Handle a single IPC command.
     async fn handle_command(cmd: Command, state: &Arc<RwLock<AppState>>) -> Response {
         match cmd {
             Command::Ping => Response::Pong,
 
+            Command::GetVersion => Response::Version(VersionInfo {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                git_hash: option_env!("BLOCKANDFOCUS_GIT_HASH").map(str::to_string),
+                build_date: option_env!("BLOCKANDFOCUS_BUILD_DATE").map(str::to_string),
+                features: env!("BLOCKANDFOCUS_FEATURES")
+                    .split(',')
+                    .filter(|feature| !feature.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                protocol_version: PROTOCOL_VERSION,
+                capabilities: CAPABILITIES.to_vec(),
+            }),
+
+            // Handled per connection in handle_connection
+            Command::Subscribe | Command::TailQueryLog { .. } => Response::Error {
+                code: ErrorCode::InvalidCommand,
+                message: "Streaming commands are only valid as a connection's command".to_string(),
+                context: None,
+            },
+
+            // Handled per connection in handle_connection
+            Command::StartSession | Command::Authenticated { .. } => Response::Error {
+                code: ErrorCode::InvalidCommand,
+                message: "Session commands cannot be nested".to_string(),
+                context: None,
+            },
+
             Command::GetStatus => {
                 let state_guard = state.read().await;
                 let config = state_guard.config.get();
+                let queries = &state_guard.stats.queries;
 
                 Response::Status(Status {
                     blocking_active: state_guard.is_blocking_active(),
                     blocked_domains_count: config.blocking.domains.len(),
-                    queries_blocked: state_guard.stats.queries_blocked,
-                    queries_forwarded: state_guard.stats.queries_forwarded,
-                    bypass_until: state_guard.bypass_until,
+                    queries_blocked: queries.blocked.load(Ordering::Relaxed),
+                    queries_forwarded: queries.forwarded.load(Ordering::Relaxed),
+                    bypass_until: state_guard.bypass.until,
                     active_schedule_rule: state_guard.schedule.active_rule_name(),
                     schedule_enabled: config.schedule.enabled,
+                    bypass_budget_remaining_minutes: state_guard.bypass_budget_remaining(),
+                    bypass_starts_at: state_guard.bypass.starts_at,
+                    allowances: state_guard.allowance_status(),
+                    schedule_snoozed_until: state_guard
+                        .schedule
+                        .snoozed_until(chrono::Utc::now().timestamp()),
+                    dry_run: config.blocking.dry_run,
+                    queries_would_block: queries.would_block.load(Ordering::Relaxed),
+                    queries_malformed: queries.malformed.load(Ordering::Relaxed),
+                    encrypted_dns_connections: state_guard.stats.encrypted_dns_connections,
+                    last_encrypted_dns: state_guard.stats.last_encrypted_dns.clone(),
+                    active_strictness: state_guard.schedule.active_strictness(),
+                    settings_lock_remaining_minutes: state_guard.settings_lock_remaining_minutes(),
+                    vacation_until: state_guard
+                        .schedule
+                        .vacation_on(chrono::Local::now().date_naive())
+                        .map(|vacation| vacation.end),
+                    wind_down_groups: state_guard.schedule.wind_down_groups(),
+                    locale: state_guard.locale(),
+                    partner: state_guard.partner.clone(),
+                    update_available: state_guard
+                        .update
+                        .as_ref()
+                        .filter(|update| update.update_available)
+                        .and_then(|update| update.latest.as_ref())
+                        .map(|latest| latest.version.clone()),
                 })
             }
 
             Command::GetBlocklist => {
                 let state_guard = state.read().await;
                 let domains = state_guard.config.blocked_domains();
-                Response::Blocklist { domains }
+                let allowances = state_guard.allowance_status();
+                Response::Blocklist { domains, allowances }
             }
 
             Command::AddDomain { domain } => {
-                let mut state_guard = state.write().await;
+                // Only a read lock: the config and blocker are swapped atomically,
+                // so DNS queries keep being answered while the file is written
+                let state_guard = state.read().await;
                 match state_guard.config.add_domain(domain.clone()).await {
                     Ok(()) => {
                         // Update the blocker with new domain list
-                        let domains = state_guard.config.blocked_domains();
-                        state_guard.blocker.update_domains(domains);
+                        state_guard.rebuild_blocker();
                         info!(domain = %domain, "Domain added to blocklist");
                         Response::Success
                     }
                     Err(e) => Response::Error {
                         code: ErrorCode::ConfigError,
                         message: format!("Failed to add domain: {}", e),
+                        context: None,
                     },
                 }
             }
 
             Command::RemoveDomain { domain } => {
-                let mut state_guard = state.write().await;
-                match state_guard.config.remove_domain(&domain).await {
+                // Only a read lock: the config and blocker are swapped atomically,
+                // so DNS queries keep being answered while the file is written
+                let state_guard = state.read().await;
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
+                if !state_guard.config.contains_domain(&domain) {
+                    return Response::Error {
+                        code: ErrorCode::InvalidDomain,
+                        message: tr(state_guard.locale(), Msg::DomainNotInBlocklist, &[]),
+                        context: Some(ErrorContext::Domain { domain: domain.to_string() }),
+                    };
+                }
+
+                if state_guard.weakening_needs_quiz() {
+                    drop(state_guard);
+                    let challenge = state.write().await.quiz.generate_challenge_for(BypassRequest {
+                        reason: None,
+                        action: GatedAction::RemoveDomain { domain },
+                    });
+                    debug!(challenge_id = %challenge.challenge_id, "Domain removal requested, quiz generated");
+                    return Response::QuizChallenge(challenge);
+                }
+
+                Self::remove_domain(&state_guard, &domain).await
+            }
+
+            Command::AddEssentialDomain { domain } => {
+                let state_guard = state.read().await;
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
+                // Never blocking a domain is an easy way around blocking, so it
+                // can't be done while blocking is on
+                if state_guard.is_blocking_active() {
+                    return Response::Error {
+                        code: ErrorCode::SettingsLocked,
+                        message: tr(state_guard.locale(), Msg::EssentialDuringBlocking, &[]),
+                        context: None,
+                    };
+                }
+
+                match state_guard.config.add_essential_domain(&domain).await {
+                    Ok(()) => {
+                        state_guard.rebuild_blocker();
+                        info!(domain = %domain, "Domain added to essential services");
+                        Response::Success
+                    }
+                    Err(e) => Response::Error {
+                        code: ErrorCode::ConfigError,
+                        message: format!("Failed to add essential domain: {}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::RemoveEssentialDomain { domain } => {
+                let state_guard = state.read().await;
+                match state_guard.config.remove_essential_domain(&domain).await {
                     Ok(true) => {
-                        // Update the blocker with new domain list
-                        let domains = state_guard.config.blocked_domains();
-                        state_guard.blocker.update_domains(domains);
-                        info!(domain = %domain, "Domain removed from blocklist");
+                        state_guard.rebuild_blocker();
+                        info!(domain = %domain, "Domain removed from essential services");
                         Response::Success
                     }
                     Ok(false) => Response::Error {
                         code: ErrorCode::InvalidDomain,
-                        message: "Domain not found in blocklist".to_string(),
+                        message: tr(state_guard.locale(), Msg::DomainNotEssential, &[]),
+                        context: Some(ErrorContext::Domain { domain: domain.to_string() }),
                     },
                     Err(e) => Response::Error {
                         code: ErrorCode::ConfigError,
-                        message: format!("Failed to remove domain: {}", e),
+                        message: format!("Failed to remove essential domain: {}", e),
+                        context: None,
                     },
                 }
             }
 
+            Command::GetEssentialDomains => {
+                let state_guard = state.read().await;
+                Response::EssentialDomains {
+                    domains: state_guard.config.get().blocking.essential_domains.clone(),
+                }
+            }
+
+            Command::SetGroupEnabled { name, enabled } => {
+                let mut state_guard = state.write().await;
+                if !state_guard.config.get().blocking.groups.iter().any(|g| g.name == name) {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state_guard.locale(), Msg::UnknownGroup, &[&name]),
+                        context: None,
+                    };
+                }
+
+                if !enabled {
+                    if let Some(refused) = Self::weakening_refused(&state_guard) {
+                        return refused;
+                    }
+                    if state_guard.weakening_needs_quiz() {
+                        let challenge = state_guard.quiz.generate_challenge_for(BypassRequest {
+                            reason: None,
+                            action: GatedAction::DisableGroup { name },
+                        });
+                        debug!(challenge_id = %challenge.challenge_id, "Group disable requested, quiz generated");
+                        return Response::QuizChallenge(challenge);
+                    }
+                }
+
+                Self::set_group_enabled(&mut state_guard, &name, enabled).await
+            }
+
             Command::GetSchedule => {
                 let state_guard = state.read().await;
                 let schedule = state_guard.config.get().schedule.clone();
@@ -180,30 +605,298 @@ impl IpcServer {
             }
 
             Command::UpdateSchedule { schedule } => {
+                if let Err(problems) = schedule.validate() {
+                    let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+                    return Response::Error {
+                        code: ErrorCode::ConfigError,
+                        message: problems.join("; "),
+                        context: None,
+                    };
+                }
+
                 let mut state_guard = state.write().await;
+                Self::update_schedule(&mut state_guard, schedule, false).await
+            }
+
+            Command::GetPendingChanges => {
+                let state_guard = state.read().await;
+                let schedule = state_guard.store.get().pending_schedule.clone();
+                Response::PendingChanges { schedule }
+            }
 
-                // Update schedule engine
-                state_guard.schedule.update(schedule.clone());
+            Command::ScheduleVacation { start, end } => {
+                let mut state_guard = state.write().await;
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
 
-                // Persist to config
-                match state_guard.config.update(|c| c.schedule = schedule).await {
+                let locale = state_guard.locale();
+                if end < start {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(locale, Msg::VacationInvalid, &[]),
+                        context: None,
+                    };
+                }
+
+                let min_notice_hours = state_guard.config.get().vacation.min_notice_hours;
+                let now = chrono::Local::now();
+                let starts_at = start
+                    .and_time(chrono::NaiveTime::MIN)
+                    .and_local_timezone(chrono::Local)
+                    .earliest();
+                let notice = chrono::Duration::hours(i64::from(min_notice_hours));
+                if starts_at.is_none_or(|starts_at| starts_at < now + notice) {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(locale, Msg::VacationNotice, &[&min_notice_hours]),
+                        context: None,
+                    };
+                }
+
+                let overlaps = state_guard
+                    .store
+                    .get()
+                    .vacations
+                    .iter()
+                    .any(|v| v.start <= end && start <= v.end);
+                if overlaps {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(locale, Msg::VacationOverlap, &[]),
+                        context: None,
+                    };
+                }
+
+                let vacation = Vacation {
+                    id: Uuid::new_v4().to_string(),
+                    start,
+                    end,
+                    created_at: now.timestamp(),
+                };
+                match state_guard.store.add_vacation(vacation, now.date_naive()) {
                     Ok(()) => {
-                        info!("Schedule updated");
+                        let vacations = state_guard.store.get().vacations.clone();
+                        state_guard.schedule.set_vacations(vacations);
+                        info!(%start, %end, "Vacation scheduled");
                         Response::Success
                     }
                     Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("Failed to schedule vacation: {}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::CancelVacation { id } => {
+                let mut state_guard = state.write().await;
+                let locale = state_guard.locale();
+                let today = chrono::Local::now().date_naive();
+
+                // Once started, a vacation runs to its end
+                let vacation = state_guard.store.get().vacations.iter().find(|v| v.id == id);
+                match vacation {
+                    None => {
+                        return Response::Error {
+                            code: ErrorCode::InvalidCommand,
+                            message: tr(locale, Msg::VacationNotFound, &[]),
+                            context: None,
+                        };
+                    }
+                    Some(vacation) if vacation.start <= today => {
+                        return Response::Error {
+                            code: ErrorCode::SettingsLocked,
+                            message: tr(locale, Msg::VacationStarted, &[]),
+                            context: None,
+                        };
+                    }
+                    Some(_) => {}
+                }
+
+                match state_guard.store.remove_vacation(&id) {
+                    Ok(_) => {
+                        let vacations = state_guard.store.get().vacations.clone();
+                        state_guard.schedule.set_vacations(vacations);
+                        info!(%id, "Vacation cancelled");
+                        Response::Success
+                    }
+                    Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("Failed to cancel vacation: {}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::GetVacations => {
+                let state_guard = state.read().await;
+                let today = chrono::Local::now().date_naive();
+                let vacations = state_guard
+                    .store
+                    .get()
+                    .vacations
+                    .iter()
+                    .filter(|v| v.end >= today)
+                    .cloned()
+                    .collect();
+                Response::Vacations { vacations }
+            }
+
+            Command::GenerateSchedule { template } => match template.generate() {
+                Ok(schedule) => Response::Schedule(schedule),
+                Err(problems) => {
+                    let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+                    Response::Error {
                         code: ErrorCode::ConfigError,
-                        message: format!("Failed to update schedule: {}", e),
+                        message: problems.join("; "),
+                        context: None,
+                    }
+                }
+            },
+
+            Command::GetScheduleTimeline => {
+                let windows = state.read().await.schedule_timeline();
+                Response::ScheduleTimeline { windows }
+            }
+
+            Command::FocusNow { minutes } => {
+                if minutes == 0 {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state.read().await.locale(), Msg::FocusTooShort, &[]),
+                        context: None,
+                    };
+                }
+
+                let mut state_guard = state.write().await;
+                let now = chrono::Local::now();
+
+                match state_guard.add_focus_rule(focus_rule(minutes, now)).await {
+                    Ok(()) => {
+                        info!(minutes, "Focus now rule added");
+                        Response::Success
+                    }
+                    Err(e) => Response::Error {
+                        code: ErrorCode::ConfigError,
+                        message: format!("Failed to add focus rule: {}", e),
+                        context: None,
                     },
                 }
             }
 
-            Command::RequestBypass { duration_minutes } => {
+            Command::SnoozeSchedule { minutes } => {
+                let mut state_guard = state.write().await;
+                if let Some(locked) = Self::settings_locked(&state_guard) {
+                    return locked;
+                }
+                let max_minutes = state_guard.config.get().bypass.snooze_max.as_mins();
+
+                if minutes == 0 || minutes > max_minutes {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state_guard.locale(), Msg::SnoozeRange, &[&max_minutes]),
+                        context: None,
+                    };
+                }
+
+                let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                if state_guard.store.snoozed_on(&today) {
+                    return Response::Error {
+                        code: ErrorCode::SnoozeUnavailable,
+                        message: tr(state_guard.locale(), Msg::SnoozedToday, &[]),
+                        context: None,
+                    };
+                }
+
+                let Some(activation) = state_guard.schedule.next_activation(chrono::Local::now())
+                else {
+                    return Response::Error {
+                        code: ErrorCode::SnoozeUnavailable,
+                        message: tr(state_guard.locale(), Msg::NothingToSnooze, &[]),
+                        context: None,
+                    };
+                };
+
+                let challenge = state_guard.quiz.generate_challenge_for(BypassRequest {
+                    reason: None,
+                    action: GatedAction::SnoozeSchedule {
+                        activation: activation.timestamp(),
+                        minutes,
+                    },
+                });
+
+                debug!(
+                    minutes,
+                    activation = %activation,
+                    challenge_id = %challenge.challenge_id,
+                    "Schedule snooze requested, quiz generated"
+                );
+
+                Response::QuizChallenge(challenge)
+            }
+
+            Command::RequestBypass { duration, reason } => {
                 let mut state_guard = state.write().await;
-                let challenge = state_guard.quiz.generate_challenge();
+                if let Some(locked) = Self::settings_locked(&state_guard) {
+                    return locked;
+                }
+                let max_minutes = state_guard.config.get().quiz.max_bypass.as_mins();
+                let duration_minutes = duration.as_mins().min(max_minutes);
+                if duration_minutes < duration.as_mins() {
+                    debug!(requested = duration.as_mins(), max_minutes, "Bypass request capped");
+                }
+                let bypass_config = state_guard.config.get().bypass.clone();
+                let strictness = state_guard.strictness();
+
+                if strictness == Strictness::Strict {
+                    return Response::Error {
+                        code: ErrorCode::BypassNotAllowed,
+                        message: tr(state_guard.locale(), Msg::StrictNoBypass, &[]),
+                        context: None,
+                    };
+                }
+
+                let reason = reason
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty());
+                if bypass_config.require_reason {
+                    let length = reason.as_ref().map_or(0, |r| r.chars().count());
+                    if length < bypass_config.min_reason_length {
+                        return Response::Error {
+                            code: ErrorCode::ReasonRequired,
+                            message: tr(
+                                state_guard.locale(),
+                                Msg::ReasonRequired,
+                                &[&bypass_config.min_reason_length],
+                            ),
+                            context: None,
+                        };
+                    }
+                }
+
+                if let Some(remaining) = state_guard.bypass_budget_available() {
+                    if duration_minutes > remaining {
+                        return Self::budget_exceeded(&state_guard, remaining);
+                    }
+                }
+
+                // Relaxed rules grant the bypass without a quiz
+                if strictness == Strictness::Relaxed {
+                    state_guard.activate_bypass(duration_minutes, reason);
+                    info!(duration_minutes, "Bypass granted without quiz (relaxed rule)");
+                    return Response::Success;
+                }
+
+                let last_reason = state_guard.store.last_bypass_reason();
+                let mut challenge = state_guard
+                    .quiz
+                    .generate_challenge_for(BypassRequest {
+                        reason,
+                        action: GatedAction::Bypass { minutes: duration_minutes },
+                    });
+                challenge.last_reason = last_reason;
 
-                // Store the requested duration for when quiz is validated
-                // (We'll need to pass it through somehow - for now, store in challenge metadata)
                 debug!(
                     duration_minutes,
                     challenge_id = %challenge.challenge_id,
@@ -213,6 +906,18 @@ impl IpcServer {
                 Response::QuizChallenge(challenge)
             }
 
+            Command::BeginQuizAnswer { challenge_id } => {
+                let mut state_guard = state.write().await;
+                match state_guard.quiz.begin_answer(&challenge_id) {
+                    Ok(challenge) => Response::QuizChallenge(challenge),
+                    Err(e) => Response::Error {
+                        code: Self::quiz_error_code(e),
+                        message: e.message(state_guard.locale()),
+                        context: None,
+                    },
+                }
+            }
+
             Command::SubmitQuizAnswers {
                 challenge_id,
                 answers,
@@ -220,35 +925,705 @@ impl IpcServer {
                 let mut state_guard = state.write().await;
 
                 match state_guard.quiz.validate_answers(&challenge_id, &answers) {
+                    Ok(QuizOutcome::NextStage(challenge)) => {
+                        debug!(stage = challenge.stage, "Quiz stage passed");
+                        Response::QuizChallenge(challenge)
+                    }
+                    Ok(QuizOutcome::Passed(request)) => {
+                        // The lock may have been taken after the quiz was handed out
+                        if let Some(locked) = Self::settings_locked(&state_guard) {
+                            return locked;
+                        }
+                        match request.action {
+                            GatedAction::Bypass { minutes } => {
+                                // A strict rule may have started since the quiz was handed out
+                                if state_guard.strictness() == Strictness::Strict {
+                                    return Response::Error {
+                                        code: ErrorCode::BypassNotAllowed,
+                                        message: tr(
+                                            state_guard.locale(),
+                                            Msg::StrictNoBypass,
+                                            &[],
+                                        ),
+                                        context: None,
+                                    };
+                                }
+                                // Other bypasses may have used the budget since
+                                // the quiz was handed out
+                                let minutes = match state_guard.bypass_budget_available() {
+                                    Some(0) => return Self::budget_exceeded(&state_guard, 0),
+                                    Some(available) => minutes.min(available),
+                                    None => minutes,
+                                };
+                                state_guard.activate_bypass(minutes, request.reason);
+                                info!(minutes, "Quiz validated, bypass activated");
+                                Response::Success
+                            }
+                            GatedAction::SnoozeSchedule {
+                                activation,
+                                minutes,
+                            } => {
+                                // Another snooze quiz may have been solved since
+                                // this one was handed out
+                                let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                                if state_guard.store.snoozed_on(&today) {
+                                    return Response::Error {
+                                        code: ErrorCode::SnoozeUnavailable,
+                                        message: tr(
+                                            state_guard.locale(),
+                                            Msg::SnoozedToday,
+                                            &[],
+                                        ),
+                                        context: None,
+                                    };
+                                }
+                                state_guard.snooze_schedule(activation, minutes);
+                                Response::Success
+                            }
+                            GatedAction::Shutdown => {
+                                info!("Quiz validated, shutting down");
+                                state_guard.authorize_shutdown();
+                                Response::Success
+                            }
+                            GatedAction::RemoveDomain { domain } => {
+                                // A strict rule may have started since the quiz was handed out
+                                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                                    return refused;
+                                }
+                                Self::remove_domain(&state_guard, &domain).await
+                            }
+                            GatedAction::DisableGroup { name } => {
+                                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                                    return refused;
+                                }
+                                Self::set_group_enabled(&mut state_guard, &name, false).await
+                            }
+                            GatedAction::UpdateSchedule { schedule } => {
+                                // Strict rules are checked again on the current schedule
+                                Self::update_schedule(&mut state_guard, schedule, true).await
+                            }
+                        }
+                    }
+                    Err(e) => Response::Error {
+                        code: Self::quiz_error_code(e),
+                        message: e.message(state_guard.locale()),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::CancelBypass => {
+                let mut state_guard = state.write().await;
+                state_guard.cancel_bypass();
+                Response::Success
+            }
+
+            Command::Shutdown => {
+                let mut state_guard = state.write().await;
+                if let Some(locked) = Self::settings_locked(&state_guard) {
+                    return locked;
+                }
+
+                if !state_guard.shutdown_guarded() {
+                    info!("Shutdown requested");
+                    state_guard.authorize_shutdown();
+                    return Response::Success;
+                }
+
+                if state_guard.strictness() == Strictness::Strict {
+                    return Response::Error {
+                        code: ErrorCode::BypassNotAllowed,
+                        message: tr(state_guard.locale(), Msg::StrictNoShutdown, &[]),
+                        context: None,
+                    };
+                }
+
+                let challenge = state_guard.quiz.generate_challenge_for(BypassRequest {
+                    reason: None,
+                    action: GatedAction::Shutdown,
+                });
+                debug!(challenge_id = %challenge.challenge_id, "Shutdown requested, quiz generated");
+                Response::QuizChallenge(challenge)
+            }
+
+            Command::LockSettings { hours } => {
+                let mut state_guard = state.write().await;
+                if hours == 0 || hours > MAX_SETTINGS_LOCK_HOURS {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state_guard.locale(), Msg::SettingsLockRange, &[&MAX_SETTINGS_LOCK_HOURS]),
+                        context: None,
+                    };
+                }
+
+                // A lock can be extended but never shortened
+                let now = chrono::Utc::now().timestamp();
+                let until = state_guard
+                    .store
+                    .settings_lock(now)
+                    .map_or(0, |lock| lock.until)
+                    .max(now + i64::from(hours) * 3600);
+                let lock = SettingsLock::new(&state_guard.config.get(), until);
+
+                match state_guard.store.lock_settings(lock) {
                     Ok(()) => {
-                        // Quiz passed, activate bypass
-                        // Default to 15 minutes if not specified
-                        // In a real implementation, we'd store the duration with the challenge
-                        state_guard.activate_bypass(15);
-                        info!("Quiz validated, bypass activated");
+                        info!(hours, until, "Settings locked");
                         Response::Success
                     }
+                    Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("Failed to lock settings: {}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::ExplainDomain { domain } => {
+                let state_guard = state.read().await;
+                Response::DomainExplanation(explain_domain(&state_guard, &domain))
+            }
+
+            Command::RunSelfCheck => Response::SelfCheck(run_self_check(state).await),
+
+            Command::SendWeeklyReport => match send_weekly_report(state).await {
+                Ok(_) => Response::Success,
+                Err(e) => Response::Error {
+                    code: ErrorCode::ReportFailed,
+                    message: format!("Sending the weekly report failed: {:#}", e),
+                    context: None,
+                },
+            },
+
+            Command::CheckForUpdate => {
+                let config = state.read().await.config.get().update.clone();
+                match check_for_update(&config).await {
+                    Ok(status) => {
+                        state.write().await.update = Some(status.clone());
+                        Response::UpdateStatus(status)
+                    }
+                    Err(e) => Response::Error {
+                        code: ErrorCode::UpdateFailed,
+                        message: format!("Update check failed: {:#}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::ApplyUpdate => {
+                let config = state.read().await.config.get().update.clone();
+                match apply_update(&config).await {
+                    Ok(version) => Response::UpdateApplied { version },
+                    Err(e) => Response::Error {
+                        code: ErrorCode::UpdateFailed,
+                        message: format!("Update failed: {:#}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::GetStats => {
+                let state_guard = state.read().await;
+                Response::Stats(state_guard.usage_stats())
+            }
+
+            Command::GetQueryLog { limit, filter } => {
+                let entries = state.read().await.query_log.recent(limit, &filter);
+                Response::QueryLog { entries }
+            }
+
+            Command::GetClientProfiles => {
+                let profiles = state.read().await.config.get().clients.clone();
+                Response::ClientProfiles { profiles }
+            }
+
+            Command::SetClientProfile { profile } => {
+                let mut state_guard = state.write().await;
+                let problems = check_client_profile(&profile, &state_guard.config.get());
+                if !problems.is_empty() {
+                    return Response::Error {
+                        code: ErrorCode::ConfigError,
+                        message: problems.join("; "),
+                        context: None,
+                    };
+                }
+                // A profile can exempt devices from blocking
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
+
+                let name = profile.name.clone();
+                let result = state_guard
+                    .config
+                    .update(|c| match c.clients.iter_mut().find(|p| p.name == profile.name) {
+                        Some(existing) => *existing = profile,
+                        None => c.clients.push(profile),
+                    })
+                    .await;
+                Self::clients_updated(&mut state_guard, result, &name)
+            }
+
+            Command::RemoveClientProfile { name } => {
+                let mut state_guard = state.write().await;
+                if !state_guard.config.get().clients.iter().any(|p| p.name == name) {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state_guard.locale(), Msg::UnknownClientProfile, &[&name]),
+                        context: None,
+                    };
+                }
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
+
+                let result =
+                    state_guard.config.update(|c| c.clients.retain(|p| p.name != name)).await;
+                Self::clients_updated(&mut state_guard, result, &name)
+            }
+
+            Command::GetSubscriptions => {
+                let state_guard = state.read().await;
+                let config = state_guard.config.get();
+                let lists = config
+                    .subscriptions
+                    .lists
+                    .iter()
+                    .map(|list| {
+                        let health = state_guard.store.subscription_health(&list.url).cloned();
+                        let health = health.unwrap_or_default();
+                        SubscriptionStatus {
+                            name: list.name.clone(),
+                            url: list.url.clone(),
+                            enabled: list.enabled,
+                            last_fetch_at: health.last_fetch_at,
+                            last_success_at: health.last_success_at,
+                            http_status: health.http_status,
+                            entries: health.entries,
+                            invalid_lines: health.invalid_lines,
+                            last_error: health.last_error,
+                        }
+                    })
+                    .collect();
+                Response::Subscriptions { lists }
+            }
+
+            Command::GetBlocklistDiffs { domain } => {
+                let state_guard = state.read().await;
+                let diffs = state_guard
+                    .store
+                    .get()
+                    .subscription_diffs
+                    .iter()
+                    .rev()
+                    .filter(|diff| match &domain {
+                        Some(domain) => diff_adds(diff, domain),
+                        None => true,
+                    })
+                    .cloned()
+                    .collect();
+                Response::BlocklistDiffs { diffs }
+            }
+
+            Command::GetCacheStats => {
+                let state_guard = state.read().await;
+                let stats = match state_guard.cache.lock() {
+                    Ok(cache) => cache.stats(),
+                    Err(_) => {
+                        return Response::Error {
+                            code: ErrorCode::InternalError,
+                            message: "DNS cache unavailable".to_string(),
+                            context: None,
+                        }
+                    }
+                };
+                Response::CacheStats(stats)
+            }
+
+            Command::FlushCache { domain } => {
+                let state_guard = state.read().await;
+                let mut cache = match state_guard.cache.lock() {
+                    Ok(cache) => cache,
+                    Err(_) => {
+                        return Response::Error {
+                            code: ErrorCode::InternalError,
+                            message: "DNS cache unavailable".to_string(),
+                            context: None,
+                        }
+                    }
+                };
+                let entries_removed = match &domain {
+                    Some(domain) => cache.flush_domain(domain),
+                    None => cache.flush(),
+                };
+                info!(domain = ?domain, entries_removed, "DNS cache flushed");
+                Response::CacheFlushed { entries_removed }
+            }
+
+            Command::GetBypassAnalytics { weeks } => {
+                let state_guard = state.read().await;
+                let weeks = weekly_bypass_analytics(
+                    &state_guard.store.get().bypass_history,
+                    weeks.min(52),
+                    chrono::Local::now(),
+                );
+                Response::BypassAnalytics { weeks }
+            }
+
+            Command::GetActivityHeatmap { weeks } => {
+                let mut state_guard = state.write().await;
+                // Include the blocks counted since the last save
+                state_guard.save_blocks();
+                let heatmap = activity_heatmap(
+                    &state_guard.store.get().block_activity,
+                    weeks.min(52),
+                    chrono::Local::now(),
+                );
+                Response::ActivityHeatmap(heatmap)
+            }
+
+            Command::PurgeData {
+                scope,
+                confirmation,
+            } => {
+                let mut state_guard = state.write().await;
+                let now = chrono::Utc::now().timestamp();
+
+                // A first request only hands out a token, so data is never
+                // deleted by a single stray command
+                let Some(confirmation) = confirmation else {
+                    let confirmation = Uuid::new_v4().to_string();
+                    state_guard.pending_purge = Some(PendingPurge {
+                        scope,
+                        confirmation: confirmation.clone(),
+                        expires_at: now + PURGE_CONFIRMATION_SECONDS as i64,
+                    });
+                    return Response::PurgeConfirmationRequired {
+                        confirmation,
+                        expires_in_seconds: PURGE_CONFIRMATION_SECONDS,
+                    };
+                };
+
+                let confirmed = state_guard.pending_purge.take().is_some_and(|pending| {
+                    pending.scope == scope
+                        && pending.expires_at >= now
+                        && pending.confirmation == confirmation
+                });
+                if !confirmed {
+                    return Response::Error {
+                        code: ErrorCode::InvalidCommand,
+                        message: tr(state_guard.locale(), Msg::PurgeConfirmationInvalid, &[]),
+                        context: None,
+                    };
+                }
+
+                match state_guard.purge_data(scope) {
+                    Ok(()) => Response::DataPurged { scope },
+                    Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("Failed to purge data: {}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::CreateBackup => {
+                let state_guard = state.read().await;
+                let now = chrono::Utc::now().timestamp();
+                let config = Config::clone(&state_guard.config.get());
+                let archive = BackupArchive::new(config, state_guard.store.get().clone(), now);
+                match archive.to_text() {
+                    Ok(archive) => Response::Backup { archive },
+                    Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("{:#}", e),
+                        context: None,
+                    },
+                }
+            }
+
+            Command::ImportExternal { source, data, dry_run } => {
+                let export = match BASE64.decode(data.trim()) {
+                    Ok(export) => export,
+                    Err(e) => {
+                        return Response::Error {
+                            code: ErrorCode::InvalidCommand,
+                            message: format!("Import data is not base64: {}", e),
+                            context: None,
+                        }
+                    }
+                };
+                let mut preview = match read_export(source, &export).await {
+                    Ok(preview) => preview,
                     Err(e) => {
-                        let code = match e {
-                            crate::quiz::QuizError::NotFound => ErrorCode::QuizNotFound,
-                            crate::quiz::QuizError::Expired => ErrorCode::QuizExpired,
-                            crate::quiz::QuizError::TooFast => ErrorCode::QuizTooFast,
-                            crate::quiz::QuizError::WrongAnswerCount
-                            | crate::quiz::QuizError::WrongAnswer => ErrorCode::QuizFailed,
-                        };
-                        Response::Error {
-                            code,
-                            message: e.to_string(),
+                        return Response::Error {
+                            code: ErrorCode::InvalidCommand,
+                            message: format!("{:#}", e),
+                            context: None,
                         }
                     }
+                };
+
+                let mut state_guard = state.write().await;
+                let subscribed = state_guard.config.get().subscriptions.lists.clone();
+                preview.subscriptions.retain(|list| !subscribed.iter().any(|l| l.url == list.url));
+                if dry_run || (preview.groups.is_empty() && preview.subscriptions.is_empty()) {
+                    return Response::ImportPreview(preview);
                 }
+
+                if let Err(e) = state_guard.config.update(|c| merge_import(c, &preview)).await {
+                    return Response::Error {
+                        code: ErrorCode::ConfigError,
+                        message: format!("Failed to import: {}", e),
+                        context: None,
+                    };
+                }
+                let groups = GroupBudgets::new(&state_guard.config.get().blocking.groups);
+                state_guard.groups = Arc::new(groups);
+                if !preview.subscriptions.is_empty() {
+                    let state = state.clone();
+                    let is_dev = std::env::var("BLOCKANDFOCUS_DEV").is_ok();
+                    tokio::spawn(async move { refresh_subscriptions(&state, is_dev).await });
+                }
+
+                info!(
+                    ?source,
+                    groups = preview.groups.len(),
+                    subscriptions = preview.subscriptions.len(),
+                    skipped = preview.skipped.len(),
+                    "Imported blocklists"
+                );
+                preview.applied = true;
+                Response::ImportPreview(preview)
             }
 
-            Command::CancelBypass => {
+            Command::RestoreBackup { archive } => {
+                let archive = match BackupArchive::parse(&archive) {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        return Response::Error {
+                            code: ErrorCode::InvalidCommand,
+                            message: format!("{:#}", e),
+                            context: None,
+                        }
+                    }
+                };
+
                 let mut state_guard = state.write().await;
-                state_guard.cancel_bypass();
+                // A backup can swap in any blocklist and schedule, so it is
+                // only restored when that can't cut blocking short
+                if let Some(refused) = Self::weakening_refused(&state_guard) {
+                    return refused;
+                }
+                if state_guard.is_blocking_active() {
+                    return Response::Error {
+                        code: ErrorCode::SettingsLocked,
+                        message: tr(state_guard.locale(), Msg::RestoreDuringBlocking, &[]),
+                        context: None,
+                    };
+                }
+
+                match state_guard.restore_backup(archive).await {
+                    Ok(()) => Response::Success,
+                    Err(e) => Response::Error {
+                        code: ErrorCode::InternalError,
+                        message: format!("Failed to restore backup: {:#}", e),
+                        context: None,
+                    },
+                }
+            }
+        }
+    }
+
+    /// Apply a schedule edit, after checking it against the settings lock and
+    /// strict rules. An edit that blocks less may take a quiz first (unless
+    /// `quiz_passed`) or wait for midnight.
+    async fn update_schedule(
+        state: &mut AppState,
+        submitted: Schedule,
+        quiz_passed: bool,
+    ) -> Response {
+        // Temporary rules (FocusNow) survive schedule edits until they expire
+        let now = chrono::Utc::now().timestamp();
+        let current = state.config.get();
+        let schedule = merge_temporary_rules(&current.schedule, submitted.clone(), now);
+        let weakens = weakens_schedule(&current.schedule, &schedule, now);
+
+        // While settings are locked, the schedule can only grow stronger
+        if weakens {
+            if let Some(locked) = Self::settings_locked(state) {
+                return locked;
+            }
+        }
+
+        // Strict rules must keep applying while they are active
+        if !state.keeps_strict_rules(&schedule) {
+            return Response::Error {
+                code: ErrorCode::SettingsLocked,
+                message: tr(state.locale(), Msg::StrictNoWeakening, &[]),
+                context: None,
+            };
+        }
+
+        if weakens && !quiz_passed && state.weakening_needs_quiz() {
+            let challenge = state.quiz.generate_challenge_for(BypassRequest {
+                reason: None,
+                action: GatedAction::UpdateSchedule { schedule: submitted },
+            });
+            debug!(challenge_id = %challenge.challenge_id, "Schedule edit reduces blocking, quiz generated");
+            return Response::QuizChallenge(challenge);
+        }
+
+        if weakens && current.protection.delay_weakening_edits {
+            let applies_at = next_midnight(chrono::Local::now()).timestamp();
+            let pending = PendingScheduleChange {
+                schedule,
+                submitted_at: now,
+                applies_at,
+            };
+            return match state.store.set_pending_schedule(Some(pending)) {
+                Ok(()) => {
+                    info!(applies_at, "Schedule edit reduces blocking, deferred");
+                    Response::ScheduleChangePending { applies_at }
+                }
+                Err(e) => Response::Error {
+                    code: ErrorCode::InternalError,
+                    message: format!("Failed to save schedule edit: {}", e),
+                    context: None,
+                },
+            };
+        }
+
+        // A newer edit replaces one still waiting for midnight
+        if state.store.get().pending_schedule.is_some() {
+            if let Err(e) = state.store.set_pending_schedule(None) {
+                warn!("Failed to drop pending schedule edit: {:#}", e);
+            }
+        }
+
+        match state.apply_schedule(schedule).await {
+            Ok(()) => {
+                info!("Schedule updated");
+                Response::Success
+            }
+            Err(e) => Response::Error {
+                code: ErrorCode::ConfigError,
+                message: format!("Failed to update schedule: {}", e),
+                context: None,
+            },
+        }
+    }
+
+    /// Remove a domain from the blocklist and rebuild the blocker.
+    async fn remove_domain(state: &AppState, domain: &DomainName) -> Response {
+        match state.config.remove_domain(domain).await {
+            Ok(true) => {
+                // Update the blocker with new domain list
+                state.rebuild_blocker();
+                info!(domain = %domain, "Domain removed from blocklist");
+                Response::Success
+            }
+            Ok(false) => Response::Error {
+                code: ErrorCode::InvalidDomain,
+                message: tr(state.locale(), Msg::DomainNotInBlocklist, &[]),
+                context: Some(ErrorContext::Domain { domain: domain.to_string() }),
+            },
+            Err(e) => Response::Error {
+                code: ErrorCode::ConfigError,
+                message: format!("Failed to remove domain: {}", e),
+                context: None,
+            },
+        }
+    }
+
+    /// Enable or disable a blocklist group and rebuild the group matchers.
+    async fn set_group_enabled(state: &mut AppState, name: &str, enabled: bool) -> Response {
+        let result = state
+            .config
+            .update(|c| {
+                for group in c.blocking.groups.iter_mut().filter(|g| g.name == name) {
+                    group.enabled = enabled;
+                }
+            })
+            .await;
+
+        match result {
+            Ok(()) => {
+                state.groups = Arc::new(GroupBudgets::new(&state.config.get().blocking.groups));
+                info!(group = %name, enabled, "Blocklist group updated");
+                Response::Success
+            }
+            Err(e) => Response::Error {
+                code: ErrorCode::ConfigError,
+                message: format!("Failed to update group: {}", e),
+                context: None,
+            },
+        }
+    }
+
+    /// Apply client profiles just saved with `result`.
+    fn clients_updated(state: &mut AppState, result: Result<()>, name: &str) -> Response {
+        match result {
+            Ok(()) => {
+                state.clients.replace(&state.config.get().clients);
+                info!(profile = %name, "Client profiles updated");
                 Response::Success
             }
+            Err(e) => Response::Error {
+                code: ErrorCode::ConfigError,
+                message: format!("Failed to update client profiles: {}", e),
+                context: None,
+            },
+        }
+    }
+
+    /// The error for a command that weakens blocking while that is not allowed at all.
+    fn weakening_refused(state: &AppState) -> Option<Response> {
+        if let Some(locked) = Self::settings_locked(state) {
+            return Some(locked);
+        }
+        (state.strictness() == Strictness::Strict).then(|| Response::Error {
+            code: ErrorCode::SettingsLocked,
+            message: tr(state.locale(), Msg::StrictNoWeakening, &[]),
+            context: None,
+        })
+    }
+
+    /// The error for a weakening command while settings are locked, if they are.
+    ///
+    /// An unreadable state file counts as a lock: it may hold one.
+    fn settings_locked(state: &AppState) -> Option<Response> {
+        if state.store.is_unreadable() {
+            return Some(Response::Error {
+                code: ErrorCode::SettingsLocked,
+                message: tr(state.locale(), Msg::StateUnreadable, &[]),
+                context: None,
+            });
+        }
+        let minutes = state.settings_lock_remaining_minutes()?;
+        Some(Response::Error {
+            code: ErrorCode::SettingsLocked,
+            message: tr(state.locale(), Msg::SettingsLocked, &[&minutes]),
+            context: Some(ErrorContext::LockedFor { minutes }),
+        })
+    }
+
+    /// The error for a bypass longer than the `remaining` budget minutes.
+    fn budget_exceeded(state: &AppState, remaining: u32) -> Response {
+        Response::Error {
+            code: ErrorCode::BudgetExceeded,
+            message: tr(state.locale(), Msg::BudgetExceeded, &[&remaining]),
+            context: Some(ErrorContext::BudgetRemaining { minutes: remaining }),
+        }
+    }
+
+    /// Map a quiz validation error to its IPC error code.
+    fn quiz_error_code(e: QuizError) -> ErrorCode {
+        match e {
+            QuizError::NotFound => ErrorCode::QuizNotFound,
+            QuizError::Expired => ErrorCode::QuizExpired,
+            QuizError::TooFast => ErrorCode::QuizTooFast,
+            QuizError::WrongPhase => ErrorCode::QuizWrongPhase,
+            QuizError::StillWaiting => ErrorCode::QuizWaitPending,
+            QuizError::WrongAnswerCount | QuizError::WrongAnswer => ErrorCode::QuizFailed,
         }
     }
 }
@@ -0,0 +1,193 @@
+//! Authenticated IPC sessions.
+//!
+//! Programs that can read the daemon's key start a session and sign each
+//! command with an HMAC (see `blockandfocus_shared::command_mac`). Unlike the
+//! socket permissions and peer checks this does not depend on the transport,
+//! so it carries over to listeners where filesystem permissions don't apply.
+
+use anyhow::{Context, Result};
+use blockandfocus_shared::{command_mac, from_hex, to_hex, Command};
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long a session stays valid after it is started.
+const SESSION_LIFETIME: Duration = Duration::from_secs(12 * 3600);
+
+/// Permissions of the key file.
+const KEY_MODE: u32 = 0o640;
+
+/// Maximum number of sessions kept; the oldest is dropped beyond this.
+const MAX_SESSIONS: usize = 64;
+
+/// How far behind the highest counter seen a counter may arrive (out of order).
+const COUNTER_WINDOW: u64 = 64;
+
+/// An authenticated session.
+struct Session {
+    nonce: String,
+    started_at: Instant,
+    /// Highest counter used so far
+    highest: u64,
+    /// Counters used within the window below `highest`
+    used: HashSet<u64>,
+}
+
+/// Sessions signed with the daemon's IPC key.
+pub struct Sessions {
+    key: Vec<u8>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Sessions {
+    /// Create a session store using `key` to check signatures.
+    pub fn new(key: Vec<u8>) -> Self {
+        Self {
+            key,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start a session, returning its ID and nonce.
+    pub fn start(&self) -> (String, String) {
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let nonce = to_hex(&rand::random::<[u8; 16]>());
+        let now = Instant::now();
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| now.duration_since(session.started_at) < SESSION_LIFETIME);
+        if sessions.len() >= MAX_SESSIONS {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, session)| session.started_at)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                nonce: nonce.clone(),
+                started_at: now,
+                highest: 0,
+                used: HashSet::new(),
+            },
+        );
+
+        (session_id, nonce)
+    }
+
+    /// Check a signed command, consuming its counter.
+    pub fn verify(
+        &self,
+        session_id: &str,
+        counter: u64,
+        mac: &str,
+        command: &Command,
+    ) -> Result<(), &'static str> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .filter(|session| session.started_at.elapsed() < SESSION_LIFETIME)
+            .ok_or("Unknown or expired session")?;
+
+        let expected = command_mac(&self.key, session_id, &session.nonce, counter, command);
+        if !constant_time_eq(expected.as_bytes(), mac.as_bytes()) {
+            return Err("Invalid command signature");
+        }
+
+        let too_old = counter.saturating_add(COUNTER_WINDOW) <= session.highest;
+        if counter == 0 || too_old || session.used.contains(&counter) {
+            return Err("Command counter already used");
+        }
+        session.used.insert(counter);
+        session.highest = session.highest.max(counter);
+        let floor = session.highest.saturating_sub(COUNTER_WINDOW);
+        session.used.retain(|used| *used > floor);
+
+        Ok(())
+    }
+}
+
+/// Read the IPC key, creating a random one if there is none yet.
+///
+/// The key is readable by the owner's group, like the socket. It is created
+/// with that mode, so it is never readable by anyone else.
+pub fn load_or_create_key(path: &str) -> Result<Vec<u8>> {
+    if let Ok(content) = std::fs::read_to_string(path) {
+        if let Some(key) = from_hex(content.trim()) {
+            return Ok(key);
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove invalid IPC key: {}", path))?;
+    }
+
+    let key = rand::random::<[u8; 32]>().to_vec();
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(KEY_MODE)
+        .open(path)
+        .with_context(|| format!("Failed to create IPC key: {}", path))?;
+    file.write_all(to_hex(&key).as_bytes())
+        .with_context(|| format!("Failed to write IPC key: {}", path))?;
+
+    info!("Created IPC key at {}", path);
+    Ok(key)
+}
+
+/// Compare without leaking where the first difference is.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_rejects_forgery_and_replay() {
+        let sessions = Sessions::new(b"secret".to_vec());
+        let (session_id, nonce) = sessions.start();
+        let command = Command::CancelBypass;
+        let sign = |counter| command_mac(b"secret", &session_id, &nonce, counter, &command);
+
+        assert!(sessions.verify(&session_id, 1, &sign(1), &command).is_ok());
+        // Replayed counter
+        assert!(sessions.verify(&session_id, 1, &sign(1), &command).is_err());
+        // Out of order within the window is fine
+        assert!(sessions.verify(&session_id, 5, &sign(5), &command).is_ok());
+        assert!(sessions.verify(&session_id, 3, &sign(3), &command).is_ok());
+        // Signature for a different command
+        assert!(sessions.verify(&session_id, 6, &sign(6), &Command::Shutdown).is_err());
+        // Wrong key or unknown session
+        let forged = command_mac(b"guess", &session_id, &nonce, 7, &command);
+        assert!(sessions.verify(&session_id, 7, &forged, &command).is_err());
+        assert!(sessions.verify("other", 7, &sign(7), &command).is_err());
+    }
+
+    #[test]
+    fn test_key_is_created_private_and_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("baf-ipc-key-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("ipc.key");
+        let path = path.to_str().unwrap();
+
+        let key = load_or_create_key(path).unwrap();
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, KEY_MODE);
+        assert_eq!(load_or_create_key(path).unwrap(), key);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
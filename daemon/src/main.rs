@@ -2,99 +2,924 @@
 //!
 //! A DNS-based domain blocker for productivity.
 
+mod analytics;
+mod blockpage;
+mod budget;
+mod clients;
+mod clock;
 mod config;
+mod diagnostics;
 mod dns;
+mod i18n;
+mod import;
 mod ipc;
+mod maintenance;
+mod partner;
+mod power;
+mod querylog;
 mod quiz;
+mod reports;
 mod schedule;
+mod store;
+mod subscriptions;
+mod supervisor;
+mod update;
+mod usage;
+
+#[cfg(test)]
+mod testing;
 
 use anyhow::Result;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{info, Level};
+use arc_swap::ArcSwap;
+use blockandfocus_shared::{
+    site_key, AllowanceStatus, BlockingWindow, BudgetKind, BudgetWarning, ClientProfile,
+    DataPurge, DomainName, EncryptedDnsDetection, Event, Locale, PartnerStatus, PurgeScope,
+    Schedule, ScheduleRule, Strictness, UpdateStatus, UsageStats, CONFIG_PATH, CONFIG_PATH_DEV,
+};
+use chrono::TimeZone;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, Notify, RwLock};
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
+use crate::blockpage::BlockPage;
+use crate::budget::{crossed_threshold, DomainAllowances, GroupBudgets};
+use crate::clients::{ClientPolicies, NeighborMonitor};
+use crate::clock::ClockMonitor;
 use crate::config::ConfigManager;
-use crate::dns::{DnsServer, DomainBlocker};
+use crate::diagnostics::{DnsWatchdog, EncryptedDnsMonitor, ProbeTracker};
+use crate::dns::{BlockingPolicy, BypassWindow, DnsCache, DnsServer, DomainBlocker};
 use crate::ipc::IpcServer;
+use crate::maintenance::{dns_cache_path, load_dns_cache, remove_dns_cache, MaintenanceMonitor};
+use crate::partner::PartnerLink;
+use crate::power::PowerMonitor;
+use crate::querylog::QueryLog;
 use crate::quiz::QuizEngine;
-use crate::schedule::ScheduleEngine;
+use crate::reports::ReportMonitor;
+use crate::schedule::{keeps_rules, merge_temporary_rules, PendingScheduleMonitor, ScheduleEngine};
+use crate::store::{BackupArchive, BypassRecord, StateStore};
+use crate::subscriptions::SubscriptionMonitor;
+use crate::supervisor::{supervise, RestartPolicy};
+use crate::update::UpdateMonitor;
+use crate::usage::UsageEstimator;
+
+/// Number of events buffered for slow subscribers before they miss some.
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Seconds blocked-query counts may go unsaved.
+const BLOCKS_SAVE_INTERVAL_SECONDS: i64 = 60;
+
+/// Address `--partner-relay` listens on by default.
+const PARTNER_RELAY_ADDR: &str = "0.0.0.0:7878";
+
+/// Days covered by the schedule timeline preview.
+const TIMELINE_DAYS: i64 = 7;
 
 /// Shared application state.
 pub struct AppState {
-    pub config: ConfigManager,
+    pub config: Arc<ConfigManager>,
     pub schedule: ScheduleEngine,
     pub quiz: QuizEngine,
-    pub blocker: DomainBlocker,
+    /// Main blocklist, swapped as a whole when the list changes
+    pub blocker: ArcSwap<DomainBlocker>,
+    /// Essential services that are never blocked
+    pub essential: ArcSwap<DomainBlocker>,
+    /// Domains from each subscribed list, by list URL
+    pub subscribed_domains: ArcSwap<HashMap<String, Vec<DomainName>>>,
+    pub groups: Arc<GroupBudgets>,
+    pub allowances: Arc<DomainAllowances>,
+    /// Policies for other devices on the network
+    pub clients: ClientPolicies,
+    /// Inputs of the blocking decision as last published, read by the DNS
+    /// server without taking the state lock
+    pub policy: Arc<ArcSwap<BlockingPolicy>>,
+    /// Usage estimates feeding group budgets and allowances
+    pub activity: UsageEstimator,
+    pub cache: Arc<Mutex<DnsCache>>,
+    pub query_log: Arc<QueryLog>,
+    /// Self-check probe queries seen by the DNS server
+    pub probes: Arc<ProbeTracker>,
+    pub store: StateStore,
     pub stats: Stats,
-    pub bypass_until: Option<i64>,
+    /// The running bypass, if any
+    pub bypass: BypassWindow,
+    /// Whether the machine has had no user input for the idle threshold
+    pub system_idle: bool,
+    /// Address the DNS server is actually bound to
+    pub dns_listen_addr: Option<SocketAddr>,
+    /// Asks the DNS server to drop and rebind its socket
+    pub dns_rebind: Arc<Notify>,
+    /// Result of the last update check
+    pub update: Option<UpdateStatus>,
+    /// Events pushed to subscribed IPC connections
+    pub events: broadcast::Sender<Event>,
+    /// Tells main to exit after an authorized shutdown
+    pub shutdown: Arc<Notify>,
+    /// Whether stopping the daemon was allowed (quiz passed or outside blocking hours)
+    pub shutdown_authorized: bool,
+    /// PurgeData request waiting to be confirmed
+    pub pending_purge: Option<PendingPurge>,
+    /// Link to a focus partner, while partner sessions are on
+    pub partner: Option<PartnerStatus>,
+}
+
+/// A PurgeData request waiting for its confirmation.
+pub struct PendingPurge {
+    pub scope: PurgeScope,
+    /// Token the client must send back
+    pub confirmation: String,
+    /// Unix timestamp after which the token is no longer accepted
+    pub expires_at: i64,
 }
 
 /// Runtime statistics.
 #[derive(Default)]
 pub struct Stats {
-    pub queries_blocked: u64,
-    pub queries_forwarded: u64,
+    /// Counted by the DNS server, which shares them
+    pub queries: Arc<QueryCounters>,
+    /// Connections to encrypted DNS resolvers seen while blocking was active
+    pub encrypted_dns_connections: u64,
+    /// Most recent encrypted DNS connection
+    pub last_encrypted_dns: Option<EncryptedDnsDetection>,
+    /// Blocked queries per matched blocklist entry
+    pub blocked_by_domain: HashMap<String, u64>,
+    /// Local date (YYYY-MM-DD) `usage` covers
+    pub usage_date: String,
+    /// Estimated usage per site for `usage_date`
+    pub usage: UsageEstimator,
+    /// Blocked queries not yet added to the stored activity history
+    pub unsaved_blocks: Option<UnsavedBlocks>,
+    /// Unix timestamp blocked queries were last saved
+    pub blocks_saved_at: i64,
+}
+
+/// Query counters the DNS server updates without taking the state lock.
+#[derive(Default)]
+pub struct QueryCounters {
+    pub blocked: AtomicU64,
+    pub forwarded: AtomicU64,
+    /// Queries forwarded in dry-run mode that would otherwise have been blocked
+    pub would_block: AtomicU64,
+    /// Packets rejected as malformed (bad header, size, question count or name)
+    pub malformed: AtomicU64,
+}
+
+impl QueryCounters {
+    pub fn reset(&self) {
+        for counter in [&self.blocked, &self.forwarded, &self.would_block, &self.malformed] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Blocked queries counted in one local hour since the last save.
+pub struct UnsavedBlocks {
+    pub date: chrono::NaiveDate,
+    pub hour: u32,
+    pub count: u32,
+}
+
+impl Stats {
+    /// The most frequently blocked blocklist entries, most blocked first.
+    pub fn top_blocked(&self, n: usize) -> Vec<String> {
+        let mut counts: Vec<(&String, &u64)> = self.blocked_by_domain.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        counts.into_iter().take(n).map(|(d, _)| d.clone()).collect()
+    }
 }
 
 impl AppState {
-    pub fn new(config: ConfigManager) -> Self {
+    pub fn new(config: ConfigManager, store: StateStore) -> Self {
         let cfg = config.get();
         let schedule_config = cfg.schedule.clone();
-        let quiz_config = cfg.quiz.clone();
-        let blocked_domains = cfg.blocking.domains.clone();
-
-        Self {
-            config,
-            schedule: ScheduleEngine::new(schedule_config),
-            quiz: QuizEngine::new(quiz_config),
-            blocker: DomainBlocker::new(blocked_domains),
+        let mut quiz = QuizEngine::new(cfg.quiz.clone());
+        quiz.set_locale(cfg.locale);
+        let groups = Arc::new(GroupBudgets::new(&cfg.blocking.groups));
+        let clients = ClientPolicies::new(&cfg.clients);
+        let allowances = Arc::new(DomainAllowances::new(&cfg.blocking.allowances));
+        let usage_config = cfg.usage.clone();
+        let mut query_log_config = cfg.query_log.clone();
+        // The log file is encrypted like the rest of the stored data, so it
+        // isn't written in plain text when the key is missing
+        let query_log_cipher = store.cipher().cloned();
+        if cfg.storage.encrypt && query_log_cipher.is_none() && query_log_config.file.take().is_some()
+        {
+            warn!("Not writing query_log.file, the storage key is unavailable");
+        }
+        let cache_config = cfg.cache.clone();
+        let mut schedule = ScheduleEngine::new(schedule_config);
+        schedule.set_vacations(store.get().vacations.clone());
+
+        let state = Self {
+            config: Arc::new(config),
+            schedule,
+            quiz,
+            blocker: ArcSwap::from_pointee(DomainBlocker::new(&cfg.blocking.domains)),
+            essential: ArcSwap::from_pointee(DomainBlocker::new(&cfg.blocking.essential_domains)),
+            subscribed_domains: ArcSwap::default(),
+            groups,
+            allowances,
+            clients,
+            policy: Arc::new(ArcSwap::from_pointee(BlockingPolicy::default())),
+            activity: UsageEstimator::new(usage_config),
+            cache: Arc::new(Mutex::new(DnsCache::new(cache_config))),
+            query_log: Arc::new(QueryLog::new(query_log_config, query_log_cipher)),
+            probes: Arc::new(ProbeTracker::default()),
+            store,
             stats: Stats::default(),
-            bypass_until: None,
+            bypass: BypassWindow::default(),
+            system_idle: false,
+            dns_listen_addr: None,
+            dns_rebind: Arc::new(Notify::new()),
+            update: None,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            shutdown: Arc::new(Notify::new()),
+            shutdown_authorized: false,
+            pending_purge: None,
+            partner: None,
+        };
+        state.publish_policy();
+        state
+    }
+
+    /// Publish the current inputs of the blocking decision to the DNS
+    /// server. Called after anything they are made from changes.
+    pub fn publish_policy(&self) {
+        let week = week_start();
+        let date = today();
+        let groups = self.config.get().blocking.groups.clone();
+        let group_minutes = groups
+            .iter()
+            .filter(|group| group.weekly_budget_minutes.is_some())
+            .map(|group| (group.name.clone(), self.store.group_minutes_used(&group.name, &week)))
+            .collect();
+        let domain_minutes = self
+            .allowances
+            .entries()
+            .into_iter()
+            .map(|(entry, _)| (entry.to_string(), self.store.domain_minutes_used(entry, &date)))
+            .collect();
+
+        self.policy.store(Arc::new(BlockingPolicy {
+            blocker: self.blocker.load_full(),
+            essential: self.essential.load_full(),
+            groups: self.groups.clone(),
+            allowances: self.allowances.clone(),
+            schedule: self.schedule.clone(),
+            clients: self.clients.clone(),
+            bypass: self.bypass.clone(),
+            week,
+            group_minutes,
+            date,
+            domain_minutes,
+        }));
+    }
+
+    /// Rebuild the blocker from the configured domains and subscribed lists.
+    pub fn rebuild_blocker(&self) {
+        let config = self.config.get();
+        let subscribed = self.subscribed_domains.load();
+
+        let mut domains = config.blocking.domains.clone();
+        for list in config.subscriptions.lists.iter().filter(|list| list.enabled) {
+            if let Some(list_domains) = subscribed.get(&list.url) {
+                domains.extend(list_domains.iter().cloned());
+            }
         }
+
+        self.blocker.store(Arc::new(DomainBlocker::new(&domains)));
+        self.essential
+            .store(Arc::new(DomainBlocker::new(&config.blocking.essential_domains)));
+        self.publish_policy();
+    }
+
+    /// Strictness of the schedule rules active right now.
+    pub fn strictness(&self) -> Strictness {
+        self.schedule.active_strictness().unwrap_or_default()
+    }
+
+    /// Minutes until the settings lock ends (None if settings are not locked).
+    pub fn settings_lock_remaining_minutes(&self) -> Option<u32> {
+        let now = chrono::Utc::now().timestamp();
+        let lock = self.store.settings_lock(now)?;
+        Some(((lock.until - now + 59) / 60) as u32)
+    }
+
+    /// Whether `schedule` keeps every strict rule that is active right now.
+    pub fn keeps_strict_rules(&self, schedule: &Schedule) -> bool {
+        let strict_rules: Vec<_> = self
+            .schedule
+            .active_rules()
+            .into_iter()
+            .filter(|rule| rule.strictness == Strictness::Strict)
+            .collect();
+        keeps_rules(schedule, &strict_rules)
+    }
+
+    /// Replace the schedule in the engine and the config file.
+    pub async fn apply_schedule(&mut self, schedule: Schedule) -> Result<()> {
+        self.schedule.update(schedule.clone());
+        self.publish_policy();
+        self.config.update(|c| c.schedule = schedule).await
+    }
+
+    /// Whether removing a domain or disabling a group requires a quiz right now.
+    pub fn weakening_needs_quiz(&self) -> bool {
+        self.config.get().protection.quiz_for_weakening
+            && self.is_blocking_active()
+            && self.strictness() != Strictness::Relaxed
+    }
+
+    /// Whether stopping the daemon requires a quiz right now.
+    pub fn shutdown_guarded(&self) -> bool {
+        self.config.get().protection.quiz_for_shutdown
+            && self.is_blocking_scheduled()
+            && !self.shutdown_authorized
+    }
+
+    /// Allow the daemon to stop and have main exit shortly.
+    pub fn authorize_shutdown(&mut self) {
+        self.shutdown_authorized = true;
+
+        // Give the IPC response time to reach the client before exiting
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            shutdown.notify_one();
+        });
+    }
+
+    /// Language for user-facing text.
+    pub fn locale(&self) -> Locale {
+        self.config.get().locale
     }
 
     /// Check if blocking is currently active.
     pub fn is_blocking_active(&self) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        self.is_blocking_scheduled() && !self.is_bypass_active(now)
+    }
+
+    /// Check if blocking would be active ignoring any bypass.
+    fn is_blocking_scheduled(&self) -> bool {
         // Check if blocking is enabled in config
         if !self.config.get().blocking.enabled {
             return false;
         }
 
-        // Check if there's an active bypass
-        if let Some(bypass_until) = self.bypass_until {
-            let now = chrono::Utc::now().timestamp();
-            if now < bypass_until {
-                return false;
+        // Check schedule (a disabled schedule blocks all the time, except on vacation)
+        self.schedule.is_blocking_time()
+    }
+
+    /// Snooze the schedule rule starting at `activation` for `minutes`.
+    pub fn snooze_schedule(&mut self, activation: i64, minutes: u32) {
+        self.schedule.snooze(activation, minutes);
+        self.publish_policy();
+
+        if let Err(e) = self.store.record_snooze(&today()) {
+            warn!("Failed to record schedule snooze: {:#}", e);
+        }
+
+        info!(activation, minutes, "Schedule snoozed");
+    }
+
+    /// Check if a bypass is in effect (past its soft-start countdown and not expired).
+    fn is_bypass_active(&self, now: i64) -> bool {
+        self.bypass.is_active(now)
+    }
+
+    /// Get the blocklist entry that blocks this domain right now, if any.
+    ///
+    /// Decided like DNS queries are, from the published `BlockingPolicy`.
+    pub fn blocked_entry(&self, domain: &str) -> Option<String> {
+        self.blocked_entry_for(domain, None)
+    }
+
+    /// Get the blocklist entry that blocks this domain right now for a
+    /// device of `profile` (or this Mac, without one), if any.
+    pub fn blocked_entry_for(
+        &self,
+        domain: &str,
+        profile: Option<&ClientProfile>,
+    ) -> Option<String> {
+        self.policy.load().blocked_entry_for(&self.config.get(), domain, profile)
+    }
+
+    /// Record a resolved query towards usage stats, group budgets and domain allowances.
+    ///
+    /// Nothing is counted while the machine is idle if `usage.pause_when_idle`
+    /// is set, so background queries do not consume budgets.
+    pub fn record_activity(&mut self, domain: &str) {
+        if self.system_idle && self.config.get().usage.pause_when_idle {
+            return;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+
+        let date = today();
+        if self.stats.usage_date != date {
+            self.stats.usage_date = date;
+            self.stats.usage = UsageEstimator::new(self.config.get().usage.clone());
+        }
+        self.stats.usage.record(&site_key(domain), now);
+
+        let group = self
+            .groups
+            .matching_group(domain)
+            .and_then(|(group, _)| Some((group.name.clone(), group.weekly_budget_minutes?)));
+        if let Some((group, budget)) = group {
+            let minutes = self.activity.record(&format!("group:{}", group), now);
+            if minutes > 0 {
+                let week = week_start();
+                let before = self.store.group_minutes_used(&group, &week);
+                if let Err(e) = self.store.add_group_minutes(&group, &week, minutes) {
+                    warn!("Failed to record group usage: {:#}", e);
+                }
+                self.publish_policy();
+                self.warn_budget(BudgetKind::Group, &group, before, before + minutes, budget);
+            }
+        }
+
+        let allowance = self
+            .allowances
+            .matching(domain)
+            .map(|(entry, daily_minutes)| (entry.to_string(), daily_minutes));
+        if let Some((entry, daily_minutes)) = allowance {
+            let minutes = self.activity.record(&format!("domain:{}", entry), now);
+            if minutes > 0 {
+                let date = today();
+                let before = self.store.domain_minutes_used(&entry, &date);
+                if let Err(e) = self.store.add_domain_minutes(&entry, &date, minutes) {
+                    warn!("Failed to record allowance usage: {:#}", e);
+                }
+                self.publish_policy();
+                self.warn_budget(BudgetKind::Domain, &entry, before, before + minutes, daily_minutes);
+            }
+        }
+    }
+
+    /// Count a blocked query in the activity history. Counts are saved when
+    /// the hour changes, and at least every `BLOCKS_SAVE_INTERVAL_SECONDS`.
+    pub fn record_block(&mut self) {
+        use chrono::Timelike;
+
+        let now = chrono::Local::now();
+        let (date, hour) = (now.date_naive(), now.hour());
+        let same_hour = |unsaved: &UnsavedBlocks| unsaved.date == date && unsaved.hour == hour;
+        if !self.stats.unsaved_blocks.as_ref().is_none_or(same_hour) {
+            self.save_blocks();
+        }
+
+        self.stats
+            .unsaved_blocks
+            .get_or_insert(UnsavedBlocks {
+                date,
+                hour,
+                count: 0,
+            })
+            .count += 1;
+        if now.timestamp() - self.stats.blocks_saved_at >= BLOCKS_SAVE_INTERVAL_SECONDS {
+            self.save_blocks();
+        }
+    }
+
+    /// Add the blocked queries counted since the last save to the store.
+    pub fn save_blocks(&mut self) {
+        self.stats.blocks_saved_at = chrono::Utc::now().timestamp();
+        let Some(unsaved) = self.stats.unsaved_blocks.take() else {
+            return;
+        };
+        if let Err(e) = self.store.add_blocks(unsaved.date, unsaved.hour, unsaved.count) {
+            warn!("Failed to record blocking activity: {:#}", e);
+        }
+    }
+
+    /// Emit a budget warning if usage crossed a warning threshold.
+    fn warn_budget(&self, kind: BudgetKind, name: &str, before: u32, after: u32, budget: u32) {
+        let Some(percent) = crossed_threshold(before, after, budget) else {
+            return;
+        };
+
+        info!(?kind, name, percent, "Usage budget threshold reached");
+        self.emit(Event::BudgetWarning(BudgetWarning {
+            kind,
+            name: name.to_string(),
+            percent,
+            used_minutes: after,
+            budget_minutes: budget,
+            remaining_minutes: budget.saturating_sub(after),
+        }));
+    }
+
+    /// Add a temporary rule to the schedule, dropping expired ones.
+    pub async fn add_focus_rule(&mut self, rule: ScheduleRule) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let mut schedule = self.config.get().schedule.clone();
+        schedule.rules.retain(|rule| rule.expires_at.is_none_or(|expires_at| now < expires_at));
+        schedule.rules.push(rule);
+
+        self.schedule.update(schedule.clone());
+        self.publish_policy();
+        self.config.update(|c| c.schedule = schedule).await
+    }
+
+    /// Push an event to all subscribers (dropped if nobody is listening).
+    pub fn emit(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    /// Handle the machine waking up after sleeping for `slept_seconds`.
+    ///
+    /// With `usage.pause_focus_on_sleep` set, running "Focus now" rules are
+    /// extended so the sleep does not use up the focus time.
+    pub async fn on_system_wake(&mut self, slept_seconds: i64) {
+        if !self.config.get().usage.pause_focus_on_sleep {
+            return;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let mut schedule = self.config.get().schedule.clone();
+        let mut extended = false;
+        for rule in &mut schedule.rules {
+            if let Some(expires_at) = rule.expires_at.as_mut() {
+                if now < *expires_at + slept_seconds {
+                    *expires_at += slept_seconds;
+                    extended = true;
+                }
+            }
+        }
+
+        if !extended {
+            return;
+        }
+
+        self.schedule.update(schedule.clone());
+        self.publish_policy();
+        match self.config.update(|c| c.schedule = schedule).await {
+            Ok(()) => info!(slept_seconds, "Extended focus rules after sleep"),
+            Err(e) => warn!("Failed to extend focus rules: {:#}", e),
+        }
+    }
+
+    /// Keep timers on real elapsed time after the wall clock moved by `jump_seconds`.
+    ///
+    /// Bypasses, focus rules, snoozes, the settings lock and deferred schedule
+    /// edits are shifted along with the clock, so changing it neither extends
+    /// nor ends them early.
+    pub async fn on_clock_jump(&mut self, jump_seconds: i64) {
+        for timestamp in [&mut self.bypass.until, &mut self.bypass.starts_at]
+            .into_iter()
+            .flatten()
+        {
+            *timestamp += jump_seconds;
+        }
+        self.bypass.excluded_until += jump_seconds;
+        self.schedule.shift_snooze(jump_seconds);
+        self.publish_policy();
+
+        if let Err(e) = self.store.update(|state| {
+            if let Some(lock) = state.settings_lock.as_mut() {
+                lock.until += jump_seconds;
+            }
+            if let Some(pending) = state.pending_schedule.as_mut() {
+                pending.applies_at += jump_seconds;
+            }
+        }) {
+            warn!("Failed to shift stored timers: {:#}", e);
+        }
+
+        let mut schedule = self.config.get().schedule.clone();
+        let mut shifted = false;
+        for expires_at in schedule.rules.iter_mut().filter_map(|rule| rule.expires_at.as_mut()) {
+            *expires_at += jump_seconds;
+            shifted = true;
+        }
+        if shifted {
+            self.schedule.update(schedule.clone());
+            self.publish_policy();
+            if let Err(e) = self.config.update(|c| c.schedule = schedule).await {
+                warn!("Failed to shift focus rules: {:#}", e);
+            }
+        }
+    }
+
+    /// Delete personal data in `scope` and tell subscribers.
+    pub fn purge_data(&mut self, scope: PurgeScope) -> anyhow::Result<()> {
+        if scope.covers(PurgeScope::QueryLog) {
+            self.query_log.clear();
+            // The DNS cache, in memory and saved, lists recently resolved names too
+            if let Ok(mut cache) = self.cache.lock() {
+                cache.flush();
+            }
+            if self.store.is_on_disk() {
+                let is_dev = std::env::var("BLOCKANDFOCUS_DEV").is_ok();
+                remove_dns_cache(dns_cache_path(is_dev));
+            }
+        }
+        if scope.covers(PurgeScope::Stats) {
+            // The counters are reset in place, the DNS server keeps counting into them
+            self.stats.queries.reset();
+            self.stats = Stats {
+                queries: self.stats.queries.clone(),
+                ..Stats::default()
+            };
+            self.store.clear_block_activity()?;
+        }
+        if scope.covers(PurgeScope::BypassHistory) {
+            self.store.clear_bypass_history()?;
+        }
+
+        info!(?scope, "Personal data purged");
+        self.emit(Event::DataPurged(DataPurge {
+            scope,
+            purged_at: chrono::Utc::now().timestamp(),
+        }));
+        Ok(())
+    }
+
+    /// Replace the configuration and persisted state with a backup's. Settings
+    /// only read at startup (DNS listener, cache, query log) apply after a restart.
+    pub async fn restore_backup(&mut self, archive: BackupArchive) -> anyhow::Result<()> {
+        let created_at = archive.created_at;
+        let previous = self.store.get().clone();
+        self.store.replace(archive.state)?;
+        if let Err(e) = self.config.update(|config| *config = archive.config).await {
+            // Keep the state file matching the config that stays in place
+            if let Err(e) = self.store.replace(previous) {
+                warn!("Failed to put back the state replaced by a backup: {:#}", e);
             }
+            return Err(e);
         }
 
-        // Check schedule
-        if self.config.get().schedule.enabled {
-            return self.schedule.is_blocking_time();
+        let config = self.config.get();
+        self.schedule.update(config.schedule.clone());
+        self.schedule.set_vacations(self.store.get().vacations.clone());
+        self.quiz.update_config(config.quiz.clone());
+        self.quiz.set_locale(config.locale);
+        self.groups = Arc::new(GroupBudgets::new(&config.blocking.groups));
+        self.allowances = Arc::new(DomainAllowances::new(&config.blocking.allowances));
+        self.clients.replace(&config.clients);
+        self.rebuild_blocker();
+
+        info!(created_at, "Backup restored");
+        Ok(())
+    }
+
+    /// Query counters and today's estimated usage per site.
+    pub fn usage_stats(&self) -> UsageStats {
+        let date = today();
+        let domains = if self.stats.usage_date == date {
+            self.stats.usage.usage()
+        } else {
+            Vec::new()
+        };
+        let blocker = self.blocker.load();
+
+        UsageStats {
+            date,
+            queries_blocked: self.stats.queries.blocked.load(Ordering::Relaxed),
+            queries_forwarded: self.stats.queries.forwarded.load(Ordering::Relaxed),
+            domains,
+            blocklist_entries: blocker.blocked_count() as u64,
+            blocker_memory_bytes: blocker.memory_bytes() as u64,
         }
+    }
+
+    /// Periods the schedule will block in over the next `TIMELINE_DAYS` days,
+    /// following a deferred schedule edit from when it applies.
+    pub fn schedule_timeline(&self) -> Vec<BlockingWindow> {
+        let now = chrono::Local::now();
+        let until = now + chrono::Duration::days(TIMELINE_DAYS);
+        let state = self.store.get();
+        let Some(pending) = state.pending_schedule.clone() else {
+            return self.schedule.timeline(now, until);
+        };
 
-        true
+        let applies_at = chrono::Local
+            .timestamp_opt(pending.applies_at, 0)
+            .single()
+            .unwrap_or(now)
+            .clamp(now, until);
+        let current = self.config.get().schedule.clone();
+        let mut next = ScheduleEngine::new(merge_temporary_rules(
+            &current,
+            pending.schedule,
+            now.timestamp(),
+        ));
+        next.set_vacations(state.vacations.clone());
+
+        let mut windows = self.schedule.timeline(now, applies_at);
+        windows.extend(next.timeline(applies_at, until));
+        windows.sort_by_key(|window| window.start);
+        windows
+    }
+
+    /// Daily allowances with today's estimated usage.
+    pub fn allowance_status(&self) -> Vec<AllowanceStatus> {
+        let date = today();
+        self.allowances
+            .entries()
+            .into_iter()
+            .map(|(domain, daily_minutes)| AllowanceStatus {
+                domain: domain.to_string(),
+                daily_minutes,
+                used_minutes: self.store.domain_minutes_used(domain, &date),
+            })
+            .collect()
+    }
+
+    /// Bypass minutes left today, or None if no daily budget is configured.
+    pub fn bypass_budget_remaining(&self) -> Option<u32> {
+        let budget = self.config.get().bypass.daily_budget_minutes?;
+        let used = self.store.bypass_minutes_used(&today());
+        Some(budget.saturating_sub(used))
+    }
+
+    /// Minutes of the daily budget a new bypass may use: what is left plus
+    /// the unused minutes of a running bypass, which the new one replaces.
+    pub fn bypass_budget_available(&self) -> Option<u32> {
+        let now = chrono::Utc::now().timestamp();
+        let remaining = self.bypass_budget_remaining()?;
+        Some(remaining.saturating_add(self.unused_bypass_minutes(now)))
+    }
+
+    /// Whole minutes left of the current bypass, counting from its start if
+    /// it is still counting down.
+    fn unused_bypass_minutes(&self, now: i64) -> u32 {
+        let starts_at = self.bypass.starts_at.unwrap_or(now);
+        self.bypass.until
+            .map_or(0, |until| ((until - now.max(starts_at)).max(0) / 60) as u32)
+    }
+
+    /// End the current bypass, if any, refunding its unused minutes to the
+    /// daily budget.
+    fn end_bypass(&mut self, now: i64) {
+        let unused_minutes = self.unused_bypass_minutes(now);
+        if unused_minutes > 0 {
+            if let Err(e) = self.store.refund_bypass_minutes(&today(), unused_minutes) {
+                warn!("Failed to refund bypass budget: {:#}", e);
+            }
+        }
+        self.bypass.starts_at = None;
+        self.bypass.until = None;
+        self.bypass.excluded.clear();
+        self.bypass.excluded_until = 0;
+        self.publish_policy();
+
+        if let Err(e) = self.store.record_bypass_cancelled(now) {
+            warn!("Failed to record bypass cancellation: {:#}", e);
+        }
     }
 
     /// Activate a bypass for the given duration.
-    pub fn activate_bypass(&mut self, duration_minutes: u32) {
+    ///
+    /// With soft-start enabled the bypass only takes effect after a countdown,
+    /// and the most-blocked domains stay blocked for its first minutes. A
+    /// running bypass is replaced, with its unused minutes refunded.
+    pub fn activate_bypass(&mut self, duration_minutes: u32, reason: Option<String>) {
         let now = chrono::Utc::now().timestamp();
-        self.bypass_until = Some(now + (duration_minutes as i64 * 60));
+        let bypass_config = self.config.get().bypass.clone();
+        if self.bypass.until.is_some_and(|until| now < until) {
+            self.end_bypass(now);
+        }
+
+        let starts_at = if bypass_config.soft_start {
+            let starts_at = now + bypass_config.soft_start_countdown.as_secs() as i64;
+            self.bypass.excluded = self.stats.top_blocked(bypass_config.soft_start_top_domains);
+            self.bypass.excluded_until =
+                starts_at + bypass_config.soft_start_period.as_secs() as i64;
+            info!(
+                countdown = %bypass_config.soft_start_countdown,
+                excluded = ?self.bypass.excluded,
+                "Soft-start bypass scheduled"
+            );
+            starts_at
+        } else {
+            self.bypass.excluded.clear();
+            self.bypass.excluded_until = 0;
+            now
+        };
+
+        self.bypass.starts_at = Some(starts_at);
+        self.bypass.until = Some(starts_at + (duration_minutes as i64 * 60));
+        self.publish_policy();
+
+        if let Err(e) = self.store.charge_bypass_minutes(&today(), duration_minutes) {
+            warn!("Failed to record bypass budget usage: {:#}", e);
+        }
+
+        let record = BypassRecord {
+            started_at: now,
+            duration_minutes,
+            reason,
+            cancelled_at: None,
+        };
+        if let Err(e) = self.store.record_bypass(record) {
+            warn!("Failed to record bypass history: {:#}", e);
+        }
+
         info!(duration_minutes, "Bypass activated");
     }
 
-    /// Cancel any active bypass.
+    /// Cancel any active bypass, refunding unused minutes to the daily budget.
     pub fn cancel_bypass(&mut self) {
-        self.bypass_until = None;
+        self.end_bypass(chrono::Utc::now().timestamp());
         info!("Bypass cancelled");
     }
 }
 
+/// Today's local date as YYYY-MM-DD (the bypass budget resets at local midnight).
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// Monday of the current local week as YYYY-MM-DD (group budgets reset weekly).
+fn week_start() -> String {
+    use chrono::Datelike;
+
+    let today = chrono::Local::now().date_naive();
+    let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+    monday.format("%Y-%m-%d").to_string()
+}
+
+/// Check a config file (the installed one by default), printing any problems.
+/// Returns the process exit code.
+fn check_config_file(path: Option<&str>) -> i32 {
+    let is_dev = std::env::var("BLOCKANDFOCUS_DEV").is_ok();
+    let path = path.unwrap_or(if is_dev { CONFIG_PATH_DEV } else { CONFIG_PATH });
+
+    match ConfigManager::check_file(path) {
+        Ok(problems) if problems.is_empty() => {
+            println!("{}: OK", path);
+            0
+        }
+        Ok(problems) => {
+            for problem in problems {
+                eprintln!("{}: {}", path, problem);
+            }
+            1
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            1
+        }
+    }
+}
+
+/// Send synthetic queries to a running daemon and print how it kept up.
+/// Returns the process exit code: 1 if any query went unanswered.
+async fn selftest_load(args: &[String]) -> i32 {
+    let is_dev = std::env::var("BLOCKANDFOCUS_DEV").is_ok();
+    let options = match diagnostics::LoadOptions::from_args(args, is_dev) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            eprintln!(
+                "Usage: blockandfocus-daemon --selftest-load \
+                 [--target ADDR] [--rate QPS] [--duration 10s] [--name NAME]..."
+            );
+            return 2;
+        }
+    };
+
+    println!(
+        "Sending {} queries/s to {} for {}s...",
+        options.rate,
+        options.target,
+        options.duration.as_secs()
+    );
+    match diagnostics::run_load_test(&options).await {
+        Ok(report) => {
+            println!("{}", report);
+            i32::from(report.dropped() > 0)
+        }
+        Err(e) => {
+            eprintln!("{:#}", e);
+            1
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    // `--check-config [path]` checks a config file and exits, so a config can be
+    // fixed while the daemon is stopped (or can't start)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--check-config") {
+        std::process::exit(check_config_file(args.get(1).map(String::as_str)));
+    }
+
+    // `--selftest-load [options]` measures a running daemon under load and exits
+    if args.first().map(String::as_str) == Some("--selftest-load") {
+        std::process::exit(selftest_load(&args[1..]).await);
+    }
+
     // Initialize logging
-    let subscriber = FmtSubscriber::builder()
+    FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
@@ -102,6 +927,12 @@ async fn main() -> Result<()> {
         )
         .init();
 
+    // `--partner-relay [addr]` pairs partner daemons instead of filtering DNS
+    if args.first().map(String::as_str) == Some("--partner-relay") {
+        let addr = args.get(1).map_or(PARTNER_RELAY_ADDR, String::as_str);
+        return partner::run_relay(addr).await;
+    }
+
     info!("BlockAndFocus daemon starting...");
 
     // Check if running in development mode
@@ -114,40 +945,139 @@ async fn main() -> Result<()> {
     let config = ConfigManager::load(is_dev)?;
     info!("Configuration loaded");
 
+    // Load persisted runtime state
+    let store = StateStore::load(is_dev, config.get().storage.encrypt);
+
+    // Undo config edits made while the daemon was down that weaken locked settings
+    let now = chrono::Utc::now().timestamp();
+    if let Some(lock) = store.settings_lock(now) {
+        let mut locked = (*config.get()).clone();
+        if lock.restore(&mut locked, now) {
+            warn!("Configuration weakens locked settings, restoring them");
+            if let Err(e) = config.update(|c| *c = locked).await {
+                warn!("Failed to restore locked settings: {:#}", e);
+            }
+        }
+    }
+
     // Create shared application state
-    let state = Arc::new(RwLock::new(AppState::new(config)));
+    let state = Arc::new(RwLock::new(AppState::new(config, store)));
+
+    // Start with the DNS cache saved by the last maintenance run
+    load_dns_cache(&*state.read().await, is_dev);
 
-    // Start DNS server
+    // Start DNS server (restarted with backoff if it fails)
     let dns_state = state.clone();
-    let dns_handle = tokio::spawn(async move {
-        if let Err(e) = DnsServer::run(dns_state).await {
-            tracing::error!("DNS server error: {}", e);
-        }
-    });
+    let dns_handle = tokio::spawn(supervise("DNS server", RestartPolicy::default(), move || {
+        DnsServer::run(dns_state.clone(), is_dev)
+    }));
 
-    // Start IPC server
+    // Start IPC server (restarted with backoff if it fails)
     let ipc_state = state.clone();
-    let ipc_handle = tokio::spawn(async move {
-        if let Err(e) = IpcServer::run(ipc_state).await {
-            tracing::error!("IPC server error: {}", e);
-        }
-    });
+    let ipc_handle = tokio::spawn(supervise("IPC server", RestartPolicy::default(), move || {
+        IpcServer::run(ipc_state.clone())
+    }));
+
+    // Start idle/sleep monitor
+    tokio::spawn(PowerMonitor::run(state.clone()));
+
+    // Start keeping timers on real time when the system clock is changed
+    tokio::spawn(ClockMonitor::run(state.clone()));
+
+    // Start encrypted DNS detection
+    tokio::spawn(EncryptedDnsMonitor::run(state.clone()));
+
+    // Start DNS listener watchdog
+    tokio::spawn(DnsWatchdog::run(state.clone()));
+
+    // Start background update checks
+    tokio::spawn(UpdateMonitor::run(state.clone()));
+
+    // Start blocklist subscription refreshes
+    tokio::spawn(SubscriptionMonitor::run(state.clone(), is_dev));
+
+    // Start applying schedule edits deferred until midnight
+    tokio::spawn(PendingScheduleMonitor::run(state.clone()));
+
+    // Start the daily maintenance window
+    tokio::spawn(MaintenanceMonitor::run(state.clone(), is_dev));
+
+    // Start the link to a focus partner
+    tokio::spawn(PartnerLink::run(state.clone()));
+
+    // Start sending the weekly report
+    tokio::spawn(ReportMonitor::run(state.clone()));
+
+    // Start learning the MAC addresses of other devices for client profiles
+    tokio::spawn(NeighborMonitor::run(state.clone()));
+
+    // Start serving the page shown for blocked sites
+    tokio::spawn(BlockPage::run(state.clone()));
 
     info!("BlockAndFocus daemon started successfully");
 
-    // Wait for shutdown signal
+    // Wait for an authorized shutdown
     tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received shutdown signal");
-        }
-        _ = dns_handle => {
-            tracing::error!("DNS server stopped unexpectedly");
+        result = wait_for_shutdown(state.clone()) => result?,
+        result = dns_handle => {
+            let reason = result.unwrap_or_else(|e| format!("DNS supervisor failed: {}", e));
+            anyhow::bail!("Shutting down: {}", reason);
         }
-        _ = ipc_handle => {
-            tracing::error!("IPC server stopped unexpectedly");
+        result = ipc_handle => {
+            let reason = result.unwrap_or_else(|e| format!("IPC supervisor failed: {}", e));
+            anyhow::bail!("Shutting down: {}", reason);
         }
     }
 
     info!("BlockAndFocus daemon shutting down");
     Ok(())
 }
+
+/// Wait until the daemon is asked to stop and is willing to.
+///
+/// With `protection.quiz_for_shutdown`, termination signals are ignored during
+/// blocking hours; the Shutdown command with its quiz is the way out. A daemon
+/// killed outright is restarted by launchd, which only leaves it stopped after
+/// a successful exit.
+async fn wait_for_shutdown(state: Arc<RwLock<AppState>>) -> Result<()> {
+    let shutdown = state.read().await.shutdown.clone();
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    loop {
+        tokio::select! {
+            _ = shutdown.notified() => {
+                info!("Shutdown authorized");
+                return Ok(());
+            }
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+
+        if state.read().await.shutdown_guarded() {
+            warn!("Ignoring termination signal during blocking hours; use the Shutdown command");
+        } else {
+            info!("Received shutdown signal");
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_blocked() {
+        let mut stats = Stats::default();
+        stats.blocked_by_domain.insert("reddit.com".to_string(), 5);
+        stats.blocked_by_domain.insert("youtube.com".to_string(), 12);
+        stats.blocked_by_domain.insert("twitter.com".to_string(), 5);
+        stats.blocked_by_domain.insert("news.com".to_string(), 1);
+
+        assert_eq!(
+            stats.top_blocked(3),
+            vec!["youtube.com", "reddit.com", "twitter.com"]
+        );
+        assert!(Stats::default().top_blocked(3).is_empty());
+    }
+}
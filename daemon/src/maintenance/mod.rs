@@ -0,0 +1,5 @@
+//! Daily maintenance window for heavyweight housekeeping.
+
+mod monitor;
+
+pub use monitor::{dns_cache_path, load_dns_cache, remove_dns_cache, MaintenanceMonitor};
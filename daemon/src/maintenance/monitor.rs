@@ -0,0 +1,325 @@
+//! Runs housekeeping once a day in the configured window: refreshing
+//! subscriptions, rotating log files, compacting the state file and saving
+//! the DNS cache. None of it is urgent, so it waits for a quiet hour.
+//!
+//! The saved DNS cache lists recently visited sites, so it is kept like the
+//! state file: readable by root only and encrypted when storage encryption
+//! is on. It isn't kept at all when the query log hides domains.
+
+use crate::store::StorageCipher;
+use crate::subscriptions::refresh_subscriptions;
+use crate::AppState;
+use blockandfocus_shared::{
+    Config, LogPrivacy, MaintenanceConfig, DNS_CACHE_PATH, DNS_CACHE_PATH_DEV,
+};
+use chrono::{DateTime, Local, TimeZone};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Permissions of the saved DNS cache.
+const DNS_CACHE_MODE: u32 = 0o600;
+
+/// How often the window is checked for being open.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Log files launchd writes the installed daemon's output to.
+const LOG_FILES: [&str; 2] = [
+    "/Library/Logs/BlockAndFocus/daemon.log",
+    "/Library/Logs/BlockAndFocus/daemon-error.log",
+];
+
+/// Runs housekeeping when the maintenance window opens.
+pub struct MaintenanceMonitor;
+
+impl MaintenanceMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>, is_dev: bool) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let (config, last_run) = {
+                let state_guard = state.read().await;
+                let config = state_guard.config.get().maintenance.clone();
+                (config, state_guard.store.get().last_maintenance_at)
+            };
+            if config.enabled && is_due(&config, &Local::now(), last_run) {
+                run_maintenance(&state, &config, is_dev).await;
+            }
+        }
+    }
+}
+
+/// Whether `now` falls in a window (today's, or yesterday's if it runs past
+/// midnight) that maintenance hasn't run in yet.
+fn is_due<Tz: TimeZone>(
+    config: &MaintenanceConfig,
+    now: &DateTime<Tz>,
+    last_run: Option<i64>,
+) -> bool {
+    let length = chrono::Duration::minutes(i64::from(config.window_minutes.max(1)));
+    let today = now.date_naive();
+    [today.pred_opt(), Some(today)].into_iter().flatten().any(|date| {
+        // A window starting in a daylight saving gap is skipped that day
+        let Some(opens) = now.timezone().from_local_datetime(&date.and_time(config.at.0)).earliest()
+        else {
+            return false;
+        };
+        let ran = last_run.is_some_and(|at| at >= opens.timestamp());
+        *now >= opens && *now < opens.clone() + length && !ran
+    })
+}
+
+async fn run_maintenance(state: &Arc<RwLock<AppState>>, config: &MaintenanceConfig, is_dev: bool) {
+    info!("Maintenance window open, starting housekeeping");
+    refresh_subscriptions(state, is_dev).await;
+
+    // Development builds log to the terminal
+    if !is_dev {
+        let max_bytes = u64::from(config.max_log_mb.max(1)) * 1024 * 1024;
+        for path in LOG_FILES {
+            match rotate_log(Path::new(path), max_bytes) {
+                Ok(true) => info!(path, "Rotated log file"),
+                Ok(false) => {}
+                Err(e) => warn!(path, "Failed to rotate log file: {}", e),
+            }
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    {
+        let mut state_guard = state.write().await;
+        let subscribed: Vec<String> = state_guard
+            .config
+            .get()
+            .subscriptions
+            .lists
+            .iter()
+            .map(|list| list.url.clone())
+            .collect();
+        match state_guard.store.compact(Local::now().date_naive(), &subscribed) {
+            Ok(0) => {}
+            Ok(dropped) => info!(dropped, "Compacted state file"),
+            Err(e) => warn!("Failed to compact state file: {:#}", e),
+        }
+        if let Err(e) = state_guard.store.record_maintenance(now) {
+            warn!("Failed to record maintenance run: {:#}", e);
+        }
+    }
+
+    save_dns_cache(&*state.read().await, is_dev, now);
+    info!("Maintenance finished");
+}
+
+/// Where the DNS cache is saved.
+pub fn dns_cache_path(is_dev: bool) -> &'static str {
+    if is_dev {
+        DNS_CACHE_PATH_DEV
+    } else {
+        DNS_CACHE_PATH
+    }
+}
+
+/// Whether the DNS cache may be saved: not when the query log is set to
+/// hide the domains visited.
+fn keeps_dns_cache(config: &Config) -> bool {
+    config.query_log.privacy == LogPrivacy::Full
+}
+
+fn save_dns_cache(state: &AppState, is_dev: bool, now: i64) {
+    let path = dns_cache_path(is_dev);
+    let config = state.config.get();
+    if !keeps_dns_cache(&config) {
+        remove_dns_cache(path);
+        return;
+    }
+
+    let Ok(saved) = state.cache.lock().map(|cache| cache.export(now)) else {
+        return;
+    };
+    let saved = if config.storage.encrypt {
+        let Some(cipher) = state.store.cipher() else {
+            warn!("Not saving the DNS cache, the storage key is unavailable");
+            return;
+        };
+        match cipher.encrypt(&saved) {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                warn!("Failed to encrypt DNS cache: {:#}", e);
+                return;
+            }
+        }
+    } else {
+        saved
+    };
+
+    match write_atomically(Path::new(path), &saved) {
+        Ok(()) => info!(bytes = saved.len(), "Saved DNS cache"),
+        Err(e) => warn!("Failed to save DNS cache to {}: {}", path, e),
+    }
+}
+
+/// Delete the saved DNS cache, if there is one.
+pub fn remove_dns_cache(path: &str) {
+    match fs::remove_file(path) {
+        Ok(()) => info!("Deleted saved DNS cache"),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to delete saved DNS cache {}: {}", path, e),
+    }
+}
+
+/// Fill the cache with the entries saved by the last maintenance run that
+/// haven't expired since.
+pub fn load_dns_cache(state: &AppState, is_dev: bool) {
+    let path = dns_cache_path(is_dev);
+    let Ok(saved) = fs::read(path) else {
+        return;
+    };
+    if !keeps_dns_cache(&state.config.get()) {
+        remove_dns_cache(path);
+        return;
+    }
+
+    let saved = if StorageCipher::is_encrypted(&saved) {
+        match state.store.cipher().map(|cipher| cipher.decrypt(&saved)) {
+            Some(Ok(plaintext)) => plaintext,
+            Some(Err(e)) => {
+                warn!("Failed to decrypt saved DNS cache: {:#}", e);
+                return;
+            }
+            None => {
+                warn!("Saved DNS cache is encrypted but the storage key is unavailable");
+                return;
+            }
+        }
+    } else {
+        saved
+    };
+    if let Ok(mut cache) = state.cache.lock() {
+        let loaded = cache.import(&saved, chrono::Utc::now().timestamp());
+        info!(entries = loaded, "Loaded saved DNS cache");
+    }
+}
+
+/// Copy a log file over `<path>.1` and empty it, if it has grown past
+/// `max_bytes`. launchd keeps the file open, so it is truncated in place
+/// rather than renamed. Returns whether it was rotated.
+fn rotate_log(path: &Path, max_bytes: u64) -> std::io::Result<bool> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if len <= max_bytes {
+        return Ok(false);
+    }
+
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    fs::copy(path, &rotated)?;
+    fs::OpenOptions::new().write(true).open(path)?.set_len(0)?;
+    Ok(true)
+}
+
+/// Write through a temporary file so a crash never leaves a truncated file.
+fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(DNS_CACHE_MODE)
+        .open(&tmp)?;
+    file.set_permissions(fs::Permissions::from_mode(DNS_CACHE_MODE))?;
+    file.write_all(data)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::NaiveTimeWrapper;
+    use chrono::{NaiveTime, Utc};
+
+    fn config(at: &str, window_minutes: u32) -> MaintenanceConfig {
+        MaintenanceConfig {
+            enabled: true,
+            at: NaiveTimeWrapper(NaiveTime::parse_from_str(at, "%H:%M").unwrap()),
+            window_minutes,
+            ..MaintenanceConfig::default()
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        format!("{}Z", time).parse().unwrap()
+    }
+
+    #[test]
+    fn test_is_due() {
+        let early = config("04:00", 120);
+        assert!(!is_due(&early, &at("2024-03-13T03:59:00"), None));
+        assert!(is_due(&early, &at("2024-03-13T04:00:00"), None));
+        assert!(is_due(&early, &at("2024-03-13T05:59:00"), None));
+        assert!(!is_due(&early, &at("2024-03-13T06:00:00"), None));
+
+        // Once per window
+        let ran = Some(at("2024-03-13T04:01:00").timestamp());
+        assert!(!is_due(&early, &at("2024-03-13T04:30:00"), ran));
+        assert!(is_due(&early, &at("2024-03-14T04:30:00"), ran));
+
+        // A window running past midnight
+        let late = config("23:30", 120);
+        assert!(is_due(&late, &at("2024-03-14T00:30:00"), ran));
+        let ran_late = Some(at("2024-03-13T23:45:00").timestamp());
+        assert!(!is_due(&late, &at("2024-03-14T00:30:00"), ran_late));
+    }
+
+    #[test]
+    fn test_rotate_log() {
+        let dir = std::env::temp_dir().join(format!("blockandfocus-logs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("daemon.log");
+
+        assert!(!rotate_log(&log, 10).unwrap());
+        fs::write(&log, "short\n").unwrap();
+        assert!(!rotate_log(&log, 10).unwrap());
+        fs::write(&log, "a line longer than ten bytes\n").unwrap();
+        assert!(rotate_log(&log, 10).unwrap());
+        assert_eq!(fs::metadata(&log).unwrap().len(), 0);
+        let rotated = fs::read_to_string(dir.join("daemon.log.1")).unwrap();
+        assert_eq!(rotated, "a line longer than ten bytes\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomically_is_private() {
+        let dir = std::env::temp_dir().join(format!("blockandfocus-cache-{}", std::process::id()));
+        let path = dir.join("dns-cache.bin");
+
+        write_atomically(&path, b"saved").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"saved");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, DNS_CACHE_MODE);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_keeps_dns_cache() {
+        let mut config = Config::default();
+        assert!(keeps_dns_cache(&config));
+        config.query_log.privacy = LogPrivacy::Hashed;
+        assert!(!keeps_dns_cache(&config));
+    }
+}
@@ -0,0 +1,298 @@
+//! The link to a partner's daemon.
+//!
+//! While linked, each side tells the other when its focus session ends. A
+//! session announced by the partner that ends later than ours (or while we
+//! have none) becomes a temporary rule here too, so both end together.
+//! Sessions can only be started or extended this way, never cut short.
+
+use crate::schedule::focus_rule_until;
+use crate::AppState;
+use anyhow::{bail, Context, Result};
+use blockandfocus_shared::{Event, PartnerConfig, PartnerSession, PartnerStatus, Schedule};
+use chrono::TimeZone;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::protocol::{read_line, Identity, Join, PartnerMessage};
+
+/// How often the configuration is checked while partner sessions are off.
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wait before reconnecting after the link dropped.
+const RECONNECT_DELAY: Duration = Duration::from_secs(15);
+
+/// How often the local session is compared with what the partner was told.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often a Hello is sent to show the link is alive.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The link is dropped after this long without hearing from the partner.
+const PARTNER_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Keeps the link to the partner up while partner sessions are on.
+pub struct PartnerLink;
+
+impl PartnerLink {
+    /// Run the link loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let own_id = Uuid::new_v4().to_string();
+
+        loop {
+            let config = state.read().await.config.get().partner.clone();
+            if !is_configured(&config) {
+                let mut state_guard = state.write().await;
+                state_guard.partner = None;
+                drop(state_guard);
+                tokio::time::sleep(CONFIG_CHECK_INTERVAL).await;
+                continue;
+            }
+            update_status(&state, |status| status.connected = false).await;
+
+            match establish(&config).await {
+                Ok(stream) => {
+                    if let Err(e) = converse(&state, &config, &own_id, stream).await {
+                        warn!("Partner link dropped: {:#}", e);
+                    }
+                }
+                Err(e) => warn!("Could not reach focus partner: {:#}", e),
+            }
+            update_status(&state, |status| status.connected = false).await;
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+fn is_configured(config: &PartnerConfig) -> bool {
+    let has_address = config.connect.is_some() || config.listen.is_some();
+    config.enabled && !config.secret.is_empty() && has_address
+}
+
+/// Connect to the relay or partner, or wait for the partner to connect.
+async fn establish(config: &PartnerConfig) -> Result<TcpStream> {
+    if let Some(addr) = &config.connect {
+        return TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("Failed to connect to {}", addr));
+    }
+    let addr = config.listen.as_deref().unwrap_or_default();
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    info!("Waiting for focus partner on {}", addr);
+    Ok(listener.accept().await?.0)
+}
+
+/// Exchange messages until the connection drops or the configuration changes.
+async fn converse(
+    state: &Arc<RwLock<AppState>>,
+    config: &PartnerConfig,
+    own_id: &str,
+    stream: TcpStream,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let room = Join::for_secret(&config.secret);
+    let identity = Identity {
+        secret: config.secret.clone(),
+        id: own_id.to_string(),
+        name: config.name.clone(),
+    };
+    let send = |message| format!("{}\n", identity.seal(message, chrono::Utc::now().timestamp()));
+
+    let mut greeting = serde_json::to_string(&room)? + "\n";
+    greeting += &send(PartnerMessage::Hello);
+    writer.write_all(greeting.as_bytes()).await?;
+
+    let mut line = String::new();
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    let mut last_heard = Instant::now();
+    let mut last_hello = Instant::now();
+    let mut announced: Option<i64> = None;
+    let mut greeted = false;
+
+    loop {
+        tokio::select! {
+            open_line = read_line(&mut reader, &mut line) => {
+                if !open_line? {
+                    bail!("connection closed");
+                }
+                let now = chrono::Utc::now().timestamp();
+                if let Ok(join) = serde_json::from_str::<Join>(&line) {
+                    // A partner connecting directly sends its room first
+                    if join.join != room.join {
+                        bail!("partner uses a different secret");
+                    }
+                    continue;
+                }
+                match identity.open(line.trim(), now) {
+                    Ok(Some((name, message))) => {
+                        last_heard = Instant::now();
+                        if message == PartnerMessage::Hello && !greeted {
+                            // Lines sent before the partner joined a relay are lost:
+                            // say hello again and repeat our session
+                            greeted = true;
+                            announced = None;
+                            writer.write_all(send(PartnerMessage::Hello).as_bytes()).await?;
+                        }
+                        handle(state, name, message, now).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Ignoring message from focus partner: {:#}", e),
+                }
+            }
+            _ = poll.tick() => {
+                let now = chrono::Utc::now().timestamp();
+                if state.read().await.config.get().partner != *config {
+                    info!("Partner settings changed, reconnecting");
+                    return Ok(());
+                }
+                if last_heard.elapsed() >= PARTNER_TIMEOUT {
+                    bail!("no word from partner for {}s", PARTNER_TIMEOUT.as_secs());
+                }
+                expire_partner_session(state, now).await;
+
+                let mut out = String::new();
+                let ends_at = focus_ends_at(&state.read().await.config.get().schedule, now);
+                if let Some(ends_at) = ends_at.filter(|ends_at| announced != Some(*ends_at)) {
+                    out += &send(PartnerMessage::Session { ends_at });
+                    announced = Some(ends_at);
+                }
+                if last_hello.elapsed() >= HEARTBEAT_INTERVAL {
+                    out += &send(PartnerMessage::Hello);
+                    last_hello = Instant::now();
+                }
+                if !out.is_empty() {
+                    writer.write_all(out.as_bytes()).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Act on a message from the partner.
+async fn handle(state: &Arc<RwLock<AppState>>, partner: String, message: PartnerMessage, now: i64) {
+    let mut state_guard = state.write().await;
+    let status = state_guard.partner.get_or_insert_with(PartnerStatus::default);
+    if !status.connected {
+        info!(%partner, "Focus partner connected");
+    }
+    status.connected = true;
+    status.name = Some(partner.clone());
+
+    let PartnerMessage::Session { ends_at } = message else {
+        return;
+    };
+    if ends_at <= now {
+        return;
+    }
+    if status.session_ends_at.replace(ends_at) != Some(ends_at) {
+        state_guard.emit(Event::PartnerSession(PartnerSession {
+            partner: partner.clone(),
+            ends_at: Some(ends_at),
+        }));
+    }
+
+    let ours = focus_ends_at(&state_guard.config.get().schedule, now);
+    if ours.is_some_and(|ours| ours >= ends_at) {
+        return;
+    }
+    let (Some(start), Some(end)) = (
+        chrono::Local.timestamp_opt(now, 0).single(),
+        chrono::Local.timestamp_opt(ends_at, 0).single(),
+    ) else {
+        return;
+    };
+    let rule = focus_rule_until(format!("Focus with {}", partner), start, end);
+    match state_guard.add_focus_rule(rule).await {
+        Ok(()) => info!(%partner, ends_at, "Joined focus partner's session"),
+        Err(e) => warn!("Failed to join focus partner's session: {:#}", e),
+    }
+}
+
+/// Clear the partner's session once it has ended, telling subscribers.
+async fn expire_partner_session(state: &Arc<RwLock<AppState>>, now: i64) {
+    let ended = |status: &PartnerStatus| status.session_ends_at.is_some_and(|at| at <= now);
+    if !state.read().await.partner.as_ref().is_some_and(ended) {
+        return;
+    }
+    let mut state_guard = state.write().await;
+    let Some(status) = state_guard.partner.as_mut().filter(|status| ended(status)) else {
+        return;
+    };
+    status.session_ends_at = None;
+    let partner = status.name.clone().unwrap_or_else(|| "partner".to_string());
+    state_guard.emit(Event::PartnerSession(PartnerSession {
+        partner,
+        ends_at: None,
+    }));
+}
+
+async fn update_status(state: &Arc<RwLock<AppState>>, update: impl FnOnce(&mut PartnerStatus)) {
+    let mut state_guard = state.write().await;
+    update(state_guard.partner.get_or_insert_with(PartnerStatus::default));
+}
+
+/// When the running focus session ends: the latest end of the unexpired
+/// temporary rules.
+fn focus_ends_at(schedule: &Schedule, now: i64) -> Option<i64> {
+    schedule
+        .rules
+        .iter()
+        .filter_map(|rule| rule.expires_at)
+        .filter(|expires_at| *expires_at > now)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDaemon;
+
+    #[tokio::test]
+    async fn test_partners_share_sessions() {
+        let relay = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = relay.local_addr().unwrap().to_string();
+        drop(relay);
+        let relay_addr = addr.clone();
+        tokio::spawn(async move { super::super::run_relay(&relay_addr).await });
+
+        let partner = |name: &str| {
+            let addr = addr.clone();
+            let name = name.to_string();
+            move |config: &mut blockandfocus_shared::Config| {
+                config.partner = PartnerConfig {
+                    enabled: true,
+                    name,
+                    secret: "shared secret".to_string(),
+                    connect: Some(addr),
+                    listen: None,
+                };
+            }
+        };
+        let alice = TestDaemon::start(partner("Alice")).await;
+        let bob = TestDaemon::start(partner("Bob")).await;
+        tokio::spawn(PartnerLink::run(alice.state.clone()));
+        tokio::spawn(PartnerLink::run(bob.state.clone()));
+
+        let mut events = bob.state.read().await.events.subscribe();
+        alice.send(blockandfocus_shared::Command::FocusNow { minutes: 25 }).await;
+
+        let event = tokio::time::timeout(Duration::from_secs(20), events.recv()).await.unwrap();
+        let Ok(Event::PartnerSession(session)) = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(session.partner, "Alice");
+
+        // Bob's daemon now has the same session
+        let state_guard = bob.state.read().await;
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(focus_ends_at(&state_guard.config.get().schedule, now), session.ends_at);
+        assert!(state_guard.partner.as_ref().is_some_and(|status| status.connected));
+    }
+}
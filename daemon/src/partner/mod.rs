@@ -0,0 +1,8 @@
+//! Focus sessions shared with a partner's installation ("body doubling").
+
+mod link;
+mod protocol;
+mod relay;
+
+pub use link::PartnerLink;
+pub use relay::run_relay;
@@ -0,0 +1,154 @@
+//! Messages between partner daemons.
+//!
+//! Each side sends JSON lines: first a `Join` naming the room, then signed
+//! envelopes. The room is a hash of the shared secret, so a relay can pair the
+//! two sides without learning the secret, and can't forge or alter messages.
+//! Envelopes older than a few minutes are refused; replaying a recent one only
+//! repeats what it already said.
+
+use anyhow::{bail, Result};
+use blockandfocus_shared::mac_hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// Longest line either side accepts, in bytes.
+pub const MAX_LINE_BYTES: usize = 4096;
+
+/// How far an envelope's timestamp may be from the receiver's clock, in seconds.
+const MAX_CLOCK_SKEW: i64 = 300;
+
+/// First line of a connection, naming the room to join.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Join {
+    pub join: String,
+}
+
+impl Join {
+    /// The room partners sharing `secret` meet in.
+    pub fn for_secret(secret: &str) -> Self {
+        let digest = Sha256::digest(format!("blockandfocus-partner\n{}", secret));
+        Self {
+            join: digest.iter().take(16).map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// What one daemon tells the other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PartnerMessage {
+    /// Sent on connecting and repeated as a heartbeat
+    Hello,
+
+    /// The sender is in a focus session until `ends_at` (Unix timestamp)
+    Session { ends_at: i64 },
+}
+
+/// A signed message as sent on the wire.
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    message: PartnerMessage,
+    /// Random ID of the sending daemon, so a relay can't reflect messages back
+    sender: String,
+    /// Name the sender shows to its partner
+    name: String,
+    /// Unix timestamp of sending
+    sent_at: i64,
+    mac: String,
+}
+
+impl Envelope {
+    fn mac(&self, secret: &str) -> String {
+        let message = serde_json::to_string(&self.message).unwrap_or_default();
+        let signed = format!("{}\n{}\n{}\n{}", self.sender, self.name, self.sent_at, message);
+        mac_hex(secret.as_bytes(), signed.as_bytes())
+    }
+}
+
+/// The sending side of a link: who we are and the secret we sign with.
+pub struct Identity {
+    pub secret: String,
+    /// Random ID of this daemon
+    pub id: String,
+    pub name: String,
+}
+
+impl Identity {
+    /// Encode and sign `message` as one line (without the newline).
+    pub fn seal(&self, message: PartnerMessage, now: i64) -> String {
+        let mut envelope = Envelope {
+            message,
+            sender: self.id.clone(),
+            name: self.name.clone(),
+            sent_at: now,
+            mac: String::new(),
+        };
+        envelope.mac = envelope.mac(&self.secret);
+        serde_json::to_string(&envelope).unwrap_or_default()
+    }
+
+    /// Decode a line from the partner, checking its signature and age.
+    /// Returns the partner's name and message, or None for our own messages.
+    pub fn open(&self, line: &str, now: i64) -> Result<Option<(String, PartnerMessage)>> {
+        let envelope: Envelope = serde_json::from_str(line)?;
+        if !constant_time_eq(envelope.mac(&self.secret).as_bytes(), envelope.mac.as_bytes()) {
+            bail!("message signature doesn't match; is the secret the same on both sides?");
+        }
+        if (now - envelope.sent_at).abs() > MAX_CLOCK_SKEW {
+            bail!("message sent at {} is too far from the local clock", envelope.sent_at);
+        }
+        Ok((envelope.sender != self.id).then_some((envelope.name, envelope.message)))
+    }
+}
+
+/// Read one line into `line`. Returns false once the connection is closed.
+pub async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut String) -> Result<bool> {
+    line.clear();
+    let read = reader.take(MAX_LINE_BYTES as u64 + 1).read_line(line).await?;
+    if read > MAX_LINE_BYTES {
+        bail!("line exceeds {} bytes", MAX_LINE_BYTES);
+    }
+    Ok(read > 0)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(secret: &str, id: &str) -> Identity {
+        Identity {
+            secret: secret.to_string(),
+            id: id.to_string(),
+            name: id.to_uppercase(),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let message = PartnerMessage::Session { ends_at: 1_700_003_600 };
+        let line = identity("secret", "a").seal(message.clone(), 1_700_000_000);
+
+        let opened = identity("secret", "b").open(&line, 1_700_000_010).unwrap();
+        assert_eq!(opened, Some(("A".to_string(), message)));
+        // Our own message, reflected back
+        assert_eq!(identity("secret", "a").open(&line, 1_700_000_010).unwrap(), None);
+        assert!(identity("other", "b").open(&line, 1_700_000_010).is_err());
+        assert!(identity("secret", "b").open(&line, 1_700_001_000).is_err());
+
+        let forged = line.replace("1700003600", "1700007200");
+        assert!(identity("secret", "b").open(&forged, 1_700_000_010).is_err());
+    }
+
+    #[test]
+    fn test_rooms() {
+        let room = Join::for_secret("secret").join;
+        assert_eq!(room.len(), 32);
+        assert_eq!(room, Join::for_secret("secret").join);
+        assert_ne!(room, Join::for_secret("other").join);
+    }
+}
@@ -0,0 +1,112 @@
+//! A small relay pairing partner daemons that can't reach each other
+//! directly, run with `blockandfocus-daemon --partner-relay [ADDR]`.
+//!
+//! Each connection joins a room; lines are passed on to the other member of
+//! the room as they are. The relay can't read the secret or forge messages.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info};
+
+use super::protocol::{read_line, Join};
+
+/// Members of a room: the two partners.
+const ROOM_SIZE: usize = 2;
+
+/// Lines queued for a slow member before newer ones are dropped.
+const QUEUE_LENGTH: usize = 16;
+
+/// Connections silent for this long are closed (partners send a heartbeat).
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Members of each room, by connection ID.
+type Rooms = Arc<Mutex<HashMap<String, Vec<(u64, mpsc::Sender<String>)>>>>;
+
+/// Accept partner connections on `addr` and pair them by room, forever.
+pub async fn run_relay(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    info!("Partner relay listening on {}", listener.local_addr()?);
+
+    let rooms = Rooms::default();
+    let next_id = AtomicU64::new(0);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let id = next_id.fetch_add(1, Ordering::Relaxed);
+        let rooms = rooms.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, id, &rooms).await {
+                debug!(%peer, "Partner relay connection closed: {:#}", e);
+            }
+        });
+    }
+}
+
+async fn serve(stream: TcpStream, id: u64, rooms: &Rooms) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+
+    if !tokio::time::timeout(IDLE_TIMEOUT, read_line(&mut reader, &mut line)).await?? {
+        return Ok(());
+    }
+    let room = serde_json::from_str::<Join>(&line).context("expected a join line")?.join;
+
+    let (sender, mut queue) = mpsc::channel::<String>(QUEUE_LENGTH);
+    {
+        let mut rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+        let members = rooms.entry(room.clone()).or_default();
+        if members.len() >= ROOM_SIZE {
+            bail!("room is full");
+        }
+        members.push((id, sender));
+    }
+    tokio::spawn(async move {
+        while let Some(line) = queue.recv().await {
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = forward(&mut reader, id, &room, rooms).await;
+
+    let mut rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(members) = rooms.get_mut(&room) {
+        members.retain(|(member, _)| *member != id);
+        if members.is_empty() {
+            rooms.remove(&room);
+        }
+    }
+    result
+}
+
+/// Pass each line from one member on to the others in its room.
+async fn forward(
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    id: u64,
+    room: &str,
+    rooms: &Rooms,
+) -> Result<()> {
+    let mut line = String::new();
+    while tokio::time::timeout(IDLE_TIMEOUT, read_line(reader, &mut line)).await?? {
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        let rooms = rooms.lock().unwrap_or_else(|e| e.into_inner());
+        for (member, sender) in rooms.get(room).into_iter().flatten() {
+            if *member != id {
+                // A member that can't keep up misses lines rather than slowing the other
+                let _ = sender.try_send(line.clone());
+            }
+        }
+    }
+    Ok(())
+}
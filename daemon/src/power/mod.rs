@@ -0,0 +1,5 @@
+//! System idle and sleep detection.
+
+mod monitor;
+
+pub use monitor::PowerMonitor;
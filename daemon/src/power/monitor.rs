@@ -0,0 +1,115 @@
+//! Periodically samples the system idle time and detects sleep.
+//!
+//! The daemon runs as a LaunchDaemon without a window server connection, so
+//! idle time is read from the HID system via `ioreg`, and sleep is detected
+//! as a jump between two samples of a clock that keeps counting during sleep
+//! (the wall clock would also jump when it is changed by hand).
+
+use crate::clock::boot_seconds;
+use crate::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+/// How often the idle time is sampled.
+const SAMPLE_INTERVAL_SECONDS: i64 = 30;
+
+/// Extra time between samples that counts as the machine having slept.
+const SLEEP_DETECTION_SLACK_SECONDS: i64 = 60;
+
+/// Tracks system idle and sleep state for usage tracking and focus timers.
+pub struct PowerMonitor;
+
+impl PowerMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(SAMPLE_INTERVAL_SECONDS as u64));
+        let mut last_sample = boot_seconds();
+
+        loop {
+            interval.tick().await;
+
+            let now = boot_seconds();
+            let slept_seconds = slept_seconds(last_sample, now);
+            last_sample = now;
+
+            let idle_seconds = system_idle_seconds().await;
+
+            let mut state_guard = state.write().await;
+            let threshold = state_guard.config.get().usage.idle_threshold_seconds as u64;
+            let idle = idle_seconds.is_some_and(|idle| idle >= threshold);
+
+            if idle != state_guard.system_idle {
+                debug!(idle, "System idle state changed");
+                state_guard.system_idle = idle;
+            }
+
+            if slept_seconds > 0 {
+                info!(slept_seconds, "System woke from sleep");
+                state_guard.on_system_wake(slept_seconds).await;
+            }
+        }
+    }
+}
+
+/// Seconds the machine was asleep between two samples (0 if it did not sleep).
+fn slept_seconds(last_sample: i64, now: i64) -> i64 {
+    let elapsed = now - last_sample;
+    if elapsed > SAMPLE_INTERVAL_SECONDS + SLEEP_DETECTION_SLACK_SECONDS {
+        elapsed - SAMPLE_INTERVAL_SECONDS
+    } else {
+        0
+    }
+}
+
+/// Seconds since the last keyboard or mouse input, if available.
+#[cfg(target_os = "macos")]
+async fn system_idle_seconds() -> Option<u64> {
+    let output = tokio::process::Command::new("/usr/sbin/ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .await
+        .ok()?;
+
+    parse_hid_idle_time(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Seconds since the last keyboard or mouse input, if available.
+#[cfg(not(target_os = "macos"))]
+async fn system_idle_seconds() -> Option<u64> {
+    None
+}
+
+/// Extract `HIDIdleTime` (nanoseconds) from `ioreg` output, in seconds.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_hid_idle_time(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let (_, value) = line.split_once("\"HIDIdleTime\" = ")?;
+        let nanoseconds: u64 = value.trim().parse().ok()?;
+        Some(nanoseconds / 1_000_000_000)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hid_idle_time() {
+        let output = r#"
+    | |   "HIDIdleTimeDelta" = 1000
+    | |   "HIDIdleTime" = 312456789012
+    | |   "HIDKeyboardModifierMappingPairs" = ()
+"#;
+        assert_eq!(parse_hid_idle_time(output), Some(312));
+        assert_eq!(parse_hid_idle_time("no idle time here"), None);
+    }
+
+    #[test]
+    fn test_slept_seconds() {
+        assert_eq!(slept_seconds(1000, 1030), 0);
+        assert_eq!(slept_seconds(1000, 1080), 0);
+        assert_eq!(slept_seconds(1000, 1000 + 3600), 3600 - SAMPLE_INTERVAL_SECONDS);
+    }
+}
@@ -0,0 +1,161 @@
+//! Query log file with size-based rotation.
+//!
+//! The file holds browsing history, so it is readable by root only. With
+//! storage encryption on, each line is an entry sealed with the storage key
+//! and base64 encoded instead of plain JSON.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blockandfocus_shared::QueryLogEntry;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::store::StorageCipher;
+
+/// Permissions of the log files.
+const FILE_MODE: u32 = 0o600;
+
+/// Appends entries as JSON lines, moving the file to `<path>.1` when it
+/// grows past its size limit.
+pub struct LogFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    max_bytes: u64,
+    /// Encrypts each entry when storage encryption is on
+    cipher: Option<Arc<StorageCipher>>,
+}
+
+impl LogFile {
+    /// Open (or create) the file for appending.
+    pub fn open(
+        path: &Path,
+        max_bytes: u64,
+        cipher: Option<Arc<StorageCipher>>,
+    ) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open_append(path)?;
+        // Files created before their mode was set may be readable by others
+        file.set_permissions(fs::Permissions::from_mode(FILE_MODE))?;
+        let len = file.metadata()?.len();
+        Ok(Self { path: path.to_path_buf(), file, len, max_bytes, cipher })
+    }
+
+    /// Append an entry, rotating first if it would not fit.
+    pub fn append(&mut self, entry: &QueryLogEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        if let Some(cipher) = &self.cipher {
+            let sealed = cipher.encrypt(&line).map_err(io::Error::other)?;
+            line = STANDARD.encode(sealed).into_bytes();
+        }
+        line.push(b'\n');
+
+        if self.len > 0 && self.len + line.len() as u64 > self.max_bytes {
+            fs::rename(&self.path, self.rotated_path())?;
+            self.file = open_append(&self.path)?;
+            self.len = 0;
+        }
+
+        self.file.write_all(&line)?;
+        self.len += line.len() as u64;
+        Ok(())
+    }
+
+    /// Empty the file and delete the rotated one.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        match fs::remove_file(self.rotated_path()) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut rotated = self.path.as_os_str().to_owned();
+        rotated.push(".1");
+        rotated.into()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).mode(FILE_MODE).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::QueryAction;
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("baf-querylog-{}", std::process::id()));
+        let path = dir.join("queries.log");
+        let entry = QueryLogEntry {
+            timestamp: 0,
+            domain: "a.com".to_string(),
+            record_type: "A".to_string(),
+            action: QueryAction::Forwarded,
+            client: "127.0.0.1".to_string(),
+            would_block: false,
+        };
+        let line_len = serde_json::to_vec(&entry).unwrap().len() as u64 + 1;
+
+        let mut file = LogFile::open(&path, line_len * 2, None).unwrap();
+        for _ in 0..3 {
+            file.append(&entry).unwrap();
+        }
+        let rotated = dir.join("queries.log.1");
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 2);
+        for file in [&path, &rotated] {
+            assert_eq!(fs::metadata(file).unwrap().permissions().mode() & 0o777, FILE_MODE);
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), line_len);
+
+        // Reopening continues where the file left off
+        let mut file = LogFile::open(&path, line_len * 2, None).unwrap();
+        file.append(&entry).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), line_len * 2);
+
+        file.clear().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        assert!(!rotated.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_encrypted_lines() {
+        let dir = std::env::temp_dir().join(format!("baf-querylog-enc-{}", std::process::id()));
+        let path = dir.join("queries.log");
+        let entry = QueryLogEntry {
+            timestamp: 0,
+            domain: "secret.example".to_string(),
+            record_type: "A".to_string(),
+            action: QueryAction::Forwarded,
+            client: "127.0.0.1".to_string(),
+            would_block: false,
+        };
+        let cipher = Arc::new(StorageCipher::new(&[7; 32]).unwrap());
+
+        let mut file = LogFile::open(&path, 1024 * 1024, Some(cipher.clone())).unwrap();
+        file.append(&entry).unwrap();
+        file.append(&entry).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret.example"));
+        for line in content.lines() {
+            let sealed = STANDARD.decode(line).unwrap();
+            let plaintext = cipher.decrypt(&sealed).unwrap();
+            let decoded: QueryLogEntry = serde_json::from_slice(&plaintext).unwrap();
+            assert_eq!(decoded.domain, "secret.example");
+        }
+        assert_eq!(content.lines().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+//! In-memory query log with live subscribers.
+//!
+//! Entries are only kept when query logging is enabled; live subscribers
+//! (e.g. TailQueryLog) receive entries either way, since nothing is stored
+//! for them. Stored entries are also appended to the log file, if one is
+//! set (encrypted when a storage cipher is given). Domains are reduced to the
+//! configured privacy level first.
+
+use blockandfocus_shared::{QueryLogConfig, QueryLogEntry, QueryLogFilter};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use super::file::LogFile;
+use super::privacy::DomainRedactor;
+use crate::store::StorageCipher;
+
+/// Number of entries buffered for slow subscribers before they miss some.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Ring buffer of recent queries plus a broadcast channel for live tails.
+pub struct QueryLog {
+    config: QueryLogConfig,
+    redactor: DomainRedactor,
+    entries: Mutex<VecDeque<QueryLogEntry>>,
+    file: Option<Mutex<LogFile>>,
+    sender: broadcast::Sender<QueryLogEntry>,
+}
+
+impl QueryLog {
+    /// Create a query log with the given settings, encrypting the log file
+    /// with `cipher` if one is given.
+    pub fn new(config: QueryLogConfig, cipher: Option<Arc<StorageCipher>>) -> Self {
+        let file = config.file.as_deref().filter(|_| config.enabled).and_then(|path| {
+            let max_bytes = u64::from(config.max_file_mb.max(1)) * 1024 * 1024;
+            match LogFile::open(Path::new(path), max_bytes, cipher.clone()) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(e) => {
+                    warn!(path, "Failed to open query log file: {}", e);
+                    None
+                }
+            }
+        });
+
+        Self {
+            redactor: DomainRedactor::new(config.privacy),
+            config,
+            entries: Mutex::new(VecDeque::new()),
+            file,
+            sender: broadcast::channel(SUBSCRIBER_CAPACITY).0,
+        }
+    }
+
+    /// Record a query (nothing is recorded with privacy set to off).
+    pub fn record(&self, mut entry: QueryLogEntry) {
+        let Some(domain) = self.redactor.redact(&entry.domain) else {
+            return;
+        };
+        entry.domain = domain;
+
+        if self.config.enabled && self.config.max_entries > 0 {
+            if let Ok(mut entries) = self.entries.lock() {
+                while entries.len() >= self.config.max_entries {
+                    entries.pop_front();
+                }
+                entries.push_back(entry.clone());
+            }
+            if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+                if let Err(e) = file.append(&entry) {
+                    warn!("Failed to write query log file: {}", e);
+                }
+            }
+        }
+
+        // No subscribers is not an error
+        let _ = self.sender.send(entry);
+    }
+
+    /// The most recent `limit` stored entries matching the filter, oldest first.
+    pub fn recent(&self, limit: usize, filter: &QueryLogFilter) -> Vec<QueryLogEntry> {
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+
+        let mut matching: Vec<QueryLogEntry> = entries
+            .iter()
+            .rev()
+            .filter(|e| filter.matches(e))
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+
+    /// Delete all stored entries, including the log files.
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            if let Err(e) = file.clear() {
+                warn!("Failed to clear query log file: {}", e);
+            }
+        }
+    }
+
+    /// The form of a domain to write in the daemon's log.
+    pub fn display_domain(&self, domain: &str) -> String {
+        self.redactor.display(domain)
+    }
+
+    /// Subscribe to entries as they are recorded.
+    pub fn subscribe(&self) -> broadcast::Receiver<QueryLogEntry> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::QueryAction;
+
+    fn entry(domain: &str, action: QueryAction) -> QueryLogEntry {
+        QueryLogEntry {
+            timestamp: 0,
+            domain: domain.to_string(),
+            record_type: "A".to_string(),
+            action,
+            client: "127.0.0.1".to_string(),
+            would_block: false,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let log = QueryLog::new(
+            QueryLogConfig {
+                enabled: true,
+                max_entries: 2,
+                ..QueryLogConfig::default()
+            },
+            None,
+        );
+        log.record(entry("a.com", QueryAction::Forwarded));
+        log.record(entry("b.com", QueryAction::Blocked));
+        log.record(entry("c.com", QueryAction::Cached));
+
+        let all = log.recent(10, &QueryLogFilter::default());
+        let domains: Vec<&str> = all.iter().map(|e| e.domain.as_str()).collect();
+        assert_eq!(domains, vec!["b.com", "c.com"]);
+
+        let blocked = log.recent(
+            10,
+            &QueryLogFilter {
+                action: Some(QueryAction::Blocked),
+                ..QueryLogFilter::default()
+            },
+        );
+        assert_eq!(blocked.len(), 1);
+        assert_eq!(blocked[0].domain, "b.com");
+    }
+
+    #[test]
+    fn test_disabled_log_still_streams() {
+        let log = QueryLog::new(QueryLogConfig::default(), None);
+        let mut tail = log.subscribe();
+
+        log.record(entry("a.com", QueryAction::Forwarded));
+
+        assert!(log.recent(10, &QueryLogFilter::default()).is_empty());
+        assert_eq!(tail.try_recv().unwrap().domain, "a.com");
+    }
+}
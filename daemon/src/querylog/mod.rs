@@ -0,0 +1,7 @@
+//! DNS query log.
+
+mod file;
+mod log;
+mod privacy;
+
+pub use log::QueryLog;
@@ -0,0 +1,75 @@
+//! Reduction of queried domains to the configured privacy level.
+//!
+//! Applied before a domain is stored, streamed or written to the daemon's log,
+//! so a privacy level short of `full` leaves no readable browsing history.
+
+use blockandfocus_shared::{site_key, LogPrivacy};
+use sha2::{Digest, Sha256};
+
+/// Hex characters of the hash kept for `hashed` (64 bits).
+const HASH_CHARS: usize = 16;
+
+/// Reduces domains according to a privacy level.
+pub struct DomainRedactor {
+    privacy: LogPrivacy,
+    /// Random per run, so hashes can't be looked up in a precomputed table
+    salt: [u8; 16],
+}
+
+impl DomainRedactor {
+    /// Create a redactor for the given privacy level.
+    pub fn new(privacy: LogPrivacy) -> Self {
+        Self {
+            privacy,
+            salt: rand::random(),
+        }
+    }
+
+    /// The form of `domain` that may be recorded, or None if nothing may be.
+    pub fn redact(&self, domain: &str) -> Option<String> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        match self.privacy {
+            LogPrivacy::Full => Some(domain),
+            LogPrivacy::RegistrableDomain => Some(site_key(&domain)),
+            LogPrivacy::Hashed => {
+                let digest = Sha256::new()
+                    .chain_update(self.salt)
+                    .chain_update(domain.as_bytes())
+                    .finalize();
+                let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+                Some(format!("#{}", &hex[..HASH_CHARS]))
+            }
+            LogPrivacy::Off => None,
+        }
+    }
+
+    /// The form of `domain` to write in the daemon's log.
+    pub fn display(&self, domain: &str) -> String {
+        self.redact(domain).unwrap_or_else(|| "[redacted]".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_privacy_levels() {
+        let full = DomainRedactor::new(LogPrivacy::Full);
+        assert_eq!(full.redact("www.YouTube.com.").as_deref(), Some("www.youtube.com"));
+
+        let site = DomainRedactor::new(LogPrivacy::RegistrableDomain);
+        assert_eq!(site.redact("news.bbc.co.uk.").as_deref(), Some("bbc.co.uk"));
+
+        let hashed = DomainRedactor::new(LogPrivacy::Hashed);
+        let hash = hashed.redact("youtube.com.").unwrap();
+        assert_eq!(hash.len(), HASH_CHARS + 1);
+        assert!(!hash.contains("youtube"));
+        assert_eq!(hashed.redact("YouTube.com").unwrap(), hash);
+        assert_ne!(hashed.redact("vimeo.com").unwrap(), hash);
+
+        let off = DomainRedactor::new(LogPrivacy::Off);
+        assert_eq!(off.redact("youtube.com"), None);
+        assert_eq!(off.display("youtube.com"), "[redacted]");
+    }
+}
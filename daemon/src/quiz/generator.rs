@@ -1,13 +1,22 @@
 //! Quiz generation and validation engine.
 
-use blockandfocus_shared::{QuizChallenge, QuizConfig};
+use super::memory;
+use crate::i18n::{tr, Msg};
+use blockandfocus_shared::{
+    AnswerKind, CustomQuestion, DomainName, Locale, QuizAnswer, QuizChallenge, QuizConfig, QuizKind,
+    QuizPhase, QuizPurpose, QuizStage, Schedule,
+};
 use chrono::Utc;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use std::collections::HashMap;
 use std::time::Instant;
 use tracing::{debug, warn};
 use uuid::Uuid;
 
+/// Bypass length guarded by challenges generated without a request.
+const DEFAULT_BYPASS_MINUTES: u32 = 15;
+
 /// Arithmetic operation for quiz questions.
 #[derive(Debug, Clone, Copy)]
 enum Operation {
@@ -16,24 +25,155 @@ enum Operation {
     Multiply,
 }
 
+/// Expected answer for a question.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExpectedAnswer {
+    /// Exact numeric answer
+    Number(i32),
+    /// Exact text answer (case-insensitive)
+    Text(String),
+    /// Any text answer of at least this many characters
+    Free(usize),
+}
+
+impl ExpectedAnswer {
+    fn kind(&self) -> AnswerKind {
+        match self {
+            ExpectedAnswer::Number(_) => AnswerKind::Number,
+            ExpectedAnswer::Text(_) | ExpectedAnswer::Free(_) => AnswerKind::Text,
+        }
+    }
+
+    /// Check whether the given answer satisfies this expectation.
+    fn accepts(&self, answer: &QuizAnswer) -> bool {
+        match (self, answer) {
+            (ExpectedAnswer::Number(expected), QuizAnswer::Number(n)) => expected == n,
+            (ExpectedAnswer::Number(expected), QuizAnswer::Text(text)) => {
+                text.trim().parse::<i32>().ok() == Some(*expected)
+            }
+            (ExpectedAnswer::Text(expected), QuizAnswer::Text(text)) => {
+                normalize_text(text) == normalize_text(expected)
+            }
+            (ExpectedAnswer::Text(expected), QuizAnswer::Number(n)) => {
+                normalize_text(expected) == n.to_string()
+            }
+            (ExpectedAnswer::Free(min_length), QuizAnswer::Text(text)) => {
+                text.trim().chars().count() >= *min_length
+            }
+            (ExpectedAnswer::Free(_), QuizAnswer::Number(_)) => false,
+        }
+    }
+}
+
 /// Internal question representation with answer.
 #[derive(Debug, Clone)]
 struct Question {
     display: String,
-    answer: i32,
+    answer: ExpectedAnswer,
+}
+
+impl From<&CustomQuestion> for Question {
+    fn from(custom: &CustomQuestion) -> Self {
+        let answer = match &custom.answer {
+            Some(answer) => ExpectedAnswer::Text(answer.clone()),
+            None => ExpectedAnswer::Free(custom.min_length),
+        };
+
+        Question {
+            display: custom.question.clone(),
+            answer,
+        }
+    }
+}
+
+/// What the user has to answer for a pending challenge.
+#[derive(Debug)]
+enum ChallengeBody {
+    /// Arithmetic questions with their answers
+    Arithmetic(Vec<Question>),
+    /// Sequence of words or digits to type back
+    Memory(Vec<String>),
+    /// Cool-down that must elapse (Unix timestamp)
+    Wait { until: i64 },
+    /// Sentence to type back exactly
+    Confirm(String),
 }
 
 /// Pending quiz challenge waiting for answers.
 #[derive(Debug)]
 struct PendingChallenge {
-    questions: Vec<Question>,
+    body: ChallengeBody,
+    phase: QuizPhase,
+    /// Start of the answer phase (used for the minimum solve time)
     created_at: Instant,
     expires_at: i64,
+    /// Friction pipeline this challenge goes through
+    stages: Vec<QuizStage>,
+    /// Index of the current stage
+    stage: usize,
+    /// The bypass request this challenge guards
+    request: BypassRequest,
+}
+
+/// What the user asked for when requesting a bypass.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BypassRequest {
+    /// Free-text reason given for the bypass
+    pub reason: Option<String>,
+    /// What passing the challenge unlocks
+    pub action: GatedAction,
+}
+
+/// An action that requires passing a challenge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatedAction {
+    /// Disable blocking for `minutes`
+    Bypass { minutes: u32 },
+    /// Delay the schedule rule activation at `activation` by `minutes`
+    SnoozeSchedule { activation: i64, minutes: u32 },
+    /// Stop the daemon during blocking hours
+    Shutdown,
+    /// Remove a domain from the blocklist while blocking is active
+    RemoveDomain { domain: DomainName },
+    /// Disable a blocklist group while blocking is active
+    DisableGroup { name: String },
+    /// Apply a schedule edit that blocks less, as submitted
+    UpdateSchedule { schedule: Schedule },
+}
+
+impl Default for GatedAction {
+    fn default() -> Self {
+        GatedAction::Bypass { minutes: DEFAULT_BYPASS_MINUTES }
+    }
+}
+
+impl GatedAction {
+    /// The purpose shown with challenges guarding this action.
+    pub fn purpose(&self) -> QuizPurpose {
+        match self {
+            GatedAction::Bypass { .. } => QuizPurpose::Bypass,
+            GatedAction::SnoozeSchedule { .. } => QuizPurpose::SnoozeSchedule,
+            GatedAction::Shutdown => QuizPurpose::Shutdown,
+            GatedAction::RemoveDomain { .. } => QuizPurpose::RemoveDomain,
+            GatedAction::DisableGroup { .. } => QuizPurpose::DisableGroup,
+            GatedAction::UpdateSchedule { .. } => QuizPurpose::UpdateSchedule,
+        }
+    }
+}
+
+/// Result of successfully answering a challenge stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuizOutcome {
+    /// All stages are completed; carries the original bypass request
+    Passed(BypassRequest),
+    /// The stage was passed; the next stage must be completed too
+    NextStage(QuizChallenge),
 }
 
 /// Quiz engine for generating and validating arithmetic challenges.
 pub struct QuizEngine {
     config: QuizConfig,
+    locale: Locale,
     pending: HashMap<String, PendingChallenge>,
 }
 
@@ -42,6 +182,7 @@ impl QuizEngine {
     pub fn new(config: QuizConfig) -> Self {
         Self {
             config,
+            locale: Locale::default(),
             pending: HashMap::new(),
         }
     }
@@ -51,53 +192,210 @@ impl QuizEngine {
         self.config = config;
     }
 
+    /// Set the language of generated instructions.
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
     /// Generate a new quiz challenge.
+    ///
+    /// The returned challenge is the first stage of the configured pipeline.
     pub fn generate_challenge(&mut self) -> QuizChallenge {
+        self.generate_challenge_for(BypassRequest::default())
+    }
+
+    /// Generate a new quiz challenge guarding the given bypass request.
+    pub fn generate_challenge_for(&mut self, request: BypassRequest) -> QuizChallenge {
         // Clean up expired challenges first
         self.cleanup_expired();
 
         let challenge_id = Uuid::new_v4().to_string();
-        let mut rng = rand::thread_rng();
-
-        let questions: Vec<Question> = (0..self.config.num_questions)
-            .map(|_| self.generate_question(&mut rng))
-            .collect();
-
-        let expires_at = Utc::now().timestamp() + self.config.timeout_seconds as i64;
-
-        let challenge = QuizChallenge {
-            challenge_id: challenge_id.clone(),
-            questions: questions.iter().map(|q| q.display.clone()).collect(),
-            expires_at,
+        let stages = if self.config.stages.is_empty() {
+            vec![QuizStage::Quiz]
+        } else {
+            self.config.stages.clone()
         };
 
-        // Store the pending challenge
-        self.pending.insert(
-            challenge_id,
-            PendingChallenge {
-                questions,
-                created_at: Instant::now(),
-                expires_at,
-            },
-        );
+        let (challenge, pending) = self.start_stage(&challenge_id, stages, 0, request);
+        self.pending.insert(challenge_id, pending);
 
         debug!(
-            num_questions = self.config.num_questions,
-            expires_in = self.config.timeout_seconds,
+            kind = ?challenge.kind,
+            total_stages = challenge.total_stages,
+            expires_in = %self.config.timeout,
             "Generated quiz challenge"
         );
 
         challenge
     }
 
-    /// Validate quiz answers.
+    /// Build the challenge for one stage of a pipeline.
+    fn start_stage(
+        &self,
+        challenge_id: &str,
+        stages: Vec<QuizStage>,
+        stage: usize,
+        request: BypassRequest,
+    ) -> (QuizChallenge, PendingChallenge) {
+        let mut rng = rand::thread_rng();
+        let now = Utc::now().timestamp();
+        let timeout = self.config.timeout.as_secs() as i64;
+
+        let mut challenge = QuizChallenge {
+            challenge_id: challenge_id.to_string(),
+            questions: Vec::new(),
+            expires_at: now + timeout,
+            kind: QuizKind::Arithmetic,
+            phase: QuizPhase::Answer,
+            reveal: Vec::new(),
+            reveal_seconds: 0,
+            answer_kinds: Vec::new(),
+            stage: stage as u32 + 1,
+            total_stages: stages.len() as u32,
+            wait_until: None,
+            last_reason: None,
+            purpose: request.action.purpose(),
+        };
+
+        let body = match &stages[stage] {
+            QuizStage::Quiz => match self.config.kind {
+                QuizKind::Memory => {
+                    let sequence = memory::generate_sequence(
+                        &mut rng,
+                        self.config.memory_length,
+                        self.config.memory_words,
+                    );
+
+                    // The reveal phase does not eat into the answering time
+                    challenge.expires_at += self.config.memory_reveal.as_secs() as i64;
+                    challenge.questions =
+                        vec![tr(self.locale, Msg::QuizMemorize, &[&sequence.len()])];
+                    challenge.kind = QuizKind::Memory;
+                    challenge.phase = QuizPhase::Reveal;
+                    challenge.reveal = sequence.clone();
+                    challenge.reveal_seconds = self.config.memory_reveal.as_secs_u32();
+                    challenge.answer_kinds = vec![AnswerKind::Text];
+
+                    ChallengeBody::Memory(sequence)
+                }
+                _ => {
+                    let mut questions: Vec<Question> = (0..self.config.num_questions)
+                        .map(|_| self.generate_question(&mut rng))
+                        .collect();
+
+                    // Mix in user-written questions at random positions
+                    let custom: Vec<&CustomQuestion> = self
+                        .config
+                        .custom_questions
+                        .choose_multiple(&mut rng, self.config.custom_questions_per_quiz as usize)
+                        .collect();
+                    for question in custom {
+                        let pos = rng.gen_range(0..=questions.len());
+                        questions.insert(pos, question.into());
+                    }
+
+                    challenge.questions = questions.iter().map(|q| q.display.clone()).collect();
+                    challenge.answer_kinds = questions.iter().map(|q| q.answer.kind()).collect();
+
+                    ChallengeBody::Arithmetic(questions)
+                }
+            },
+            QuizStage::Wait { seconds } => {
+                let until = now + *seconds as i64;
+                challenge.expires_at = until + timeout;
+                challenge.questions = vec![tr(self.locale, Msg::QuizWait, &[seconds])];
+                challenge.kind = QuizKind::Wait;
+                challenge.wait_until = Some(until);
+
+                ChallengeBody::Wait { until }
+            }
+            QuizStage::Confirm { sentence } => {
+                challenge.questions = vec![tr(self.locale, Msg::QuizConfirm, &[sentence])];
+                challenge.kind = QuizKind::Confirm;
+                challenge.answer_kinds = vec![AnswerKind::Text];
+
+                ChallengeBody::Confirm(sentence.clone())
+            }
+        };
+
+        let pending = PendingChallenge {
+            body,
+            phase: challenge.phase,
+            created_at: Instant::now(),
+            expires_at: challenge.expires_at,
+            stages,
+            stage,
+            request,
+        };
+
+        (challenge, pending)
+    }
+
+    /// End the reveal phase of a memory challenge.
     ///
-    /// Returns Ok(()) if all answers are correct, Err with reason otherwise.
+    /// Returns the challenge as it should be shown while answering (without
+    /// the items to memorize). The minimum solve time starts counting now.
+    pub fn begin_answer(&mut self, challenge_id: &str) -> Result<QuizChallenge, QuizError> {
+        let challenge = self
+            .pending
+            .get_mut(challenge_id)
+            .ok_or(QuizError::NotFound)?;
+
+        if Utc::now().timestamp() > challenge.expires_at {
+            self.pending.remove(challenge_id);
+            return Err(QuizError::Expired);
+        }
+
+        let len = match (&challenge.body, challenge.phase) {
+            (ChallengeBody::Memory(sequence), QuizPhase::Reveal) => sequence.len(),
+            _ => return Err(QuizError::WrongPhase),
+        };
+
+        challenge.phase = QuizPhase::Answer;
+        challenge.created_at = Instant::now();
+
+        debug!(challenge_id, "Memory challenge moved to answer phase");
+
+        Ok(QuizChallenge {
+            challenge_id: challenge_id.to_string(),
+            questions: vec![tr(self.locale, Msg::QuizRecall, &[&len])],
+            expires_at: challenge.expires_at,
+            kind: QuizKind::Memory,
+            phase: QuizPhase::Answer,
+            reveal: Vec::new(),
+            reveal_seconds: 0,
+            answer_kinds: vec![AnswerKind::Text],
+            stage: challenge.stage as u32 + 1,
+            total_stages: challenge.stages.len() as u32,
+            wait_until: None,
+            last_reason: None,
+            purpose: challenge.request.action.purpose(),
+        })
+    }
+
+    /// Validate quiz answers for the current stage.
+    ///
+    /// Returns `Passed` once the last stage is completed, `NextStage` with the
+    /// following challenge while stages remain, and Err with reason otherwise.
+    /// A failed stage consumes the whole challenge.
     pub fn validate_answers(
         &mut self,
         challenge_id: &str,
-        answers: &[i32],
-    ) -> Result<(), QuizError> {
+        answers: &[QuizAnswer],
+    ) -> Result<QuizOutcome, QuizError> {
+        // Early submissions are rejected without consuming the challenge
+        if let Some(challenge) = self.pending.get(challenge_id) {
+            // Answers are not accepted while the memory sequence is still shown
+            if challenge.phase == QuizPhase::Reveal {
+                return Err(QuizError::WrongPhase);
+            }
+            if let ChallengeBody::Wait { until } = challenge.body {
+                if Utc::now().timestamp() < until {
+                    return Err(QuizError::StillWaiting);
+                }
+            }
+        }
+
         // Get and remove the challenge (one-time use)
         let challenge = self
             .pending
@@ -110,37 +408,69 @@ impl QuizEngine {
             return Err(QuizError::Expired);
         }
 
-        // Check minimum solve time (anti-automation)
-        let solve_time = challenge.created_at.elapsed();
-        if solve_time.as_secs() < self.config.min_solve_seconds as u64 {
-            warn!(
-                solve_time_secs = solve_time.as_secs(),
-                min_required = self.config.min_solve_seconds,
-                "Quiz solved suspiciously fast"
-            );
-            return Err(QuizError::TooFast);
+        match &challenge.body {
+            ChallengeBody::Arithmetic(_) | ChallengeBody::Memory(_) => {
+                // Check minimum solve time (anti-automation)
+                let solve_time = challenge.created_at.elapsed();
+                if solve_time.as_secs() < self.config.min_solve.as_secs() {
+                    warn!(
+                        solve_time_secs = solve_time.as_secs(),
+                        min_required = %self.config.min_solve,
+                        "Quiz solved suspiciously fast"
+                    );
+                    return Err(QuizError::TooFast);
+                }
+            }
+            ChallengeBody::Wait { .. } | ChallengeBody::Confirm(_) => {}
         }
 
-        // Check answer count
-        if answers.len() != challenge.questions.len() {
-            return Err(QuizError::WrongAnswerCount);
+        match &challenge.body {
+            ChallengeBody::Arithmetic(questions) => {
+                // Check answer count
+                if answers.len() != questions.len() {
+                    return Err(QuizError::WrongAnswerCount);
+                }
+
+                // Verify each answer
+                for (i, (question, answer)) in questions.iter().zip(answers).enumerate() {
+                    if !question.answer.accepts(answer) {
+                        debug!(
+                            question_index = i,
+                            expected = ?question.answer,
+                            got = ?answer,
+                            "Wrong answer"
+                        );
+                        return Err(QuizError::WrongAnswer);
+                    }
+                }
+            }
+            ChallengeBody::Memory(sequence) => {
+                if !memory::sequence_matches(sequence, answers) {
+                    debug!("Wrong memory sequence");
+                    return Err(QuizError::WrongAnswer);
+                }
+            }
+            ChallengeBody::Wait { .. } => {}
+            ChallengeBody::Confirm(sentence) => {
+                let expected = ExpectedAnswer::Text(sentence.clone());
+                if answers.len() != 1 || !expected.accepts(&answers[0]) {
+                    debug!("Wrong confirmation sentence");
+                    return Err(QuizError::WrongAnswer);
+                }
+            }
         }
 
-        // Verify each answer
-        for (i, (question, answer)) in challenge.questions.iter().zip(answers).enumerate() {
-            if question.answer != *answer {
-                debug!(
-                    question_index = i,
-                    expected = question.answer,
-                    got = answer,
-                    "Wrong answer"
-                );
-                return Err(QuizError::WrongAnswer);
-            }
+        let next = challenge.stage + 1;
+        if next < challenge.stages.len() {
+            let (next_challenge, pending) =
+                self.start_stage(challenge_id, challenge.stages, next, challenge.request);
+            self.pending.insert(challenge_id.to_string(), pending);
+            debug!(stage = next + 1, "Quiz stage passed, moving to next stage");
+            return Ok(QuizOutcome::NextStage(next_challenge));
         }
 
         debug!("Quiz validated successfully");
-        Ok(())
+        Ok(QuizOutcome::Passed(challenge.request))
     }
 
     /// Generate a single arithmetic question.
@@ -151,17 +481,17 @@ impl QuizEngine {
             _ => Operation::Multiply,
         };
 
-        let (a, b, answer, display) = match op {
+        let (answer, display) = match op {
             Operation::Add => {
                 let a = rng.gen_range(self.config.min_operand..=self.config.max_operand);
                 let b = rng.gen_range(self.config.min_operand..=self.config.max_operand);
-                (a, b, a + b, format!("{} + {} = ?", a, b))
+                (a + b, format!("{} + {} = ?", a, b))
             }
             Operation::Subtract => {
                 // Ensure positive result
                 let a = rng.gen_range(self.config.min_operand..=self.config.max_operand);
                 let b = rng.gen_range(self.config.min_operand..=a);
-                (a, b, a - b, format!("{} - {} = ?", a, b))
+                (a - b, format!("{} - {} = ?", a, b))
             }
             Operation::Multiply => {
                 // Use smaller numbers for multiplication
@@ -169,11 +499,14 @@ impl QuizEngine {
                 let min = 2;
                 let a = rng.gen_range(min..=max);
                 let b = rng.gen_range(min..=max);
-                (a, b, a * b, format!("{} × {} = ?", a, b))
+                (a * b, format!("{} × {} = ?", a, b))
             }
         };
 
-        Question { display, answer }
+        Question {
+            display,
+            answer: ExpectedAnswer::Number(answer),
+        }
     }
 
     /// Remove expired challenges.
@@ -183,6 +516,14 @@ impl QuizEngine {
     }
 }
 
+/// Normalize free text for comparison (lowercase, collapsed whitespace).
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
 /// Quiz validation errors.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QuizError {
@@ -196,6 +537,27 @@ pub enum QuizError {
     WrongAnswerCount,
     /// One or more answers are incorrect
     WrongAnswer,
+    /// Challenge is not in the right phase for this action
+    WrongPhase,
+    /// A wait stage has not elapsed yet
+    StillWaiting,
+}
+
+impl QuizError {
+    /// User-facing description in the given language.
+    pub fn message(self, locale: Locale) -> String {
+        let msg = match self {
+            QuizError::NotFound => Msg::QuizNotFound,
+            QuizError::Expired => Msg::QuizExpired,
+            QuizError::TooFast => Msg::QuizTooFast,
+            QuizError::WrongAnswerCount => Msg::QuizWrongAnswerCount,
+            QuizError::WrongAnswer => Msg::QuizWrongAnswer,
+            QuizError::WrongPhase => Msg::QuizWrongPhase,
+            QuizError::StillWaiting => Msg::QuizStillWaiting,
+        };
+
+        tr(locale, msg, &[])
+    }
 }
 
 impl std::fmt::Display for QuizError {
@@ -206,6 +568,8 @@ impl std::fmt::Display for QuizError {
             QuizError::TooFast => write!(f, "Quiz was solved too quickly"),
             QuizError::WrongAnswerCount => write!(f, "Wrong number of answers"),
             QuizError::WrongAnswer => write!(f, "One or more answers are incorrect"),
+            QuizError::WrongPhase => write!(f, "Quiz challenge is not in the right phase"),
+            QuizError::StillWaiting => write!(f, "The waiting period has not elapsed yet"),
         }
     }
 }
@@ -215,14 +579,32 @@ impl std::error::Error for QuizError {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blockandfocus_shared::Duration;
 
     fn test_config() -> QuizConfig {
         QuizConfig {
             num_questions: 3,
             min_operand: 1,
             max_operand: 10,
-            timeout_seconds: 60,
-            min_solve_seconds: 0, // Disable for tests
+            timeout: Duration::from_secs(60),
+            min_solve: Duration::from_secs(0), // Disable for tests
+            ..QuizConfig::default()
+        }
+    }
+
+    fn correct_answers(engine: &QuizEngine, challenge_id: &str) -> Vec<QuizAnswer> {
+        match &engine.pending.get(challenge_id).unwrap().body {
+            ChallengeBody::Arithmetic(questions) => questions
+                .iter()
+                .map(|q| match &q.answer {
+                    ExpectedAnswer::Number(n) => QuizAnswer::Number(*n),
+                    ExpectedAnswer::Text(text) => QuizAnswer::Text(text.clone()),
+                    ExpectedAnswer::Free(len) => QuizAnswer::Text("x".repeat(*len)),
+                })
+                .collect(),
+            ChallengeBody::Memory(sequence) => vec![QuizAnswer::Text(sequence.join(" "))],
+            ChallengeBody::Wait { .. } => Vec::new(),
+            ChallengeBody::Confirm(sentence) => vec![QuizAnswer::Text(sentence.clone())],
         }
     }
 
@@ -242,8 +624,7 @@ mod tests {
         let challenge = engine.generate_challenge();
 
         // Get the correct answers from the pending challenge
-        let pending = engine.pending.get(&challenge.challenge_id).unwrap();
-        let correct_answers: Vec<i32> = pending.questions.iter().map(|q| q.answer).collect();
+        let correct_answers = correct_answers(&engine, &challenge.challenge_id);
 
         let result = engine.validate_answers(&challenge.challenge_id, &correct_answers);
         assert!(result.is_ok());
@@ -255,7 +636,7 @@ mod tests {
         let challenge = engine.generate_challenge();
 
         // Submit wrong answers
-        let wrong_answers = vec![99999, 99999, 99999];
+        let wrong_answers = vec![QuizAnswer::Number(99999); 3];
         let result = engine.validate_answers(&challenge.challenge_id, &wrong_answers);
         assert_eq!(result, Err(QuizError::WrongAnswer));
     }
@@ -263,7 +644,7 @@ mod tests {
     #[test]
     fn test_challenge_not_found() {
         let mut engine = QuizEngine::new(test_config());
-        let result = engine.validate_answers("nonexistent", &[1, 2, 3]);
+        let result = engine.validate_answers("nonexistent", &[QuizAnswer::Number(1)]);
         assert_eq!(result, Err(QuizError::NotFound));
     }
 
@@ -272,8 +653,7 @@ mod tests {
         let mut engine = QuizEngine::new(test_config());
         let challenge = engine.generate_challenge();
 
-        let pending = engine.pending.get(&challenge.challenge_id).unwrap();
-        let correct_answers: Vec<i32> = pending.questions.iter().map(|q| q.answer).collect();
+        let correct_answers = correct_answers(&engine, &challenge.challenge_id);
 
         // First validation succeeds
         let result = engine.validate_answers(&challenge.challenge_id, &correct_answers);
@@ -283,4 +663,204 @@ mod tests {
         let result = engine.validate_answers(&challenge.challenge_id, &correct_answers);
         assert_eq!(result, Err(QuizError::NotFound));
     }
+
+    #[test]
+    fn test_memory_challenge_phases() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            kind: QuizKind::Memory,
+            ..test_config()
+        });
+        let challenge = engine.generate_challenge();
+
+        assert_eq!(challenge.kind, QuizKind::Memory);
+        assert_eq!(challenge.phase, QuizPhase::Reveal);
+        assert_eq!(challenge.reveal.len(), 6);
+
+        let answers = vec![QuizAnswer::Text(challenge.reveal.join(" "))];
+
+        // Answers are rejected during the reveal phase without consuming the challenge
+        assert_eq!(
+            engine.validate_answers(&challenge.challenge_id, &answers),
+            Err(QuizError::WrongPhase)
+        );
+
+        let answering = engine.begin_answer(&challenge.challenge_id).unwrap();
+        assert_eq!(answering.phase, QuizPhase::Answer);
+        assert!(answering.reveal.is_empty());
+
+        // The reveal phase cannot be entered twice
+        assert_eq!(
+            engine.begin_answer(&challenge.challenge_id).unwrap_err(),
+            QuizError::WrongPhase
+        );
+
+        assert!(engine
+            .validate_answers(&challenge.challenge_id, &answers)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_memory_challenge_wrong_sequence() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            kind: QuizKind::Memory,
+            memory_words: false,
+            ..test_config()
+        });
+        let challenge = engine.generate_challenge();
+        engine.begin_answer(&challenge.challenge_id).unwrap();
+
+        let result = engine.validate_answers(
+            &challenge.challenge_id,
+            &[QuizAnswer::Text("not the sequence".to_string())],
+        );
+        assert_eq!(result, Err(QuizError::WrongAnswer));
+    }
+
+    #[test]
+    fn test_custom_questions_mixed_in() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            custom_questions: vec![CustomQuestion {
+                question: "What's your quarterly goal?".to_string(),
+                answer: None,
+                min_length: 10,
+            }],
+            ..test_config()
+        });
+        let challenge = engine.generate_challenge();
+
+        assert_eq!(challenge.questions.len(), 4);
+        let pos = challenge
+            .questions
+            .iter()
+            .position(|q| q == "What's your quarterly goal?")
+            .unwrap();
+        assert_eq!(challenge.answer_kinds[pos], AnswerKind::Text);
+        assert_eq!(
+            challenge
+                .answer_kinds
+                .iter()
+                .filter(|k| **k == AnswerKind::Number)
+                .count(),
+            3
+        );
+
+        // A too-short intention answer is rejected
+        let mut answers = correct_answers(&engine, &challenge.challenge_id);
+        answers[pos] = QuizAnswer::Text("dunno".to_string());
+        assert_eq!(
+            engine.validate_answers(&challenge.challenge_id, &answers),
+            Err(QuizError::WrongAnswer)
+        );
+    }
+
+    #[test]
+    fn test_expected_answer_matching() {
+        let text = ExpectedAnswer::Text("Ship the  Beta".to_string());
+        assert!(text.accepts(&QuizAnswer::Text("ship the beta".to_string())));
+        assert!(!text.accepts(&QuizAnswer::Text("ship it".to_string())));
+
+        let free = ExpectedAnswer::Free(10);
+        assert!(free.accepts(&QuizAnswer::Text("finish the thesis".to_string())));
+        assert!(!free.accepts(&QuizAnswer::Text("   short   ".to_string())));
+
+        let number = ExpectedAnswer::Number(42);
+        assert!(number.accepts(&QuizAnswer::Number(42)));
+        assert!(number.accepts(&QuizAnswer::Text(" 42 ".to_string())));
+        assert!(!number.accepts(&QuizAnswer::Number(41)));
+    }
+
+    #[test]
+    fn test_multi_stage_pipeline() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            stages: vec![
+                QuizStage::Quiz,
+                QuizStage::Wait { seconds: 0 },
+                QuizStage::Confirm {
+                    sentence: "I choose to lose focus".to_string(),
+                },
+            ],
+            ..test_config()
+        });
+
+        let challenge = engine.generate_challenge();
+        assert_eq!(challenge.stage, 1);
+        assert_eq!(challenge.total_stages, 3);
+
+        let answers = correct_answers(&engine, &challenge.challenge_id);
+        let wait = match engine.validate_answers(&challenge.challenge_id, &answers) {
+            Ok(QuizOutcome::NextStage(next)) => next,
+            other => panic!("Expected next stage, got {:?}", other),
+        };
+        assert_eq!(wait.kind, QuizKind::Wait);
+        assert_eq!(wait.challenge_id, challenge.challenge_id);
+
+        let confirm = match engine.validate_answers(&challenge.challenge_id, &[]) {
+            Ok(QuizOutcome::NextStage(next)) => next,
+            other => panic!("Expected next stage, got {:?}", other),
+        };
+        assert_eq!(confirm.kind, QuizKind::Confirm);
+        assert_eq!(confirm.stage, 3);
+
+        let result = engine.validate_answers(
+            &challenge.challenge_id,
+            &[QuizAnswer::Text("i choose to lose  focus".to_string())],
+        );
+        assert!(matches!(result, Ok(QuizOutcome::Passed(_))));
+    }
+
+    #[test]
+    fn test_wait_stage_not_elapsed() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            stages: vec![QuizStage::Wait { seconds: 120 }],
+            ..test_config()
+        });
+        let challenge = engine.generate_challenge();
+        assert!(challenge.wait_until.is_some());
+
+        // Early submission is rejected but does not consume the challenge
+        assert_eq!(
+            engine.validate_answers(&challenge.challenge_id, &[]).unwrap_err(),
+            QuizError::StillWaiting
+        );
+        assert!(engine.pending.contains_key(&challenge.challenge_id));
+    }
+
+    #[test]
+    fn test_failed_stage_consumes_challenge() {
+        let mut engine = QuizEngine::new(QuizConfig {
+            stages: vec![
+                QuizStage::Confirm {
+                    sentence: "I really need this".to_string(),
+                },
+                QuizStage::Quiz,
+            ],
+            ..test_config()
+        });
+        let challenge = engine.generate_challenge();
+
+        let result = engine.validate_answers(
+            &challenge.challenge_id,
+            &[QuizAnswer::Text("whatever".to_string())],
+        );
+        assert_eq!(result.unwrap_err(), QuizError::WrongAnswer);
+        assert!(!engine.pending.contains_key(&challenge.challenge_id));
+    }
+
+    #[test]
+    fn test_request_returned_on_pass() {
+        let mut engine = QuizEngine::new(test_config());
+        let request = BypassRequest {
+            reason: Some("Checking flight times".to_string()),
+            action: GatedAction::SnoozeSchedule {
+                activation: 1_750_000_000,
+                minutes: 15,
+            },
+        };
+        let challenge = engine.generate_challenge_for(request.clone());
+        assert_eq!(challenge.purpose, QuizPurpose::SnoozeSchedule);
+
+        let answers = correct_answers(&engine, &challenge.challenge_id);
+        let result = engine.validate_answers(&challenge.challenge_id, &answers);
+        assert_eq!(result, Ok(QuizOutcome::Passed(request)));
+    }
 }
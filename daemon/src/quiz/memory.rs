@@ -0,0 +1,114 @@
+//! Word/digit sequence memory challenges.
+
+use blockandfocus_shared::QuizAnswer;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Short, unambiguous words that are easy to read but hard to guess.
+const WORDS: &[&str] = &[
+    "apple", "river", "stone", "cloud", "tiger", "lemon", "piano", "glass", "forest", "candle",
+    "rocket", "silver", "garden", "winter", "orange", "bridge", "pencil", "anchor", "marble",
+    "violet", "copper", "desert", "falcon", "harbor", "island", "jungle", "kettle", "ladder",
+    "meadow", "napkin", "oyster", "pepper", "quartz", "saddle", "tunnel", "velvet", "walnut",
+    "yellow", "zipper", "basket", "camera", "dragon", "engine", "feather", "guitar", "helmet",
+    "insect", "jacket", "lizard", "mirror", "needle", "parrot", "rabbit", "spider", "turtle",
+];
+
+/// Generate a random sequence of words or single digits.
+pub fn generate_sequence(rng: &mut impl Rng, length: u32, use_words: bool) -> Vec<String> {
+    (0..length.max(1))
+        .map(|_| {
+            if use_words {
+                WORDS.choose(rng).copied().unwrap_or("apple").to_string()
+            } else {
+                rng.gen_range(0..10).to_string()
+            }
+        })
+        .collect()
+}
+
+/// Check submitted answers against the expected sequence.
+///
+/// Answers may be given as one text answer ("apple river stone"), one answer
+/// per item, or numbers for digit sequences. Comparison ignores case and
+/// extra whitespace.
+pub fn sequence_matches(sequence: &[String], answers: &[QuizAnswer]) -> bool {
+    let tokens: Vec<String> = answers
+        .iter()
+        .flat_map(|answer| match answer {
+            QuizAnswer::Number(n) => vec![n.to_string()],
+            QuizAnswer::Text(text) => text
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|t| !t.is_empty())
+                .map(|t| t.to_lowercase())
+                .collect(),
+        })
+        .collect();
+
+    // A digit sequence may be typed without separators ("40172")
+    let tokens = if tokens.len() == 1 && sequence.len() > 1 && sequence.iter().all(|s| s.len() == 1)
+    {
+        tokens[0].chars().map(|c| c.to_string()).collect()
+    } else {
+        tokens
+    };
+
+    tokens.len() == sequence.len() && tokens.iter().zip(sequence).all(|(t, s)| t == s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_generate_sequence() {
+        let mut rng = rand::thread_rng();
+        let words = generate_sequence(&mut rng, 6, true);
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| WORDS.contains(&w.as_str())));
+
+        let digits = generate_sequence(&mut rng, 8, false);
+        assert_eq!(digits.len(), 8);
+        assert!(digits.iter().all(|d| d.len() == 1 && d.chars().all(|c| c.is_ascii_digit())));
+    }
+
+    #[test]
+    fn test_word_sequence_matching() {
+        let sequence = seq(&["apple", "river", "stone"]);
+
+        assert!(sequence_matches(
+            &sequence,
+            &[QuizAnswer::Text("Apple  river stone".to_string())]
+        ));
+        assert!(sequence_matches(
+            &sequence,
+            &[
+                QuizAnswer::Text("apple".to_string()),
+                QuizAnswer::Text("river".to_string()),
+                QuizAnswer::Text("stone".to_string()),
+            ]
+        ));
+        assert!(!sequence_matches(
+            &sequence,
+            &[QuizAnswer::Text("apple stone river".to_string())]
+        ));
+        assert!(!sequence_matches(&sequence, &[QuizAnswer::Text("apple river".to_string())]));
+    }
+
+    #[test]
+    fn test_digit_sequence_matching() {
+        let sequence = seq(&["4", "0", "7"]);
+
+        assert!(sequence_matches(&sequence, &[QuizAnswer::Text("407".to_string())]));
+        assert!(sequence_matches(&sequence, &[QuizAnswer::Text("4 0 7".to_string())]));
+        assert!(sequence_matches(
+            &sequence,
+            &[QuizAnswer::Number(4), QuizAnswer::Number(0), QuizAnswer::Number(7)]
+        ));
+        assert!(!sequence_matches(&sequence, &[QuizAnswer::Text("470".to_string())]));
+    }
+}
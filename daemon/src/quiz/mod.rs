@@ -1,6 +1,7 @@
 //! Quiz system for bypass friction.
 
 mod generator;
+mod memory;
 mod validator;
 
-pub use generator::{QuizEngine, QuizError};
+pub use generator::{BypassRequest, GatedAction, QuizEngine, QuizError, QuizOutcome};
@@ -4,4 +4,5 @@
 //! This file exists for potential future expansion (e.g., HMAC tokens, validation receipts).
 
 // Re-export QuizError for convenience
+#[allow(unused_imports)]
 pub use super::generator::QuizError;
@@ -0,0 +1,148 @@
+//! Sending the report to a webhook or by email, through curl.
+
+use super::weekly::WeeklyReport;
+use anyhow::{Context, Result};
+use blockandfocus_shared::{ReportConfig, ReportEmail};
+use chrono::{DateTime, Local};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// How long one delivery may take, in seconds.
+const DELIVERY_TIMEOUT_SECONDS: &str = "30";
+
+/// Send the report through every configured channel. All are tried even if
+/// one fails; the error lists the ones that did.
+pub async fn deliver(
+    config: &ReportConfig,
+    subject: &str,
+    text: &str,
+    report: &WeeklyReport,
+) -> Result<()> {
+    if config.webhook.is_none() && config.email.is_none() {
+        anyhow::bail!("reports.webhook and reports.email are both unset");
+    }
+
+    let mut failures = Vec::new();
+    if let Some(url) = &config.webhook {
+        let body = serde_json::json!({ "subject": subject, "text": text, "report": report });
+        let args = ["-H", "Content-Type: application/json", "--data-binary", "@-", url];
+        if let Err(e) = curl(&args, body.to_string().as_bytes()).await {
+            failures.push(format!("webhook: {:#}", e));
+        }
+    }
+    if let Some(email) = &config.email {
+        if let Err(e) = send_email(email, subject, text).await {
+            failures.push(format!("email: {:#}", e));
+        }
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!("{}", failures.join("; "));
+    }
+
+    Ok(())
+}
+
+async fn send_email(email: &ReportEmail, subject: &str, text: &str) -> Result<()> {
+    if email.to.is_empty() {
+        anyhow::bail!("reports.email.to lists no recipients");
+    }
+
+    // TLS is required: smtps:// has it from the start, smtp:// must offer STARTTLS
+    let mut args = vec!["--url", &email.server, "--ssl-reqd", "--mail-from", &email.from];
+    for to in &email.to {
+        args.extend(["--mail-rcpt", to.as_str()]);
+    }
+    // curl hides the login from the process list
+    let login;
+    if let Some(username) = &email.username {
+        login = format!("{}:{}", username, email.password.as_deref().unwrap_or_default());
+        args.extend(["--user", &login]);
+    }
+    args.extend(["--upload-file", "-", "--crlf"]);
+
+    let message = email_message(email, subject, text, Local::now());
+    curl(&args, message.as_bytes()).await
+}
+
+/// The report as a plain-text email.
+fn email_message(email: &ReportEmail, subject: &str, text: &str, now: DateTime<Local>) -> String {
+    format!(
+        "From: {}\nTo: {}\nSubject: {}\nDate: {}\nMIME-Version: 1.0\n\
+         Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n{}",
+        email.from,
+        email.to.join(", "),
+        encode_header(subject),
+        now.to_rfc2822(),
+        text
+    )
+}
+
+/// Encode a header value as an RFC 2047 encoded word if it isn't plain
+/// ASCII; line breaks (which would start a new header) become spaces.
+fn encode_header(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    if value.is_ascii() {
+        return value;
+    }
+
+    let mut encoded = "=?UTF-8?Q?".to_string();
+    for byte in value.bytes() {
+        match byte {
+            b' ' => encoded.push('_'),
+            b if b.is_ascii_alphanumeric() => encoded.push(b as char),
+            b => encoded.push_str(&format!("={:02X}", b)),
+        }
+    }
+    encoded + "?="
+}
+
+/// Run curl with `input` on its standard input.
+async fn curl(args: &[&str], input: &[u8]) -> Result<()> {
+    let mut child = Command::new("/usr/bin/curl")
+        .args(["-fsS", "--max-time", DELIVERY_TIMEOUT_SECONDS])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run curl")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input).await.context("Failed to pass the report to curl")?;
+    }
+    let output = child.wait_with_output().await.context("Failed to run curl")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_email_message() {
+        let email = ReportEmail {
+            server: "smtps://smtp.example.com".to_string(),
+            from: "me@example.com".to_string(),
+            to: vec!["coach@example.com".to_string(), "me@example.com".to_string()],
+            ..Default::default()
+        };
+        let now = Local.with_ymd_and_hms(2025, 6, 15, 19, 0, 0).unwrap();
+        let message = email_message(&email, "Weekly\nreport", "Blocked: 14\n", now);
+
+        let (headers, body) = message.split_once("\n\n").unwrap();
+        assert!(headers.contains("\nTo: coach@example.com, me@example.com\n"));
+        assert!(headers.contains("\nSubject: Weekly report\n"));
+        assert_eq!(body, "Blocked: 14\n");
+
+        assert_eq!(encode_header("Rapport hebdo"), "Rapport hebdo");
+        assert_eq!(encode_header("Più ore"), "=?UTF-8?Q?Pi=C3=B9_ore?=");
+    }
+}
@@ -0,0 +1,7 @@
+//! Weekly report of blocking and bypass activity, sent every Sunday evening.
+
+mod delivery;
+mod monitor;
+mod weekly;
+
+pub use monitor::{send_weekly_report, ReportMonitor};
@@ -0,0 +1,115 @@
+//! Sends the weekly report on Sunday evening.
+
+use super::delivery::deliver;
+use super::weekly::WeeklyReport;
+use crate::i18n::{tr, Msg};
+use crate::AppState;
+use anyhow::Result;
+use blockandfocus_shared::ReportConfig;
+use chrono::{DateTime, Datelike, Local, Weekday};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// How often the send time is checked for having passed.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait before trying again after a delivery failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Sends the weekly report when it is due.
+pub struct ReportMonitor;
+
+impl ReportMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut failed_at: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+            let (config, last_week) = {
+                let state_guard = state.read().await;
+                let config = state_guard.config.get().reports.clone();
+                (config, state_guard.store.get().last_report_week.clone())
+            };
+            let retrying = failed_at.is_some_and(|at| at.elapsed() < RETRY_INTERVAL);
+            let due = is_due(&config, &Local::now(), last_week.as_deref());
+            if !config.enabled || retrying || !due {
+                continue;
+            }
+
+            match send_weekly_report(&state).await {
+                Ok(week_start) => {
+                    info!(week_start, "Sent weekly report");
+                    failed_at = None;
+                    if let Err(e) = state.write().await.store.record_report(&week_start) {
+                        warn!("Failed to record weekly report: {:#}", e);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to send weekly report: {:#}", e);
+                    failed_at = Some(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+/// Whether it is Sunday past the send time and this week's report hasn't
+/// been sent yet.
+fn is_due(config: &ReportConfig, now: &DateTime<Local>, last_week: Option<&str>) -> bool {
+    let today = now.date_naive();
+    let monday = today - chrono::Duration::days(6);
+    today.weekday() == Weekday::Sun
+        && now.time() >= config.at.0
+        && last_week != Some(monday.format("%Y-%m-%d").to_string().as_str())
+}
+
+/// Render this week's report and deliver it. Returns the week's Monday.
+pub async fn send_weekly_report(state: &Arc<RwLock<AppState>>) -> Result<String> {
+    let (config, report, locale) = {
+        let state_guard = state.read().await;
+        let config = state_guard.config.get();
+        let report = WeeklyReport::build(state_guard.store.get(), Local::now());
+        (config.reports.clone(), report, config.locale)
+    };
+
+    let subject = match &config.subject {
+        Some(subject) => subject.clone(),
+        None => tr(locale, Msg::WeeklyReportSubject, &[]),
+    };
+    let template = match &config.template {
+        Some(template) => template.clone(),
+        None => tr(locale, Msg::WeeklyReportText, &[]),
+    };
+    deliver(&config, &report.render(&subject), &report.render(&template), &report).await?;
+
+    Ok(report.week_start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::NaiveTimeWrapper;
+    use chrono::{NaiveTime, TimeZone};
+
+    #[test]
+    fn test_is_due() {
+        let config = ReportConfig {
+            at: NaiveTimeWrapper(NaiveTime::from_hms_opt(19, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        let at = |d, h| Local.with_ymd_and_hms(2025, 6, d, h, 30, 0).unwrap();
+
+        assert!(is_due(&config, &at(15, 19), None));
+        assert!(is_due(&config, &at(15, 23), Some("2025-06-02")));
+        // Already sent this week
+        assert!(!is_due(&config, &at(15, 21), Some("2025-06-09")));
+        // Too early on Sunday, or not Sunday
+        assert!(!is_due(&config, &at(15, 18), None));
+        assert!(!is_due(&config, &at(14, 20), None));
+        assert!(!is_due(&config, &at(16, 20), None));
+    }
+}
@@ -0,0 +1,156 @@
+//! Building and rendering the weekly report.
+
+use crate::analytics::weekly_bypass_analytics;
+use crate::store::PersistentState;
+use blockandfocus_shared::ReasonCount;
+use chrono::{DateTime, Datelike, Duration, Local};
+use serde::Serialize;
+
+/// Minutes spent on one blocklist group during the week.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupMinutes {
+    pub group: String,
+    pub minutes: u32,
+}
+
+/// Activity during one week, Monday to Sunday.
+#[derive(Debug, Clone, Serialize)]
+pub struct WeeklyReport {
+    /// Monday, as YYYY-MM-DD
+    pub week_start: String,
+    /// Sunday, as YYYY-MM-DD
+    pub week_end: String,
+    /// Blocked queries
+    pub blocked: u64,
+    /// Day with the most blocked queries, as YYYY-MM-DD
+    pub busiest_day: Option<String>,
+    pub bypasses: u32,
+    /// Minutes spent in a bypass
+    pub bypass_minutes: u64,
+    /// Average minutes a bypass ran beyond its first request
+    pub overrun_minutes: f64,
+    pub top_reasons: Vec<ReasonCount>,
+    /// Estimated time on groups with a weekly budget, most used first
+    pub groups: Vec<GroupMinutes>,
+}
+
+impl WeeklyReport {
+    /// Report on the week containing `now`.
+    pub fn build(state: &PersistentState, now: DateTime<Local>) -> Self {
+        let bypasses = weekly_bypass_analytics(&state.bypass_history, 1, now).remove(0);
+        let today = now.date_naive();
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let week_start = monday.format("%Y-%m-%d").to_string();
+        let week_end = (monday + Duration::days(6)).format("%Y-%m-%d").to_string();
+
+        // Dates are YYYY-MM-DD, so they compare in calendar order
+        let days: Vec<(&str, u64)> = state
+            .block_activity
+            .iter()
+            .filter(|day| day.date >= week_start && day.date <= week_end)
+            .map(|day| (day.date.as_str(), day.hours.iter().map(|&n| u64::from(n)).sum()))
+            .collect();
+        let busiest_day = days
+            .iter()
+            .filter(|(_, blocked)| *blocked > 0)
+            .max_by_key(|(_, blocked)| *blocked)
+            .map(|(date, _)| date.to_string());
+
+        let mut groups: Vec<GroupMinutes> = state
+            .group_usage
+            .iter()
+            .filter(|usage| usage.week_start == week_start && usage.used_minutes > 0)
+            .map(|usage| GroupMinutes { group: usage.group.clone(), minutes: usage.used_minutes })
+            .collect();
+        groups.sort_by_key(|group| std::cmp::Reverse(group.minutes));
+
+        Self {
+            week_start,
+            week_end,
+            blocked: days.iter().map(|(_, blocked)| blocked).sum(),
+            busiest_day,
+            bypasses: bypasses.bypass_count,
+            bypass_minutes: bypasses.total_minutes_used,
+            overrun_minutes: bypasses.average_overrun_minutes,
+            top_reasons: bypasses.top_reasons,
+            groups,
+        }
+    }
+
+    /// Fill in a template's `{placeholder}`s; unknown ones are left as they are.
+    pub fn render(&self, template: &str) -> String {
+        let none = || "-".to_string();
+        let top_reasons = self
+            .top_reasons
+            .iter()
+            .map(|reason| format!("{} ({})", reason.reason, reason.count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let groups = self
+            .groups
+            .iter()
+            .map(|group| format!("{} {} min", group.group, group.minutes))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let values = [
+            ("week_start", self.week_start.clone()),
+            ("week_end", self.week_end.clone()),
+            ("blocked", self.blocked.to_string()),
+            ("busiest_day", self.busiest_day.clone().unwrap_or_else(none)),
+            ("bypasses", self.bypasses.to_string()),
+            ("bypass_minutes", self.bypass_minutes.to_string()),
+            ("overrun_minutes", format!("{:.0}", self.overrun_minutes)),
+            ("top_reasons", Some(top_reasons).filter(|s| !s.is_empty()).unwrap_or_else(none)),
+            ("groups", Some(groups).filter(|s| !s.is_empty()).unwrap_or_else(none)),
+        ];
+        values.iter().fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{BypassRecord, StateStore};
+    use chrono::{NaiveDate, TimeZone};
+
+    #[test]
+    fn test_build_and_render() {
+        let now = Local.with_ymd_and_hms(2025, 6, 15, 19, 0, 0).unwrap(); // Sunday
+        let monday = NaiveDate::from_ymd_opt(2025, 6, 9).unwrap();
+        let mut store = StateStore::in_memory();
+        store.add_blocks(monday, 9, 4).unwrap();
+        store.add_blocks(monday + Duration::days(2), 10, 7).unwrap();
+        store.add_blocks(monday + Duration::days(2), 11, 3).unwrap();
+        // The week before isn't counted
+        store.add_blocks(monday - Duration::days(1), 10, 50).unwrap();
+        store.add_group_minutes("social", "2025-06-09", 45).unwrap();
+        store.add_group_minutes("news", "2025-06-09", 90).unwrap();
+        store
+            .record_bypass(BypassRecord {
+                started_at: Local.with_ymd_and_hms(2025, 6, 10, 12, 0, 0).unwrap().timestamp(),
+                duration_minutes: 15,
+                reason: Some("Flights".to_string()),
+                cancelled_at: None,
+            })
+            .unwrap();
+
+        let report = WeeklyReport::build(store.get(), now);
+        assert_eq!(report.week_start, "2025-06-09");
+        assert_eq!(report.week_end, "2025-06-15");
+        assert_eq!(report.blocked, 14);
+        assert_eq!(report.busiest_day.as_deref(), Some("2025-06-11"));
+        assert_eq!(report.bypasses, 1);
+        assert_eq!(report.bypass_minutes, 15);
+
+        assert_eq!(
+            report.render("{blocked} blocked, {bypasses} bypass ({top_reasons}); {groups} {other}"),
+            "14 blocked, 1 bypass (flights (1)); news 90 min, social 45 min {other}"
+        );
+
+        let empty = WeeklyReport::build(&PersistentState::default(), now);
+        assert_eq!(empty.render("{busiest_day} {top_reasons} {groups}"), "- - -");
+    }
+}
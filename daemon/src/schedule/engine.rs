@@ -1,18 +1,29 @@
 //! Schedule evaluation engine.
 
-use blockandfocus_shared::{Schedule, ScheduleRule, WeekdayWrapper};
-use chrono::{Datelike, Local, Timelike, Weekday};
+use blockandfocus_shared::{
+    BlockingWindow, NaiveTimeWrapper, Schedule, ScheduleRule, Strictness, Vacation, WindDownStage,
+};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, Timelike, Weekday};
 use tracing::debug;
 
 /// Engine for evaluating schedule rules.
+#[derive(Clone)]
 pub struct ScheduleEngine {
     schedule: Schedule,
+    /// Window (start, end) during which regular rules are snoozed
+    snoozed: Option<(i64, i64)>,
+    /// Date ranges during which the schedule is suspended
+    vacations: Vec<Vacation>,
 }
 
 impl ScheduleEngine {
     /// Create a new schedule engine.
     pub fn new(schedule: Schedule) -> Self {
-        Self { schedule }
+        Self {
+            schedule,
+            snoozed: None,
+            vacations: Vec::new(),
+        }
     }
 
     /// Update the schedule configuration.
@@ -20,15 +31,27 @@ impl ScheduleEngine {
         self.schedule = schedule;
     }
 
+    /// Replace the vacations during which the schedule is suspended.
+    pub fn set_vacations(&mut self, vacations: Vec<Vacation>) {
+        self.vacations = vacations;
+    }
+
+    /// Get the vacation covering a local date, if any.
+    pub fn vacation_on(&self, date: NaiveDate) -> Option<&Vacation> {
+        self.vacations
+            .iter()
+            .find(|vacation| vacation.start <= date && date <= vacation.end)
+    }
+
     /// Check if blocking should be active based on schedule.
     ///
     /// Returns true if:
-    /// - Schedule is disabled (blocking always active), OR
+    /// - Schedule is disabled (blocking always active) and no vacation is in progress, OR
     /// - Current time falls within any active schedule rule
     pub fn is_blocking_time(&self) -> bool {
         if !self.schedule.enabled {
-            // Schedule disabled means blocking is always active
-            return true;
+            // Schedule disabled means blocking is always active, except on vacation
+            return self.vacation_on(Local::now().date_naive()).is_none();
         }
 
         if self.schedule.rules.is_empty() {
@@ -37,11 +60,9 @@ impl ScheduleEngine {
         }
 
         let now = Local::now();
-        let current_day = now.weekday();
-        let current_time = now.time();
 
         for rule in &self.schedule.rules {
-            if self.rule_matches(rule, current_day, current_time) {
+            if self.rule_active(rule, now) && blocks_everything(rule, now.time()) {
                 debug!(
                     rule_name = %rule.name,
                     "Schedule rule active"
@@ -60,11 +81,9 @@ impl ScheduleEngine {
         }
 
         let now = Local::now();
-        let current_day = now.weekday();
-        let current_time = now.time();
 
         for rule in &self.schedule.rules {
-            if self.rule_matches(rule, current_day, current_time) {
+            if self.rule_active(rule, now) {
                 return Some(rule.name.clone());
             }
         }
@@ -72,6 +91,243 @@ impl ScheduleEngine {
         None
     }
 
+    /// Get the rules that apply right now.
+    pub fn active_rules(&self) -> Vec<&ScheduleRule> {
+        if !self.schedule.enabled {
+            return Vec::new();
+        }
+
+        let now = Local::now();
+        self.schedule
+            .rules
+            .iter()
+            .filter(|rule| self.rule_active(rule, now))
+            .collect()
+    }
+
+    /// Groups blocked by active wind-down rules that have not reached full blocking.
+    pub fn wind_down_groups(&self) -> Vec<String> {
+        if !self.schedule.enabled {
+            return Vec::new();
+        }
+
+        let now = Local::now();
+        let mut groups: Vec<String> = self
+            .schedule
+            .rules
+            .iter()
+            .filter(|rule| self.rule_active(rule, now))
+            .flat_map(|rule| reached_stages(rule, now.time()))
+            .flat_map(|stage| stage.groups.iter().cloned())
+            .collect();
+        groups.sort();
+        groups.dedup();
+        groups
+    }
+
+    /// Strictness of the strictest rule that applies right now.
+    pub fn active_strictness(&self) -> Option<Strictness> {
+        self.active_rules().iter().map(|rule| rule.strictness).max()
+    }
+
+    /// Check if a rule applies at the given moment.
+    ///
+    /// Temporary rules apply continuously until they expire; regular rules
+    /// are skipped while snoozed or on vacation.
+    fn rule_active(&self, rule: &ScheduleRule, now: DateTime<Local>) -> bool {
+        match rule.expires_at {
+            Some(expires_at) => now.timestamp() < expires_at,
+            None => {
+                !self.is_snoozed(now.timestamp())
+                    && self.vacation_on(now.date_naive()).is_none()
+                    && self.rule_matches(rule, now.weekday(), now.time())
+            }
+        }
+    }
+
+    /// Find when the next regular rule starts after `now` (within a week).
+    ///
+    /// Days on vacation are skipped.
+    pub fn next_activation(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        if !self.schedule.enabled {
+            return None;
+        }
+
+        (0..=7)
+            .filter_map(|offset| now.date_naive().checked_add_signed(Duration::days(offset)))
+            .filter(|date| self.vacation_on(*date).is_none())
+            .flat_map(|date| {
+                self.schedule
+                    .rules
+                    .iter()
+                    .filter(move |rule| {
+                        rule.expires_at.is_none()
+                            && rule.days.iter().any(|d| Weekday::from(*d) == date.weekday())
+                    })
+                    .filter_map(move |rule| {
+                        date.and_time(rule.start_time.0)
+                            .and_local_timezone(Local)
+                            .earliest()
+                    })
+            })
+            .filter(|start| *start > now)
+            .min()
+    }
+
+    /// Suppress regular rules from `activation` for `minutes`.
+    pub fn snooze(&mut self, activation: i64, minutes: u32) {
+        self.snoozed = Some((activation, activation + minutes as i64 * 60));
+    }
+
+    /// Move the snooze window by `seconds`, e.g. after the system clock changed.
+    pub fn shift_snooze(&mut self, seconds: i64) {
+        if let Some((start, end)) = self.snoozed.as_mut() {
+            *start += seconds;
+            *end += seconds;
+        }
+    }
+
+    /// End of the snooze window, if one is set and not over yet.
+    pub fn snoozed_until(&self, now: i64) -> Option<i64> {
+        self.snoozed.map(|(_, end)| end).filter(|end| now < *end)
+    }
+
+    /// Periods in which the schedule blocks between `from` and `until`.
+    ///
+    /// Follows the same rules as `is_blocking_time` (vacations and the snooze
+    /// included), so overnight rules show exactly the hours they cover. A
+    /// wind-down rule gets one window per stage reached.
+    pub fn timeline(&self, from: DateTime<Local>, until: DateTime<Local>) -> Vec<BlockingWindow> {
+        let days: Vec<NaiveDate> = from
+            .date_naive()
+            .iter_days()
+            .take_while(|date| *date <= until.date_naive())
+            .filter(|date| self.vacation_on(*date).is_none())
+            .collect();
+
+        let mut windows = Vec::new();
+        if !self.schedule.enabled {
+            // Blocking around the clock, except on vacation
+            for date in &days {
+                windows.extend(window(None, Vec::new(), Strictness::default(), *date, 0, DAY));
+            }
+        }
+
+        for rule in self.schedule.rules.iter().filter(|_| self.schedule.enabled) {
+            if let Some(expires_at) = rule.expires_at {
+                windows.push(BlockingWindow {
+                    rule: Some(rule.name.clone()),
+                    groups: Vec::new(),
+                    strictness: rule.strictness,
+                    start: from.timestamp(),
+                    end: expires_at,
+                });
+                continue;
+            }
+
+            let rule_days = days
+                .iter()
+                .filter(|date| rule.days.iter().any(|d| Weekday::from(*d) == date.weekday()));
+            for date in rule_days {
+                for (start, end) in daily_windows(rule) {
+                    // Wind-down stages split the window where they begin
+                    let mut bounds: Vec<u32> = rule
+                        .stages
+                        .iter()
+                        .map(|stage| stage.at.0.num_seconds_from_midnight())
+                        .filter(|at| start < *at && *at < end)
+                        .collect();
+                    bounds.push(start);
+                    bounds.push(end);
+                    bounds.sort();
+                    bounds.dedup();
+
+                    for pair in bounds.windows(2) {
+                        let time = NaiveTime::from_num_seconds_from_midnight_opt(pair[0], 0)
+                            .unwrap_or(NaiveTime::MIN);
+                        let groups = if blocks_everything(rule, time) {
+                            Vec::new()
+                        } else {
+                            let mut groups: Vec<String> = reached_stages(rule, time)
+                                .flat_map(|stage| stage.groups.iter().cloned())
+                                .collect();
+                            if groups.is_empty() {
+                                // Before the first stage nothing is blocked
+                                continue;
+                            }
+                            groups.sort();
+                            groups.dedup();
+                            groups
+                        };
+                        let name = Some(rule.name.clone());
+                        windows.extend(
+                            window(name, groups, rule.strictness, *date, pair[0], pair[1])
+                                .into_iter()
+                                .flat_map(|window| self.without_snooze(window)),
+                        );
+                    }
+                }
+            }
+        }
+
+        let (from, until) = (from.timestamp(), until.timestamp());
+        let mut windows: Vec<BlockingWindow> = windows
+            .into_iter()
+            .map(|window| BlockingWindow {
+                start: window.start.max(from),
+                end: window.end.min(until),
+                ..window
+            })
+            .filter(|window| window.start < window.end)
+            .collect();
+        windows.sort_by(|a, b| (&a.rule, a.start).cmp(&(&b.rule, b.start)));
+
+        // Join the halves of overnight rules and consecutive days
+        let mut merged: Vec<BlockingWindow> = Vec::new();
+        for window in windows {
+            match merged.last_mut() {
+                Some(last)
+                    if last.rule == window.rule
+                        && last.groups == window.groups
+                        && last.end == window.start =>
+                {
+                    last.end = window.end;
+                }
+                _ => merged.push(window),
+            }
+        }
+        merged.sort_by_key(|window| window.start);
+        merged
+    }
+
+    /// The parts of `window` outside the snooze.
+    fn without_snooze(&self, window: BlockingWindow) -> Vec<BlockingWindow> {
+        let Some((start, end)) = self.snoozed else {
+            return vec![window];
+        };
+        if end <= window.start || window.end <= start {
+            return vec![window];
+        }
+
+        let mut parts = Vec::new();
+        if window.start < start {
+            parts.push(BlockingWindow {
+                end: start,
+                ..window.clone()
+            });
+        }
+        if end < window.end {
+            parts.push(BlockingWindow { start: end, ..window });
+        }
+        parts
+    }
+
+    /// Check if regular rules are snoozed at the given time.
+    fn is_snoozed(&self, now: i64) -> bool {
+        self.snoozed
+            .is_some_and(|(start, end)| now >= start && now < end)
+    }
+
     /// Check if a specific rule matches the given day and time.
     fn rule_matches(
         &self,
@@ -104,19 +360,184 @@ impl ScheduleEngine {
     }
 }
 
+/// Whether an active rule blocks everything at the given time of day.
+///
+/// Wind-down rules only do once they reach a stage without groups.
+fn blocks_everything(rule: &ScheduleRule, time: NaiveTime) -> bool {
+    rule.stages.is_empty() || reached_stages(rule, time).any(|stage| stage.groups.is_empty())
+}
+
+/// Wind-down stages of a rule that have begun by `time`, assuming the rule is active.
+///
+/// Times are compared by how long after the rule's start they fall, so
+/// stages of overnight rules past midnight come after those before it.
+fn reached_stages(rule: &ScheduleRule, time: NaiveTime) -> impl Iterator<Item = &WindDownStage> {
+    let start = rule.start_time.0.num_seconds_from_midnight();
+    let since_start =
+        move |t: NaiveTime| (t.num_seconds_from_midnight() + 24 * 3600 - start) % (24 * 3600);
+    let elapsed = since_start(time);
+    rule.stages.iter().filter(move |stage| since_start(stage.at.0) <= elapsed)
+}
+
+/// Build a temporary "Focus now" rule covering the next `minutes` minutes.
+pub fn focus_rule(minutes: u32, now: DateTime<Local>) -> ScheduleRule {
+    let end = now + Duration::minutes(minutes as i64);
+    focus_rule_until(format!("Focus now ({} min)", minutes), now, end)
+}
+
+/// A temporary rule blocking from `now` until `end`.
+pub fn focus_rule_until(name: String, now: DateTime<Local>, end: DateTime<Local>) -> ScheduleRule {
+    ScheduleRule {
+        name,
+        days: vec![now.weekday().into()],
+        start_time: NaiveTimeWrapper(now.time()),
+        end_time: NaiveTimeWrapper(end.time()),
+        expires_at: Some(end.timestamp()),
+        strictness: Strictness::default(),
+        stages: Vec::new(),
+    }
+}
+
+/// Whether `incoming` blocks at least whenever, and at least as strictly as, each rule in `rules`.
+///
+/// A disabled schedule blocks around the clock, so it keeps every rule.
+/// Used to refuse schedule edits that would weaken a strict rule while it is active.
+pub fn keeps_rules(incoming: &Schedule, rules: &[&ScheduleRule]) -> bool {
+    !incoming.enabled
+        || rules
+            .iter()
+            .all(|rule| incoming.rules.iter().any(|other| covers(other, rule)))
+}
+
+/// Whether replacing `current` with `incoming` would block less at some point.
+///
+/// Rules of `current` that expired before `now` are not counted.
+pub fn weakens_schedule(current: &Schedule, incoming: &Schedule, now: i64) -> bool {
+    if !current.enabled {
+        // Blocking around the clock; any schedule blocks less
+        return incoming.enabled;
+    }
+
+    let rules: Vec<&ScheduleRule> = current
+        .rules
+        .iter()
+        .filter(|rule| rule.expires_at.is_none_or(|expires_at| now < expires_at))
+        .collect();
+    !keeps_rules(incoming, &rules)
+}
+
+/// Whether `rule` applies whenever `other` does, at least as strictly.
+fn covers(rule: &ScheduleRule, other: &ScheduleRule) -> bool {
+    if rule.strictness < other.strictness {
+        return false;
+    }
+    // A wind-down rule blocks less than a plain rule over the same window
+    if !rule.stages.is_empty() && rule.stages != other.stages {
+        return false;
+    }
+
+    match (rule.expires_at, other.expires_at) {
+        // Temporary rules apply around the clock until they expire
+        (Some(end), Some(other_end)) => end >= other_end,
+        (None, Some(_)) | (Some(_), None) => false,
+        (None, None) => {
+            let rule_windows = daily_windows(rule);
+            other.days.iter().all(|day| rule.days.contains(day))
+                && daily_windows(other)
+                    .into_iter()
+                    .all(|(start, end)| rule_windows.iter().any(|(s, e)| *s <= start && end <= *e))
+        }
+    }
+}
+
+/// Seconds in a day.
+const DAY: u32 = 24 * 3600;
+
+/// Time ranges a rule covers on each of its days, in seconds since midnight.
+///
+/// Matches `rule_matches`: an overnight rule covers the end and the start of
+/// the same day.
+fn daily_windows(rule: &ScheduleRule) -> Vec<(u32, u32)> {
+    let start = rule.start_time.0.num_seconds_from_midnight();
+    let end = rule.end_time.0.num_seconds_from_midnight();
+
+    if start <= end {
+        vec![(start, end)]
+    } else {
+        vec![(0, end), (start, DAY)]
+    }
+    .into_iter()
+    .filter(|(start, end)| start < end)
+    .collect()
+}
+
+/// A window on `date` from `start` to `end` seconds after midnight, or None if
+/// either moment doesn't exist locally (skipped by a DST change).
+fn window(
+    rule: Option<String>,
+    groups: Vec<String>,
+    strictness: Strictness,
+    date: NaiveDate,
+    start: u32,
+    end: u32,
+) -> Option<BlockingWindow> {
+    let at = |seconds: u32| {
+        (date.and_time(NaiveTime::MIN) + Duration::seconds(seconds as i64))
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|moment| moment.timestamp())
+    };
+
+    Some(BlockingWindow {
+        rule,
+        groups,
+        strictness,
+        start: at(start)?,
+        end: at(end)?,
+    })
+}
+
+/// Start of the next local day.
+pub fn next_midnight(now: DateTime<Local>) -> DateTime<Local> {
+    let tomorrow = now.date_naive() + Duration::days(1);
+    tomorrow
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        // Midnight can be skipped by a DST change; the next hour exists
+        .unwrap_or_else(|| now + Duration::days(1))
+}
+
+/// Apply a schedule update without dropping unexpired temporary rules.
+///
+/// Temporary rules in the incoming schedule are ignored: they can only be
+/// added with FocusNow and disappear once they expire.
+pub fn merge_temporary_rules(current: &Schedule, mut incoming: Schedule, now: i64) -> Schedule {
+    incoming.rules.retain(|rule| rule.expires_at.is_none());
+    incoming.rules.extend(
+        current
+            .rules
+            .iter()
+            .filter(|rule| rule.expires_at.is_some_and(|expires_at| now < expires_at))
+            .cloned(),
+    );
+    incoming
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use blockandfocus_shared::NaiveTimeWrapper;
-    use chrono::NaiveTime;
+    use blockandfocus_shared::WeekdayWrapper;
+    use chrono::TimeZone;
 
     fn make_rule(name: &str, days: Vec<WeekdayWrapper>, start: &str, end: &str) -> ScheduleRule {
-        ScheduleRule {
-            name: name.to_string(),
-            days,
-            start_time: NaiveTimeWrapper(NaiveTime::parse_from_str(start, "%H:%M").unwrap()),
-            end_time: NaiveTimeWrapper(NaiveTime::parse_from_str(end, "%H:%M").unwrap()),
-        }
+        let time = |s| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        ScheduleRule::builder()
+            .name(name)
+            .days(days)
+            .between(time(start), time(end))
+            .build()
+            .unwrap()
     }
 
     #[test]
@@ -208,4 +629,303 @@ mod tests {
         let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
         assert!(!engine.rule_matches(&rule, Weekday::Mon, noon));
     }
+
+    #[test]
+    fn test_focus_rule() {
+        let now = Local::now();
+        let rule = focus_rule(30, now);
+
+        let engine = ScheduleEngine::new(Schedule {
+            enabled: true,
+            rules: vec![rule.clone()],
+        });
+
+        assert!(engine.is_blocking_time());
+        assert_eq!(engine.active_rule_name(), Some(rule.name.clone()));
+        assert!(!engine.rule_active(&rule, now + Duration::minutes(31)));
+    }
+
+    #[test]
+    fn test_merge_keeps_temporary_rules() {
+        let now = Local::now();
+        let work = make_rule("Work", vec![WeekdayWrapper::Mon], "09:00", "17:00");
+        let focus = focus_rule(30, now);
+        let mut expired = focus_rule(10, now - Duration::hours(1));
+        expired.name = "Expired".to_string();
+
+        let current = Schedule {
+            enabled: true,
+            rules: vec![work, focus.clone(), expired],
+        };
+
+        // The incoming schedule tries to drop everything
+        let merged = merge_temporary_rules(
+            &current,
+            Schedule {
+                enabled: true,
+                rules: vec![],
+            },
+            now.timestamp(),
+        );
+
+        let names: Vec<&str> = merged.rules.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec![focus.name.as_str()]);
+    }
+
+    #[test]
+    fn test_active_strictness_and_keeps_rules() {
+        let now = Local::now();
+        let mut relaxed = focus_rule(30, now);
+        relaxed.strictness = Strictness::Relaxed;
+        let mut strict = focus_rule(60, now);
+        strict.strictness = Strictness::Strict;
+        let schedule = Schedule {
+            enabled: true,
+            rules: vec![relaxed.clone(), strict.clone()],
+        };
+
+        let engine = ScheduleEngine::new(schedule.clone());
+        assert_eq!(engine.active_strictness(), Some(Strictness::Strict));
+        assert_eq!(ScheduleEngine::new(Schedule::default()).active_strictness(), None);
+
+        let active = engine.active_rules();
+        assert!(keeps_rules(&schedule, &active));
+        // A disabled schedule blocks around the clock
+        assert!(keeps_rules(&Schedule { enabled: false, ..schedule.clone() }, &active));
+
+        let mut weakened = schedule.clone();
+        weakened.rules[1].strictness = Strictness::Normal;
+        assert!(!keeps_rules(&weakened, &[&strict]));
+        assert!(keeps_rules(&weakened, &[&relaxed]));
+    }
+
+    #[test]
+    fn test_weakens_schedule() {
+        let weekdays = vec![WeekdayWrapper::Mon, WeekdayWrapper::Tue];
+        let schedule = |rules: Vec<ScheduleRule>| Schedule { enabled: true, rules };
+        let current = schedule(vec![make_rule("Work", weekdays.clone(), "09:00", "17:00")]);
+
+        // Longer hours, more days and extra rules block more
+        let longer = schedule(vec![make_rule("Work", weekdays.clone(), "08:00", "18:00")]);
+        assert!(!weakens_schedule(&current, &longer, 0));
+        let mut more_days = longer.clone();
+        more_days.rules[0] = make_rule("Work", weekdays.clone(), "09:00", "17:00");
+        more_days.rules[0].days.push(WeekdayWrapper::Wed);
+        assert!(!weakens_schedule(&current, &more_days, 0));
+        let overnight = schedule(vec![make_rule("Always", weekdays.clone(), "08:00", "07:00")]);
+        assert!(!weakens_schedule(&current, &overnight, 0));
+
+        // Shorter hours, fewer days, removed rules and lower strictness block less
+        let shorter = schedule(vec![make_rule("Work", weekdays.clone(), "10:00", "17:00")]);
+        assert!(weakens_schedule(&current, &shorter, 0));
+        let mut fewer_days = current.clone();
+        fewer_days.rules[0].days.pop();
+        assert!(weakens_schedule(&current, &fewer_days, 0));
+        assert!(weakens_schedule(&current, &schedule(vec![]), 0));
+        let mut relaxed = current.clone();
+        relaxed.rules[0].strictness = Strictness::Relaxed;
+        assert!(weakens_schedule(&current, &relaxed, 0));
+
+        // Disabling the schedule blocks around the clock; enabling it blocks less
+        let disabled = Schedule { enabled: false, ..current.clone() };
+        assert!(!weakens_schedule(&current, &disabled, 0));
+        assert!(weakens_schedule(&disabled, &current, 0));
+
+        // Expired temporary rules do not count
+        let mut focus = make_rule("Focus", weekdays.clone(), "12:00", "13:00");
+        focus.expires_at = Some(100);
+        let with_focus = schedule(vec![current.rules[0].clone(), focus]);
+        assert!(weakens_schedule(&with_focus, &current, 50));
+        assert!(!weakens_schedule(&with_focus, &current, 100));
+    }
+
+    #[test]
+    fn test_next_midnight() {
+        let now = Local.with_ymd_and_hms(2026, 3, 14, 15, 30, 0).unwrap();
+        let midnight = next_midnight(now);
+        assert_eq!(midnight.date_naive(), now.date_naive() + Duration::days(1));
+        assert_eq!(midnight.time(), NaiveTime::MIN);
+    }
+
+    #[test]
+    fn test_next_activation_and_snooze() {
+        let now = Local::now();
+        let soon = now + Duration::minutes(10);
+        let rule = make_rule(
+            "Deep Work",
+            vec![soon.weekday().into()],
+            &soon.format("%H:%M").to_string(),
+            &(soon + Duration::hours(1)).format("%H:%M").to_string(),
+        );
+
+        let mut engine = ScheduleEngine::new(Schedule {
+            enabled: true,
+            rules: vec![rule],
+        });
+
+        let activation = engine.next_activation(now).unwrap();
+        assert!(activation > now && activation <= soon);
+
+        // Snoozing for 30 minutes suppresses the rule right after it starts
+        engine.snooze(activation.timestamp(), 30);
+        let shortly_after = activation + Duration::minutes(5);
+        assert!(!engine.rule_active(&engine.schedule.rules[0], shortly_after));
+        assert!(engine.rule_active(&engine.schedule.rules[0], activation + Duration::minutes(31)));
+        assert_eq!(
+            engine.snoozed_until(now.timestamp()),
+            Some(activation.timestamp() + 30 * 60)
+        );
+    }
+
+    #[test]
+    fn test_vacation_suspends_regular_rules() {
+        let now = Local::now();
+        let today = now.date_naive();
+        let all_day = make_rule("All day", vec![now.weekday().into()], "00:00", "23:59");
+        let focus = focus_rule(30, now);
+
+        let mut engine = ScheduleEngine::new(Schedule {
+            enabled: true,
+            rules: vec![all_day.clone(), focus.clone()],
+        });
+        engine.set_vacations(vec![Vacation {
+            id: "v1".to_string(),
+            start: today,
+            end: today + Duration::days(1),
+            created_at: 0,
+        }]);
+
+        // Regular rules are suspended, temporary ones still apply
+        assert!(!engine.rule_active(&all_day, now));
+        assert!(engine.rule_active(&focus, now));
+        assert!(engine.vacation_on(today + Duration::days(2)).is_none());
+
+        // Vacation days are skipped when looking for the next activation
+        let activation = engine.next_activation(now).unwrap();
+        assert!(activation.date_naive() >= today + Duration::days(2));
+
+        // A disabled schedule stops blocking around the clock while on vacation
+        engine.update(Schedule {
+            enabled: false,
+            rules: vec![],
+        });
+        assert!(!engine.is_blocking_time());
+        engine.set_vacations(vec![]);
+        assert!(engine.is_blocking_time());
+    }
+
+    #[test]
+    fn test_wind_down_stages() {
+        let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let stage = |at: &str, groups: &[&str]| WindDownStage {
+            at: NaiveTimeWrapper(time(at)),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        };
+        let mut rule = make_rule("Wind down", vec![WeekdayWrapper::Mon], "21:00", "07:00");
+        rule.stages = vec![
+            stage("21:00", &["Video"]),
+            stage("22:00", &["Social"]),
+            stage("23:00", &[]),
+        ];
+
+        let reached = |at: &str| reached_stages(&rule, time(at)).count();
+        assert_eq!(reached("21:30"), 1);
+        assert_eq!(reached("22:00"), 2);
+        assert!(!blocks_everything(&rule, time("22:59")));
+        assert!(blocks_everything(&rule, time("23:00")));
+        // Past midnight the last stage still applies
+        assert!(blocks_everything(&rule, time("02:00")));
+
+        // A wind-down rule does not stand in for a plain rule over the same window
+        let plain = make_rule("Night", vec![WeekdayWrapper::Mon], "21:00", "07:00");
+        assert!(!covers(&rule, &plain));
+        assert!(covers(&plain, &rule));
+    }
+
+    /// Local timestamp of a time in March 2026 (the 2nd is a Monday).
+    fn march(day: u32, time: &str) -> i64 {
+        let time = NaiveTime::parse_from_str(time, "%H:%M").unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 3, day).unwrap();
+        date.and_time(time).and_local_timezone(Local).earliest().unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_timeline_overnight_vacation_and_snooze() {
+        let rule = make_rule(
+            "Night",
+            vec![WeekdayWrapper::Mon, WeekdayWrapper::Tue, WeekdayWrapper::Thu],
+            "22:00",
+            "06:00",
+        );
+        let mut engine = ScheduleEngine::new(Schedule {
+            enabled: true,
+            rules: vec![rule],
+        });
+        engine.set_vacations(vec![Vacation {
+            id: "v1".to_string(),
+            start: NaiveDate::from_ymd_opt(2026, 3, 5).unwrap(),
+            end: NaiveDate::from_ymd_opt(2026, 3, 5).unwrap(),
+            created_at: 0,
+        }]);
+        engine.snooze(march(2, "22:00"), 30);
+
+        let from = Local.timestamp_opt(march(2, "12:00"), 0).unwrap();
+        let spans: Vec<(i64, i64)> = engine
+            .timeline(from, from + Duration::days(7))
+            .iter()
+            .map(|window| (window.start, window.end))
+            .collect();
+
+        // Overnight rules cover the end and the start of each listed day, so
+        // Monday night runs on into Tuesday but Tuesday night stops at midnight
+        assert_eq!(
+            spans,
+            vec![
+                (march(2, "22:30"), march(3, "06:00")),
+                (march(3, "22:00"), march(4, "00:00")),
+                (march(9, "00:00"), march(9, "06:00")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timeline_wind_down_and_disabled_schedule() {
+        let stage = |at: &str, groups: &[&str]| WindDownStage {
+            at: NaiveTimeWrapper(NaiveTime::parse_from_str(at, "%H:%M").unwrap()),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        };
+        let mut rule = make_rule("Wind down", vec![WeekdayWrapper::Mon], "21:00", "23:30");
+        rule.stages = vec![stage("22:00", &["Video"]), stage("23:00", &[])];
+        let mut engine = ScheduleEngine::new(Schedule {
+            enabled: true,
+            rules: vec![rule],
+        });
+
+        let from = Local.timestamp_opt(march(2, "12:00"), 0).unwrap();
+        let until = Local.timestamp_opt(march(3, "12:00"), 0).unwrap();
+        let windows = engine.timeline(from, until);
+        let spans: Vec<(i64, i64, Vec<String>)> = windows
+            .into_iter()
+            .map(|window| (window.start, window.end, window.groups))
+            .collect();
+
+        // Nothing is blocked before the first stage
+        assert_eq!(
+            spans,
+            vec![
+                (march(2, "22:00"), march(2, "23:00"), vec!["Video".to_string()]),
+                (march(2, "23:00"), march(2, "23:30"), Vec::new()),
+            ]
+        );
+
+        // A disabled schedule blocks around the clock
+        engine.update(Schedule {
+            enabled: false,
+            rules: vec![],
+        });
+        let windows = engine.timeline(from, until);
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].rule, None);
+        assert_eq!((windows[0].start, windows[0].end), (from.timestamp(), until.timestamp()));
+    }
 }
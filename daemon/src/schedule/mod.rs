@@ -1,5 +1,10 @@
 //! Schedule engine for time-based blocking.
 
 mod engine;
+mod pending;
 
-pub use engine::ScheduleEngine;
+pub use engine::{
+    focus_rule, focus_rule_until, keeps_rules, merge_temporary_rules, next_midnight,
+    weakens_schedule, ScheduleEngine,
+};
+pub use pending::PendingScheduleMonitor;
@@ -0,0 +1,62 @@
+//! Applies schedule edits deferred until midnight.
+
+use crate::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use super::engine::{merge_temporary_rules, weakens_schedule};
+
+/// How often a pending edit is checked for being due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Applies a pending schedule edit once its time has come.
+pub struct PendingScheduleMonitor;
+
+impl PendingScheduleMonitor {
+    /// Run the monitor loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            apply_due(&state).await;
+        }
+    }
+}
+
+/// Apply the pending edit if it is due and nothing forbids weakening right now.
+async fn apply_due(state: &Arc<RwLock<AppState>>) {
+    let now = chrono::Utc::now().timestamp();
+    let due = |applies_at: i64| now >= applies_at;
+
+    // Most checks find nothing to do; don't hold up DNS queries for them
+    let pending = state.read().await.store.get().pending_schedule.clone();
+    let Some(pending) = pending.filter(|pending| due(pending.applies_at)) else {
+        return;
+    };
+
+    let mut state_guard = state.write().await;
+
+    // Rules added with FocusNow since the edit was made are kept
+    let current = state_guard.config.get();
+    let schedule = merge_temporary_rules(&current.schedule, pending.schedule, now);
+
+    let locked = (state_guard.settings_lock_remaining_minutes().is_some()
+        || state_guard.store.is_unreadable())
+        && weakens_schedule(&current.schedule, &schedule, now);
+    if locked || !state_guard.keeps_strict_rules(&schedule) {
+        debug!("Pending schedule edit waits for the settings lock or strict rules to end");
+        return;
+    }
+
+    if let Err(e) = state_guard.apply_schedule(schedule).await {
+        warn!("Failed to apply pending schedule edit: {:#}", e);
+        return;
+    }
+    if let Err(e) = state_guard.store.set_pending_schedule(None) {
+        warn!("Failed to clear pending schedule edit: {:#}", e);
+    }
+    info!(submitted_at = pending.submitted_at, "Deferred schedule edit applied");
+}
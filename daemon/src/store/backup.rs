@@ -0,0 +1,106 @@
+//! Backups of the configuration and persisted state in a single archive.
+
+use super::PersistentState;
+use crate::config::check_config;
+use anyhow::{Context, Result};
+use blockandfocus_shared::Config;
+use serde::{Deserialize, Serialize};
+
+/// Marks a JSON document as a backup archive.
+const FORMAT: &str = "blockandfocus-backup";
+
+/// Version of the archive layout; archives from newer layouts are refused.
+const VERSION: u32 = 1;
+
+/// Everything needed to rebuild an installation: the configuration and the
+/// state file (bypass history, budgets, activity history, settings lock...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupArchive {
+    pub format: String,
+    pub version: u32,
+    /// Unix timestamp the backup was taken
+    pub created_at: i64,
+    /// Version of the daemon that took it
+    pub daemon_version: String,
+    pub config: Config,
+    pub state: PersistentState,
+}
+
+impl BackupArchive {
+    pub fn new(config: Config, state: PersistentState, now: i64) -> Self {
+        Self {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created_at: now,
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            config,
+            state,
+        }
+    }
+
+    /// The archive as JSON text.
+    pub fn to_text(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize backup")
+    }
+
+    /// Parse an archive and check it can be restored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(text).context("Backup is not JSON")?;
+        if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+            anyhow::bail!("Not a BlockAndFocus backup");
+        }
+        let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or_default();
+        if version != u64::from(VERSION) {
+            anyhow::bail!("Unsupported backup version {} (expected {})", version, VERSION);
+        }
+
+        let archive: Self = serde_json::from_value(value).context("Backup is damaged")?;
+        let problems = check_config(&archive.config);
+        if !problems.is_empty() {
+            anyhow::bail!("Backup config has problems: {}", problems.join("; "));
+        }
+
+        Ok(archive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::StateStore;
+
+    #[test]
+    fn test_round_trip() {
+        let mut config = Config::default();
+        config.blocking.domains = vec!["example.org".parse().unwrap()];
+        let mut store = StateStore::in_memory();
+        store.record_snooze("2025-06-09").unwrap();
+
+        let text = BackupArchive::new(config, store.get().clone(), 1000).to_text().unwrap();
+        let archive = BackupArchive::parse(&text).unwrap();
+        assert_eq!(archive.created_at, 1000);
+        assert_eq!(archive.config.blocking.domains[0].as_str(), "example.org");
+        assert!(archive.state.last_snooze_date.is_some());
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        let archive = BackupArchive::new(Config::default(), PersistentState::default(), 1000);
+        let valid = serde_json::to_value(&archive).unwrap();
+
+        let with = |key: &str, value: serde_json::Value| {
+            let mut changed = valid.clone();
+            changed[key] = value;
+            BackupArchive::parse(&changed.to_string()).unwrap_err().to_string()
+        };
+        assert_eq!(with("format", "something-else".into()), "Not a BlockAndFocus backup");
+        assert_eq!(with("version", 2.into()), "Unsupported backup version 2 (expected 1)");
+        assert_eq!(with("state", "gone".into()), "Backup is damaged");
+
+        let mut config = Config::default();
+        config.dns.upstream.clear();
+        let text = BackupArchive::new(config, PersistentState::default(), 1000).to_text().unwrap();
+        assert!(BackupArchive::parse(&text).unwrap_err().to_string().contains("dns.upstream"));
+        assert!(BackupArchive::parse("{").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Encryption of stored files.
+//!
+//! Files are sealed with ChaCha20-Poly1305 under a key the daemon keeps in the
+//! System keychain (in a root-only file for development). An encrypted file
+//! starts with a magic header, so files written before encryption was turned
+//! on (or after it was turned off) are still read.
+
+use anyhow::{anyhow, Context, Result};
+use blockandfocus_shared::{from_hex, to_hex};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use tracing::info;
+
+/// Header identifying an encrypted file (and its format version).
+const MAGIC: &[u8] = b"BAFENC1\n";
+
+/// Permissions of the development key file.
+const KEY_FILE_MODE: u32 = 0o600;
+
+/// Keychain service the storage key is saved under.
+#[cfg(target_os = "macos")]
+const KEYCHAIN_SERVICE: &str = "com.blockandfocus.storage";
+
+/// Keychain account the storage key is saved under.
+#[cfg(target_os = "macos")]
+const KEYCHAIN_ACCOUNT: &str = "blockandfocus";
+
+/// Keychain holding the storage key, readable only by root.
+#[cfg(target_os = "macos")]
+const SYSTEM_KEYCHAIN: &str = "/Library/Keychains/System.keychain";
+
+/// Encrypts and decrypts stored files with the daemon's storage key.
+pub struct StorageCipher {
+    key: LessSafeKey,
+}
+
+impl StorageCipher {
+    /// Create a cipher from a 32-byte key.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, key)
+            .map_err(|_| anyhow!("Storage key must be 32 bytes"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    /// Load the storage key, creating one the first time.
+    pub fn load(is_dev: bool) -> Result<Self> {
+        let key = if is_dev {
+            load_or_create_file_key(blockandfocus_shared::STORAGE_KEY_PATH_DEV)?
+        } else {
+            load_or_create_keychain_key()?
+        };
+        Self::new(&key)
+    }
+
+    /// Whether `data` was written by `encrypt`.
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    /// Encrypt `plaintext` with a fresh random nonce.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce_bytes = rand::random::<[u8; NONCE_LEN]>();
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::from(MAGIC),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("Failed to encrypt data"))?;
+
+        let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&nonce_bytes);
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt data written by `encrypt`, failing if it was altered.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let rest = data
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| anyhow!("Data is not encrypted"))?;
+        if rest.len() < NONCE_LEN {
+            return Err(anyhow!("Encrypted data is truncated"));
+        }
+        let (nonce_bytes, sealed) = rest.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
+            .map_err(|_| anyhow!("Invalid nonce"))?;
+
+        let mut sealed = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(MAGIC), &mut sealed)
+            .map_err(|_| anyhow!("Encrypted data is corrupt or the key is wrong"))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// Read the key from the System keychain, adding a random one if there is none.
+#[cfg(target_os = "macos")]
+fn load_or_create_keychain_key() -> Result<Vec<u8>> {
+    if let Some(key) = find_keychain_key()? {
+        return Ok(key);
+    }
+
+    // The key goes to `security` on stdin, so it never shows up in the
+    // process list the way a `-w <key>` argument would
+    let key = rand::random::<[u8; 32]>().to_vec();
+    let mut child = std::process::Command::new("/usr/bin/security")
+        .arg("-i")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .context("Failed to run security")?;
+    let add = format!(
+        "add-generic-password -s {} -a {} -U -w {} {}\n",
+        KEYCHAIN_SERVICE,
+        KEYCHAIN_ACCOUNT,
+        to_hex(&key),
+        SYSTEM_KEYCHAIN
+    );
+    let mut stdin = child.stdin.take().context("Failed to open the stdin of security")?;
+    stdin.write_all(add.as_bytes()).context("Failed to pass the storage key to security")?;
+    drop(stdin);
+    child.wait().context("Failed to run security")?;
+
+    // `security -i` exits successfully even when a command fails
+    if find_keychain_key()?.as_ref() != Some(&key) {
+        return Err(anyhow!("Failed to add the storage key to the keychain"));
+    }
+
+    info!("Created storage key in the System keychain");
+    Ok(key)
+}
+
+/// Read the key from the System keychain, if it has one.
+#[cfg(target_os = "macos")]
+fn find_keychain_key() -> Result<Option<Vec<u8>>> {
+    let output = std::process::Command::new("/usr/bin/security")
+        .args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w"])
+        .arg(SYSTEM_KEYCHAIN)
+        .output()
+        .context("Failed to run security")?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let hex = String::from_utf8_lossy(&output.stdout);
+    from_hex(hex.trim())
+        .map(Some)
+        .ok_or_else(|| anyhow!("Storage key in the keychain is invalid"))
+}
+
+/// The keychain is only available on macOS.
+#[cfg(not(target_os = "macos"))]
+fn load_or_create_keychain_key() -> Result<Vec<u8>> {
+    Err(anyhow!("The keychain is not available on this platform"))
+}
+
+/// Read the key from a file, creating a random one if there is none yet.
+///
+/// The file is created readable by root only, so the key is never exposed.
+fn load_or_create_file_key(path: &str) -> Result<Vec<u8>> {
+    if let Ok(content) = std::fs::read_to_string(path) {
+        if let Some(key) = from_hex(content.trim()) {
+            return Ok(key);
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove invalid storage key: {}", path))?;
+    }
+
+    let key = rand::random::<[u8; 32]>().to_vec();
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(KEY_FILE_MODE)
+        .open(path)
+        .with_context(|| format!("Failed to create storage key: {}", path))?;
+    file.write_all(to_hex(&key).as_bytes())
+        .with_context(|| format!("Failed to write storage key: {}", path))?;
+
+    info!("Created storage key at {}", path);
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_tampering() {
+        let cipher = StorageCipher::new(&[7; 32]).unwrap();
+        let sealed = cipher.encrypt(b"{\"bypass_history\":[]}").unwrap();
+        assert!(StorageCipher::is_encrypted(&sealed));
+        assert!(!StorageCipher::is_encrypted(b"{}"));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), b"{\"bypass_history\":[]}");
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        let other = StorageCipher::new(&[8; 32]).unwrap();
+        assert!(other.decrypt(&sealed).is_err());
+        assert!(cipher.decrypt(&sealed[..MAGIC.len() + 4]).is_err());
+    }
+
+    #[test]
+    fn test_key_file_is_created_private_and_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("baf-storage-key-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("storage.key");
+        let path = path.to_str().unwrap();
+
+        let key = load_or_create_file_key(path).unwrap();
+        assert_eq!(key.len(), 32);
+        let mode = std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, KEY_FILE_MODE);
+        assert_eq!(load_or_create_file_key(path).unwrap(), key);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,179 @@
+//! Settings lock: a snapshot of the blocking settings that may only grow
+//! stronger until the lock ends.
+//!
+//! The snapshot lives in the state file rather than the config file, so the
+//! lock cannot be escaped by editing the config and restarting the daemon:
+//! anything the edit weakened is restored at startup.
+
+use blockandfocus_shared::{BlocklistSubscription, Config, DomainName, Schedule};
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::keeps_rules;
+
+/// Blocking settings frozen until `until`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsLock {
+    /// Unix timestamp when the lock ends
+    pub until: i64,
+
+    /// Whether blocking was enabled
+    pub blocking_enabled: bool,
+
+    /// Whether dry-run mode was on
+    pub dry_run: bool,
+
+    /// Blocked domains
+    pub domains: Vec<DomainName>,
+
+    /// Names of enabled blocklist groups
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// Schedule, including its temporary rules
+    pub schedule: Schedule,
+
+    /// Enabled blocklist subscriptions
+    pub subscriptions: Vec<BlocklistSubscription>,
+}
+
+impl SettingsLock {
+    /// Snapshot the blocking settings of a configuration.
+    pub fn new(config: &Config, until: i64) -> Self {
+        Self {
+            until,
+            blocking_enabled: config.blocking.enabled,
+            dry_run: config.blocking.dry_run,
+            domains: config.blocking.domains.clone(),
+            groups: config
+                .blocking
+                .groups
+                .iter()
+                .filter(|group| group.enabled)
+                .map(|group| group.name.clone())
+                .collect(),
+            schedule: config.schedule.clone(),
+            subscriptions: config
+                .subscriptions
+                .lists
+                .iter()
+                .filter(|list| list.enabled)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Undo anything in `config` that is weaker than the snapshot.
+    ///
+    /// Additions made since the lock was taken are kept. Returns whether
+    /// anything had to be restored.
+    pub fn restore(&self, config: &mut Config, now: i64) -> bool {
+        let mut restored = false;
+
+        if self.blocking_enabled && !config.blocking.enabled {
+            config.blocking.enabled = true;
+            restored = true;
+        }
+        if !self.dry_run && config.blocking.dry_run {
+            config.blocking.dry_run = false;
+            restored = true;
+        }
+
+        for domain in &self.domains {
+            if !config.blocking.domains.contains(domain) {
+                config.blocking.domains.push(domain.clone());
+                restored = true;
+            }
+        }
+
+        for group in config.blocking.groups.iter_mut() {
+            if !group.enabled && self.groups.contains(&group.name) {
+                group.enabled = true;
+                restored = true;
+            }
+        }
+
+        // A disabled schedule blocks around the clock
+        if !self.schedule.enabled && config.schedule.enabled {
+            config.schedule.enabled = false;
+            restored = true;
+        }
+        let unexpired = self
+            .schedule
+            .rules
+            .iter()
+            .filter(|rule| rule.expires_at.is_none_or(|expires_at| now < expires_at));
+        for rule in unexpired {
+            if !keeps_rules(&config.schedule, &[rule]) {
+                config.schedule.rules.push(rule.clone());
+                restored = true;
+            }
+        }
+
+        for list in &self.subscriptions {
+            match config.subscriptions.lists.iter_mut().find(|l| l.url == list.url) {
+                Some(existing) if existing.enabled => {}
+                Some(existing) => {
+                    existing.enabled = true;
+                    restored = true;
+                }
+                None => {
+                    config.subscriptions.lists.push(list.clone());
+                    restored = true;
+                }
+            }
+        }
+
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::{NaiveTimeWrapper, ScheduleRule, WeekdayWrapper};
+    use chrono::NaiveTime;
+
+    fn time(hour: u32) -> NaiveTimeWrapper {
+        NaiveTimeWrapper(NaiveTime::from_hms_opt(hour, 0, 0).unwrap())
+    }
+
+    fn rule(name: &str) -> ScheduleRule {
+        ScheduleRule::builder()
+            .name(name)
+            .days([WeekdayWrapper::Mon])
+            .between(time(9).0, time(17).0)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_restore_undoes_weakening_only() {
+        let mut config = Config::default();
+        config.blocking.domains = vec!["a.com".parse().unwrap(), "b.com".parse().unwrap()];
+        config.schedule.enabled = true;
+        config.schedule.rules = vec![rule("Work")];
+        let lock = SettingsLock::new(&config, 1000);
+
+        // Nothing to restore while the config is unchanged or stronger
+        config.blocking.domains.push("c.com".parse().unwrap());
+        assert!(!lock.restore(&mut config, 0));
+
+        config.blocking.domains.retain(|d| d != "a.com");
+        config.blocking.dry_run = true;
+        config.schedule.rules[0].end_time = time(10);
+        assert!(lock.restore(&mut config, 0));
+
+        assert_eq!(config.blocking.domains, vec!["b.com", "c.com", "a.com"]);
+        assert!(!config.blocking.dry_run);
+        assert!(config.schedule.rules.contains(&rule("Work")));
+        assert!(!lock.restore(&mut config, 0));
+
+        // Disabling the schedule blocks around the clock; enabling it is undone
+        config.schedule.enabled = false;
+        assert!(!lock.restore(&mut config, 0));
+        let lock = SettingsLock::new(&config, 1000);
+        config.schedule.enabled = true;
+        assert!(lock.restore(&mut config, 0));
+        assert!(!config.schedule.enabled);
+    }
+}
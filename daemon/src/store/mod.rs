@@ -0,0 +1,12 @@
+//! Persistent runtime state (bypass history and other daemon-managed data).
+
+mod backup;
+#[cfg_attr(not(feature = "encryption"), path = "plaintext.rs")]
+mod crypto;
+mod lock;
+mod state;
+
+pub use backup::BackupArchive;
+pub use crypto::StorageCipher;
+pub use lock::SettingsLock;
+pub use state::{BypassRecord, DailyBlocks, PersistentState, StateStore, SubscriptionHealth};
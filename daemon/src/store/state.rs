@@ -0,0 +1,846 @@
+//! State persistence.
+//!
+//! Unlike the configuration, this file is owned by the daemon and is not meant
+//! to be edited by hand.
+
+use anyhow::{Context, Result};
+use blockandfocus_shared::{
+    BlocklistDiff, PendingScheduleChange, Vacation, STATE_PATH, STATE_PATH_DEV,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use super::crypto::StorageCipher;
+use super::lock::SettingsLock;
+
+/// Maximum number of bypass records kept in history.
+const MAX_BYPASS_HISTORY: usize = 1000;
+
+/// Maximum number of subscription diffs kept.
+const MAX_SUBSCRIPTION_DIFFS: usize = 20;
+
+/// Days of blocking activity kept (52 weeks).
+const MAX_ACTIVITY_DAYS: i64 = 52 * 7;
+
+/// Permissions of the state file, which holds bypass reasons and history.
+const FILE_MODE: u32 = 0o600;
+
+/// A granted bypass.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BypassRecord {
+    /// Unix timestamp when the bypass was granted
+    pub started_at: i64,
+
+    /// Requested duration in minutes
+    pub duration_minutes: u32,
+
+    /// Reason given by the user
+    #[serde(default)]
+    pub reason: Option<String>,
+
+    /// Unix timestamp when the bypass was cancelled early
+    #[serde(default)]
+    pub cancelled_at: Option<i64>,
+}
+
+/// Bypass minutes consumed on a given day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BypassBudgetDay {
+    /// Local date (YYYY-MM-DD) the counter belongs to
+    pub date: String,
+
+    /// Bypass minutes used on that date
+    pub used_minutes: u32,
+}
+
+/// Estimated usage of a blocklist group during one week.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupUsageWeek {
+    /// Group name
+    pub group: String,
+
+    /// Local date (YYYY-MM-DD) of the Monday starting the week
+    pub week_start: String,
+
+    /// Estimated minutes of use
+    pub used_minutes: u32,
+}
+
+/// Estimated usage of an allowance domain during one day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainUsageDay {
+    /// Allowance domain
+    pub domain: String,
+
+    /// Local date (YYYY-MM-DD)
+    pub date: String,
+
+    /// Estimated minutes of use
+    pub used_minutes: u32,
+}
+
+/// Blocked queries during one day, by hour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyBlocks {
+    /// Local date (YYYY-MM-DD)
+    pub date: String,
+
+    /// Blocked queries in each local hour (24 entries)
+    pub hours: Vec<u32>,
+}
+
+/// Refresh health of a subscribed blocklist, by URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubscriptionHealth {
+    /// URL of the list
+    pub url: String,
+
+    /// Unix timestamp of the last download attempt
+    pub last_fetch_at: Option<i64>,
+
+    /// Unix timestamp of the last successful download
+    pub last_success_at: Option<i64>,
+
+    /// HTTP status of the last download attempt
+    pub http_status: Option<u16>,
+
+    /// Domains taken from the list
+    pub entries: u64,
+
+    /// Lines of the list that were not valid domains
+    pub invalid_lines: u64,
+
+    /// Error of the last download attempt
+    pub last_error: Option<String>,
+}
+
+/// Everything the daemon persists besides the configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersistentState {
+    /// Granted bypasses, oldest first
+    #[serde(default)]
+    pub bypass_history: Vec<BypassRecord>,
+
+    /// Daily bypass budget accounting
+    #[serde(default)]
+    pub bypass_budget: BypassBudgetDay,
+
+    /// Weekly usage of blocklist groups (current week only)
+    #[serde(default)]
+    pub group_usage: Vec<GroupUsageWeek>,
+
+    /// Daily usage of allowance domains (current day only)
+    #[serde(default)]
+    pub domain_usage: Vec<DomainUsageDay>,
+
+    /// Local date (YYYY-MM-DD) the schedule was last snoozed
+    #[serde(default)]
+    pub last_snooze_date: Option<String>,
+
+    /// Refresh health of subscribed blocklists
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionHealth>,
+
+    /// Recent changes to subscribed lists, oldest first
+    #[serde(default)]
+    pub subscription_diffs: Vec<BlocklistDiff>,
+
+    /// Blocking settings frozen by LockSettings
+    #[serde(default)]
+    pub settings_lock: Option<SettingsLock>,
+
+    /// Schedule edit that reduces blocking, waiting for midnight
+    #[serde(default)]
+    pub pending_schedule: Option<PendingScheduleChange>,
+
+    /// Upcoming and current vacations, earliest first
+    #[serde(default)]
+    pub vacations: Vec<Vacation>,
+
+    /// Blocked queries per hour over the last 52 weeks, oldest day first
+    #[serde(default)]
+    pub block_activity: Vec<DailyBlocks>,
+
+    /// Unix timestamp of the last maintenance run
+    #[serde(default)]
+    pub last_maintenance_at: Option<i64>,
+
+    /// Week (its Monday, as YYYY-MM-DD) the last weekly report was sent for
+    #[serde(default)]
+    pub last_report_week: Option<String>,
+}
+
+/// Store for daemon-managed state, saved as JSON after every change.
+pub struct StateStore {
+    state: PersistentState,
+    path: Option<String>,
+    /// Encrypts the file when storage encryption is on
+    cipher: Option<Arc<StorageCipher>>,
+    /// Leaves the file alone: it couldn't be read, or encryption is on but
+    /// the key is unavailable
+    read_only: bool,
+    /// The file exists but couldn't be read, so it may hold a settings lock
+    unreadable: bool,
+}
+
+impl StateStore {
+    /// Load state from disk, starting fresh if the file is missing.
+    ///
+    /// With `encrypt` the file is written encrypted from now on. An encrypted
+    /// file is read whether or not `encrypt` is set. A file that can't be read
+    /// (corrupt, or encrypted and the key is unavailable) is left as it is and
+    /// the state is kept in memory only, as it is when encryption is on but
+    /// the key is unavailable, so the file is never replaced by fresh or
+    /// plaintext state.
+    pub fn load(is_dev: bool, encrypt: bool) -> Self {
+        let path = if is_dev { STATE_PATH_DEV } else { STATE_PATH };
+        Self::open(path, encrypt, || StorageCipher::load(is_dev))
+    }
+
+    /// Load the state file at `path`, getting the key from `load_key` if needed.
+    pub fn open(
+        path: &str,
+        encrypt: bool,
+        load_key: impl FnOnce() -> Result<StorageCipher>,
+    ) -> Self {
+        let mut unreadable = false;
+        let data = match fs::read(path) {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("Could not read state from {}: {}, starting fresh", path, e);
+                unreadable = true;
+                None
+            }
+        };
+        let encrypted = data.as_deref().is_some_and(StorageCipher::is_encrypted);
+
+        let needs_key = encrypt || encrypted;
+        let cipher = if needs_key {
+            match load_key() {
+                Ok(cipher) => Some(Arc::new(cipher)),
+                Err(e) => {
+                    error!("Storage key unavailable: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let state = if let Some(data) = data {
+            match Self::decode(&data, cipher.as_deref()) {
+                Ok(state) => {
+                    info!("Loaded state from {}", path);
+                    state
+                }
+                Err(e) => {
+                    warn!("Could not load state from {}: {:#}, starting fresh", path, e);
+                    unreadable = true;
+                    PersistentState::default()
+                }
+            }
+        } else {
+            PersistentState::default()
+        };
+
+        let read_only = unreadable || (needs_key && cipher.is_none());
+        if unreadable {
+            error!("Not saving state to {}, settings stay locked until it can be read", path);
+        } else if read_only {
+            error!("Not saving state to {}, the storage key is unavailable", path);
+        }
+
+        Self {
+            state,
+            path: Some(path.to_string()),
+            cipher: cipher.filter(|_| encrypt),
+            read_only,
+            unreadable,
+        }
+    }
+
+    /// Parse the state file, decrypting it if needed.
+    fn decode(data: &[u8], cipher: Option<&StorageCipher>) -> Result<PersistentState> {
+        let plaintext;
+        let content = if StorageCipher::is_encrypted(data) {
+            let cipher = cipher.context("State file is encrypted but the key is unavailable")?;
+            plaintext = cipher.decrypt(data).context("Failed to decrypt state file")?;
+            &plaintext[..]
+        } else {
+            data
+        };
+        serde_json::from_slice(content).context("Failed to parse state file")
+    }
+
+    /// Create a store that is never written to disk.
+    pub fn in_memory() -> Self {
+        Self {
+            state: PersistentState::default(),
+            path: None,
+            cipher: None,
+            read_only: false,
+            unreadable: false,
+        }
+    }
+
+    /// Whether the state file exists but couldn't be read. Settings may have
+    /// been locked, so they must not be weakened until it is fixed.
+    pub fn is_unreadable(&self) -> bool {
+        self.unreadable
+    }
+
+    /// Whether the state is kept in a file (not for an in-memory store).
+    pub fn is_on_disk(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// The cipher the state file is written with, when storage encryption
+    /// is on and the key is available.
+    pub fn cipher(&self) -> Option<&Arc<StorageCipher>> {
+        self.cipher.as_ref()
+    }
+
+    /// Get the current state.
+    pub fn get(&self) -> &PersistentState {
+        &self.state
+    }
+
+    /// Update and persist the state.
+    pub fn update<F>(&mut self, updater: F) -> Result<()>
+    where
+        F: FnOnce(&mut PersistentState),
+    {
+        updater(&mut self.state);
+        self.save()
+    }
+
+    /// Replace the whole state, keeping the current one if the new one can't be saved.
+    pub fn replace(&mut self, state: PersistentState) -> Result<()> {
+        let previous = std::mem::replace(&mut self.state, state);
+        if let Err(e) = self.save() {
+            self.state = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Record a granted bypass.
+    pub fn record_bypass(&mut self, record: BypassRecord) -> Result<()> {
+        self.update(|state| {
+            state.bypass_history.push(record);
+            let excess = state.bypass_history.len().saturating_sub(MAX_BYPASS_HISTORY);
+            state.bypass_history.drain(..excess);
+        })
+    }
+
+    /// Mark the most recent bypass as cancelled, if it is still running.
+    pub fn record_bypass_cancelled(&mut self, now: i64) -> Result<()> {
+        self.update(|state| {
+            if let Some(last) = state.bypass_history.last_mut() {
+                let ends_at = last.started_at + last.duration_minutes as i64 * 60;
+                if last.cancelled_at.is_none() && now < ends_at {
+                    last.cancelled_at = Some(now);
+                }
+            }
+        })
+    }
+
+    /// Delete the bypass history (the daily budget is kept).
+    pub fn clear_bypass_history(&mut self) -> Result<()> {
+        self.update(|state| state.bypass_history.clear())
+    }
+
+    /// Add `count` blocked queries to an hour of a local date, dropping days
+    /// older than 52 weeks.
+    pub fn add_blocks(&mut self, date: NaiveDate, hour: u32, count: u32) -> Result<()> {
+        let cutoff = date - chrono::Duration::days(MAX_ACTIVITY_DAYS);
+        let cutoff = cutoff.format("%Y-%m-%d").to_string();
+        let date = date.format("%Y-%m-%d").to_string();
+        self.update(|state| {
+            state.block_activity.retain(|day| day.date > cutoff);
+            if state.block_activity.last().is_none_or(|day| day.date != date) {
+                state.block_activity.push(DailyBlocks {
+                    date,
+                    hours: vec![0; 24],
+                });
+            }
+            if let Some(blocked) = state
+                .block_activity
+                .last_mut()
+                .and_then(|day| day.hours.get_mut(hour as usize))
+            {
+                *blocked = blocked.saturating_add(count);
+            }
+        })
+    }
+
+    /// Delete the blocking activity history.
+    pub fn clear_block_activity(&mut self) -> Result<()> {
+        self.update(|state| state.block_activity.clear())
+    }
+
+    /// Bypass minutes used on the given local date.
+    pub fn bypass_minutes_used(&self, date: &str) -> u32 {
+        let budget = &self.state.bypass_budget;
+        if budget.date == date {
+            budget.used_minutes
+        } else {
+            0
+        }
+    }
+
+    /// Charge bypass minutes to the given local date.
+    pub fn charge_bypass_minutes(&mut self, date: &str, minutes: u32) -> Result<()> {
+        self.update(|state| {
+            let budget = &mut state.bypass_budget;
+            if budget.date != date {
+                budget.date = date.to_string();
+                budget.used_minutes = 0;
+            }
+            budget.used_minutes = budget.used_minutes.saturating_add(minutes);
+        })
+    }
+
+    /// Give back unused bypass minutes (only if charged on the same date).
+    pub fn refund_bypass_minutes(&mut self, date: &str, minutes: u32) -> Result<()> {
+        self.update(|state| {
+            let budget = &mut state.bypass_budget;
+            if budget.date == date {
+                budget.used_minutes = budget.used_minutes.saturating_sub(minutes);
+            }
+        })
+    }
+
+    /// Estimated minutes a group was used during the given week.
+    pub fn group_minutes_used(&self, group: &str, week_start: &str) -> u32 {
+        self.state
+            .group_usage
+            .iter()
+            .find(|u| u.group == group && u.week_start == week_start)
+            .map_or(0, |u| u.used_minutes)
+    }
+
+    /// Add usage minutes to a group for the given week, dropping older weeks.
+    pub fn add_group_minutes(&mut self, group: &str, week_start: &str, minutes: u32) -> Result<()> {
+        self.update(|state| {
+            state.group_usage.retain(|u| u.week_start == week_start);
+            match state.group_usage.iter_mut().find(|u| u.group == group) {
+                Some(usage) => usage.used_minutes = usage.used_minutes.saturating_add(minutes),
+                None => state.group_usage.push(GroupUsageWeek {
+                    group: group.to_string(),
+                    week_start: week_start.to_string(),
+                    used_minutes: minutes,
+                }),
+            }
+        })
+    }
+
+    /// Estimated minutes an allowance domain was used on the given date.
+    pub fn domain_minutes_used(&self, domain: &str, date: &str) -> u32 {
+        self.state
+            .domain_usage
+            .iter()
+            .find(|u| u.domain == domain && u.date == date)
+            .map_or(0, |u| u.used_minutes)
+    }
+
+    /// Add usage minutes to an allowance domain for the given date, dropping older days.
+    pub fn add_domain_minutes(&mut self, domain: &str, date: &str, minutes: u32) -> Result<()> {
+        self.update(|state| {
+            state.domain_usage.retain(|u| u.date == date);
+            match state.domain_usage.iter_mut().find(|u| u.domain == domain) {
+                Some(usage) => usage.used_minutes = usage.used_minutes.saturating_add(minutes),
+                None => state.domain_usage.push(DomainUsageDay {
+                    domain: domain.to_string(),
+                    date: date.to_string(),
+                    used_minutes: minutes,
+                }),
+            }
+        })
+    }
+
+    /// Whether the schedule was already snoozed on the given date.
+    pub fn snoozed_on(&self, date: &str) -> bool {
+        self.state.last_snooze_date.as_deref() == Some(date)
+    }
+
+    /// Record that the schedule was snoozed on the given date.
+    pub fn record_snooze(&mut self, date: &str) -> Result<()> {
+        self.update(|state| state.last_snooze_date = Some(date.to_string()))
+    }
+
+    /// Refresh health of a subscribed list.
+    pub fn subscription_health(&self, url: &str) -> Option<&SubscriptionHealth> {
+        self.state.subscriptions.iter().find(|h| h.url == url)
+    }
+
+    /// Record the refresh health of subscribed lists, replacing earlier records per URL.
+    pub fn record_subscription_health(&mut self, health: Vec<SubscriptionHealth>) -> Result<()> {
+        self.update(|state| {
+            for health in health {
+                match state.subscriptions.iter_mut().find(|h| h.url == health.url) {
+                    Some(existing) => *existing = health,
+                    None => state.subscriptions.push(health),
+                }
+            }
+        })
+    }
+
+    /// Record changes to subscribed lists, keeping only the most recent ones.
+    pub fn record_subscription_diffs(&mut self, diffs: Vec<BlocklistDiff>) -> Result<()> {
+        self.update(|state| {
+            state.subscription_diffs.extend(diffs);
+            let excess = state.subscription_diffs.len().saturating_sub(MAX_SUBSCRIPTION_DIFFS);
+            state.subscription_diffs.drain(..excess);
+        })
+    }
+
+    /// The settings lock, if it has not ended yet.
+    pub fn settings_lock(&self, now: i64) -> Option<&SettingsLock> {
+        self.state.settings_lock.as_ref().filter(|lock| now < lock.until)
+    }
+
+    /// Replace the settings lock.
+    pub fn lock_settings(&mut self, lock: SettingsLock) -> Result<()> {
+        self.update(|state| state.settings_lock = Some(lock))
+    }
+
+    /// Replace or clear the schedule edit waiting to take effect.
+    pub fn set_pending_schedule(&mut self, pending: Option<PendingScheduleChange>) -> Result<()> {
+        self.update(|state| state.pending_schedule = pending)
+    }
+
+    /// Add a vacation, dropping those that ended before `today`.
+    pub fn add_vacation(&mut self, vacation: Vacation, today: NaiveDate) -> Result<()> {
+        self.update(|state| {
+            state.vacations.retain(|v| v.end >= today);
+            state.vacations.push(vacation);
+            state.vacations.sort_by_key(|v| v.start);
+        })
+    }
+
+    /// Remove a vacation by ID. Returns whether it existed.
+    pub fn remove_vacation(&mut self, id: &str) -> Result<bool> {
+        let before = self.state.vacations.len();
+        self.update(|state| state.vacations.retain(|v| v.id != id))?;
+        Ok(self.state.vacations.len() < before)
+    }
+
+    /// Drop records that no longer count on `today`: usage from past days and
+    /// weeks, ended vacations, activity older than 52 weeks and the health of
+    /// lists no longer in `subscribed`. Returns the number of records dropped.
+    pub fn compact(&mut self, today: NaiveDate, subscribed: &[String]) -> Result<usize> {
+        let date = today.format("%Y-%m-%d").to_string();
+        let last_week = (today - chrono::Duration::days(7)).format("%Y-%m-%d").to_string();
+        let cutoff = today - chrono::Duration::days(MAX_ACTIVITY_DAYS);
+        let cutoff = cutoff.format("%Y-%m-%d").to_string();
+
+        let mut compacted = self.state.clone();
+        let before = record_count(&compacted);
+        compacted.group_usage.retain(|u| u.week_start > last_week);
+        compacted.domain_usage.retain(|u| u.date == date);
+        compacted.vacations.retain(|v| v.end >= today);
+        compacted.block_activity.retain(|day| day.date > cutoff);
+        compacted.subscriptions.retain(|h| subscribed.contains(&h.url));
+        let dropped = before - record_count(&compacted);
+
+        if dropped > 0 {
+            self.update(|state| *state = compacted)?;
+        }
+        Ok(dropped)
+    }
+
+    /// Record that maintenance ran at `now`.
+    pub fn record_maintenance(&mut self, now: i64) -> Result<()> {
+        self.update(|state| state.last_maintenance_at = Some(now))
+    }
+
+    /// Record that the report for the week starting `week_start` was sent.
+    pub fn record_report(&mut self, week_start: &str) -> Result<()> {
+        self.update(|state| state.last_report_week = Some(week_start.to_string()))
+    }
+
+    /// The most recent reason given for a bypass.
+    pub fn last_bypass_reason(&self) -> Option<String> {
+        self.state
+            .bypass_history
+            .iter()
+            .rev()
+            .find_map(|r| r.reason.clone())
+    }
+
+    /// Save the state to disk.
+    fn save(&self) -> Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| !self.read_only) else {
+            return Ok(());
+        };
+
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create state directory: {:?}", parent))?;
+        }
+
+        let mut content = serde_json::to_vec_pretty(&self.state).context("Failed to serialize state")?;
+        if let Some(cipher) = &self.cipher {
+            content = cipher.encrypt(&content)?;
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let tmp = format!("{}.tmp", path);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(FILE_MODE)
+            .open(&tmp)
+            .with_context(|| format!("Failed to write state file: {}", tmp))?;
+        // A temporary file left over by a crash keeps its permissions otherwise
+        file.set_permissions(fs::Permissions::from_mode(FILE_MODE))
+            .and_then(|()| file.write_all(&content))
+            .with_context(|| format!("Failed to write state file: {}", tmp))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace state file: {}", path))?;
+
+        Ok(())
+    }
+}
+
+/// Records `StateStore::compact` can drop.
+fn record_count(state: &PersistentState) -> usize {
+    state.group_usage.len()
+        + state.domain_usage.len()
+        + state.vacations.len()
+        + state.block_activity.len()
+        + state.subscriptions.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started_at: i64, reason: Option<&str>) -> BypassRecord {
+        BypassRecord {
+            started_at,
+            duration_minutes: 15,
+            reason: reason.map(|r| r.to_string()),
+            cancelled_at: None,
+        }
+    }
+
+    #[test]
+    fn test_last_bypass_reason() {
+        let mut store = StateStore::in_memory();
+        assert_eq!(store.last_bypass_reason(), None);
+
+        store.record_bypass(record(100, Some("check flights"))).unwrap();
+        store.record_bypass(record(200, None)).unwrap();
+        assert_eq!(store.last_bypass_reason().as_deref(), Some("check flights"));
+    }
+
+    #[test]
+    fn test_clear_bypass_history_keeps_budget() {
+        let mut store = StateStore::in_memory();
+        store.record_bypass(record(100, Some("check flights"))).unwrap();
+        store.charge_bypass_minutes("2024-01-15", 15).unwrap();
+
+        store.clear_bypass_history().unwrap();
+        assert!(store.get().bypass_history.is_empty());
+        assert_eq!(store.bypass_minutes_used("2024-01-15"), 15);
+    }
+
+    #[test]
+    fn test_cancel_marks_running_bypass() {
+        let mut store = StateStore::in_memory();
+        store.record_bypass(record(1000, None)).unwrap();
+
+        store.record_bypass_cancelled(1300).unwrap();
+        assert_eq!(store.get().bypass_history[0].cancelled_at, Some(1300));
+
+        // Cancelling again does not overwrite the first cancellation
+        store.record_bypass_cancelled(1400).unwrap();
+        assert_eq!(store.get().bypass_history[0].cancelled_at, Some(1300));
+    }
+
+    #[test]
+    fn test_bypass_budget_accounting() {
+        let mut store = StateStore::in_memory();
+        assert_eq!(store.bypass_minutes_used("2025-06-10"), 0);
+
+        store.charge_bypass_minutes("2025-06-10", 20).unwrap();
+        store.refund_bypass_minutes("2025-06-10", 5).unwrap();
+        assert_eq!(store.bypass_minutes_used("2025-06-10"), 15);
+
+        // A new day starts from zero, and stale refunds are ignored
+        assert_eq!(store.bypass_minutes_used("2025-06-11"), 0);
+        store.charge_bypass_minutes("2025-06-11", 10).unwrap();
+        store.refund_bypass_minutes("2025-06-10", 10).unwrap();
+        assert_eq!(store.bypass_minutes_used("2025-06-11"), 10);
+    }
+
+    #[test]
+    fn test_group_usage_resets_weekly() {
+        let mut store = StateStore::in_memory();
+        store.add_group_minutes("Video", "2025-06-09", 30).unwrap();
+        store.add_group_minutes("Video", "2025-06-09", 1).unwrap();
+        store.add_group_minutes("Social", "2025-06-09", 5).unwrap();
+        assert_eq!(store.group_minutes_used("Video", "2025-06-09"), 31);
+        assert_eq!(store.group_minutes_used("Social", "2025-06-09"), 5);
+
+        // A new week drops last week's counters
+        store.add_group_minutes("Video", "2025-06-16", 2).unwrap();
+        assert_eq!(store.group_minutes_used("Video", "2025-06-16"), 2);
+        assert_eq!(store.group_minutes_used("Social", "2025-06-09"), 0);
+        assert_eq!(store.get().group_usage.len(), 1);
+    }
+
+    #[test]
+    fn test_domain_usage_resets_daily() {
+        let mut store = StateStore::in_memory();
+        store.add_domain_minutes("youtube.com", "2025-06-10", 29).unwrap();
+        store.add_domain_minutes("youtube.com", "2025-06-10", 1).unwrap();
+        assert_eq!(store.domain_minutes_used("youtube.com", "2025-06-10"), 30);
+
+        store.add_domain_minutes("reddit.com", "2025-06-11", 1).unwrap();
+        assert_eq!(store.domain_minutes_used("youtube.com", "2025-06-11"), 0);
+        assert_eq!(store.get().domain_usage.len(), 1);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut store = StateStore::in_memory();
+        for i in 0..(MAX_BYPASS_HISTORY + 5) {
+            store.record_bypass(record(i as i64, None)).unwrap();
+        }
+        let history = &store.get().bypass_history;
+        assert_eq!(history.len(), MAX_BYPASS_HISTORY);
+        assert_eq!(history[0].started_at, 5);
+    }
+
+    #[test]
+    fn test_subscription_health_replaces_per_url() {
+        let mut store = StateStore::in_memory();
+        let health = |url: &str, entries| SubscriptionHealth {
+            url: url.to_string(),
+            entries,
+            ..Default::default()
+        };
+
+        store
+            .record_subscription_health(vec![health("https://a/list", 10), health("https://b/list", 20)])
+            .unwrap();
+        store.record_subscription_health(vec![health("https://a/list", 11)]).unwrap();
+
+        assert_eq!(store.subscription_health("https://a/list").unwrap().entries, 11);
+        assert_eq!(store.subscription_health("https://b/list").unwrap().entries, 20);
+        assert!(store.subscription_health("https://c/list").is_none());
+    }
+
+    #[test]
+    fn test_block_activity() {
+        let mut store = StateStore::in_memory();
+        let day = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        store.add_blocks(day, 9, 3).unwrap();
+        store.add_blocks(day, 9, 2).unwrap();
+        store.add_blocks(day, 23, 1).unwrap();
+        store.add_blocks(day + chrono::Duration::days(1), 0, 4).unwrap();
+
+        let activity = &store.get().block_activity;
+        assert_eq!(activity.len(), 2);
+        assert_eq!(activity[0].date, "2024-01-15");
+        assert_eq!((activity[0].hours[9], activity[0].hours[23]), (5, 1));
+        assert_eq!(activity[1].hours[0], 4);
+
+        // Days older than 52 weeks are dropped
+        store.add_blocks(day + chrono::Duration::days(MAX_ACTIVITY_DAYS), 12, 1).unwrap();
+        assert_eq!(store.get().block_activity.len(), 2);
+        assert_eq!(store.get().block_activity[0].date, "2024-01-16");
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut store = StateStore::in_memory();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap();
+        store.add_group_minutes("social", "2024-03-04", 30).unwrap();
+        store.add_domain_minutes("news.com", "2024-03-12", 10).unwrap();
+        store.add_blocks(today - chrono::Duration::days(MAX_ACTIVITY_DAYS), 9, 1).unwrap();
+        let health = |url: &str| SubscriptionHealth {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        let lists = vec![health("https://a/list"), health("https://b/list")];
+        store.record_subscription_health(lists).unwrap();
+
+        let subscribed = vec!["https://a/list".to_string()];
+        assert_eq!(store.compact(today, &subscribed).unwrap(), 4);
+        let state = store.get();
+        assert!(state.group_usage.is_empty() && state.domain_usage.is_empty());
+        assert!(state.block_activity.is_empty());
+        assert_eq!(state.subscriptions.len(), 1);
+
+        // This week's usage is kept
+        store.add_group_minutes("social", "2024-03-11", 30).unwrap();
+        assert_eq!(store.compact(today, &subscribed).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_state_file_is_private() {
+        let dir = std::env::temp_dir().join(format!("baf-state-mode-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        fs::write(dir.join("state.json.tmp"), "").unwrap();
+
+        let mut store = StateStore::open(path.to_str().unwrap(), false, || unreachable!());
+        store.record_snooze("2025-06-09").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, FILE_MODE);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_file_kept_without_key() {
+        let dir = std::env::temp_dir().join(format!("baf-state-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let path_str = path.to_str().unwrap();
+        let no_key = || Err(anyhow::anyhow!("keychain unavailable"));
+
+        let sealed = b"BAFENC1\n0123456789ab sealed state".to_vec();
+        fs::write(&path, &sealed).unwrap();
+        for encrypt in [true, false] {
+            let mut store = StateStore::open(path_str, encrypt, no_key);
+            store.record_snooze("2025-06-09").unwrap();
+            assert_eq!(fs::read(&path).unwrap(), sealed);
+        }
+
+        // Nor is state written in plaintext when encryption is on
+        fs::remove_file(&path).unwrap();
+        let mut store = StateStore::open(path_str, true, no_key);
+        store.record_snooze("2025-06-09").unwrap();
+        assert!(!path.exists());
+        assert!(!store.is_unreadable());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_file_kept_and_reported() {
+        let dir = std::env::temp_dir().join(format!("baf-state-corrupt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        fs::write(&path, "{\"settings_lock\": {").unwrap();
+
+        let open = || StateStore::open(path.to_str().unwrap(), false, || unreachable!());
+        let mut store = open();
+        assert!(store.is_unreadable());
+        store.record_snooze("2025-06-09").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"settings_lock\": {");
+
+        fs::remove_file(&path).unwrap();
+        assert!(!open().is_unreadable());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! On-disk copies of downloaded blocklists with their HTTP validators.
+
+use anyhow::{Context, Result};
+use blockandfocus_shared::{SUBSCRIPTIONS_DIR, SUBSCRIPTIONS_DIR_DEV};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+/// Validators and content hash of a cached list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CachedList {
+    /// `ETag` header of the cached download
+    pub etag: Option<String>,
+
+    /// `Last-Modified` header of the cached download
+    pub last_modified: Option<String>,
+
+    /// SHA-256 of the list contents, hex encoded
+    pub sha256: String,
+}
+
+/// Directory of downloaded lists, one body and one metadata file per URL.
+pub struct ListCache {
+    dir: PathBuf,
+}
+
+impl ListCache {
+    pub fn new(is_dev: bool) -> Self {
+        let dir = if is_dev { SUBSCRIPTIONS_DIR_DEV } else { SUBSCRIPTIONS_DIR };
+        Self { dir: PathBuf::from(dir) }
+    }
+
+    /// Metadata of the cached copy of a list, if any.
+    pub fn meta(&self, url: &str) -> Option<CachedList> {
+        // Validators without the body they describe would make a 304 useless
+        if !self.path(url, "txt").exists() {
+            return None;
+        }
+        let content = fs::read_to_string(self.path(url, "json")).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Contents of the cached copy of a list, if any.
+    pub fn body(&self, url: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(url, "txt")).ok()
+    }
+
+    /// Path a new download of a list is written to before it is stored.
+    pub fn download_path(&self, url: &str) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        Ok(self.path(url, "download"))
+    }
+
+    /// Replace the cached copy of a list with a finished download.
+    pub fn store(&self, url: &str, meta: &CachedList) -> Result<()> {
+        fs::rename(self.path(url, "download"), self.path(url, "txt"))
+            .context("Failed to store downloaded list")?;
+        self.store_meta(url, meta)
+    }
+
+    /// Update the metadata of a cached list.
+    pub fn store_meta(&self, url: &str, meta: &CachedList) -> Result<()> {
+        let content = serde_json::to_string_pretty(meta)?;
+        fs::write(self.path(url, "json"), content).context("Failed to write list metadata")
+    }
+
+    /// Files of a list are named after a hash of its URL.
+    fn path(&self, url: &str, extension: &str) -> PathBuf {
+        let name = &sha256_hex(url.as_bytes())[..16];
+        self.dir.join(format!("{}.{}", name, extension))
+    }
+}
+
+/// SHA-256 of some bytes, hex encoded.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
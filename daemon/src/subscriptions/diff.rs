@@ -0,0 +1,75 @@
+//! Changes between two versions of a subscribed list.
+
+use blockandfocus_shared::{BlocklistDiff, DomainName};
+use std::collections::HashSet;
+
+/// Most domains kept per direction in a diff; counts stay exact.
+const MAX_DIFF_DOMAINS: usize = 1000;
+
+/// Domains added to and removed from a list, or None if nothing changed.
+pub fn diff_lists(
+    name: &str,
+    url: &str,
+    old: &[DomainName],
+    new: &[DomainName],
+    refreshed_at: i64,
+) -> Option<BlocklistDiff> {
+    let old_set: HashSet<&str> = old.iter().map(DomainName::as_str).collect();
+    let new_set: HashSet<&str> = new.iter().map(DomainName::as_str).collect();
+
+    let mut added: Vec<&str> = new_set.difference(&old_set).copied().collect();
+    let mut removed: Vec<&str> = old_set.difference(&new_set).copied().collect();
+    if added.is_empty() && removed.is_empty() {
+        return None;
+    }
+    added.sort_unstable();
+    removed.sort_unstable();
+
+    Some(BlocklistDiff {
+        list: name.to_string(),
+        url: url.to_string(),
+        refreshed_at,
+        added_count: added.len() as u64,
+        removed_count: removed.len() as u64,
+        added: added.into_iter().take(MAX_DIFF_DOMAINS).map(str::to_string).collect(),
+        removed: removed.into_iter().take(MAX_DIFF_DOMAINS).map(str::to_string).collect(),
+    })
+}
+
+/// Whether a diff added `domain` or one of its parents.
+pub fn diff_adds(diff: &BlocklistDiff, domain: &str) -> bool {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    diff.added.iter().any(|entry| {
+        domain == *entry
+            || domain
+                .strip_suffix(entry.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domains(list: &[&str]) -> Vec<DomainName> {
+        list.iter().map(|d| d.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_diff_lists() {
+        let old = domains(&["a.com", "b.com", "c.com"]);
+        let new = domains(&["c.com", "d.com", "b.com", "e.com"]);
+
+        let diff = diff_lists("Ads", "https://example.com/ads", &old, &new, 100).unwrap();
+        assert_eq!(diff.added, vec!["d.com", "e.com"]);
+        assert_eq!(diff.removed, vec!["a.com"]);
+        assert_eq!((diff.added_count, diff.removed_count), (2, 1));
+
+        assert!(diff_lists("Ads", "https://example.com/ads", &old, &old, 100).is_none());
+
+        assert!(diff_adds(&diff, "D.com."));
+        assert!(diff_adds(&diff, "login.e.com"));
+        assert!(!diff_adds(&diff, "notd.com"));
+        assert!(!diff_adds(&diff, "a.com"));
+    }
+}
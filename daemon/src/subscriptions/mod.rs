@@ -0,0 +1,9 @@
+//! Remote blocklist subscriptions.
+
+mod cache;
+mod diff;
+mod parser;
+mod refresh;
+
+pub use diff::diff_adds;
+pub use refresh::{refresh_subscriptions, SubscriptionMonitor};
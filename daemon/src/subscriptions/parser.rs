@@ -0,0 +1,92 @@
+//! Parsing of downloaded blocklists.
+
+use blockandfocus_shared::DomainName;
+use std::net::IpAddr;
+
+/// Host names found in hosts files that must never be blocked.
+const RESERVED_HOSTS: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// Domains taken from a downloaded list.
+pub struct ParsedList {
+    pub domains: Vec<DomainName>,
+
+    /// Lines containing something that is not an IP address or a domain
+    pub invalid_lines: u64,
+}
+
+/// Extract domains from a list in hosts-file or one-domain-per-line format.
+///
+/// Comments (`#` or `!`), IP addresses and invalid names are skipped, and
+/// simple adblock rules (`||example.com^`) are accepted.
+pub fn parse_list(text: &str) -> ParsedList {
+    let mut domains = Vec::new();
+    let mut invalid_lines = 0;
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('!') {
+            continue;
+        }
+
+        let mut invalid = false;
+        for token in line.split_whitespace() {
+            if token.parse::<IpAddr>().is_ok() {
+                continue;
+            }
+
+            match DomainName::parse(token.trim_start_matches("||").trim_end_matches('^')) {
+                Ok(domain) if RESERVED_HOSTS.contains(&domain.as_str()) => {}
+                // A blockable domain has at least two labels
+                Ok(domain) if domain.as_str().contains('.') => domains.push(domain),
+                _ => invalid = true,
+            }
+        }
+        if invalid {
+            invalid_lines += 1;
+        }
+    }
+
+    ParsedList {
+        domains,
+        invalid_lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let text = "\
+# Example hosts file
+127.0.0.1 localhost
+::1 ip6-localhost
+0.0.0.0 ads.example.com tracker.example.net # inline comment
+Plain.Example.org.
+||adblock.example.com^
+! adblock comment
+not a domain
+*.wildcard.com
+";
+
+        let parsed = parse_list(text);
+        assert_eq!(
+            parsed.domains,
+            vec![
+                "ads.example.com",
+                "tracker.example.net",
+                "plain.example.org",
+                "adblock.example.com",
+            ]
+        );
+        assert_eq!(parsed.invalid_lines, 2);
+    }
+}
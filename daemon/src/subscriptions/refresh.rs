@@ -0,0 +1,350 @@
+//! Periodic download of subscribed blocklists.
+//!
+//! Lists are fetched concurrently, a few at a time, each with its own
+//! timeout, so one slow mirror only delays its own list. A list that fails
+//! to download keeps its previous contents until the next refresh.
+//!
+//! Downloads are conditional on the cached copy's `ETag` and `Last-Modified`
+//! headers, and a full download whose content hash matches the cached copy
+//! is not parsed again, so unchanged lists cost almost nothing.
+
+use crate::store::SubscriptionHealth;
+use crate::AppState;
+use anyhow::{Context, Result};
+use blockandfocus_shared::{BlocklistSubscription, DomainName, SubscriptionConfig};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use super::cache::{sha256_hex, CachedList, ListCache};
+use super::diff::diff_lists;
+use super::parser::{parse_list, ParsedList};
+
+/// Periodically refreshes subscribed blocklists in the background.
+pub struct SubscriptionMonitor;
+
+impl SubscriptionMonitor {
+    /// Run the refresh loop.
+    pub async fn run(state: Arc<RwLock<AppState>>, is_dev: bool) {
+        let cache = Arc::new(ListCache::new(is_dev));
+
+        // Block with the cached lists right away instead of waiting for the network
+        load_cached(&state, &cache).await;
+
+        loop {
+            let (config, in_maintenance) = {
+                let config = state.read().await.config.get();
+                (config.subscriptions.clone(), config.maintenance.enabled)
+            };
+            let interval = Duration::from_secs(u64::from(config.refresh_interval_hours.max(1)) * 3600);
+
+            // With a maintenance window, lists are refreshed there; only lists
+            // never downloaded are fetched right away
+            let due = if in_maintenance {
+                config.lists.iter().any(|list| list.enabled && cache.meta(&list.url).is_none())
+            } else {
+                config.lists.iter().any(|list| list.enabled)
+            };
+            if due {
+                refresh(&state, &config, &cache).await;
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Refresh all enabled lists now, as the maintenance window does.
+pub async fn refresh_subscriptions(state: &Arc<RwLock<AppState>>, is_dev: bool) {
+    let config = state.read().await.config.get().subscriptions.clone();
+    if config.lists.iter().any(|list| list.enabled) {
+        refresh(state, &config, &Arc::new(ListCache::new(is_dev))).await;
+    }
+}
+
+/// Load the cached copies of enabled lists and rebuild the blocker with them.
+async fn load_cached(state: &Arc<RwLock<AppState>>, cache: &ListCache) {
+    let state_guard = state.read().await;
+    let config = state_guard.config.get();
+
+    let lists: HashMap<String, Vec<DomainName>> = config
+        .subscriptions
+        .lists
+        .iter()
+        .filter(|list| list.enabled)
+        .filter_map(|list| Some((list.url.clone(), cached_domains(cache, &list.url)?)))
+        .collect();
+
+    if !lists.is_empty() {
+        info!("Loaded {} cached blocklist subscriptions", lists.len());
+        state_guard.subscribed_domains.store(Arc::new(lists));
+        state_guard.rebuild_blocker();
+    }
+}
+
+/// Download all enabled lists, rebuild the blocker with them and record their health.
+async fn refresh(state: &Arc<RwLock<AppState>>, config: &SubscriptionConfig, cache: &Arc<ListCache>) {
+    let results = fetch_all(config, cache).await;
+    let now = chrono::Utc::now().timestamp();
+
+    let mut state_guard = state.write().await;
+    let previous = state_guard.subscribed_domains.load_full();
+    let mut lists = HashMap::new();
+    let mut health = Vec::new();
+    let mut diffs = Vec::new();
+
+    for (list, fetched) in results {
+        let mut list_health = state_guard
+            .store
+            .subscription_health(&list.url)
+            .cloned()
+            .unwrap_or_else(|| SubscriptionHealth {
+                url: list.url.clone(),
+                ..Default::default()
+            });
+        list_health.last_fetch_at = Some(now);
+        list_health.http_status = fetched.http_status;
+
+        match fetched.list {
+            Ok(unchanged) => {
+                list_health.last_success_at = Some(now);
+                list_health.last_error = None;
+
+                let parsed = match unchanged {
+                    Some(parsed) => {
+                        info!(list = %list.name, entries = parsed.domains.len(), "Blocklist subscription refreshed");
+                        list_health.invalid_lines = parsed.invalid_lines;
+                        if let Some(old) = previous.get(&list.url) {
+                            diffs.extend(diff_lists(&list.name, &list.url, old, &parsed.domains, now));
+                        }
+                        Some(parsed.domains)
+                    }
+                    None => previous
+                        .get(&list.url)
+                        .cloned()
+                        .or_else(|| cached_domains(cache, &list.url)),
+                };
+                if let Some(domains) = parsed {
+                    list_health.entries = domains.len() as u64;
+                    lists.insert(list.url, domains);
+                }
+            }
+            Err(e) => {
+                warn!(list = %list.name, "Failed to refresh blocklist subscription: {:#}", e);
+                list_health.last_error = Some(format!("{:#}", e));
+                if let Some(domains) = previous.get(&list.url) {
+                    lists.insert(list.url, domains.clone());
+                }
+            }
+        }
+
+        health.push(list_health);
+    }
+
+    if let Err(e) = state_guard.store.record_subscription_health(health) {
+        warn!("Failed to record subscription health: {:#}", e);
+    }
+    if !diffs.is_empty() {
+        if let Err(e) = state_guard.store.record_subscription_diffs(diffs) {
+            warn!("Failed to record subscription changes: {:#}", e);
+        }
+    }
+    // The rebuild only needs the read half; DNS queries wait on the write lock meanwhile
+    let state_guard = state_guard.downgrade();
+    state_guard.subscribed_domains.store(Arc::new(lists));
+    state_guard.rebuild_blocker();
+}
+
+/// Outcome of downloading one list.
+struct Fetched {
+    /// Final HTTP status, if a response arrived
+    http_status: Option<u16>,
+
+    /// The parsed list, or None if it is unchanged since the cached copy
+    list: Result<Option<ParsedList>>,
+}
+
+/// Fetch enabled lists concurrently, at most `max_concurrent_fetches` at a time.
+async fn fetch_all(
+    config: &SubscriptionConfig,
+    cache: &Arc<ListCache>,
+) -> Vec<(BlocklistSubscription, Fetched)> {
+    let permits = Arc::new(Semaphore::new(config.max_concurrent_fetches.max(1) as usize));
+    let timeout = Duration::from_secs(u64::from(config.fetch_timeout_seconds.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for list in config.lists.iter().filter(|list| list.enabled).cloned() {
+        let permits = permits.clone();
+        let cache = cache.clone();
+        tasks.spawn(async move {
+            // The semaphore is never closed
+            let _permit = permits.acquire_owned().await;
+            let fetched = match tokio::time::timeout(timeout, fetch(&list.url, timeout, &cache)).await {
+                Ok(fetched) => fetched,
+                Err(_) => Fetched {
+                    http_status: None,
+                    list: Err(anyhow::anyhow!("Timed out after {}s", timeout.as_secs())),
+                },
+            };
+            (list, fetched)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => warn!("Blocklist download task failed: {}", e),
+        }
+    }
+
+    results
+}
+
+/// Download and parse one list.
+async fn fetch(url: &str, timeout: Duration, cache: &ListCache) -> Fetched {
+    let mut http_status = None;
+    let list = download(url, timeout, cache, &mut http_status).await;
+    Fetched { http_status, list }
+}
+
+/// Download a list into the cache, or None if it is unchanged since the cached copy.
+async fn download(
+    url: &str,
+    timeout: Duration,
+    cache: &ListCache,
+    http_status: &mut Option<u16>,
+) -> Result<Option<ParsedList>> {
+    let cached = cache.meta(url);
+    let download = cache.download_path(url)?;
+
+    let mut command = Command::new("/usr/bin/curl");
+    command
+        .args(["--max-time", &timeout.as_secs().to_string(), "-sSL"])
+        // Headers and the status code go to stdout, the body to the download file
+        .args(["-D", "-", "-w", "\n%{http_code}", "-o"])
+        .arg(&download);
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            command.args(["-H", &format!("If-None-Match: {}", etag)]);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            command.args(["-H", &format!("If-Modified-Since: {}", last_modified)]);
+        }
+    }
+
+    let output = command
+        .arg(url)
+        // Dropped on timeout, which must not leave curl running
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run curl")?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&download);
+        anyhow::bail!("Download failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let response = parse_response(&String::from_utf8_lossy(&output.stdout));
+    *http_status = Some(response.status);
+    match response.status {
+        304 => {
+            let _ = fs::remove_file(&download);
+            return Ok(None);
+        }
+        200..=299 => {}
+        status => {
+            let _ = fs::remove_file(&download);
+            anyhow::bail!("Server answered HTTP {}", status);
+        }
+    }
+
+    let body = fs::read(&download).context("Failed to read downloaded list")?;
+    let meta = CachedList {
+        etag: response.etag,
+        last_modified: response.last_modified,
+        sha256: sha256_hex(&body),
+    };
+
+    if cached.is_some_and(|cached| cached.sha256 == meta.sha256) {
+        let _ = fs::remove_file(&download);
+        cache.store_meta(url, &meta)?;
+        return Ok(None);
+    }
+
+    cache.store(url, &meta)?;
+    Ok(Some(parse_list(&String::from_utf8_lossy(&body))))
+}
+
+/// Parse the cached copy of a list.
+fn cached_domains(cache: &ListCache, url: &str) -> Option<Vec<DomainName>> {
+    let body = cache.body(url)?;
+    Some(parse_list(&String::from_utf8_lossy(&body)).domains)
+}
+
+/// Status and validators of the final response in curl's header dump.
+#[derive(Debug, Default, PartialEq)]
+struct ResponseInfo {
+    status: u16,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Parse `curl -D - -w '\n%{http_code}'` output.
+///
+/// Redirects produce one header block per response; only the last counts.
+fn parse_response(output: &str) -> ResponseInfo {
+    let mut info = ResponseInfo::default();
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("HTTP/") {
+            info = ResponseInfo::default();
+        } else if let Some((name, value)) = line.split_once(':') {
+            let value = Some(value.trim().to_string());
+            if name.eq_ignore_ascii_case("etag") {
+                info.etag = value;
+            } else if name.eq_ignore_ascii_case("last-modified") {
+                info.last_modified = value;
+            }
+        } else if let Ok(status) = line.parse() {
+            info.status = status;
+        }
+    }
+
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let output = "HTTP/1.1 301 Moved Permanently\r\n\
+Location: https://example.com/list.txt\r\n\
+ETag: \"redirect\"\r\n\
+\r\n\
+HTTP/2 200\r\n\
+etag: \"abc123\"\r\n\
+last-modified: Wed, 21 Oct 2026 07:28:00 GMT\r\n\
+\r\n\
+\n200";
+
+        assert_eq!(
+            parse_response(output),
+            ResponseInfo {
+                status: 200,
+                etag: Some("\"abc123\"".to_string()),
+                last_modified: Some("Wed, 21 Oct 2026 07:28:00 GMT".to_string()),
+            }
+        );
+        assert_eq!(parse_response("HTTP/1.1 304 Not Modified\r\n\r\n\n304").status, 304);
+    }
+}
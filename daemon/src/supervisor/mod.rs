@@ -0,0 +1,5 @@
+//! Restarting long-running subsystems when they fail.
+
+mod restart;
+
+pub use restart::{supervise, RestartPolicy};
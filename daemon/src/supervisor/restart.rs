@@ -0,0 +1,144 @@
+//! Restart loop with exponential backoff.
+
+use anyhow::Result;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+/// When and how often a failed subsystem is restarted.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Delay before the first restart
+    pub initial_backoff: Duration,
+
+    /// Upper bound for the delay between restarts
+    pub max_backoff: Duration,
+
+    /// Consecutive failures after which the subsystem is given up on
+    pub max_failures: u32,
+
+    /// A run lasting at least this long resets the failure count
+    pub healthy_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            max_failures: 5,
+            healthy_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restarting after `failures` consecutive failures.
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Run a subsystem, restarting it with backoff whenever it stops or panics.
+///
+/// Subsystems are expected to run forever, so returning `Ok` counts as a
+/// failure too. Resolves with a description of the last failure once the
+/// subsystem failed `max_failures` times in a row.
+pub async fn supervise<F, Fut>(name: &'static str, policy: RestartPolicy, mut start: F) -> String
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let mut failures = 0;
+
+    loop {
+        let started = Instant::now();
+        let reason = match tokio::spawn(start()).await {
+            Ok(Ok(())) => "stopped unexpectedly".to_string(),
+            Ok(Err(e)) => format!("failed: {:#}", e),
+            Err(e) if e.is_panic() => "panicked".to_string(),
+            Err(e) => format!("was cancelled: {}", e),
+        };
+
+        if started.elapsed() >= policy.healthy_after {
+            failures = 0;
+        }
+        failures += 1;
+
+        if failures >= policy.max_failures {
+            error!("{} {} ({} failures in a row), giving up", name, reason, failures);
+            return format!("{} {} after {} restarts", name, reason, failures - 1);
+        }
+
+        let delay = policy.backoff(failures);
+        warn!("{} {}, restarting in {:?}", name, reason, delay);
+        tokio::time::sleep(delay).await;
+        info!("Restarting {}", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    fn fast_policy() -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            max_failures: 3,
+            healthy_after: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(8));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+        assert_eq!(policy.backoff(100), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_supervise_gives_up_after_repeated_failures() {
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let counter = attempts.clone();
+        let reason = supervise("test", fast_policy(), move || {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                anyhow::bail!("socket closed")
+            }
+        })
+        .await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(reason, "test failed: socket closed after 2 restarts");
+    }
+
+    #[tokio::test]
+    async fn test_supervise_restarts_after_panic() {
+        let attempts = Arc::new(AtomicU32::new(0));
+
+        let counter = attempts.clone();
+        let reason = supervise("test", fast_policy(), move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("boom");
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(reason, "test stopped unexpectedly after 2 restarts");
+    }
+}
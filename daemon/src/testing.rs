@@ -0,0 +1,964 @@
+//! In-process daemon for end-to-end tests.
+//!
+//! `TestDaemon` runs the real DNS and IPC servers against a temporary config,
+//! on an ephemeral DNS port and a socket in a temporary directory, so tests
+//! can drive the daemon the way the app does and check what DNS clients see,
+//! without root. Forwarded queries go to a `MockUpstream` (by default one
+//! answering every name with [`UPSTREAM_ADDRESS`]), so nothing leaves the
+//! machine.
+
+use crate::config::ConfigManager;
+use crate::dns::mock::MockUpstream;
+use crate::dns::DnsServer;
+use crate::ipc::IpcServer;
+use crate::store::StateStore;
+use crate::AppState;
+use blockandfocus_shared::{Command, Config, Response};
+use hickory_proto::op::{Message, MessageType, Query};
+use hickory_proto::rr::{Name, RData, RecordType};
+use hickory_proto::serialize::binary::{BinDecodable, BinEncodable};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UdpSocket, UnixStream};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// Address the default upstream answers every A query with.
+pub const UPSTREAM_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+
+/// How long a test waits for the daemon to start or answer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Distinguishes the temporary directories of daemons in the same process.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A daemon running inside the test process.
+pub struct TestDaemon {
+    pub state: Arc<RwLock<AppState>>,
+    /// Upstream answering forwarded queries
+    pub upstream: Arc<MockUpstream>,
+    /// Address the DNS server is bound to
+    pub dns_addr: SocketAddr,
+    socket_path: PathBuf,
+    dir: PathBuf,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestDaemon {
+    /// Start a daemon with the default config, as changed by `configure`.
+    pub async fn start(configure: impl FnOnce(&mut Config)) -> Self {
+        let upstream = MockUpstream::new().default_answer(UPSTREAM_ADDRESS.into());
+        Self::start_with(upstream, configure).await
+    }
+
+    /// Start a daemon forwarding to `upstream`.
+    pub async fn start_with(upstream: MockUpstream, configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "blockandfocus-test-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut config = Config::default();
+        config.dns.listen_address = "127.0.0.1".to_string();
+        config.dns.listen_port = 0;
+        configure(&mut config);
+
+        let config_path = dir.join("config.toml");
+        std::fs::write(&config_path, toml::to_string(&config).unwrap()).unwrap();
+        let config = ConfigManager::load_from(config_path.display().to_string()).unwrap();
+        let state = Arc::new(RwLock::new(AppState::new(config, StateStore::in_memory())));
+
+        let socket_path = dir.join("ipc.sock");
+        let key_path = dir.join("ipc.key");
+        let upstream = Arc::new(upstream);
+        let dns_state = state.clone();
+        let dns_upstream = upstream.clone();
+        let ipc_state = state.clone();
+        let (socket, key) = (socket_path.display().to_string(), key_path.display().to_string());
+        let tasks = vec![
+            tokio::spawn(async move {
+                if let Err(e) = DnsServer::run_with(dns_state, false, dns_upstream).await {
+                    panic!("Test DNS server failed: {:#}", e);
+                }
+            }),
+            tokio::spawn(async move {
+                if let Err(e) = IpcServer::serve(ipc_state, &socket, &key).await {
+                    panic!("Test IPC server failed: {:#}", e);
+                }
+            }),
+        ];
+
+        let started = async {
+            loop {
+                let dns_addr = state.read().await.dns_listen_addr;
+                if let Some(dns_addr) = dns_addr.filter(|_| socket_path.exists()) {
+                    return dns_addr;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        let dns_addr = tokio::time::timeout(TIMEOUT, started)
+            .await
+            .expect("Test daemon did not start");
+
+        Self {
+            state,
+            upstream,
+            dns_addr,
+            socket_path,
+            dir,
+            tasks,
+        }
+    }
+
+    /// Send a command over IPC, as the app does, and return the response.
+    pub async fn send(&self, command: Command) -> Response {
+        let exchange = async {
+            let stream = UnixStream::connect(&self.socket_path).await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut line = serde_json::to_string(&command).unwrap();
+            line.push('\n');
+            writer.write_all(line.as_bytes()).await.unwrap();
+
+            let mut response = String::new();
+            BufReader::new(reader).read_line(&mut response).await.unwrap();
+            serde_json::from_str(&response).unwrap()
+        };
+        tokio::time::timeout(TIMEOUT, exchange).await.expect("No IPC response")
+    }
+
+    /// Send a one-shot text command and return everything the daemon writes back.
+    pub async fn quick(&self, line: &str) -> String {
+        let exchange = async {
+            let mut stream = UnixStream::connect(&self.socket_path).await.unwrap();
+            stream.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).await.unwrap();
+            reply
+        };
+        tokio::time::timeout(TIMEOUT, exchange).await.expect("No IPC response")
+    }
+
+    /// Send a DNS query for `domain` to the daemon and return the response.
+    pub async fn query(&self, domain: &str, record_type: RecordType) -> Message {
+        let query = query_message(domain, record_type);
+        let response = self.exchange(&query.to_bytes().unwrap()).await;
+        assert_eq!(response.id(), query.id());
+        response
+    }
+
+    /// Send a raw packet to the DNS server and return the response.
+    pub async fn exchange(&self, packet: &[u8]) -> Message {
+        let exchange = async {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.send_to(packet, self.dns_addr).await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let len = socket.recv(&mut buf).await.unwrap();
+            Message::from_bytes(&buf[..len]).unwrap()
+        };
+        tokio::time::timeout(TIMEOUT, exchange).await.expect("No DNS response")
+    }
+
+    /// Send raw packets to the DNS server over one TCP connection and return
+    /// the responses.
+    pub async fn exchange_tcp(&self, packets: &[&[u8]]) -> Vec<Message> {
+        let exchange = async {
+            let mut stream = TcpStream::connect(self.dns_addr).await.unwrap();
+            let mut responses = Vec::new();
+            for packet in packets {
+                stream.write_u16(packet.len() as u16).await.unwrap();
+                stream.write_all(packet).await.unwrap();
+
+                let mut buf = vec![0u8; stream.read_u16().await.unwrap().into()];
+                stream.read_exact(&mut buf).await.unwrap();
+                responses.push(Message::from_bytes(&buf).unwrap());
+            }
+            responses
+        };
+        tokio::time::timeout(TIMEOUT, exchange).await.expect("No DNS response")
+    }
+
+    /// Resolve `domain` through the daemon, returning the A records answered.
+    pub async fn resolve(&self, domain: &str) -> Vec<Ipv4Addr> {
+        self.query(domain, RecordType::A)
+            .await
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                RData::A(a) => Some(a.0),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the daemon answers `domain` with the blocked address.
+    pub async fn is_blocked(&self, domain: &str) -> bool {
+        self.resolve(domain).await == [Ipv4Addr::UNSPECIFIED]
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A recursive query for `domain` with a random ID.
+pub fn query_message(domain: &str, record_type: RecordType) -> Message {
+    let mut query = Message::new();
+    query.set_id(rand::random());
+    query.set_message_type(MessageType::Query);
+    query.set_recursion_desired(true);
+    query.add_query(Query::query(Name::from_str(domain).unwrap(), record_type));
+    query
+}
+
+/// Whether `addrs` is the default upstream's answer.
+pub fn from_upstream(addrs: &[Ipv4Addr]) -> bool {
+    addrs == [UPSTREAM_ADDRESS]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockandfocus_shared::{
+        Duration as BypassDuration, ErrorCode, PurgeScope, QueryAction, QueryLogFilter, Schedule,
+        ScheduleRule, Strictness,
+    };
+
+    #[tokio::test]
+    async fn test_block_then_bypass() {
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains.clear();
+            // A relaxed rule is active, so bypasses are granted without a quiz
+            config.schedule.enabled = true;
+            config.schedule.rules = vec![ScheduleRule::builder()
+                .name("Test")
+                .expires_at(chrono::Utc::now().timestamp() + 3600)
+                .strictness(Strictness::Relaxed)
+                .build()
+                .unwrap()];
+        })
+        .await;
+
+        assert!(from_upstream(&daemon.resolve("distraction.test").await));
+
+        let add = Command::AddDomain { domain: "distraction.test".parse().unwrap() };
+        assert!(matches!(daemon.send(add).await, Response::Success));
+        assert!(daemon.is_blocked("distraction.test").await);
+        assert!(daemon.is_blocked("www.distraction.test").await);
+        let config = daemon.state.read().await.config.get();
+        assert!(config.blocking.domains.iter().any(|d| d.as_str() == "distraction.test"));
+        assert!(from_upstream(&daemon.resolve("work.test").await));
+
+        let bypass = Command::RequestBypass {
+            duration: BypassDuration::from_mins(15),
+            reason: None,
+        };
+        assert!(matches!(daemon.send(bypass).await, Response::Success));
+        assert!(from_upstream(&daemon.resolve("distraction.test").await));
+
+        match daemon.send(Command::GetStatus).await {
+            Response::Status(status) => {
+                assert!(status.bypass_until.is_some());
+                assert_eq!(status.queries_blocked, 2);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bypass_duration_after_quiz() {
+        use blockandfocus_shared::{QuizAnswer, QuizStage};
+
+        let sentence = "I really need this";
+        let daemon = TestDaemon::start(|config| {
+            config.quiz.min_solve = BypassDuration::from_secs(0);
+            config.quiz.stages = vec![QuizStage::Confirm { sentence: sentence.to_string() }];
+            config.quiz.max_bypass = BypassDuration::from_mins(45);
+        })
+        .await;
+
+        let bypass = |minutes| Command::RequestBypass {
+            duration: BypassDuration::from_mins(minutes),
+            reason: None,
+        };
+        for (requested, granted) in [(30, 30), (120, 45)] {
+            let challenge = match daemon.send(bypass(requested)).await {
+                Response::QuizChallenge(challenge) => challenge,
+                other => panic!("Unexpected response: {:?}", other),
+            };
+            let submit = Command::SubmitQuizAnswers {
+                challenge_id: challenge.challenge_id,
+                answers: vec![QuizAnswer::Text(sentence.to_string())],
+            };
+            assert!(matches!(daemon.send(submit).await, Response::Success));
+
+            let until = daemon.state.read().await.bypass.until.unwrap();
+            let minutes = (until - chrono::Utc::now().timestamp() + 30) / 60;
+            assert_eq!(minutes, granted);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bypass_budget_rechecked_after_quiz() {
+        use blockandfocus_shared::{QuizAnswer, QuizStage};
+
+        let sentence = "I really need this";
+        let daemon = TestDaemon::start(|config| {
+            config.quiz.min_solve = BypassDuration::from_secs(0);
+            config.quiz.stages = vec![QuizStage::Confirm { sentence: sentence.to_string() }];
+            config.bypass.daily_budget_minutes = Some(30);
+        })
+        .await;
+
+        // Both quizzes are handed out while the whole budget is left
+        let bypass = |minutes| Command::RequestBypass {
+            duration: BypassDuration::from_mins(minutes),
+            reason: None,
+        };
+        let mut challenges = Vec::new();
+        for minutes in [20, 20] {
+            match daemon.send(bypass(minutes)).await {
+                Response::QuizChallenge(challenge) => challenges.push(challenge),
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        }
+        let submit = |challenge_id| Command::SubmitQuizAnswers {
+            challenge_id,
+            answers: vec![QuizAnswer::Text(sentence.to_string())],
+        };
+        for challenge in challenges {
+            assert!(matches!(daemon.send(submit(challenge.challenge_id)).await, Response::Success));
+        }
+
+        // The second bypass replaced the first, whose unused minutes were
+        // refunded, and never took more than the budget
+        let state = daemon.state.read().await;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let used = state.store.bypass_minutes_used(&today);
+        assert!(used <= 30, "{} minutes used", used);
+        let minutes = (state.bypass.until.unwrap() - chrono::Utc::now().timestamp() + 30) / 60;
+        assert_eq!(minutes, 20);
+    }
+
+    #[tokio::test]
+    async fn test_strict_rule_started_during_quiz() {
+        use blockandfocus_shared::{QuizAnswer, QuizStage};
+
+        let sentence = "I really need this";
+        let daemon = TestDaemon::start(|config| {
+            config.quiz.min_solve = BypassDuration::from_secs(0);
+            config.quiz.stages = vec![QuizStage::Confirm { sentence: sentence.to_string() }];
+        })
+        .await;
+
+        let request =
+            Command::RequestBypass { duration: BypassDuration::from_mins(10), reason: None };
+        let challenge = match daemon.send(request).await {
+            Response::QuizChallenge(challenge) => challenge,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+
+        {
+            let mut state = daemon.state.write().await;
+            let mut schedule = state.config.get().schedule.clone();
+            schedule.enabled = true;
+            schedule.rules = vec![ScheduleRule::builder()
+                .name("Exam")
+                .expires_at(chrono::Utc::now().timestamp() + 3600)
+                .strictness(Strictness::Strict)
+                .build()
+                .unwrap()];
+            state.schedule.update(schedule);
+        }
+
+        let submit = Command::SubmitQuizAnswers {
+            challenge_id: challenge.challenge_id,
+            answers: vec![QuizAnswer::Text(sentence.to_string())],
+        };
+        match daemon.send(submit).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::BypassNotAllowed),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(daemon.state.read().await.bypass.until.is_none());
+    }
+
+    /// A daemon gating weakening edits behind a one-stage quiz, with the
+    /// sentence that passes it.
+    async fn weakening_quiz_daemon() -> (TestDaemon, &'static str) {
+        use blockandfocus_shared::QuizStage;
+
+        let sentence = "I really need this";
+        let daemon = TestDaemon::start(|config| {
+            config.quiz.min_solve = BypassDuration::from_secs(0);
+            config.quiz.stages = vec![QuizStage::Confirm { sentence: sentence.to_string() }];
+            config.protection.quiz_for_weakening = true;
+        })
+        .await;
+        (daemon, sentence)
+    }
+
+    /// Office hours, blocking less than the default around-the-clock blocking.
+    fn office_hours(enabled: bool) -> Schedule {
+        let time = |s| chrono::NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+        let rule = ScheduleRule::builder()
+            .name("Work")
+            .weekdays()
+            .between(time("09:00"), time("17:00"))
+            .build()
+            .unwrap();
+        Schedule { enabled, rules: vec![rule] }
+    }
+
+    #[tokio::test]
+    async fn test_schedule_weakening_takes_quiz() {
+        use blockandfocus_shared::{QuizAnswer, QuizPurpose};
+
+        let (daemon, sentence) = weakening_quiz_daemon().await;
+        let schedule = || async { daemon.state.read().await.config.get().schedule.clone() };
+
+        // Still blocking around the clock: applied directly
+        let update = |schedule| Command::UpdateSchedule { schedule };
+        assert!(matches!(daemon.send(update(office_hours(false))).await, Response::Success));
+        assert_eq!(schedule().await, office_hours(false));
+
+        // Blocking only during office hours takes the quiz first
+        let challenge = match daemon.send(update(office_hours(true))).await {
+            Response::QuizChallenge(challenge) => challenge,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+        assert_eq!(challenge.purpose, QuizPurpose::UpdateSchedule);
+        assert_eq!(schedule().await, office_hours(false));
+
+        let submit = Command::SubmitQuizAnswers {
+            challenge_id: challenge.challenge_id,
+            answers: vec![QuizAnswer::Text(sentence.to_string())],
+        };
+        assert!(matches!(daemon.send(submit).await, Response::Success));
+        assert_eq!(schedule().await, office_hours(true));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_weakening_rechecked_after_quiz() {
+        use blockandfocus_shared::QuizAnswer;
+
+        let (daemon, sentence) = weakening_quiz_daemon().await;
+        let challenge = || async {
+            let update = Command::UpdateSchedule { schedule: office_hours(true) };
+            match daemon.send(update).await {
+                Response::QuizChallenge(challenge) => challenge,
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        };
+        let submit = |challenge: blockandfocus_shared::QuizChallenge| {
+            daemon.send(Command::SubmitQuizAnswers {
+                challenge_id: challenge.challenge_id,
+                answers: vec![QuizAnswer::Text(sentence.to_string())],
+            })
+        };
+        let refused = |response| match response {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::SettingsLocked),
+            other => panic!("Unexpected response: {:?}", other),
+        };
+
+        // A strict rule started while the quiz was being solved
+        let pending = challenge().await;
+        let original = daemon.state.read().await.config.get().schedule.clone();
+        {
+            let mut state = daemon.state.write().await;
+            let mut schedule = original.clone();
+            schedule.enabled = true;
+            schedule.rules = vec![ScheduleRule::builder()
+                .name("Exam")
+                .expires_at(chrono::Utc::now().timestamp() + 3600)
+                .strictness(Strictness::Strict)
+                .build()
+                .unwrap()];
+            state.schedule.update(schedule);
+        }
+        refused(submit(pending).await);
+        assert_eq!(daemon.state.read().await.config.get().schedule, original);
+        daemon.state.write().await.schedule.update(original.clone());
+
+        // Settings were locked while the quiz was being solved
+        let pending = challenge().await;
+        assert!(matches!(daemon.send(Command::LockSettings { hours: 1 }).await, Response::Success));
+        refused(submit(pending).await);
+        assert_eq!(daemon.state.read().await.config.get().schedule, original);
+    }
+
+    #[tokio::test]
+    async fn test_one_snooze_per_day_after_quiz() {
+        use blockandfocus_shared::{QuizAnswer, QuizStage, WeekdayWrapper::*};
+
+        let sentence = "I really need this";
+        let daemon = TestDaemon::start(|config| {
+            config.quiz.min_solve = BypassDuration::from_secs(0);
+            config.quiz.stages = vec![QuizStage::Confirm { sentence: sentence.to_string() }];
+            config.schedule.enabled = true;
+            config.schedule.rules = vec![ScheduleRule::builder()
+                .name("Evening")
+                .days([Mon, Tue, Wed, Thu, Fri, Sat, Sun])
+                .between(
+                    chrono::NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+                    chrono::NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                )
+                .build()
+                .unwrap()];
+        })
+        .await;
+
+        // Both quizzes are handed out before either snooze is used
+        let mut challenges = Vec::new();
+        for _ in 0..2 {
+            match daemon.send(Command::SnoozeSchedule { minutes: 15 }).await {
+                Response::QuizChallenge(challenge) => challenges.push(challenge),
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        }
+        let submit = |challenge_id| Command::SubmitQuizAnswers {
+            challenge_id,
+            answers: vec![QuizAnswer::Text(sentence.to_string())],
+        };
+        let mut challenges = challenges.into_iter();
+        let first = challenges.next().unwrap();
+        assert!(matches!(daemon.send(submit(first.challenge_id)).await, Response::Success));
+        let second = challenges.next().unwrap();
+        match daemon.send(submit(second.challenge_id)).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::SnoozeUnavailable),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_upstream_failure() {
+        use crate::dns::mock::MockFailure;
+        use hickory_proto::op::ResponseCode;
+
+        let upstream = MockUpstream::new().answer("example.test", UPSTREAM_ADDRESS.into());
+        let daemon = TestDaemon::start_with(upstream, |config| {
+            config.blocking.domains.clear();
+        })
+        .await;
+
+        assert!(from_upstream(&daemon.resolve("example.test").await));
+        assert!(from_upstream(&daemon.resolve("example.test").await));
+        assert_eq!(daemon.upstream.queries(), 1);
+
+        // Cached answers outlive the upstream; anything else fails
+        daemon.upstream.set_failure(Some(MockFailure::Timeout));
+        assert!(from_upstream(&daemon.resolve("example.test").await));
+        let response = daemon.query("other.test", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+        assert_eq!(daemon.upstream.queries(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_serve_stale() {
+        use crate::dns::mock::MockFailure;
+        use hickory_proto::op::ResponseCode;
+
+        let upstream = MockUpstream::new().answer("example.test", UPSTREAM_ADDRESS.into());
+        let daemon = TestDaemon::start_with(upstream, |config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+            config.dns.max_ttl = Some(BypassDuration::from_secs(1));
+        })
+        .await;
+
+        assert!(from_upstream(&daemon.resolve("example.test").await));
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        // Expired and every upstream is down: the old answer is better than nothing
+        daemon.upstream.set_failure(Some(MockFailure::Timeout));
+        let response = daemon.query("example.test", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert_eq!(response.answers()[0].ttl(), 30);
+        assert_eq!(daemon.upstream.queries(), 2);
+
+        // Blocking still applies, and names never seen still fail
+        assert!(daemon.is_blocked("distraction.test").await);
+        let response = daemon.query("other.test", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+    }
+
+    #[tokio::test]
+    async fn test_cname_cloaking() {
+        let upstream = MockUpstream::new()
+            .alias("metrics.news.test", "collect.tracker.test")
+            .answer("collect.tracker.test", UPSTREAM_ADDRESS.into());
+        let daemon = TestDaemon::start_with(upstream, |config| {
+            config.blocking.domains = vec!["tracker.test".parse().unwrap()];
+        })
+        .await;
+
+        assert!(daemon.is_blocked("metrics.news.test").await);
+        match daemon.send(Command::GetStatus).await {
+            Response::Status(status) => assert_eq!(status.queries_blocked, 1),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // With the check off the chain is answered as the upstream gave it
+        let state = daemon.state.read().await;
+        state.config.update(|config| config.blocking.block_cname_cloaking = false).await.unwrap();
+        drop(state);
+        let response = daemon.query("metrics.news.test", RecordType::A).await;
+        assert_eq!(response.answers().len(), 2);
+        assert!(from_upstream(&daemon.resolve("metrics.news.test").await));
+    }
+
+    #[tokio::test]
+    async fn test_block_modes() {
+        use blockandfocus_shared::BlockMode;
+        use hickory_proto::op::ResponseCode;
+
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+            config.blocking.block_mode = BlockMode::Redirect;
+            config.blocking.redirect_to = vec!["192.0.2.80".parse().unwrap()];
+        })
+        .await;
+
+        assert_eq!(daemon.resolve("distraction.test").await, [Ipv4Addr::new(192, 0, 2, 80)]);
+        // No IPv6 address to redirect to, and no HTTPS record either way
+        for record_type in [RecordType::AAAA, RecordType::HTTPS] {
+            let response = daemon.query("distraction.test", record_type).await;
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            assert!(response.answers().is_empty());
+        }
+
+        for (mode, code) in [
+            (BlockMode::Nxdomain, ResponseCode::NXDomain),
+            (BlockMode::Refused, ResponseCode::Refused),
+        ] {
+            let state = daemon.state.read().await;
+            state.config.update(|config| config.blocking.block_mode = mode).await.unwrap();
+            drop(state);
+            for record_type in [RecordType::A, RecordType::AAAA, RecordType::HTTPS] {
+                let response = daemon.query("distraction.test", record_type).await;
+                assert_eq!(response.response_code(), code);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_negative_caching() {
+        use hickory_proto::op::ResponseCode;
+
+        let upstream = MockUpstream::new().answer("v4only.test", UPSTREAM_ADDRESS.into());
+        let daemon = TestDaemon::start_with(upstream, |config| {
+            config.blocking.domains.clear();
+        })
+        .await;
+
+        for _ in 0..2 {
+            let response = daemon.query("missing.test", RecordType::A).await;
+            assert_eq!(response.response_code(), ResponseCode::NXDomain);
+            let response = daemon.query("v4only.test", RecordType::AAAA).await;
+            assert_eq!(response.response_code(), ResponseCode::NoError);
+            assert!(response.answers().is_empty());
+        }
+        assert_eq!(daemon.upstream.queries(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_packets() {
+        use hickory_proto::op::ResponseCode;
+
+        let daemon = TestDaemon::start(|_| {}).await;
+
+        // A header claiming two questions, followed by garbage
+        let mut packet = vec![0xab, 0xcd, 0x01, 0x00, 0x00, 0x02, 0, 0, 0, 0, 0, 0];
+        packet.extend_from_slice(&[0xff; 40]);
+        let response = daemon.exchange(&packet).await;
+        assert_eq!(response.id(), 0xabcd);
+        assert_eq!(response.response_code(), ResponseCode::FormErr);
+
+        assert!(from_upstream(&daemon.resolve("example.test").await));
+        match daemon.send(Command::GetStatus).await {
+            Response::Status(status) => assert_eq!(status.queries_malformed, 1),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tcp_and_truncation() {
+        // Far more addresses than fit in a 512-byte datagram
+        let upstream = (1..=80u8).fold(MockUpstream::new(), |upstream, i| {
+            upstream.answer("large.test", Ipv4Addr::new(192, 0, 2, i).into())
+        });
+        let daemon = TestDaemon::start_with(upstream, |config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+        })
+        .await;
+
+        let response = daemon.query("large.test", RecordType::A).await;
+        assert!(response.truncated());
+        assert!(response.answers().is_empty());
+        assert_eq!(response.queries().len(), 1);
+
+        // Over TCP the full answer arrives, and the connection takes several queries
+        let large = query_message("large.test", RecordType::A).to_bytes().unwrap();
+        let blocked = query_message("distraction.test", RecordType::A).to_bytes().unwrap();
+        let responses = daemon.exchange_tcp(&[&large, &blocked]).await;
+        assert!(!responses[0].truncated());
+        assert_eq!(responses[0].answers().len(), 80);
+        match responses[1].answers()[0].data() {
+            RData::A(a) => assert_eq!(a.0, Ipv4Addr::UNSPECIFIED),
+            other => panic!("Unexpected answer: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() {
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains.clear();
+            // No rules, so blocking is off and a backup can be restored
+            config.schedule.enabled = true;
+            config.schedule.rules.clear();
+        })
+        .await;
+
+        let archive = match daemon.send(Command::CreateBackup).await {
+            Response::Backup { archive } => archive,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+
+        let add = Command::AddDomain { domain: "distraction.test".parse().unwrap() };
+        assert!(matches!(daemon.send(add).await, Response::Success));
+        let damaged = Command::RestoreBackup { archive: archive.replace("\"config\"", "\"x\"") };
+        assert!(matches!(daemon.send(damaged).await, Response::Error { .. }));
+        assert!(!daemon.state.read().await.config.get().blocking.domains.is_empty());
+
+        let restore = Command::RestoreBackup { archive: archive.clone() };
+        assert!(matches!(daemon.send(restore).await, Response::Success));
+        assert!(daemon.state.read().await.config.get().blocking.domains.is_empty());
+        assert!(from_upstream(&daemon.resolve("distraction.test").await));
+
+        // Not while a focus session is blocking
+        assert!(matches!(daemon.send(Command::FocusNow { minutes: 25 }).await, Response::Success));
+        match daemon.send(Command::RestoreBackup { archive }).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::SettingsLocked),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_external() {
+        use base64::Engine;
+        use blockandfocus_shared::ImportSource;
+
+        let daemon = TestDaemon::start(|config| config.blocking.domains.clear()).await;
+        let list = b"distraction.test\nexample.test/some/page\n";
+        let import = |dry_run| Command::ImportExternal {
+            source: ImportSource::ColdTurkey,
+            data: base64::engine::general_purpose::STANDARD.encode(list),
+            dry_run,
+        };
+
+        match daemon.send(import(true)).await {
+            Response::ImportPreview(preview) => {
+                assert!(!preview.applied);
+                assert_eq!(preview.groups[0].domains, vec!["distraction.test"]);
+                assert_eq!(preview.skipped.len(), 1);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(daemon.state.read().await.config.get().blocking.groups.is_empty());
+        assert!(from_upstream(&daemon.resolve("distraction.test").await));
+
+        match daemon.send(import(false)).await {
+            Response::ImportPreview(preview) => assert!(preview.applied),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(daemon.is_blocked("distraction.test").await);
+        assert_eq!(daemon.state.read().await.config.get().blocking.groups[0].name, "Cold Turkey");
+
+        let garbage = Command::ImportExternal {
+            source: ImportSource::SelfControl,
+            data: "not base64!".to_string(),
+            dry_run: true,
+        };
+        assert!(matches!(daemon.send(garbage).await, Response::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_client_profiles() {
+        use blockandfocus_shared::{BlocklistGroup, ClientBlocking, ClientProfile};
+
+        // Test queries come from 127.0.0.1, standing in for a device on the network
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+            config.blocking.groups = vec![BlocklistGroup {
+                name: "Games".to_string(),
+                domains: vec!["games.test".parse().unwrap()],
+                weekly_budget_minutes: None,
+                enabled: false,
+            }];
+            config.schedule.enabled = true;
+            config.schedule.rules.clear();
+        })
+        .await;
+        let mut profile = ClientProfile {
+            name: "Tablet".to_string(),
+            clients: vec!["127.0.0.0/8".to_string()],
+            blocking: ClientBlocking::Always,
+            allow_bypass: false,
+            groups: vec!["Games".to_string()],
+        };
+
+        // No rules, so nothing is blocked for this Mac
+        assert!(from_upstream(&daemon.resolve("distraction.test").await));
+        let set = |profile: &ClientProfile| Command::SetClientProfile { profile: profile.clone() };
+        assert!(matches!(daemon.send(set(&profile)).await, Response::Success));
+        assert!(daemon.is_blocked("distraction.test").await);
+        assert!(daemon.is_blocked("games.test").await);
+
+        daemon.state.write().await.activate_bypass(15, None);
+        assert!(daemon.is_blocked("games.test").await);
+
+        profile.allow_bypass = true;
+        assert!(matches!(daemon.send(set(&profile)).await, Response::Success));
+        assert!(from_upstream(&daemon.resolve("games.test").await));
+
+        profile.groups = vec!["Unknown".to_string()];
+        match daemon.send(set(&profile)).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::ConfigError),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let remove = Command::RemoveClientProfile { name: "Tablet".to_string() };
+        assert!(matches!(daemon.send(remove).await, Response::Success));
+        match daemon.send(Command::GetClientProfiles).await {
+            Response::ClientProfiles { profiles } => assert!(profiles.is_empty()),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quick_commands() {
+        let daemon = TestDaemon::start(|config| config.blocking.domains.clear()).await;
+
+        assert_eq!(daemon.quick("ping").await, "pong\n");
+        assert_eq!(daemon.quick("block distraction.test").await, "ok\n");
+        assert!(daemon.is_blocked("distraction.test").await);
+        assert!(daemon.quick("status").await.contains("1 domains"));
+        assert!(daemon.quick("help").await.contains("block <domain>"));
+        assert!(daemon.quick("frobnicate").await.starts_with("error: "));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_survives_reconnecting() {
+        let daemon = TestDaemon::start(|_| {}).await;
+
+        // Every quick command is a new connection from the same user
+        for _ in 0..5 {
+            assert!(!daemon.quick("snooze 10").await.contains("Too many"));
+        }
+        assert!(daemon.quick("snooze 10").await.contains("Too many"));
+        match daemon.send(Command::SnoozeSchedule { minutes: 10 }).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::RateLimited),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_purge_clears_dns_cache() {
+        let daemon = TestDaemon::start(|config| config.blocking.domains.clear()).await;
+        assert!(from_upstream(&daemon.resolve("visited.test").await));
+        let cached = || async {
+            match daemon.send(Command::GetCacheStats).await {
+                Response::CacheStats(stats) => stats.entries,
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        };
+        assert_eq!(cached().await, 1);
+
+        let purge = |confirmation| Command::PurgeData { scope: PurgeScope::QueryLog, confirmation };
+        let confirmation = match daemon.send(purge(None)).await {
+            Response::PurgeConfirmationRequired { confirmation, .. } => confirmation,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+        let response = daemon.send(purge(Some(confirmation))).await;
+        assert!(matches!(response, Response::DataPurged { .. }));
+        assert_eq!(cached().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_query_log() {
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+            config.query_log.enabled = true;
+        })
+        .await;
+        assert!(daemon.is_blocked("distraction.test").await);
+        assert!(from_upstream(&daemon.resolve("allowed.test").await));
+
+        let get = |limit, filter| daemon.send(Command::GetQueryLog { limit, filter });
+        match get(1, QueryLogFilter::default()).await {
+            Response::QueryLog { entries } => {
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].domain, "allowed.test");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        let blocked = QueryLogFilter { action: Some(QueryAction::Blocked), ..Default::default() };
+        match get(10, blocked).await {
+            Response::QueryLog { entries } => {
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].domain, "distraction.test");
+                assert_eq!(entries[0].record_type, "A");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_log_needs_signed_command() {
+        let daemon = TestDaemon::start(|config| {
+            config.query_log.enabled = true;
+            config.protection.require_authenticated_commands = true;
+        })
+        .await;
+
+        let filter = QueryLogFilter::default();
+        match daemon.send(Command::GetQueryLog { limit: 10, filter }).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::Unauthenticated),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_state_locks_settings() {
+        let daemon = TestDaemon::start(|config| {
+            config.blocking.domains = vec!["distraction.test".parse().unwrap()];
+        })
+        .await;
+        let path = daemon.dir.join("state.json");
+        std::fs::write(&path, "not json").unwrap();
+        daemon.state.write().await.store =
+            StateStore::open(path.to_str().unwrap(), false, || unreachable!());
+
+        let remove = Command::RemoveDomain { domain: "distraction.test".parse().unwrap() };
+        match daemon.send(remove).await {
+            Response::Error { code, .. } => assert_eq!(code, ErrorCode::SettingsLocked),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(daemon.is_blocked("distraction.test").await);
+
+        // Strengthening still works
+        let add = Command::AddDomain { domain: "more.test".parse().unwrap() };
+        assert!(matches!(daemon.send(add).await, Response::Success));
+    }
+}
@@ -0,0 +1,26 @@
+//! Stand-ins for builds without the `self-update` feature, where the daemon
+//! is updated by the system's package manager instead.
+
+use crate::AppState;
+use anyhow::{bail, Result};
+use blockandfocus_shared::{UpdateConfig, UpdateStatus};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Background update checks; there are none in this build.
+pub struct UpdateMonitor;
+
+impl UpdateMonitor {
+    pub async fn run(_state: Arc<RwLock<AppState>>) {
+        info!("Built without self-update; update checks are disabled");
+    }
+}
+
+pub async fn check_for_update(_config: &UpdateConfig) -> Result<UpdateStatus> {
+    bail!("this build of the daemon doesn't include self-update")
+}
+
+pub async fn apply_update(_config: &UpdateConfig) -> Result<String> {
+    bail!("this build of the daemon doesn't include self-update")
+}
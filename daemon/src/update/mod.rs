@@ -0,0 +1,11 @@
+//! Daemon self-update: checking for new releases and installing them.
+
+#[cfg(not(feature = "self-update"))]
+mod disabled;
+#[cfg(feature = "self-update")]
+mod updater;
+
+#[cfg(not(feature = "self-update"))]
+pub use disabled::{apply_update, check_for_update, UpdateMonitor};
+#[cfg(feature = "self-update")]
+pub use updater::{apply_update, check_for_update, UpdateMonitor};
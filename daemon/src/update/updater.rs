@@ -0,0 +1,241 @@
+//! Update checks against a release manifest and in-place binary replacement.
+//!
+//! The manifest is a small JSON document (see [`UpdateManifest`]) pointing at
+//! the new daemon binary. Before the running binary is replaced, the download
+//! must match the manifest checksum and carry a valid code signature from the
+//! same team as the installed daemon. The daemon then exits and launchd
+//! restarts it from the new binary.
+
+use crate::AppState;
+use anyhow::{Context, Result};
+use blockandfocus_shared::{Event, UpdateConfig, UpdateManifest, UpdateStatus};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Version of the running daemon.
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Time allowed for fetching the manifest or the binary, in seconds.
+const DOWNLOAD_TIMEOUT_SECONDS: &str = "120";
+
+/// Exit status used to have launchd restart the daemon after an update.
+const RESTART_EXIT_CODE: i32 = 75;
+
+/// Periodically checks for updates in the background.
+pub struct UpdateMonitor;
+
+impl UpdateMonitor {
+    /// Run the update check loop.
+    pub async fn run(state: Arc<RwLock<AppState>>) {
+        loop {
+            let config = state.read().await.config.get().update.clone();
+            let interval = Duration::from_secs(u64::from(config.check_interval_hours.max(1)) * 3600);
+
+            if config.check_automatically {
+                match check_for_update(&config).await {
+                    Ok(status) => {
+                        let mut state_guard = state.write().await;
+                        if status.update_available && state_guard.update != Some(status.clone()) {
+                            if let Some(latest) = &status.latest {
+                                info!("Daemon update available: {}", latest.version);
+                                state_guard.emit(Event::UpdateAvailable(latest.clone()));
+                            }
+                        }
+                        state_guard.update = Some(status);
+                    }
+                    Err(e) => warn!("Update check failed: {:#}", e),
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Fetch the release manifest and compare it with the running version.
+pub async fn check_for_update(config: &UpdateConfig) -> Result<UpdateStatus> {
+    let body = curl(&["-fsSL", &config.manifest_url]).await?;
+    let manifest: UpdateManifest =
+        serde_json::from_slice(&body).context("Failed to parse update manifest")?;
+
+    Ok(UpdateStatus {
+        current_version: CURRENT_VERSION.to_string(),
+        update_available: is_newer(&manifest.version, CURRENT_VERSION),
+        latest: Some(manifest),
+        checked_at: chrono::Utc::now().timestamp(),
+    })
+}
+
+/// Download, verify and install the latest daemon, then schedule a restart.
+///
+/// Returns the installed version.
+pub async fn apply_update(config: &UpdateConfig) -> Result<String> {
+    let status = check_for_update(config).await?;
+    let manifest = match status.latest {
+        Some(manifest) if status.update_available => manifest,
+        _ => anyhow::bail!("Already running the latest version ({})", CURRENT_VERSION),
+    };
+
+    let current = std::env::current_exe().context("Failed to locate the daemon binary")?;
+    let staged = current.with_extension("update");
+    let staged_str = staged.to_string_lossy().to_string();
+
+    info!("Downloading daemon {} from {}", manifest.version, manifest.url);
+    curl(&["-fsSL", "-o", &staged_str, &manifest.url]).await?;
+
+    if let Err(e) = verify(&staged, &current, &manifest).await {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+
+    install(&staged, &current)?;
+    info!("Installed daemon {}, restarting", manifest.version);
+
+    // Give the IPC response time to reach the client before exiting
+    tokio::spawn(async {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        std::process::exit(RESTART_EXIT_CODE);
+    });
+
+    Ok(manifest.version)
+}
+
+/// Check the checksum and code signature of a downloaded binary.
+async fn verify(staged: &Path, current: &Path, manifest: &UpdateManifest) -> Result<()> {
+    let output = run("/usr/bin/shasum", &["-a", "256", &staged.to_string_lossy()]).await?;
+    let checksum = output.split_whitespace().next().unwrap_or_default();
+    if !checksum.eq_ignore_ascii_case(manifest.sha256.trim()) {
+        anyhow::bail!("Checksum mismatch for downloaded update");
+    }
+
+    run("/usr/bin/codesign", &["--verify", "--strict", &staged.to_string_lossy()])
+        .await
+        .context("Downloaded update is not validly signed")?;
+
+    let expected = team_identifier(current).await?;
+    let actual = team_identifier(staged).await?;
+    if expected.is_none() || expected != actual {
+        anyhow::bail!("Downloaded update is not signed by the same team as the installed daemon");
+    }
+
+    Ok(())
+}
+
+/// Move the verified binary over the running one.
+fn install(staged: &Path, current: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(staged, std::fs::Permissions::from_mode(0o755))
+        .context("Failed to set update permissions")?;
+    // rename() is atomic; the running process keeps the old inode
+    std::fs::rename(staged, current).context("Failed to replace the daemon binary")
+}
+
+/// Team identifier from a binary's code signature.
+async fn team_identifier(path: &Path) -> Result<Option<String>> {
+    // codesign prints signature details on stderr
+    let output = Command::new("/usr/bin/codesign")
+        .args(["-dv", &path.to_string_lossy()])
+        .output()
+        .await
+        .context("Failed to run codesign")?;
+
+    Ok(parse_team_identifier(&String::from_utf8_lossy(&output.stderr)))
+}
+
+/// Find `TeamIdentifier=` in `codesign -dv` output.
+fn parse_team_identifier(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("TeamIdentifier="))
+        .map(str::trim)
+        .filter(|team| !team.is_empty() && *team != "not set")
+        .map(str::to_string)
+}
+
+/// Run curl with a timeout and return its output.
+async fn curl(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("/usr/bin/curl")
+        .args(["--max-time", DOWNLOAD_TIMEOUT_SECONDS])
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run curl")?;
+
+    if !output.status.success() {
+        anyhow::bail!("Download failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(output.stdout)
+}
+
+/// Run a command and return its stdout, failing on a non-zero exit status.
+async fn run(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", program))?;
+
+    if !output.status.success() {
+        anyhow::bail!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether `latest` is a higher `major.minor.patch` version than `current`.
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Parse `1.2.3` (optionally prefixed with `v`); missing parts count as 0.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    // Ignore pre-release and build metadata
+    let version = version.split(['-', '+']).next()?;
+
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("v1.0", "0.9.9"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+        assert!(!is_newer("0.2.0-beta.1", "0.2.0"));
+        assert!(!is_newer("latest", "0.1.0"));
+        assert!(!is_newer("1.2.3.4", "0.1.0"));
+    }
+
+    #[test]
+    fn test_parse_team_identifier() {
+        let output = "Executable=/Library/PrivilegedHelperTools/blockandfocus-daemon\n\
+                      Identifier=com.blockandfocus.daemon\n\
+                      TeamIdentifier=ABCDE12345\n";
+        assert_eq!(parse_team_identifier(output), Some("ABCDE12345".to_string()));
+        assert_eq!(parse_team_identifier("TeamIdentifier=not set\n"), None);
+        assert_eq!(parse_team_identifier("code object is not signed at all\n"), None);
+    }
+}
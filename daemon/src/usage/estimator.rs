@@ -0,0 +1,139 @@
+//! Sessionizes query timestamps into approximate minutes of use.
+//!
+//! Browsers and apps query a site's domains repeatedly while it is in use.
+//! Queries closer together than the gap threshold belong to one session; a
+//! session lasts from its first to its last query, and a lone query counts
+//! as a short minimum session.
+
+use blockandfocus_shared::{DomainUsage, UsageConfig};
+use std::collections::HashMap;
+
+/// Running usage for one key (a site, group or allowance domain).
+#[derive(Debug, Default)]
+struct KeyUsage {
+    /// Timestamp of the most recent query
+    last_seen: Option<i64>,
+    /// Estimated seconds of use
+    seconds: i64,
+    /// Seconds not yet reported as whole minutes
+    unreported_seconds: i64,
+    sessions: u32,
+    queries: u64,
+}
+
+/// Estimates usage time per key from query timestamps.
+#[derive(Default)]
+pub struct UsageEstimator {
+    config: UsageConfig,
+    keys: HashMap<String, KeyUsage>,
+}
+
+impl UsageEstimator {
+    /// Create an estimator with the given session thresholds.
+    pub fn new(config: UsageConfig) -> Self {
+        Self {
+            config,
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Record a query for a key at the given time.
+    ///
+    /// Returns the whole minutes of use added by this query, for callers that
+    /// accumulate usage elsewhere (e.g. budgets).
+    pub fn record(&mut self, key: &str, now: i64) -> u32 {
+        let gap = self.config.session_gap_seconds as i64;
+        let min_session = self.config.min_session_seconds as i64;
+        let usage = self.keys.entry(key.to_string()).or_default();
+
+        let added = match usage.last_seen {
+            Some(last) if now >= last && now - last <= gap => now - last,
+            // Out-of-order timestamps belong to the current session
+            Some(last) if now < last => 0,
+            _ => {
+                usage.sessions += 1;
+                min_session
+            }
+        };
+
+        usage.last_seen = Some(usage.last_seen.map_or(now, |last| last.max(now)));
+        usage.queries += 1;
+        usage.seconds += added;
+        usage.unreported_seconds += added;
+
+        let minutes = usage.unreported_seconds / 60;
+        usage.unreported_seconds %= 60;
+        minutes as u32
+    }
+
+    /// Estimated usage per key, most used first.
+    pub fn usage(&self) -> Vec<DomainUsage> {
+        let mut usage: Vec<DomainUsage> = self
+            .keys
+            .iter()
+            .map(|(key, u)| DomainUsage {
+                domain: key.clone(),
+                minutes: (u.seconds / 60) as u32,
+                sessions: u.sessions,
+                queries: u.queries,
+            })
+            .collect();
+        usage.sort_by(|a, b| b.minutes.cmp(&a.minutes).then_with(|| a.domain.cmp(&b.domain)));
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimator() -> UsageEstimator {
+        UsageEstimator::new(UsageConfig {
+            session_gap_seconds: 300,
+            min_session_seconds: 60,
+            ..UsageConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_sessionizing() {
+        let mut usage = estimator();
+
+        // Session 1: 0s..600s in steps below the gap threshold
+        assert_eq!(usage.record("youtube.com", 0), 1);
+        assert_eq!(usage.record("youtube.com", 200), 3);
+        assert_eq!(usage.record("youtube.com", 400), 3);
+        assert_eq!(usage.record("youtube.com", 600), 4);
+
+        // A long gap starts a new session with the minimum duration
+        assert_eq!(usage.record("youtube.com", 5000), 1);
+
+        let stats = usage.usage();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].minutes, 12);
+        assert_eq!(stats[0].sessions, 2);
+        assert_eq!(stats[0].queries, 5);
+    }
+
+    #[test]
+    fn test_partial_minutes_carry_over() {
+        let mut usage = estimator();
+
+        assert_eq!(usage.record("reddit.com", 0), 1);
+        assert_eq!(usage.record("reddit.com", 30), 0);
+        assert_eq!(usage.record("reddit.com", 60), 1);
+        assert_eq!(usage.record("reddit.com", 90), 0);
+    }
+
+    #[test]
+    fn test_usage_sorted_by_minutes() {
+        let mut usage = estimator();
+        usage.record("a.com", 0);
+        usage.record("b.com", 0);
+        usage.record("b.com", 240);
+
+        let stats = usage.usage();
+        assert_eq!(stats[0].domain, "b.com");
+        assert_eq!(stats[1].domain, "a.com");
+    }
+}
@@ -0,0 +1,5 @@
+//! DNS-based usage time estimation.
+
+mod estimator;
+
+pub use estimator::UsageEstimator;
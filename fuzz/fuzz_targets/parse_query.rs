@@ -0,0 +1,26 @@
+//! Feeds arbitrary packets to the DNS server's query parser.
+//!
+//! Run with `just fuzz-dns` (needs nightly and cargo-fuzz).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../daemon/src/dns/parse.rs"]
+#[allow(dead_code)]
+mod parse;
+
+fuzz_target!(|packet: &[u8]| {
+    match parse::parse_query(packet) {
+        // Anything accepted is a single question within the limits
+        Ok(query) => {
+            assert_eq!(query.queries().len(), 1);
+            assert!(packet.len() <= parse::MAX_QUERY_LEN);
+        }
+        Err(rejected) => {
+            if let Some(response) = rejected.response {
+                assert_eq!(response.id(), u16::from_be_bytes([packet[0], packet[1]]));
+            }
+        }
+    }
+});
@@ -0,0 +1,44 @@
+#!/bin/bash
+# BlockAndFocus Daemon Install Script
+# Run as root by the app (after the administrator prompt) to install or
+# upgrade the daemon bundled with it.
+#
+# Usage: install-daemon.sh <daemon binary> <launchd plist> <progress file>
+#
+# The name of each step is appended to the progress file as it starts, so the
+# app can tell which step failed.
+
+set -euo pipefail
+
+SOURCE_DAEMON="$1"
+SOURCE_PLIST="$2"
+PROGRESS="$3"
+
+LABEL="com.blockandfocus.daemon"
+DAEMON_PATH="/Library/PrivilegedHelperTools/blockandfocus-daemon"
+PLIST_PATH="/Library/LaunchDaemons/$LABEL.plist"
+CONFIG_DIR="/Library/Application Support/BlockAndFocus"
+LOG_DIR="/Library/Logs/BlockAndFocus"
+
+step() {
+    echo "$1" >> "$PROGRESS"
+}
+
+# Stop a daemon that is already installed (an upgrade)
+step stopping
+launchctl bootout "system/$LABEL" 2>/dev/null || true
+for _ in $(seq 1 20); do
+    launchctl print "system/$LABEL" >/dev/null 2>&1 || break
+    sleep 0.5
+done
+
+step copying_daemon
+mkdir -p "$(dirname "$DAEMON_PATH")" "$CONFIG_DIR" "$LOG_DIR"
+install -m 755 -o root -g wheel "$SOURCE_DAEMON" "$DAEMON_PATH"
+
+step writing_plist
+install -m 644 -o root -g wheel "$SOURCE_PLIST" "$PLIST_PATH"
+
+step bootstrapping
+launchctl enable "system/$LABEL"
+launchctl bootstrap system "$PLIST_PATH"
@@ -59,8 +59,8 @@ end_time = "17:00"
 num_questions = 3
 min_operand = 10
 max_operand = 99
-timeout_seconds = 60
-min_solve_seconds = 3
+timeout = "1m"
+min_solve = "3s"
 EOF
     chown root:wheel "$CONFIG_DIR/config.toml"
     chmod 600 "$CONFIG_DIR/config.toml"
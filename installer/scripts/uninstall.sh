@@ -8,11 +8,25 @@ PLIST_PATH="/Library/LaunchDaemons/com.blockandfocus.daemon.plist"
 CONFIG_DIR="/Library/Application Support/BlockAndFocus"
 LOG_DIR="/Library/Logs/BlockAndFocus"
 APP_PATH="/Applications/BlockAndFocus.app"
+SOCKET_PATH="/var/run/blockandfocus.sock"
 
 echo "BlockAndFocus: Starting uninstallation..."
 
-# Stop and unload the daemon
+# Ask the daemon to stop first: during blocking hours it may insist on a quiz
 echo "BlockAndFocus: Stopping daemon..."
+if [ -S "$SOCKET_PATH" ]; then
+    RESPONSE=$(echo '{"type":"Shutdown"}' | nc -U "$SOCKET_PATH" 2>/dev/null || true)
+    case "$RESPONSE" in
+        ""|*'"type":"Success"'*)
+            ;;
+        *)
+            echo "BlockAndFocus: The daemon refuses to stop during blocking hours."
+            echo "Stop it from the menu bar app (after a quiz) or wait until blocking ends, then run this again."
+            exit 1
+            ;;
+    esac
+fi
+
 launchctl unload "$PLIST_PATH" 2>/dev/null || true
 
 # Restore original DNS settings
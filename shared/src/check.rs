@@ -0,0 +1,252 @@
+//! Offline configuration checks.
+//!
+//! Catches mistakes a config file can parse with but that make it behave
+//! differently from what its author meant, so a config can be checked
+//! (`bafctl config validate`, `blockandfocus-daemon --check-config`) before
+//! the daemon loads it.
+
+use crate::{
+    parse_client, BlockMode, ClientProfile, Config, Error, ScheduleRule, UpstreamProtocol,
+};
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+/// Parse the contents of a config file.
+pub fn parse_config(content: &str) -> Result<Config, Error> {
+    toml::from_str(content).map_err(|e| Error::invalid(e.to_string()))
+}
+
+/// Problems found in `config`, one sentence each.
+pub fn check_config(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if config.dns.upstream.is_empty() {
+        problems.push("dns.upstream lists no servers, so nothing can be resolved".to_string());
+    }
+    let zone_servers = config.dns.forward_zones.iter().flat_map(|zone| &zone.servers);
+    for server in config.dns.upstream.iter().chain(zone_servers) {
+        if server.protocol == UpstreamProtocol::Https {
+            problems.push(format!("{} uses DNS over HTTPS, which isn't supported yet", server));
+        }
+    }
+    if config.dns.listen_address.parse::<IpAddr>().is_err() {
+        problems.push(format!(
+            "dns.listen_address \"{}\" is not an IP address",
+            config.dns.listen_address
+        ));
+    }
+
+    if config.blocking.block_mode == BlockMode::Redirect && config.blocking.redirect_to.is_empty() {
+        problems.push(
+            "blocking.block_mode is redirect but blocking.redirect_to lists no addresses, so \
+             blocked names get no answers"
+                .to_string(),
+        );
+    }
+    let page = &config.block_page;
+    if page.enabled
+        && (config.blocking.block_mode != BlockMode::Redirect
+            || !config.blocking.redirect_to.contains(&page.address))
+    {
+        problems.push(format!(
+            "block_page is enabled but blocked names don't resolve to {}; set \
+             blocking.block_mode to redirect and add it to blocking.redirect_to",
+            page.address
+        ));
+    }
+
+    let mut groups = HashSet::new();
+    for group in &config.blocking.groups {
+        if !groups.insert(group.name.as_str()) {
+            problems.push(format!("Blocklist group \"{}\" is defined twice", group.name));
+        }
+    }
+
+    if let Err(schedule_problems) = config.schedule.validate() {
+        problems.extend(schedule_problems.iter().map(ToString::to_string));
+    }
+    for rule in &config.schedule.rules {
+        check_stage_groups(rule, &groups, &mut problems);
+    }
+
+    let mut profiles = HashSet::new();
+    for profile in &config.clients {
+        if !profiles.insert(profile.name.as_str()) {
+            problems.push(format!("Client profile \"{}\" is defined twice", profile.name));
+        }
+        problems.extend(check_client_profile(profile, config));
+    }
+
+    let reports = &config.reports;
+    if reports.enabled && reports.webhook.is_none() && reports.email.is_none() {
+        problems.push("reports is enabled but sets neither webhook nor email".to_string());
+    }
+    if reports.email.as_ref().is_some_and(|email| email.to.is_empty()) {
+        problems.push("reports.email.to lists no recipients".to_string());
+    }
+
+    problems
+}
+
+/// Problems with one client profile, checked against the groups in `config`.
+pub fn check_client_profile(profile: &ClientProfile, config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    if profile.name.trim().is_empty() {
+        problems.push("A client profile has no name".to_string());
+    }
+    if profile.clients.is_empty() {
+        problems.push(format!("Client profile \"{}\" lists no clients", profile.name));
+    }
+    for error in profile.clients.iter().filter_map(|client| parse_client(client).err()) {
+        problems.push(format!("Client profile \"{}\": {}", profile.name, error));
+    }
+    for group in &profile.groups {
+        if !config.blocking.groups.iter().any(|g| &g.name == group) {
+            problems.push(format!(
+                "Client profile \"{}\" names unknown group \"{}\"",
+                profile.name, group
+            ));
+        }
+    }
+    problems
+}
+
+fn check_stage_groups(rule: &ScheduleRule, groups: &HashSet<&str>, problems: &mut Vec<String>) {
+    for stage in &rule.stages {
+        for group in stage.groups.iter().filter(|g| !groups.contains(g.as_str())) {
+            problems.push(format!(
+                "Wind-down stage at {} of schedule rule \"{}\" names unknown group \"{}\"",
+                stage.at.0.format("%H:%M"),
+                rule.name,
+                group
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlocklistGroup, Schedule};
+
+    #[test]
+    fn test_default_config_is_clean() {
+        assert!(check_config(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_schedule_problems() {
+        let mut config = Config::default();
+        config.blocking.groups = vec![BlocklistGroup {
+            name: "social".to_string(),
+            domains: vec!["instagram.com".parse().unwrap()],
+            weekly_budget_minutes: None,
+            enabled: true,
+        }];
+        config.schedule = toml::from_str::<Schedule>(
+            r#"
+            enabled = true
+
+            [[rules]]
+            name = "Evening"
+            days = ["mon"]
+            start_time = "20:00"
+            end_time = "02:00"
+            stages = [
+                { at = "01:00", groups = ["social"] },
+                { at = "03:00", groups = ["tv"] },
+            ]
+
+            [[rules]]
+            name = "Evening"
+            days = []
+            start_time = "09:00"
+            end_time = "09:00"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            check_config(&config),
+            vec![
+                "Wind-down stage at 03:00 is outside schedule rule \"Evening\"",
+                "Schedule rule \"Evening\" is defined twice",
+                "Schedule rule \"Evening\" has no days, so it never applies",
+                "Schedule rule \"Evening\" starts and ends at the same time, so it never blocks",
+                "Wind-down stage at 03:00 of schedule rule \"Evening\" names unknown group \"tv\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upstream_problems() {
+        let mut config = Config::default();
+        let mut server = crate::UpstreamServer::parse("9.9.9.9").unwrap();
+        server.protocol = UpstreamProtocol::Tls;
+        config.dns.upstream = vec![server.clone()];
+        assert!(check_config(&config).is_empty());
+
+        server.protocol = UpstreamProtocol::Https;
+        config.dns.upstream = vec![server];
+        assert_eq!(
+            check_config(&config),
+            vec!["https://9.9.9.9:443 uses DNS over HTTPS, which isn't supported yet"]
+        );
+    }
+
+    #[test]
+    fn test_redirect_needs_addresses() {
+        let mut config = Config::default();
+        config.blocking.block_mode = BlockMode::Redirect;
+        assert_eq!(check_config(&config).len(), 1);
+        config.blocking.redirect_to = vec!["192.168.1.10".parse().unwrap()];
+        assert!(check_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_block_page_needs_redirect() {
+        let mut config = Config::default();
+        config.block_page.enabled = true;
+        assert_eq!(check_config(&config).len(), 1);
+        config.blocking.block_mode = BlockMode::Redirect;
+        config.blocking.redirect_to = vec!["127.0.0.1".parse().unwrap()];
+        assert!(check_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_report_problems() {
+        let mut config = Config::default();
+        config.reports.enabled = true;
+        assert_eq!(
+            check_config(&config),
+            vec!["reports is enabled but sets neither webhook nor email"]
+        );
+
+        config.reports.email = Some(Default::default());
+        assert_eq!(check_config(&config), vec!["reports.email.to lists no recipients"]);
+    }
+
+    #[test]
+    fn test_client_problems() {
+        let mut config = Config::default();
+        let profile = ClientProfile {
+            name: "Tablet".to_string(),
+            clients: vec!["192.168.1.23".to_string(), "tablet".to_string()],
+            blocking: Default::default(),
+            allow_bypass: false,
+            groups: vec!["games".to_string()],
+        };
+        config.clients = vec![profile.clone(), profile];
+
+        assert_eq!(
+            check_config(&config),
+            vec![
+                "Client profile \"Tablet\": \"tablet\" is not an IP address, range or MAC address",
+                "Client profile \"Tablet\" names unknown group \"games\"",
+                "Client profile \"Tablet\" is defined twice",
+                "Client profile \"Tablet\": \"tablet\" is not an IP address, range or MAC address",
+                "Client profile \"Tablet\" names unknown group \"games\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Entries of a client profile's `clients` list.
+//!
+//! Shared so a config can be checked without the daemon (`bafctl config
+//! validate`) the same way the daemon reads it.
+
+use std::net::IpAddr;
+
+/// One entry of a profile's `clients` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMatch {
+    Address(IpAddr),
+    /// Network address and prefix length
+    Range(IpAddr, u8),
+    /// Lowercase, colon-separated, two digits per byte
+    Mac(String),
+}
+
+impl ClientMatch {
+    /// Whether a query from `ip` (with `mac`, if known) matches this entry.
+    pub fn matches(&self, ip: IpAddr, mac: Option<&str>) -> bool {
+        match self {
+            Self::Address(address) => *address == ip,
+            Self::Range(network, prefix) => in_range(ip, *network, *prefix),
+            Self::Mac(wanted) => mac == Some(wanted.as_str()),
+        }
+    }
+}
+
+/// Parse a `clients` entry: an IP address, a CIDR range or a MAC address.
+pub fn parse_client(entry: &str) -> Result<ClientMatch, String> {
+    let entry = entry.trim();
+    if let Some((network, prefix)) = entry.split_once('/') {
+        let network: IpAddr = network.parse().map_err(|_| format!("\"{}\" is not a range", entry))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        return match prefix.parse::<u8>() {
+            Ok(prefix) if prefix <= max => Ok(ClientMatch::Range(network.to_canonical(), prefix)),
+            _ => Err(format!("\"{}\" has an invalid prefix length", entry)),
+        };
+    }
+    if let Ok(address) = entry.parse::<IpAddr>() {
+        return Ok(ClientMatch::Address(address.to_canonical()));
+    }
+    normalize_mac(entry)
+        .map(ClientMatch::Mac)
+        .ok_or_else(|| format!("\"{}\" is not an IP address, range or MAC address", entry))
+}
+
+/// A MAC address as `aa:bb:cc:dd:ee:ff`. macOS's arp drops leading zeros
+/// (`a:b:c:d:e:f`), and `-` separators are accepted too.
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let bytes: Vec<u8> = mac
+        .split([':', '-'])
+        .map(|part| match part.len() {
+            1 | 2 => u8::from_str_radix(part, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (bytes.len() == 6).then(|| {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+    })
+}
+
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip.to_canonical(), network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert_eq!(parse_client("192.168.1.23"), Ok(ClientMatch::Address(ip("192.168.1.23"))));
+        assert_eq!(parse_client("10.0.0.0/8"), Ok(ClientMatch::Range(ip("10.0.0.0"), 8)));
+        assert_eq!(parse_client("fd00::/64"), Ok(ClientMatch::Range(ip("fd00::"), 64)));
+        assert_eq!(
+            parse_client("A4-83-E7-02-03-4F"),
+            Ok(ClientMatch::Mac("a4:83:e7:02:03:4f".to_string()))
+        );
+        assert_eq!(normalize_mac("a4:83:e7:2:3:4f").as_deref(), Some("a4:83:e7:02:03:4f"));
+
+        assert!(parse_client("10.0.0.0/33").is_err());
+        assert!(parse_client("tablet").is_err());
+        assert!(parse_client("a4:83:e7:02:03").is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! Validated domain names.
+//!
+//! Domains arrive from the app, the config file and downloaded lists in many
+//! spellings ("Example.COM.", "bücher.de", "xn--bcher-kva.de"). A `DomainName`
+//! holds the one normalized form used for matching: lowercase ASCII, with
+//! internationalized labels in punycode and no trailing dot.
+
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt;
+
+/// Longest domain name, in characters (RFC 1035).
+const MAX_NAME_LEN: usize = 253;
+
+/// Longest label, in characters (RFC 1035).
+const MAX_LABEL_LEN: usize = 63;
+
+/// A valid, normalized domain name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DomainName(String);
+
+impl DomainName {
+    /// Parse and normalize a domain name.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let trimmed = input.trim().trim_end_matches('.');
+        if trimmed.is_empty() {
+            return Err(Error::invalid("Domain name is empty"));
+        }
+
+        let invalid = || Error::invalid(format!("\"{}\" is not a valid domain name", input.trim()));
+        let ascii = idna::domain_to_ascii(trimmed).map_err(|_| invalid())?;
+        if ascii.len() > MAX_NAME_LEN {
+            return Err(Error::invalid(format!(
+                "\"{}\" is longer than {} characters",
+                input.trim(),
+                MAX_NAME_LEN
+            )));
+        }
+
+        for label in ascii.split('.') {
+            let valid = !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+            if !valid {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self(ascii))
+    }
+
+    /// The normalized (ASCII) form.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The name with internationalized labels decoded, for display.
+    pub fn to_unicode(&self) -> String {
+        idna::domain_to_unicode(&self.0).0
+    }
+}
+
+impl fmt::Display for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for DomainName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for DomainName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for DomainName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for DomainName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::str::FromStr for DomainName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for DomainName {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Error> {
+        Self::parse(&s)
+    }
+}
+
+impl From<DomainName> for String {
+    fn from(domain: DomainName) -> Self {
+        domain.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<String, Error> {
+        DomainName::parse(input).map(String::from)
+    }
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(parse("  Facebook.COM. ").unwrap(), "facebook.com");
+        assert_eq!(parse("_dmarc.example.com").unwrap(), "_dmarc.example.com");
+        assert_eq!(parse("bücher.de").unwrap(), "xn--bcher-kva.de");
+        assert_eq!(parse("XN--BCHER-KVA.de").unwrap(), "xn--bcher-kva.de");
+        assert_eq!(DomainName::parse("xn--bcher-kva.de").unwrap().to_unicode(), "bücher.de");
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for input in ["", " . ", "a..com", "-a.com", "a-.com", "a b.com", "a/b.com", "*.a.com"] {
+            assert!(parse(input).is_err(), "{:?} should be rejected", input);
+        }
+        assert!(parse(&format!("{}.com", "a".repeat(64))).is_err());
+        assert!(parse(&format!("{}.com", "a".repeat(63))).is_ok());
+        assert!(parse(&vec!["a".repeat(60); 5].join(".")).is_err());
+    }
+
+    #[test]
+    fn test_serde_rejects_invalid() {
+        let domains: Vec<DomainName> = serde_json::from_str(r#"["Example.com."]"#).unwrap();
+        assert_eq!(domains[0], "example.com");
+        assert_eq!(serde_json::to_string(&domains).unwrap(), r#"["example.com"]"#);
+        assert!(serde_json::from_str::<Vec<DomainName>>(r#"["not a domain"]"#).is_err());
+    }
+}
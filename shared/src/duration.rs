@@ -0,0 +1,220 @@
+//! Human-friendly durations for config and IPC.
+//!
+//! Written as "90s", "45m", "2h", "1d" or combinations like "1h30m", and
+//! always serialized in that canonical form. Plain integers are accepted too,
+//! as seconds, so configs written before these fields took durations keep
+//! loading; `Duration::deserialize_minutes` reads unitless values (`15` or
+//! `"15"`) as minutes for fields that used to count minutes.
+
+use crate::Error;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Units in the canonical form, largest first.
+const UNITS: [(char, u64); 4] = [('d', 86_400), ('h', 3_600), ('m', 60), ('s', 1)];
+
+/// A length of time, to the second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration(u64);
+
+impl Duration {
+    pub const fn from_secs(secs: u64) -> Self {
+        Self(secs)
+    }
+
+    /// A duration of `mins` minutes, saturating.
+    pub const fn from_mins(mins: u64) -> Self {
+        Self(mins.saturating_mul(60))
+    }
+
+    /// A duration of `mins` minutes, or None if that many seconds overflow.
+    pub const fn checked_from_mins(mins: u64) -> Option<Self> {
+        match mins.checked_mul(60) {
+            Some(secs) => Some(Self(secs)),
+            None => None,
+        }
+    }
+
+    pub const fn as_secs(&self) -> u64 {
+        self.0
+    }
+
+    /// Whole minutes, rounding a partial minute up.
+    pub fn as_mins(&self) -> u32 {
+        u32::try_from(self.0.div_ceil(60)).unwrap_or(u32::MAX)
+    }
+
+    /// Seconds as a `u32` (e.g. for DNS TTLs), saturating.
+    pub fn as_secs_u32(&self) -> u32 {
+        u32::try_from(self.0).unwrap_or(u32::MAX)
+    }
+
+    /// Deserialize a duration, reading unitless values as minutes, whether
+    /// written as a number or as text.
+    pub fn deserialize_minutes<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let minutes = |mins| {
+            Self::checked_from_mins(mins).ok_or_else(|| {
+                de::Error::custom(format!("{} minutes is too long a duration", mins))
+            })
+        };
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(mins) => minutes(mins),
+            Raw::Text(text) => match text.trim().parse() {
+                Ok(mins) => minutes(mins),
+                Err(_) => text.parse().map_err(de::Error::custom),
+            },
+        }
+    }
+}
+
+impl FromStr for Duration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::invalid(format!(
+                "\"{}\" is not a duration (expected e.g. \"90s\", \"45m\", \"2h\" or \"1h30m\")",
+                s
+            ))
+        };
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(invalid());
+        }
+        if let Ok(secs) = s.parse() {
+            return Ok(Self(secs));
+        }
+
+        let mut total: u64 = 0;
+        let mut number = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let unit = UNITS
+                .iter()
+                .find(|(name, _)| *name == c.to_ascii_lowercase())
+                .map(|(_, secs)| *secs)
+                .ok_or_else(invalid)?;
+            let value: u64 = number.parse().map_err(|_| invalid())?;
+            total = value
+                .checked_mul(unit)
+                .and_then(|secs| total.checked_add(secs))
+                .ok_or_else(invalid)?;
+            number.clear();
+        }
+        if !number.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self(total))
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("0s");
+        }
+        let mut rest = self.0;
+        for (name, secs) in UNITS {
+            if rest >= secs {
+                write!(f, "{}{}", rest / secs, name)?;
+                rest %= secs;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Duration> for std::time::Duration {
+    fn from(duration: Duration) -> Self {
+        std::time::Duration::from_secs(duration.0)
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(secs) => Ok(Self(secs)),
+            Raw::Text(text) => text.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+/// A duration as written: a bare number or text.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Raw {
+    Number(u64),
+    Text(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> u64 {
+        s.parse::<Duration>().unwrap().as_secs()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(parse("90s"), 90);
+        assert_eq!(parse("45m"), 2700);
+        assert_eq!(parse("2h"), 7200);
+        assert_eq!(parse("1h30m"), 5400);
+        assert_eq!(parse("1d"), 86_400);
+        assert_eq!(parse("60"), 60);
+        for invalid in ["", "m", "5x", "1h30", "-5m", "1.5h"] {
+            assert!(invalid.parse::<Duration>().is_err(), "{:?} should be rejected", invalid);
+        }
+
+        assert_eq!(Duration::from_secs(90).to_string(), "1m30s");
+        assert_eq!(Duration::from_secs(5400).to_string(), "1h30m");
+        assert_eq!(Duration::from_secs(0).to_string(), "0s");
+        assert_eq!(Duration::from_secs(61).as_mins(), 2);
+    }
+
+    #[test]
+    fn test_serde() {
+        #[derive(Deserialize)]
+        struct Legacy {
+            #[serde(deserialize_with = "Duration::deserialize_minutes")]
+            minutes: Duration,
+            seconds: Duration,
+        }
+
+        let parsed: Legacy = toml::from_str("minutes = 15\nseconds = 60").unwrap();
+        assert_eq!(parsed.minutes, Duration::from_mins(15));
+        assert_eq!(parsed.seconds, Duration::from_secs(60));
+
+        // Unitless text means the same as the bare number
+        let parsed: Legacy = toml::from_str("minutes = \"15\"\nseconds = \"60\"").unwrap();
+        assert_eq!(parsed.minutes, Duration::from_mins(15));
+        assert_eq!(parsed.seconds, Duration::from_secs(60));
+        let parsed: Legacy = serde_json::from_str(r#"{"minutes":" 15 ","seconds":60}"#).unwrap();
+        assert_eq!(parsed.minutes, Duration::from_mins(15));
+
+        let parsed: Legacy = serde_json::from_str(r#"{"minutes":"2h","seconds":"90s"}"#).unwrap();
+        assert_eq!(parsed.minutes, Duration::from_mins(120));
+        assert_eq!(serde_json::to_string(&parsed.seconds).unwrap(), r#""1m30s""#);
+
+        // Too many minutes to count in seconds
+        let huge = format!(r#"{{"minutes":{},"seconds":0}}"#, u64::MAX / 60 + 1);
+        assert!(serde_json::from_str::<Legacy>(&huge).is_err());
+        let huge = format!(r#"{{"minutes":"{}","seconds":0}}"#, u64::MAX / 60 + 1);
+        assert!(serde_json::from_str::<Legacy>(&huge).is_err());
+        assert_eq!(Duration::from_mins(u64::MAX).as_secs(), u64::MAX);
+    }
+}
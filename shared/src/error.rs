@@ -0,0 +1,168 @@
+//! Typed errors shared by the daemon and the app.
+//!
+//! The daemon answers a failed command with `Response::Error`, carrying an
+//! `ErrorCode` and, where it has one, structured `ErrorContext` (how long to
+//! wait, which domain, how long settings stay locked). The app turns that,
+//! and its own failures, into an `Error` it hands to the webview, which
+//! branches on `kind`, `code` and `context` rather than the English message.
+
+use crate::{ErrorCode, Response};
+use serde::{Deserialize, Serialize};
+
+/// Details the app can act on without parsing the message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ErrorContext {
+    /// Retry after this many seconds
+    RetryAfter { seconds: u64 },
+    /// The domain the command was about
+    Domain { domain: String },
+    /// Settings stay locked for this many more minutes
+    LockedFor { minutes: u32 },
+    /// Minutes left in the bypass budget
+    BudgetRemaining { minutes: u32 },
+}
+
+/// Why a command failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Error {
+    /// The daemon refused or failed the command
+    #[error("{message}")]
+    Daemon {
+        code: ErrorCode,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        context: Option<ErrorContext>,
+    },
+    /// The daemon couldn't be reached
+    #[error("{message}")]
+    Unavailable { message: String },
+    /// The daemon answered with a response the command doesn't expect
+    #[error("{message}")]
+    UnexpectedResponse { message: String },
+    /// The input was rejected before anything was sent
+    #[error("{message}")]
+    Invalid { message: String },
+    /// The app failed on its own side (saving settings, installing the daemon)
+    #[error("{message}")]
+    App { message: String },
+}
+
+impl Error {
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::Unavailable { message: message.into() }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::Invalid { message: message.into() }
+    }
+
+    pub fn app(message: impl Into<String>) -> Self {
+        Self::App { message: message.into() }
+    }
+
+    /// The daemon's error code, if the daemon refused the command.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Daemon { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The structured context, if there is any.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Daemon { context, .. } => context.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A response that isn't the one a command expects, as an error.
+impl From<Response> for Error {
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Error { code, message, context } => Self::Daemon { code, message, context },
+            _ => Self::UnexpectedResponse {
+                message: "Unexpected response from daemon".to_string(),
+            },
+        }
+    }
+}
+
+impl From<Error> for Response {
+    fn from(error: Error) -> Self {
+        let code = match &error {
+            Error::Daemon { code, .. } => *code,
+            Error::Invalid { .. } => ErrorCode::InvalidCommand,
+            Error::Unavailable { .. } | Error::UnexpectedResponse { .. } | Error::App { .. } => {
+                ErrorCode::InternalError
+            }
+        };
+        let context = error.context().cloned();
+        Response::Error { code, message: error.to_string(), context }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_round_trip() {
+        let response = Response::Error {
+            code: ErrorCode::RateLimited,
+            message: "Slow down".to_string(),
+            context: Some(ErrorContext::RetryAfter { seconds: 3 }),
+        };
+        let error = Error::from(response);
+        assert_eq!(error.code(), Some(ErrorCode::RateLimited));
+        assert_eq!(error.context(), Some(&ErrorContext::RetryAfter { seconds: 3 }));
+        assert_eq!(error.to_string(), "Slow down");
+
+        match Response::from(error) {
+            Response::Error { code, context, .. } => {
+                assert_eq!(code, ErrorCode::RateLimited);
+                assert_eq!(context, Some(ErrorContext::RetryAfter { seconds: 3 }));
+            }
+            _ => panic!("Expected an error response"),
+        }
+
+        let unexpected = Error::from(Response::Success);
+        assert!(matches!(unexpected, Error::UnexpectedResponse { .. }));
+        assert!(matches!(
+            Response::from(Error::invalid("bad")),
+            Response::Error { code: ErrorCode::InvalidCommand, .. }
+        ));
+    }
+
+    #[test]
+    fn test_serialization_for_the_app() {
+        let error = Error::Daemon {
+            code: ErrorCode::SettingsLocked,
+            message: "Settings are locked".to_string(),
+            context: Some(ErrorContext::LockedFor { minutes: 20 }),
+        };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "daemon",
+                "code": "settings_locked",
+                "message": "Settings are locked",
+                "context": { "type": "locked_for", "minutes": 20 },
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Error::unavailable("Daemon not running")).unwrap(),
+            serde_json::json!({ "kind": "unavailable", "message": "Daemon not running" })
+        );
+    }
+
+    #[test]
+    fn test_old_error_responses_still_parse() {
+        let json = r#"{"type":"Error","payload":{"code":"invalid_domain","message":"No"}}"#;
+        let response: Response = serde_json::from_str(json).unwrap();
+        assert!(matches!(response, Response::Error { context: None, .. }));
+    }
+}
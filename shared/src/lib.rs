@@ -1,8 +1,28 @@
 //! Shared types for BlockAndFocus IPC protocol and configuration.
 
-use chrono::{NaiveTime, Weekday};
+use chrono::{NaiveDate, NaiveTime, Weekday};
 use serde::{Deserialize, Serialize};
 
+mod auth;
+mod check;
+mod client;
+mod domain;
+mod duration;
+mod error;
+mod hex;
+mod schedule;
+mod upstream;
+
+pub use auth::{command_mac, mac_hex};
+pub use check::{check_client_profile, check_config, parse_config};
+pub use client::{normalize_mac, parse_client, ClientMatch};
+pub use domain::DomainName;
+pub use duration::Duration;
+pub use error::{Error, ErrorContext};
+pub use hex::{from_hex, to_hex};
+pub use schedule::{ScheduleProblem, ScheduleRuleBuilder, ScheduleTemplate};
+pub use upstream::{UpstreamProtocol, UpstreamServer};
+
 /// IPC Commands sent from the UI to the daemon.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
@@ -14,31 +34,202 @@ pub enum Command {
     GetBlocklist,
 
     /// Add a domain to the blocklist
-    AddDomain { domain: String },
+    AddDomain { domain: DomainName },
 
-    /// Remove a domain from the blocklist
-    RemoveDomain { domain: String },
+    /// Remove a domain from the blocklist (answers with a quiz challenge while
+    /// blocking is active when `protection.quiz_for_weakening` is set)
+    RemoveDomain { domain: DomainName },
+
+    /// Enable or disable a blocklist group (disabling is quiz-gated like RemoveDomain)
+    SetGroupEnabled { name: String, enabled: bool },
 
     /// Get the current schedule configuration
     GetSchedule,
 
-    /// Update the schedule configuration
+    /// Update the schedule configuration. With `protection.delay_weakening_edits`,
+    /// edits that reduce blocking are answered with `ScheduleChangePending` and only
+    /// take effect at the next midnight; a later edit replaces a pending one. With
+    /// `protection.quiz_for_weakening`, they first take a quiz while blocking is active
     UpdateSchedule { schedule: Schedule },
 
+    /// Get schedule edits waiting to take effect
+    GetPendingChanges,
+
+    /// Suspend the schedule from `start` to `end` (local dates, inclusive).
+    /// Must be scheduled `vacation.min_notice_hours` in advance
+    ScheduleVacation { start: NaiveDate, end: NaiveDate },
+
+    /// Cancel a vacation that has not started yet
+    CancelVacation { id: String },
+
+    /// Get upcoming and current vacations
+    GetVacations,
+
+    /// Get the periods the schedule will block in over the next 7 days
+    GetScheduleTimeline,
+
+    /// Generate a schedule from a template, answered with `Schedule`. Nothing is
+    /// applied; the schedule can be edited and then sent with `UpdateSchedule`
+    GenerateSchedule { template: ScheduleTemplate },
+
+    /// Never block a domain (only outside blocking hours, since it weakens blocking)
+    AddEssentialDomain { domain: DomainName },
+
+    /// Remove a domain from the essential-services list
+    RemoveEssentialDomain { domain: DomainName },
+
+    /// Get the essential-services list
+    GetEssentialDomains,
+
+    /// Block right now for the next N minutes by adding a temporary schedule rule
+    FocusNow { minutes: u32 },
+
+    /// Delay the next schedule rule activation (triggers quiz challenge, once per day)
+    SnoozeSchedule { minutes: u32 },
+
     /// Request a bypass (triggers quiz challenge)
-    RequestBypass { duration_minutes: u32 },
+    RequestBypass {
+        /// How long the bypass lasts, e.g. "15m" (unitless values are minutes)
+        #[serde(alias = "duration_minutes", deserialize_with = "Duration::deserialize_minutes")]
+        duration: Duration,
+        /// Why the bypass is needed (required when `bypass.require_reason` is set)
+        #[serde(default)]
+        reason: Option<String>,
+    },
+
+    /// End the reveal phase of a memory challenge and start answering
+    BeginQuizAnswer { challenge_id: String },
 
     /// Submit quiz answers to complete bypass request
     SubmitQuizAnswers {
         challenge_id: String,
-        answers: Vec<i32>,
+        answers: Vec<QuizAnswer>,
     },
 
     /// Cancel an active bypass early
     CancelBypass,
 
+    /// Stop the daemon. During blocking hours this answers with a quiz
+    /// challenge when `protection.quiz_for_shutdown` is set
+    Shutdown,
+
+    /// Freeze every change that weakens blocking for the next N hours.
+    /// The lock cannot be lifted early, only extended.
+    LockSettings { hours: u32 },
+
+    /// Get DNS response cache statistics
+    GetCacheStats,
+
+    /// Flush the DNS response cache (a single name, or everything if None)
+    FlushCache { domain: Option<String> },
+
+    /// Get a weekly breakdown of bypasses for the last `weeks` weeks
+    GetBypassAnalytics { weeks: u32 },
+
+    /// Get blocked queries per day and hour for the last `weeks` weeks
+    GetActivityHeatmap { weeks: u32 },
+
+    /// Get query counters and today's estimated usage per site
+    GetStats,
+
+    /// Explain whether a domain would be blocked right now, and why
+    ExplainDomain { domain: String },
+
+    /// Check that the system actually sends its DNS queries through the daemon
+    RunSelfCheck,
+
+    /// Send this week's report now, to check the delivery settings
+    SendWeeklyReport,
+
+    /// Subscribe to daemon events; the connection then only streams `Response::Event`
+    Subscribe,
+
+    /// Get up to `limit` of the most recent stored queries matching `filter`
+    GetQueryLog {
+        #[serde(default = "default_query_log_limit")]
+        limit: usize,
+        #[serde(default)]
+        filter: QueryLogFilter,
+    },
+
+    /// Stream DNS queries as they happen; the connection then only streams
+    /// `Response::QueryLogEntry`, starting with up to `recent` stored entries
+    TailQueryLog {
+        #[serde(default)]
+        filter: QueryLogFilter,
+        #[serde(default)]
+        recent: usize,
+    },
+
+    /// Check the release manifest for a newer daemon
+    CheckForUpdate,
+
+    /// Download, verify and install the latest daemon, then restart
+    ApplyUpdate,
+
+    /// Get the daemon version and build information
+    GetVersion,
+
+    /// Get the refresh status of subscribed blocklists
+    GetSubscriptions,
+
+    /// Get recent changes to subscribed lists, newest first; with `domain`,
+    /// only changes that added it or one of its parents
+    GetBlocklistDiffs {
+        #[serde(default)]
+        domain: Option<String>,
+    },
+
+    /// Get the per-client policy profiles for devices using this daemon as DNS server
+    GetClientProfiles,
+
+    /// Add a client profile, or replace the one with the same name (refused
+    /// while settings are locked or a strict rule is active)
+    SetClientProfile { profile: ClientProfile },
+
+    /// Remove a client profile (refused while settings are locked or a strict
+    /// rule is active)
+    RemoveClientProfile { name: String },
+
+    /// Delete personal data. Sent without `confirmation` it only returns a
+    /// token; sending it again with that token within a minute deletes the data
+    PurgeData {
+        scope: PurgeScope,
+        #[serde(default)]
+        confirmation: Option<String>,
+    },
+
+    /// Export the configuration and persisted state as a single archive
+    CreateBackup,
+
+    /// Turn another blocker's export (base64 of the file) into groups and
+    /// subscriptions; with `dry_run` only report what would be created
+    ImportExternal {
+        source: ImportSource,
+        data: String,
+        #[serde(default)]
+        dry_run: bool,
+    },
+
+    /// Replace the configuration and persisted state with a `CreateBackup`
+    /// archive (refused while blocking is active or settings are locked)
+    RestoreBackup { archive: String },
+
     /// Ping to check if daemon is alive
     Ping,
+
+    /// Start an authenticated session (the client must be able to read the IPC key)
+    StartSession,
+
+    /// A command signed for an authenticated session.
+    /// `mac` is `command_mac(key, session_id, nonce, counter, command)`; each
+    /// counter may be used once per session
+    Authenticated {
+        session_id: String,
+        counter: u64,
+        mac: String,
+        command: Box<Command>,
+    },
 }
 
 /// IPC Responses sent from the daemon to the UI.
@@ -49,7 +240,12 @@ pub enum Response {
     Status(Status),
 
     /// Current blocklist
-    Blocklist { domains: Vec<String> },
+    Blocklist {
+        domains: Vec<DomainName>,
+        /// Daily allowances and today's usage
+        #[serde(default)]
+        allowances: Vec<AllowanceStatus>,
+    },
 
     /// Current schedule configuration
     Schedule(Schedule),
@@ -57,18 +253,113 @@ pub enum Response {
     /// Quiz challenge for bypass request
     QuizChallenge(QuizChallenge),
 
+    /// DNS response cache statistics
+    CacheStats(CacheStats),
+
+    /// Cache flush result
+    CacheFlushed { entries_removed: usize },
+
+    /// Weekly bypass breakdown, most recent week first
+    BypassAnalytics { weeks: Vec<WeeklyBypassSummary> },
+
+    /// Blocked queries per day and hour
+    ActivityHeatmap(ActivityHeatmap),
+
+    /// Query counters and usage estimates
+    Stats(UsageStats),
+
+    /// Why a domain is or is not blocked
+    DomainExplanation(DomainExplanation),
+
+    /// Result of the DNS routing self-check
+    SelfCheck(SelfCheckReport),
+
+    /// Subscription confirmed; events follow on this connection
+    Subscribed,
+
+    /// An event pushed to subscribers
+    Event(Event),
+
+    /// A query pushed to TailQueryLog subscribers
+    QueryLogEntry(QueryLogEntry),
+
+    /// Stored queries, oldest first
+    QueryLog { entries: Vec<QueryLogEntry> },
+
+    /// Result of an update check
+    UpdateStatus(UpdateStatus),
+
+    /// Update installed; the daemon restarts shortly
+    UpdateApplied { version: String },
+
+    /// Daemon version and build information
+    Version(VersionInfo),
+
+    /// Subscribed blocklists with their refresh status
+    Subscriptions { lists: Vec<SubscriptionStatus> },
+
+    /// Per-client policy profiles
+    ClientProfiles { profiles: Vec<ClientProfile> },
+
+    /// Recent changes to subscribed lists, newest first
+    BlocklistDiffs { diffs: Vec<BlocklistDiff> },
+
+    /// The schedule edit reduces blocking and was deferred until `applies_at`
+    ScheduleChangePending { applies_at: i64 },
+
+    /// Schedule edit waiting to take effect, if any
+    PendingChanges { schedule: Option<PendingScheduleChange> },
+
+    /// Upcoming and current vacations, earliest first
+    Vacations { vacations: Vec<Vacation> },
+
+    /// Periods the schedule will block in, earliest first
+    ScheduleTimeline { windows: Vec<BlockingWindow> },
+
+    /// Domains that are never blocked
+    EssentialDomains { domains: Vec<DomainName> },
+
+    /// Send PurgeData again with this confirmation to delete the data
+    PurgeConfirmationRequired {
+        confirmation: String,
+        expires_in_seconds: u32,
+    },
+
+    /// Personal data was deleted
+    DataPurged { scope: PurgeScope },
+
+    /// A backup archive (JSON text) to keep and pass to RestoreBackup later
+    Backup { archive: String },
+
+    /// What ImportExternal created, or would create on a dry run
+    ImportPreview(ImportPreview),
+
     /// Operation completed successfully
     Success,
 
     /// Pong response to ping
     Pong,
 
-    /// Error response
-    Error { code: ErrorCode, message: String },
+    /// An authenticated session was started
+    SessionStarted {
+        session_id: String,
+        nonce: String,
+        /// Optional features the daemon supports (empty from older daemons)
+        #[serde(default)]
+        capabilities: Vec<Capability>,
+    },
+
+    /// Error response, with structured context where the daemon has it
+    Error {
+        code: ErrorCode,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        context: Option<ErrorContext>,
+    },
 }
 
 /// Current daemon status.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Status {
     /// Whether blocking is currently active
     pub blocking_active: bool,
@@ -76,236 +367,1928 @@ pub struct Status {
     /// Number of domains in the blocklist
     pub blocked_domains_count: usize,
 
-    /// Number of DNS queries blocked since daemon start
-    pub queries_blocked: u64,
+    /// Number of DNS queries blocked since daemon start
+    pub queries_blocked: u64,
+
+    /// Number of DNS queries forwarded since daemon start
+    pub queries_forwarded: u64,
+
+    /// Unix timestamp when bypass expires (None if no active bypass)
+    pub bypass_until: Option<i64>,
+
+    /// Name of the currently active schedule rule (None if outside schedule)
+    pub active_schedule_rule: Option<String>,
+
+    /// Whether the schedule is enabled
+    pub schedule_enabled: bool,
+
+    /// Bypass minutes left today (None if no daily budget is configured)
+    #[serde(default)]
+    pub bypass_budget_remaining_minutes: Option<u32>,
+
+    /// Unix timestamp when a granted bypass takes effect (soft-start countdown)
+    #[serde(default)]
+    pub bypass_starts_at: Option<i64>,
+
+    /// Daily allowances and today's usage
+    #[serde(default)]
+    pub allowances: Vec<AllowanceStatus>,
+
+    /// Unix timestamp until which the schedule is snoozed
+    #[serde(default)]
+    pub schedule_snoozed_until: Option<i64>,
+
+    /// Last day of the vacation in progress (None if not on vacation)
+    #[serde(default)]
+    pub vacation_until: Option<NaiveDate>,
+
+    /// Groups blocked by wind-down rules ahead of full blocking
+    #[serde(default)]
+    pub wind_down_groups: Vec<String>,
+
+    /// Whether dry-run (observe-only) mode is on
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Queries that would have been blocked outside dry-run mode since daemon start
+    #[serde(default)]
+    pub queries_would_block: u64,
+
+    /// Malformed DNS packets rejected since daemon start
+    #[serde(default)]
+    pub queries_malformed: u64,
+
+    /// Connections to encrypted DNS resolvers seen while blocking was active
+    #[serde(default)]
+    pub encrypted_dns_connections: u64,
+
+    /// Most recent encrypted DNS connection (an app may be evading the filter)
+    #[serde(default)]
+    pub last_encrypted_dns: Option<EncryptedDnsDetection>,
+
+    /// Version of a newer daemon release, if one was found
+    #[serde(default)]
+    pub update_available: Option<String>,
+
+    /// Strictness of the active schedule rules (None if outside schedule)
+    #[serde(default)]
+    pub active_strictness: Option<Strictness>,
+
+    /// Minutes until the settings lock ends (None if settings are not locked)
+    #[serde(default)]
+    pub settings_lock_remaining_minutes: Option<u32>,
+
+    /// Language of daemon-produced text
+    #[serde(default)]
+    pub locale: Locale,
+
+    /// Link to a focus partner (None if partner sessions are off)
+    #[serde(default)]
+    pub partner: Option<PartnerStatus>,
+}
+
+/// State of the link to a focus partner.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartnerStatus {
+    /// Whether the partner's daemon is connected
+    pub connected: bool,
+
+    /// Name the partner introduced themselves with
+    pub name: Option<String>,
+
+    /// Unix timestamp when the partner's focus session ends (None if none is running)
+    pub session_ends_at: Option<i64>,
+}
+
+/// DNS response cache statistics.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Number of cached responses
+    pub entries: usize,
+
+    /// Approximate memory used by cached responses in bytes
+    pub bytes: usize,
+
+    /// Number of lookups answered from the cache
+    pub hits: u64,
+
+    /// Number of lookups not found in the cache
+    pub misses: u64,
+
+    /// Number of entries evicted to respect the cache limits
+    pub evictions: u64,
+}
+
+/// Events pushed by the daemon to subscribed connections.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum Event {
+    /// A usage budget crossed a warning threshold (75%, 90% or 100%)
+    BudgetWarning(BudgetWarning),
+
+    /// An app connected to an encrypted DNS resolver, bypassing the filter
+    EncryptedDnsDetected(EncryptedDnsDetection),
+
+    /// A newer daemon release is available
+    UpdateAvailable(UpdateManifest),
+
+    /// The DNS listener stopped answering and is being rebound
+    DnsWatchdogAlert(DnsWatchdogAlert),
+
+    /// The system clock was set or the time zone changed
+    ClockChanged(ClockChange),
+
+    /// Personal data was deleted with PurgeData
+    DataPurged(DataPurge),
+
+    /// A focus partner's session started, was extended or ended
+    PartnerSession(PartnerSession),
+
+    /// A bypass was asked for from the block page; the app should come to
+    /// the front so it can be requested there
+    BypassRequested(BypassPrompt),
+}
+
+/// A bypass asked for from the block page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BypassPrompt {
+    /// Site the block page was shown for
+    pub domain: String,
+}
+
+/// A change to a focus partner's session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartnerSession {
+    /// Partner's name
+    pub partner: String,
+
+    /// Unix timestamp when the session ends (None once it has ended)
+    pub ends_at: Option<i64>,
+}
+
+/// Personal data deleted with PurgeData.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataPurge {
+    /// What was deleted
+    pub scope: PurgeScope,
+
+    /// Unix timestamp of the deletion
+    pub purged_at: i64,
+}
+
+/// Personal data PurgeData can delete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgeScope {
+    /// Stored query log entries, and the DNS cache of recently resolved names
+    QueryLog,
+    /// Query counters, blocked-domain counts and today's per-site usage
+    Stats,
+    /// Granted bypasses and their reasons (the daily bypass budget is kept)
+    BypassHistory,
+    /// All of the above
+    All,
+}
+
+impl PurgeScope {
+    /// Whether this scope includes `other`.
+    pub fn covers(self, other: PurgeScope) -> bool {
+        self == PurgeScope::All || self == other
+    }
+}
+
+/// A change to the system clock or time zone, after which blocking was re-evaluated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockChange {
+    /// Seconds the wall clock moved beyond real elapsed time (0 for a time zone change)
+    pub jump_seconds: i64,
+
+    /// Offset of local time from UTC before the change, in seconds
+    pub previous_utc_offset_seconds: i32,
+
+    /// Offset of local time from UTC after the change, in seconds
+    pub utc_offset_seconds: i32,
+
+    /// Whether blocking is active after the change
+    pub blocking_active: bool,
+}
+
+/// The DNS listener failed repeated watchdog probes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsWatchdogAlert {
+    /// Address the listener is bound to
+    pub listen_addr: String,
+
+    /// Probes that went unanswered in a row
+    pub consecutive_failures: u32,
+}
+
+/// Daemon version and build information.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// Semantic version of the daemon
+    pub version: String,
+
+    /// Git commit the daemon was built from
+    pub git_hash: Option<String>,
+
+    /// UTC build timestamp
+    pub build_date: Option<String>,
+
+    /// Cargo features enabled in the build
+    pub features: Vec<String>,
+
+    /// IPC protocol version spoken by the daemon
+    pub protocol_version: u32,
+
+    /// Optional features the daemon supports (empty from older daemons)
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+impl VersionInfo {
+    /// Whether the daemon supports `capability`.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// An optional protocol feature a client can check for before relying on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// `Subscribe` streams daemon events
+    EventStreaming,
+    /// `TailQueryLog` streams query log entries
+    QueryLogTail,
+    /// Blocklist groups (`SetGroupEnabled`, group budgets, wind-down stages)
+    Groups,
+    /// `FocusNow` starts a focus session
+    FocusSessions,
+    /// `ScheduleVacation` pauses the schedule for whole days
+    Vacations,
+    /// Subscribed blocklists (`GetSubscriptions`, `GetBlocklistDiffs`)
+    Subscriptions,
+    /// `Response::Error` carries structured `ErrorContext`
+    ErrorContext,
+    /// A capability added by a newer daemon than this build knows about
+    #[serde(other)]
+    Unknown,
+}
+
+/// Release manifest describing the latest daemon build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateManifest {
+    /// Release version (`major.minor.patch`)
+    pub version: String,
+
+    /// Download URL of the signed daemon binary
+    pub url: String,
+
+    /// SHA-256 of the binary, hex encoded
+    pub sha256: String,
+
+    /// Release notes
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Result of checking for a daemon update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateStatus {
+    /// Version of the running daemon
+    pub current_version: String,
+
+    /// Latest release found (None if the check failed)
+    pub latest: Option<UpdateManifest>,
+
+    /// Whether the latest release is newer than the running daemon
+    pub update_available: bool,
+
+    /// Unix timestamp of the check
+    pub checked_at: i64,
+}
+
+/// Blockers whose exports `ImportExternal` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    /// A SelfControl blocklist (`.selfcontrol` file or its preferences plist)
+    SelfControl,
+    /// A Cold Turkey Blocker export (settings JSON or a plain list of sites)
+    ColdTurkey,
+    /// A Pi-hole Teleporter archive
+    PiHole,
+}
+
+/// Result of `ImportExternal`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportPreview {
+    /// Whether the import was applied (false for a dry run)
+    pub applied: bool,
+
+    /// Blocklist groups created; a group whose name exists is merged into it
+    pub groups: Vec<BlocklistGroup>,
+
+    /// Blocklist subscriptions added
+    pub subscriptions: Vec<BlocklistSubscription>,
+
+    /// Entries that have no BlockAndFocus equivalent, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Refresh health of a subscribed blocklist.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionStatus {
+    /// Display name
+    pub name: String,
+
+    /// URL of the list
+    pub url: String,
+
+    /// Whether the list is merged into the blocklist
+    pub enabled: bool,
+
+    /// Unix timestamp of the last download attempt
+    pub last_fetch_at: Option<i64>,
+
+    /// Unix timestamp of the last successful download (or unchanged check)
+    pub last_success_at: Option<i64>,
+
+    /// HTTP status of the last download attempt, if a response arrived
+    pub http_status: Option<u16>,
+
+    /// Domains taken from the list
+    pub entries: u64,
+
+    /// Lines of the list that were not valid domains
+    pub invalid_lines: u64,
+
+    /// Error of the last download attempt, if it failed
+    pub last_error: Option<String>,
+}
+
+/// A date range during which the schedule is suspended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vacation {
+    /// Unique vacation ID
+    pub id: String,
+
+    /// First day (local date)
+    pub start: NaiveDate,
+
+    /// Last day (local date, inclusive)
+    pub end: NaiveDate,
+
+    /// Unix timestamp when the vacation was scheduled
+    pub created_at: i64,
+}
+
+/// A schedule edit that reduces blocking, deferred until the next midnight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingScheduleChange {
+    /// The schedule that will replace the current one
+    pub schedule: Schedule,
+
+    /// Unix timestamp when the edit was made
+    pub submitted_at: i64,
+
+    /// Unix timestamp when the edit takes effect
+    pub applies_at: i64,
+}
+
+/// A period in which the schedule blocks, for previewing the week ahead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockingWindow {
+    /// Rule that blocks (None when the schedule is disabled and blocking never stops)
+    pub rule: Option<String>,
+
+    /// Groups blocked by a wind-down rule that isn't blocking everything yet
+    /// (empty when everything is blocked)
+    pub groups: Vec<String>,
+
+    /// How hard the rule is to get around
+    pub strictness: Strictness,
+
+    /// Unix timestamp when the window starts
+    pub start: i64,
+
+    /// Unix timestamp when the window ends
+    pub end: i64,
+}
+
+/// Domains added to and removed from a subscribed list by one refresh.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlocklistDiff {
+    /// Name of the list
+    pub list: String,
+
+    /// URL of the list
+    pub url: String,
+
+    /// Unix timestamp of the refresh
+    pub refreshed_at: i64,
+
+    /// Added domains, sorted (truncated for very large changes)
+    pub added: Vec<String>,
+
+    /// Removed domains, sorted (truncated for very large changes)
+    pub removed: Vec<String>,
+
+    /// Number of added domains
+    pub added_count: u64,
+
+    /// Number of removed domains
+    pub removed_count: u64,
+}
+
+/// A connection to a public DNS-over-HTTPS/TLS resolver while blocking was active.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedDnsDetection {
+    /// Resolver address
+    pub resolver: String,
+
+    /// Remote port (443 for DoH, 853 for DoT)
+    pub port: u16,
+
+    /// Unix timestamp when the connection was seen
+    pub detected_at: i64,
+}
+
+/// What a usage budget applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetKind {
+    /// Weekly budget of a blocklist group
+    Group,
+
+    /// Daily allowance of a single domain
+    Domain,
+}
+
+/// A usage budget reaching a warning threshold.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetWarning {
+    /// Whether this is a group budget or a domain allowance
+    pub kind: BudgetKind,
+
+    /// Group name or domain
+    pub name: String,
+
+    /// Threshold reached, in percent of the budget
+    pub percent: u8,
+
+    /// Estimated minutes used in the current period
+    pub used_minutes: u32,
+
+    /// Minutes allowed in the current period
+    pub budget_minutes: u32,
+
+    /// Minutes left before the budget is exhausted
+    pub remaining_minutes: u32,
+}
+
+/// Result of resolving test names through the daemon and the system resolver.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfCheckReport {
+    /// The daemon answered a query sent directly to its listener
+    pub listener_reachable: bool,
+
+    /// A query made through the system resolver reached the daemon
+    pub system_routed: bool,
+
+    /// Blocklist entry used to test blocking
+    pub blocked_name: Option<String>,
+
+    /// Whether the blocked name resolved to a blocked address
+    /// (None if blocking is not active right now)
+    pub blocked_name_blocked: Option<bool>,
+
+    /// Name used to test normal resolution
+    pub allowed_name: String,
+
+    /// Whether the allowed name resolved to a real address
+    pub allowed_name_resolved: bool,
+
+    /// Human-readable verdict
+    pub summary: String,
+}
+
+/// Where a domain's matching entry comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchSource {
+    /// The main blocklist
+    Blocklist,
+
+    /// A blocklist group
+    Group,
+
+    /// A daily domain allowance
+    Allowance,
+
+    /// The essential-services list (never blocked)
+    Essential,
+}
+
+/// Whether a domain would be blocked right now, and why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainExplanation {
+    /// Normalized domain that was checked
+    pub domain: String,
+
+    /// Whether a query for the domain would be blocked right now
+    pub blocked: bool,
+
+    /// Human-readable summary of the decision
+    pub reason: String,
+
+    /// Entry the domain matched (the domain itself or a parent domain)
+    pub matched_entry: Option<String>,
+
+    /// Where the matched entry comes from
+    pub match_source: Option<MatchSource>,
+
+    /// Whether the entry matched exactly rather than as a parent domain
+    pub exact_match: bool,
+
+    /// Group containing the entry
+    pub group: Option<String>,
+
+    /// Minutes allowed per period for a budgeted group or allowance
+    pub budget_minutes: Option<u32>,
+
+    /// Minutes used in the current period for a budgeted group or allowance
+    pub budget_used_minutes: Option<u32>,
+
+    /// Whether blocking is enabled at all
+    pub blocking_enabled: bool,
+
+    /// Whether the schedule is enabled
+    pub schedule_enabled: bool,
+
+    /// Name of the schedule rule active right now
+    pub active_schedule_rule: Option<String>,
+
+    /// Unix timestamp until which the schedule is snoozed
+    pub schedule_snoozed_until: Option<i64>,
+
+    /// Whether a bypass is in effect
+    pub bypass_active: bool,
+
+    /// Unix timestamp when the bypass expires
+    pub bypass_until: Option<i64>,
+}
+
+/// How the daemon answered a DNS query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryAction {
+    /// Answered with a blocked response
+    Blocked,
+
+    /// Forwarded to an upstream resolver
+    Forwarded,
+
+    /// Answered from the response cache
+    Cached,
+
+    /// Upstream resolution failed (SERVFAIL)
+    Failed,
+
+    /// Every upstream failed and an expired cached answer was served instead
+    Stale,
+
+    /// Answered locally without forwarding (link-local names, suppressed AAAA)
+    Local,
+}
+
+/// A single DNS query handled by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// Unix timestamp of the query
+    pub timestamp: i64,
+
+    /// Queried name (without trailing dot)
+    pub domain: String,
+
+    /// Record type (e.g. "A", "AAAA")
+    pub record_type: String,
+
+    /// How the query was answered
+    pub action: QueryAction,
+
+    /// Address of the client that sent the query
+    pub client: String,
+
+    /// Forwarded only because of dry-run mode; blocking would have blocked it
+    #[serde(default)]
+    pub would_block: bool,
+}
+
+/// Criteria for selecting query log entries. Empty fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryLogFilter {
+    /// Only names containing this text (case-insensitive)
+    pub domain: Option<String>,
+
+    /// Only queries answered this way
+    pub action: Option<QueryAction>,
+}
+
+impl QueryLogFilter {
+    /// Check whether an entry matches the filter.
+    pub fn matches(&self, entry: &QueryLogEntry) -> bool {
+        let domain_matches = self
+            .domain
+            .as_ref()
+            .is_none_or(|d| entry.domain.to_lowercase().contains(&d.to_lowercase()));
+        let action_matches = self.action.is_none_or(|a| entry.action == a);
+        domain_matches && action_matches
+    }
+}
+
+/// Query counters and today's estimated usage.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// Local date (YYYY-MM-DD) the usage estimates cover
+    pub date: String,
+
+    /// Number of DNS queries blocked since daemon start
+    pub queries_blocked: u64,
+
+    /// Number of DNS queries forwarded since daemon start
+    pub queries_forwarded: u64,
+
+    /// Estimated usage per site, most used first
+    pub domains: Vec<DomainUsage>,
+
+    /// Number of distinct blocklist entries
+    #[serde(default)]
+    pub blocklist_entries: u64,
+
+    /// Approximate memory used by the blocklist, in bytes
+    #[serde(default)]
+    pub blocker_memory_bytes: u64,
+}
+
+/// Estimated usage of a single site, derived from its DNS queries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainUsage {
+    /// Site (e.g. "youtube.com" for all of its subdomains)
+    pub domain: String,
+
+    /// Approximate minutes of use
+    pub minutes: u32,
+
+    /// Number of distinct sessions
+    pub sessions: u32,
+
+    /// Number of resolved queries
+    pub queries: u64,
+}
+
+/// Blocked queries per day and hour, for a heatmap of when blocking kicks in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityHeatmap {
+    /// Every day from the Monday `weeks` weeks back to today, oldest first
+    pub days: Vec<HeatmapDay>,
+
+    /// Highest count of any single hour, for scaling colors
+    pub max_hourly: u32,
+}
+
+/// Blocked queries during one day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeatmapDay {
+    /// Local date as YYYY-MM-DD
+    pub date: String,
+
+    /// Blocked queries in each local hour (24 entries)
+    pub hours: Vec<u32>,
+
+    /// Blocked queries over the whole day
+    pub total: u32,
+}
+
+/// Bypass statistics for one week.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyBypassSummary {
+    /// First day of the week (Monday), as YYYY-MM-DD
+    pub week_start: String,
+
+    /// Number of bypasses granted
+    pub bypass_count: u32,
+
+    /// Sum of requested bypass durations in minutes
+    pub total_minutes_requested: u64,
+
+    /// Minutes actually spent in a bypass (early cancellations excluded)
+    pub total_minutes_used: u64,
+
+    /// Average minutes a bypass ran beyond its first request (through follow-up bypasses)
+    pub average_overrun_minutes: f64,
+
+    /// Most common reasons, most frequent first
+    pub top_reasons: Vec<ReasonCount>,
+}
+
+/// How often a bypass reason was given.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReasonCount {
+    pub reason: String,
+    pub count: u32,
+}
+
+/// Quiz challenge for bypass requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuizChallenge {
+    /// Unique challenge ID
+    pub challenge_id: String,
+
+    /// Questions to display (e.g., "23 + 45 = ?")
+    pub questions: Vec<String>,
+
+    /// Unix timestamp when this challenge expires
+    pub expires_at: i64,
+
+    /// Kind of challenge
+    #[serde(default)]
+    pub kind: QuizKind,
+
+    /// Current phase of the challenge
+    #[serde(default)]
+    pub phase: QuizPhase,
+
+    /// Items to memorize during the reveal phase (empty otherwise)
+    #[serde(default)]
+    pub reveal: Vec<String>,
+
+    /// How long the reveal items should be shown, in seconds
+    #[serde(default)]
+    pub reveal_seconds: u32,
+
+    /// Expected input for each question (empty means all numeric)
+    #[serde(default)]
+    pub answer_kinds: Vec<AnswerKind>,
+
+    /// Current stage of a multi-stage challenge (1-based)
+    #[serde(default)]
+    pub stage: u32,
+
+    /// Total number of stages in the challenge
+    #[serde(default)]
+    pub total_stages: u32,
+
+    /// Unix timestamp until which a wait stage must run
+    #[serde(default)]
+    pub wait_until: Option<i64>,
+
+    /// Reason given for the previous bypass, shown as a reminder
+    #[serde(default)]
+    pub last_reason: Option<String>,
+
+    /// What passing the challenge unlocks
+    #[serde(default)]
+    pub purpose: QuizPurpose,
+}
+
+/// What passing a quiz challenge unlocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuizPurpose {
+    /// Temporarily disable blocking
+    #[default]
+    Bypass,
+
+    /// Delay the next schedule rule activation
+    SnoozeSchedule,
+
+    /// Stop the daemon
+    Shutdown,
+
+    /// Remove a domain from the blocklist
+    RemoveDomain,
+
+    /// Disable a blocklist group
+    DisableGroup,
+
+    /// Change the schedule so it blocks less
+    UpdateSchedule,
+}
+
+/// One step of the bypass friction pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuizStage {
+    /// A quiz of the configured kind (arithmetic or memory)
+    Quiz,
+
+    /// A mandatory waiting period
+    Wait { seconds: u32 },
+
+    /// Type a confirmation sentence exactly
+    Confirm { sentence: String },
+}
+
+/// Expected input for a quiz question.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerKind {
+    /// Numeric answer
+    #[default]
+    Number,
+
+    /// Free-text answer
+    Text,
+}
+
+/// Kind of quiz challenge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuizKind {
+    /// Arithmetic questions with numeric answers
+    #[default]
+    Arithmetic,
+
+    /// Memorize a sequence of words or digits, then type it back
+    Memory,
+
+    /// Wait stage of a multi-stage challenge
+    Wait,
+
+    /// Typed confirmation stage of a multi-stage challenge
+    Confirm,
+}
+
+/// Phase of a quiz challenge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuizPhase {
+    /// Items are being shown to the user; answers are not accepted yet
+    Reveal,
+
+    /// Answers are being collected
+    #[default]
+    Answer,
+}
+
+/// A single quiz answer: a number for arithmetic questions or free text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum QuizAnswer {
+    Number(i32),
+    Text(String),
+}
+
+impl From<i32> for QuizAnswer {
+    fn from(n: i32) -> Self {
+        QuizAnswer::Number(n)
+    }
+}
+
+impl From<String> for QuizAnswer {
+    fn from(s: String) -> Self {
+        QuizAnswer::Text(s)
+    }
+}
+
+/// Schedule configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Whether scheduling is enabled
+    pub enabled: bool,
+
+    /// List of schedule rules
+    pub rules: Vec<ScheduleRule>,
+}
+
+/// A single schedule rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    /// Human-readable name for this rule
+    pub name: String,
+
+    /// Days of the week this rule applies
+    pub days: Vec<WeekdayWrapper>,
+
+    /// Start time (blocking begins)
+    pub start_time: NaiveTimeWrapper,
+
+    /// End time (blocking ends)
+    pub end_time: NaiveTimeWrapper,
+
+    /// Unix timestamp when a temporary rule (e.g. "Focus now") expires.
+    /// Temporary rules apply continuously until then, regardless of days and times.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+
+    /// How hard the rule is to get around while it is active
+    #[serde(default)]
+    pub strictness: Strictness,
+
+    /// Wind-down stages. When set, the rule blocks only the groups of the
+    /// stages reached so far, and everything once a stage without groups is reached
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<WindDownStage>,
+}
+
+/// A step of a wind-down rule: from `at` on, also block `groups`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindDownStage {
+    /// Time of day the stage begins (within the rule's window)
+    pub at: NaiveTimeWrapper,
+
+    /// Blocklist groups to block; an empty list blocks everything
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// How a schedule rule can be bypassed while it is active.
+///
+/// Ordered from most to least permissive; when several rules are active the
+/// strictest one applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Bypasses are granted without a quiz
+    Relaxed,
+    /// Bypasses require the quiz
+    #[default]
+    Normal,
+    /// No bypasses, and the blocklist and schedule cannot be weakened
+    Strict,
+}
+
+/// Wrapper for chrono::Weekday with serde support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekdayWrapper {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<WeekdayWrapper> for Weekday {
+    fn from(w: WeekdayWrapper) -> Self {
+        match w {
+            WeekdayWrapper::Mon => Weekday::Mon,
+            WeekdayWrapper::Tue => Weekday::Tue,
+            WeekdayWrapper::Wed => Weekday::Wed,
+            WeekdayWrapper::Thu => Weekday::Thu,
+            WeekdayWrapper::Fri => Weekday::Fri,
+            WeekdayWrapper::Sat => Weekday::Sat,
+            WeekdayWrapper::Sun => Weekday::Sun,
+        }
+    }
+}
+
+impl From<Weekday> for WeekdayWrapper {
+    fn from(w: Weekday) -> Self {
+        match w {
+            Weekday::Mon => WeekdayWrapper::Mon,
+            Weekday::Tue => WeekdayWrapper::Tue,
+            Weekday::Wed => WeekdayWrapper::Wed,
+            Weekday::Thu => WeekdayWrapper::Thu,
+            Weekday::Fri => WeekdayWrapper::Fri,
+            Weekday::Sat => WeekdayWrapper::Sat,
+            Weekday::Sun => WeekdayWrapper::Sun,
+        }
+    }
+}
+
+/// Wrapper for NaiveTime with string serialization (HH:MM format).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NaiveTimeWrapper(pub NaiveTime);
+
+impl Serialize for NaiveTimeWrapper {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let s = self.0.format("%H:%M").to_string();
+        serializer.serialize_str(&s)
+    }
+}
+
+impl<'de> Deserialize<'de> for NaiveTimeWrapper {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        NaiveTime::parse_from_str(&s, "%H:%M")
+            .map(NaiveTimeWrapper)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<NaiveTime> for NaiveTimeWrapper {
+    fn from(t: NaiveTime) -> Self {
+        NaiveTimeWrapper(t)
+    }
+}
+
+impl From<NaiveTimeWrapper> for NaiveTime {
+    fn from(t: NaiveTimeWrapper) -> Self {
+        t.0
+    }
+}
+
+/// Error codes for IPC responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Invalid command format
+    InvalidCommand,
+
+    /// Domain is invalid or already exists/doesn't exist
+    InvalidDomain,
+
+    /// Quiz challenge not found or expired
+    QuizNotFound,
+
+    /// Quiz challenge has expired
+    QuizExpired,
+
+    /// Wrong quiz answers
+    QuizFailed,
+
+    /// Quiz solved too quickly (anti-automation)
+    QuizTooFast,
+
+    /// Quiz is not in the expected phase (e.g. answering during reveal)
+    QuizWrongPhase,
+
+    /// A wait stage of the quiz has not elapsed yet
+    QuizWaitPending,
+
+    /// Cannot bypass during strict schedule
+    BypassNotAllowed,
+
+    /// Blocking cannot be weakened right now
+    SettingsLocked,
+
+    /// The schedule cannot be snoozed (already snoozed today or nothing upcoming)
+    SnoozeUnavailable,
+
+    /// A bypass reason is required (or too short)
+    ReasonRequired,
+
+    /// The requested bypass exceeds the remaining daily budget
+    BudgetExceeded,
+
+    /// Configuration error
+    ConfigError,
+
+    /// Checking for or installing an update failed
+    UpdateFailed,
+
+    /// The weekly report could not be delivered
+    ReportFailed,
+
+    /// Too many commands or connections; retry later
+    RateLimited,
+
+    /// The connecting program is not trusted to send this command
+    UntrustedClient,
+
+    /// The command must be signed for a valid session (or the signature is wrong)
+    Unauthenticated,
+
+    /// Internal daemon error
+    InternalError,
+}
+
+/// Configuration file structure.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Language of quiz questions, errors and reports produced by the daemon
+    #[serde(default)]
+    pub locale: Locale,
+    pub dns: DnsConfig,
+    pub blocking: BlockingConfig,
+    pub schedule: Schedule,
+    pub quiz: QuizConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub bypass: BypassConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub query_log: QueryLogConfig,
+    #[serde(default)]
+    pub update: UpdateConfig,
+    #[serde(default)]
+    pub subscriptions: SubscriptionConfig,
+    #[serde(default)]
+    pub protection: ProtectionConfig,
+    #[serde(default)]
+    pub vacation: VacationConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub partner: PartnerConfig,
+    #[serde(default)]
+    pub reports: ReportConfig,
+    #[serde(default)]
+    pub block_page: BlockPageConfig,
+    /// Policies for other devices using this daemon as their DNS server
+    #[serde(default)]
+    pub clients: Vec<ClientProfile>,
+}
+
+/// Language for text produced by the daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    /// English
+    #[default]
+    En,
+    /// Italian
+    It,
+    /// Spanish
+    Es,
+    /// German
+    De,
+    /// French
+    Fr,
+}
+
+impl Locale {
+    /// All supported locales.
+    pub const ALL: [Locale; 5] = [Locale::En, Locale::It, Locale::Es, Locale::De, Locale::Fr];
+}
+
+/// DNS server configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsConfig {
+    /// Upstream DNS servers
+    pub upstream: Vec<UpstreamServer>,
+
+    /// Address to listen on
+    pub listen_address: String,
+
+    /// Port to listen on
+    pub listen_port: u16,
+
+    /// Strip private-range A/AAAA answers for public domains (DNS rebinding protection)
+    #[serde(default)]
+    pub rebinding_protection: bool,
+
+    /// Domains allowed to resolve to private addresses (split-horizon names)
+    #[serde(default)]
+    pub rebinding_allowlist: Vec<String>,
+
+    /// TTL for blocked answers; kept low so unblocking takes effect quickly
+    #[serde(default = "default_blocked_ttl", alias = "blocked_ttl_seconds")]
+    pub blocked_ttl: Duration,
+
+    /// Minimum TTL applied to forwarded answers
+    #[serde(default, alias = "min_ttl_seconds")]
+    pub min_ttl: Option<Duration>,
+
+    /// Maximum TTL applied to forwarded answers
+    #[serde(default, alias = "max_ttl_seconds")]
+    pub max_ttl: Option<Duration>,
+
+    /// Watch for apps using encrypted DNS resolvers while blocking is active
+    #[serde(default = "default_true")]
+    pub detect_encrypted_dns: bool,
+
+    /// Seconds between watchdog probes of the listener (0 disables the watchdog)
+    #[serde(default = "default_watchdog_interval")]
+    pub watchdog_interval_seconds: u32,
+
+    /// Unanswered probes in a row before the listener is rebound
+    #[serde(default = "default_watchdog_failure_threshold")]
+    pub watchdog_failure_threshold: u32,
+
+    /// Zones answered by their own servers, e.g. a VPN's resolver for internal names
+    #[serde(default)]
+    pub forward_zones: Vec<ForwardZone>,
+
+    /// What to do with `.local` and link-local reverse names
+    #[serde(default)]
+    pub link_local_names: LinkLocalPolicy,
+
+    /// Answer AAAA queries for allowed domains with no addresses, for networks
+    /// where IPv6 is broken
+    #[serde(default)]
+    pub force_ipv4: bool,
+
+    /// How long an unused connection to a TCP upstream is kept open for reuse
+    #[serde(default = "default_upstream_idle_timeout", alias = "upstream_idle_timeout_seconds")]
+    pub upstream_idle_timeout: Duration,
+}
+
+/// Handling of `.local` and link-local reverse names, which public resolvers
+/// can't answer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkLocalPolicy {
+    /// Answer NXDOMAIN without forwarding (multicast DNS still works)
+    #[default]
+    Nxdomain,
+    /// Forward like any other name (for networks using `.local` in unicast DNS)
+    Forward,
+}
+
+/// A zone forwarded to its own servers while they are reachable (split-horizon DNS).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardZone {
+    /// Zone (subdomains included), e.g. "internal.company.com"
+    pub zone: String,
+
+    /// Servers answering for the zone (plain DNS only)
+    pub servers: Vec<UpstreamServer>,
+
+    /// Use the regular upstreams while the zone's servers are unreachable
+    /// (otherwise queries fail)
+    #[serde(default = "default_true")]
+    pub fallback: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_blocked_ttl() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_watchdog_interval() -> u32 {
+    30
+}
+
+fn default_upstream_idle_timeout() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_watchdog_failure_threshold() -> u32 {
+    3
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            upstream: vec![
+                UpstreamServer::plain([1, 1, 1, 1].into()),
+                UpstreamServer::plain([8, 8, 8, 8].into()),
+            ],
+            listen_address: "127.0.0.1".to_string(),
+            listen_port: 53,
+            rebinding_protection: false,
+            rebinding_allowlist: Vec::new(),
+            blocked_ttl: default_blocked_ttl(),
+            min_ttl: None,
+            max_ttl: None,
+            detect_encrypted_dns: true,
+            watchdog_interval_seconds: default_watchdog_interval(),
+            watchdog_failure_threshold: default_watchdog_failure_threshold(),
+            forward_zones: Vec::new(),
+            link_local_names: LinkLocalPolicy::Nxdomain,
+            force_ipv4: false,
+            upstream_idle_timeout: default_upstream_idle_timeout(),
+        }
+    }
+}
+
+/// DNS response cache configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Whether forwarded responses are cached
+    pub enabled: bool,
+
+    /// Maximum number of cached responses
+    pub max_entries: usize,
+
+    /// Maximum total size of cached responses in bytes
+    pub max_bytes: usize,
+
+    /// Longest time a "no such name" or "no such record" answer is cached,
+    /// whatever the zone's SOA asks for (RFC 2308); zero disables negative caching
+    pub max_negative_ttl: Duration,
+
+    /// How long past its TTL an answer is kept to be served when every
+    /// upstream fails (RFC 8767); zero disables serving stale answers
+    pub max_stale: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: 10_000,
+            max_bytes: 8 * 1024 * 1024,
+            max_negative_ttl: Duration::from_mins(15),
+            max_stale: Duration::from_mins(24 * 60),
+        }
+    }
+}
+
+/// Query log configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryLogConfig {
+    /// Keep recent queries in memory (live tails work either way)
+    pub enabled: bool,
+
+    /// Maximum number of entries kept
+    pub max_entries: usize,
+
+    /// How much of each queried domain is recorded (in the log and log files)
+    pub privacy: LogPrivacy,
+
+    /// Also append stored queries to this file, one JSON object per line
+    pub file: Option<String>,
+
+    /// The file is moved to `<file>.1` once larger than this, in megabytes
+    pub max_file_mb: u32,
+}
+
+impl Default for QueryLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: 1000,
+            privacy: LogPrivacy::Full,
+            file: None,
+            max_file_mb: 10,
+        }
+    }
+}
+
+fn default_query_log_limit() -> usize {
+    100
+}
+
+/// How much of each queried domain is recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogPrivacy {
+    /// The full domain
+    #[default]
+    Full,
+    /// Only the registrable domain ("www.youtube.com" -> "youtube.com")
+    RegistrableDomain,
+    /// A salted hash of the domain, so repeat visits can still be counted
+    Hashed,
+    /// Nothing is recorded
+    Off,
+}
+
+/// Daemon self-update configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    /// Check for new releases in the background
+    pub check_automatically: bool,
+
+    /// URL of the release manifest
+    pub manifest_url: String,
+
+    /// Hours between background checks
+    pub check_interval_hours: u32,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            check_automatically: true,
+            manifest_url: "https://github.com/niccologrillo/BlockAndFocus/releases/latest/download/update.json"
+                .to_string(),
+            check_interval_hours: 24,
+        }
+    }
+}
+
+/// Remote blocklists merged into the blocklist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionConfig {
+    /// Subscribed lists
+    pub lists: Vec<BlocklistSubscription>,
+
+    /// Hours between refreshes
+    pub refresh_interval_hours: u32,
+
+    /// Lists downloaded at the same time
+    pub max_concurrent_fetches: u32,
+
+    /// Time allowed for downloading a single list, in seconds
+    pub fetch_timeout_seconds: u32,
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            lists: Vec::new(),
+            refresh_interval_hours: 24,
+            max_concurrent_fetches: 4,
+            fetch_timeout_seconds: 60,
+        }
+    }
+}
+
+/// Vacations: date ranges during which the schedule is suspended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VacationConfig {
+    /// How long before its first day a vacation must be scheduled, in hours
+    pub min_notice_hours: u32,
+}
+
+impl Default for VacationConfig {
+    fn default() -> Self {
+        Self { min_notice_hours: 48 }
+    }
+}
+
+/// How the daemon stores its data on disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Encrypt the state file, with a key kept in the System keychain
+    pub encrypt: bool,
+}
+
+/// A daily window for heavyweight housekeeping: refreshing subscriptions,
+/// rotating log files, compacting the state file and saving the DNS cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    /// Do housekeeping in the window; subscriptions are then refreshed there
+    /// instead of every `refresh_interval_hours`
+    pub enabled: bool,
+
+    /// Local time the window opens (HH:MM)
+    pub at: NaiveTimeWrapper,
+
+    /// How long the window stays open, in minutes; a window missed while
+    /// the machine was asleep or off waits for the next day
+    pub window_minutes: u32,
+
+    /// Log files larger than this are rotated, in megabytes
+    pub max_log_mb: u32,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            at: NaiveTimeWrapper(NaiveTime::from_hms_opt(4, 0, 0).unwrap()),
+            window_minutes: 120,
+            max_log_mb: 10,
+        }
+    }
+}
+
+/// Focus sessions shared with a partner's installation: a session started on
+/// either side starts (or is extended) on the other, ending at the same time.
+///
+/// The daemons talk directly (one `listen`s, the other `connect`s) or both
+/// connect to a relay (`blockandfocus-daemon --partner-relay`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PartnerConfig {
+    /// Link with the partner
+    pub enabled: bool,
+
+    /// Name shown to the partner
+    pub name: String,
+
+    /// Secret both sides share; it signs every message and picks the relay room
+    pub secret: String,
+
+    /// Address of the relay or the partner's daemon (host:port)
+    pub connect: Option<String>,
+
+    /// Address to accept the partner's connection on, when not connecting
+    pub listen: Option<String>,
+}
+
+/// A weekly report of blocking and bypass activity, sent every Sunday evening
+/// to a webhook and/or by email.
+///
+/// `subject` and `template` may use these placeholders: `{week_start}`,
+/// `{week_end}`, `{blocked}`, `{busiest_day}`, `{bypasses}`, `{bypass_minutes}`,
+/// `{overrun_minutes}`, `{top_reasons}` and `{groups}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportConfig {
+    /// Send the report
+    pub enabled: bool,
+
+    /// Local time on Sunday the report is sent (HH:MM); a report missed while
+    /// the machine was asleep or off is skipped
+    pub at: NaiveTimeWrapper,
 
-    /// Number of DNS queries forwarded since daemon start
-    pub queries_forwarded: u64,
+    /// URL the report is POSTed to as JSON (`subject`, `text` and `report`)
+    pub webhook: Option<String>,
 
-    /// Unix timestamp when bypass expires (None if no active bypass)
-    pub bypass_until: Option<i64>,
+    /// Send the report by email
+    pub email: Option<ReportEmail>,
 
-    /// Name of the currently active schedule rule (None if outside schedule)
-    pub active_schedule_rule: Option<String>,
+    /// Subject line (a translated default when unset)
+    pub subject: Option<String>,
 
-    /// Whether the schedule is enabled
-    pub schedule_enabled: bool,
+    /// Report text (a translated default when unset)
+    pub template: Option<String>,
 }
 
-/// Quiz challenge for bypass requests.
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            at: NaiveTimeWrapper(NaiveTime::from_hms_opt(19, 0, 0).unwrap()),
+            webhook: None,
+            email: None,
+            subject: None,
+            template: None,
+        }
+    }
+}
+
+/// Local page explaining the block, shown by browsers for blocked sites.
+///
+/// Blocked names must resolve to `address`, with `blocking.block_mode =
+/// "redirect"` and the address in `blocking.redirect_to`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QuizChallenge {
-    /// Unique challenge ID
-    pub challenge_id: String,
+#[serde(default)]
+pub struct BlockPageConfig {
+    /// Serve the page
+    pub enabled: bool,
 
-    /// Questions to display (e.g., "23 + 45 = ?")
-    pub questions: Vec<String>,
+    /// Address the page is served on
+    pub address: std::net::IpAddr,
 
-    /// Unix timestamp when this challenge expires
-    pub expires_at: i64,
+    /// Port for HTTP
+    pub http_port: u16,
+
+    /// Port where HTTPS connections are closed at once, so browsers fail
+    /// fast (no certificate could be valid for a blocked site); None to not
+    /// listen for HTTPS
+    pub https_port: Option<u16>,
 }
 
-/// Schedule configuration.
+impl Default for BlockPageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: std::net::Ipv4Addr::LOCALHOST.into(),
+            http_port: 80,
+            https_port: Some(443),
+        }
+    }
+}
+
+/// SMTP settings for sending the weekly report.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct Schedule {
-    /// Whether scheduling is enabled
-    pub enabled: bool,
+#[serde(default)]
+pub struct ReportEmail {
+    /// Mail server, e.g. `smtps://smtp.example.com:465` or
+    /// `smtp://smtp.example.com:587` (upgraded with STARTTLS)
+    pub server: String,
 
-    /// List of schedule rules
-    pub rules: Vec<ScheduleRule>,
+    /// Login for the mail server, if it needs one
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    /// Sender address
+    pub from: String,
+
+    /// Recipient addresses
+    pub to: Vec<String>,
 }
 
-/// A single schedule rule.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScheduleRule {
-    /// Human-readable name for this rule
+/// How blocking applies to the devices of a client profile, when the daemon
+/// serves other devices on the network. Queries from devices matching no
+/// profile, including this Mac, follow the normal configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientProfile {
+    /// Profile name
     pub name: String,
 
-    /// Days of the week this rule applies
-    pub days: Vec<WeekdayWrapper>,
+    /// Devices in the profile: IP addresses, CIDR ranges (`192.168.1.0/28`)
+    /// or MAC addresses, looked up in the ARP table
+    pub clients: Vec<String>,
 
-    /// Start time (blocking begins)
-    pub start_time: NaiveTimeWrapper,
+    /// When these devices are blocked
+    #[serde(default)]
+    pub blocking: ClientBlocking,
 
-    /// End time (blocking ends)
-    pub end_time: NaiveTimeWrapper,
+    /// Whether bypasses taken on this Mac also unblock these devices
+    #[serde(default = "default_true")]
+    pub allow_bypass: bool,
+
+    /// Blocklist groups blocked for these devices whenever blocking applies
+    /// to them, even if the group is disabled or has budget left
+    #[serde(default)]
+    pub groups: Vec<String>,
 }
 
-/// Wrapper for chrono::Weekday with serde support.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// When the devices of a client profile are blocked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
-pub enum WeekdayWrapper {
-    Mon,
-    Tue,
-    Wed,
-    Thu,
-    Fri,
-    Sat,
-    Sun,
+pub enum ClientBlocking {
+    /// Following the schedule, like this Mac
+    #[default]
+    Schedule,
+    /// All the time, whatever the schedule (as long as blocking is enabled)
+    Always,
+    /// Never
+    Never,
 }
 
-impl From<WeekdayWrapper> for Weekday {
-    fn from(w: WeekdayWrapper) -> Self {
-        match w {
-            WeekdayWrapper::Mon => Weekday::Mon,
-            WeekdayWrapper::Tue => Weekday::Tue,
-            WeekdayWrapper::Wed => Weekday::Wed,
-            WeekdayWrapper::Thu => Weekday::Thu,
-            WeekdayWrapper::Fri => Weekday::Fri,
-            WeekdayWrapper::Sat => Weekday::Sat,
-            WeekdayWrapper::Sun => Weekday::Sun,
-        }
-    }
-}
+/// Protection against stopping the daemon to get around blocking.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtectionConfig {
+    /// During blocking hours, only stop after a quiz: Shutdown answers with a
+    /// challenge and termination signals are ignored
+    pub quiz_for_shutdown: bool,
 
-impl From<Weekday> for WeekdayWrapper {
-    fn from(w: Weekday) -> Self {
-        match w {
-            Weekday::Mon => WeekdayWrapper::Mon,
-            Weekday::Tue => WeekdayWrapper::Tue,
-            Weekday::Wed => WeekdayWrapper::Wed,
-            Weekday::Thu => WeekdayWrapper::Thu,
-            Weekday::Fri => WeekdayWrapper::Fri,
-            Weekday::Sat => WeekdayWrapper::Sat,
-            Weekday::Sun => WeekdayWrapper::Sun,
-        }
-    }
-}
+    /// While blocking is active, removing a domain, disabling a group or
+    /// reducing the schedule takes a quiz
+    pub quiz_for_weakening: bool,
 
-/// Wrapper for NaiveTime with string serialization (HH:MM format).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct NaiveTimeWrapper(pub NaiveTime);
+    /// Schedule edits that reduce blocking only take effect at the next midnight
+    pub delay_weakening_edits: bool,
 
-impl Serialize for NaiveTimeWrapper {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let s = self.0.format("%H:%M").to_string();
-        serializer.serialize_str(&s)
-    }
+    /// Only accept commands that change state or read the query log from
+    /// trusted programs (and root)
+    pub trusted_clients_only: bool,
+
+    /// Executables, or app bundles containing them, that are trusted
+    pub trusted_clients: Vec<String>,
+
+    /// Code signing requirement trusted executables must also satisfy (macOS),
+    /// e.g. `identifier "com.blockandfocus.app" and anchor apple generic`
+    pub client_code_requirement: Option<String>,
+
+    /// Commands that change state or read the query log must be signed with
+    /// the IPC key in an authenticated session
+    pub require_authenticated_commands: bool,
 }
 
-impl<'de> Deserialize<'de> for NaiveTimeWrapper {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        NaiveTime::parse_from_str(&s, "%H:%M")
-            .map(NaiveTimeWrapper)
-            .map_err(serde::de::Error::custom)
-    }
+/// A remote blocklist in hosts-file or one-domain-per-line format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlocklistSubscription {
+    /// Display name
+    pub name: String,
+
+    /// URL of the list
+    pub url: String,
+
+    /// Whether the list is merged into the blocklist
+    #[serde(default = "default_true")]
+    pub enabled: bool,
 }
 
-impl From<NaiveTime> for NaiveTimeWrapper {
-    fn from(t: NaiveTime) -> Self {
-        NaiveTimeWrapper(t)
-    }
+/// Usage time estimation configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageConfig {
+    /// Queries further apart than this start a new session, in seconds
+    pub session_gap_seconds: u32,
+
+    /// Time counted for a session with a single query, in seconds
+    pub min_session_seconds: u32,
+
+    /// Stop counting usage while the machine is idle (no keyboard or mouse input)
+    pub pause_when_idle: bool,
+
+    /// Seconds without input after which the machine counts as idle
+    pub idle_threshold_seconds: u32,
+
+    /// Extend running "Focus now" rules by the time the machine was asleep
+    pub pause_focus_on_sleep: bool,
 }
 
-impl From<NaiveTimeWrapper> for NaiveTime {
-    fn from(t: NaiveTimeWrapper) -> Self {
-        t.0
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            session_gap_seconds: 300,
+            min_session_seconds: 60,
+            pause_when_idle: false,
+            idle_threshold_seconds: 300,
+            pause_focus_on_sleep: false,
+        }
     }
 }
 
-/// Error codes for IPC responses.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum ErrorCode {
-    /// Invalid command format
-    InvalidCommand,
+/// Blocking configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockingConfig {
+    /// Whether blocking is enabled
+    pub enabled: bool,
 
-    /// Domain is invalid or already exists/doesn't exist
-    InvalidDomain,
+    /// List of blocked domains
+    pub domains: Vec<DomainName>,
 
-    /// Quiz challenge not found or expired
-    QuizNotFound,
+    /// Observe only: count and log what would be blocked, but forward everything
+    #[serde(default)]
+    pub dry_run: bool,
 
-    /// Quiz challenge has expired
-    QuizExpired,
+    /// Named groups of domains with their own limits
+    #[serde(default)]
+    pub groups: Vec<BlocklistGroup>,
 
-    /// Wrong quiz answers
-    QuizFailed,
+    /// Domains allowed for a number of minutes per day, then blocked until midnight
+    #[serde(default)]
+    pub allowances: Vec<DomainAllowance>,
 
-    /// Quiz solved too quickly (anti-automation)
-    QuizTooFast,
+    /// Domains never blocked, whatever else matches them (subdomains included)
+    #[serde(default = "default_essential_domains")]
+    pub essential_domains: Vec<DomainName>,
 
-    /// Cannot bypass during strict schedule
-    BypassNotAllowed,
+    /// Also block answers whose CNAME chain passes through a blocked domain,
+    /// which catches trackers disguised as first-party subdomains
+    #[serde(default = "default_true")]
+    pub block_cname_cloaking: bool,
 
-    /// Configuration error
-    ConfigError,
+    /// How blocked queries are answered
+    #[serde(default)]
+    pub block_mode: BlockMode,
 
-    /// Internal daemon error
-    InternalError,
+    /// Addresses blocked A and AAAA queries are answered with in
+    /// `BlockMode::Redirect` (IPv4 and/or IPv6)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirect_to: Vec<std::net::IpAddr>,
 }
 
-/// Configuration file structure.
+/// How blocked queries are answered.
+///
+/// With the address modes, HTTPS/SVCB queries get an empty answer, so
+/// browsers fall back to the blocked A/AAAA answers; other types get NXDOMAIN.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockMode {
+    /// 0.0.0.0 and ::, so connections fail at once
+    #[default]
+    NullIp,
+    /// The name doesn't exist
+    Nxdomain,
+    /// The server refuses to answer
+    Refused,
+    /// The addresses in `redirect_to`, e.g. a page explaining the block
+    Redirect,
+}
+
+/// Services that must keep resolving for the machine to stay usable: captive
+/// portal detection, Apple push and software update, and the certificate
+/// revocation checks banks and other secure sites rely on.
+pub const ESSENTIAL_DOMAINS: &[&str] = &[
+    // Captive portal detection
+    "captive.apple.com",
+    "connectivitycheck.gstatic.com",
+    "clients3.google.com",
+    "www.msftconnecttest.com",
+    "detectportal.firefox.com",
+    // Apple push notifications, time and software update
+    "push.apple.com",
+    "time.apple.com",
+    "mesu.apple.com",
+    "gdmf.apple.com",
+    "swscan.apple.com",
+    "swdist.apple.com",
+    "swcdn.apple.com",
+    "updates.cdn-apple.com",
+    // Certificate revocation and validation
+    "ocsp.apple.com",
+    "ocsp2.apple.com",
+    "crl.apple.com",
+    "valid.apple.com",
+    "ocsp.digicert.com",
+    "crl3.digicert.com",
+    "crl4.digicert.com",
+    "ocsp.sectigo.com",
+    "ocsp.globalsign.com",
+    "ocsp.pki.goog",
+    "lencr.org",
+];
+
+fn default_essential_domains() -> Vec<DomainName> {
+    ESSENTIAL_DOMAINS
+        .iter()
+        .map(|d| DomainName::parse(d).expect("essential domains are valid"))
+        .collect()
+}
+
+/// A domain that resolves normally until its daily allowance is used up.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub dns: DnsConfig,
-    pub blocking: BlockingConfig,
-    pub schedule: Schedule,
-    pub quiz: QuizConfig,
+pub struct DomainAllowance {
+    /// Domain (subdomains included)
+    pub domain: DomainName,
+
+    /// Estimated minutes of use allowed per day
+    pub daily_minutes: u32,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            dns: DnsConfig::default(),
-            blocking: BlockingConfig::default(),
-            schedule: Schedule::default(),
-            quiz: QuizConfig::default(),
-        }
-    }
+/// Usage of a daily domain allowance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowanceStatus {
+    /// Domain the allowance applies to
+    pub domain: String,
+
+    /// Minutes allowed per day
+    pub daily_minutes: u32,
+
+    /// Estimated minutes used today
+    pub used_minutes: u32,
 }
 
-/// DNS server configuration.
+/// A named group of domains, e.g. "Video" for youtube.com and netflix.com.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DnsConfig {
-    /// Upstream DNS servers
-    pub upstream: Vec<String>,
+pub struct BlocklistGroup {
+    /// Group name
+    pub name: String,
 
-    /// Address to listen on
-    pub listen_address: String,
+    /// Domains in this group (subdomains included)
+    pub domains: Vec<DomainName>,
 
-    /// Port to listen on
-    pub listen_port: u16,
+    /// Estimated minutes of use allowed per week before the group is blocked.
+    /// None blocks the group like the main blocklist.
+    #[serde(default)]
+    pub weekly_budget_minutes: Option<u32>,
+
+    /// Whether the group is applied
+    #[serde(default = "default_true")]
+    pub enabled: bool,
 }
 
-impl Default for DnsConfig {
+impl Default for BlockingConfig {
     fn default() -> Self {
         Self {
-            upstream: vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()],
-            listen_address: "127.0.0.1".to_string(),
-            listen_port: 53,
+            enabled: true,
+            domains: ["facebook.com", "twitter.com", "instagram.com", "reddit.com", "tiktok.com"]
+                .iter()
+                .map(|d| DomainName::parse(d).expect("default domains are valid"))
+                .collect(),
+            dry_run: false,
+            groups: Vec::new(),
+            allowances: Vec::new(),
+            essential_domains: default_essential_domains(),
+            block_cname_cloaking: true,
+            block_mode: BlockMode::default(),
+            redirect_to: Vec::new(),
         }
     }
 }
 
-/// Blocking configuration.
+/// Bypass policy configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BlockingConfig {
-    /// Whether blocking is enabled
-    pub enabled: bool,
+#[serde(default)]
+pub struct BypassConfig {
+    /// Require a typed reason with every bypass request
+    pub require_reason: bool,
 
-    /// List of blocked domains
-    pub domains: Vec<String>,
+    /// Minimum reason length in characters
+    pub min_reason_length: usize,
+
+    /// Total bypass minutes allowed per day (None for unlimited)
+    pub daily_budget_minutes: Option<u32>,
+
+    /// Longest allowed schedule snooze (unitless values are minutes)
+    #[serde(alias = "snooze_max_minutes", deserialize_with = "Duration::deserialize_minutes")]
+    pub snooze_max: Duration,
+
+    /// Start granted bypasses gradually (countdown, then most-blocked domains last)
+    pub soft_start: bool,
+
+    /// Countdown before a soft-start bypass takes effect
+    #[serde(alias = "soft_start_countdown_seconds")]
+    pub soft_start_countdown: Duration,
+
+    /// Number of most-blocked domains kept blocked at the start of a bypass
+    pub soft_start_top_domains: usize,
+
+    /// How long the most-blocked domains stay blocked (unitless values are minutes)
+    #[serde(alias = "soft_start_minutes", deserialize_with = "Duration::deserialize_minutes")]
+    pub soft_start_period: Duration,
 }
 
-impl Default for BlockingConfig {
+impl Default for BypassConfig {
     fn default() -> Self {
         Self {
-            enabled: true,
-            domains: vec![
-                "facebook.com".to_string(),
-                "twitter.com".to_string(),
-                "instagram.com".to_string(),
-                "reddit.com".to_string(),
-                "tiktok.com".to_string(),
-            ],
+            require_reason: false,
+            min_reason_length: 15,
+            daily_budget_minutes: None,
+            snooze_max: Duration::from_mins(30),
+            soft_start: false,
+            soft_start_countdown: Duration::from_secs(60),
+            soft_start_top_domains: 3,
+            soft_start_period: Duration::from_mins(5),
         }
     }
 }
@@ -322,11 +2305,90 @@ pub struct QuizConfig {
     /// Maximum operand value
     pub max_operand: i32,
 
-    /// Quiz timeout in seconds
-    pub timeout_seconds: u32,
+    /// How long a quiz can be answered, e.g. "1m" (plain integers are seconds)
+    #[serde(alias = "timeout_seconds")]
+    pub timeout: Duration,
 
     /// Minimum time to solve (anti-automation)
-    pub min_solve_seconds: u32,
+    #[serde(alias = "min_solve_seconds")]
+    pub min_solve: Duration,
+
+    /// Kind of challenge to generate
+    #[serde(default)]
+    pub kind: QuizKind,
+
+    /// Number of items in a memory sequence
+    #[serde(default = "default_memory_length")]
+    pub memory_length: u32,
+
+    /// How long the memory sequence is shown
+    #[serde(default = "default_memory_reveal", alias = "memory_reveal_seconds")]
+    pub memory_reveal: Duration,
+
+    /// Use words (true) or digits (false) for memory sequences
+    #[serde(default = "default_memory_words")]
+    pub memory_words: bool,
+
+    /// User-written questions mixed into arithmetic challenges
+    #[serde(default)]
+    pub custom_questions: Vec<CustomQuestion>,
+
+    /// Number of custom questions added to each arithmetic challenge
+    #[serde(default = "default_custom_questions_per_quiz")]
+    pub custom_questions_per_quiz: u32,
+
+    /// Friction pipeline for bypass requests (empty means a single quiz)
+    #[serde(default)]
+    pub stages: Vec<QuizStage>,
+
+    /// Longest bypass that can be requested, e.g. "1h"; longer requests are
+    /// cut to it (unitless values are minutes)
+    #[serde(
+        default = "default_max_bypass",
+        alias = "max_bypass_minutes",
+        deserialize_with = "Duration::deserialize_minutes"
+    )]
+    pub max_bypass: Duration,
+}
+
+/// A user-written quiz question.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomQuestion {
+    /// Question text (e.g., "What's your quarterly goal?")
+    pub question: String,
+
+    /// Expected answer (case-insensitive). If None, any answer of at least
+    /// `min_length` characters is accepted (an intention check).
+    #[serde(default)]
+    pub answer: Option<String>,
+
+    /// Minimum answer length for free answers
+    #[serde(default = "default_custom_min_length")]
+    pub min_length: usize,
+}
+
+fn default_custom_questions_per_quiz() -> u32 {
+    1
+}
+
+fn default_custom_min_length() -> usize {
+    10
+}
+
+fn default_max_bypass() -> Duration {
+    Duration::from_mins(60)
+}
+
+fn default_memory_length() -> u32 {
+    6
+}
+
+fn default_memory_reveal() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_memory_words() -> bool {
+    true
 }
 
 impl Default for QuizConfig {
@@ -335,32 +2397,126 @@ impl Default for QuizConfig {
             num_questions: 3,
             min_operand: 10,
             max_operand: 99,
-            timeout_seconds: 60,
-            min_solve_seconds: 3,
+            timeout: Duration::from_secs(60),
+            min_solve: Duration::from_secs(3),
+            kind: QuizKind::Arithmetic,
+            memory_length: default_memory_length(),
+            memory_reveal: default_memory_reveal(),
+            memory_words: default_memory_words(),
+            custom_questions: Vec::new(),
+            custom_questions_per_quiz: default_custom_questions_per_quiz(),
+            stages: Vec::new(),
+            max_bypass: default_max_bypass(),
         }
     }
 }
 
+/// Reduce a query name to the site it most likely belongs to.
+///
+/// Keeps the last two labels ("www.youtube.com" -> "youtube.com"), or three
+/// for common two-part country suffixes ("news.bbc.co.uk" -> "bbc.co.uk").
+/// This is a heuristic, not a public suffix list lookup.
+pub fn site_key(domain: &str) -> String {
+    let domain = domain.to_lowercase();
+    let labels: Vec<&str> = domain
+        .trim()
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    let two_part_suffix = labels.len() >= 3
+        && labels[labels.len() - 1].len() == 2
+        && matches!(labels[labels.len() - 2], "co" | "com" | "org" | "net" | "ac" | "gov" | "edu");
+    let keep = if two_part_suffix { 3 } else { 2 };
+
+    labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+/// IPC protocol version; bumped on incompatible `Command`/`Response` changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Capabilities of this build of the protocol, as reported by the daemon.
+pub const CAPABILITIES: &[Capability] = &[
+    Capability::EventStreaming,
+    Capability::QueryLogTail,
+    Capability::Groups,
+    Capability::FocusSessions,
+    Capability::Vacations,
+    Capability::Subscriptions,
+    Capability::ErrorContext,
+];
+
 /// Socket path for IPC.
 pub const IPC_SOCKET_PATH: &str = "/var/run/blockandfocus.sock";
 
 /// Development socket path (for non-root testing).
 pub const IPC_SOCKET_PATH_DEV: &str = "/tmp/blockandfocus-dev.sock";
 
+/// Key for signing IPC commands in authenticated sessions.
+pub const IPC_KEY_PATH: &str = "/Library/Application Support/BlockAndFocus/ipc.key";
+
+/// Development IPC key path.
+pub const IPC_KEY_PATH_DEV: &str = "/tmp/blockandfocus-dev.key";
+
+/// File the development daemon writes its DNS port to.
+pub const DNS_PORT_FILE_DEV: &str = "/tmp/blockandfocus-dev.port";
+
 /// Config file path.
 pub const CONFIG_PATH: &str = "/Library/Application Support/BlockAndFocus/config.toml";
 
 /// Development config path.
 pub const CONFIG_PATH_DEV: &str = "./config.toml";
 
+/// Daemon state file path (bypass history and other runtime data).
+pub const STATE_PATH: &str = "/Library/Application Support/BlockAndFocus/state.json";
+
+/// Development state file path.
+pub const STATE_PATH_DEV: &str = "./state.json";
+
+/// Development storage key path (the production key lives in the keychain).
+pub const STORAGE_KEY_PATH_DEV: &str = "/tmp/blockandfocus-dev.storage-key";
+
+/// Saved DNS cache, reloaded at startup.
+pub const DNS_CACHE_PATH: &str = "/Library/Application Support/BlockAndFocus/dns-cache.bin";
+
+/// Development saved DNS cache.
+pub const DNS_CACHE_PATH_DEV: &str = "./dns-cache.bin";
+
+/// Directory holding downloaded blocklist subscriptions.
+pub const SUBSCRIPTIONS_DIR: &str = "/Library/Application Support/BlockAndFocus/subscriptions";
+
+/// Development subscription directory.
+pub const SUBSCRIPTIONS_DIR_DEV: &str = "./subscriptions";
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::Timelike;
+
+    #[test]
+    fn test_site_key() {
+        assert_eq!(site_key("www.YouTube.com."), "youtube.com");
+        assert_eq!(site_key("youtube.com"), "youtube.com");
+        assert_eq!(site_key("news.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(site_key("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_essential_domains_default() {
+        let blocking: BlockingConfig =
+            serde_json::from_str(r#"{"enabled":true,"domains":[]}"#).unwrap();
+        assert!(blocking.essential_domains.iter().any(|d| d == "captive.apple.com"));
+
+        let blocking: BlockingConfig =
+            serde_json::from_str(r#"{"enabled":true,"domains":[],"essential_domains":[]}"#).unwrap();
+        assert!(blocking.essential_domains.is_empty());
+    }
 
     #[test]
     fn test_command_serialization() {
         let cmd = Command::AddDomain {
-            domain: "facebook.com".to_string(),
+            domain: DomainName::parse("facebook.com").unwrap(),
         };
         let json = serde_json::to_string(&cmd).unwrap();
         assert!(json.contains("AddDomain"));
@@ -371,6 +2527,10 @@ mod tests {
             Command::AddDomain { domain } => assert_eq!(domain, "facebook.com"),
             _ => panic!("Wrong command type"),
         }
+
+        // Invalid domains are rejected with the command
+        let invalid = r#"{"type":"AddDomain","payload":{"domain":"not a domain"}}"#;
+        assert!(serde_json::from_str::<Command>(invalid).is_err());
     }
 
     #[test]
@@ -383,12 +2543,81 @@ mod tests {
             bypass_until: None,
             active_schedule_rule: Some("Work Hours".to_string()),
             schedule_enabled: true,
+            ..Default::default()
         });
         let json = serde_json::to_string(&resp).unwrap();
         assert!(json.contains("Status"));
         assert!(json.contains("blocking_active"));
     }
 
+    #[test]
+    fn test_legacy_duration_keys() {
+        let quiz: QuizConfig = toml::from_str(
+            "num_questions = 3\nmin_operand = 1\nmax_operand = 9\n\
+             timeout_seconds = 90\nmin_solve_seconds = 3",
+        )
+        .unwrap();
+        assert_eq!(quiz.timeout, Duration::from_secs(90));
+        assert_eq!(quiz.min_solve, Duration::from_secs(3));
+
+        let bypass: BypassConfig = toml::from_str("snooze_max_minutes = 20").unwrap();
+        assert_eq!(bypass.snooze_max, Duration::from_mins(20));
+        let bypass: BypassConfig = toml::from_str("snooze_max = \"1h\"").unwrap();
+        assert_eq!(bypass.snooze_max, Duration::from_mins(60));
+        let bypass: BypassConfig =
+            toml::from_str("soft_start_countdown_seconds = 30\nsoft_start_minutes = 10").unwrap();
+        assert_eq!(bypass.soft_start_countdown, Duration::from_secs(30));
+        assert_eq!(bypass.soft_start_period, Duration::from_mins(10));
+
+        let legacy = r#"{"type":"RequestBypass","payload":{"duration_minutes":15}}"#;
+        match serde_json::from_str::<Command>(legacy).unwrap() {
+            Command::RequestBypass { duration, .. } => {
+                assert_eq!(duration, Duration::from_mins(15));
+            }
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_capabilities_degrade_gracefully() {
+        // Older daemons send no capabilities; newer ones may send unknown ones
+        let old = r#"{"version":"0.1.0","git_hash":null,"build_date":null,"features":[],
+            "protocol_version":1}"#;
+        let info: VersionInfo = serde_json::from_str(old).unwrap();
+        assert!(!info.supports(Capability::EventStreaming));
+
+        let new = r#"{"type":"SessionStarted","payload":{"session_id":"s","nonce":"n",
+            "capabilities":["event_streaming","teleportation"]}}"#;
+        match serde_json::from_str::<Response>(new).unwrap() {
+            Response::SessionStarted { capabilities, .. } => {
+                assert_eq!(capabilities, vec![Capability::EventStreaming, Capability::Unknown]);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_quiz_answer_serialization() {
+        let parsed: Vec<QuizAnswer> = serde_json::from_str(r#"[42, "apple river"]"#).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                QuizAnswer::Number(42),
+                QuizAnswer::Text("apple river".to_string())
+            ]
+        );
+
+        // Old clients send plain numeric answers
+        let cmd: Command = serde_json::from_str(
+            r#"{"type":"SubmitQuizAnswers","payload":{"challenge_id":"abc","answers":[1,2,3]}}"#,
+        )
+        .unwrap();
+        match cmd {
+            Command::SubmitQuizAnswers { answers, .. } => assert_eq!(answers.len(), 3),
+            _ => panic!("Wrong command type"),
+        }
+    }
+
     #[test]
     fn test_time_wrapper_serialization() {
         let time = NaiveTimeWrapper(NaiveTime::from_hms_opt(9, 30, 0).unwrap());
@@ -0,0 +1,362 @@
+//! Building and validating schedules.
+//!
+//! A schedule can parse and still never block what its author meant to: a
+//! rule without days, one that starts and ends at the same time, a wind-down
+//! stage past the rule's end. `Schedule::validate` finds those, and
+//! `ScheduleRule::builder` refuses to build such a rule in the first place.
+//! `ScheduleTemplate` builds whole schedules for common routines.
+
+use crate::{NaiveTimeWrapper, Schedule, ScheduleRule, Strictness, WeekdayWrapper, WindDownStage};
+use chrono::{NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Something wrong with a schedule or one of its rules.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScheduleProblem {
+    #[error("A schedule rule has no name")]
+    MissingName,
+    #[error("Schedule rule \"{0}\" is defined twice")]
+    DuplicateName(String),
+    #[error("Schedule rule \"{0}\" has no days, so it never applies")]
+    NoDays(String),
+    #[error("Schedule rule \"{0}\" starts and ends at the same time, so it never blocks")]
+    EmptyWindow(String),
+    #[error("Wind-down stage at {at} is outside schedule rule \"{rule}\"")]
+    StageOutsideRule { rule: String, at: String },
+    #[error("The break must start after work starts and end before work ends")]
+    BreakOutsideWorkday,
+}
+
+impl Schedule {
+    /// Check every rule, and that no two rules share a name.
+    pub fn validate(&self) -> Result<(), Vec<ScheduleProblem>> {
+        let mut problems = Vec::new();
+        let mut names = HashSet::new();
+        for rule in &self.rules {
+            if !names.insert(rule.name.as_str()) {
+                problems.push(ScheduleProblem::DuplicateName(rule.name.clone()));
+            }
+            problems.extend(rule.problems());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+impl ScheduleRule {
+    /// Start building a rule, checked when it is built.
+    pub fn builder() -> ScheduleRuleBuilder {
+        ScheduleRuleBuilder::default()
+    }
+
+    /// Problems with this rule on its own.
+    pub fn problems(&self) -> Vec<ScheduleProblem> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push(ScheduleProblem::MissingName);
+        }
+        // Temporary rules apply until they expire, whatever their days and times
+        if self.expires_at.is_some() {
+            return problems;
+        }
+
+        if self.days.is_empty() {
+            problems.push(ScheduleProblem::NoDays(self.name.clone()));
+        }
+        let length = self.since_start(self.end_time.0);
+        if length == 0 {
+            problems.push(ScheduleProblem::EmptyWindow(self.name.clone()));
+        }
+        for stage in &self.stages {
+            if self.since_start(stage.at.0) >= length {
+                problems.push(ScheduleProblem::StageOutsideRule {
+                    rule: self.name.clone(),
+                    at: stage.at.0.format("%H:%M").to_string(),
+                });
+            }
+        }
+        problems
+    }
+
+    /// Seconds from the rule's start to `time`, counting past midnight for
+    /// overnight rules.
+    fn since_start(&self, time: NaiveTime) -> u32 {
+        let day = 24 * 3600;
+        let start = self.start_time.0.num_seconds_from_midnight();
+        (time.num_seconds_from_midnight() + day - start) % day
+    }
+}
+
+/// Builder for a `ScheduleRule`.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleRuleBuilder {
+    name: String,
+    days: Vec<WeekdayWrapper>,
+    start_time: NaiveTime,
+    end_time: NaiveTime,
+    expires_at: Option<i64>,
+    strictness: Strictness,
+    stages: Vec<WindDownStage>,
+}
+
+impl ScheduleRuleBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn days(mut self, days: impl IntoIterator<Item = WeekdayWrapper>) -> Self {
+        self.days.extend(days);
+        self
+    }
+
+    /// Monday to Friday.
+    pub fn weekdays(self) -> Self {
+        use WeekdayWrapper::*;
+        self.days([Mon, Tue, Wed, Thu, Fri])
+    }
+
+    /// Block from `start` until `end`; an `end` before `start` runs past midnight.
+    pub fn between(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.start_time = start;
+        self.end_time = end;
+        self
+    }
+
+    /// Make the rule temporary, applying around the clock until `expires_at`.
+    pub fn expires_at(mut self, expires_at: i64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Add a wind-down stage; an empty `groups` blocks everything from `at` on.
+    pub fn stage(mut self, at: NaiveTime, groups: impl IntoIterator<Item = String>) -> Self {
+        self.stages.push(WindDownStage {
+            at: NaiveTimeWrapper(at),
+            groups: groups.into_iter().collect(),
+        });
+        self
+    }
+
+    /// The rule, or what is wrong with it.
+    pub fn build(self) -> Result<ScheduleRule, Vec<ScheduleProblem>> {
+        let rule = ScheduleRule {
+            name: self.name,
+            days: self.days,
+            start_time: NaiveTimeWrapper(self.start_time),
+            end_time: NaiveTimeWrapper(self.end_time),
+            expires_at: self.expires_at,
+            strictness: self.strictness,
+            stages: self.stages,
+        };
+        let problems = rule.problems();
+        if problems.is_empty() {
+            Ok(rule)
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+/// A common routine to generate a schedule from. Every parameter has a
+/// default, so `{"template": "workday"}` is enough.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "template", rename_all = "snake_case")]
+pub enum ScheduleTemplate {
+    /// Working hours with a lunch break left unblocked
+    Workday {
+        #[serde(default = "weekdays")]
+        days: Vec<WeekdayWrapper>,
+        #[serde(default = "at::<9, 0>")]
+        start: NaiveTimeWrapper,
+        #[serde(default = "at::<17, 0>")]
+        end: NaiveTimeWrapper,
+        #[serde(default = "at::<12, 0>")]
+        break_start: NaiveTimeWrapper,
+        #[serde(default = "at::<13, 0>")]
+        break_end: NaiveTimeWrapper,
+        #[serde(default)]
+        strictness: Strictness,
+    },
+    /// Evenings kept for homework, before each school day
+    StudentEvenings {
+        #[serde(default = "school_nights")]
+        days: Vec<WeekdayWrapper>,
+        #[serde(default = "at::<18, 0>")]
+        start: NaiveTimeWrapper,
+        #[serde(default = "at::<21, 0>")]
+        end: NaiveTimeWrapper,
+        #[serde(default)]
+        strictness: Strictness,
+    },
+    /// Blocking from a late bedtime until the morning, every night
+    NightOwl {
+        #[serde(default = "every_day")]
+        days: Vec<WeekdayWrapper>,
+        #[serde(default = "at::<1, 0>")]
+        bedtime: NaiveTimeWrapper,
+        #[serde(default = "at::<8, 0>")]
+        wake: NaiveTimeWrapper,
+        #[serde(default)]
+        strictness: Strictness,
+    },
+}
+
+impl ScheduleTemplate {
+    /// The schedule, enabled, or what is wrong with the parameters.
+    pub fn generate(&self) -> Result<Schedule, Vec<ScheduleProblem>> {
+        let rule = |name: &str, days: &[WeekdayWrapper], from: NaiveTimeWrapper, to| {
+            ScheduleRule::builder()
+                .name(name)
+                .days(days.iter().copied())
+                .between(from.0, to)
+                .strictness(self.strictness())
+        };
+
+        let rules = match self {
+            Self::Workday { days, start, end, break_start, break_end, .. } => {
+                // Overnight workdays are fine, as long as the break is inside them
+                let morning = rule("Workday morning", days, *start, break_start.0).build()?;
+                let since_start = |time: NaiveTimeWrapper| morning.since_start(time.0);
+                if since_start(*break_end) <= since_start(*break_start)
+                    || since_start(*end) <= since_start(*break_end)
+                {
+                    return Err(vec![ScheduleProblem::BreakOutsideWorkday]);
+                }
+                vec![morning, rule("Workday afternoon", days, *break_end, end.0).build()?]
+            }
+            Self::StudentEvenings { days, start, end, .. } => {
+                vec![rule("Study evenings", days, *start, end.0).build()?]
+            }
+            Self::NightOwl { days, bedtime, wake, .. } => {
+                vec![rule("Bedtime", days, *bedtime, wake.0).build()?]
+            }
+        };
+
+        let schedule = Schedule { enabled: true, rules };
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    fn strictness(&self) -> Strictness {
+        match self {
+            Self::Workday { strictness, .. }
+            | Self::StudentEvenings { strictness, .. }
+            | Self::NightOwl { strictness, .. } => *strictness,
+        }
+    }
+}
+
+fn at<const HOUR: u32, const MINUTE: u32>() -> NaiveTimeWrapper {
+    NaiveTimeWrapper(NaiveTime::from_hms_opt(HOUR, MINUTE, 0).unwrap_or_default())
+}
+
+fn weekdays() -> Vec<WeekdayWrapper> {
+    use WeekdayWrapper::*;
+    vec![Mon, Tue, Wed, Thu, Fri]
+}
+
+fn school_nights() -> Vec<WeekdayWrapper> {
+    use WeekdayWrapper::*;
+    vec![Sun, Mon, Tue, Wed, Thu]
+}
+
+fn every_day() -> Vec<WeekdayWrapper> {
+    use WeekdayWrapper::*;
+    vec![Mon, Tue, Wed, Thu, Fri, Sat, Sun]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_builder() {
+        let rule = ScheduleRule::builder()
+            .name("Evening")
+            .weekdays()
+            .between(time(20), time(2))
+            .stage(time(1), ["social".to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(rule.days.len(), 5);
+        assert_eq!(rule.end_time.0, time(2));
+
+        let problems = ScheduleRule::builder()
+            .between(time(9), time(9))
+            .stage(time(10), [])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                ScheduleProblem::MissingName,
+                ScheduleProblem::NoDays(String::new()),
+                ScheduleProblem::EmptyWindow(String::new()),
+                ScheduleProblem::StageOutsideRule { rule: String::new(), at: "10:00".into() },
+            ]
+        );
+
+        // Temporary rules apply around the clock, so days and times don't matter
+        assert!(ScheduleRule::builder().name("Focus").expires_at(0).build().is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let rule = |name: &str| {
+            ScheduleRule::builder().name(name).weekdays().between(time(9), time(17)).build().unwrap()
+        };
+        let mut schedule = Schedule {
+            enabled: true,
+            rules: vec![rule("Work"), rule("Study")],
+        };
+        assert_eq!(schedule.validate(), Ok(()));
+
+        schedule.rules.push(rule("Work"));
+        assert_eq!(
+            schedule.validate(),
+            Err(vec![ScheduleProblem::DuplicateName("Work".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        let template: ScheduleTemplate = serde_json::from_str(r#"{"template":"workday"}"#).unwrap();
+        let schedule = template.generate().unwrap();
+        assert!(schedule.enabled);
+        assert_eq!(schedule.rules.len(), 2);
+        assert_eq!(schedule.rules[0].end_time.0, time(12));
+        assert_eq!(schedule.rules[1].start_time.0, time(13));
+        assert_eq!(schedule.rules[1].days.len(), 5);
+
+        let json = r#"{"template":"night_owl","bedtime":"23:30","strictness":"strict"}"#;
+        let template: ScheduleTemplate = serde_json::from_str(json).unwrap();
+        let bedtime = &template.generate().unwrap().rules[0];
+        assert_eq!(bedtime.days.len(), 7);
+        assert_eq!(bedtime.end_time.0, time(8));
+        assert_eq!(bedtime.strictness, Strictness::Strict);
+
+        let json = r#"{"template":"workday","break_start":"18:00","break_end":"19:00"}"#;
+        let template: ScheduleTemplate = serde_json::from_str(json).unwrap();
+        assert_eq!(template.generate(), Err(vec![ScheduleProblem::BreakOutsideWorkday]));
+
+        let json = r#"{"template":"student_evenings","days":[]}"#;
+        let template: ScheduleTemplate = serde_json::from_str(json).unwrap();
+        let problems = template.generate().unwrap_err();
+        assert_eq!(problems, vec![ScheduleProblem::NoDays("Study evenings".to_string())]);
+    }
+}
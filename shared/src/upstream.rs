@@ -0,0 +1,274 @@
+//! Upstream DNS server configuration.
+//!
+//! Entries are written as tables, but the plain strings of older configs
+//! ("1.1.1.1", "9.9.9.9:5353", "[2606:4700::1111]:53") are still accepted as
+//! plain DNS servers.
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+/// Transport used to reach an upstream server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamProtocol {
+    /// Plain DNS over UDP (retried over TCP when truncated)
+    #[default]
+    Udp,
+    /// Plain DNS over TCP
+    Tcp,
+    /// DNS over TLS
+    Tls,
+    /// DNS over HTTPS
+    Https,
+}
+
+impl UpstreamProtocol {
+    /// Port used when an entry does not give one.
+    pub fn default_port(self) -> u16 {
+        match self {
+            UpstreamProtocol::Udp | UpstreamProtocol::Tcp => 53,
+            UpstreamProtocol::Tls => 853,
+            UpstreamProtocol::Https => 443,
+        }
+    }
+
+    /// Whether queries are encrypted on the wire.
+    pub fn is_encrypted(self) -> bool {
+        matches!(self, UpstreamProtocol::Tls | UpstreamProtocol::Https)
+    }
+}
+
+/// An upstream DNS server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UpstreamEntry")]
+pub struct UpstreamServer {
+    /// IP address, or a hostname for TLS/HTTPS servers (see `bootstrap`)
+    pub address: String,
+
+    /// Port (the protocol's default if None)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// Transport used to reach the server
+    #[serde(default)]
+    pub protocol: UpstreamProtocol,
+
+    /// IP address to connect to when `address` is a hostname (we can't resolve
+    /// it through ourselves)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<IpAddr>,
+
+    /// Name sent as SNI and checked against the server's certificate, for
+    /// TLS/HTTPS servers given by IP address whose certificate is issued to a
+    /// hostname
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_name: Option<String>,
+
+    /// Relative share of queries sent to this server
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+impl UpstreamServer {
+    /// A plain DNS server at `ip` on port 53.
+    pub fn plain(ip: IpAddr) -> Self {
+        Self {
+            address: ip.to_string(),
+            port: None,
+            protocol: UpstreamProtocol::Udp,
+            bootstrap: None,
+            tls_name: None,
+            weight: default_weight(),
+        }
+    }
+
+    /// Port to connect to.
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(self.protocol.default_port())
+    }
+
+    /// IP address to connect to: the address itself, or the bootstrap address
+    /// for a hostname.
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.address.parse().ok().or(self.bootstrap)
+    }
+
+    /// Socket address to connect to.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        Some(SocketAddr::new(self.ip()?, self.port()))
+    }
+
+    /// Name to verify the server's certificate against (TLS/HTTPS only): the
+    /// configured `tls_name`, or else the address itself, hostname or IP.
+    pub fn tls_name(&self) -> Option<&str> {
+        self.protocol
+            .is_encrypted()
+            .then(|| self.tls_name.as_deref().unwrap_or(&self.address))
+    }
+
+    /// Parse the plain string form: an IP address with an optional port.
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim();
+        let (ip, port) = if let Ok(addr) = entry.parse::<SocketAddr>() {
+            (addr.ip(), Some(addr.port()))
+        } else if let Ok(ip) = unbracket(entry).parse() {
+            (ip, None)
+        } else {
+            return Err(format!("Invalid upstream server \"{}\"", entry));
+        };
+
+        Ok(Self {
+            port,
+            ..Self::plain(ip)
+        })
+    }
+}
+
+impl std::fmt::Display for UpstreamServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = match self.protocol {
+            UpstreamProtocol::Udp => "udp",
+            UpstreamProtocol::Tcp => "tcp",
+            UpstreamProtocol::Tls => "tls",
+            UpstreamProtocol::Https => "https",
+        };
+        match self.address.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => write!(f, "{}://[{}]:{}", scheme, ip, self.port()),
+            _ => write!(f, "{}://{}:{}", scheme, self.address, self.port()),
+        }
+    }
+}
+
+/// An IPv6 address without its brackets. Unbalanced brackets are kept, so
+/// the address doesn't parse.
+fn unbracket(address: &str) -> &str {
+    address
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .unwrap_or(address)
+}
+
+/// How an upstream entry may be written in the config.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UpstreamEntry {
+    /// Plain string form of older configs
+    Plain(String),
+    /// Table form
+    Full {
+        address: String,
+        #[serde(default)]
+        port: Option<u16>,
+        #[serde(default)]
+        protocol: UpstreamProtocol,
+        #[serde(default)]
+        bootstrap: Option<IpAddr>,
+        #[serde(default)]
+        tls_name: Option<String>,
+        #[serde(default = "default_weight")]
+        weight: u32,
+    },
+}
+
+impl TryFrom<UpstreamEntry> for UpstreamServer {
+    type Error = String;
+
+    fn try_from(entry: UpstreamEntry) -> Result<Self, String> {
+        match entry {
+            UpstreamEntry::Plain(entry) => Self::parse(&entry),
+            UpstreamEntry::Full {
+                address,
+                port,
+                protocol,
+                bootstrap,
+                tls_name,
+                weight,
+            } => {
+                let address = unbracket(address.trim());
+                let server = Self {
+                    address: address.to_string(),
+                    port,
+                    protocol,
+                    bootstrap,
+                    tls_name,
+                    weight,
+                };
+                if server.ip().is_none() {
+                    return Err(format!(
+                        "Upstream \"{}\" is a hostname and needs a bootstrap address",
+                        address
+                    ));
+                }
+                if server.tls_name.is_some() && !protocol.is_encrypted() {
+                    return Err(format!(
+                        "Upstream \"{}\" has a tls_name but doesn't use tls or https",
+                        address
+                    ));
+                }
+                Ok(server)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Dns {
+        upstream: Vec<UpstreamServer>,
+    }
+
+    #[test]
+    fn test_plain_and_table_entries() {
+        let dns: Dns = serde_json::from_str(
+            r#"{"upstream": [
+                "1.1.1.1",
+                "9.9.9.9:5353",
+                "[2606:4700::1111]:53",
+                "2001:4860:4860::8888",
+                {"address": "dns.quad9.net", "protocol": "tls", "bootstrap": "9.9.9.9", "weight": 3},
+                {"address": "1.1.1.1", "protocol": "tls", "tls_name": "cloudflare-dns.com"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let addrs: Vec<String> = dns.upstream.iter().map(|u| u.to_string()).collect();
+        assert_eq!(
+            addrs,
+            vec![
+                "udp://1.1.1.1:53",
+                "udp://9.9.9.9:5353",
+                "udp://[2606:4700::1111]:53",
+                "udp://[2001:4860:4860::8888]:53",
+                "tls://dns.quad9.net:853",
+                "tls://1.1.1.1:853",
+            ]
+        );
+        assert_eq!(dns.upstream[4].socket_addr(), Some("9.9.9.9:853".parse().unwrap()));
+        assert_eq!(dns.upstream[4].tls_name(), Some("dns.quad9.net"));
+        assert_eq!(dns.upstream[5].tls_name(), Some("cloudflare-dns.com"));
+        assert_eq!(dns.upstream[0].tls_name(), None);
+        assert_eq!(dns.upstream[4].weight, 3);
+        assert_eq!(dns.upstream[0].weight, 1);
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        assert!(serde_json::from_str::<Dns>(r#"{"upstream": ["not an ip"]}"#).is_err());
+        assert!(
+            serde_json::from_str::<Dns>(r#"{"upstream": [{"address": "dns.google"}]}"#).is_err()
+        );
+        let plain_with_name = r#"{"upstream": [{"address": "1.1.1.1", "tls_name": "one.one"}]}"#;
+        assert!(serde_json::from_str::<Dns>(plain_with_name).is_err());
+
+        for unbalanced in ["[2606:4700::1111", "2606:4700::1111]"] {
+            assert!(UpstreamServer::parse(unbalanced).is_err(), "{}", unbalanced);
+        }
+    }
+}
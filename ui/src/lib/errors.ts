@@ -0,0 +1,25 @@
+// Errors returned by Tauri commands (blockandfocus_shared::Error)
+
+export type ErrorContext =
+  | { type: "retry_after"; seconds: number }
+  | { type: "domain"; domain: string }
+  | { type: "locked_for"; minutes: number }
+  | { type: "budget_remaining"; minutes: number };
+
+export type CommandError =
+  | { kind: "daemon"; code: string; message: string; context?: ErrorContext }
+  | { kind: "unavailable" | "unexpected_response" | "invalid" | "app"; message: string };
+
+export function isCommandError(e: unknown): e is CommandError {
+  return typeof e === "object" && e !== null && "kind" in e && "message" in e;
+}
+
+// Message to show for anything a command threw
+export function errorMessage(e: unknown): string {
+  return isCommandError(e) ? e.message : String(e);
+}
+
+// The daemon's error code, e.g. "rate_limited", if the daemon refused the command
+export function errorCode(e: unknown): string | null {
+  return isCommandError(e) && e.kind === "daemon" ? e.code : null;
+}